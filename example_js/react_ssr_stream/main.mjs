@@ -1,12 +1,20 @@
 import * as React from 'react';
 import { renderToPipeableStream } from 'react-dom/server';
 import { createServer } from 'http';
+import { createGzip } from 'zlib';
 
 import LazyHome from './component/LazyHome.jsx';
 
 createServer((req, res) => {
   res.setHeader('Content-type', 'text/html; charset=utf-8');
-  renderToPipeableStream(<LazyHome />).pipe(res);
+  if (/\bgzip\b/.test(req.headers['accept-encoding'] ?? '')) {
+    res.setHeader('Content-Encoding', 'gzip');
+    const gzip = createGzip();
+    gzip.pipe(res);
+    renderToPipeableStream(<LazyHome />).pipe(gzip);
+  } else {
+    renderToPipeableStream(<LazyHome />).pipe(res);
+  }
 }).listen(8001, () => {
   print('listen 8001...');
 })
@@ -0,0 +1,24 @@
+import process from 'process';
+import { createInterface } from 'readline';
+
+// wasmedge --dir .:. wasmedge_quickjs.wasm example_js/readline.js
+const rl = createInterface({ input: process.stdin, output: process.stdout, prompt: 'js> ' });
+
+rl.question('What is your name? ', (name) => {
+    print(`Hello ${name}, every line is evaluated until an empty one or EOF`);
+    rl.prompt();
+    rl.on('line', (line) => {
+        if (line === '') {
+            rl.close();
+            return;
+        }
+        try {
+            print(eval(line));
+        } catch (e) {
+            print(e);
+        }
+        rl.prompt();
+    });
+});
+
+rl.on('close', () => print('bye'));
@@ -0,0 +1,11 @@
+import { fetch } from 'http'
+
+async function test_fetch() {
+    try {
+        let r = await fetch('https://httpbin.org/get?id=1')
+        print('test_fetch\n', await r.text())
+    } catch (e) {
+        print(e)
+    }
+}
+test_fetch()
@@ -12,8 +12,11 @@ async function handle_client(cs) {
       }
       let s = new TextDecoder().decode(d);
       print('server recv:', s);
-      cs.write('echo:' + s);
+      if (!cs.write('echo:' + s)) {
+        await new Promise((resolve) => cs.on('drain', resolve));
+      }
     }
+    await cs.shutdown();
   } catch (e) {
     print('server handle_client error:', e);
   }
@@ -38,7 +41,7 @@ server_start();
 async function connect_test() {
   try {
     let ss = await net.WasiTcpConn.connect('127.0.0.1:8000')
-    ss.write('hello');
+    ss.write('hello', (e) => print('client flushed:', e || 'ok'));
     let msg = await ss.read() || "";
     print('client recv:', new TextDecoder().decode(msg));
   } catch (e) {
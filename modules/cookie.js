@@ -0,0 +1,82 @@
+// Cookie helpers in the shape of the npm `cookie` package, plus the jar that
+// `fetch` keeps cookies in.
+//
+//     import { parse, serialize, CookieJar } from 'cookie'
+//     parse('a=1; b=2')                         // { a: '1', b: '2' }
+//     serialize('sid', 'x', { httpOnly: true }) // 'sid=x; HttpOnly'
+//     fetch(url, { cookieJar: new CookieJar() })
+
+import * as httpx from 'wasi_http'
+import { globalCookieJar } from 'internal/fetch'
+
+export const CookieJar = httpx.CookieJar;
+export { globalCookieJar };
+
+function decode(value) {
+    try {
+        return value.includes('%') ? decodeURIComponent(value) : value;
+    } catch {
+        return value;
+    }
+}
+
+/**
+ * The cookies of a `Cookie` header as an object. The first of several
+ * cookies with the same name wins, values are percent-decoded unless
+ * `options.decode` says otherwise.
+ */
+export function parse(header, options = {}) {
+    const dec = options.decode ?? decode;
+    const cookies = {};
+    for (const [name, value] of httpx.parseCookie(`${header}`)) {
+        if (!Object.prototype.hasOwnProperty.call(cookies, name)) {
+            cookies[name] = dec(value);
+        }
+    }
+    return cookies;
+}
+
+/**
+ * A `Set-Cookie` header value. `value` is percent-encoded unless
+ * `options.encode` says otherwise; the other options are `expires` (a
+ * Date), `maxAge` in seconds, `domain`, `path`, `secure`, `httpOnly`,
+ * `sameSite` (`'strict'`, `'lax'`, `'none'` or `true` for strict) and
+ * `partitioned`. Throws a TypeError for names and values a header cannot
+ * carry.
+ */
+export function serialize(name, value, options = {}) {
+    const enc = options.encode ?? encodeURIComponent;
+    const native = { ...options };
+    if (options.expires !== undefined) {
+        const time = options.expires instanceof Date ? options.expires.getTime() : NaN;
+        if (Number.isNaN(time)) {
+            throw new TypeError('option expires is invalid');
+        }
+        native.expires = time;
+    }
+    if (options.maxAge !== undefined && !Number.isFinite(options.maxAge)) {
+        throw new TypeError('option maxAge is invalid');
+    }
+    return httpx.serializeCookie(`${name}`, enc(`${value}`), native);
+}
+
+/**
+ * The cookie of a `Set-Cookie` header value as `{ name, value, expires,
+ * maxAge, domain, path, secure, httpOnly, sameSite, partitioned }`, or null
+ * when it has no `name=value` part. Unknown attributes are dropped.
+ */
+export function parseSetCookie(header) {
+    const cookie = httpx.parseSetCookie(`${header}`);
+    if (cookie !== null && cookie.expires !== undefined) {
+        cookie.expires = new Date(cookie.expires);
+    }
+    return cookie;
+}
+
+export default {
+    CookieJar,
+    globalCookieJar,
+    parse,
+    serialize,
+    parseSetCookie,
+};
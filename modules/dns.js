@@ -0,0 +1,117 @@
+// Node.js compatible `dns`. The callback functions here and the promise
+// ones in `dns/promises` share the default resolver, so `setServers()` on
+// either changes both.
+
+import * as dns from './internal/dns'
+import promises from './dns/promises'
+import { validateFunction } from './internal/validators'
+
+export {
+    NODATA, FORMERR, SERVFAIL, NOTFOUND, NOTIMP, REFUSED, BADQUERY, BADNAME,
+    BADFAMILY, BADRESP, CONNREFUSED, TIMEOUT, EOF, FILE, NOMEM, DESTRUCTION,
+    BADSTR, BADFLAGS, NONAME, BADHINTS, NOTINITIALIZED, CANCELLED,
+} from './internal/dns'
+
+export const ADDRCONFIG = 1024;
+export const V4MAPPED = 2048;
+export const ALL = 256;
+
+export { promises };
+
+function settle(promise, callback, spread) {
+    promise.then(
+        (result) => spread ? callback(null, ...result) : callback(null, result),
+        (err) => callback(err),
+    );
+}
+
+const RESOLVE_METHODS = [
+    'resolve', 'resolve4', 'resolve6', 'resolveAny', 'resolveCname', 'resolveMx',
+    'resolveNs', 'resolvePtr', 'resolveSoa', 'resolveSrv', 'resolveTxt', 'reverse',
+];
+
+/** A resolver with its own servers and cache, see `dns/promises`. */
+export class Resolver {
+    constructor(options) {
+        this._handle = new dns.Resolver(options);
+    }
+
+    cancel() {
+        this._handle.cancel();
+    }
+
+    getServers() {
+        return this._handle.getServers();
+    }
+
+    setServers(servers) {
+        this._handle.setServers(servers);
+    }
+}
+
+for (const name of RESOLVE_METHODS) {
+    Resolver.prototype[name] = function (hostname, ...args) {
+        const callback = args.pop();
+        validateFunction(callback, 'callback');
+        settle(this._handle[name](hostname, ...args), callback);
+    };
+}
+
+const resolver = Object.create(Resolver.prototype);
+resolver._handle = dns.defaultResolver;
+
+/**
+ * `callback(err, address, family)`, or `callback(err, addresses)` with
+ * `options.all`.
+ */
+export function lookup(hostname, options, callback) {
+    if (typeof options === 'function') {
+        callback = options;
+        options = {};
+    }
+    validateFunction(callback, 'callback');
+    const found = dns.lookup(hostname, options ?? {});
+    if (typeof options === 'object' && options?.all) {
+        settle(found, callback);
+    } else {
+        settle(found.then(({ address, family }) => [address, family]), callback, true);
+    }
+}
+
+export const getServers = () => resolver.getServers();
+export const setServers = (servers) => resolver.setServers(servers);
+export const resolve = (...args) => resolver.resolve(...args);
+export const resolve4 = (...args) => resolver.resolve4(...args);
+export const resolve6 = (...args) => resolver.resolve6(...args);
+export const resolveAny = (...args) => resolver.resolveAny(...args);
+export const resolveCname = (...args) => resolver.resolveCname(...args);
+export const resolveMx = (...args) => resolver.resolveMx(...args);
+export const resolveNs = (...args) => resolver.resolveNs(...args);
+export const resolvePtr = (...args) => resolver.resolvePtr(...args);
+export const resolveSoa = (...args) => resolver.resolveSoa(...args);
+export const resolveSrv = (...args) => resolver.resolveSrv(...args);
+export const resolveTxt = (...args) => resolver.resolveTxt(...args);
+export const reverse = (...args) => resolver.reverse(...args);
+
+export default {
+    ADDRCONFIG,
+    V4MAPPED,
+    ALL,
+    Resolver,
+    promises,
+    lookup,
+    getServers,
+    setServers,
+    resolve,
+    resolve4,
+    resolve6,
+    resolveAny,
+    resolveCname,
+    resolveMx,
+    resolveNs,
+    resolvePtr,
+    resolveSoa,
+    resolveSrv,
+    resolveTxt,
+    reverse,
+};
@@ -0,0 +1,40 @@
+import * as dns from '../internal/dns'
+
+export const Resolver = dns.Resolver;
+
+const resolver = dns.defaultResolver;
+
+export const lookup = dns.lookup;
+export const getServers = () => resolver.getServers();
+export const setServers = (servers) => resolver.setServers(servers);
+export const resolve = (hostname, rrtype) => resolver.resolve(hostname, rrtype);
+export const resolve4 = (hostname, options) => resolver.resolve4(hostname, options);
+export const resolve6 = (hostname, options) => resolver.resolve6(hostname, options);
+export const resolveAny = (hostname) => resolver.resolveAny(hostname);
+export const resolveCname = (hostname) => resolver.resolveCname(hostname);
+export const resolveMx = (hostname) => resolver.resolveMx(hostname);
+export const resolveNs = (hostname) => resolver.resolveNs(hostname);
+export const resolvePtr = (hostname) => resolver.resolvePtr(hostname);
+export const resolveSoa = (hostname) => resolver.resolveSoa(hostname);
+export const resolveSrv = (hostname) => resolver.resolveSrv(hostname);
+export const resolveTxt = (hostname) => resolver.resolveTxt(hostname);
+export const reverse = (ip) => resolver.reverse(ip);
+
+export default {
+    Resolver,
+    lookup,
+    getServers,
+    setServers,
+    resolve,
+    resolve4,
+    resolve6,
+    resolveAny,
+    resolveCname,
+    resolveMx,
+    resolveNs,
+    resolvePtr,
+    resolveSoa,
+    resolveSrv,
+    resolveTxt,
+    reverse,
+};
@@ -73,6 +73,8 @@ import {
     writeSync,
     writeFile,
     writeFileSync,
+    writeFileAtomic,
+    writeFileAtomicSync,
     appendFile,
     appendFileSync,
     writev,
@@ -86,8 +88,12 @@ import {
     watch,
     watchFile,
     unwatch,
+    unwatchFile,
     cp,
     cpSync,
+    lock,
+    lockSync,
+    FileLock,
     createWriteStream,
     WriteStream,
     createReadStream,
@@ -174,6 +180,8 @@ export default {
     writeSync,
     writeFile,
     writeFileSync,
+    writeFileAtomic,
+    writeFileAtomicSync,
     appendFile,
     appendFileSync,
     writev,
@@ -187,8 +195,12 @@ export default {
     watch,
     watchFile,
     unwatch,
+    unwatchFile,
     cp,
     cpSync,
+    lock,
+    lockSync,
+    FileLock,
     createWriteStream,
     WriteStream,
     createReadStream,
@@ -280,6 +292,8 @@ export {
     writeSync,
     writeFile,
     writeFileSync,
+    writeFileAtomic,
+    writeFileAtomicSync,
     appendFile,
     appendFileSync,
     writev,
@@ -293,8 +307,12 @@ export {
     watch,
     watchFile,
     unwatch,
+    unwatchFile,
     cp,
     cpSync,
+    lock,
+    lockSync,
+    FileLock,
     createWriteStream,
     WriteStream,
     createReadStream,
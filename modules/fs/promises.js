@@ -14,6 +14,7 @@ export const chown = promisify(fs.chown);
 export const copyFile = promisify(fs.copyFile);
 export const cp = promisify(fs.cp);
 export const lchmod = promisify(fs.lchmod);
+export const lock = promisify(fs.lock);
 export const lchown = promisify(fs.lchown);
 export const lutimes = promisify(fs.lutimes);
 export const link = promisify(fs.link);
@@ -55,6 +56,7 @@ export const truncate = async (path, len) => {
 export const unlink = promisify(fs.unlink);
 export const utimes = promisify(fs.utimes);
 export const watch = promisify(fs.watch);
+export const writeFileAtomic = promisify(fs.writeFileAtomic);
 export const writeFile = async (path, ...args) => {
     let file = await open(path, "w");
     await file.writeFile(...args);
@@ -71,6 +73,7 @@ const promises = {
     copyFile,
     cp,
     lchmod,
+    lock,
     lchown,
     lutimes,
     link,
@@ -93,6 +96,7 @@ const promises = {
     utimes,
     watch,
     writeFile,
+    writeFileAtomic,
     constants
 }
 
@@ -1,225 +1,18 @@
 import * as net from 'wasi_net'
 import * as httpx from 'wasi_http'
-import { TextDecoder } from 'util'
+import { fetch, Headers, Request, Response, trailersOf, parseFormData } from 'internal/fetch'
+import { Agent, globalAgent } from 'internal/http_agent'
 import { Buffer } from 'buffer'
 import { EventEmitter } from 'events'
 import process from 'process'
 import { validatePort } from 'internal/validators'
 import { Readable, Writable } from "stream";
 import { isTypedArray } from 'util/types'
+import { parse as parseCookie, serialize as serializeCookie } from 'cookie'
 
 const URL = httpx.URL;
 
-export class Request {
-    constructor(input, init = {}) {
-
-        let parsedURL
-
-        if (input instanceof Request) {
-            parsedURL = new URL(input.url)
-        } else {
-            parsedURL = new URL(input)
-            input = {}
-        }
-
-        this.url = parsedURL;
-
-        if (parsedURL.username !== '' || parsedURL.password !== '') {
-            throw new TypeError(`${parsedURL} is an url with embedded credentails.`)
-        }
-
-        let method = init.method || input.method || 'GET'
-        this.method = method.toUpperCase();
-
-        let headers = init.headers || input.headers || {}
-        if (!headers.has('Accept')) {
-            headers.set('Accept', '*/*')
-        }
-
-        this.headers = headers
-    }
-
-    get [Symbol.toStringTag]() {
-        return 'Request'
-    }
-
-    clone() {
-        return new Request(this)
-    }
-}
-
-export class Response {
-    #chunked = false;
-    #chunkBuff = null;
-    #bodyUsed = false
-
-    constructor(resp, buffer, reader, option = {}) {
-        this.response = resp
-        this.buffer = buffer
-        this.reader = reader
-        this.url = option.url
-
-        this.headers = resp.headers
-        this.statusText = resp.statusText
-        this.status = resp.status
-
-        if (typeof (resp.bodyLength) === "number") {
-            this.#chunked = false
-        } else {
-            this.#chunked = true
-            this.#chunkBuff = buffer
-            this.buffer = new httpx.Buffer()
-        }
-
-        this.onChunk = undefined;
-    }
-
-    get chunked() {
-        return this.#chunked
-    }
-
-    get ok() {
-        return this.status >= 200 && this.status < 300;
-    }
-
-    get bodyUsed() {
-        return this.#bodyUsed
-    }
-
-    async #readChunk() {
-        while (true) {
-            let chunk = this.#chunkBuff.parseChunk();
-
-            if (chunk === undefined) {
-                let data = await this.reader.read()
-                if (data === undefined) {
-                    throw new Error('socket is shutdown')
-                }
-                this.#chunkBuff.write(data)
-                continue
-            } else if (chunk === null) {
-                // end
-                return null
-            } else if (chunk instanceof ArrayBuffer) {
-                return chunk
-            } else {
-                throw chunk
-            }
-        }
-    }
-
-    async #readBody() {
-        while (true) {
-
-            if (this.buffer.byteLength >= this.response.bodyLength) {
-                let buf = this.buffer.buffer;
-                this.buffer.clear();
-                return buf;
-            }
-
-            let data = await this.reader.read()
-            if (data === undefined) {
-                let buf = this.buffer.buffer;
-                this.buffer.clear();
-                return buf;
-            }
-
-            this.buffer.write(data)
-        }
-    }
-
-    async arrayBuffer() {
-        this.#bodyUsed = true;
-        if (this.#chunked) {
-            while (true) {
-                let chunk = await this.#readChunk();
-                if (chunk === null) {
-                    let buf = this.buffer.buffer;
-                    this.buffer.clear();
-                    return buf;
-                }
-                this.buffer.write(chunk)
-                if (typeof this.onChunk === 'function') {
-                    let onChunk = this.onChunk;
-                    onChunk(chunk)
-                }
-            }
-        } else {
-            let body = await this.#readBody()
-            if (typeof this.onChunk === 'function') {
-                let onChunk = this.onChunk;
-                onChunk(body)
-            }
-            return body
-        }
-    }
-
-    async text() {
-        return new TextDecoder().decode(await this.arrayBuffer())
-    }
-
-    async json() {
-        return JSON.parse(await this.text())
-    }
-
-    get [Symbol.toStringTag]() {
-        return 'Response'
-    }
-}
-
-async function wait_response(reader, url) {
-    let buf = new httpx.Buffer()
-    let resp = undefined
-    while (true) {
-        let buff = await reader.read()
-        if (buff == undefined && resp == undefined) {
-            throw new TypeError('Illegal response')
-        }
-        buf.append(buff)
-        resp = buf.parseResponse()
-        if (resp instanceof httpx.WasiResponse) {
-            return new Response(resp, buf, reader, { url })
-        }
-    }
-}
-
-export async function fetch(input, init = {}) {
-    let url = new httpx.URL(input)
-    if (url.username !== '' || url.password != '') {
-        throw new TypeError(`${input} is an url with embedded credentails.`)
-    }
-
-    let method = init.method || 'GET'
-    method = method.toUpperCase();
-
-    let headers = init.headers || {}
-    if (!headers['Accept']) {
-        headers['Accept'] = '*/*'
-    }
-    if (!headers['Host']) {
-        headers['Host'] = url.host
-    }
-
-    let addr = net.nsloopup(url.host, `${url.port}`)[0];
-
-    let s = await net.WasiTcpConn.connect(addr)
-    let req = new httpx.WasiRequest()
-    req.version = init.version || 'HTTP/1.1'
-    req.headers = headers
-
-    let path = url.path
-    let query = url.query
-    if (query != undefined) {
-        req.uri = `${path}?${query}`
-    } else {
-        req.uri = path
-    }
-
-    req.method = method
-    req.body = init.body || ''
-    s.write(req.encode())
-    return await wait_response(s, url)
-}
+export { fetch, Headers, Request, Response, Agent, globalAgent };
 
 const STATUS_CODES = {
     100: 'Continue',                   // RFC 7231 6.2.1
@@ -326,7 +119,9 @@ class ClientRequest extends Writable {
 
     async _final() {
         try {
-            const opts = { body: this.body, method: this.opts.method, headers: this.opts.headers };
+            const method = (this.opts.method ?? 'GET').toUpperCase();
+            const body = method === 'GET' || method === 'HEAD' ? null : this.body.buffer;
+            const opts = { body, method, headers: this.opts.headers, ca: this.opts.ca, servername: this.opts.servername, agent: this.opts.agent, redirect: 'manual', decompress: false };
             const mayResponse = await fetch(this._createUrlStrFromOptions(this.opts), opts)
             const res = new IncomingMessageForClient(mayResponse);
             this.emit("response", res);
@@ -357,7 +152,7 @@ class ClientRequest extends Writable {
                 path,
                 port,
             } = opts;
-            return `${protocol}//${auth ? `${auth}@` : ""}${host ?? hostname}${port ? `:${port}` : ""}${path}`;
+            return `${protocol ?? "http:"}//${auth ? `${auth}@` : ""}${host ?? hostname}${port ? `:${port}` : ""}${path}`;
         }
     }
 
@@ -370,18 +165,25 @@ export class IncomingMessageForClient extends Readable {
     constructor(response) {
         super();
         this.response = response;
+        this.reader = undefined;
     }
 
     async _read(_size) {
+        if (this.reader === undefined) {
+            if (this.response.body === null) {
+                this.push(null);
+                return;
+            }
+            this.reader = this.response.body.getReader();
+        }
         try {
-            this.response.onChunk = (chunk) => {
-                this.push(Buffer.from(chunk));
+            const { value, done } = await this.reader.read();
+            if (done) {
+                this.push(null);
+            } else {
+                this.push(Buffer.from(value.buffer, value.byteOffset, value.byteLength));
             }
-
-            const _ = await this.response.arrayBuffer();
-            this.emit('end')
         } catch (e) {
-            // deno-lint-ignore no-explicit-any
             this.destroy(e);
         }
     }
@@ -394,9 +196,19 @@ export class IncomingMessageForClient extends Readable {
     }
 
     get trailers() {
+        if (this.response) {
+            return Object.fromEntries(trailersOf(this.response).entries());
+        }
         return {};
     }
 
+    get rawTrailers() {
+        if (this.response) {
+            return [...trailersOf(this.response)].flat();
+        }
+        return [];
+    }
+
     get statusCode() {
         return this.response?.status || 0;
     }
@@ -414,8 +226,9 @@ export class ServerResponse extends Writable {
     #conn;
     #firstChunk = null;
     #_end = false;
+    #trailers = undefined;
 
-    constructor(conn) {
+    constructor(conn, req) {
         super({
             autoDestroy: true,
             defaultEncoding: "utf-8",
@@ -435,7 +248,9 @@ export class ServerResponse extends Writable {
                     }
                 }
 
-                this.#conn.write(chunk);
+                if (!this.#isHead()) {
+                    this.#conn.write(chunk);
+                }
                 return cb();
             },
             final: (cb) => {
@@ -444,11 +259,8 @@ export class ServerResponse extends Writable {
                 } else if (!this.headersSent) {
                     this.respond(true);
                 }
-                if (this.#conn.connection == 'close') {
-                    this.#conn.close()
-                } else {
-                    this.#conn.end();
-                }
+                // the connection loop decides whether the socket stays open
+                this.#conn.end(undefined, this.#trailers);
                 return cb();
             },
             destroy: (err, cb) => {
@@ -459,6 +271,30 @@ export class ServerResponse extends Writable {
             },
         });
         this.#conn = conn;
+        this.socket = conn.socket;
+        this.req = req;
+    }
+
+    #isHead() {
+        return this.req?.method === "HEAD";
+    }
+
+    get connection() {
+        return this.socket;
+    }
+
+    // Sends the interim `100 Continue` a client waits for before sending the
+    // body of a request with `Expect: 100-continue`.
+    writeContinue(cb) {
+        this.#conn.writeContinue();
+        if (typeof cb === "function") {
+            process.nextTick(cb);
+        }
+    }
+
+    setTimeout(msecs, cb) {
+        this.socket.setTimeout(msecs, cb);
+        return this;
     }
 
     setHeader(name, value) {
@@ -466,6 +302,23 @@ export class ServerResponse extends Writable {
         return this;
     }
 
+    appendHeader(name, value) {
+        const key = name.toLowerCase();
+        const prev = this.#headers[key];
+        this.#headers[key] = prev === undefined ? value : [].concat(prev, value);
+        return this;
+    }
+
+    // Non-standard, adds a `Set-Cookie` header, see `serialize` in the
+    // `cookie` module for the options.
+    setCookie(name, value, options = undefined) {
+        return this.appendHeader("set-cookie", serializeCookie(name, value, options));
+    }
+
+    clearCookie(name, options = {}) {
+        return this.setCookie(name, "", { ...options, expires: new Date(0), maxAge: undefined });
+    }
+
     getHeader(name) {
         return this.#headers[name.toLowerCase()];
     }
@@ -479,6 +332,19 @@ export class ServerResponse extends Writable {
         return this.#headers[name.toLowerCase()] != undefined;
     }
 
+    // Trailers are only sent with a chunked body, i.e. when the response
+    // is written in more than one piece over HTTP/1.1.
+    addTrailers(headers) {
+        this.#trailers ??= {};
+        const entries = Array.isArray(headers) ? headers : Object.entries(headers);
+        for (const [name, value] of entries) {
+            if (/[\r\n]/.test(`${name}${value}`)) {
+                throw new TypeError(`Invalid trailer ${name}`);
+            }
+            this.#trailers[name] = value;
+        }
+    }
+
     writeHead(status, headers) {
         this.statusCode = status;
         for (const k in headers) {
@@ -490,8 +356,8 @@ export class ServerResponse extends Writable {
     #ensureHeaders(singleChunk) {
         if (this.statusCode === undefined) {
             this.statusCode = 200;
-            this.statusMessage = "OK";
         }
+        this.statusMessage ??= STATUS_CODES[this.statusCode] ?? "";
         if (typeof singleChunk === "string" && !this.hasHeader("content-type")) {
             this.setHeader("content-type", "text/plain;charset=UTF-8");
         }
@@ -501,15 +367,24 @@ export class ServerResponse extends Writable {
 
         this.headersSent = true;
         this.#ensureHeaders(singleChunk);
+        if (this.#isHead()) {
+            // the head describes the body that is never sent
+            if (final && singleChunk && !this.hasHeader("content-length")) {
+                this.setHeader("content-length", Buffer.byteLength(singleChunk));
+            }
+            this.#conn.respondWith(undefined, {
+                headers: this.#headers,
+                status: this.statusCode,
+                statusText: this.statusMessage,
+            });
+            return;
+        }
         if (final) {
             this.#conn.respondWith(
                 singleChunk, {
                 headers: this.#headers,
                 status: this.statusCode,
                 statusText: this.statusMessage,
-            }
-            ).catch(() => {
-                // ignore this error
             });
         } else {
             this.#conn.chunk({
@@ -540,34 +415,103 @@ export class ServerResponse extends Writable {
 
 export class IncomingMessageForServer extends Readable {
     #req;
+    #body;
+    #trailers = {};
+    #rawTrailers = [];
     url;
+    complete = false;
+    aborted = false;
 
     constructor(req, conn) {
-        // Check if no body (GET/HEAD/OPTIONS/...)
-        let value = req.body;
         super({
             autoDestroy: true,
             emitClose: true,
             objectMode: false,
-            read: async function (_size) {
-                if (!value) {
-                    this.push(null);
-                } else {
-                    this.push(Buffer.from(value));
-                    value = null;
-                }
+            read: (_size) => {
+                this.#pull();
             },
             destroy: (err, cb) => {
-                conn.close();
+                // an unfinished body leaves the connection out of sync
+                if (!this.complete) {
+                    conn.socket.destroy();
+                }
                 cb(err);
             },
         });
         this.#req = req;
+        this.#body = req.bodyLength === 0 ? null : conn.body(req);
         this.url = req.uri;
+        this.socket = conn.socket;
+    }
+
+    async #pull() {
+        let next = { done: true };
+        try {
+            if (this.#body !== null) {
+                next = await this.#body.next();
+            }
+        } catch (e) {
+            this.aborted = true;
+            this.emit("aborted");
+            this.destroy(e);
+            return;
+        }
+        if (next.done) {
+            this.#finish();
+            this.push(null);
+        } else {
+            this.push(Buffer.from(next.value));
+        }
+    }
+
+    #finish() {
+        if (!this.complete && this.#req.bodyLength === "chunked") {
+            const conn = this.socket.httpConn;
+            this.#trailers = conn.buf.trailers;
+            this.#rawTrailers = conn.buf.rawTrailers;
+        }
+        this.complete = true;
+    }
+
+    // Reads and drops whatever the handler left unread so the connection can
+    // carry the next request. Resolves false when that is not possible.
+    async _dump() {
+        if (this.complete || this.#body === null) {
+            this.#finish();
+            return true;
+        }
+        try {
+            while (!(await this.#body.next()).done) {
+                // discard
+            }
+        } catch {
+            return false;
+        }
+        this.#finish();
+        return true;
+    }
+
+    setTimeout(msecs, cb) {
+        this.socket.setTimeout(msecs, cb);
+        return this;
     }
 
-    get aborted() {
-        return false;
+    /**
+     * Non-standard, reads an urlencoded or multipart body into a FormData,
+     * spilling large uploads to disk as `Response.prototype.formData()`.
+     */
+    async formData(options = undefined) {
+        return await parseFormData(this.headers['content-type'], this, options);
+    }
+
+    /** Non-standard, the cookies of the `Cookie` header by name. */
+    get cookies() {
+        const header = this.headers['cookie'];
+        return header === undefined ? {} : parseCookie([].concat(header).join('; '));
+    }
+
+    get connection() {
+        return this.socket;
     }
     get httpVersion() {
         return this.#req.version;
@@ -576,11 +520,188 @@ export class IncomingMessageForServer extends Readable {
     get headers() {
         return this.#req.headers;
     }
+    get rawHeaders() {
+        return this.#req.rawHeaders;
+    }
+    get trailers() {
+        return this.#trailers;
+    }
+    get rawTrailers() {
+        return this.#rawTrailers;
+    }
     get method() {
         return this.#req.method;
     }
 }
 
+function parseAddress(addr) {
+    const i = addr.lastIndexOf(':');
+    return {
+        address: addr.slice(0, i).replace(/^\[(.*)\]$/, '$1'),
+        port: Number(addr.slice(i + 1)),
+    };
+}
+
+// The accepted connection as seen by request handlers, `req.socket` and the
+// socket passed to "connection", "timeout" and "clientError" listeners.
+class ServerSocket extends EventEmitter {
+    #conn;
+    #pendingRead = undefined;
+    destroyed = false;
+
+    constructor(conn) {
+        super();
+        this.#conn = conn;
+        this.httpConn = undefined;
+        conn.on('timeout', () => this.emit('timeout'));
+    }
+
+    get remoteAddress() {
+        return this.destroyed ? undefined : parseAddress(this.#conn.peer()).address;
+    }
+    get remotePort() {
+        return this.destroyed ? undefined : parseAddress(this.#conn.peer()).port;
+    }
+    get localAddress() {
+        return this.destroyed ? undefined : parseAddress(this.#conn.local()).address;
+    }
+    get localPort() {
+        return this.destroyed ? undefined : parseAddress(this.#conn.local()).port;
+    }
+    get timeout() {
+        return this.destroyed ? 0 : this.#conn.timeout;
+    }
+    // set on connections accepted by an https server
+    get servername() {
+        return this.#conn.servername;
+    }
+    get alpnProtocol() {
+        return this.#conn.alpnProtocol;
+    }
+
+    // Resolves with the next bytes, or undefined once the peer closed the
+    // connection or the socket was destroyed.
+    read() {
+        if (this.destroyed) {
+            return Promise.resolve(undefined);
+        }
+        return new Promise((resolve) => {
+            this.#pendingRead = resolve;
+            this.#conn.read().then((data) => {
+                resolve(data);
+            }, (e) => {
+                this.emit('error', e);
+                resolve(undefined);
+            }).finally(() => {
+                this.#pendingRead = undefined;
+            });
+        });
+    }
+
+    write(data, cb) {
+        if (this.destroyed) {
+            return false;
+        }
+        if (isTypedArray(data)) {
+            data = data.buffer.slice(data.byteOffset, data.byteOffset + data.byteLength);
+        }
+        return this.#conn.write(data, cb);
+    }
+
+    // Flushes what is buffered, then closes the connection.
+    end(data, cb) {
+        if (typeof data === "function") {
+            cb = data;
+            data = undefined;
+        }
+        if (data !== undefined && data !== null) {
+            this.write(data);
+        }
+        if (this.destroyed) {
+            return this;
+        }
+        this.#conn.shutdown().catch(() => {
+            // the peer is gone already
+        }).finally(() => {
+            this.destroy();
+            if (typeof cb === "function") {
+                cb();
+            }
+        });
+        return this;
+    }
+
+    destroy(err) {
+        if (this.destroyed) {
+            return this;
+        }
+        this.destroyed = true;
+        try {
+            this.#conn.close();
+        } catch {
+            // already closed
+        }
+        this.#pendingRead?.(undefined);
+        if (err) {
+            this.emit('error', err);
+        }
+        process.nextTick(() => this.emit('close', !!err));
+        return this;
+    }
+
+    // The connection keeps the inactivity timer, reads and writes restart
+    // it.
+    setTimeout(msecs, cb) {
+        if (typeof cb === "function") {
+            this.once('timeout', cb);
+        }
+        if (!this.destroyed) {
+            this.#conn.timeout = msecs;
+        }
+        return this;
+    }
+
+    setNoDelay() {
+        return this;
+    }
+
+    setKeepAlive() {
+        return this;
+    }
+}
+
+const TIMED_OUT = Symbol('timed out');
+
+function connectionReset() {
+    const err = new Error('aborted');
+    err.code = 'ECONNRESET';
+    return err;
+}
+
+const PARSE_ERROR_CODES = {
+    HeadersTooLarge: 'HPE_HEADER_OVERFLOW',
+    TooManyHeaders: 'HPE_HEADER_OVERFLOW',
+    InvalidMethod: 'HPE_INVALID_METHOD',
+    InvalidUrl: 'HPE_INVALID_URL',
+    InvalidVersion: 'HPE_INVALID_VERSION',
+    InvalidHeaders: 'HPE_INVALID_HEADER_TOKEN',
+    InvalidHeaderName: 'HPE_INVALID_HEADER_TOKEN',
+    InvalidHeaderValue: 'HPE_INVALID_HEADER_TOKEN',
+    InvalidContentLength: 'HPE_INVALID_CONTENT_LENGTH',
+    InvalidTransferEncoding: 'HPE_INVALID_TRANSFER_ENCODING',
+    AmbiguousLength: 'HPE_UNEXPECTED_CONTENT_LENGTH',
+    InvalidChunk: 'HPE_INVALID_CHUNK_SIZE',
+};
+
+function parseError(e, rawPacket) {
+    const reason = e?.message ?? String(e);
+    const err = new Error(`Parse Error: ${reason}`);
+    err.code = PARSE_ERROR_CODES[reason] ?? 'HPE_INVALID_CONSTANT';
+    err.reason = reason;
+    err.rawPacket = rawPacket;
+    return err;
+}
+
 class HttpConn {
 
     #chunk = undefined;
@@ -588,9 +709,14 @@ class HttpConn {
     #version = "HTTP/1.1";
     #chunkBuffer = undefined;
     #respHeaders;
+    #continued = false;
 
-    constructor(socket) {
+    constructor(socket, limits) {
         this.socket = socket
+        this.limits = limits
+        // bytes read from the socket and not parsed yet
+        this.buf = new httpx.Buffer();
+        socket.httpConn = this;
     }
 
     get connection() {
@@ -601,33 +727,117 @@ class HttpConn {
         return this.#version
     }
 
-    async nextRequest() {
-        let buffer = new httpx.Buffer();
+    get continued() {
+        return this.#continued;
+    }
+
+    // Reads more bytes into `buf`. Resolves false at the end of the stream
+    // and TIMED_OUT when nothing arrived within `msecs` (0 waits forever).
+    async #fill(msecs = 0) {
+        let timer;
+        const read = this.socket.read();
+        const d = msecs > 0 ? await Promise.race([read, new Promise((resolve) => {
+            timer = setTimeout(() => resolve(TIMED_OUT), msecs);
+        })]) : await read;
+        clearTimeout(timer);
+        if (d === TIMED_OUT) {
+            return d;
+        }
+        if (d === undefined || d.byteLength <= 0) {
+            return false;
+        }
+        this.buf.append(d);
+        return true;
+    }
+
+    /**
+     * Resolves with the head of the next request, or null when the peer
+     * closed the connection or stayed idle for `idleTimeout` ms. A head that
+     * does not parse, or is not complete `headersTimeout` ms after its first
+     * byte, rejects.
+     */
+    async nextRequest(idleTimeout, headersTimeout) {
+        let deadline = this.buf.length > 0 ? Date.now() + headersTimeout : 0;
         while (true) {
-            let d = await this.socket.read();
-            if (d == undefined || d.byteLength <= 0) {
+            let req;
+            try {
+                req = this.buf.parseRequestHead(this.limits);
+            } catch (e) {
+                throw parseError(e, this.buf.buffer);
+            }
+            if (req instanceof httpx.WasiRequest) {
+                this.#version = req.version;
+                if (this.#version == "HTTP/1.1") {
+                    this.#connection = (req.getHeader('connection') ?? "keep-alive").toLowerCase()
+                } else if (this.#version == "HTTP/1.0") {
+                    this.#connection = (req.getHeader('connection') ?? "close").toLowerCase()
+                }
+                this.#continued = false;
+                return req
+            }
+            const idle = deadline === 0;
+            let msecs = idle ? idleTimeout : 0;
+            if (!idle && headersTimeout > 0) {
+                msecs = Math.max(deadline - Date.now(), 1);
+            }
+            const r = await this.#fill(msecs);
+            if (r === TIMED_OUT && !idle) {
+                const err = new Error('Request timeout');
+                err.code = 'ERR_HTTP_REQUEST_TIMEOUT';
+                throw err;
+            }
+            if (r !== true) {
                 return null;
             }
-            buffer.append(d);
-            try {
-                let req = buffer.parseRequest();
-                if (req instanceof httpx.WasiRequest) {
-                    this.#version = req.version;
-                    if (this.#version == "HTTP/1.1") {
-                        this.#connection = (req.getHeader('connection') ?? "keep-alive").toLowerCase()
-                    } else if (this.#version == "HTTP/1.0") {
-                        this.#connection = (req.getHeader('connection') ?? "close").toLowerCase()
+            if (idle) {
+                // the clock for the head starts with its first byte
+                deadline = Date.now() + headersTimeout;
+            }
+        }
+    }
+
+    // Yields the body of `req` as ArrayBuffers as it arrives on the socket.
+    async *body(req) {
+        const length = req.bodyLength;
+        if (length === "chunked") {
+            while (true) {
+                const chunk = this.buf.parseChunk();
+                if (chunk === undefined) {
+                    if (!await this.#fill()) {
+                        throw connectionReset();
                     }
-                    return req
+                } else if (chunk === null) {
+                    return;
+                } else if (chunk instanceof ArrayBuffer) {
+                    yield chunk;
+                } else {
+                    throw parseError(chunk, this.buf.buffer);
                 }
-            } catch (e) {
-                return null;
             }
         }
+        let remaining = length;
+        while (remaining > 0) {
+            const data = this.buf.take(remaining);
+            if (data === null) {
+                if (!await this.#fill()) {
+                    throw connectionReset();
+                }
+                continue;
+            }
+            remaining -= data.byteLength;
+            yield data;
+        }
+    }
+
+    writeContinue() {
+        if (!this.#continued && this.socket) {
+            this.#continued = true;
+            this.socket.write("HTTP/1.1 100 Continue\r\n\r\n");
+        }
     }
 
     respondWith(body, resp_header) {
-        if (!this.socket) {
+        if (this.socket.destroyed) {
             return
         }
         if (body) {
@@ -642,13 +852,24 @@ class HttpConn {
     }
 
     chunk(resp_header) {
+        // with a known length the body follows the head as it is written
+        if (resp_header.headers['content-length'] !== undefined) {
+            this.respondWith(undefined, resp_header);
+            return;
+        }
         if (this.#version == "HTTP/1.1") {
             let resp = new httpx.WasiResponse()
             resp.version = this.#version;
             resp.headers = resp_header.headers;
             resp.status = resp_header.status;
             resp.statusText = resp_header.statusText;
-            this.#chunk = resp.chunk(this.socket)
+            // the chunk encoder writes everything, including the last
+            // chunk, through `write`/`end` without closing the socket
+            const socket = this.socket;
+            this.#chunk = resp.chunk({
+                write: (data) => socket.write(data),
+                end: (data) => socket.write(data),
+            })
         } else {
             this.#chunkBuffer = new httpx.Buffer();
             this.#respHeaders = resp_header;
@@ -662,9 +883,9 @@ class HttpConn {
         }
     }
 
-    end(chunk) {
+    end(chunk, trailers) {
         if (this.#chunk) {
-            this.#chunk.end(chunk)
+            this.#chunk.end(chunk, trailers)
             this.#chunk = undefined
             return
         }
@@ -681,13 +902,15 @@ class HttpConn {
 
     close() {
         this.end()
-        this.socket = undefined
+        this.socket.end()
     }
 }
 
+// default limit for the request head, Node's `--max-http-header-size`
+export const maxHeaderSize = 16384;
 
-export function Server(handler) {
-    return new ServerImpl(handler);
+export function Server(options, handler) {
+    return new ServerImpl(options, handler);
 }
 
 
@@ -727,19 +950,48 @@ function _normalizeArgs(args) {
 }
 
 
+function isUpgrade(req) {
+    return req.headers['upgrade'] !== undefined
+        && /(^|,)\s*upgrade\s*(,|$)/i.test(req.headers['connection'] ?? '');
+}
+
 class ServerImpl extends EventEmitter {
     #httpConnections = new Set();
     #listener = undefined;
     #listening = false;
+    #secureContext = undefined;
 
-    constructor(handler) {
+    constructor(options, handler) {
         super();
 
+        if (typeof options === "function") {
+            handler = options;
+            options = {};
+        }
+        // set by https.createServer(), accepted connections are handshaked with it first
+        this.#secureContext = options?.secureContext;
+        this.maxHeaderSize = options?.maxHeaderSize ?? maxHeaderSize;
+        this.maxHeadersCount = options?.maxHeadersCount ?? null;
+        // inactivity timeout of a socket, 0 disables it
+        this.timeout = 0;
+        // time allowed to receive a whole request head
+        this.headersTimeout = options?.headersTimeout ?? 60000;
+        // time an idle keep-alive connection waits for the next request
+        this.keepAliveTimeout = options?.keepAliveTimeout ?? 5000;
+
         if (handler !== undefined) {
             this.on("request", handler);
         }
     }
 
+    setTimeout(msecs, cb) {
+        this.timeout = msecs;
+        if (typeof cb === "function") {
+            this.on("timeout", cb);
+        }
+        return this;
+    }
+
     listen(...args) {
         // TODO(bnoordhuis) Delegate to net.Server#listen().
         const normalized = _normalizeArgs(args);
@@ -768,34 +1020,123 @@ class ServerImpl extends EventEmitter {
         return this;
     }
 
-    async #listenLoop() {
-        const go = async (httpConn) => {
-            try {
-                for (; ;) {
-                    let request = null;
-                    try {
-                        // Note: httpConn.nextRequest() calls httpConn.close() on error.
-                        request = await httpConn.nextRequest();
-                    } catch {
-                        // Connection closed.
-                        // TODO(bnoordhuis) Emit "clientError" event on the http.Server
-                        // instance? Node emits it when request parsing fails and expects
-                        // the listener to send a raw 4xx HTTP response on the underlying
-                        // net.Socket but we don't have one to pass to the listener.
-                    }
-                    if (request === null) {
-                        break;
-                    }
-                    const req = new IncomingMessageForServer(request, httpConn);
-                    const res = new ServerResponse(httpConn);
-                    this.emit("request", req, res);
+    #limits() {
+        return { maxHeaderSize: this.maxHeaderSize, maxHeadersCount: this.maxHeadersCount ?? undefined };
+    }
+
+    // Without a "clientError" listener the client gets a minimal error
+    // response before the connection is closed, like in Node.
+    #clientError(err, socket) {
+        if (this.listenerCount("clientError") > 0) {
+            this.emit("clientError", err, socket);
+            return;
+        }
+        let status = 400;
+        if (err.code === 'HPE_HEADER_OVERFLOW') {
+            status = 431;
+        } else if (err.code === 'ERR_HTTP_REQUEST_TIMEOUT') {
+            status = 408;
+        }
+        socket.end(`HTTP/1.1 ${status} ${STATUS_CODES[status]}\r\nConnection: close\r\n\r\n`);
+    }
+
+    #dispatch(req, res) {
+        const expect = req.headers['expect'];
+        if (expect === undefined) {
+            this.emit("request", req, res);
+        } else if (expect.toLowerCase() === '100-continue') {
+            if (this.listenerCount("checkContinue") > 0) {
+                this.emit("checkContinue", req, res);
+            } else {
+                res.writeContinue();
+                this.emit("request", req, res);
+            }
+        } else if (this.listenerCount("checkExpectation") > 0) {
+            this.emit("checkExpectation", req, res);
+        } else {
+            res.writeHead(417);
+            res.end();
+        }
+    }
+
+    // Serves the requests of one connection in order. The next head is only
+    // read once the response is finished and the body of the request has
+    // been consumed.
+    async #serve(httpConn) {
+        const socket = httpConn.socket;
+        let idleTimeout = this.headersTimeout;
+        try {
+            while (!socket.destroyed) {
+                let request;
+                try {
+                    request = await httpConn.nextRequest(idleTimeout, this.headersTimeout);
+                } catch (e) {
+                    this.#clientError(e, socket);
+                    return;
+                }
+                if (request === null) {
+                    break;
+                }
+                idleTimeout = this.keepAliveTimeout;
+
+                const req = new IncomingMessageForServer(request, httpConn);
+                // the connection now belongs to the "upgrade" listener, along
+                // with whatever the client sent after the head
+                if (isUpgrade(req) && this.listenerCount("upgrade") > 0) {
+                    const head = Buffer.from(httpConn.buf.take() ?? new ArrayBuffer(0));
+                    socket.setTimeout(0);
+                    this.emit("upgrade", req, socket, head);
+                    return;
+                }
+                const res = new ServerResponse(httpConn, req);
+                let onClose;
+                const finished = new Promise((resolve) => {
+                    onClose = resolve;
+                    res.once("finish", resolve);
+                    socket.once("close", resolve);
+                });
+                this.#dispatch(req, res);
+                await finished;
+                socket.removeListener("close", onClose);
+
+                const connection = String(res.getHeader("connection") ?? "").toLowerCase();
+                if (socket.destroyed || httpConn.connection === 'close' || connection === 'close') {
+                    break;
+                }
+                // a client still waiting for 100 Continue will not send the body
+                if (!req.complete && req.headers['expect'] !== undefined && !httpConn.continued) {
+                    break;
+                }
+                if (!await req._dump()) {
+                    break;
                 }
-            } finally {
-                this.#httpConnections.delete(httpConn);
-                httpConn.close()
             }
-        };
+            socket.end();
+        } finally {
+            this.#httpConnections.delete(httpConn);
+        }
+    }
+
+    #accept(conn) {
+        const socket = new ServerSocket(conn);
+        const httpConn = new HttpConn(socket, this.#limits());
+        this.#httpConnections.add(httpConn);
+        if (this.timeout > 0) {
+            socket.setTimeout(this.timeout);
+        }
+        socket.on("timeout", () => {
+            if (!this.emit("timeout", socket)) {
+                socket.destroy();
+            }
+        });
+        socket.on("error", () => {
+            // the connection loop sees the socket end
+        });
+        this.emit("connection", socket);
+        this.#serve(httpConn);
+    }
 
+    async #listenLoop() {
         const listener = this.#listener;
 
         if (listener !== undefined) {
@@ -804,26 +1145,28 @@ class ServerImpl extends EventEmitter {
             try {
                 while (this.#listening) {
                     let tcp_conn = await listener.accept();
+                    if (this.#secureContext) {
+                        this.#secureContext.context.accept(tcp_conn, 120000).then((tls_conn) => {
+                            this.#accept(tls_conn);
+                        }, (e) => this.emit('tlsClientError', e));
+                        continue;
+                    }
                     try {
-                        let httpConn = new HttpConn(tcp_conn);
-                        this.#httpConnections.add(httpConn);
-                        go(httpConn);
+                        this.#accept(tcp_conn);
                     } catch {
                         continue;
                     }
                 }
             } catch (e) {
-                this.emit('error', e)
+                if (this.#listening) {
+                    this.emit('error', e)
+                }
             } finally {
-                this.listening = false
+                this.#listening = false
             }
         }
     }
 
-    get listening() {
-        return this.#listening !== undefined;
-    }
-
     close(cb) {
         const listening = this.#listening;
 
@@ -845,11 +1188,7 @@ class ServerImpl extends EventEmitter {
             this.#listening = false;
 
             for (const httpConn of this.#httpConnections) {
-                try {
-                    httpConn.close();
-                } catch {
-                    // Already closed.
-                }
+                httpConn.socket.destroy();
             }
 
             this.#httpConnections.clear();
@@ -863,18 +1202,19 @@ class ServerImpl extends EventEmitter {
     }
 
     address() {
-        const addr = this.#listener.addr;
+        const addr = this.#listener.local();
+        const i = addr.lastIndexOf(':');
         return {
-            port: addr.port,
-            address: addr.hostname,
+            port: Number(addr.slice(i + 1)),
+            address: addr.slice(0, i),
         };
     }
 }
 
 Server.prototype = ServerImpl.prototype;
 
-export function createServer(handler) {
-    return Server(handler);
+export function createServer(options, handler) {
+    return Server(options, handler);
 }
 
 function urlToHttpOptions(url) {
@@ -918,6 +1258,8 @@ export function get(...args) {
 }
 
 export default {
+    Agent,
+    globalAgent,
     ClientRequest,
     STATUS_CODES,
     METHODS,
@@ -927,4 +1269,5 @@ export default {
     ServerResponse,
     request,
     get,
+    maxHeaderSize,
 };
\ No newline at end of file
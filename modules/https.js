@@ -0,0 +1,39 @@
+import http from 'http'
+import { createSecureContext } from 'tls'
+
+export function createServer(options, handler) {
+    if (typeof options === 'function') {
+        handler = options;
+        options = {};
+    }
+    options = { ...options, secureContext: createSecureContext(options) };
+    return http.createServer(options, handler);
+}
+
+export const Server = createServer;
+
+export const Agent = http.Agent;
+export const globalAgent = http.globalAgent;
+
+export function request(...args) {
+    // urls carry their own scheme, plain option objects default to https
+    if (typeof args[0] === 'object' && args[0] !== null && args[0].href === undefined) {
+        args[0] = { protocol: 'https:', ...args[0] };
+    }
+    return http.request(...args);
+}
+
+export function get(...args) {
+    const req = request(...args);
+    req.end();
+    return req;
+}
+
+export default {
+    Agent,
+    globalAgent,
+    createServer,
+    Server,
+    request,
+    get,
+};
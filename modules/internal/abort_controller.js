@@ -0,0 +1,176 @@
+// AbortController / AbortSignal for fetch and the streams `signal` options.
+// There is no EventTarget yet, so AbortSignal carries its own minimal
+// listener list with the same addEventListener/removeEventListener shape.
+
+const kCreate = Symbol('kCreate');
+
+const DOMExceptionCodes = {
+    IndexSizeError: 1,
+    HierarchyRequestError: 3,
+    WrongDocumentError: 4,
+    InvalidCharacterError: 5,
+    NoModificationAllowedError: 7,
+    NotFoundError: 8,
+    NotSupportedError: 9,
+    InvalidStateError: 11,
+    SyntaxError: 12,
+    InvalidModificationError: 13,
+    NamespaceError: 14,
+    InvalidAccessError: 15,
+    TypeMismatchError: 17,
+    SecurityError: 18,
+    NetworkError: 19,
+    AbortError: 20,
+    URLMismatchError: 21,
+    QuotaExceededError: 22,
+    TimeoutError: 23,
+    InvalidNodeTypeError: 24,
+    DataCloneError: 25,
+};
+
+export class DOMException extends Error {
+    constructor(message = '', name = 'Error') {
+        super(message);
+        Object.defineProperty(this, 'name', {
+            value: `${name}`,
+            enumerable: false,
+            configurable: true,
+            writable: true,
+        });
+    }
+
+    get code() {
+        return DOMExceptionCodes[this.name] ?? 0;
+    }
+}
+
+export class AbortSignal {
+    #aborted = false;
+    #reason = undefined;
+    #listeners = [];
+
+    constructor(key) {
+        if (key !== kCreate) {
+            throw new TypeError('Illegal constructor');
+        }
+        this.onabort = null;
+    }
+
+    get aborted() {
+        return this.#aborted;
+    }
+
+    get reason() {
+        return this.#reason;
+    }
+
+    throwIfAborted() {
+        if (this.#aborted) {
+            throw this.#reason;
+        }
+    }
+
+    addEventListener(type, listener, options) {
+        if (type !== 'abort' || listener === null || listener === undefined) {
+            return;
+        }
+        if (this.#listeners.some((l) => l.listener === listener)) {
+            return;
+        }
+        const once = typeof options === 'object' && options !== null && !!options.once;
+        this.#listeners.push({ listener, once });
+    }
+
+    removeEventListener(type, listener) {
+        if (type !== 'abort') {
+            return;
+        }
+        this.#listeners = this.#listeners.filter((l) => l.listener !== listener);
+    }
+
+    dispatchEvent(event) {
+        if (typeof this.onabort === 'function') {
+            this.onabort.call(this, event);
+        }
+        for (const l of this.#listeners.slice()) {
+            if (l.once) {
+                this.removeEventListener(event.type, l.listener);
+            }
+            if (typeof l.listener === 'function') {
+                l.listener.call(this, event);
+            } else if (typeof l.listener?.handleEvent === 'function') {
+                l.listener.handleEvent(event);
+            }
+        }
+        return true;
+    }
+
+    static abort(reason) {
+        const signal = new AbortSignal(kCreate);
+        abortSignal(signal, reason);
+        return signal;
+    }
+
+    static timeout(ms) {
+        const signal = new AbortSignal(kCreate);
+        setTimeout(() => {
+            abortSignal(signal, new DOMException('The operation was aborted due to timeout', 'TimeoutError'));
+        }, ms | 0);
+        return signal;
+    }
+
+    static any(signals) {
+        const signal = new AbortSignal(kCreate);
+        for (const s of signals) {
+            if (s.aborted) {
+                abortSignal(signal, s.reason);
+                return signal;
+            }
+        }
+        for (const s of signals) {
+            s.addEventListener('abort', () => abortSignal(signal, s.reason), { once: true });
+        }
+        return signal;
+    }
+
+    _abort(reason) {
+        if (this.#aborted) {
+            return;
+        }
+        this.#aborted = true;
+        this.#reason = reason === undefined
+            ? new DOMException('This operation was aborted', 'AbortError')
+            : reason;
+        this.dispatchEvent({ type: 'abort', target: this, currentTarget: this });
+    }
+
+    get [Symbol.toStringTag]() {
+        return 'AbortSignal';
+    }
+}
+
+function abortSignal(signal, reason) {
+    signal._abort(reason);
+}
+
+export class AbortController {
+    #signal = new AbortSignal(kCreate);
+
+    get signal() {
+        return this.#signal;
+    }
+
+    abort(reason) {
+        abortSignal(this.#signal, reason);
+    }
+
+    get [Symbol.toStringTag]() {
+        return 'AbortController';
+    }
+}
+
+export default {
+    AbortController,
+    AbortSignal,
+    DOMException,
+};
@@ -0,0 +1,586 @@
+// The resolver behind `dns` and `dns/promises`.
+//
+// Queries go to the configured servers over UDP, truncated answers are asked
+// again over TCP, and everything waits on the event loop. Answers are cached
+// for their TTL. `lookup()` uses the same servers once `setServers()` was
+// called; until then it asks the host resolver (`wasi_net.nsloopup`), which
+// blocks, but is what `getaddrinfo` would answer.
+
+import * as net from 'wasi_net'
+import { encodeQuery, decodeResponse } from '_node:dns'
+import { readFileSync } from 'fs'
+import { toASCII } from 'punycode'
+import { ERR_INVALID_ARG_TYPE, ERR_INVALID_ARG_VALUE } from 'internal/errors'
+
+export const NODATA = 'ENODATA';
+export const FORMERR = 'EFORMERR';
+export const SERVFAIL = 'ESERVFAIL';
+export const NOTFOUND = 'ENOTFOUND';
+export const NOTIMP = 'ENOTIMP';
+export const REFUSED = 'EREFUSED';
+export const BADQUERY = 'EBADQUERY';
+export const BADNAME = 'EBADNAME';
+export const BADFAMILY = 'EBADFAMILY';
+export const BADRESP = 'EBADRESP';
+export const CONNREFUSED = 'ECONNREFUSED';
+export const TIMEOUT = 'ETIMEOUT';
+export const EOF = 'EOF';
+export const FILE = 'EFILE';
+export const NOMEM = 'ENOMEM';
+export const DESTRUCTION = 'EDESTRUCTION';
+export const BADSTR = 'EBADSTR';
+export const BADFLAGS = 'EBADFLAGS';
+export const NONAME = 'ENONAME';
+export const BADHINTS = 'EBADHINTS';
+export const NOTINITIALIZED = 'ENOTINITIALIZED';
+export const CANCELLED = 'ECANCELLED';
+
+const RCODES = [null, FORMERR, SERVFAIL, NOTFOUND, NOTIMP, REFUSED];
+
+const SYSCALLS = {
+    A: 'queryA',
+    AAAA: 'queryAaaa',
+    ANY: 'queryAny',
+    CNAME: 'queryCname',
+    MX: 'queryMx',
+    NS: 'queryNs',
+    PTR: 'queryPtr',
+    SOA: 'querySoa',
+    SRV: 'querySrv',
+    TXT: 'queryTxt',
+};
+
+export class DNSException extends Error {
+    constructor(code, syscall, hostname) {
+        super(hostname ? `${syscall} ${code} ${hostname}` : `${syscall} ${code}`);
+        this.code = code;
+        this.errno = undefined;
+        this.syscall = syscall;
+        if (hostname) {
+            this.hostname = hostname;
+        }
+    }
+
+    get name() {
+        return 'Error';
+    }
+}
+
+export function isIPv4(s) {
+    const parts = `${s}`.split('.');
+    return parts.length === 4 && parts.every((p) => /^(0|[1-9]\d{0,2})$/.test(p) && Number(p) < 256);
+}
+
+export function isIPv6(s) {
+    s = `${s}`.replace(/%.*$/, '');
+    const last = s.lastIndexOf(':');
+    if (last !== -1 && s.includes('.', last)) {
+        // an embedded IPv4 address takes the place of two groups
+        if (!isIPv4(s.slice(last + 1))) {
+            return false;
+        }
+        s = `${s.slice(0, last + 1)}0:0`;
+    }
+    const halves = s.split('::');
+    if (halves.length > 2) {
+        return false;
+    }
+    const groups = halves.flatMap((half) => half === '' ? [] : half.split(':'));
+    if (!groups.every((g) => /^[0-9a-fA-F]{1,4}$/.test(g))) {
+        return false;
+    }
+    return halves.length === 2 ? groups.length < 8 : groups.length === 8;
+}
+
+export function isIP(s) {
+    return isIPv4(s) ? 4 : isIPv6(s) ? 6 : 0;
+}
+
+// `addr`, `addr:port`, `[addr]` or `[addr]:port`, as `setServers()` takes
+// them.
+function parseServer(server) {
+    let host = server;
+    let port = 53;
+    let m;
+    if ((m = /^\[([^\]]+)\](?::(\d+))?$/.exec(server)) !== null) {
+        host = m[1];
+        port = m[2] === undefined ? 53 : Number(m[2]);
+    } else if ((m = /^([^:]+):(\d+)$/.exec(server)) !== null) {
+        host = m[1];
+        port = Number(m[2]);
+    }
+    const family = isIP(host);
+    if (family === 0 || port < 1 || port > 65535) {
+        throw new ERR_INVALID_ARG_VALUE('servers', server, 'is not a valid IP address');
+    }
+    return { host, port, family };
+}
+
+function formatServer({ host, port, family }) {
+    if (port === 53) {
+        return host;
+    }
+    return family === 6 ? `[${host}]:${port}` : `${host}:${port}`;
+}
+
+function socketAddress({ host, port, family }) {
+    return family === 6 ? `[${host}]:${port}` : `${host}:${port}`;
+}
+
+function systemServers() {
+    const servers = [];
+    try {
+        for (const line of readFileSync('/etc/resolv.conf', 'utf8').split('\n')) {
+            const m = /^\s*nameserver\s+(\S+)/.exec(line);
+            if (m !== null && isIP(m[1]) !== 0) {
+                servers.push(parseServer(m[1].includes(':') ? `[${m[1]}]` : m[1]));
+            }
+        }
+    } catch {
+        // not readable from the sandbox
+    }
+    return servers.length > 0 ? servers : [parseServer('127.0.0.1')];
+}
+
+let defaultServers = null;
+
+function toQueryName(hostname) {
+    try {
+        return toASCII(hostname);
+    } catch {
+        return hostname;
+    }
+}
+
+function recvTimeout(left) {
+    return Math.max(1, Math.ceil(left));
+}
+
+function isTimeout(e) {
+    return /timed out/i.test(e?.message ?? '');
+}
+
+function isRefused(e) {
+    return /refused/i.test(e?.message ?? '');
+}
+
+async function exchangeUdp(job, server, query, id, timeout) {
+    const sock = net.WasiUdpSocket.connect(socketAddress(server));
+    job.close = () => sock.close();
+    try {
+        sock.send(query);
+        const deadline = Date.now() + timeout;
+        for (; ;) {
+            const left = deadline - Date.now();
+            if (left <= 0) {
+                throw new DNSException(TIMEOUT);
+            }
+            const buf = await sock.recv(recvTimeout(left));
+            let reply;
+            try {
+                reply = decodeResponse(buf);
+            } catch {
+                // not an answer, keep waiting
+                continue;
+            }
+            if (reply.id === id) {
+                return reply;
+            }
+        }
+    } finally {
+        job.close = null;
+        sock.close();
+    }
+}
+
+async function exchangeTcp(job, server, query, timeout) {
+    const deadline = Date.now() + timeout;
+    const conn = await net.WasiTcpConn.connect(socketAddress(server), timeout);
+    job.close = () => conn.close();
+    try {
+        const msg = new Uint8Array(query.byteLength + 2);
+        msg[0] = query.byteLength >> 8;
+        msg[1] = query.byteLength & 0xff;
+        msg.set(new Uint8Array(query), 2);
+        await new Promise((resolve, reject) => conn.write(msg.buffer, (e) => e ? reject(e) : resolve()));
+        let buf = new Uint8Array(0);
+        for (; ;) {
+            if (buf.length >= 2 && buf.length >= 2 + (buf[0] << 8 | buf[1])) {
+                const len = buf[0] << 8 | buf[1];
+                return decodeResponse(buf.slice(2, 2 + len).buffer);
+            }
+            const left = deadline - Date.now();
+            if (left <= 0) {
+                throw new DNSException(TIMEOUT);
+            }
+            const chunk = await conn.read(recvTimeout(left));
+            if (chunk === undefined) {
+                throw new DNSException(EOF);
+            }
+            const next = new Uint8Array(buf.length + chunk.byteLength);
+            next.set(buf);
+            next.set(new Uint8Array(chunk), buf.length);
+            buf = next;
+        }
+    } finally {
+        job.close = null;
+        conn.close();
+    }
+}
+
+function reverseName(ip) {
+    if (isIPv4(ip)) {
+        return `${ip.split('.').reverse().join('.')}.in-addr.arpa`;
+    }
+    if (!isIPv6(ip)) {
+        return null;
+    }
+    const halves = ip.split('::');
+    const groups = (part) => part === '' ? [] : part.split(':');
+    const head = groups(halves[0]);
+    const tail = halves.length === 2 ? groups(halves[1]) : [];
+    const words = head.concat(Array(8 - head.length - tail.length).fill('0'), tail);
+    const nibbles = words.map((w) => w.padStart(4, '0')).join('').split('');
+    return `${nibbles.reverse().join('.')}.ip6.arpa`;
+}
+
+function toResult(rrtype, records, options) {
+    switch (rrtype) {
+        case 'A':
+        case 'AAAA':
+            return records.map((r) => options.ttl ? { address: r.address, ttl: r.ttl } : r.address);
+        case 'CNAME':
+        case 'NS':
+        case 'PTR':
+            return records.map((r) => r.value);
+        case 'MX':
+            return records.map(({ priority, exchange }) => ({ priority, exchange }));
+        case 'TXT':
+            return records.map((r) => r.entries);
+        case 'SRV':
+            return records.map(({ priority, weight, port, name }) => ({ priority, weight, port, name }));
+        case 'SOA': {
+            const { nsname, hostmaster, serial, refresh, retry, expire, minttl } = records[0];
+            return { nsname, hostmaster, serial, refresh, retry, expire, minttl };
+        }
+        default:
+            return records.map((r) => {
+                const { name, ttl, ...rest } = r;
+                return r.type === 'A' || r.type === 'AAAA' ? { ...rest, ttl } : rest;
+            });
+    }
+}
+
+/**
+ * A resolver with its own servers and cache. `options` are `timeout` per
+ * try in ms (default 5000), `tries` per server (default 4), `cache` to keep
+ * answers for their TTL (default true), `maxTtl` in seconds to cap that, and
+ * `tcp` to skip UDP.
+ */
+export class Resolver {
+    #servers = null;
+    #timeout;
+    #tries;
+    #cache;
+    #maxTtl;
+    #tcp;
+    #pending = new Set();
+
+    constructor(options = {}) {
+        const { timeout = -1, tries = 4, cache = true, maxTtl = Infinity, tcp = false } = options;
+        if (typeof timeout !== 'number' || !(timeout === -1 || timeout >= 0)) {
+            throw new ERR_INVALID_ARG_VALUE('options.timeout', timeout);
+        }
+        if (!Number.isInteger(tries) || tries < 1) {
+            throw new ERR_INVALID_ARG_VALUE('options.tries', tries);
+        }
+        this.#timeout = timeout === -1 ? 5000 : timeout;
+        this.#tries = tries;
+        this.#cache = cache ? new Map() : null;
+        this.#maxTtl = maxTtl;
+        this.#tcp = tcp === true;
+    }
+
+    /** Whether `setServers()` picked the servers. */
+    get configured() {
+        return this.#servers !== null;
+    }
+
+    getServers() {
+        return this.#currentServers().map(formatServer);
+    }
+
+    setServers(servers) {
+        if (!Array.isArray(servers)) {
+            throw new ERR_INVALID_ARG_TYPE('servers', 'Array', servers);
+        }
+        this.#servers = servers.map((s) => {
+            if (typeof s !== 'string') {
+                throw new ERR_INVALID_ARG_TYPE('servers[]', 'string', s);
+            }
+            return parseServer(s);
+        });
+        this.#cache?.clear();
+    }
+
+    /** Rejects the outstanding queries with `ECANCELLED`. */
+    cancel() {
+        for (const job of this.#pending) {
+            job.close?.();
+            job.reject(new DNSException(CANCELLED, job.syscall, job.hostname));
+        }
+        this.#pending.clear();
+    }
+
+    #currentServers() {
+        if (this.#servers !== null) {
+            return this.#servers;
+        }
+        defaultServers ??= systemServers();
+        return defaultServers;
+    }
+
+    /**
+     * The records of `rrtype` for `hostname` in the shape node's
+     * `resolve*()` functions give them.
+     */
+    resolve(hostname, rrtype = 'A', options = {}) {
+        if (typeof hostname !== 'string') {
+            return Promise.reject(new ERR_INVALID_ARG_TYPE('hostname', 'string', hostname));
+        }
+        if (typeof rrtype !== 'string' || SYSCALLS[rrtype] === undefined) {
+            return Promise.reject(new ERR_INVALID_ARG_VALUE('rrtype', rrtype));
+        }
+        return this.#query(hostname, rrtype).then((records) => toResult(rrtype, records, options));
+    }
+
+    resolve4(hostname, options = {}) {
+        return this.resolve(hostname, 'A', options);
+    }
+
+    resolve6(hostname, options = {}) {
+        return this.resolve(hostname, 'AAAA', options);
+    }
+
+    resolveAny(hostname) {
+        return this.resolve(hostname, 'ANY');
+    }
+
+    resolveCname(hostname) {
+        return this.resolve(hostname, 'CNAME');
+    }
+
+    resolveMx(hostname) {
+        return this.resolve(hostname, 'MX');
+    }
+
+    resolveNs(hostname) {
+        return this.resolve(hostname, 'NS');
+    }
+
+    resolvePtr(hostname) {
+        return this.resolve(hostname, 'PTR');
+    }
+
+    resolveSoa(hostname) {
+        return this.resolve(hostname, 'SOA');
+    }
+
+    resolveSrv(hostname) {
+        return this.resolve(hostname, 'SRV');
+    }
+
+    resolveTxt(hostname) {
+        return this.resolve(hostname, 'TXT');
+    }
+
+    /** The host names of `ip` from its PTR records. */
+    reverse(ip) {
+        const name = reverseName(`${ip}`);
+        if (name === null) {
+            return Promise.reject(new ERR_INVALID_ARG_VALUE('ip', ip, 'is not a valid IP address'));
+        }
+        return this.#query(name, 'PTR', 'getHostByAddr', `${ip}`).then((records) => records.map((r) => r.value));
+    }
+
+    #query(hostname, rrtype, syscall = SYSCALLS[rrtype], shown = hostname) {
+        const name = toQueryName(hostname).replace(/\.$/, '').toLowerCase();
+        const key = `${rrtype}:${name}`;
+        const cached = this.#cache?.get(key);
+        if (cached !== undefined) {
+            if (cached.expires > Date.now()) {
+                return cached.error
+                    ? Promise.reject(new DNSException(cached.error, syscall, shown))
+                    : Promise.resolve(cached.records);
+            }
+            this.#cache.delete(key);
+        }
+        return new Promise((resolve, reject) => {
+            const job = { reject, close: null, syscall, hostname: shown };
+            this.#pending.add(job);
+            this.#ask(job, name, rrtype).then((reply) => {
+                if (!this.#pending.delete(job)) {
+                    return;
+                }
+                const records = reply.answers.filter((r) => rrtype === 'ANY' || r.type === rrtype);
+                const code = reply.rcode !== 0 ? RCODES[reply.rcode] ?? BADRESP : records.length === 0 ? NODATA : null;
+                if (code !== null) {
+                    if (code === NOTFOUND || code === NODATA) {
+                        this.#store(key, reply.authority.map((r) => Math.min(r.ttl, r.minttl)), { error: code });
+                    }
+                    reject(new DNSException(code, syscall, shown));
+                } else {
+                    this.#store(key, records.map((r) => r.ttl), { records });
+                    resolve(records);
+                }
+            }, (e) => {
+                if (this.#pending.delete(job)) {
+                    reject(e instanceof DNSException
+                        ? new DNSException(e.code, syscall, shown)
+                        : new DNSException(isRefused(e) ? CONNREFUSED : BADRESP, syscall, shown));
+                }
+            });
+        });
+    }
+
+    #store(key, ttls, entry) {
+        if (this.#cache === null || ttls.length === 0) {
+            return;
+        }
+        const ttl = Math.min(Math.min(...ttls), this.#maxTtl);
+        if (ttl > 0) {
+            entry.expires = Date.now() + ttl * 1000;
+            this.#cache.set(key, entry);
+        }
+    }
+
+    // Tries the servers in turn. Timeouts and refused connections move on to
+    // the next try, answers other than SERVFAIL, NOTIMP and REFUSED are
+    // final.
+    async #ask(job, name, rrtype) {
+        let query;
+        const id = Math.floor(Math.random() * 0x10000);
+        try {
+            query = encodeQuery(id, name, rrtype);
+        } catch (e) {
+            throw new DNSException(e.code ?? BADNAME);
+        }
+        let last = new DNSException(TIMEOUT);
+        for (let attempt = 0; attempt < this.#tries; attempt++) {
+            for (const server of this.#currentServers()) {
+                if (!this.#pending.has(job)) {
+                    return null;
+                }
+                try {
+                    let reply = this.#tcp ? null : await exchangeUdp(job, server, query, id, this.#timeout);
+                    if (reply === null || reply.truncated) {
+                        reply = await exchangeTcp(job, server, query, this.#timeout);
+                    }
+                    if (reply.rcode === 2 || reply.rcode === 4 || reply.rcode === 5) {
+                        last = new DNSException(RCODES[reply.rcode]);
+                        continue;
+                    }
+                    return reply;
+                } catch (e) {
+                    if (e instanceof DNSException) {
+                        last = e;
+                    } else if (isTimeout(e)) {
+                        last = new DNSException(TIMEOUT);
+                    } else if (isRefused(e)) {
+                        last = new DNSException(CONNREFUSED);
+                    } else {
+                        throw e;
+                    }
+                }
+            }
+        }
+        throw last;
+    }
+}
+
+export const defaultResolver = new Resolver();
+
+function lookupFamily(family) {
+    if (family === 'IPv4') {
+        return 4;
+    }
+    if (family === 'IPv6') {
+        return 6;
+    }
+    if (family !== 0 && family !== 4 && family !== 6) {
+        throw new ERR_INVALID_ARG_VALUE('options.family', family, 'must be one of: 0, 4, 6');
+    }
+    return family;
+}
+
+function hostLookup(hostname) {
+    let addrs;
+    try {
+        addrs = net.nsloopup(hostname, '0');
+    } catch {
+        addrs = [];
+    }
+    return addrs.map((addr) => {
+        const address = addr.startsWith('[') ? addr.slice(1, addr.lastIndexOf(']')) : addr.slice(0, addr.lastIndexOf(':'));
+        return { address, family: isIPv6(address) ? 6 : 4 };
+    });
+}
+
+async function dnsLookup(hostname, family, all) {
+    const found = [];
+    for (const [rrtype, fam] of [['A', 4], ['AAAA', 6]]) {
+        if (family !== 0 && family !== fam) {
+            continue;
+        }
+        try {
+            const addrs = await defaultResolver.resolve(hostname, rrtype);
+            found.push(...addrs.map((address) => ({ address, family: fam })));
+        } catch (e) {
+            if (e.code !== NODATA && e.code !== NOTFOUND) {
+                throw e;
+            }
+        }
+        if (found.length > 0 && !all) {
+            break;
+        }
+    }
+    return found;
+}
+
+/**
+ * `{ address, family }` for `hostname`, or all of them as an array when
+ * `options.all` is set. `options.family` is 0, 4 or 6. IP literals and
+ * `localhost` are answered without asking anyone.
+ */
+export async function lookup(hostname, options = {}) {
+    if (typeof options === 'number') {
+        options = { family: options };
+    }
+    if (hostname !== null && hostname !== undefined && typeof hostname !== 'string') {
+        throw new ERR_INVALID_ARG_TYPE('hostname', 'string', hostname);
+    }
+    const family = lookupFamily(options.family ?? 0);
+    const all = options.all === true;
+    let found;
+    if (!hostname) {
+        found = [];
+    } else if (isIP(hostname) !== 0) {
+        found = [{ address: hostname, family: isIP(hostname) }];
+    } else if (hostname.toLowerCase() === 'localhost' || hostname.toLowerCase().endsWith('.localhost')) {
+        found = [{ address: '127.0.0.1', family: 4 }, { address: '::1', family: 6 }];
+    } else if (defaultResolver.configured) {
+        found = await dnsLookup(hostname, family, all);
+    } else {
+        // let the caller's synchronous code finish before the blocking call
+        await Promise.resolve();
+        found = hostLookup(hostname);
+    }
+    found = found.filter((a) => family === 0 || a.family === family);
+    if (!hostname && found.length === 0) {
+        return all ? [] : { address: null, family: family === 6 ? 6 : 4 };
+    }
+    if (found.length === 0) {
+        throw new DNSException(NOTFOUND, 'getaddrinfo', hostname);
+    }
+    return all ? found : found[0];
+}
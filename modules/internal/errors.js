@@ -664,6 +664,9 @@ export const ERR_FS_CP_SOCKET = makeSystemErrorWithCode('ERR_FS_CP_SOCKET', 'Can
 export const ERR_FS_CP_SYMLINK_TO_SUBDIRECTORY = makeSystemErrorWithCode('ERR_FS_CP_SYMLINK_TO_SUBDIRECTORY',
     'Cannot overwrite symlink in subdirectory of self');
 export const ERR_FS_CP_UNKNOWN = makeSystemErrorWithCode('ERR_FS_CP_UNKNOWN', 'Cannot copy an unknown file type');
+export const ERR_FS_LOCKED = makeSystemErrorWithCode('ERR_FS_LOCKED', 'Lock file is held');
+export const ERR_FS_LOCK_COMPROMISED = makeSystemErrorWithCode('ERR_FS_LOCK_COMPROMISED',
+    'Lock file was taken over');
 
 /**
  * 
@@ -790,6 +793,13 @@ export class ERR_DIR_CONCURRENT_OPERATION extends Error {
     }
 }
 
+export class ERR_INVALID_STATE extends Error {
+    constructor(x) {
+        super(`Invalid state: ${x}`);
+        this.code = "ERR_INVALID_STATE";
+    }
+}
+
 export class ERR_FS_FILE_TOO_LARGE extends RangeError {
     constructor(x) {
         super(
@@ -0,0 +1,1532 @@
+// WHATWG fetch on top of `wasi_net`/`wasi_tls` and the `wasi_http` parser.
+// Bodies are exposed as `ReadableStream`s that pull from the socket, so a
+// response can be consumed incrementally or all at once.
+
+import * as net from 'wasi_net'
+import * as httpx from 'wasi_http'
+import process from 'process'
+import { URL, URLSearchParams } from 'whatwg_url'
+import { TextDecoder, TextEncoder } from 'util'
+import { ReadableStream, isDisturbed } from 'stream/web'
+import { AbortController, AbortSignal, DOMException } from 'internal/abort_controller'
+import { Agent, globalAgent } from 'internal/http_agent'
+import { ZlibStream } from '_node:zlib'
+
+const encoder = new TextEncoder();
+
+const NULL_BODY_STATUS = [101, 103, 204, 205, 304];
+const REDIRECT_STATUS = [301, 302, 303, 307, 308];
+const FORBIDDEN_METHODS = ['CONNECT', 'TRACE', 'TRACK'];
+const NORMALIZED_METHODS = ['DELETE', 'GET', 'HEAD', 'OPTIONS', 'POST', 'PUT'];
+const MAX_REDIRECTS = 20;
+
+const HEADER_NAME = /^[!#$%&'*+\-.^_`|~0-9A-Za-z]+$/;
+const HEADER_VALUE_INVALID = /[\0\r\n]/;
+
+function toUint8Array(chunk) {
+    if (typeof chunk === 'string') {
+        return encoder.encode(chunk);
+    }
+    if (chunk instanceof ArrayBuffer) {
+        return new Uint8Array(chunk);
+    }
+    if (ArrayBuffer.isView(chunk)) {
+        return new Uint8Array(chunk.buffer, chunk.byteOffset, chunk.byteLength);
+    }
+    throw new TypeError('body chunks must be strings, ArrayBuffers or ArrayBuffer views');
+}
+
+function toArrayBuffer(u8) {
+    if (u8.byteOffset === 0 && u8.byteLength === u8.buffer.byteLength) {
+        return u8.buffer;
+    }
+    return u8.buffer.slice(u8.byteOffset, u8.byteOffset + u8.byteLength);
+}
+
+function concat(chunks) {
+    let len = 0;
+    for (const c of chunks) {
+        len += c.byteLength;
+    }
+    const out = new Uint8Array(len);
+    let pos = 0;
+    for (const c of chunks) {
+        out.set(c, pos);
+        pos += c.byteLength;
+    }
+    return out;
+}
+
+// ---------------------------------------------------------------------------
+// Headers
+
+function normalizeValue(value) {
+    return `${value}`.replace(/^[\t\n\r ]+|[\t\n\r ]+$/g, '');
+}
+
+function validateHeader(name, value) {
+    if (!HEADER_NAME.test(name)) {
+        throw new TypeError(`Header name must be a valid HTTP token ["${name}"]`);
+    }
+    if (HEADER_VALUE_INVALID.test(value)) {
+        throw new TypeError(`Invalid value for header "${name}"`);
+    }
+}
+
+export class Headers {
+    #list = [];
+    #guard = 'none';
+
+    constructor(init = undefined) {
+        if (init === undefined || init === null) {
+            return;
+        }
+        if (init instanceof Headers) {
+            for (const [name, value] of init.#list) {
+                this.#list.push([name, value]);
+            }
+        } else if (typeof init === 'object' && typeof init[Symbol.iterator] === 'function') {
+            for (const pair of init) {
+                const entry = [...pair];
+                if (entry.length !== 2) {
+                    throw new TypeError('Header pairs must contain exactly two items');
+                }
+                this.append(entry[0], entry[1]);
+            }
+        } else if (typeof init === 'object') {
+            for (const name of Object.keys(init)) {
+                this.append(name, init[name]);
+            }
+        } else {
+            throw new TypeError('Headers init must be an object or an iterable');
+        }
+    }
+
+    #checkMutable() {
+        if (this.#guard === 'immutable') {
+            throw new TypeError('Headers are immutable');
+        }
+    }
+
+    append(name, value) {
+        this.#checkMutable();
+        name = `${name}`;
+        value = normalizeValue(value);
+        validateHeader(name, value);
+        this.#list.push([name.toLowerCase(), value]);
+    }
+
+    delete(name) {
+        this.#checkMutable();
+        name = `${name}`.toLowerCase();
+        this.#list = this.#list.filter(([n]) => n !== name);
+    }
+
+    get(name) {
+        name = `${name}`.toLowerCase();
+        const values = this.#list.filter(([n]) => n === name).map(([, v]) => v);
+        if (values.length === 0) {
+            return null;
+        }
+        return values.join(', ');
+    }
+
+    getSetCookie() {
+        return this.#list.filter(([n]) => n === 'set-cookie').map(([, v]) => v);
+    }
+
+    has(name) {
+        name = `${name}`.toLowerCase();
+        return this.#list.some(([n]) => n === name);
+    }
+
+    set(name, value) {
+        this.#checkMutable();
+        name = `${name}`;
+        value = normalizeValue(value);
+        validateHeader(name, value);
+        const lower = name.toLowerCase();
+        const i = this.#list.findIndex(([n]) => n === lower);
+        if (i < 0) {
+            this.#list.push([lower, value]);
+        } else {
+            this.#list[i][1] = value;
+            this.#list = this.#list.filter(([n], j) => j <= i || n !== lower);
+        }
+    }
+
+    forEach(callback, thisArg = undefined) {
+        for (const [name, value] of this) {
+            callback.call(thisArg, value, name, this);
+        }
+    }
+
+    *entries() {
+        const names = [...new Set(this.#list.map(([n]) => n))].sort();
+        for (const name of names) {
+            if (name === 'set-cookie') {
+                for (const value of this.getSetCookie()) {
+                    yield [name, value];
+                }
+            } else {
+                yield [name, this.get(name)];
+            }
+        }
+    }
+
+    *keys() {
+        for (const [name] of this.entries()) {
+            yield name;
+        }
+    }
+
+    *values() {
+        for (const [, value] of this.entries()) {
+            yield value;
+        }
+    }
+
+    [Symbol.iterator]() {
+        return this.entries();
+    }
+
+    get [Symbol.toStringTag]() {
+        return 'Headers';
+    }
+
+    static _setGuard(headers, guard) {
+        headers.#guard = guard;
+        return headers;
+    }
+
+    // raw (name, value) pairs in insertion order, used to serialize requests
+    static _list(headers) {
+        return headers.#list;
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Blob, File and FormData
+
+export class Blob {
+    #bytes;
+    // set instead of #bytes for a spilled upload: the file and the byte range
+    #file = null;
+    #type;
+
+    constructor(parts = [], options = {}) {
+        const chunks = [];
+        for (const part of parts) {
+            if (part instanceof Blob) {
+                chunks.push(part.#data());
+            } else {
+                chunks.push(toUint8Array(typeof part === 'string' || part instanceof ArrayBuffer || ArrayBuffer.isView(part) ? part : `${part}`));
+            }
+        }
+        this.#bytes = concat(chunks);
+        const type = `${options?.type ?? ''}`;
+        this.#type = /[^ -~]/.test(type) ? '' : type.toLowerCase();
+    }
+
+    // Makes `blob` read the first `size` bytes of the file at `path`.
+    static _spilled(blob, path, size) {
+        blob.#bytes = null;
+        blob.#file = { path, start: 0, end: size };
+        return blob;
+    }
+
+    #data() {
+        if (this.#file === null) {
+            return this.#bytes;
+        }
+        const { path, start, end } = this.#file;
+        return new Uint8Array(httpx.readUpload(path, start, end));
+    }
+
+    get size() {
+        return this.#file === null ? this.#bytes.byteLength : this.#file.end - this.#file.start;
+    }
+
+    get type() {
+        return this.#type;
+    }
+
+    async arrayBuffer() {
+        return toArrayBuffer(this.#file === null ? this.#bytes.slice() : this.#data());
+    }
+
+    async bytes() {
+        return this.#file === null ? this.#bytes.slice() : this.#data();
+    }
+
+    async text() {
+        return new TextDecoder().decode(this.#data());
+    }
+
+    slice(start = 0, end = this.size, type = '') {
+        const blob = new Blob([], { type });
+        if (this.#file === null) {
+            blob.#bytes = this.#bytes.slice(start, end);
+            return blob;
+        }
+        const size = this.size;
+        const relative = (n) => n < 0 ? Math.max(size + n, 0) : Math.min(n, size);
+        const from = relative(start);
+        const to = Math.max(relative(end), from);
+        const file = this.#file;
+        blob.#bytes = null;
+        blob.#file = { path: file.path, start: file.start + from, end: file.start + to };
+        return blob;
+    }
+
+    stream() {
+        if (this.#file !== null) {
+            // read spilled files piece by piece
+            const { path, end } = this.#file;
+            let pos = this.#file.start;
+            return new ReadableStream({
+                pull(controller) {
+                    if (pos >= end) {
+                        controller.close();
+                        return;
+                    }
+                    const next = Math.min(pos + 65536, end);
+                    controller.enqueue(new Uint8Array(httpx.readUpload(path, pos, next)));
+                    pos = next;
+                },
+            });
+        }
+        const bytes = this.#bytes;
+        return new ReadableStream({
+            start(controller) {
+                if (bytes.byteLength > 0) {
+                    controller.enqueue(bytes.slice());
+                }
+                controller.close();
+            },
+        });
+    }
+
+    get [Symbol.toStringTag]() {
+        return 'Blob';
+    }
+}
+
+export class File extends Blob {
+    #name;
+    #lastModified;
+
+    constructor(parts, name, options = {}) {
+        if (arguments.length < 2) {
+            throw new TypeError('File constructor needs at least 2 arguments');
+        }
+        super(parts, options);
+        this.#name = `${name}`;
+        this.#lastModified = options?.lastModified ?? Date.now();
+    }
+
+    get name() {
+        return this.#name;
+    }
+
+    get lastModified() {
+        return this.#lastModified;
+    }
+
+    get [Symbol.toStringTag]() {
+        return 'File';
+    }
+}
+
+function toFormEntry(name, value, filename) {
+    name = `${name}`;
+    if (value instanceof Blob) {
+        if (!(value instanceof File) || filename !== undefined) {
+            value = new File([value], filename ?? (value instanceof File ? value.name : 'blob'), { type: value.type });
+        }
+        return [name, value];
+    }
+    return [name, `${value}`];
+}
+
+export class FormData {
+    #entries = [];
+
+    constructor(form = undefined) {
+        if (form !== undefined) {
+            throw new TypeError('FormData does not support form elements');
+        }
+    }
+
+    append(name, value, filename = undefined) {
+        this.#entries.push(toFormEntry(name, value, filename));
+    }
+
+    delete(name) {
+        name = `${name}`;
+        this.#entries = this.#entries.filter(([n]) => n !== name);
+    }
+
+    get(name) {
+        name = `${name}`;
+        return this.#entries.find(([n]) => n === name)?.[1] ?? null;
+    }
+
+    getAll(name) {
+        name = `${name}`;
+        return this.#entries.filter(([n]) => n === name).map(([, v]) => v);
+    }
+
+    has(name) {
+        name = `${name}`;
+        return this.#entries.some(([n]) => n === name);
+    }
+
+    set(name, value, filename = undefined) {
+        const entry = toFormEntry(name, value, filename);
+        const i = this.#entries.findIndex(([n]) => n === entry[0]);
+        if (i < 0) {
+            this.#entries.push(entry);
+        } else {
+            this.#entries[i] = entry;
+            this.#entries = this.#entries.filter(([n], j) => j <= i || n !== entry[0]);
+        }
+    }
+
+    forEach(callback, thisArg = undefined) {
+        for (const [name, value] of this) {
+            callback.call(thisArg, value, name, this);
+        }
+    }
+
+    *entries() {
+        yield* this.#entries.slice();
+    }
+
+    *keys() {
+        for (const [name] of this.#entries.slice()) {
+            yield name;
+        }
+    }
+
+    *values() {
+        for (const [, value] of this.#entries.slice()) {
+            yield value;
+        }
+    }
+
+    [Symbol.iterator]() {
+        return this.entries();
+    }
+
+    get [Symbol.toStringTag]() {
+        return 'FormData';
+    }
+}
+
+function escapeFormName(name) {
+    return name.replace(/\r\n?|\n/g, '\r\n').replace(/\n/g, '%0A').replace(/\r/g, '%0D').replace(/"/g, '%22');
+}
+
+async function encodeMultipart(form, boundary) {
+    const chunks = [];
+    for (const [name, value] of form) {
+        let head = `--${boundary}\r\nContent-Disposition: form-data; name="${escapeFormName(name)}"`;
+        if (typeof value === 'string') {
+            chunks.push(encoder.encode(`${head}\r\n\r\n${value.replace(/\r(?!\n)|(?<!\r)\n/g, '\r\n')}\r\n`));
+        } else {
+            head += `; filename="${escapeFormName(value.name)}"\r\nContent-Type: ${value.type || 'application/octet-stream'}\r\n\r\n`;
+            chunks.push(encoder.encode(head), await value.bytes(), encoder.encode('\r\n'));
+        }
+    }
+    chunks.push(encoder.encode(`--${boundary}--\r\n`));
+    return concat(chunks);
+}
+
+// ---------------------------------------------------------------------------
+// Body
+
+// Turns a BodyInit into `{ stream, source, type, length }`. `source` keeps the
+// original bytes when there are any, which is what lets a redirected request
+// re-send its body.
+function extractBody(object) {
+    if (object instanceof ReadableStream) {
+        if (object.locked || isDisturbed(object)) {
+            throw new TypeError('ReadableStream body is locked or disturbed');
+        }
+        return { stream: object, source: null, type: null, length: null };
+    }
+    let source;
+    let type = null;
+    if (typeof object === 'string') {
+        source = encoder.encode(object);
+        type = 'text/plain;charset=UTF-8';
+    } else if (object instanceof URLSearchParams) {
+        source = encoder.encode(object.toString());
+        type = 'application/x-www-form-urlencoded;charset=UTF-8';
+    } else if (object instanceof ArrayBuffer || ArrayBuffer.isView(object)) {
+        source = toUint8Array(object).slice();
+    } else if (object instanceof Blob) {
+        return {
+            stream: object.stream(),
+            source: object,
+            type: object.type || null,
+            length: object.size,
+        };
+    } else if (object instanceof FormData) {
+        const boundary = `----formdata-wasmedge-${Math.random().toString(16).slice(2)}${Date.now().toString(16)}`;
+        const pending = encodeMultipart(object, boundary);
+        return {
+            stream: new ReadableStream({
+                async pull(controller) {
+                    controller.enqueue(await pending);
+                    controller.close();
+                },
+            }, { highWaterMark: 0 }),
+            source: pending,
+            type: `multipart/form-data; boundary=${boundary}`,
+            length: null,
+        };
+    } else if (typeof object?.[Symbol.asyncIterator] === 'function') {
+        return { stream: ReadableStream.from(object), source: null, type: null, length: null };
+    } else {
+        source = encoder.encode(`${object}`);
+        type = 'text/plain;charset=UTF-8';
+    }
+    return { stream: bytesStream(source), source, type, length: source.byteLength };
+}
+
+function bytesStream(bytes) {
+    return new ReadableStream({
+        start(controller) {
+            if (bytes.byteLength > 0) {
+                controller.enqueue(bytes);
+            }
+            controller.close();
+        },
+    });
+}
+
+async function sourceBytes(source) {
+    if (source instanceof Blob) {
+        return await source.bytes();
+    }
+    return await source;
+}
+
+async function consumeBody(body) {
+    if (body === null) {
+        return new Uint8Array(0);
+    }
+    const reader = body.stream.getReader();
+    const chunks = [];
+    while (true) {
+        const { value, done } = await reader.read();
+        if (done) {
+            break;
+        }
+        chunks.push(toUint8Array(value));
+    }
+    return concat(chunks);
+}
+
+function mimeEssence(type) {
+    return (type ?? '').split(';')[0].trim().toLowerCase();
+}
+
+class Body {
+    #body = null;
+
+    _initBody(body) {
+        this.#body = body;
+    }
+
+    _getBody() {
+        return this.#body;
+    }
+
+    get body() {
+        return this.#body?.stream ?? null;
+    }
+
+    get bodyUsed() {
+        return this.#body !== null && isDisturbed(this.#body.stream);
+    }
+
+    async #consume() {
+        if (this.bodyUsed) {
+            throw new TypeError('Body has already been consumed');
+        }
+        if (this.#body?.stream.locked) {
+            throw new TypeError('Body is locked');
+        }
+        return await consumeBody(this.#body);
+    }
+
+    async arrayBuffer() {
+        return toArrayBuffer(await this.#consume());
+    }
+
+    async bytes() {
+        return await this.#consume();
+    }
+
+    async blob() {
+        const bytes = await this.#consume();
+        return new Blob([bytes], { type: this.headers.get('content-type') ?? '' });
+    }
+
+    async text() {
+        return new TextDecoder().decode(await this.#consume());
+    }
+
+    async json() {
+        return JSON.parse(await this.text());
+    }
+
+    // `options` are those of `parseFormData()`.
+    async formData(options = undefined) {
+        if (this.bodyUsed) {
+            throw new TypeError('Body has already been consumed');
+        }
+        if (this.#body?.stream.locked) {
+            throw new TypeError('Body is locked');
+        }
+        return await parseFormData(this.headers.get('content-type'), this.#body?.stream ?? [], options);
+    }
+}
+
+/**
+ * Parses an `application/x-www-form-urlencoded` or `multipart/form-data`
+ * body from `chunks`, an async iterable of byte chunks, into a FormData.
+ * Multipart parts are parsed as they arrive; file parts bigger than
+ * `options.spillThreshold` bytes are written to `options.uploadDir` and read
+ * back from there. Those files are not removed, their `filepath` property
+ * tells where they are.
+ */
+export async function parseFormData(contentType, chunks, options = {}) {
+    const form = new FormData();
+    const essence = mimeEssence(contentType);
+    if (essence === 'application/x-www-form-urlencoded') {
+        const body = [];
+        for await (const chunk of chunks) {
+            body.push(toUint8Array(chunk));
+        }
+        for (const [name, value] of httpx.parseUrlencoded(toArrayBuffer(concat(body)))) {
+            form.append(name, value);
+        }
+        return form;
+    }
+    if (essence !== 'multipart/form-data') {
+        throw new TypeError(`Could not parse content as FormData, content-type is ${contentType}`);
+    }
+
+    let fields;
+    try {
+        const parser = new httpx.MultipartParser(contentType, options ?? {});
+        for await (const chunk of chunks) {
+            parser.push(toArrayBuffer(toUint8Array(chunk)));
+        }
+        fields = parser.finish();
+    } catch (e) {
+        throw new TypeError(`Could not parse content as FormData: ${e.message}`, { cause: e });
+    }
+    for (const field of fields) {
+        if (field.filename === undefined) {
+            form.append(field.name, new TextDecoder().decode(field.data));
+            continue;
+        }
+        const type = field.type ?? 'text/plain';
+        let file;
+        if (field.path === undefined) {
+            file = new File([field.data], field.filename, { type });
+        } else {
+            file = Blob._spilled(new File([], field.filename, { type }), field.path, field.size);
+            file.filepath = field.path;
+        }
+        form.append(field.name, file);
+    }
+    return form;
+}
+
+// ---------------------------------------------------------------------------
+// Request
+
+function normalizeMethod(method) {
+    method = `${method}`;
+    if (!HEADER_NAME.test(method)) {
+        throw new TypeError(`'${method}' is not a valid HTTP method`);
+    }
+    const upper = method.toUpperCase();
+    if (FORBIDDEN_METHODS.includes(upper)) {
+        throw new TypeError(`'${method}' HTTP method is unsupported`);
+    }
+    return NORMALIZED_METHODS.includes(upper) ? upper : method;
+}
+
+export class Request extends Body {
+    #url;
+    #method = 'GET';
+    #headers;
+    #signal;
+    #redirect = 'follow';
+    #credentials = 'same-origin';
+    #init = {};
+
+    constructor(input, init = {}) {
+        super();
+        init = init ?? {};
+        let inputRequest = null;
+        if (input instanceof Request) {
+            inputRequest = input;
+            this.#url = input.#url;
+            this.#method = input.#method;
+            this.#redirect = input.#redirect;
+            this.#credentials = input.#credentials;
+            this.#init = { ...input.#init };
+        } else {
+            const url = new URL(`${input}`);
+            if (url.username !== '' || url.password !== '') {
+                throw new TypeError(`${url} is an url with embedded credentials.`);
+            }
+            this.#url = url.href;
+        }
+
+        if (init.method !== undefined) {
+            this.#method = normalizeMethod(init.method);
+        }
+        if (init.redirect !== undefined) {
+            if (!['follow', 'error', 'manual'].includes(init.redirect)) {
+                throw new TypeError(`'${init.redirect}' is not a valid redirect mode`);
+            }
+            this.#redirect = init.redirect;
+        }
+        if (init.credentials !== undefined) {
+            if (!['omit', 'same-origin', 'include'].includes(init.credentials)) {
+                throw new TypeError(`'${init.credentials}' is not a valid credentials mode`);
+            }
+            this.#credentials = init.credentials;
+        }
+        // non standard options understood by this fetch
+        for (const key of ['timeout', 'ca', 'servername', 'agent', 'decompress', 'cookieJar']) {
+            if (init[key] !== undefined) {
+                this.#init[key] = init[key];
+            }
+        }
+
+        const controller = new AbortController();
+        const signal = init.signal !== undefined ? init.signal : inputRequest?.signal;
+        if (signal) {
+            if (signal.aborted) {
+                controller.abort(signal.reason);
+            } else {
+                signal.addEventListener('abort', () => controller.abort(signal.reason), { once: true });
+            }
+        }
+        this.#signal = controller.signal;
+
+        this.#headers = new Headers(init.headers ?? inputRequest?.headers);
+
+        let inputBody = init.body !== undefined ? init.body : inputRequest?._getBody() ?? null;
+        if (inputBody !== null && inputBody !== undefined && (this.#method === 'GET' || this.#method === 'HEAD')) {
+            throw new TypeError('Request with GET/HEAD method cannot have body');
+        }
+        let body = null;
+        if (init.body !== undefined && init.body !== null) {
+            body = extractBody(init.body);
+            if (body.type !== null && !this.#headers.has('content-type')) {
+                this.#headers.append('content-type', body.type);
+            }
+        } else if (init.body === undefined && inputRequest !== null && inputRequest._getBody() !== null) {
+            if (inputRequest.bodyUsed) {
+                throw new TypeError('Cannot construct a Request with a Request whose body has been used');
+            }
+            // take over the input's body, as the spec's "transfer" does
+            body = inputRequest._getBody();
+            inputRequest._initBody({ ...body, stream: disturbedStream() });
+        }
+        this._initBody(body);
+    }
+
+    get url() {
+        return this.#url;
+    }
+
+    get method() {
+        return this.#method;
+    }
+
+    get headers() {
+        return this.#headers;
+    }
+
+    get signal() {
+        return this.#signal;
+    }
+
+    get redirect() {
+        return this.#redirect;
+    }
+
+    get credentials() {
+        return this.#credentials;
+    }
+
+    get timeout() {
+        return this.#init.timeout;
+    }
+
+    get ca() {
+        return this.#init.ca;
+    }
+
+    get servername() {
+        return this.#init.servername;
+    }
+
+    get agent() {
+        return this.#init.agent;
+    }
+
+    get decompress() {
+        return this.#init.decompress;
+    }
+
+    get cookieJar() {
+        return this.#init.cookieJar;
+    }
+
+    clone() {
+        if (this.bodyUsed) {
+            throw new TypeError('Cannot clone a Request whose body has been used');
+        }
+        const body = this._getBody();
+        const init = {
+            method: this.#method,
+            headers: this.#headers,
+            signal: this.#signal,
+            redirect: this.#redirect,
+            credentials: this.#credentials,
+            ...this.#init,
+        };
+        const request = new Request(this.#url, init);
+        if (body !== null) {
+            const [a, b] = body.stream.tee();
+            this._initBody({ ...body, stream: a });
+            request._initBody({ ...body, stream: b });
+        }
+        return request;
+    }
+
+    get [Symbol.toStringTag]() {
+        return 'Request';
+    }
+}
+
+function disturbedStream() {
+    const stream = new ReadableStream({ start(c) { c.close(); } });
+    stream.getReader().read();
+    return stream;
+}
+
+// ---------------------------------------------------------------------------
+// Response
+
+export class Response extends Body {
+    #status = 200;
+    #statusText = '';
+    #headers;
+    #type = 'default';
+    #urlList = [];
+
+    constructor(body = null, init = {}) {
+        super();
+        init = init ?? {};
+        const status = init.status ?? 200;
+        if (!Number.isInteger(status) || status < 200 || status > 599) {
+            throw new RangeError(`init["status"] must be in the range of 200 to 599, inclusive.`);
+        }
+        const statusText = `${init.statusText ?? ''}`;
+        if (/[^\t -~\u0080-\u00ff]/.test(statusText)) {
+            throw new TypeError('Invalid statusText');
+        }
+        this.#status = status;
+        this.#statusText = statusText;
+        this.#headers = new Headers(init.headers);
+        if (body !== null && body !== undefined) {
+            if (NULL_BODY_STATUS.includes(status)) {
+                throw new TypeError(`Response with status ${status} cannot have a body`);
+            }
+            const extracted = extractBody(body);
+            if (extracted.type !== null && !this.#headers.has('content-type')) {
+                this.#headers.append('content-type', extracted.type);
+            }
+            this._initBody(extracted);
+        }
+    }
+
+    static error() {
+        const response = new Response(null, { status: 200 });
+        response.#status = 0;
+        response.#type = 'error';
+        Headers._setGuard(response.#headers, 'immutable');
+        return response;
+    }
+
+    static redirect(url, status = 302) {
+        const parsed = new URL(`${url}`);
+        if (!REDIRECT_STATUS.includes(status)) {
+            throw new RangeError(`Invalid status code ${status}`);
+        }
+        const response = new Response(null, { status, headers: { location: parsed.href } });
+        Headers._setGuard(response.#headers, 'immutable');
+        return response;
+    }
+
+    static json(data, init = {}) {
+        const text = JSON.stringify(data);
+        if (text === undefined) {
+            throw new TypeError('Value is not JSON serializable');
+        }
+        const headers = new Headers(init?.headers);
+        if (!headers.has('content-type')) {
+            headers.set('content-type', 'application/json');
+        }
+        return new Response(text, { ...init, headers });
+    }
+
+    // builds the Response handed out by fetch, the status may be outside of
+    // what the constructor accepts (e.g. 1xx or 0)
+    static _fromNetwork({ status, statusText, headers, stream, urlList }) {
+        const response = new Response(null, { headers });
+        response.#status = status;
+        response.#statusText = statusText;
+        response.#type = 'basic';
+        response.#urlList = urlList;
+        Headers._setGuard(response.#headers, 'immutable');
+        if (stream !== null) {
+            response._initBody({ stream, source: null, type: null, length: null });
+        }
+        return response;
+    }
+
+    get type() {
+        return this.#type;
+    }
+
+    get url() {
+        const url = this.#urlList[this.#urlList.length - 1];
+        return url === undefined ? '' : url.replace(/#.*$/, '');
+    }
+
+    get redirected() {
+        return this.#urlList.length > 1;
+    }
+
+    get status() {
+        return this.#status;
+    }
+
+    get ok() {
+        return this.#status >= 200 && this.#status < 300;
+    }
+
+    get statusText() {
+        return this.#statusText;
+    }
+
+    get headers() {
+        return this.#headers;
+    }
+
+    clone() {
+        if (this.bodyUsed) {
+            throw new TypeError('Cannot clone a Response whose body has been used');
+        }
+        const response = new Response(null, { headers: this.#headers });
+        response.#status = this.#status;
+        response.#statusText = this.#statusText;
+        response.#type = this.#type;
+        response.#urlList = this.#urlList.slice();
+        const body = this._getBody();
+        if (body !== null) {
+            const [a, b] = body.stream.tee();
+            this._initBody({ ...body, stream: a });
+            response._initBody({ ...body, stream: b });
+        }
+        return response;
+    }
+
+    get [Symbol.toStringTag]() {
+        return 'Response';
+    }
+}
+
+// ---------------------------------------------------------------------------
+// fetch
+
+function defaultPort(url) {
+    return url.port === '' ? (url.protocol === 'https:' ? 443 : 80) : Number(url.port);
+}
+
+async function connect(url, request) {
+    const port = defaultPort(url);
+    if (url.protocol === 'https:') {
+        if (!process.features.tls) {
+            throw new TypeError(`${url.href}: https is not supported, wasmedge_quickjs was built without the tls feature`);
+        }
+        const tls = await import('tls');
+        return await tls.rawConnect({
+            host: url.hostname,
+            port,
+            servername: request.servername,
+            ALPNProtocols: ['http/1.1'],
+            ca: request.ca,
+        });
+    }
+    const addr = net.nsloopup(url.hostname, `${port}`)[0];
+    if (addr === undefined) {
+        throw new TypeError(`getaddrinfo ENOTFOUND ${url.hostname}`);
+    }
+    return await net.WasiTcpConn.connect(addr);
+}
+
+function writeAll(conn, data) {
+    return new Promise((resolve, reject) => {
+        conn.write(data, (e) => e ? reject(e) : resolve());
+    });
+}
+
+async function sendRequest(conn, url, request, body, keepAlive) {
+    const headers = new Headers(request.headers);
+    if (!headers.has('host')) {
+        headers.set('host', url.host);
+    }
+    if (!headers.has('accept')) {
+        headers.set('accept', '*/*');
+    }
+    if (!headers.has('accept-encoding') && request.decompress !== false) {
+        headers.set('accept-encoding', 'gzip, deflate, br');
+    }
+    if (!headers.has('user-agent')) {
+        headers.set('user-agent', `wasmedge_quickjs/${process.version}`);
+    }
+    headers.set('connection', keepAlive ? 'keep-alive' : 'close');
+    let bytes = null;
+    if (body !== null && body.source !== null) {
+        bytes = await sourceBytes(body.source);
+        headers.set('content-length', `${bytes.byteLength}`);
+        headers.delete('transfer-encoding');
+    } else if (body !== null) {
+        headers.set('transfer-encoding', 'chunked');
+        headers.delete('content-length');
+    } else if (['POST', 'PUT', 'PATCH'].includes(request.method)) {
+        headers.set('content-length', '0');
+    }
+
+    let head = `${request.method} ${url.pathname}${url.search} HTTP/1.1\r\n`;
+    for (const [name, value] of Headers._list(headers)) {
+        head += `${name}: ${value}\r\n`;
+    }
+    head += '\r\n';
+    conn.write(head);
+
+    if (bytes !== null) {
+        if (bytes.byteLength > 0) {
+            await writeAll(conn, toArrayBuffer(bytes));
+        }
+    } else if (body !== null) {
+        const reader = body.stream.getReader();
+        while (true) {
+            const { value, done } = await reader.read();
+            if (done) {
+                break;
+            }
+            const chunk = toUint8Array(value);
+            if (chunk.byteLength > 0) {
+                conn.write(`${chunk.byteLength.toString(16)}\r\n`);
+                conn.write(toArrayBuffer(chunk));
+                await writeAll(conn, '\r\n');
+            }
+        }
+        await writeAll(conn, '0\r\n\r\n');
+    }
+    await conn.flush();
+}
+
+async function readHead(conn, buf, head) {
+    while (true) {
+        const resp = buf.parseResponse({ head });
+        if (resp instanceof httpx.WasiResponse) {
+            // interim responses are skipped, the final one follows them
+            if (resp.status >= 100 && resp.status < 200 && resp.status !== 101) {
+                continue;
+            }
+            return resp;
+        }
+        if (resp instanceof Error) {
+            throw new TypeError(`Invalid response: ${resp.message}`);
+        }
+        const data = await conn.read();
+        if (data === undefined) {
+            if (buf.length === 0) {
+                return null;
+            }
+            throw new TypeError('Connection closed before a response was received');
+        }
+        buf.write(data);
+    }
+}
+
+// trailer fields of network responses, filled in once a chunked body is read
+const responseTrailers = new WeakMap();
+
+/**
+ * The trailer fields a response sent after its chunked body. They are empty
+ * until the whole body has been read.
+ */
+export function trailersOf(response) {
+    return responseTrailers.get(response) ?? new Headers();
+}
+
+// Yields the response body as Uint8Arrays according to its framing. Trailers
+// of a chunked body are appended to `trailers`.
+async function* readBody(conn, buf, framing, trailers) {
+    if (framing.kind === 'chunked') {
+        while (true) {
+            const chunk = buf.parseChunk();
+            if (chunk === undefined) {
+                const data = await conn.read();
+                if (data === undefined) {
+                    throw new TypeError('Connection closed in the middle of a chunked body');
+                }
+                buf.write(data);
+            } else if (chunk === null) {
+                const raw = buf.rawTrailers;
+                for (let i = 0; i + 1 < raw.length; i += 2) {
+                    trailers.append(raw[i], raw[i + 1]);
+                }
+                return;
+            } else if (chunk instanceof ArrayBuffer) {
+                yield new Uint8Array(chunk);
+            } else {
+                throw new TypeError(`Invalid chunked body: ${chunk.message}`);
+            }
+        }
+    }
+
+    // bytes past the body stay in `buf`, they belong to the next response
+    let remaining = framing.kind === 'length' ? framing.length : Infinity;
+    while (remaining > 0) {
+        const data = buf.take(remaining);
+        if (data === null) {
+            const next = await conn.read();
+            if (next === undefined) {
+                if (remaining !== Infinity) {
+                    throw new TypeError('Connection closed before the whole body was received');
+                }
+                return;
+            }
+            buf.write(next);
+            continue;
+        }
+        remaining -= data.byteLength;
+        yield new Uint8Array(data);
+    }
+}
+
+function bodyFraming(method, status, headers) {
+    if (method === 'HEAD' || NULL_BODY_STATUS.includes(status) || (status >= 100 && status < 200)) {
+        return { kind: 'none' };
+    }
+    const te = headers.get('transfer-encoding');
+    if (te !== null && te.toLowerCase().split(',').map((s) => s.trim()).pop() === 'chunked') {
+        return { kind: 'chunked' };
+    }
+    const cl = headers.get('content-length');
+    if (cl !== null) {
+        const values = new Set(cl.split(',').map((s) => s.trim()));
+        const length = Number([...values][0]);
+        if (values.size !== 1 || !/^\d+$/.test([...values][0]) || !Number.isSafeInteger(length)) {
+            throw new TypeError(`Invalid content-length: ${cl}`);
+        }
+        return { kind: 'length', length };
+    }
+    return { kind: 'eof' };
+}
+
+// `onDone(complete)` runs once, `complete` tells whether the whole body was
+// read so the connection can be reused.
+const DECODERS = {
+    'gzip': 'gunzip',
+    'x-gzip': 'gunzip',
+    'deflate': 'inflate',
+    'br': 'brotliDecompress',
+};
+
+/**
+ * The codecs undoing a `Content-Encoding` list, last applied first. Returns
+ * null for `identity` only or when one of the codings is unknown, the body
+ * is then passed on as it is.
+ */
+function contentDecoders(headers) {
+    const codings = (headers.get('content-encoding') ?? '')
+        .split(',')
+        .map((c) => c.trim().toLowerCase())
+        .filter((c) => c !== '' && c !== 'identity');
+    if (codings.length === 0 || codings.some((c) => DECODERS[c] === undefined)) {
+        return null;
+    }
+    return codings.reverse().map((c) => DECODERS[c]);
+}
+
+async function* decodeBody(chunks, modes) {
+    const decoders = modes.map((mode) => new ZlibStream(mode));
+    const pass = (bytes, last) => {
+        for (const decoder of decoders) {
+            const out = new Uint8Array(decoder.write(toArrayBuffer(bytes)));
+            bytes = last ? concat([out, new Uint8Array(decoder.finish())]) : out;
+        }
+        return bytes;
+    };
+    try {
+        for await (const chunk of chunks) {
+            const out = pass(chunk, false);
+            if (out.byteLength > 0) {
+                yield out;
+            }
+        }
+        const out = pass(new Uint8Array(0), true);
+        if (out.byteLength > 0) {
+            yield out;
+        }
+    } catch (e) {
+        if (e.code === 'Z_DATA_ERROR' || e.code === 'Z_BUF_ERROR') {
+            throw new TypeError(`fetch failed: invalid compressed body (${e.message})`);
+        }
+        throw e;
+    }
+}
+
+function responseStream(conn, buf, framing, signal, trailers, decoders, onDone) {
+    if (framing.kind === 'none' || (framing.kind === 'length' && framing.length === 0)) {
+        onDone(true);
+        return null;
+    }
+    let chunks = readBody(conn, buf, framing, trailers);
+    if (decoders !== null) {
+        chunks = decodeBody(chunks, decoders);
+    }
+    return new ReadableStream({
+        async pull(controller) {
+            let next;
+            try {
+                next = await chunks.next();
+            } catch (e) {
+                onDone(false);
+                throw signal.aborted ? signal.reason : e;
+            }
+            if (next.done) {
+                onDone(true);
+                controller.close();
+            } else {
+                controller.enqueue(next.value);
+            }
+        },
+        cancel() {
+            onDone(false);
+        },
+    }, { highWaterMark: 0 });
+}
+
+function canReuse(resp, headers, framing) {
+    if (framing.kind === 'eof') {
+        return false;
+    }
+    const connection = (headers.get('connection') ?? '').toLowerCase().split(',').map((s) => s.trim());
+    if (connection.includes('close')) {
+        return false;
+    }
+    return resp.version === 'HTTP/1.1' || connection.includes('keep-alive');
+}
+
+function keepAliveTimeout(headers) {
+    const m = /timeout=(\d+)/i.exec(headers.get('keep-alive') ?? '');
+    return m === null ? undefined : Number(m[1]) * 1000;
+}
+
+async function acquire(agent, options, state) {
+    const pending = agent.acquire(options);
+    try {
+        return await state.race(pending);
+    } catch (e) {
+        // hand back a connection that shows up after the fetch gave up on it
+        pending.then((pooled) => agent.release(pooled, false), () => { });
+        throw e;
+    }
+}
+
+async function httpFetch(url, request, body, state) {
+    const agent = state.agent;
+    const options = {
+        protocol: url.protocol,
+        host: url.hostname,
+        port: defaultPort(url),
+        servername: request.servername,
+        connect: () => connect(url, request),
+    };
+    // a streamed body cannot be replayed, so it never risks a stale connection
+    let pooled = await acquire(agent, { ...options, fresh: body !== null && body.source === null }, state);
+    while (true) {
+        state.pooled = pooled;
+        const { conn, buf } = pooled;
+        let resp;
+        try {
+            await state.race(sendRequest(conn, url, request, body, agent.keepAlive));
+            resp = await state.race(readHead(conn, buf, request.method === 'HEAD'));
+        } catch (e) {
+            if (state.aborted || !pooled.reused) {
+                throw e;
+            }
+            resp = null;
+        }
+        if (resp === null) {
+            state.close();
+            // the server dropped an idle keep-alive connection, retry once on
+            // a new one when the request can be sent again
+            if (pooled.reused && (body === null || body.source !== null)) {
+                pooled = await acquire(agent, { ...options, fresh: true }, state);
+                continue;
+            }
+            throw new TypeError('Connection closed before a response was received');
+        }
+
+        const headers = new Headers();
+        // raw pairs keep repeated fields such as set-cookie apart
+        const raw = resp.rawHeaders;
+        for (let i = 0; i + 1 < raw.length; i += 2) {
+            headers.append(raw[i], raw[i + 1]);
+        }
+        const framing = bodyFraming(request.method, resp.status, headers);
+        const reusable = canReuse(resp, headers, framing);
+        return { resp, headers, conn, buf, framing, reusable, idleTimeout: keepAliveTimeout(headers) };
+    }
+}
+
+/** The cookies `fetch` stores and sends unless told otherwise. */
+export const globalCookieJar = new httpx.CookieJar();
+
+// The request headers with the cookies of `jar` for `url`, unless the
+// caller set a Cookie header of their own.
+function withCookies(headers, jar, url) {
+    if (jar === null || headers.has('cookie')) {
+        return headers;
+    }
+    const cookie = jar.getCookieString(url.href);
+    if (cookie === '') {
+        return headers;
+    }
+    headers = new Headers(headers);
+    headers.set('cookie', cookie);
+    return headers;
+}
+
+function isSameOrigin(a, b) {
+    return a.protocol === b.protocol && a.host === b.host;
+}
+
+/**
+ * `fetch(input, init)` as in the WHATWG spec, minus CORS and caches. Besides
+ * the standard options `init` accepts `timeout` (milliseconds until the
+ * response headers arrive), `ca` (PEM string for https), `servername`
+ * (the name sent and verified for https instead of the url host), `agent` (an
+ * `http.Agent` to pool connections with, `false` for a one-off connection)
+ * and `decompress` (`false` keeps gzip, deflate and br bodies encoded).
+ *
+ * Cookies set by responses, redirects included, are kept in `cookieJar`
+ * (`globalCookieJar` by default) and sent with later requests that match
+ * them. `cookieJar: false` or `credentials: 'omit'` leave cookies alone.
+ */
+export async function fetch(input, init = undefined) {
+    let request = new Request(input, init);
+    const signal = request.signal;
+    signal.throwIfAborted();
+
+    let rejectAbort;
+    const aborted = new Promise((_, reject) => {
+        rejectAbort = reject;
+    });
+    aborted.catch(() => { });
+
+    const state = {
+        agent: request.agent === false ? new Agent() : request.agent ?? globalAgent,
+        pooled: undefined,
+        aborted: false,
+        race(p) {
+            return Promise.race([p, aborted]);
+        },
+        release(reusable, idleTimeout) {
+            if (this.pooled !== undefined) {
+                this.agent.release(this.pooled, reusable, idleTimeout);
+                this.pooled = undefined;
+            }
+        },
+        close() {
+            this.release(false);
+        },
+    };
+
+    const onAbort = () => {
+        state.aborted = true;
+        rejectAbort(signal.reason);
+        state.close();
+    };
+    signal.addEventListener('abort', onAbort, { once: true });
+
+    let timer;
+    if (request.timeout !== undefined) {
+        timer = setTimeout(() => {
+            state.aborted = true;
+            rejectAbort(new DOMException('The operation timed out.', 'TimeoutError'));
+            state.close();
+        }, request.timeout);
+    }
+    const done = () => {
+        if (timer !== undefined) {
+            clearTimeout(timer);
+            timer = undefined;
+        }
+    };
+
+    const jar = request.credentials === 'omit' || request.cookieJar === false
+        ? null
+        : request.cookieJar ?? globalCookieJar;
+    let url = new URL(request.url);
+    let method = request.method;
+    let headers = new Headers(request.headers);
+    let body = request._getBody();
+    const urlList = [url.href];
+
+    try {
+        for (let redirects = 0; ; redirects++) {
+            if (url.protocol !== 'http:' && url.protocol !== 'https:') {
+                throw new TypeError(`fetch failed: unsupported scheme ${url.protocol}`);
+            }
+            const current = { method, headers: withCookies(headers, jar, url), ca: request.ca, servername: request.servername, decompress: request.decompress };
+            const { resp, headers: respHeaders, conn, buf, framing, reusable, idleTimeout } = await httpFetch(url, current, body, state);
+            if (jar !== null) {
+                for (const cookie of respHeaders.getSetCookie()) {
+                    jar.setCookie(cookie, url.href);
+                }
+            }
+            const location = respHeaders.get('location');
+
+            if (REDIRECT_STATUS.includes(resp.status) && location !== null && request.redirect !== 'manual') {
+                // only an empty redirect body leaves the connection reusable
+                const empty = framing.kind === 'none' || (framing.kind === 'length' && framing.length === 0);
+                state.release(reusable && empty, idleTimeout);
+                if (request.redirect === 'error') {
+                    throw new TypeError(`fetch failed: unexpected redirect to ${location}`);
+                }
+                if (redirects >= MAX_REDIRECTS) {
+                    throw new TypeError('fetch failed: too many redirects');
+                }
+                const next = new URL(location, url);
+                if (body !== null && body.source === null && resp.status !== 303) {
+                    throw new TypeError('fetch failed: cannot follow a redirect with a streamed request body');
+                }
+                if (resp.status === 303 && method !== 'GET' && method !== 'HEAD'
+                    || (resp.status === 301 || resp.status === 302) && method === 'POST') {
+                    method = 'GET';
+                    body = null;
+                    headers = new Headers(headers);
+                    for (const name of ['content-type', 'content-length', 'content-encoding', 'content-language', 'content-location']) {
+                        headers.delete(name);
+                    }
+                }
+                if (!isSameOrigin(url, next)) {
+                    headers = new Headers(headers);
+                    headers.delete('authorization');
+                    headers.delete('cookie');
+                    headers.delete('proxy-authorization');
+                    headers.delete('host');
+                }
+                if (body !== null) {
+                    body = { ...body, stream: bytesStream(await sourceBytes(body.source)) };
+                }
+                url = next;
+                urlList.push(url.href);
+                continue;
+            }
+
+            done();
+            const trailers = new Headers();
+            const decoders = request.decompress === false ? null : contentDecoders(respHeaders);
+            const stream = responseStream(conn, buf, framing, signal, trailers, decoders, (complete) => {
+                signal.removeEventListener('abort', onAbort);
+                state.release(complete && reusable, idleTimeout);
+            });
+            if (stream !== null) {
+                // an abort after the headers arrived errors the body instead
+                signal.removeEventListener('abort', onAbort);
+                signal.addEventListener('abort', () => {
+                    state.close();
+                }, { once: true });
+            }
+            const response = Response._fromNetwork({
+                status: resp.status,
+                statusText: resp.statusText,
+                headers: respHeaders,
+                stream,
+                urlList,
+            });
+            responseTrailers.set(response, trailers);
+            return response;
+        }
+    } catch (e) {
+        done();
+        signal.removeEventListener('abort', onAbort);
+        state.close();
+        if (signal.aborted) {
+            throw signal.reason;
+        }
+        throw e;
+    }
+}
+
+function defineGlobal(name, value) {
+    Object.defineProperty(globalThis, name, {
+        value,
+        writable: true,
+        configurable: true,
+        enumerable: false,
+    });
+}
+
+defineGlobal('fetch', fetch);
+defineGlobal('Headers', Headers);
+defineGlobal('Request', Request);
+defineGlobal('Response', Response);
+defineGlobal('FormData', FormData);
+defineGlobal('Blob', Blob);
+defineGlobal('File', File);
+defineGlobal('AbortController', AbortController);
+defineGlobal('AbortSignal', AbortSignal);
+defineGlobal('DOMException', DOMException);
+defineGlobal('ReadableStream', ReadableStream);
+
+export { AbortController, AbortSignal, DOMException, ReadableStream };
+
+export default {
+    fetch,
+    Headers,
+    Request,
+    Response,
+    FormData,
+    Blob,
+    File,
+    AbortController,
+    AbortSignal,
+    DOMException,
+    ReadableStream,
+};
@@ -1,4 +1,4 @@
-import { validateFunction, validateInteger, validateBoolean } from "../internal/validators"
+import { validateFunction, validateInteger, validateInt32, validateBoolean, validateAbortSignal } from "../internal/validators"
 import { getValidatedPath, getValidMode, Stats, validateBufferArray, validateEncoding, stringToFlags } from "../internal/fs/utils"
 import * as binding from "_node:fs"
 import * as errors from "../internal/errors"
@@ -10,12 +10,17 @@ import { kCustomPromisifiedSymbol, promisify, customPromisifyArgs } from "../int
 import { cpFn } from "../internal/fs/cp/cp";
 import cpSyncFn from "../internal/fs/cp/cp-sync";
 import { createWriteStream, WriteStream, createReadStream, ReadStream } from "../internal/fs/stream"
+import { Interface } from "../internal/readline/interface"
+import { lock, lockSync, FileLock } from "../internal/fs/lock"
+import { wasiFsSyscallErrorMap } from "../internal/fs/errno"
+import { ReadableStream } from "../stream/web"
 import EventEmitter from "../events"
 import { normalize, join as pathJoin } from "path"
 import uv from "../internal_binding/uv"
 import { URL } from "../url"
 import { relative, dirname } from "../path"
 import { Readable } from 'stream';
+import process from "process";
 
 // Ensure that callbacks run in the global context. Only use this function
 // for callbacks that are passed to the binding layer, callbacks that are
@@ -202,199 +207,46 @@ class BigIntStats {
     isSocket() { return this.#origin.is_socket };
 }
 
-const codeToErrorMsg = {
-    "E2BIG": "argument list too long",
-    "EACCES": "permission denied",
-    "EADDRINUSE": "address already in use",
-    "EADDRNOTAVAIL": "address not available",
-    "EAFNOSUPPORT": "address family not supported",
-    "EAGAIN": "resource temporarily unavailable",
-    "EAI_ADDRFAMILY": "address family not supported",
-    "EAI_AGAIN": "temporary failure",
-    "EAI_BADFLAGS": "bad ai_flags value",
-    "EAI_BADHINTS": "invalid value for hints",
-    "EAI_CANCELED": "request canceled",
-    "EAI_FAIL": "permanent failure",
-    "EAI_FAMILY": "ai_family not supported",
-    "EAI_MEMORY": "out of memory",
-    "EAI_NODATA": "no address",
-    "EAI_NONAME": "unknown node or service",
-    "EAI_OVERFLOW": "argument buffer overflow",
-    "EAI_PROTOCOL": "resolved protocol is unknown",
-    "EAI_SERVICE": "service not available for socket type",
-    "EAI_SOCKTYPE": "socket type not supported",
-    "EALREADY": "connection already in progress",
-    "EBADF": "bad file descriptor",
-    "EBUSY": "resource busy or locked",
-    "ECANCELED": "operation canceled",
-    "ECHARSET": "invalid Unicode character",
-    "ECONNABORTED": "software caused connection abort",
-    "ECONNREFUSED": "connection refused",
-    "ECONNRESET": "connection reset by peer",
-    "EDESTADDRREQ": "destination address required",
-    "EEXIST": "file already exists",
-    "EFAULT": "bad address in system call argument",
-    "EFBIG": "file too large",
-    "EHOSTUNREACH": "host is unreachable",
-    "EINTR": "interrupted system call",
-    "EINVAL": "invalid argument",
-    "EIO": "i/o error",
-    "EISCONN": "socket is already connected",
-    "EISDIR": "illegal operation on a directory",
-    "ELOOP": "too many symbolic links encountered",
-    "EMFILE": "too many open files",
-    "EMSGSIZE": "message too long",
-    "ENAMETOOLONG": "name too long",
-    "ENETDOWN": "network is down",
-    "ENETUNREACH": "network is unreachable",
-    "ENFILE": "file table overflow",
-    "ENOBUFS": "no buffer space available",
-    "ENODEV": "no such device",
-    "ENOENT": "no such file or directory",
-    "ENOMEM": "not enough memory",
-    "ENONET": "machine is not on the network",
-    "ENOPROTOOPT": "protocol not available",
-    "ENOSPC": "no space left on device",
-    "ENOSYS": "function not implemented",
-    "ENOTCONN": "socket is not connected",
-    "ENOTDIR": "not a directory",
-    "ENOTEMPTY": "directory not empty",
-    "ENOTSOCK": "socket operation on non-socket",
-    "ENOTSUP": "operation not supported on socket",
-    "EPERM": "operation not permitted",
-    "EPIPE": "broken pipe",
-    "EPROTO": "protocol error",
-    "EPROTONOSUPPORT": "protocol not supported",
-    "EPROTOTYPE": "protocol wrong type for socket",
-    "ERANGE": "result too large",
-    "EROFS": "read-only file system",
-    "ESHUTDOWN": "cannot send after transport endpoint shutdown",
-    "ESPIPE": "invalid seek",
-    "ESRCH": "no such process",
-    "ETIMEDOUT": "connection timed out",
-    "ETXTBSY": "text file is busy",
-    "EXDEV": "cross-device link not permitted",
-    "UNKNOWN": "unknown error",
-    "EOF": "end of file",
-    "ENXIO": "no such device or address",
-    "EMLINK": "too many links",
-    "EHOSTDOWN": "host is down",
-    "EREMOTEIO": "remote I/O error",
-    "ENOTTY": "inappropriate ioctl for device",
-    "EFTYPE": "inappropriate file type or format",
-    "EILSEQ": "illegal byte sequence",
-};
-
-const codeToUvErrno = {
-    "E2BIG": uv.UV_E2BIG,
-    "EACCES": uv.UV_EACCES,
-    "EADDRINUSE": uv.UV_EADDRINUSE,
-    "EADDRNOTAVAIL": uv.UV_EADDRNOTAVAIL,
-    "EAFNOSUPPORT": uv.UV_EAFNOSUPPORT,
-    "EAGAIN": uv.UV_EAGAIN,
-    "EAI_ADDRFAMILY": uv.UV_EAI_ADDRFAMILY,
-    "EAI_AGAIN": uv.UV_EAI_AGAIN,
-    "EAI_BADFLAGS": uv.UV_EAI_BADFLAGS,
-    "EAI_BADHINTS": uv.UV_EAI_BADHINTS,
-    "EAI_CANCELED": uv.UV_EAI_CANCELED,
-    "EAI_FAIL": uv.UV_EAI_FAIL,
-    "EAI_FAMILY": uv.UV_EAI_FAMILY,
-    "EAI_MEMORY": uv.UV_EAI_MEMORY,
-    "EAI_NODATA": uv.UV_EAI_NODATA,
-    "EAI_NONAME": uv.UV_EAI_NONAME,
-    "EAI_OVERFLOW": uv.UV_EAI_OVERFLOW,
-    "EAI_PROTOCOL": uv.UV_EAI_PROTOCOL,
-    "EAI_SERVICE": uv.UV_EAI_SERVICE,
-    "EAI_SOCKTYPE": uv.UV_EAI_SOCKTYPE,
-    "EALREADY": uv.UV_EALREADY,
-    "EBADF": uv.UV_EBADF,
-    "EBUSY": uv.UV_EBUSY,
-    "ECANCELED": uv.UV_ECANCELED,
-    "ECHARSET": uv.UV_ECHARSET,
-    "ECONNABORTED": uv.UV_ECONNABORTED,
-    "ECONNREFUSED": uv.UV_ECONNREFUSED,
-    "ECONNRESET": uv.UV_ECONNRESET,
-    "EDESTADDRREQ": uv.UV_EDESTADDRREQ,
-    "EEXIST": uv.UV_EEXIST,
-    "EFAULT": uv.UV_EFAULT,
-    "EFBIG": uv.UV_EFBIG,
-    "EHOSTUNREACH": uv.UV_EHOSTUNREACH,
-    "EINTR": uv.UV_EINTR,
-    "EINVAL": uv.UV_EINVAL,
-    "EIO": uv.UV_EIO,
-    "EISCONN": uv.UV_EISCONN,
-    "EISDIR": uv.UV_EISDIR,
-    "ELOOP": uv.UV_ELOOP,
-    "EMFILE": uv.UV_EMFILE,
-    "EMSGSIZE": uv.UV_EMSGSIZE,
-    "ENAMETOOLONG": uv.UV_ENAMETOOLONG,
-    "ENETDOWN": uv.UV_ENETDOWN,
-    "ENETUNREACH": uv.UV_ENETUNREACH,
-    "ENFILE": uv.UV_ENFILE,
-    "ENOBUFS": uv.UV_ENOBUFS,
-    "ENODEV": uv.UV_ENODEV,
-    "ENOENT": uv.UV_ENOENT,
-    "ENOMEM": uv.UV_ENOMEM,
-    "ENONET": uv.UV_ENONET,
-    "ENOPROTOOPT": uv.UV_ENOPROTOOPT,
-    "ENOSPC": uv.UV_ENOSPC,
-    "ENOSYS": uv.UV_ENOSYS,
-    "ENOTCONN": uv.UV_ENOTCONN,
-    "ENOTDIR": uv.UV_ENOTDIR,
-    "ENOTEMPTY": uv.UV_ENOTEMPTY,
-    "ENOTSOCK": uv.UV_ENOTSOCK,
-    "ENOTSUP": uv.UV_ENOTSUP,
-    "EPERM": uv.UV_EPERM,
-    "EPIPE": uv.UV_EPIPE,
-    "EPROTO": uv.UV_EPROTO,
-    "EPROTONOSUPPORT": uv.UV_EPROTONOSUPPORT,
-    "EPROTOTYPE": uv.UV_EPROTOTYPE,
-    "ERANGE": uv.UV_ERANGE,
-    "EROFS": uv.UV_EROFS,
-    "ESHUTDOWN": uv.UV_ESHUTDOWN,
-    "ESPIPE": uv.UV_ESPIPE,
-    "ESRCH": uv.UV_ESRCH,
-    "ETIMEDOUT": uv.UV_ETIMEDOUT,
-    "ETXTBSY": uv.UV_ETXTBSY,
-    "EXDEV": uv.UV_EXDEV,
-    "UNKNOWN": uv.UV_UNKNOWN,
-    "EOF": uv.UV_EOF,
-    "ENXIO": uv.UV_ENXIO,
-    "EMLINK": uv.UV_EMLINK,
-    "EHOSTDOWN": uv.UV_EHOSTDOWN,
-    "EREMOTEIO": uv.UV_EREMOTEIO,
-    "ENOTTY": uv.UV_ENOTTY,
-    "EFTYPE": uv.UV_EFTYPE,
-    "EILSEQ": uv.UV_EILSEQ,
-};
+/**
+ * Hands the outcome of an async binding to a node style `callback`. The
+ * async bindings run on the fs pool of `_node:fs`, a large file or tree
+ * does not hold up the event loop.
+ */
+function settle(promise, callback, mapError, mapResult = (res) => res) {
+    promise.then((res) => {
+        callback(null, mapResult(res));
+    }, (err) => {
+        callback(mapError(err));
+    });
+}
 
-function wasiFsSyscallErrorMap(err, syscall, path, dest) {
-    if (typeof (err) === "string") {
-        err = {
-            code: err
-        };
-    }
-    let code = (codeToErrorMsg[err.code] === undefined) ? ("E" + err.code) : err.code;
-    if (codeToErrorMsg[code] === undefined) {
-        return err;
-    }
-    let msg = `${code}: ${codeToErrorMsg[code]}, ${syscall}`;
-    if (path !== undefined) {
-        msg += ` '${path}'`;
-    }
-    if (dest !== undefined) {
-        msg += ` -> '${dest}'`;
-    }
-    let e = new Error(msg);
-    e.code = code;
-    e.errno = codeToUvErrno[code];
-    e.syscall = syscall;
-    e.path = path;
-    e.dest = dest;
-    if (err.stack) {
-        e.stack = err.stack;
+function settleStat(promise, options, callback, syscall, path) {
+    if (typeof (options) !== "object" || options === null) {
+        options = {};
     }
-    return e;
+    options = applyDefaultValue(options, { bigint: false, throwIfNoEntry: true });
+    promise = promise.catch((err) => {
+        if (err.code === "NOENT" && options.throwIfNoEntry === false) {
+            return undefined;
+        }
+        throw err;
+    });
+    settle(promise, callback, (err) => wasiFsSyscallErrorMap(err, syscall, path), (stat) => {
+        if (stat === undefined) {
+            return undefined;
+        }
+        return options.bigint === true ? new BigIntStats(stat) : new Stats(stat);
+    });
+}
+
+// false when `path` does not exist, other errors reject
+function pathExists(path) {
+    return binding.stat(path).then(() => true, (err) => {
+        if (err.code === "NOENT") {
+            return false;
+        }
+        throw err;
+    });
 }
 
 function stat(path, options, callback) {
@@ -405,14 +257,7 @@ function stat(path, options, callback) {
     validateFunction(callback, "callback");
     path = getValidatedPath(path);
 
-    setTimeout(() => {
-        try {
-            let res = statSync(path, options);
-            callback(null, res);
-        } catch (err) {
-            callback(err);
-        }
-    }, 0);
+    settleStat(binding.stat(path), options, callback, "stat", path);
 }
 
 /**
@@ -452,14 +297,7 @@ function lstat(path, options, callback) {
     validateFunction(callback, "callback");
     path = getValidatedPath(path);
 
-    setTimeout(() => {
-        try {
-            let res = lstatSync(path, options);
-            callback(null, res);
-        } catch (err) {
-            callback(err);
-        }
-    }, 0);
+    settleStat(binding.lstat(path), options, callback, "lstat", path);
 }
 
 function lstatSync(path, options = { bigint: false, throwIfNoEntry: true }) {
@@ -487,15 +325,9 @@ function fstat(fd, options, callback) {
         callback = options;
     }
     validateFunction(callback, "callback");
+    validateInteger(fd, "fd");
 
-    setTimeout(() => {
-        try {
-            let res = fstatSync(fd, options);
-            callback(null, res);
-        } catch (err) {
-            callback(err);
-        }
-    }, 0);
+    settleStat(binding.fstat(fd), options, callback, "fstat");
 }
 
 function fstatSync(fd, options = { bigint: false, throwIfNoEntry: true }) {
@@ -529,14 +361,15 @@ function access(path, mode = constants.F_OK, callback) {
     path = getValidatedPath(path);
     validateFunction(callback, "callback");
 
-    setTimeout(() => {
-        try {
-            accessSync(path, mode);
+    binding.stat(path).then((stat) => {
+        if (hasAccess(new Stats(stat), mode)) {
             callback(null);
-        } catch (err) {
-            callback(err);
+        } else {
+            callback(wasiFsSyscallErrorMap("ACCES", "access", path));
         }
-    }, 0);
+    }, (err) => {
+        callback(wasiFsSyscallErrorMap(err, "access", path));
+    });
 }
 
 function accessSync(path, mode = constants.F_OK) {
@@ -544,16 +377,15 @@ function accessSync(path, mode = constants.F_OK) {
 
     mode = getValidMode(mode, "access");
 
+    let stat;
     try {
-        const stat = statSync(path, { throwIfNoEntry: true });
-        if ((stat.mode & mode) === mode) {
-            return undefined;
-        } else {
-            throw new Error(`EACCES: permission denied, access '${path}'`);
-        }
+        stat = statSync(path, { throwIfNoEntry: true });
     } catch (err) {
         throw wasiFsSyscallErrorMap(err, "access", path);
     }
+    if (!hasAccess(stat, mode)) {
+        throw wasiFsSyscallErrorMap("ACCES", "access", path);
+    }
 }
 
 function exists(path, callback) {
@@ -566,9 +398,7 @@ function exists(path, callback) {
         return;
     }
 
-    setTimeout(() => {
-        callback(existsSync(path));
-    }, 0);
+    binding.stat(path).then(() => callback(true), () => callback(false));
 }
 
 exists[kCustomPromisifiedSymbol] = (path) => {
@@ -603,22 +433,19 @@ function mkdir(path, options, callback) {
         };
     } else if (typeof (options) === "string") {
         options = {
-            mode: parseInt(options)
+            mode: parseFileMode(options)
         };
     }
 
     options = applyDefaultValue(options, { recursive: false, mode: 0o777 });
 
     validateBoolean(options.recursive, "options.recursive");
+    options.mode = parseFileMode(options.mode);
 
-    setTimeout(() => {
-        try {
-            let newPath = mkdirSync(path, options);
-            callback(null, newPath);
-        } catch (err) {
-            callback(err);
-        }
-    }, 0);
+    // the first directory created with `recursive`
+    settle(binding.mkdir(path, options.recursive, options.mode), callback, (err) => {
+        return wasiFsSyscallErrorMap(err, "mkdir", path);
+    });
 }
 
 function mkdirSync(path, options = { recursive: false, mode: 0o777 }) {
@@ -630,91 +457,183 @@ function mkdirSync(path, options = { recursive: false, mode: 0o777 }) {
         };
     } else if (typeof (options) === "string") {
         options = {
-            mode: parseInt(options)
+            mode: parseFileMode(options)
         };
     }
 
     options = applyDefaultValue(options, { recursive: false, mode: 0o777 });
 
     validateBoolean(options.recursive, "options.recursive");
+    options.mode = parseFileMode(options.mode);
 
     try {
-        let dirs = path.split("/");
-        let dir = "";
-        let allExist = true;
-        for (const d of dirs) {
-            dir = pathJoin(dir, d);
-            if (!existsSync(dir)) {
-                allExist = false;
-                break;
-            }
-        }
-        binding.mkdirSync(path, options.recursive, options.mode);
-        return allExist ? undefined : dir;
+        // the first directory created with `recursive`
+        return binding.mkdirSync(path, options.recursive, options.mode);
     } catch (err) {
         throw wasiFsSyscallErrorMap(err, "mkdir", path);
     }
 }
 
-// wasi unspported *chown, *chownSync, *chmod, *chmodSync
+// WASI has no chmod or chown, `_node:fs` keeps the modes and owners set
+// here in memory for the run and `stat` reports them.
+const kMaxUserId = 2 ** 32 - 1;
+
+// the ids `stat` reports for a file nobody chowned, the process owns those
+const kProcessUid = 0;
+const kProcessGid = 0;
+
+function validateId(value, name, min = -1) {
+    if (typeof (value) !== "number") {
+        throw new errors.ERR_INVALID_ARG_TYPE(name, "number", value);
+    }
+    if (!Number.isInteger(value)) {
+        throw new errors.ERR_OUT_OF_RANGE(name, "an integer", value);
+    }
+    if (value < min || value > kMaxUserId) {
+        throw new errors.ERR_OUT_OF_RANGE(name, `>= ${min} && <= ${kMaxUserId}`, value);
+    }
+}
+
+function parseFileMode(mode, name = "mode") {
+    if (typeof (mode) === "string") {
+        if (!/^[0-7]+$/.test(mode)) {
+            throw new errors.ERR_INVALID_ARG_VALUE(name, mode, "must be a 32-bit unsigned integer or an octal string");
+        }
+        mode = parseInt(mode, 8);
+    }
+    validateId(mode, name, 0);
+    return mode;
+}
+
+// the owner bits for the owner, then the group bits, then the other bits
+function hasAccess(stat, mode) {
+    let bits = stat.mode;
+    if (stat.uid === kProcessUid) {
+        bits >>= 6;
+    } else if (stat.gid === kProcessGid) {
+        bits >>= 3;
+    }
+    return (bits & mode) === mode;
+}
+
 function fchown(fd, uid, gid, callback) {
-    validateFunction(callback);
+    validateInt32(fd, "fd", 0);
+    validateId(uid, "uid");
+    validateId(gid, "gid");
+    validateFunction(callback, "callback");
 
-    callback(undefined);
+    settle(binding.chown(fd, uid, gid), callback, (err) => wasiFsSyscallErrorMap(err, "fchown"));
 }
 
 function fchownSync(fd, uid, gid) {
-    return undefined;
+    validateInt32(fd, "fd", 0);
+    validateId(uid, "uid");
+    validateId(gid, "gid");
+
+    try {
+        binding.chownSync(fd, uid, gid);
+    } catch (err) {
+        throw wasiFsSyscallErrorMap(err, "fchown");
+    }
 }
 
 function lchown(path, uid, gid, callback) {
-    validateFunction(callback);
+    path = getValidatedPath(path);
+    validateId(uid, "uid");
+    validateId(gid, "gid");
+    validateFunction(callback, "callback");
 
-    callback(undefined);
+    settle(binding.chown(path, uid, gid, false), callback, (err) => wasiFsSyscallErrorMap(err, "lchown", path));
 }
 
 function lchownSync(path, uid, gid) {
-    return undefined;
+    path = getValidatedPath(path);
+    validateId(uid, "uid");
+    validateId(gid, "gid");
+
+    try {
+        binding.chownSync(path, uid, gid, false);
+    } catch (err) {
+        throw wasiFsSyscallErrorMap(err, "lchown", path);
+    }
 }
 
 function chown(path, uid, gid, callback) {
-    validateFunction(callback);
+    path = getValidatedPath(path);
+    validateId(uid, "uid");
+    validateId(gid, "gid");
+    validateFunction(callback, "callback");
 
-    callback(undefined);
+    settle(binding.chown(path, uid, gid, true), callback, (err) => wasiFsSyscallErrorMap(err, "chown", path));
 }
 
 function chownSync(path, uid, gid) {
-    return undefined;
+    path = getValidatedPath(path);
+    validateId(uid, "uid");
+    validateId(gid, "gid");
+
+    try {
+        binding.chownSync(path, uid, gid, true);
+    } catch (err) {
+        throw wasiFsSyscallErrorMap(err, "chown", path);
+    }
 }
 
 function chmod(path, mode, callback) {
-    validateFunction(callback);
+    path = getValidatedPath(path);
+    mode = parseFileMode(mode);
+    validateFunction(callback, "callback");
 
-    callback(undefined);
+    settle(binding.chmod(path, mode, true), callback, (err) => wasiFsSyscallErrorMap(err, "chmod", path));
 }
 
 function chmodSync(path, mode) {
-    return undefined;
+    path = getValidatedPath(path);
+    mode = parseFileMode(mode);
+
+    try {
+        binding.chmodSync(path, mode, true);
+    } catch (err) {
+        throw wasiFsSyscallErrorMap(err, "chmod", path);
+    }
 }
 
 function lchmod(path, mode, callback) {
-    validateFunction(callback);
+    path = getValidatedPath(path);
+    mode = parseFileMode(mode);
+    validateFunction(callback, "callback");
 
-    callback(undefined);
+    settle(binding.chmod(path, mode, false), callback, (err) => wasiFsSyscallErrorMap(err, "lchmod", path));
 }
 
 function lchmodSync(path, mode) {
-    return undefined;
+    path = getValidatedPath(path);
+    mode = parseFileMode(mode);
+
+    try {
+        binding.chmodSync(path, mode, false);
+    } catch (err) {
+        throw wasiFsSyscallErrorMap(err, "lchmod", path);
+    }
 }
 
 function fchmod(fd, mode, callback) {
+    validateInt32(fd, "fd", 0);
+    mode = parseFileMode(mode);
     validateFunction(callback, "callback");
 
-    callback(undefined);
+    settle(binding.chmod(fd, mode), callback, (err) => wasiFsSyscallErrorMap(err, "fchmod"));
 }
 
 function fchmodSync(fd, mode) {
-    return undefined;
+    validateInt32(fd, "fd", 0);
+    mode = parseFileMode(mode);
+
+    try {
+        binding.chmodSync(fd, mode);
+    } catch (err) {
+        throw wasiFsSyscallErrorMap(err, "fchmod");
+    }
 }
 
 function getValidTime(time, name) {
@@ -737,15 +656,15 @@ function utimes(path, atime, mtime, callback) {
     atime = getValidTime(atime);
     mtime = getValidTime(mtime);
     validateFunction(callback, "callback");
-    setTimeout(() => {
-        try {
-            utimesSync(path, atime, mtime);
-        } catch (err) {
-            callback(err);
-            return;
-        }
-        callback(null);
-    }, 0);
+    if (atime instanceof Date) {
+        atime = atime.getTime();
+    }
+    if (mtime instanceof Date) {
+        mtime = mtime.getTime();
+    }
+    settle(binding.utime(path, atime, mtime), callback, (err) => {
+        return wasiFsSyscallErrorMap(err, "utime", path);
+    });
 }
 
 function utimesSync(path, atime, mtime) {
@@ -811,15 +730,7 @@ function futimes(fd, atime, mtime, callback) {
     atime = getValidTime(atime, "atime");
     mtime = getValidTime(mtime, "mtime");
 
-    setTimeout(() => {
-        try {
-            futimesSync(fd, atime, mtime);
-        } catch (err) {
-            callback(err);
-            return;
-        }
-        callback(null);
-    }, 0);
+    settle(binding.futime(fd, atime, mtime), callback, (err) => wasiFsSyscallErrorMap(err, "futime"));
 }
 
 function futimesSync(fd, atime, mtime) {
@@ -847,14 +758,11 @@ function rmdir(path, options, callback) {
         options = {};
     }
     validateFunction(callback, "callback");
-    setTimeout(() => {
-        try {
-            rmdirSync(path, options, callback);
-            callback(null);
-        } catch (err) {
-            callback(err);
-        }
-    }, 0);
+    options = applyDefaultValue(options ?? {}, { maxRetries: 0, recursive: false, retryDelay: 100 });
+
+    settle(binding.rmdir(path, options.recursive), callback, (err) => {
+        return wasiFsSyscallErrorMap(err, "rmdir", path);
+    });
 }
 
 function rmdirSync(path, options = { maxRetries: 0, recursive: false, retryDelay: 100 }) {
@@ -870,6 +778,7 @@ function rmdirSync(path, options = { maxRetries: 0, recursive: false, retryDelay
 }
 
 function rm(path, options, callback) {
+    path = getValidatedPath(path);
     if (typeof (options) === "function") {
         callback = options;
         options = {};
@@ -884,15 +793,12 @@ function rm(path, options, callback) {
     validateInteger(options.maxRetries, "options.maxRetries", 0);
     validateInteger(options.retryDelay, "options.retryDelay", 0);
 
-    setTimeout(() => {
-        try {
-            rmSync(path, options);
-            callback(null);
-        }
-        catch (err) {
-            callback(err);
-        }
-    }, 0);
+    // a large tree is removed over several turns of the event loop
+    binding.rm(path, options.recursive, options.force).then(() => {
+        callback(null);
+    }, (err) => {
+        callback(wasiFsSyscallErrorMap(err, "rm", path));
+    });
 }
 
 function rmSync(path, options = { force: false, maxRetries: 1, recursive: false, retryDelay: 100 }) {
@@ -923,14 +829,17 @@ function rename(oldPath, newPath, callback) {
     oldPath = getValidatedPath(oldPath, "oldPath");
     newPath = getValidatedPath(newPath, "newPath");
     validateFunction(callback, "callback");
-    setTimeout(() => {
-        try {
-            renameSync(oldPath, newPath);
-            callback(null);
-        } catch (err) {
-            callback(err);
+
+    let renamed = Promise.all([pathExists(oldPath), pathExists(newPath)]).then(([from, to]) => {
+        if (!from) {
+            throw "NOENT";
         }
-    }, 0);
+        if (to) {
+            throw "EXIST";
+        }
+        return binding.rename(oldPath, newPath);
+    });
+    settle(renamed, callback, (err) => wasiFsSyscallErrorMap(err, "rename", oldPath, newPath));
 }
 
 function renameSync(oldPath, newPath) {
@@ -991,19 +900,8 @@ function truncate(path, len, callback) {
     if (len < 0) {
         len = 0;
     }
-    setTimeout(() => {
-        try {
-            if (typeof (path) === "number") {
-                ftruncateSync(path, len);
-            } else {
-                truncateSync(path, len);
-            }
-            callback(null);
-        }
-        catch (err) {
-            callback(err);
-        }
-    }, 0);
+    let truncated = typeof (path) === "number" ? binding.ftruncate(path, len) : binding.truncate(path, len);
+    settle(truncated, callback, (err) => wasiFsSyscallErrorMap(err, "truncate", path));
 }
 
 function truncateSync(path, len = 0) {
@@ -1040,15 +938,7 @@ function ftruncate(fd, len, callback) {
     if (len < 0) {
         len = 0;
     }
-    setTimeout(() => {
-        try {
-            ftruncateSync(fd, len);
-            callback(null);
-        }
-        catch (err) {
-            callback(err);
-        }
-    }, 0);
+    settle(binding.ftruncate(fd, len), callback, (err) => wasiFsSyscallErrorMap(err, "ftruncate"));
 }
 
 function ftruncateSync(fd, len = 0) {
@@ -1081,13 +971,19 @@ function realpath(path, options = { encoding: "utf8" }, callback) {
         encoding: "utf8"
     });
     validateEncoding(options.encoding, "encoding");
-    setTimeout(() => {
-        try {
-            callback(null, realpathSync(path, options));
-        } catch (err) {
-            callback(err);
-        }
-    }, 0);
+    let useBuffer = options.encoding === "buffer" || options.encoding === "Buffer";
+    let resolved = binding.lstat(path).then((stat) => {
+        return new Stats(stat).isSymbolicLink() ? binding.realpath(path) : path;
+    }, (err) => {
+        // like realpathSync, which looks at the path first
+        throw wasiFsSyscallErrorMap(err, "lstat", path);
+    });
+    settle(resolved, callback, (err) => {
+        return err instanceof Error ? err : wasiFsSyscallErrorMap(err, "realpath", path);
+    }, (res) => {
+        res = normalize(res);
+        return useBuffer ? Buffer.from(res, "utf8") : res;
+    });
 }
 
 function realpathSync(path, options = { encoding: "utf8" }) {
@@ -1196,14 +1092,19 @@ function copyFile(src, dest, mode, callback) {
     dest = getValidatedPath(dest, "dest");
     validateInteger(mode, "mode", 0, 7);
     validateFunction(callback, "callback");
-    setTimeout(() => {
-        try {
-            copyFileSync(src, dest, mode);
-            callback(null);
-        } catch (err) {
-            callback(err);
+
+    let copied = pathExists(src).then((found) => {
+        if (!found) {
+            throw "NOENT";
         }
-    }, 0);
+        return (mode & constants.COPYFILE_EXCL) ? pathExists(dest) : false;
+    }).then((exists) => {
+        if (exists) {
+            throw "EXIST";
+        }
+        return binding.copyFile(src, dest);
+    });
+    settle(copied, callback, (err) => wasiFsSyscallErrorMap(err, "copyfile", src, dest));
 }
 
 function copyFileSync(src, dest, mode = 0) {
@@ -1236,14 +1137,9 @@ function link(existingPath, newPath, callback) {
     newPath = getValidatedPath(newPath);
     validateFunction(callback, "callback");
 
-    setTimeout(() => {
-        try {
-            linkSync(existingPath, newPath);
-            callback(null);
-        } catch (err) {
-            callback(err);
-        }
-    }, 0);
+    settle(binding.link(existingPath, newPath), callback, (err) => {
+        return wasiFsSyscallErrorMap(err, "link", existingPath, newPath);
+    });
 }
 
 function linkSync(existingPath, newPath) {
@@ -1271,14 +1167,17 @@ function symlink(target, path, type, callback) {
         throw new errors.ERR_FS_INVALID_SYMLINK_TYPE(type);
     }
 
-    setTimeout(() => {
-        try {
-            symlinkSync(target, path);
-            callback(null);
-        } catch (err) {
-            callback(err);
-        }
-    }, 0);
+    settle(binding.symlink(symlinkTarget(target, path), path), callback, (err) => {
+        return wasiFsSyscallErrorMap(err, "symlink", target, path);
+    });
+}
+
+// WASI resolves the target relative to the directory of the link
+function symlinkTarget(target, path) {
+    if (dirname(target) === "." || dirname(target) === "") {
+        return target;
+    }
+    return relative(dirname(path), target);
 }
 
 function symlinkSync(target, path, type) {
@@ -1290,15 +1189,8 @@ function symlinkSync(target, path, type) {
     target = getValidatedPath(target);
     path = getValidatedPath(path);
 
-    let reTarget;
-    if (dirname(target) === "." || dirname(target) === "") {
-        reTarget = target;
-    } else {
-        reTarget = relative(dirname(path), target);
-    }
-
     try {
-        binding.symlinkSync(reTarget, path);
+        binding.symlinkSync(symlinkTarget(target, path), path);
     } catch (err) {
         throw wasiFsSyscallErrorMap(err, "symlink", target, path);
     }
@@ -1308,14 +1200,7 @@ function close(fd, callback) {
     validateInteger(fd, "fd");
     validateFunction(callback, "callback");
 
-    setTimeout(() => {
-        try {
-            closeSync(fd);
-            callback(null);
-        } catch (err) {
-            callback(err);
-        }
-    }, 0);
+    settle(binding.fclose(fd), callback, (err) => wasiFsSyscallErrorMap(err, "close"));
 }
 
 function closeSync(fd) {
@@ -1330,15 +1215,9 @@ function closeSync(fd) {
 
 function fsync(fd, callback) {
     validateFunction(callback, "callback");
+    validateInteger(fd, "fd");
 
-    setTimeout(() => {
-        try {
-            fsyncSync(fd);
-            callback(null);
-        } catch (err) {
-            callback(err);
-        }
-    }, 0);
+    settle(binding.fsync(fd), callback, (err) => wasiFsSyscallErrorMap(err, "fsync"));
 }
 
 function fsyncSync(fd) {
@@ -1354,15 +1233,9 @@ function fsyncSync(fd) {
 
 function fdatasync(fd, callback) {
     validateFunction(callback, "callback");
+    validateInteger(fd, "fd");
 
-    setTimeout(() => {
-        try {
-            fdatasyncSync(fd);
-            callback(null);
-        } catch (err) {
-            callback(err);
-        }
-    }, 0);
+    settle(binding.fdatasync(fd), callback, (err) => wasiFsSyscallErrorMap(err, "fdatasync"));
 }
 
 function fdatasyncSync(fd) {
@@ -1375,27 +1248,9 @@ function fdatasyncSync(fd) {
     }
 }
 
+// regular files are read in chunks on the fs pool, anything else is polled
 function fread(fd, position, length) {
-    // poll a file will make infinite loop in wasmedge, so fallback to readSync
-    let stat = null;
-    try {
-        stat = fstatSync(fd);
-    } catch (err) {
-        return new Promise((res, rej) => {
-            rej(err);
-        })
-    }
-    if (stat.isFile()) {
-        return new Promise((res, rej) => {
-            try {
-                res(binding.freadSync(fd, position, length));
-            } catch (e) {
-                rej(e);
-            }
-        })
-    } else {
-        return binding.fread(fd, position, length);
-    }
+    return binding.fread(fd, position, length);
 }
 
 function read(fd, buffer, offset, length, position, callback) {
@@ -1484,13 +1339,14 @@ function read(fd, buffer, offset, length, position, callback) {
             buffer.set(new Uint8Array(data.slice(0, len)), offset);
         }
         callback(null, len, buffer)
-    }).catch((e) => {
+    }).catch((err) => {
         if (err.code === "INVAL") {
             let e = new Error(err.message);
             e.code = "EOVERFLOW";
             callback(e);
+            return;
         }
-        callback(wasiFsSyscallErrorMap(e, "read"));
+        callback(wasiFsSyscallErrorMap(err, "read"));
     })
 }
 
@@ -1633,14 +1489,9 @@ function open(path, flag = "r", mode = 0o666, callback) {
         throw err;
     }
 
-    setTimeout(() => {
-        try {
-            let fd = openSync(path, flag, mode);
-            callback(null, fd);
-        } catch (err) {
-            callback(err);
-        }
-    }, 0);
+    settle(binding.open(path, stringToFlags(flag), mode), callback, (err) => {
+        return wasiFsSyscallErrorMap(err, "open", path);
+    });
 }
 
 function readFile(path, option, callback) {
@@ -1778,14 +1629,12 @@ function readlink(path, option, callback) {
         encoding: "utf8"
     });
     validateEncoding(option.encoding, "encoding");
-    setTimeout(() => {
-        try {
-            let res = readlinkSync(path, option);
-            callback(null, res);
-        } catch (err) {
-            callback(err);
+    settle(binding.readlink(path), callback, (err) => wasiFsSyscallErrorMap(err, "readlink", path), (res) => {
+        if (option.encoding === "buffer" || option.encoding === "Buffer") {
+            return Buffer.from(res);
         }
-    }, 0);
+        return res;
+    });
 }
 
 function readv(fd, buffer, position, callback) {
@@ -1865,26 +1714,7 @@ function readvSync(fd, buffer, position = -1) {
 }
 
 function fwrite(fd, position, buffer) {
-    // poll a file will make infinite loop in wasmedge, so fallback to readSync
-    let stat = null;
-    try {
-        stat = fstatSync(fd);
-    } catch (err) {
-        return new Promise((res, rej) => {
-            rej(err);
-        })
-    }
-    if (stat.isFile()) {
-        return new Promise((res, rej) => {
-            try {
-                res(binding.fwriteSync(fd, position, buffer));
-            } catch (e) {
-                rej(e);
-            }
-        })
-    } else {
-        return binding.fwrite(fd, position, buffer);
-    }
+    return binding.fwrite(fd, position, buffer);
 }
 
 function write(fd, buffer, offset, length, position, callback) {
@@ -2166,6 +1996,54 @@ function writeFileSync(file, data, options = {}) {
     }
 }
 
+function atomicWriteArgs(file, data, options) {
+    options = options ?? {};
+    if (typeof (options) === "string") {
+        validateEncoding(options, "option");
+        options = {
+            encoding: options
+        };
+    } else {
+        validateObject(options, "option");
+    }
+    options = applyDefaultValue(options, {
+        encoding: "utf8",
+        mode: 0o666,
+        fsync: true
+    });
+    validateInteger(options.mode, "options.mode", 0, 0o777);
+    validateBoolean(options.fsync, "options.fsync");
+    file = getValidatedPath(file);
+    let buffer = typeof (data) === "string" ? Buffer.from(data, options.encoding) : data;
+    if (!isArrayBufferView(buffer)) {
+        throw new errors.ERR_INVALID_ARG_TYPE("data", ["string", "Buffer", "TypedArray", "DataView"], data);
+    }
+    return [file, buffer, options.mode, options.fsync];
+}
+
+// The data goes to a new file in the same directory which is renamed over
+// `file`, readers in other instances see the old contents or the new ones.
+function writeFileAtomic(file, data, options, callback) {
+    if (typeof (options) === "function") {
+        callback = options;
+        options = {};
+    }
+    validateFunction(callback, "callback");
+    const args = atomicWriteArgs(file, data, options);
+    settle(binding.writeFileAtomic(...args), callback, (err) => {
+        return wasiFsSyscallErrorMap(err, err.syscall, err.path);
+    });
+}
+
+function writeFileAtomicSync(file, data, options) {
+    const args = atomicWriteArgs(file, data, options);
+    try {
+        binding.writeFileAtomicSync(...args);
+    } catch (err) {
+        throw wasiFsSyscallErrorMap(err, err.syscall, err.path);
+    }
+}
+
 function appendFile(file, data, options, callback) {
     if (typeof (options) === "function") {
         callback = options;
@@ -2328,6 +2206,8 @@ class Dirent {
     constructor(innerData) {
         this.filetype = innerData.filetype;
         this.name = innerData.name;
+        this.parentPath = innerData.parentPath;
+        this.path = innerData.parentPath;
     }
 
     isFile = () => this.filetype === FILETYPE_REGULAR_FILE;
@@ -2360,7 +2240,9 @@ class Dir {
         if (this.#idx === this.#dataBuf.length && !this.#fin) {
             try {
                 let data = binding.freaddirSync(this.#fd, this.#cookie);
-                this.#dataBuf.push(...data.res.filter(d => d.name !== "." && d.name !== ".."));
+                this.#dataBuf.push(...data.res
+                    .filter(d => d.name !== "." && d.name !== "..")
+                    .map(d => ({ ...d, parentPath: this.path })));
                 this.#fin = data.fin;
                 this.#cookie = data.cookie;
             } catch (err) {
@@ -2477,23 +2359,13 @@ function opendir(path, options, callback) {
         validateInteger(options.bufferSize, "bufferSize", 1);
     }
 
-    try {
-        if (!statSync(path).isDirectory()) {
-            throw wasiFsSyscallErrorMap("NOTDIR", "opendir", path);
+    let opened = binding.stat(path).then((stat) => {
+        if (!new Stats(stat).isDirectory()) {
+            throw "NOTDIR";
         }
-    } catch (err) {
-        callback(wasiFsSyscallErrorMap(err, "opendir", path));
-        return;
-    }
-
-    setTimeout(() => {
-        try {
-            let fd = openSync(path);
-            callback(null, new Dir(fd, path));
-        } catch (err) {
-            callback(err);
-        }
-    }, 0);
+        return binding.open(path, stringToFlags("r"), 0o666);
+    });
+    settle(opened, callback, (err) => wasiFsSyscallErrorMap(err, "opendir", path), (fd) => new Dir(fd, path));
 }
 
 function opendirSync(path, options) {
@@ -2515,6 +2387,29 @@ function opendirSync(path, options) {
     return new Dir(fd, path);
 }
 
+/**
+ * The names, or Dirents with `withFileTypes`, from the entries of the
+ * native listing of `path`.
+ */
+function listedEntries(path, entries, options) {
+    return entries.map(({ name, filetype }) => {
+        if (options.withFileTypes) {
+            let slash = name.lastIndexOf("/");
+            return new Dirent({
+                filetype,
+                name: name.slice(slash + 1),
+                parentPath: slash === -1 ? path : pathJoin(path, name.slice(0, slash)),
+            });
+        }
+        if (options.encoding === "buffer") {
+            return Buffer.from(name);
+        } else if (options.encoding !== "utf8") {
+            return Buffer.from(name).toString(options.encoding);
+        }
+        return name;
+    });
+}
+
 function readdir(path, options, callback) {
     if (typeof (options) === "function") {
         callback = options;
@@ -2533,23 +2428,10 @@ function readdir(path, options, callback) {
     path = getValidatedPath(path);
     validateFunction(callback, "callback");
 
-    setTimeout(async () => {
-        try {
-            let data = [];
-            let dir = opendirSync(path, { encoding: options.encoding });
-            for await (const p of dir) {
-                if (options.encoding === "buffer") {
-                    p.name === Buffer.from(p.name);
-                } else if (options.encoding !== "utf8") {
-                    p.name = Buffer.from(p.name).toString(options.encoding);
-                }
-                data.push(options.withFileTypes ? p : p.name);
-            }
-            callback(null, data);
-        } catch (err) {
-            callback(wasiFsSyscallErrorMap(err, "scandir", path));
-        }
-    }, 0);
+    // a large tree is listed over several turns of the event loop
+    settle(binding.readdir(path, options.recursive === true), callback, (err) => {
+        return wasiFsSyscallErrorMap(err, "scandir", path);
+    }, (entries) => listedEntries(path, entries, options));
 }
 
 function readdirSync(path, options) {
@@ -2564,6 +2446,13 @@ function readdirSync(path, options) {
         withFileTypes: false
     });
     validateEncoding(options.encoding, "encoding");
+    if (options.recursive) {
+        try {
+            return listedEntries(path, binding.readdirSync(path, true), options);
+        } catch (err) {
+            throw wasiFsSyscallErrorMap(err, "scandir", path);
+        }
+    }
     let data = [];
     try {
         let dir = opendirSync(path);
@@ -2578,18 +2467,204 @@ function readdirSync(path, options) {
     }
 }
 
-function watch() {
-    throw new Error(`'watch' is unsupported`);
+/**
+ * Reports changes under a path as `change` events. WASI has no file
+ * notifications, the path and the entries of a directory are polled every
+ * `interval` ms.
+ */
+class FSWatcher extends EventEmitter {
+    _handle = undefined;
+    #encoding = "utf8";
+
+    _start(path, options) {
+        this.#encoding = options.encoding;
+        this._handle = binding.watch(path, options.interval, options.recursive, (eventType, filename) => {
+            if (this.#encoding === "buffer") {
+                filename = Buffer.from(filename);
+            } else if (this.#encoding !== "utf8" && this.#encoding !== "utf-8") {
+                filename = Buffer.from(filename).toString(this.#encoding);
+            }
+            this.emit("change", eventType, filename);
+        });
+    }
+
+    close() {
+        if (this._handle === undefined) {
+            return;
+        }
+        this._handle.close();
+        this._handle = undefined;
+        process.nextTick(() => this.emit("close"));
+    }
+
+    ref() {
+        return this;
+    }
+
+    unref() {
+        return this;
+    }
 }
 
-function unwatch() {
-    throw new Error(`'unwatch' is unsupported`);
+/**
+ * Watches `filename` for changes. Besides node's options this takes
+ * `interval`, how often in ms it is polled, 1000 by default.
+ * @param {string | Buffer | URL} filename
+ * @param {string | {
+ *   persistent?: boolean;
+ *   recursive?: boolean;
+ *   encoding?: string;
+ *   signal?: AbortSignal;
+ *   interval?: number;
+ *   }} [options]
+ * @param {(eventType?: string, filename?: string | Buffer) => any} [listener]
+ * @returns {FSWatcher}
+ */
+function watch(filename, options, listener) {
+    if (typeof options === "function") {
+        listener = options;
+        options = {};
+    }
+    if (typeof options === "string") {
+        options = { encoding: options };
+    }
+    options = applyDefaultValue(options ?? {}, {
+        persistent: true,
+        recursive: false,
+        encoding: "utf8",
+        signal: undefined,
+        interval: 1000
+    });
+    validateBoolean(options.persistent, "options.persistent");
+    validateBoolean(options.recursive, "options.recursive");
+    validateInteger(options.interval, "options.interval", 0);
+    validateAbortSignal(options.signal, "options.signal");
+    if (options.encoding !== "buffer") {
+        validateEncoding(options.encoding, "encoding");
+    }
+    const path = getValidatedPath(filename);
+    try {
+        binding.statSync(path);
+    } catch (err) {
+        const e = wasiFsSyscallErrorMap(err, "watch", path);
+        e.filename = path;
+        throw e;
+    }
+
+    const watcher = new FSWatcher();
+    watcher._start(path, options);
+    if (listener) {
+        watcher.on("change", listener);
+    }
+    if (options.signal) {
+        if (options.signal.aborted) {
+            process.nextTick(() => watcher.close());
+        } else {
+            options.signal.addEventListener("abort", () => watcher.close(), { once: true });
+        }
+    }
+    return watcher;
 }
 
-function watchFile() {
-    throw new Error(`'watchFile' is unsupported`);
+/**
+ * Calls its `change` listeners with the current and the previous stats of
+ * a path whenever they differ, polling every `interval` ms.
+ */
+class StatWatcher extends EventEmitter {
+    _handle = undefined;
+    #bigint;
+
+    constructor(bigint) {
+        super();
+        this.#bigint = bigint;
+    }
+
+    _start(path, interval) {
+        const toStats = (stat) => this.#bigint ? new BigIntStats(stat) : new Stats(stat);
+        this._handle = binding.watchFile(path, interval, (current, previous) => {
+            this.emit("change", toStats(current), toStats(previous));
+        });
+    }
+
+    stop() {
+        if (this._handle === undefined) {
+            return;
+        }
+        this._handle.close();
+        this._handle = undefined;
+        process.nextTick(() => this.emit("stop"));
+    }
+
+    ref() {
+        return this;
+    }
+
+    unref() {
+        return this;
+    }
+}
+
+const statWatchers = new Map();
+
+/**
+ * @param {string | Buffer | URL} filename
+ * @param {{
+ *   bigint?: boolean;
+ *   persistent?: boolean;
+ *   interval?: number;
+ *   }} [options]
+ * @param {(current?: Stats, previous?: Stats) => any} listener
+ * @returns {StatWatcher}
+ */
+function watchFile(filename, options, listener) {
+    const path = getValidatedPath(filename);
+    if (typeof options === "function") {
+        listener = options;
+        options = {};
+    }
+    options = applyDefaultValue(options ?? {}, {
+        bigint: false,
+        persistent: true,
+        interval: 5007
+    });
+    validateFunction(listener, "listener");
+    validateInteger(options.interval, "options.interval", 0);
+
+    let watcher = statWatchers.get(path);
+    if (watcher === undefined) {
+        watcher = new StatWatcher(options.bigint);
+        watcher._start(path, options.interval);
+        statWatchers.set(path, watcher);
+    }
+    watcher.addListener("change", listener);
+    return watcher;
 }
 
+/**
+ * Removes `listener`, or every listener, from the watcher of `filename`.
+ * Polling stops with the last one.
+ * @param {string | Buffer | URL} filename
+ * @param {() => any} [listener]
+ */
+function unwatchFile(filename, listener) {
+    const path = getValidatedPath(filename);
+    const watcher = statWatchers.get(path);
+    if (watcher === undefined) {
+        return;
+    }
+    if (typeof listener === "function") {
+        watcher.removeListener("change", listener);
+    } else {
+        watcher.removeAllListeners("change");
+    }
+    if (watcher.listenerCount("change") === 0) {
+        watcher.stop();
+        statWatchers.delete(path);
+    }
+}
+
+const unwatch = unwatchFile;
+
 function getOwnPropertyValueOrDefault(options, key, defaultValue) {
     return options == null || !Object.prototype.hasOwnProperty(options, key) ?
         defaultValue :
@@ -2695,10 +2770,14 @@ function cp(src, dest, options, callback) {
 class FileHandle extends EventEmitter {
     #fd = -1;
     #path = "";
+    // closes the fd with a warning if the handle is collected while open
+    #guard = null;
+    #locked = false;
     constructor(fd, path) {
         super();
         this.#fd = fd;
         this.#path = path;
+        this.#guard = new binding.FdGuard(fd);
     }
 
     // for test-fs-promises-file-handle-close-error can re-define property
@@ -2710,8 +2789,12 @@ class FileHandle extends EventEmitter {
         return await promisify(appendFile)(this.fd, data, options);
     }
 
-    async chown() {
-        return undefined;
+    async chmod(mode) {
+        return await promisify(fchmod)(this.fd, mode);
+    }
+
+    async chown(uid, gid) {
+        return await promisify(fchown)(this.fd, uid, gid);
     }
 
     async close() {
@@ -2719,26 +2802,82 @@ class FileHandle extends EventEmitter {
             return;
         }
         this.emit("close");
-        return await promisify(close)(this.#fd).then(() => { this.#fd = -1; });
+        return await promisify(close)(this.#fd).then(() => {
+            this.#guard.release();
+            this.#fd = -1;
+        });
     }
 
-    async createReadStream(options) {
-        return createReadStream(this.#path, options);
+    createReadStream(options) {
+        return createReadStream(this.#path, { ...options, fd: this });
     }
 
-    async createWriteStream(options) {
-        return createWriteStream(this.#path, options);
+    createWriteStream(options) {
+        return createWriteStream(this.#path, { ...options, fd: this });
     }
 
     async datasync() {
         return await promisify(fdatasync)(this.fd);
     }
 
-    async read(...args) {
-        if (args.length === 3) {
-            args.push(-1);
+    // read(), read(options), read(buffer, options) and
+    // read(buffer, offset, length, position)
+    async read(buffer, offset, length, position) {
+        if (!isArrayBufferView(buffer)) {
+            const options = buffer ?? {};
+            buffer = options.buffer ?? Buffer.alloc(16384);
+            offset = options.offset;
+            length = options.length;
+            position = options.position;
+        } else if (offset !== null && typeof (offset) === "object") {
+            const options = offset;
+            offset = options.offset;
+            length = options.length;
+            position = options.position;
         }
-        return await promisify(read)(this.fd, ...args);
+        offset = offset ?? 0;
+        length = length ?? buffer.byteLength - offset;
+        return await promisify(read)(this.fd, buffer, offset, length, position ?? -1);
+    }
+
+    readLines(options) {
+        return new Interface(this.createReadStream({ encoding: "utf8", ...options }));
+    }
+
+    // a WHATWG stream of Uint8Arrays from the current position on
+    readableWebStream(options) {
+        if (this.#fd === -1) {
+            throw new errors.ERR_INVALID_STATE("The FileHandle is closed");
+        }
+        if (this.#locked) {
+            throw new errors.ERR_INVALID_STATE("The FileHandle is locked");
+        }
+        this.#locked = true;
+        const reader = new binding.FileReader(this.#fd, -1, Infinity);
+        const release = async () => {
+            this.#locked = false;
+            if (options?.autoClose === true) {
+                await this.close();
+            }
+        };
+        return new ReadableStream({
+            pull: async (controller) => {
+                let chunk;
+                try {
+                    chunk = await reader.read();
+                } catch (err) {
+                    await release();
+                    throw err;
+                }
+                if (chunk === null) {
+                    controller.close();
+                    await release();
+                } else {
+                    controller.enqueue(new Uint8Array(chunk));
+                }
+            },
+            cancel: release,
+        }, { highWaterMark: 0 });
     }
 
     async readFile(options) {
@@ -2824,6 +2963,7 @@ class FileHandle extends EventEmitter {
 }
 
 export {
+    wasiFsSyscallErrorMap,
     stat,
     statSync,
     lstat,
@@ -2896,6 +3036,8 @@ export {
     writeSync,
     writeFile,
     writeFileSync,
+    writeFileAtomic,
+    writeFileAtomicSync,
     appendFile,
     appendFileSync,
     writev,
@@ -2909,11 +3051,15 @@ export {
     watch,
     watchFile,
     unwatch,
+    unwatchFile,
     cp,
     cpSync,
+    lock,
+    lockSync,
+    FileLock,
     createWriteStream,
     WriteStream,
     createReadStream,
     ReadStream,
     FileHandle,
-}
\ No newline at end of file
+}
\ No newline at end of file
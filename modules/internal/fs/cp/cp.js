@@ -48,6 +48,8 @@ import {
 } from "path";
 
 import process from "process";
+import * as binding from "_node:fs";
+import { wasiFsSyscallErrorMap } from "internal/fs";
 
 async function cpFn(src, dest, opts) {
   // Warn about using preserveTimestamps on 32-bit node
@@ -62,7 +64,30 @@ async function cpFn(src, dest, opts) {
   if (opts.filter) {
     return handleFilter(checkParentDir, destStat, src, dest, opts);
   }
-  return checkParentDir(destStat, src, dest, opts);
+  // Without a filter to await the tree is copied natively, a few entries
+  // per turn of the event loop.
+  if (!await pathExists(dirname(dest))) {
+    await mkdir(dirname(dest), { recursive: true });
+  }
+  return binding.cp(src, dest, opts).catch((err) => {
+    throw nativeCpError(err);
+  });
+}
+
+// The native copy names the node error of a refused entry in `cpCode`,
+// other errors are those of the failed call.
+function nativeCpError(err) {
+  if (err.cpCode === undefined) {
+    return wasiFsSyscallErrorMap(err, err.syscall, err.path);
+  }
+  const code = `E${err.code}`;
+  return new errors[err.cpCode]({
+    message: err.message,
+    path: err.path,
+    syscall: 'cp',
+    errno: os.errno[code],
+    code,
+  });
 }
 
 async function checkPaths(src, dest, opts) {
@@ -385,4 +410,5 @@ export {
   areIdentical,
   cpFn,
   isSrcSubdir,
+  nativeCpError,
 };
@@ -0,0 +1,200 @@
+// The errors of the WASI calls as node reports them, `ENOENT: no such file
+// or directory, open 'x'` with the libuv errno. It imports nothing that
+// imports `process`, so `process` can use it as well.
+
+import uv from "../../internal_binding/uv";
+
+const codeToErrorMsg = {
+    "E2BIG": "argument list too long",
+    "EACCES": "permission denied",
+    "EADDRINUSE": "address already in use",
+    "EADDRNOTAVAIL": "address not available",
+    "EAFNOSUPPORT": "address family not supported",
+    "EAGAIN": "resource temporarily unavailable",
+    "EAI_ADDRFAMILY": "address family not supported",
+    "EAI_AGAIN": "temporary failure",
+    "EAI_BADFLAGS": "bad ai_flags value",
+    "EAI_BADHINTS": "invalid value for hints",
+    "EAI_CANCELED": "request canceled",
+    "EAI_FAIL": "permanent failure",
+    "EAI_FAMILY": "ai_family not supported",
+    "EAI_MEMORY": "out of memory",
+    "EAI_NODATA": "no address",
+    "EAI_NONAME": "unknown node or service",
+    "EAI_OVERFLOW": "argument buffer overflow",
+    "EAI_PROTOCOL": "resolved protocol is unknown",
+    "EAI_SERVICE": "service not available for socket type",
+    "EAI_SOCKTYPE": "socket type not supported",
+    "EALREADY": "connection already in progress",
+    "EBADF": "bad file descriptor",
+    "EBUSY": "resource busy or locked",
+    "ECANCELED": "operation canceled",
+    "ECHARSET": "invalid Unicode character",
+    "ECONNABORTED": "software caused connection abort",
+    "ECONNREFUSED": "connection refused",
+    "ECONNRESET": "connection reset by peer",
+    "EDESTADDRREQ": "destination address required",
+    "EEXIST": "file already exists",
+    "EFAULT": "bad address in system call argument",
+    "EFBIG": "file too large",
+    "EHOSTUNREACH": "host is unreachable",
+    "EINTR": "interrupted system call",
+    "EINVAL": "invalid argument",
+    "EIO": "i/o error",
+    "EISCONN": "socket is already connected",
+    "EISDIR": "illegal operation on a directory",
+    "ELOOP": "too many symbolic links encountered",
+    "EMFILE": "too many open files",
+    "EMSGSIZE": "message too long",
+    "ENAMETOOLONG": "name too long",
+    "ENETDOWN": "network is down",
+    "ENETUNREACH": "network is unreachable",
+    "ENFILE": "file table overflow",
+    "ENOBUFS": "no buffer space available",
+    "ENODEV": "no such device",
+    "ENOENT": "no such file or directory",
+    "ENOMEM": "not enough memory",
+    "ENONET": "machine is not on the network",
+    "ENOPROTOOPT": "protocol not available",
+    "ENOSPC": "no space left on device",
+    "ENOSYS": "function not implemented",
+    "ENOTCONN": "socket is not connected",
+    "ENOTDIR": "not a directory",
+    "ENOTEMPTY": "directory not empty",
+    "ENOTSOCK": "socket operation on non-socket",
+    "ENOTSUP": "operation not supported on socket",
+    "EPERM": "operation not permitted",
+    "EPIPE": "broken pipe",
+    "EPROTO": "protocol error",
+    "EPROTONOSUPPORT": "protocol not supported",
+    "EPROTOTYPE": "protocol wrong type for socket",
+    "ERANGE": "result too large",
+    "EROFS": "read-only file system",
+    "ESHUTDOWN": "cannot send after transport endpoint shutdown",
+    "ESPIPE": "invalid seek",
+    "ESRCH": "no such process",
+    "ETIMEDOUT": "connection timed out",
+    "ETXTBSY": "text file is busy",
+    "EXDEV": "cross-device link not permitted",
+    "UNKNOWN": "unknown error",
+    "EOF": "end of file",
+    "ENXIO": "no such device or address",
+    "EMLINK": "too many links",
+    "EHOSTDOWN": "host is down",
+    "EREMOTEIO": "remote I/O error",
+    "ENOTTY": "inappropriate ioctl for device",
+    "EFTYPE": "inappropriate file type or format",
+    "EILSEQ": "illegal byte sequence",
+};
+
+const codeToUvErrno = {
+    "E2BIG": uv.UV_E2BIG,
+    "EACCES": uv.UV_EACCES,
+    "EADDRINUSE": uv.UV_EADDRINUSE,
+    "EADDRNOTAVAIL": uv.UV_EADDRNOTAVAIL,
+    "EAFNOSUPPORT": uv.UV_EAFNOSUPPORT,
+    "EAGAIN": uv.UV_EAGAIN,
+    "EAI_ADDRFAMILY": uv.UV_EAI_ADDRFAMILY,
+    "EAI_AGAIN": uv.UV_EAI_AGAIN,
+    "EAI_BADFLAGS": uv.UV_EAI_BADFLAGS,
+    "EAI_BADHINTS": uv.UV_EAI_BADHINTS,
+    "EAI_CANCELED": uv.UV_EAI_CANCELED,
+    "EAI_FAIL": uv.UV_EAI_FAIL,
+    "EAI_FAMILY": uv.UV_EAI_FAMILY,
+    "EAI_MEMORY": uv.UV_EAI_MEMORY,
+    "EAI_NODATA": uv.UV_EAI_NODATA,
+    "EAI_NONAME": uv.UV_EAI_NONAME,
+    "EAI_OVERFLOW": uv.UV_EAI_OVERFLOW,
+    "EAI_PROTOCOL": uv.UV_EAI_PROTOCOL,
+    "EAI_SERVICE": uv.UV_EAI_SERVICE,
+    "EAI_SOCKTYPE": uv.UV_EAI_SOCKTYPE,
+    "EALREADY": uv.UV_EALREADY,
+    "EBADF": uv.UV_EBADF,
+    "EBUSY": uv.UV_EBUSY,
+    "ECANCELED": uv.UV_ECANCELED,
+    "ECHARSET": uv.UV_ECHARSET,
+    "ECONNABORTED": uv.UV_ECONNABORTED,
+    "ECONNREFUSED": uv.UV_ECONNREFUSED,
+    "ECONNRESET": uv.UV_ECONNRESET,
+    "EDESTADDRREQ": uv.UV_EDESTADDRREQ,
+    "EEXIST": uv.UV_EEXIST,
+    "EFAULT": uv.UV_EFAULT,
+    "EFBIG": uv.UV_EFBIG,
+    "EHOSTUNREACH": uv.UV_EHOSTUNREACH,
+    "EINTR": uv.UV_EINTR,
+    "EINVAL": uv.UV_EINVAL,
+    "EIO": uv.UV_EIO,
+    "EISCONN": uv.UV_EISCONN,
+    "EISDIR": uv.UV_EISDIR,
+    "ELOOP": uv.UV_ELOOP,
+    "EMFILE": uv.UV_EMFILE,
+    "EMSGSIZE": uv.UV_EMSGSIZE,
+    "ENAMETOOLONG": uv.UV_ENAMETOOLONG,
+    "ENETDOWN": uv.UV_ENETDOWN,
+    "ENETUNREACH": uv.UV_ENETUNREACH,
+    "ENFILE": uv.UV_ENFILE,
+    "ENOBUFS": uv.UV_ENOBUFS,
+    "ENODEV": uv.UV_ENODEV,
+    "ENOENT": uv.UV_ENOENT,
+    "ENOMEM": uv.UV_ENOMEM,
+    "ENONET": uv.UV_ENONET,
+    "ENOPROTOOPT": uv.UV_ENOPROTOOPT,
+    "ENOSPC": uv.UV_ENOSPC,
+    "ENOSYS": uv.UV_ENOSYS,
+    "ENOTCONN": uv.UV_ENOTCONN,
+    "ENOTDIR": uv.UV_ENOTDIR,
+    "ENOTEMPTY": uv.UV_ENOTEMPTY,
+    "ENOTSOCK": uv.UV_ENOTSOCK,
+    "ENOTSUP": uv.UV_ENOTSUP,
+    "EPERM": uv.UV_EPERM,
+    "EPIPE": uv.UV_EPIPE,
+    "EPROTO": uv.UV_EPROTO,
+    "EPROTONOSUPPORT": uv.UV_EPROTONOSUPPORT,
+    "EPROTOTYPE": uv.UV_EPROTOTYPE,
+    "ERANGE": uv.UV_ERANGE,
+    "EROFS": uv.UV_EROFS,
+    "ESHUTDOWN": uv.UV_ESHUTDOWN,
+    "ESPIPE": uv.UV_ESPIPE,
+    "ESRCH": uv.UV_ESRCH,
+    "ETIMEDOUT": uv.UV_ETIMEDOUT,
+    "ETXTBSY": uv.UV_ETXTBSY,
+    "EXDEV": uv.UV_EXDEV,
+    "UNKNOWN": uv.UV_UNKNOWN,
+    "EOF": uv.UV_EOF,
+    "ENXIO": uv.UV_ENXIO,
+    "EMLINK": uv.UV_EMLINK,
+    "EHOSTDOWN": uv.UV_EHOSTDOWN,
+    "EREMOTEIO": uv.UV_EREMOTEIO,
+    "ENOTTY": uv.UV_ENOTTY,
+    "EFTYPE": uv.UV_EFTYPE,
+    "EILSEQ": uv.UV_EILSEQ,
+};
+
+export function wasiFsSyscallErrorMap(err, syscall, path, dest) {
+    if (typeof (err) === "string") {
+        err = {
+            code: err
+        };
+    }
+    let code = (codeToErrorMsg[err.code] === undefined) ? ("E" + err.code) : err.code;
+    if (codeToErrorMsg[code] === undefined) {
+        return err;
+    }
+    let msg = `${code}: ${codeToErrorMsg[code]}, ${syscall}`;
+    if (path !== undefined) {
+        msg += ` '${path}'`;
+    }
+    if (dest !== undefined) {
+        msg += ` -> '${dest}'`;
+    }
+    let e = new Error(msg);
+    e.code = code;
+    e.errno = codeToUvErrno[code];
+    e.syscall = syscall;
+    e.path = path;
+    e.dest = dest;
+    if (err.stack) {
+        e.stack = err.stack;
+    }
+    return e;
+}
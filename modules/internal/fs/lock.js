@@ -0,0 +1,210 @@
+// Advisory lock files, for instances that share a directory.
+//
+// A lock is the file `${path}.lock`, created exclusively and holding a
+// random token. Whoever holds it refreshes its mtime, a lock left alone for
+// longer than `stale` ms is taken to be from an instance that went away and
+// is taken over. The token tells a holder whether its lock is still its own.
+
+import * as binding from "_node:fs";
+import { getValidatedPath } from "./utils";
+import { validateFunction, validateInteger, validateObject } from "../validators";
+import { ERR_FS_LOCKED, ERR_FS_LOCK_COMPROMISED } from "../errors";
+import { os } from "../../internal_binding/constants";
+import { wasiFsSyscallErrorMap } from "../fs";
+
+const kPath = Symbol('kPath');
+const kToken = Symbol('kToken');
+const kTimer = Symbol('kTimer');
+
+function mapError(err) {
+    return wasiFsSyscallErrorMap(err, err.syscall, err.path);
+}
+
+function lockedError(path) {
+    return new ERR_FS_LOCKED({
+        message: 'lock file already exists',
+        path,
+        syscall: 'lock',
+        errno: os.errno.EEXIST,
+        code: 'EEXIST',
+    });
+}
+
+function compromisedError(path) {
+    return new ERR_FS_LOCK_COMPROMISED({
+        message: 'lock file no longer holds this lock',
+        path,
+        syscall: 'lock',
+        errno: os.errno.ENOENT,
+        code: 'ENOENT',
+    });
+}
+
+function normalizeOptions(path, options) {
+    path = getValidatedPath(path);
+    options = options ?? {};
+    validateObject(options, "options");
+    const res = {
+        lockfilePath: options.lockfilePath ?? `${path}.lock`,
+        stale: options.stale ?? 10000,
+        update: options.update ?? 0,
+        retries: options.retries ?? 0,
+        retryWait: options.retryWait ?? 100,
+        onCompromised: options.onCompromised ?? ((err) => {
+            throw err;
+        }),
+    };
+    res.lockfilePath = getValidatedPath(res.lockfilePath, "options.lockfilePath");
+    validateInteger(res.stale, "options.stale", 0);
+    validateInteger(res.update, "options.update", 0);
+    validateInteger(res.retries, "options.retries", 0);
+    validateInteger(res.retryWait, "options.retryWait", 0);
+    validateFunction(res.onCompromised, "options.onCompromised");
+    return res;
+}
+
+// the lock is no longer this holder's, released or taken over
+function stop(lock) {
+    if (lock[kTimer] !== null) {
+        clearInterval(lock[kTimer]);
+        lock[kTimer] = null;
+    }
+    lock[kToken] = null;
+}
+
+function check(lock, touched) {
+    if (!touched) {
+        stop(lock);
+        throw compromisedError(lock[kPath]);
+    }
+}
+
+/**
+ * A lock file this instance holds.
+ */
+export class FileLock {
+    constructor(path, token, options) {
+        this[kPath] = path;
+        this[kToken] = token;
+        this[kTimer] = null;
+        if (options.update > 0) {
+            this[kTimer] = setInterval(() => {
+                this.refresh().catch((err) => {
+                    stop(this);
+                    options.onCompromised(err);
+                });
+            }, options.update);
+        }
+    }
+
+    /** The path of the lock file. */
+    get path() {
+        return this[kPath];
+    }
+
+    /** `false` once released, or once it turned out to be taken over. */
+    get held() {
+        return this[kToken] !== null;
+    }
+
+    /** Marks the lock fresh, so nobody takes it over as stale. */
+    refreshSync() {
+        if (!this.held) {
+            throw compromisedError(this[kPath]);
+        }
+        let touched;
+        try {
+            touched = binding.touchLockSync(this[kPath], this[kToken]);
+        } catch (err) {
+            throw mapError(err);
+        }
+        check(this, touched);
+    }
+
+    async refresh() {
+        if (!this.held) {
+            throw compromisedError(this[kPath]);
+        }
+        const touched = await binding.touchLock(this[kPath], this[kToken]).catch((err) => {
+            throw mapError(err);
+        });
+        check(this, touched);
+    }
+
+    /** Removes the lock file, throws if someone else took it over. */
+    releaseSync() {
+        if (!this.held) {
+            throw compromisedError(this[kPath]);
+        }
+        const token = this[kToken];
+        stop(this);
+        let removed;
+        try {
+            removed = binding.unlockSync(this[kPath], token);
+        } catch (err) {
+            throw mapError(err);
+        }
+        if (!removed) {
+            throw compromisedError(this[kPath]);
+        }
+    }
+
+    async release() {
+        if (!this.held) {
+            throw compromisedError(this[kPath]);
+        }
+        const token = this[kToken];
+        stop(this);
+        const removed = await binding.unlock(this[kPath], token).catch((err) => {
+            throw mapError(err);
+        });
+        if (!removed) {
+            throw compromisedError(this[kPath]);
+        }
+    }
+}
+
+/**
+ * Takes the lock on `path`, throws `ERR_FS_LOCKED` while another holder has
+ * it. `retries` only applies to the async `lock`.
+ * @param {string | URL} path
+ * @param {{ lockfilePath?: string, stale?: number, update?: number, onCompromised?: (err: Error) => void }} [options]
+ * @returns {FileLock}
+ */
+export function lockSync(path, options) {
+    options = normalizeOptions(path, options);
+    let token;
+    try {
+        token = binding.lockSync(options.lockfilePath, options.stale);
+    } catch (err) {
+        throw mapError(err);
+    }
+    if (token === null) {
+        throw lockedError(options.lockfilePath);
+    }
+    return new FileLock(options.lockfilePath, token, options);
+}
+
+export function lock(path, options, callback) {
+    if (typeof (options) === "function") {
+        callback = options;
+        options = {};
+    }
+    validateFunction(callback, "callback");
+    options = normalizeOptions(path, options);
+    let attempts = 0;
+    const attempt = () => {
+        binding.lock(options.lockfilePath, options.stale).then((token) => {
+            if (token !== null) {
+                return callback(null, new FileLock(options.lockfilePath, token, options));
+            }
+            if (attempts++ < options.retries) {
+                return setTimeout(attempt, options.retryWait);
+            }
+            callback(lockedError(options.lockfilePath));
+        }, (err) => {
+            callback(mapError(err));
+        });
+    };
+    attempt();
+}
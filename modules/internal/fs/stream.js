@@ -1,253 +1,259 @@
 // Copyright 2018-2022 the Deno authors. All rights reserved. MIT license.
 // Copyright Joyent, Inc. and Node.js contributors. All rights reserved. MIT license.
 
-import { Writable, Readable } from "stream";
+import { Writable, Readable, finished } from "stream";
+import { Buffer } from "buffer";
 import { validateEncoding } from "./utils";
-import { URL } from "url";
 import { toPathIfFileURL } from "../url";
-import fs, { open, write, close, statSync } from "../../fs";
+import fs from "../../fs";
+import { FileReader, FileWriter } from "_node:fs";
 import { validateInteger, validateObject } from "../validators";
-import { nextTick } from "../../process";
-
-const kIsPerformingIO = Symbol('kIsPerformingIO');
+import { ERR_OUT_OF_RANGE } from "../errors";
 
 const kFs = Symbol('kFs');
+const kHandle = Symbol('kHandle');
 
-function notImplemented(msg) {
-    throw new Error(msg);
+function normalizeOptions(opts) {
+    if (typeof (opts) === "string") {
+        validateEncoding(opts, "encoding");
+        return { encoding: opts };
+    }
+    if (opts === null || opts === undefined) {
+        return {};
+    }
+    validateObject(opts, "options");
+    if (opts.encoding) {
+        validateEncoding(opts.encoding, "encoding");
+    }
+    return opts;
 }
 
-export class WriteStreamClass extends Writable {
-    fd = null;
-    bytesWritten = 0;
-    pos = 0;
-    [kFs] = {
-        open: fs.open,
-        write: fs.write
-    };
-    [kIsPerformingIO] = false;
+// `options.fd` is a number or a FileHandle, which then also closes it
+function importFd(stream, opts) {
+    if (opts.fd instanceof fs.FileHandle) {
+        stream[kHandle] = opts.fd;
+        return opts.fd.fd;
+    }
+    if (opts.fd !== undefined && opts.fd !== null) {
+        validateInteger(opts.fd, "fd", 0, 2147483647);
+        return opts.fd;
+    }
+    return null;
+}
+
+function initStream(stream, path, opts, flags) {
+    // looked up on every call so a patched `fs.close` is the one used
+    stream[kFs] = opts.fs ? { open: fs.open, close: fs.close, ...opts.fs } : fs;
+    stream.fd = importFd(stream, opts);
+    stream.path = toPathIfFileURL(path);
+    stream.flags = opts.flags ?? flags;
+    stream.mode = opts.mode ?? 0o666;
+    stream.start = opts.start;
+    if (stream.start !== undefined) {
+        validateInteger(stream.start, "start", 0);
+    }
+}
+
+// the `_construct` of both streams, an fd that was handed over is used as is
+function openStream(stream, callback) {
+    if (stream.fd !== null) {
+        callback();
+        return;
+    }
+    stream[kFs].open(stream.path, stream.flags, stream.mode, (err, fd) => {
+        if (err) {
+            callback(err);
+            return;
+        }
+        stream.fd = fd;
+        callback();
+        stream.emit("open", fd);
+        stream.emit("ready");
+    });
+}
+
+// waits for the read or write in flight, the fd must outlive it
+function closeStream(stream, io, err, cb) {
+    if (io) {
+        io.then(() => closeStream(stream, null, err, cb));
+        return;
+    }
+    if (stream.fd === null) {
+        cb(err);
+        return;
+    }
+    const done = (er) => cb(er || err);
+    if (stream[kHandle]) {
+        stream[kHandle].close().then(() => done(), done);
+    } else {
+        stream[kFs].close(stream.fd, done);
+    }
+    stream.fd = null;
+}
+
+/**
+ * Reads the inclusive range `[start, end]` of a file through the native
+ * `FileReader`. Every chunk is an `ArrayBuffer` of its own that the pushed
+ * `Buffer` wraps without a copy, and a chunk is only read when the stream
+ * wants more, so memory stays at about one `highWaterMark` (64 KiB by
+ * default) however large the file.
+ */
+export class ReadStreamClass extends Readable {
+    #reader = null;
+    #io = null;
+
     constructor(path, opts) {
-        super(opts);
-        if (typeof (opts) === "string") {
-            validateEncoding(opts, "encoding");
-            opts = {};
+        opts = normalizeOptions(opts);
+        super({
+            highWaterMark: opts.highWaterMark ?? 64 * 1024,
+            encoding: opts.encoding,
+            autoDestroy: opts.autoClose ?? true,
+            emitClose: opts.emitClose ?? true,
+            signal: opts.signal,
+        });
+        initStream(this, path, opts, "r");
+        this.end = opts.end ?? Infinity;
+        if (this.end !== Infinity) {
+            validateInteger(this.end, "end", 0);
+            if ((this.start ?? 0) > this.end) {
+                throw new ERR_OUT_OF_RANGE("start", `<= "end" (here: ${this.end})`, this.start);
+            }
         }
-        if (opts === null || opts === undefined) {
-            opts = {};
+        this.bytesRead = 0;
+    }
+
+    get pending() {
+        return this.fd === null;
+    }
+
+    _construct(callback) {
+        openStream(this, (err) => {
+            if (!err) {
+                this.#reader = new FileReader(this.fd, this.start ?? -1, this.end);
+            }
+            callback(err);
+        });
+    }
+
+    _read(n) {
+        this.#io = this.#reader.read(n).then((chunk) => {
+            this.#io = null;
+            if (this.destroyed) {
+                return;
+            }
+            if (chunk === null) {
+                this.push(null);
+                return;
+            }
+            this.bytesRead += chunk.byteLength;
+            this.push(Buffer.from(chunk));
+        }, (err) => {
+            this.#io = null;
+            this.destroy(err);
+        });
+    }
+
+    _destroy(err, cb) {
+        closeStream(this, this.#io, err, cb);
+    }
+
+    close(cb) {
+        if (typeof (cb) === "function") {
+            finished(this, cb);
         }
-        validateObject(opts, "options");
+        this.destroy();
+    }
+}
+
+/**
+ * Writes through the native `FileWriter` from `start` on, or from the
+ * current offset. Buffered chunks go down in one `_writev` call.
+ */
+export class WriteStreamClass extends Writable {
+    #writer = null;
+    #io = null;
+
+    constructor(path, opts) {
+        opts = normalizeOptions(opts);
+        super({
+            highWaterMark: opts.highWaterMark,
+            autoDestroy: opts.autoClose ?? true,
+            emitClose: opts.emitClose ?? true,
+            signal: opts.signal,
+        });
+        initStream(this, path, opts, "w");
         if (opts.encoding) {
-            validateEncoding(opts.encoding, "encoding");
             this.setDefaultEncoding(opts.encoding);
         }
-        if (opts.start) {
-            validateInteger(opts.start, "start");
-        }
-        this.pending = true;
-        this.path = toPathIfFileURL(path);
-        this.flags = opts.flags || "w";
-        this.mode = opts.mode || 0o666;
-        this[kFs] = opts.fs ?? {
-            open: fs.open, write: fs.write, close: fs.close
-        };
+        this.bytesWritten = 0;
+    }
+
+    get pending() {
+        return this.fd === null;
     }
 
     _construct(callback) {
-        this[kFs].open(
-            this.path.toString(),
-            this.flags,
-            this.mode,
-            (err, fd) => {
-                if (err) {
-                    callback(err);
-                    return;
-                }
-                this.pending = false;
-                this.fd = fd;
-                callback();
-                this.emit("open", this.fd);
-                this.emit("ready");
-            },
-        );
-    }
-
-    _write(
-        data,
-        _encoding,
-        cb,
-    ) {
-        this[kIsPerformingIO] = true;
-        this[kFs].write(
-            this.fd,
-            data,
-            0,
-            data.length,
-            this.pos,
-            (er) => {
-                this[kIsPerformingIO] = false;
-                if (this.destroyed) {
-                    // Tell ._destroy() that it's safe to close the fd now.
-                    cb(er);
-                    return this.emit(kIoDone, er);
-                }
-
-                if (er) {
-                    return cb(er);
-                }
-
-                this.bytesWritten += bytes;
-                cb();
-            },
-        );
-
-        if (this.pos !== undefined) {
-            this.pos += data.length;
-        }
+        openStream(this, (err) => {
+            if (!err) {
+                this.#writer = new FileWriter(this.fd, this.start ?? -1);
+            }
+            callback(err);
+        });
+    }
+
+    _write(chunk, encoding, cb) {
+        this._writev([{ chunk, encoding }], cb);
+    }
+
+    _writev(chunks, cb) {
+        this.#io = this.#writer.write(chunks.map(({ chunk }) => chunk)).then((n) => {
+            this.#io = null;
+            this.bytesWritten += n;
+            cb();
+        }, (err) => {
+            this.#io = null;
+            cb(err);
+        });
     }
 
     _destroy(err, cb) {
-        if (this[kIsPerformingIO]) {
-            this.once(kIoDone, (er) => closeStream(this, err || er, cb));
+        closeStream(this, this.#io, err, cb);
+    }
+
+    close(cb) {
+        if (typeof (cb) === "function") {
+            finished(this, cb);
+        }
+        if (this.writableEnded) {
+            this.destroy();
         } else {
-            closeStream(this, err, cb);
+            this.once("finish", () => this.destroy());
+            this.end();
         }
     }
-}
 
-function closeStream(
-    stream,
-    err,
-    cb,
-) {
-    if (!stream.fd) {
-        cb(err);
-    } else {
-        stream[kFs].close(stream.fd, (er) => {
-            cb(er || err);
-        });
-        stream.fd = null;
+    destroySoon() {
+        this.end();
     }
 }
 
-export function WriteStream(
-    path,
-    opts,
-) {
-    return new WriteStreamClass(path, opts);
+// node lets both be called without `new`
+export function ReadStream(path, opts) {
+    return new ReadStreamClass(path, opts);
 }
 
-WriteStream.prototype = WriteStreamClass.prototype;
+ReadStream.prototype = ReadStreamClass.prototype;
 
-export function createWriteStream(
-    path,
-    opts,
-) {
-    return new WriteStreamClass(path, opts);
+export function createReadStream(path, opts) {
+    return new ReadStreamClass(path, opts);
 }
 
-export class ReadStream extends Readable {
-    constructor(path, opts) {
-        path = path instanceof URL ? fromFileUrl(path) : path;
-        if (opts && opts.start) {
-            validateInteger(opts.start, "start");
-        }
-        if (opts && opts.end) {
-            validateInteger(opts.end, "end");
-        }
-        const hasBadOptions = opts && (
-            opts.start || opts.end || opts.fs
-        );
-        if (opts === null || typeof (opts) === "undefined") {
-            opts = "utf8";
-        }
-        if (typeof (opts) === "string") {
-            validateEncoding(opts, "encoding");
-        } else {
-            validateEncoding(opts.encoding || "utf8", "encoding");
-        }
-        // skip
-        if (hasBadOptions && false) {
-            notImplemented(
-                `fs.ReadStream.prototype.constructor with unsupported options (${JSON.stringify(opts)
-                })`,
-            );
-        }
-        const buffer = Buffer.alloc(16 * 1024);
-        let curPos = 0;
-        let notClose = true;
-        if (opts.fd) {
-            setTimeout(() => {
-                if (this.file === undefined) {
-                    this.file = opts.fd;
-                    this.pending = false;
-                    this.emit("ready");
-                }
-            }, 0);
-        } else {
-            fs.promises.open(path, fs.constants.O_RDONLY).then(f => {
-                if (this.file === undefined) {
-                    notClose = false;
-                    this.file = f;
-                    this.pending = false;
-                    this.emit("ready");
-                }
-            });
-        }
-        super({
-            autoDestroy: true,
-            emitClose: true,
-            objectMode: false,
-            read: async function (_size) {
-                try {
-                    if (this.file === undefined) {
-                        if (opts.fd) {
-                            if (opts.fd instanceof fs.FileHandle) {
-                                this.file = opts.fd;
-                            } else {
-                                this.file = new fs.FileHandle(opts.fd, path);
-                            }
-                        } else {
-                            this.file = new fs.FileHandle(fs.openSync(path, fs.constants.O_RDONLY), path);
-                            notClose = false;
-                        }
-                        this.pending = false;
-                        this.emit("ready");
-                    }
-                    opts.end = opts.end ?? fs.fstatSync(this.file.fd).size;
-                    opts.start = opts.start ?? 0;
-                    const { bytesRead: n } = await this.file.read(buffer, 0, opts.end - opts.start - curPos + 1, curPos === 0 ? opts.start : -1);
-                    curPos += n;
-                    if (n === 0) {
-                        this.emit("end");
-                    } else {
-                        this.push(n ? Buffer.from(buffer.slice(0, n)) : null);
-                        if (curPos >= opts.end) {
-                            this.emit("end");
-                        }
-                    }
-                } catch (err) {
-                    this.destroy(err);
-                }
-            },
-            destroy: (err, cb) => {
-                try {
-                    if (!notClose) {
-                        this.file.close();
-                    }
-                    // deno-lint-ignore no-empty
-                } catch { }
-                cb(err);
-            },
-        });
-        this.pending = true;
-        this.path = path;
-    }
+export function WriteStream(path, opts) {
+    return new WriteStreamClass(path, opts);
 }
 
-export function createReadStream(
-    path,
-    options,
-) {
-    return new ReadStream(path, options);
-}
+WriteStream.prototype = WriteStreamClass.prototype;
 
+export function createWriteStream(path, opts) {
+    return new WriteStreamClass(path, opts);
+}
@@ -0,0 +1,41 @@
+// Copyright Joyent, Inc. and Node.js contributors. All rights reserved. MIT license.
+
+import { Writable } from "stream";
+import { closeSync, writeSync } from "../../fs";
+
+/**
+ * A `Writable` that writes every chunk with `fs.writeSync` before it
+ * returns, for stdout and stderr, where nothing written may be lost on
+ * exit.
+ */
+export class SyncWriteStream extends Writable {
+    constructor(fd, options) {
+        options = options ?? {};
+        super({ autoDestroy: true });
+        this.fd = fd;
+        this.readable = false;
+        this.autoClose = options.autoClose ?? true;
+    }
+
+    _write(chunk, encoding, cb) {
+        try {
+            writeSync(this.fd, chunk);
+        } catch (err) {
+            cb(err);
+            return;
+        }
+        cb();
+    }
+
+    _destroy(err, cb) {
+        if (this.fd !== null && this.autoClose) {
+            closeSync(this.fd);
+        }
+        this.fd = null;
+        cb(err);
+    }
+
+    destroySoon() {
+        this.destroy();
+    }
+}
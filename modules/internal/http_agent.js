@@ -0,0 +1,270 @@
+// Connection pooling for the HTTP client. An Agent hands out connections per
+// origin, queues requests above `maxSockets` and keeps finished connections
+// around for reuse when `keepAlive` is set.
+//
+// Idle connections do not hold timers: they are expired lazily whenever the
+// pool is touched, so a pooled connection never keeps the event loop alive.
+
+import * as httpx from 'wasi_http'
+import { EventEmitter } from 'events'
+
+let nextId = 0;
+
+class PooledConnection {
+    constructor(key, conn) {
+        this.id = nextId++;
+        this.key = key;
+        this.conn = conn;
+        // carries bytes that arrived after the previous response
+        this.buf = new httpx.Buffer();
+        this.requests = 0;
+        this.idleSince = 0;
+        this.idleTimeout = undefined;
+    }
+
+    get reused() {
+        return this.requests > 1;
+    }
+
+    close() {
+        try {
+            this.conn.close();
+        } catch (_) {
+            // already closed
+        }
+    }
+}
+
+export class Agent extends EventEmitter {
+    #connect;
+
+    constructor(options = {}) {
+        super();
+        options = options ?? {};
+        this.options = { ...options };
+        this.keepAlive = options.keepAlive ?? false;
+        this.keepAliveMsecs = options.keepAliveMsecs ?? 1000;
+        this.maxSockets = options.maxSockets ?? Infinity;
+        this.maxTotalSockets = options.maxTotalSockets ?? Infinity;
+        this.maxFreeSockets = options.maxFreeSockets ?? 256;
+        // how long a free socket may stay idle before it is closed
+        this.timeout = options.timeout ?? 5000;
+        this.scheduling = options.scheduling ?? 'lifo';
+        if (!(this.maxSockets > 0) || !(this.maxTotalSockets > 0)) {
+            throw new RangeError('maxSockets and maxTotalSockets must be positive');
+        }
+        this.sockets = {};
+        this.freeSockets = {};
+        this.requests = {};
+        this.#connect = options.connect;
+        this.destroyed = false;
+    }
+
+    getName(options = {}) {
+        const protocol = options.protocol ?? 'http:';
+        const port = options.port || (protocol === 'https:' ? 443 : 80);
+        let name = `${protocol}//${options.host ?? options.hostname ?? 'localhost'}:${port}`;
+        if (options.servername && options.servername !== options.host) {
+            name += `:${options.servername}`;
+        }
+        return name;
+    }
+
+    get totalSocketCount() {
+        let n = 0;
+        for (const list of [...Object.values(this.sockets), ...Object.values(this.freeSockets)]) {
+            n += list.length;
+        }
+        return n;
+    }
+
+    #expireIdle(now = Date.now()) {
+        for (const key of Object.keys(this.freeSockets)) {
+            const list = this.freeSockets[key].filter((pooled) => {
+                if (now - pooled.idleSince >= pooled.idleTimeout) {
+                    pooled.close();
+                    this.emit('free-timeout', pooled);
+                    return false;
+                }
+                return true;
+            });
+            if (list.length === 0) {
+                delete this.freeSockets[key];
+            } else {
+                this.freeSockets[key] = list;
+            }
+        }
+    }
+
+    #track(pooled) {
+        (this.sockets[pooled.key] ??= []).push(pooled);
+        pooled.requests++;
+        return pooled;
+    }
+
+    #untrack(pooled) {
+        const list = this.sockets[pooled.key];
+        if (list === undefined) {
+            return false;
+        }
+        const i = list.indexOf(pooled);
+        if (i < 0) {
+            return false;
+        }
+        list.splice(i, 1);
+        if (list.length === 0) {
+            delete this.sockets[pooled.key];
+        }
+        return true;
+    }
+
+    #takeFree(key) {
+        const list = this.freeSockets[key];
+        if (list === undefined) {
+            return undefined;
+        }
+        const pooled = this.scheduling === 'fifo' ? list.shift() : list.pop();
+        if (list.length === 0) {
+            delete this.freeSockets[key];
+        }
+        return pooled;
+    }
+
+    #hasCapacity(key) {
+        const active = this.sockets[key]?.length ?? 0;
+        return active < this.maxSockets && this.totalSocketCount < this.maxTotalSockets;
+    }
+
+    #evictFree() {
+        // make room under maxTotalSockets by closing the oldest free socket
+        let oldest = undefined;
+        for (const list of Object.values(this.freeSockets)) {
+            for (const pooled of list) {
+                if (oldest === undefined || pooled.idleSince < oldest.idleSince) {
+                    oldest = pooled;
+                }
+            }
+        }
+        if (oldest === undefined) {
+            return false;
+        }
+        const list = this.freeSockets[oldest.key];
+        list.splice(list.indexOf(oldest), 1);
+        if (list.length === 0) {
+            delete this.freeSockets[oldest.key];
+        }
+        oldest.close();
+        return true;
+    }
+
+    async #create(key, options) {
+        const connect = options.connect ?? this.#connect;
+        const conn = await connect(options);
+        return new PooledConnection(key, conn);
+    }
+
+    /**
+     * Resolves with a pooled connection for `options` (protocol, host, port).
+     * `options.connect(options)` opens a new connection when none is free,
+     * `options.fresh` skips the free list.
+     */
+    async acquire(options) {
+        if (this.destroyed) {
+            throw new Error('Agent has been destroyed');
+        }
+        const key = this.getName(options);
+        this.#expireIdle();
+        if (!options.fresh) {
+            const free = this.#takeFree(key);
+            if (free !== undefined) {
+                return this.#track(free);
+            }
+        }
+        if (!this.#hasCapacity(key)) {
+            const active = this.sockets[key]?.length ?? 0;
+            if (!(active < this.maxSockets) || !this.#evictFree()) {
+                await new Promise((resolve, reject) => {
+                    (this.requests[key] ??= []).push({ resolve, reject, options });
+                });
+                return await this.acquire({ ...options, fresh: false });
+            }
+        }
+        // reserve the slot before connecting so concurrent acquires see it
+        const placeholder = { key, placeholder: true };
+        (this.sockets[key] ??= []).push(placeholder);
+        try {
+            const pooled = await this.#create(key, options);
+            this.#untrack(placeholder);
+            return this.#track(pooled);
+        } catch (e) {
+            this.#untrack(placeholder);
+            this.#next(key);
+            throw e;
+        }
+    }
+
+    // wakes the first request waiting for a slot on `key`, or on any origin
+    // when only the maxTotalSockets limit was holding requests back
+    #next(key) {
+        if (this.requests[key] === undefined) {
+            key = Object.keys(this.requests)[0];
+        }
+        const queue = this.requests[key];
+        if (queue === undefined) {
+            return;
+        }
+        const waiter = queue.shift();
+        if (queue.length === 0) {
+            delete this.requests[key];
+        }
+        waiter?.resolve();
+    }
+
+    /**
+     * Returns a connection after its response has been read. Reusable
+     * connections of a keep-alive agent go back to the free list, everything
+     * else is closed. `idleTimeout` is the server's `Keep-Alive: timeout`.
+     */
+    release(pooled, reusable, idleTimeout = undefined) {
+        if (!this.#untrack(pooled)) {
+            return;
+        }
+        const freeCount = this.freeSockets[pooled.key]?.length ?? 0;
+        if (reusable && this.keepAlive && !this.destroyed && freeCount < this.maxFreeSockets) {
+            pooled.idleSince = Date.now();
+            pooled.idleTimeout = Math.min(this.timeout, idleTimeout ?? Infinity);
+            (this.freeSockets[pooled.key] ??= []).push(pooled);
+            this.emit('free', pooled);
+        } else {
+            pooled.close();
+        }
+        this.#expireIdle();
+        this.#next(pooled.key);
+    }
+
+    destroy() {
+        this.destroyed = true;
+        for (const list of [...Object.values(this.sockets), ...Object.values(this.freeSockets)]) {
+            for (const pooled of list) {
+                if (!pooled.placeholder) {
+                    pooled.close();
+                }
+            }
+        }
+        for (const queue of Object.values(this.requests)) {
+            for (const waiter of queue) {
+                waiter.reject(new Error('Agent has been destroyed'));
+            }
+        }
+        this.sockets = {};
+        this.freeSockets = {};
+        this.requests = {};
+    }
+}
+
+export const globalAgent = new Agent({ keepAlive: true, timeout: 5000 });
+
+export default {
+    Agent,
+    globalAgent,
+};
@@ -0,0 +1,132 @@
+// `process.stdin`, `process.stdout` and `process.stderr`, loaded by
+// process.js on first use.
+
+import { Readable, Writable } from "stream";
+import { Buffer } from "buffer";
+import { SyncWriteStream } from "../fs/sync_write_stream";
+import { wasiFsSyscallErrorMap } from "../fs/errno";
+import { fwrite } from "_node:fs";
+import { guessHandleType, stdinRead, stdinReadStop } from "_node:process";
+import { _setStdio } from "process";
+
+/**
+ * stdin, every chunk is read once fd 0 has input, through the poll of the
+ * event loop. No read waits while it is paused, so a paused stdin does not
+ * keep the event loop from ending.
+ */
+class StdinStream extends Readable {
+    #paused = false;
+    // `_read` was called but no read waits for input
+    #stopped = false;
+
+    constructor() {
+        super({ highWaterMark: 64 * 1024 });
+        this.fd = 0;
+        this.isRaw = false;
+    }
+
+    _read(n) {
+        if (this.#paused) {
+            this.#stopped = true;
+            return;
+        }
+        stdinRead(n).then((chunk) => {
+            // `undefined` when `pause()` stopped the read
+            if (chunk === undefined || this.destroyed) {
+                return;
+            }
+            this.push(chunk.byteLength === 0 ? null : Buffer.from(chunk));
+        }, (err) => {
+            this.destroy(wasiFsSyscallErrorMap(err, "read"));
+        });
+    }
+
+    pause() {
+        this.#paused = true;
+        if (stdinReadStop()) {
+            this.#stopped = true;
+        }
+        return super.pause();
+    }
+
+    resume() {
+        this.#paused = false;
+        super.resume();
+        if (this.#stopped && !this.destroyed) {
+            this.#stopped = false;
+            this._read(this.readableHighWaterMark);
+        }
+        return this;
+    }
+
+    _destroy(err, cb) {
+        stdinReadStop();
+        cb(err);
+    }
+
+    /**
+     * WASI cannot switch the terminal out of line mode, input still comes a
+     * line at a time once enter is pressed. Kept so that code written for a
+     * TTY runs.
+     */
+    setRawMode(mode) {
+        this.isRaw = Boolean(mode);
+        return this;
+    }
+}
+
+/**
+ * stdout or stderr on a terminal or pipe, written through the poll of the
+ * event loop. `write()` returns `false` once more than `highWaterMark`
+ * bytes wait to go out, 'drain' follows when they are written.
+ */
+class StdioWriteStream extends Writable {
+    constructor(fd) {
+        super();
+        this.fd = fd;
+    }
+
+    _write(chunk, encoding, cb) {
+        this._writev([{ chunk, encoding }], cb);
+    }
+
+    _writev(chunks, cb) {
+        writeAll(this.fd, Buffer.concat(chunks.map(({ chunk }) => chunk)), cb);
+    }
+}
+
+// a terminal or pipe may take less than all of it at once
+function writeAll(fd, data, cb) {
+    const buffer = data.buffer.slice(data.byteOffset, data.byteOffset + data.byteLength);
+    fwrite(fd, -1, buffer).then((n) => {
+        if (n < data.byteLength) {
+            writeAll(fd, data.subarray(n), cb);
+        } else {
+            cb();
+        }
+    }, (err) => {
+        cb(wasiFsSyscallErrorMap(err, "write"));
+    });
+}
+
+function markTTY(stream, type) {
+    if (type === "TTY") {
+        stream.isTTY = true;
+    }
+    return stream;
+}
+
+// a file is written right away as in node, it stays open for the whole run
+function createWritable(fd) {
+    const type = guessHandleType(fd);
+    if (type === "FILE") {
+        return new SyncWriteStream(fd, { autoClose: false });
+    }
+    return markTTY(new StdioWriteStream(fd), type);
+}
+
+_setStdio({
+    stdin: markTTY(new StdinStream(), guessHandleType(0)),
+    stdout: createWritable(1),
+    stderr: createWritable(2),
+});
@@ -0,0 +1,209 @@
+// The line splitting of `readline.Interface`, also what
+// `FileHandle.readLines()` returns. There is no line editing: the input
+// comes from a file, a pipe or a terminal in line mode.
+
+import EventEmitter from "../../events";
+import { StringDecoder } from "../../string_decoder";
+import { validateFunction, validateString } from "../validators";
+
+/**
+ * Emits every line of `input` as 'line', then 'close' at its end. It can
+ * also be read with `for await`, the input then only reads on once the
+ * lines of the last chunk were taken. `question()` writes to `output` and
+ * hands the next line to its callback instead.
+ */
+export class Interface extends EventEmitter {
+    #partial = "";
+    #queue = [];
+    #iterating = false;
+    #wake = null;
+    #error = null;
+    #decoder = new StringDecoder("utf8");
+    #questionCallback = null;
+    #prompt = "> ";
+
+    constructor(input, output, completer, terminal) {
+        super();
+        if (input?.input !== undefined) {
+            output = input.output;
+            terminal = input.terminal;
+            if (input.prompt !== undefined) {
+                validateString(input.prompt, "prompt");
+                this.#prompt = input.prompt;
+            }
+            input = input.input;
+        }
+        this.input = input;
+        this.output = output;
+        this.terminal = Boolean(terminal ?? output?.isTTY);
+        this.line = "";
+        this.closed = false;
+        this.paused = false;
+        input.on("data", (chunk) => this.#onData(chunk));
+        input.on("end", () => {
+            const rest = this.#partial + this.#decoder.end();
+            this.#partial = "";
+            if (rest.length > 0) {
+                this.#onLine(rest);
+            }
+            this.close();
+        });
+        input.on("error", (err) => {
+            this.#error = err;
+            this.#notify();
+            if (!this.#iterating || this.listenerCount("error") > 0) {
+                this.emit("error", err);
+            }
+        });
+    }
+
+    #onData(chunk) {
+        if (typeof (chunk) !== "string") {
+            chunk = this.#decoder.write(chunk);
+        }
+        const lines = (this.#partial + chunk).split(/\r?\n/);
+        this.#partial = lines.pop();
+        this.line = this.#partial;
+        for (const line of lines) {
+            this.#onLine(line);
+        }
+        if (this.#iterating && this.#queue.length > 0) {
+            this.input.pause();
+        }
+    }
+
+    #onLine(line) {
+        if (this.#questionCallback !== null) {
+            const callback = this.#questionCallback;
+            this.#questionCallback = null;
+            callback(line);
+            return;
+        }
+        if (this.#iterating) {
+            this.#queue.push(line);
+            this.#notify();
+        }
+        this.emit("line", line);
+    }
+
+    #notify() {
+        const wake = this.#wake;
+        this.#wake = null;
+        wake?.();
+    }
+
+    getPrompt() {
+        return this.#prompt;
+    }
+
+    setPrompt(prompt) {
+        validateString(prompt, "prompt");
+        this.#prompt = prompt;
+    }
+
+    /** Writes the prompt to `output` and reads on. */
+    prompt() {
+        if (this.closed) {
+            return;
+        }
+        this.resume();
+        this.output?.write(this.#prompt);
+    }
+
+    /**
+     * Writes `query` to `output`, the next line goes to `callback` and not
+     * to the 'line' listeners. Ignored while another question waits.
+     */
+    question(query, options, callback) {
+        if (typeof (options) === "function") {
+            callback = options;
+            options = {};
+        }
+        validateFunction(callback, "callback");
+        if (this.closed || this.#questionCallback !== null) {
+            return;
+        }
+        const signal = options?.signal;
+        if (signal?.aborted) {
+            return;
+        }
+        this.#questionCallback = (answer) => {
+            signal?.removeEventListener("abort", onAbort);
+            callback(answer);
+        };
+        const onAbort = () => {
+            this.#questionCallback = null;
+        };
+        signal?.addEventListener("abort", onAbort, { once: true });
+        this.resume();
+        this.output?.write(query);
+    }
+
+    /** Takes `data` as if it came from `input`. */
+    write(data) {
+        if (!this.closed) {
+            this.#onData(data);
+        }
+    }
+
+    pause() {
+        if (!this.paused) {
+            this.paused = true;
+            this.input.pause();
+            this.emit("pause");
+        }
+        return this;
+    }
+
+    resume() {
+        if (this.paused) {
+            this.paused = false;
+            this.input.resume();
+            this.emit("resume");
+        }
+        return this;
+    }
+
+    close() {
+        if (this.closed) {
+            return;
+        }
+        this.pause();
+        this.closed = true;
+        this.#questionCallback = null;
+        this.#notify();
+        this.emit("close");
+    }
+
+    [Symbol.asyncIterator]() {
+        this.#iterating = true;
+        const next = () => {
+            if (this.#queue.length > 0) {
+                const value = this.#queue.shift();
+                if (this.#queue.length === 0 && !this.closed) {
+                    this.input.resume();
+                }
+                return Promise.resolve({ value, done: false });
+            }
+            if (this.#error !== null) {
+                return Promise.reject(this.#error);
+            }
+            if (this.closed) {
+                return Promise.resolve({ value: undefined, done: true });
+            }
+            return new Promise((resolve) => {
+                this.#wake = resolve;
+            }).then(next);
+        };
+        return {
+            next,
+            return: () => {
+                this.close();
+                return Promise.resolve({ value: undefined, done: true });
+            },
+            [Symbol.asyncIterator]() {
+                return this;
+            },
+        };
+    }
+}
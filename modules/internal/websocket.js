@@ -0,0 +1,774 @@
+// WebSocket (RFC 6455) on top of `wasi_net`/`wasi_tls`. Framing, masking
+// and permessage-deflate are done by the native `WebSocketCodec`.
+//
+// `WebSocket` is the browser class and, like the `ws` package, also an
+// EventEmitter emitting "open", "message" (data, isBinary), "ping", "pong",
+// "close" (code, reason) and "error". `WebSocketServer` accepts upgrades
+// from an `http.Server`.
+
+import * as net from 'wasi_net'
+import * as httpx from 'wasi_http'
+import process from 'process'
+import { Buffer } from 'buffer'
+import { EventEmitter } from 'events'
+import { URL } from 'whatwg_url'
+import { TextEncoder } from 'util'
+import { Blob } from 'internal/fetch'
+import { DOMException } from 'internal/abort_controller'
+import http from 'http'
+
+const CONNECTING = 0;
+const OPEN = 1;
+const CLOSING = 2;
+const CLOSED = 3;
+
+// how long a closing handshake may take before the socket is dropped
+const CLOSE_TIMEOUT = 30000;
+
+const kAccept = Symbol('kAccept');
+const encoder = new TextEncoder();
+
+export class Event {
+    constructor(type, init = {}) {
+        this.type = `${type}`;
+        this.target = init.target ?? null;
+        this.currentTarget = this.target;
+        this.timeStamp = Date.now();
+    }
+}
+
+export class MessageEvent extends Event {
+    constructor(type, init = {}) {
+        super(type, init);
+        this.data = init.data;
+        this.origin = init.origin ?? '';
+    }
+}
+
+export class CloseEvent extends Event {
+    constructor(type, init = {}) {
+        super(type, init);
+        this.code = init.code ?? 0;
+        this.reason = init.reason ?? '';
+        this.wasClean = !!init.wasClean;
+    }
+}
+
+export class ErrorEvent extends Event {
+    constructor(type, init = {}) {
+        super(type, init);
+        this.error = init.error;
+        this.message = init.message ?? init.error?.message ?? '';
+    }
+}
+
+// The peer connection as `read()`, `write()`, `end()` and `destroy()`, the
+// shape of the server side socket of `http.Server`.
+function connTransport(conn) {
+    let pendingRead;
+    let destroyed = false;
+    return {
+        read() {
+            if (destroyed) {
+                return Promise.resolve(undefined);
+            }
+            return new Promise((resolve) => {
+                pendingRead = resolve;
+                conn.read().then(resolve, () => resolve(undefined)).finally(() => {
+                    pendingRead = undefined;
+                });
+            });
+        },
+        write(data, cb) {
+            if (!destroyed) {
+                conn.write(data, cb);
+            }
+        },
+        end() {
+            if (destroyed) {
+                return;
+            }
+            Promise.resolve(conn.shutdown?.()).catch(() => { }).finally(() => this.destroy());
+        },
+        destroy() {
+            if (destroyed) {
+                return;
+            }
+            destroyed = true;
+            try {
+                conn.close();
+            } catch {
+                // already closed
+            }
+            pendingRead?.(undefined);
+        },
+    };
+}
+
+function toArrayBuffer(data) {
+    if (data instanceof ArrayBuffer) {
+        return data;
+    }
+    return data.buffer.slice(data.byteOffset, data.byteOffset + data.byteLength);
+}
+
+function isValidCloseCode(code) {
+    return code === 1000 || (code >= 3000 && code <= 4999);
+}
+
+async function connect(url) {
+    const port = url.port !== '' ? Number(url.port) : url.protocol === 'wss:' ? 443 : 80;
+    if (url.protocol === 'wss:') {
+        if (!process.features.tls) {
+            throw new TypeError(`${url.href}: wss is not supported, wasmedge_quickjs was built without the tls feature`);
+        }
+        const tls = await import('tls');
+        return await tls.rawConnect({ host: url.hostname, port, ALPNProtocols: ['http/1.1'] });
+    }
+    const addr = net.nsloopup(url.hostname, `${port}`)[0];
+    if (addr === undefined) {
+        throw new TypeError(`getaddrinfo ENOTFOUND ${url.hostname}`);
+    }
+    return await net.WasiTcpConn.connect(addr);
+}
+
+// Sends the opening handshake and checks the answer. Resolves with the
+// negotiated protocol, the extension header and the bytes after the head.
+async function handshake(conn, url, protocols, perMessageDeflate) {
+    const key = httpx.websocketKey();
+    let head = `GET ${url.pathname}${url.search} HTTP/1.1\r\n`
+        + `Host: ${url.host}\r\n`
+        + 'Upgrade: websocket\r\n'
+        + 'Connection: Upgrade\r\n'
+        + `Sec-WebSocket-Key: ${key}\r\n`
+        + 'Sec-WebSocket-Version: 13\r\n'
+        + `User-Agent: wasmedge_quickjs/${process.version}\r\n`;
+    if (perMessageDeflate) {
+        head += 'Sec-WebSocket-Extensions: permessage-deflate; client_max_window_bits\r\n';
+    }
+    if (protocols.length > 0) {
+        head += `Sec-WebSocket-Protocol: ${protocols.join(', ')}\r\n`;
+    }
+    conn.write(head + '\r\n');
+
+    const buf = new httpx.Buffer();
+    let resp;
+    while (true) {
+        resp = buf.parseResponse({});
+        if (resp instanceof httpx.WasiResponse) {
+            break;
+        }
+        if (resp instanceof Error) {
+            throw new TypeError(`Invalid handshake response: ${resp.message}`);
+        }
+        const data = await conn.read();
+        if (data === undefined) {
+            throw new TypeError('Connection closed during the WebSocket handshake');
+        }
+        buf.write(data);
+    }
+    const headers = resp.headers;
+    if (resp.status !== 101) {
+        throw new TypeError(`Unexpected response status ${resp.status} to the WebSocket handshake`);
+    }
+    if (`${headers['upgrade']}`.toLowerCase() !== 'websocket'
+        || !/(^|,)\s*upgrade\s*(,|$)/i.test(headers['connection'] ?? '')) {
+        throw new TypeError('The server did not upgrade the connection to WebSocket');
+    }
+    if (headers['sec-websocket-accept'] !== httpx.websocketAccept(key)) {
+        throw new TypeError('Invalid Sec-WebSocket-Accept header');
+    }
+    const protocol = headers['sec-websocket-protocol'] ?? '';
+    if (protocol !== '' && !protocols.includes(protocol)) {
+        throw new TypeError(`The server selected the unrequested subprotocol ${protocol}`);
+    }
+    return { protocol, extensions: headers['sec-websocket-extensions'] ?? '', rest: buf.take() };
+}
+
+export class WebSocket extends EventEmitter {
+    static CONNECTING = CONNECTING;
+    static OPEN = OPEN;
+    static CLOSING = CLOSING;
+    static CLOSED = CLOSED;
+
+    #url = '';
+    #origin = '';
+    #isClient = false;
+    #readyState = CONNECTING;
+    #protocol = '';
+    #extensions = '';
+    #binaryType = 'blob';
+    #bufferedAmount = 0;
+    #transport = null;
+    #codec = null;
+    #closeSent = false;
+    #closeTimer = undefined;
+    #closeEmitted = false;
+    #handlers = {};
+    #listeners = new Map();
+    #sendChain = Promise.resolve();
+    #pendingSends = 0;
+
+    constructor(url, protocols = [], options = undefined) {
+        super();
+        if (url === kAccept) {
+            return;
+        }
+        let parsed;
+        try {
+            parsed = new URL(`${url}`);
+        } catch {
+            throw new DOMException(`Invalid URL: ${url}`, 'SyntaxError');
+        }
+        if (parsed.protocol === 'http:' || parsed.protocol === 'https:') {
+            parsed.protocol = parsed.protocol === 'http:' ? 'ws:' : 'wss:';
+        }
+        if (parsed.protocol !== 'ws:' && parsed.protocol !== 'wss:') {
+            throw new DOMException(`The URL's scheme must be ws or wss, got ${parsed.protocol}`, 'SyntaxError');
+        }
+        if (parsed.hash !== '') {
+            throw new DOMException('The URL must not contain a fragment', 'SyntaxError');
+        }
+        protocols = typeof protocols === 'string' ? [protocols] : [...protocols];
+        if (new Set(protocols).size !== protocols.length
+            || protocols.some((p) => !/^[!#$%&'*+\-.^_`|~0-9A-Za-z]+$/.test(p))) {
+            throw new DOMException('Invalid or duplicated subprotocol', 'SyntaxError');
+        }
+        this.#url = parsed.href;
+        this.#origin = parsed.origin;
+        this.#isClient = true;
+        this.#open(parsed, protocols, options ?? {});
+    }
+
+    async #open(url, protocols, options) {
+        let conn;
+        try {
+            conn = await connect(url);
+            if (this.#readyState !== CONNECTING) {
+                conn.close();
+                return;
+            }
+            const { protocol, extensions, rest } = await handshake(conn, url, protocols, options.perMessageDeflate !== false);
+            if (this.#readyState !== CONNECTING) {
+                conn.close();
+                return;
+            }
+            const codec = new httpx.WebSocketCodec({
+                client: true,
+                extensions,
+                maxPayload: options.maxPayload,
+                perMessageDeflate: options.perMessageDeflate,
+            });
+            this.#start(connTransport(conn), codec, rest, protocol, extensions);
+        } catch (e) {
+            try {
+                conn?.close();
+            } catch {
+                // already closed
+            }
+            this.#emit('error', e);
+            this.#finish(1006, '', false);
+        }
+    }
+
+    // A server side WebSocket on the socket of an accepted upgrade.
+    static [kAccept](socket, codec, head, protocol, extensions) {
+        const ws = new WebSocket(kAccept);
+        ws.#binaryType = 'nodebuffer';
+        ws.#start(socket, codec, head, protocol, extensions);
+        return ws;
+    }
+
+    #start(transport, codec, head, protocol, extensions) {
+        this.#transport = transport;
+        this.#codec = codec;
+        this.#protocol = protocol;
+        this.#extensions = extensions;
+        this.#readyState = OPEN;
+        if (head && head.byteLength > 0) {
+            codec.push(toArrayBuffer(head));
+        }
+        this.#emit('open');
+        this.#readLoop();
+    }
+
+    async #readLoop() {
+        try {
+            while (true) {
+                let message;
+                while ((message = this.#codec.next()) !== undefined) {
+                    this.#onMessage(message);
+                }
+                const data = await this.#transport.read();
+                if (data === undefined) {
+                    break;
+                }
+                this.#codec.push(data);
+            }
+            this.#transport.destroy();
+            this.#finish(1006, '', false);
+        } catch (e) {
+            // a protocol error, the close code tells which
+            this.#sendClose(e.closeCode ?? 1002, e.message);
+            this.#transport.end();
+            this.#emit('error', e);
+            this.#finish(1006, '', false);
+        }
+    }
+
+    #onMessage(message) {
+        if (this.#closeEmitted) {
+            return;
+        }
+        switch (message.type) {
+            case 'text':
+            case 'binary':
+                if (this.#readyState === OPEN) {
+                    this.#emit('message', message.data, message.type === 'binary');
+                }
+                break;
+            case 'ping':
+                if (!this.#closeSent) {
+                    this.#transport.write(this.#codec.encode('pong', message.data));
+                }
+                this.#emit('ping', Buffer.from(message.data));
+                break;
+            case 'pong':
+                this.#emit('pong', Buffer.from(message.data));
+                break;
+            case 'close':
+                // echo the close frame; the server closes the TCP connection,
+                // a client waits for that a little while
+                this.#sendClose(message.code, message.reason);
+                this.#finish(message.code ?? 1005, message.reason ?? '', true);
+                if (this.#isClient) {
+                    this.#closeTimer = setTimeout(() => this.#transport.destroy(), 1000);
+                } else {
+                    this.#transport.end();
+                }
+                break;
+        }
+    }
+
+    #sendClose(code, reason) {
+        if (this.#closeSent) {
+            return;
+        }
+        this.#closeSent = true;
+        this.#transport.write(code === undefined
+            ? this.#codec.encode('close')
+            : this.#codec.encode('close', code, reason ?? ''));
+    }
+
+    #finish(code, reason, wasClean) {
+        if (this.#closeEmitted) {
+            return;
+        }
+        this.#closeEmitted = true;
+        this.#readyState = CLOSED;
+        clearTimeout(this.#closeTimer);
+        this.#emit('close', code, reason, wasClean);
+    }
+
+    // Emits to the EventEmitter listeners with `ws` package arguments and
+    // to the DOM style listeners with event objects.
+    #emit(type, ...args) {
+        if (type !== 'error' || this.listenerCount('error') > 0) {
+            if (type === 'message') {
+                super.emit(type, Buffer.from(args[0]), args[1]);
+            } else if (type === 'close') {
+                super.emit(type, args[0], Buffer.from(args[1]));
+            } else {
+                super.emit(type, ...args);
+            }
+        }
+        let event;
+        switch (type) {
+            case 'open':
+                event = new Event('open', { target: this });
+                break;
+            case 'message':
+                event = new MessageEvent('message', { target: this, data: this.#messageData(args[0]), origin: this.#origin });
+                break;
+            case 'close':
+                event = new CloseEvent('close', { target: this, code: args[0], reason: args[1], wasClean: args[2] });
+                break;
+            case 'error':
+                event = new ErrorEvent('error', { target: this, error: args[0] });
+                break;
+            default:
+                return;
+        }
+        this.dispatchEvent(event);
+    }
+
+    #messageData(data) {
+        if (typeof data === 'string') {
+            return data;
+        }
+        switch (this.#binaryType) {
+            case 'arraybuffer':
+                return data;
+            case 'nodebuffer':
+                return Buffer.from(data);
+            default:
+                return new Blob([data]);
+        }
+    }
+
+    addEventListener(type, listener, options = undefined) {
+        if (listener === null || listener === undefined) {
+            return;
+        }
+        const list = this.#listeners.get(type) ?? [];
+        if (list.some((l) => l.listener === listener)) {
+            return;
+        }
+        const once = typeof options === 'object' && options !== null && !!options.once;
+        list.push({ listener, once });
+        this.#listeners.set(type, list);
+    }
+
+    removeEventListener(type, listener) {
+        const list = this.#listeners.get(type);
+        if (list !== undefined) {
+            this.#listeners.set(type, list.filter((l) => l.listener !== listener));
+        }
+    }
+
+    dispatchEvent(event) {
+        const handler = this.#handlers[event.type];
+        const list = this.#listeners.get(event.type) ?? [];
+        for (const l of handler ? [{ listener: handler }, ...list] : list) {
+            if (l.once) {
+                this.removeEventListener(event.type, l.listener);
+            }
+            // a throwing listener does not stop the others
+            try {
+                if (typeof l.listener === 'function') {
+                    l.listener.call(this, event);
+                } else if (typeof l.listener?.handleEvent === 'function') {
+                    l.listener.handleEvent(event);
+                }
+            } catch (e) {
+                Promise.reject(e);
+            }
+        }
+        return true;
+    }
+
+    get onopen() {
+        return this.#handlers.open ?? null;
+    }
+    set onopen(handler) {
+        this.#handlers.open = typeof handler === 'function' ? handler : null;
+    }
+    get onmessage() {
+        return this.#handlers.message ?? null;
+    }
+    set onmessage(handler) {
+        this.#handlers.message = typeof handler === 'function' ? handler : null;
+    }
+    get onerror() {
+        return this.#handlers.error ?? null;
+    }
+    set onerror(handler) {
+        this.#handlers.error = typeof handler === 'function' ? handler : null;
+    }
+    get onclose() {
+        return this.#handlers.close ?? null;
+    }
+    set onclose(handler) {
+        this.#handlers.close = typeof handler === 'function' ? handler : null;
+    }
+
+    get url() {
+        return this.#url;
+    }
+    get readyState() {
+        return this.#readyState;
+    }
+    get protocol() {
+        return this.#protocol;
+    }
+    get extensions() {
+        return this.#extensions;
+    }
+    get bufferedAmount() {
+        return this.#bufferedAmount;
+    }
+    get binaryType() {
+        return this.#binaryType;
+    }
+    set binaryType(type) {
+        if (['blob', 'arraybuffer', 'nodebuffer'].includes(type)) {
+            this.#binaryType = type;
+        }
+    }
+
+    get CONNECTING() {
+        return CONNECTING;
+    }
+    get OPEN() {
+        return OPEN;
+    }
+    get CLOSING() {
+        return CLOSING;
+    }
+    get CLOSED() {
+        return CLOSED;
+    }
+
+    send(data) {
+        if (this.#readyState === CONNECTING) {
+            throw new DOMException('WebSocket is not open', 'InvalidStateError');
+        }
+        let size;
+        let frame;
+        if (typeof data === 'string') {
+            size = encoder.encode(data).byteLength;
+            frame = () => this.#codec.encode('text', data);
+        } else if (data instanceof ArrayBuffer || ArrayBuffer.isView(data)) {
+            const bytes = toArrayBuffer(data);
+            size = bytes.byteLength;
+            frame = () => this.#codec.encode('binary', bytes);
+        } else if (data instanceof Blob) {
+            size = data.size;
+            const bytes = data.arrayBuffer();
+            frame = async () => this.#codec.encode('binary', await bytes);
+        } else {
+            return this.send(`${data}`);
+        }
+        this.#bufferedAmount += size;
+        if (this.#readyState !== OPEN) {
+            // counted but never sent, as the spec asks
+            return;
+        }
+        this.#enqueue(frame, () => this.#bufferedAmount -= size);
+    }
+
+    // Writes frames in order. Blobs are read asynchronously, anything sent
+    // while one is pending waits for it.
+    #enqueue(frame, cb) {
+        if (this.#pendingSends === 0) {
+            const bytes = frame();
+            if (!(bytes instanceof Promise)) {
+                this.#transport.write(bytes, cb);
+                return;
+            }
+            frame = () => bytes;
+        }
+        this.#pendingSends++;
+        this.#sendChain = this.#sendChain.then(async () => {
+            const bytes = await frame();
+            if (!this.#closeSent) {
+                this.#transport.write(bytes, cb);
+            }
+        }).catch(() => { }).finally(() => this.#pendingSends--);
+    }
+
+    /** Non-standard, as in the `ws` package. */
+    ping(data = new ArrayBuffer(0)) {
+        if (this.#readyState !== OPEN) {
+            throw new DOMException('WebSocket is not open', 'InvalidStateError');
+        }
+        this.#transport.write(this.#codec.encode('ping', typeof data === 'string' ? data : toArrayBuffer(data)));
+    }
+
+    close(code = undefined, reason = undefined) {
+        if (code !== undefined && !isValidCloseCode(code)) {
+            throw new DOMException(`Invalid close code ${code}`, 'InvalidAccessError');
+        }
+        if (reason !== undefined && encoder.encode(reason).byteLength > 123) {
+            throw new DOMException('The close reason must be at most 123 bytes', 'SyntaxError');
+        }
+        if (this.#readyState === CLOSING || this.#readyState === CLOSED) {
+            return;
+        }
+        if (this.#readyState === CONNECTING) {
+            this.#readyState = CLOSING;
+            this.#emit('error', new Error('WebSocket was closed before the connection was established'));
+            this.#finish(1006, '', false);
+            return;
+        }
+        this.#readyState = CLOSING;
+        if (this.#pendingSends === 0) {
+            this.#sendClose(code, reason);
+        } else {
+            // the close frame goes out after the data queued before it
+            this.#sendChain = this.#sendChain.then(() => this.#sendClose(code, reason));
+        }
+        this.#closeTimer = setTimeout(() => {
+            this.#transport.destroy();
+            this.#finish(1006, '', false);
+        }, CLOSE_TIMEOUT);
+    }
+
+    /** Non-standard, drops the connection without a closing handshake. */
+    terminate() {
+        if (this.#readyState === CLOSED) {
+            return;
+        }
+        this.#readyState = CLOSING;
+        this.#transport?.destroy();
+        this.#finish(1006, '', false);
+    }
+}
+
+function abortHandshake(socket, status, headers = {}) {
+    let head = `HTTP/1.1 ${status} ${http.STATUS_CODES[status]}\r\nConnection: close\r\n`;
+    for (const [name, value] of Object.entries(headers)) {
+        head += `${name}: ${value}\r\n`;
+    }
+    socket.end(head + '\r\n');
+}
+
+/**
+ * Accepts WebSocket upgrades, on its own server with `port`, on the
+ * "upgrade" event of `server`, or with `noServer` through `handleUpgrade()`
+ * called by the application.
+ */
+export class WebSocketServer extends EventEmitter {
+    #server = undefined;
+    #ownServer = false;
+    #onUpgrade = undefined;
+
+    constructor(options = {}, callback = undefined) {
+        super();
+        const count = [options.port, options.server, options.noServer].filter((o) => o !== undefined && o !== false).length;
+        if (count !== 1) {
+            throw new TypeError('One and only one of the "port", "server", or "noServer" options must be specified');
+        }
+        this.options = {
+            path: undefined,
+            maxPayload: 100 * 1024 * 1024,
+            perMessageDeflate: false,
+            handleProtocols: undefined,
+            ...options,
+        };
+        this.clients = new Set();
+
+        if (options.port !== undefined) {
+            this.#server = http.createServer((req, res) => {
+                const body = http.STATUS_CODES[426];
+                res.writeHead(426, { 'Content-Length': body.length, 'Content-Type': 'text/plain' });
+                res.end(body);
+            });
+            this.#ownServer = true;
+            this.#server.listen(options.port, callback);
+        } else if (options.server !== undefined) {
+            this.#server = options.server;
+        }
+        if (this.#server !== undefined) {
+            this.#onUpgrade = (req, socket, head) => {
+                this.handleUpgrade(req, socket, head, (ws) => this.emit('connection', ws, req));
+            };
+            this.#server.on('upgrade', this.#onUpgrade);
+            this.#server.on('listening', () => this.emit('listening'));
+            this.#server.on('error', (e) => this.emit('error', e));
+        }
+    }
+
+    address() {
+        if (this.#server === undefined) {
+            throw new Error('The server is operating in "noServer" mode');
+        }
+        return this.#server.address();
+    }
+
+    shouldHandle(req) {
+        if (this.options.path === undefined) {
+            return true;
+        }
+        const i = req.url.indexOf('?');
+        return (i === -1 ? req.url : req.url.slice(0, i)) === this.options.path;
+    }
+
+    handleUpgrade(req, socket, head, cb) {
+        const key = req.headers['sec-websocket-key'];
+        if (req.method !== 'GET'
+            || `${req.headers['upgrade']}`.toLowerCase() !== 'websocket'
+            || !/^[+/0-9A-Za-z]{22}==$/.test(key ?? '')
+            || !this.shouldHandle(req)) {
+            abortHandshake(socket, 400);
+            return;
+        }
+        if (req.headers['sec-websocket-version'] !== '13') {
+            abortHandshake(socket, 426, { 'Sec-WebSocket-Version': '13' });
+            return;
+        }
+
+        let protocol = '';
+        const offered = (req.headers['sec-websocket-protocol'] ?? '').split(',').map((p) => p.trim()).filter((p) => p !== '');
+        if (offered.length > 0) {
+            protocol = this.options.handleProtocols
+                ? this.options.handleProtocols(new Set(offered), req) || ''
+                : offered[0];
+        }
+
+        let codec;
+        try {
+            codec = new httpx.WebSocketCodec({
+                client: false,
+                extensions: req.headers['sec-websocket-extensions'],
+                maxPayload: this.options.maxPayload,
+                perMessageDeflate: this.options.perMessageDeflate,
+            });
+        } catch {
+            abortHandshake(socket, 400);
+            return;
+        }
+
+        let response = 'HTTP/1.1 101 Switching Protocols\r\n'
+            + 'Upgrade: websocket\r\n'
+            + 'Connection: Upgrade\r\n'
+            + `Sec-WebSocket-Accept: ${httpx.websocketAccept(key)}\r\n`;
+        if (protocol !== '') {
+            response += `Sec-WebSocket-Protocol: ${protocol}\r\n`;
+        }
+        if (codec.extensions !== '') {
+            response += `Sec-WebSocket-Extensions: ${codec.extensions}\r\n`;
+        }
+        socket.write(response + '\r\n');
+
+        const ws = WebSocket[kAccept](socket, codec, head, protocol, codec.extensions);
+        this.clients.add(ws);
+        ws.on('close', () => this.clients.delete(ws));
+        cb(ws, req);
+    }
+
+    // Stops accepting upgrades, open connections are left alone as in `ws`.
+    close(cb) {
+        if (typeof cb === 'function') {
+            this.once('close', cb);
+        }
+        if (this.#server !== undefined) {
+            this.#server.removeListener('upgrade', this.#onUpgrade);
+            if (this.#ownServer) {
+                this.#server.close();
+            }
+            this.#server = undefined;
+        }
+        process.nextTick(() => this.emit('close'));
+    }
+}
+
+function defineGlobal(name, value) {
+    Object.defineProperty(globalThis, name, {
+        value,
+        writable: true,
+        configurable: true,
+        enumerable: false,
+    });
+}
+
+defineGlobal('WebSocket', WebSocket);
+
+export default {
+    WebSocket,
+    WebSocketServer,
+    Event,
+    MessageEvent,
+    CloseEvent,
+    ErrorEvent,
+};
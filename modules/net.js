@@ -0,0 +1,458 @@
+import * as wasi_net from 'wasi_net'
+import { Buffer } from 'buffer'
+import { EventEmitter } from 'events'
+import fs from 'fs'
+import process from 'process'
+import { Duplex } from 'stream'
+import { validatePort } from 'internal/validators'
+import { isIP, isIPv4, isIPv6, lookup } from './internal/dns'
+
+export { isIP, isIPv4, isIPv6 };
+
+function toArrayBuffer(chunk) {
+    if (typeof chunk === 'string') {
+        return chunk;
+    }
+    if (chunk instanceof ArrayBuffer) {
+        return chunk;
+    }
+    return chunk.buffer.slice(chunk.byteOffset, chunk.byteOffset + chunk.byteLength);
+}
+
+function splitAddress(addr) {
+    let i = addr.lastIndexOf(':');
+    let address = addr.slice(0, i);
+    if (address.startsWith('[')) {
+        address = address.slice(1, -1);
+    }
+    return { address, family: isIPv6(address) ? 'IPv6' : 'IPv4', port: Number(addr.slice(i + 1)) };
+}
+
+function joinAddress(address, port) {
+    return isIPv6(address) ? `[${address}]:${port}` : `${address}:${port}`;
+}
+
+// The native calls only have the OS message, node code looks at `code`.
+const errorCodes = [
+    [/refused/i, 'ECONNREFUSED'],
+    [/reset/i, 'ECONNRESET'],
+    [/in use/i, 'EADDRINUSE'],
+    [/not available|cannot assign/i, 'EADDRNOTAVAIL'],
+    [/no such file|not found/i, 'ENOENT'],
+    [/permission denied/i, 'EACCES'],
+    [/timed out/i, 'ETIMEDOUT'],
+    [/unsupported|not supported/i, 'ENOTSUP'],
+    [/invalid/i, 'EINVAL'],
+];
+
+function socketError(e, syscall, address) {
+    const err = e instanceof Error ? e : new Error(`${e}`);
+    if (err.code === undefined) {
+        err.code = errorCodes.find(([re]) => re.test(err.message))?.[1] ?? 'UNKNOWN';
+    }
+    err.syscall ??= syscall;
+    if (address !== undefined) {
+        err.message = `${syscall} ${err.code} ${address}`;
+        err.address = address;
+    }
+    return err;
+}
+
+function normalizeArgs(args) {
+    let options = {};
+    let cb = undefined;
+    if (typeof args[args.length - 1] === 'function') {
+        cb = args[args.length - 1];
+        args = args.slice(0, -1);
+    }
+    if (typeof args[0] === 'object' && args[0] !== null) {
+        options = { ...args[0] };
+    } else if (typeof args[0] === 'string' && !/^\d+$/.test(args[0])) {
+        options.path = args[0];
+    } else {
+        options.port = args[0];
+        if (typeof args[1] === 'string') {
+            options.host = args[1];
+        }
+    }
+    return [options, cb];
+}
+
+async function connectTo(options) {
+    if (typeof options.path === 'string') {
+        try {
+            return await wasi_net.WasiTcpConn.connectUnix(options.path);
+        } catch (e) {
+            throw socketError(e, 'connect', options.path);
+        }
+    }
+    validatePort(options.port, 'options.port');
+    const { address } = await lookup(options.host ?? 'localhost', { family: options.family ?? 0 });
+    const addr = joinAddress(address, options.port | 0);
+    try {
+        return await wasi_net.WasiTcpConn.connect(addr);
+    } catch (e) {
+        const err = socketError(e, 'connect', addr);
+        err.port = options.port | 0;
+        throw err;
+    }
+}
+
+export class Socket extends Duplex {
+    #conn = undefined;
+    #connecting = undefined;
+    #reading = false;
+    #timeout = 0;
+
+    /**
+     * `options.handle` wraps a connected `WasiTcpConn`, otherwise the socket
+     * waits for `connect()`.
+     */
+    constructor(options = {}) {
+        super({ allowHalfOpen: options.allowHalfOpen ?? false });
+        this.connecting = false;
+        this.#conn = options.handle;
+    }
+
+    connect(...args) {
+        const [options, cb] = normalizeArgs(args);
+        if (cb) {
+            this.once('connect', cb);
+        }
+        this.connecting = true;
+        this.#connecting = connectTo(options).then((conn) => {
+            this.connecting = false;
+            if (this.destroyed) {
+                conn.close();
+                return undefined;
+            }
+            this.#conn = conn;
+            this.#applyTimeout();
+            this.emit('connect');
+            this.emit('ready');
+            return conn;
+        }, (e) => {
+            this.connecting = false;
+            this.destroy(e);
+        });
+        if (options.timeout) {
+            this.setTimeout(options.timeout);
+        }
+        return this;
+    }
+
+    async #ready() {
+        return this.#conn ?? await this.#connecting;
+    }
+
+    _read(_size) {
+        if (this.#reading) {
+            return;
+        }
+        this.#reading = true;
+        this.#pump();
+    }
+
+    async #pump() {
+        try {
+            const conn = await this.#ready();
+            while (conn) {
+                const data = await conn.read();
+                if (data === undefined) {
+                    this.push(null);
+                    break;
+                }
+                if (!this.push(Buffer.from(data))) {
+                    break;
+                }
+            }
+        } catch (e) {
+            this.destroy(socketError(e, 'read'));
+        } finally {
+            this.#reading = false;
+        }
+    }
+
+    _write(chunk, _encoding, cb) {
+        this.#ready().then((conn) => {
+            if (!conn) {
+                return cb(new Error('socket is not connected'));
+            }
+            conn.write(toArrayBuffer(chunk), (e) => cb(e && socketError(e, 'write')));
+        }, cb);
+    }
+
+    _final(cb) {
+        this.#ready()
+            .then((conn) => conn?.shutdown())
+            .then(() => cb(), cb);
+    }
+
+    _destroy(err, cb) {
+        this.#conn?.close();
+        this.#conn = undefined;
+        cb(err);
+    }
+
+    get timeout() {
+        return this.#timeout;
+    }
+
+    /**
+     * Emits `timeout` once nothing was read or written for `msecs` ms, 0
+     * turns it off. The socket stays open.
+     */
+    setTimeout(msecs, cb) {
+        this.#timeout = msecs;
+        if (typeof cb === 'function') {
+            if (msecs === 0) {
+                this.removeListener('timeout', cb);
+            } else {
+                this.once('timeout', cb);
+            }
+        }
+        this.#applyTimeout();
+        return this;
+    }
+
+    #applyTimeout() {
+        if (this.#conn) {
+            this.#conn.on('timeout', () => this.emit('timeout'));
+            this.#conn.timeout = this.#timeout;
+        }
+    }
+
+    setNoDelay(_noDelay) {
+        return this;
+    }
+
+    setKeepAlive(_enable, _initialDelay) {
+        return this;
+    }
+
+    ref() {
+        return this;
+    }
+
+    unref() {
+        return this;
+    }
+
+    get pending() {
+        return !this.#conn;
+    }
+
+    get readyState() {
+        if (this.connecting) {
+            return 'opening';
+        }
+        if (!this.#conn) {
+            return 'closed';
+        }
+        if (this.readable && this.writable) {
+            return 'open';
+        }
+        return this.readable ? 'readOnly' : 'writeOnly';
+    }
+
+    get bufferSize() {
+        return this.#conn ? this.#conn.bufferedAmount : 0;
+    }
+
+    // unix sockets have no address to report
+    #address(which) {
+        try {
+            return this.#conn ? splitAddress(this.#conn[which]()) : undefined;
+        } catch {
+            return undefined;
+        }
+    }
+
+    address() {
+        return this.#address('local') ?? {};
+    }
+
+    get remoteAddress() {
+        return this.#address('peer')?.address;
+    }
+
+    get remoteFamily() {
+        return this.#address('peer')?.family;
+    }
+
+    get remotePort() {
+        return this.#address('peer')?.port;
+    }
+
+    get localAddress() {
+        return this.#address('local')?.address;
+    }
+
+    get localPort() {
+        return this.#address('local')?.port;
+    }
+}
+
+export function connect(...args) {
+    const [options] = normalizeArgs(args);
+    const socket = new Socket(options);
+    return socket.connect(...args);
+}
+
+export const createConnection = connect;
+
+export class Server extends EventEmitter {
+    #listener = undefined;
+    #listening = false;
+    #path = undefined;
+    #connections = new Set();
+
+    constructor(options, connectionListener) {
+        super();
+        if (typeof options === 'function') {
+            connectionListener = options;
+            options = {};
+        }
+        this.allowHalfOpen = options?.allowHalfOpen ?? false;
+        this.maxConnections = undefined;
+        if (connectionListener) {
+            this.on('connection', connectionListener);
+        }
+    }
+
+    /**
+     * `listen(port)` listens on TCP, `listen(path)` on a unix socket. The
+     * host and backlog arguments are accepted and ignored.
+     */
+    listen(...args) {
+        const [options, cb] = normalizeArgs(args);
+        if (cb) {
+            this.once('listening', cb);
+        }
+        try {
+            if (typeof options.path === 'string') {
+                this.#listener = new wasi_net.WasiTcpServer(options.path);
+                this.#path = options.path;
+            } else {
+                const port = options.port ?? 0;
+                validatePort(port, 'options.port');
+                this.#listener = new wasi_net.WasiTcpServer(Number(port));
+            }
+        } catch (e) {
+            if (e.code === 'ERR_SOCKET_BAD_PORT') {
+                throw e;
+            }
+            const err = socketError(e, 'listen', options.path ?? joinAddress('0.0.0.0', options.port ?? 0));
+            process.nextTick(() => this.emit('error', err));
+            return this;
+        }
+        this.#listening = true;
+        process.nextTick(() => this.emit('listening'));
+        this.#acceptLoop();
+        return this;
+    }
+
+    async #acceptLoop() {
+        const listener = this.#listener;
+        try {
+            while (this.#listening) {
+                const conn = await listener.accept();
+                if (this.maxConnections !== undefined && this.#connections.size >= this.maxConnections) {
+                    conn.close();
+                    continue;
+                }
+                const socket = new Socket({ handle: conn, allowHalfOpen: this.allowHalfOpen });
+                this.#connections.add(socket);
+                socket.once('close', () => this.#connections.delete(socket));
+                this.emit('connection', socket);
+            }
+        } catch (e) {
+            if (this.#listening) {
+                this.emit('error', socketError(e, 'accept'));
+            }
+        }
+    }
+
+    get listening() {
+        return this.#listening;
+    }
+
+    /** The path for a unix socket server, like node. */
+    address() {
+        if (!this.#listener) {
+            return null;
+        }
+        if (this.#path !== undefined) {
+            return this.#path;
+        }
+        return splitAddress(this.#listener.local());
+    }
+
+    getConnections(cb) {
+        const count = this.#connections.size;
+        process.nextTick(() => cb(null, count));
+        return this;
+    }
+
+    /** Stops accepting, open connections stay. A unix socket file is removed. */
+    close(cb) {
+        if (typeof cb === 'function') {
+            this.once('close', cb);
+        }
+        if (this.#listening) {
+            this.#listening = false;
+            this.#listener.close();
+            this.#listener = undefined;
+            if (this.#path !== undefined) {
+                try {
+                    fs.unlinkSync(this.#path);
+                } catch {
+                    // already gone
+                }
+                this.#path = undefined;
+            }
+        }
+        process.nextTick(() => this.emit('close'));
+        return this;
+    }
+
+    ref() {
+        return this;
+    }
+
+    unref() {
+        return this;
+    }
+}
+
+export function createServer(options, connectionListener) {
+    return new Server(options, connectionListener);
+}
+
+/**
+ * Not in node: two sockets connected to each other over a unix socket, for
+ * talking to a child or a worker. The socket file lives in `dir` (TMPDIR or
+ * /tmp by default) only while they connect.
+ */
+export function socketpair(dir) {
+    dir = dir ?? process.env?.TMPDIR ?? '/tmp';
+    let conns;
+    try {
+        conns = wasi_net.socketpair(dir);
+    } catch (e) {
+        throw socketError(e, 'socketpair');
+    }
+    return conns.map((handle) => new Socket({ handle }));
+}
+
+export default {
+    Socket,
+    Server,
+    createServer,
+    connect,
+    createConnection,
+    socketpair,
+    isIP,
+    isIPv4,
+    isIPv6,
+};
@@ -1,3 +1,10 @@
+import { _features } from '_node:os';
+import { umask as fsUmask } from '_node:fs';
+import * as processBinding from '_node:process';
+import { validateString } from './internal/validators';
+import { ERR_INVALID_ARG_TYPE, ERR_OUT_OF_RANGE } from './internal/errors';
+import { wasiFsSyscallErrorMap } from './internal/fs/errno';
+
 function unimplemented(name) {
   throw new Error('Node.js process ' + name + ' is not supported');
 }
@@ -17,10 +24,25 @@ var emitWarning = function (message, type) {
 
 var binding = function (name) { unimplemented('binding'); };
 
-var umask = function (mask) { return 0; };
+// the mask of the modes files and directories are created with
+var umask = function (mask) {
+  if (typeof mask === 'string') {
+    mask = parseInt(mask, 8);
+  }
+  return mask === undefined ? fsUmask() : fsUmask(mask);
+};
 
-var cwd = function () { return './'; };
-var chdir = function (dir) { unimplemented('chdir'); };
+// relative paths of fs and the module loader are taken from it, it starts
+// out as `/`
+var cwd = function () { return processBinding.cwd(); };
+var chdir = function (directory) {
+  validateString(directory, 'directory');
+  try {
+    processBinding.chdir(directory);
+  } catch (err) {
+    throw wasiFsSyscallErrorMap(err, 'chdir', processBinding.cwd(), directory);
+  }
+};
 
 var release = {
   name: 'wasmedge_quickjs',
@@ -42,12 +64,27 @@ function _getActiveRequests() { return []; }
 function _getActiveHandles() { return []; }
 var reallyExit = noop;
 var _kill = noop;
-var cpuUsage = function () { return {}; };
-var resourceUsage = cpuUsage;
-var memoryUsage = cpuUsage;
+// WASI has one clock for the CPU time of the process, all of it counts as user
+var cpuUsage = function (previousValue) {
+  var user = processBinding.cpuUsage();
+  if (previousValue !== undefined) {
+    return { user: user - previousValue.user, system: 0 };
+  }
+  return { user: user, system: 0 };
+};
+var memoryUsage = function () { return processBinding.memoryUsage(); };
+memoryUsage.rss = function () { return processBinding.memoryUsage().rss; };
+// wasm memory only ever grows, the current size is the peak
+var resourceUsage = function () {
+  return {
+    userCPUTime: processBinding.cpuUsage(),
+    systemCPUTime: 0,
+    maxRSS: Math.floor(memoryUsage.rss() / 1024),
+  };
+};
 var kill = noop;
 var exit = globalThis.exit;
-var openStdin = noop;
+var openStdin = function () { return process.stdin.resume(); };
 var allowedNodeEnvironmentFlags = {};
 function assert(condition, message) {
   if (!condition) { throw new Error(message || 'assertion error'); }
@@ -57,10 +94,10 @@ var features = {
   debug: false,
   uv: false,
   ipv6: false,
-  tls_alpn: false,
-  tls_sni: false,
+  tls_alpn: _features.tls_alpn,
+  tls_sni: _features.tls_sni,
   tls_ocsp: false,
-  tls: false,
+  tls: _features.tls,
   cached_builtins: true,
 };
 var _fatalExceptions = noop;
@@ -73,6 +110,18 @@ var _stopProfilerIdleNotifier = noop;
 var stdout = undefined;
 var stderr = undefined;
 var stdin = undefined;
+// the streams on fds 0 to 2 are made when first asked for: they need
+// `stream` and `fs`, which import this module
+function loadStdio() {
+  if (stdout === undefined) {
+    processBinding._import('internal/process/stdio');
+  }
+}
+function _setStdio(streams) {
+  stdin = streams.stdin;
+  stdout = streams.stdout;
+  stderr = streams.stderr;
+}
 var abort = noop;
 var pid = 2;
 var ppid = 1;
@@ -82,46 +131,35 @@ var argv0 = 'wasmedge-quickjs';
 var _preload_modules = [];
 var setSourceMapsEnabled = noop;
 
-var _performance = {
-  now: undefined,
-  timing: undefined,
-};
-if (_performance.now === undefined) {
-  var nowOffset = Date.now();
-
-  if (_performance.timing && _performance.timing.navigationStart) {
-    nowOffset = _performance.timing.navigationStart;
-  }
-  _performance.now = function () { return Date.now() - nowOffset; };
-}
-
 function uptime() {
-  return _performance.now() / 1000;
+  return processBinding.uptime();
 }
 
 var nanoPerSec = 1000000000;
+// [seconds, nanoseconds] of a monotonic clock, or since `previousTimestamp`
 function hrtime(previousTimestamp) {
-  var baseNow = Math.floor((Date.now() - _performance.now()) * 1e-3);
-  var clocktime = _performance.now() * 1e-3;
-  var seconds = Math.floor(clocktime) + baseNow;
-  var nanoseconds = Math.floor((clocktime % 1) * 1e9);
-  if (previousTimestamp) {
-    seconds = seconds - previousTimestamp[0];
-    nanoseconds = nanoseconds - previousTimestamp[1];
-    if (nanoseconds < 0) {
-      seconds--;
-      nanoseconds += nanoPerSec;
-    }
+  var now = processBinding.hrtime();
+  if (previousTimestamp === undefined) {
+    return now;
+  }
+  if (!Array.isArray(previousTimestamp)) {
+    throw new ERR_INVALID_ARG_TYPE('time', 'Array', previousTimestamp);
+  }
+  if (previousTimestamp.length !== 2) {
+    throw new ERR_OUT_OF_RANGE('time', 2, previousTimestamp.length);
+  }
+  var seconds = now[0] - previousTimestamp[0];
+  var nanoseconds = now[1] - previousTimestamp[1];
+  if (nanoseconds < 0) {
+    seconds--;
+    nanoseconds += nanoPerSec;
   }
   return [seconds, nanoseconds];
 }
 
-hrtime.bigint = function (time) {
-  var diff = hrtime(time);
-  if (typeof BigInt === 'undefined') {
-    return diff[0] * nanoPerSec + diff[1];
-  }
-  return BigInt(diff[0] * nanoPerSec) + BigInt(diff[1]);
+hrtime.bigint = function () {
+  var now = processBinding.hrtime();
+  return BigInt(now[0]) * BigInt(nanoPerSec) + BigInt(now[1]);
 };
 
 var _maxListeners = 10;
@@ -187,9 +225,9 @@ var process = {
   _debugEnd: _debugEnd,
   _startProfilerIdleNotifier: _startProfilerIdleNotifier,
   _stopProfilerIdleNotifier: _stopProfilerIdleNotifier,
-  stdout: stdout,
-  stdin: stdin,
-  stderr: stderr,
+  get stdout() { loadStdio(); return stdout; },
+  get stdin() { loadStdio(); return stdin; },
+  get stderr() { loadStdio(); return stderr; },
   abort: abort,
   umask: umask,
   chdir: chdir,
@@ -210,4 +248,4 @@ var process = {
 
 const nextTick = globalThis.nextTick;
 
-export { _debugEnd, _debugProcess, _events, _eventsCount, _exiting, _fatalExceptions, _getActiveHandles, _getActiveRequests, _kill, _linkedBinding, _maxListeners, _preload_modules, _rawDebug, _startProfilerIdleNotifier, _stopProfilerIdleNotifier, _tickCallback, abort, addListener, allowedNodeEnvironmentFlags, arch, argv, argv0, assert, binding, chdir, config, cpuUsage, cwd, debugPort, process as default, dlopen, domain, emit, emitWarning, env, execArgv, execPath, exit, features, hasUncaughtExceptionCaptureCallback, hrtime, kill, listeners, memoryUsage, moduleLoadList, nextTick, off, on, once, openStdin, pid, platform, ppid, prependListener, prependOnceListener, reallyExit, release, removeAllListeners, removeListener, resourceUsage, setSourceMapsEnabled, setUncaughtExceptionCaptureCallback, stderr, stdin, stdout, title, umask, uptime, version, versions };
+export { _debugEnd, _debugProcess, _events, _eventsCount, _exiting, _fatalExceptions, _getActiveHandles, _getActiveRequests, _kill, _linkedBinding, _maxListeners, _preload_modules, _rawDebug, _setStdio, _startProfilerIdleNotifier, _stopProfilerIdleNotifier, _tickCallback, abort, addListener, allowedNodeEnvironmentFlags, arch, argv, argv0, assert, binding, chdir, config, cpuUsage, cwd, debugPort, process as default, dlopen, domain, emit, emitWarning, env, execArgv, execPath, exit, features, hasUncaughtExceptionCaptureCallback, hrtime, kill, listeners, memoryUsage, moduleLoadList, nextTick, off, on, once, openStdin, pid, platform, ppid, prependListener, prependOnceListener, reallyExit, release, removeAllListeners, removeListener, resourceUsage, setSourceMapsEnabled, setUncaughtExceptionCaptureCallback, stderr, stdin, stdout, title, umask, uptime, version, versions };
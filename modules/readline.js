@@ -0,0 +1,19 @@
+import { Interface } from "./internal/readline/interface";
+import promises from "./readline/promises";
+
+/**
+ * Reads `input` line by line, `input` is a readable stream or an options
+ * object with `input`, `output` and `prompt`.
+ * @returns {Interface}
+ */
+export function createInterface(input, output, completer, terminal) {
+    return new Interface(input, output, completer, terminal);
+}
+
+export { Interface, promises };
+
+export default {
+    Interface,
+    createInterface,
+    promises,
+};
@@ -0,0 +1,46 @@
+import { Interface as BaseInterface } from "../internal/readline/interface";
+import { AbortError } from "../internal/errors";
+
+/**
+ * `readline.Interface` with a `question()` that returns a promise. It
+ * rejects with an `AbortError` if the interface closes or `signal` aborts
+ * before the answer.
+ */
+export class Interface extends BaseInterface {
+    question(query, options) {
+        return new Promise((resolve, reject) => {
+            const signal = options?.signal;
+            if (signal?.aborted) {
+                reject(new AbortError());
+                return;
+            }
+            if (this.closed) {
+                reject(new AbortError());
+                return;
+            }
+            const onClose = () => {
+                signal?.removeEventListener("abort", onAbort);
+                reject(new AbortError());
+            };
+            const onAbort = () => {
+                this.off("close", onClose);
+                reject(new AbortError());
+            };
+            this.once("close", onClose);
+            super.question(query, options ?? {}, (answer) => {
+                this.off("close", onClose);
+                resolve(answer);
+            });
+            signal?.addEventListener("abort", onAbort, { once: true });
+        });
+    }
+}
+
+export function createInterface(input, output, completer, terminal) {
+    return new Interface(input, output, completer, terminal);
+}
+
+export default {
+    Interface,
+    createInterface,
+};
@@ -0,0 +1,520 @@
+// Request routing for `http.createServer()`: routes with path parameters,
+// middleware chains with error handlers, JSON helpers and static files.
+//
+//     const router = new Router();
+//     router.use(json());
+//     router.get('/users/:id', (req, res) => res.json({ id: req.params.id }));
+//     router.use('/assets', serveStatic('./public'));
+//     http.createServer(router.handler()).listen(8000);
+//
+// Handlers are called as `(req, res, next)`, those with four parameters as
+// `(err, req, res, next)` once an error was passed to `next()` or thrown. A
+// rejected promise returned by a handler counts as thrown.
+
+import { statSync, openSync, readSync, closeSync } from 'fs'
+import { posix } from 'path'
+import { Buffer } from 'buffer'
+import { URLSearchParams } from 'whatwg_url'
+import http from 'http'
+
+const METHODS = ['get', 'post', 'put', 'delete', 'patch', 'head', 'options'];
+
+// methods of the routes whose path matched, shared by nested routers
+const allowedMethods = new WeakMap();
+
+function escapeRegExp(s) {
+    return s.replace(/[.*+?^${}()|[\]\\]/g, '\\$&');
+}
+
+// Turns `/users/:id/files/*path` into a regular expression and the names of
+// its parameters. `:name?` makes a segment optional, `*` matches the rest of
+// the path. Prefixes (`end` false) match whole segments only.
+function compilePath(path, end) {
+    const keys = [];
+    let source = '';
+    for (const segment of path.split('/').filter((s) => s !== '')) {
+        let m;
+        if ((m = /^:(\w+)(\?)?$/.exec(segment)) !== null) {
+            keys.push(m[1]);
+            source += m[2] ? '(?:/([^/]+))?' : '/([^/]+)';
+        } else if ((m = /^\*(\w*)$/.exec(segment)) !== null) {
+            keys.push(m[1] || '*');
+            source += '(?:/(.*))?';
+        } else {
+            source += `/${escapeRegExp(segment)}`;
+        }
+    }
+    source = end ? `^${source}/?$` : `^${source}(?=/|$)`;
+    return { regexp: new RegExp(source, 'i'), keys };
+}
+
+function decodeParam(value) {
+    try {
+        return decodeURIComponent(value);
+    } catch {
+        const err = new URIError(`Failed to decode param '${value}'`);
+        err.status = 400;
+        throw err;
+    }
+}
+
+class Layer {
+    constructor(method, path, end, handler) {
+        this.method = method;
+        this.path = path;
+        this.end = end;
+        this.handler = handler;
+        Object.assign(this, compilePath(path, end));
+    }
+
+    // the matched part of `path` and the parameters, or null
+    match(path) {
+        const m = this.regexp.exec(path);
+        if (m === null) {
+            return null;
+        }
+        const params = {};
+        this.keys.forEach((key, i) => {
+            if (m[i + 1] !== undefined) {
+                params[key] = decodeParam(m[i + 1]);
+            }
+        });
+        return { matched: m[0], params };
+    }
+}
+
+function parseQuery(search) {
+    const query = {};
+    for (const [name, value] of new URLSearchParams(search)) {
+        if (Object.prototype.hasOwnProperty.call(query, name)) {
+            query[name] = [].concat(query[name], value);
+        } else {
+            query[name] = value;
+        }
+    }
+    return query;
+}
+
+function installHelpers(req, res) {
+    if (req.originalUrl !== undefined) {
+        return;
+    }
+    const i = req.url.indexOf('?');
+    req.originalUrl = req.url;
+    req.path = i === -1 ? req.url : req.url.slice(0, i);
+    req.query = parseQuery(i === -1 ? '' : req.url.slice(i + 1));
+    req.baseUrl = '';
+    req.params = {};
+
+    res.status = function (code) {
+        this.statusCode = code;
+        return this;
+    };
+    res.json = function (value) {
+        const body = JSON.stringify(value) ?? 'null';
+        if (!this.hasHeader('content-type')) {
+            this.setHeader('Content-Type', 'application/json; charset=utf-8');
+        }
+        this.setHeader('Content-Length', Buffer.byteLength(body));
+        this.end(body);
+        return this;
+    };
+    res.redirect = function (location, status = 302) {
+        this.statusCode = status;
+        this.setHeader('Location', location);
+        this.setHeader('Content-Length', 0);
+        this.end();
+        return this;
+    };
+}
+
+// What answers a request no handler finished.
+function finalHandler(req, res, err, allowed) {
+    if (err !== undefined && res.headersSent) {
+        // too late for an error response
+        res.socket?.destroy();
+        return;
+    }
+    let status = 404;
+    if (err !== undefined) {
+        status = Number(err.status ?? err.statusCode);
+        if (!(status >= 400 && status < 600)) {
+            status = 500;
+        }
+    } else if (allowed.size > 0) {
+        const allow = [...allowed].join(', ');
+        res.setHeader('Allow', allow);
+        if (req.method === 'OPTIONS') {
+            res.statusCode = 204;
+            res.end();
+            return;
+        }
+        status = 405;
+    }
+    const body = http.STATUS_CODES[status] ?? `${status}`;
+    res.statusCode = status;
+    res.setHeader('Content-Type', 'text/plain; charset=utf-8');
+    res.setHeader('Content-Length', Buffer.byteLength(body));
+    res.end(body);
+}
+
+export class Router {
+    #layers = [];
+
+    constructor() {
+        for (const method of METHODS) {
+            this[method] = (path, ...handlers) => this.route(method.toUpperCase(), path, ...handlers);
+        }
+    }
+
+    /** Adds `handlers` for `method` (null for any) on `path`. */
+    route(method, path, ...handlers) {
+        for (const handler of handlers.flat()) {
+            this.#layers.push(new Layer(method, path, true, handler));
+        }
+        return this;
+    }
+
+    all(path, ...handlers) {
+        return this.route(null, path, ...handlers);
+    }
+
+    /**
+     * Adds middleware for every request below `path` (default `/`). Inside
+     * it `req.path` is relative to `path`, which is added to `req.baseUrl`.
+     * Routers can be mounted like any middleware.
+     */
+    use(path, ...handlers) {
+        if (typeof path !== 'string') {
+            handlers.unshift(path);
+            path = '/';
+        }
+        for (let handler of handlers.flat()) {
+            if (handler instanceof Router) {
+                const router = handler;
+                handler = (req, res, next) => router.handle(req, res, next);
+            }
+            this.#layers.push(new Layer(null, path, false, handler));
+        }
+        return this;
+    }
+
+    /** A `(req, res)` listener for the "request" event of `http.Server`. */
+    handler() {
+        return (req, res) => this.handle(req, res);
+    }
+
+    /**
+     * Runs the request through the matching layers. `done` is called when
+     * none of them ended the response, without it a 404, 405 or error
+     * response is sent.
+     */
+    handle(req, res, done = undefined) {
+        installHelpers(req, res);
+        if (!allowedMethods.has(req)) {
+            allowedMethods.set(req, new Set());
+        }
+        const allowed = allowedMethods.get(req);
+        const path = req.path;
+        const baseUrl = req.baseUrl;
+        const params = req.params;
+        let index = 0;
+
+        const restore = () => {
+            req.path = path;
+            req.baseUrl = baseUrl;
+            req.params = params;
+        };
+
+        const next = (err) => {
+            restore();
+            if (err === 'route') {
+                err = undefined;
+            }
+            while (index < this.#layers.length) {
+                const layer = this.#layers[index++];
+                let match;
+                try {
+                    match = layer.match(path);
+                } catch (e) {
+                    err = e;
+                    continue;
+                }
+                if (match === null) {
+                    continue;
+                }
+                if (layer.method !== null && layer.end) {
+                    const method = req.method === 'HEAD' && layer.method === 'GET' ? 'GET' : req.method;
+                    if (layer.method !== method) {
+                        allowed.add(layer.method);
+                        if (layer.method === 'GET') {
+                            allowed.add('HEAD');
+                        }
+                        continue;
+                    }
+                }
+                const isErrorHandler = layer.handler.length === 4;
+                if ((err !== undefined) !== isErrorHandler) {
+                    continue;
+                }
+                req.params = { ...params, ...match.params };
+                if (!layer.end) {
+                    req.baseUrl = baseUrl + match.matched;
+                    req.path = path.slice(match.matched.length) || '/';
+                }
+                call(layer.handler, err);
+                return;
+            }
+            if (done !== undefined) {
+                done(err);
+            } else {
+                finalHandler(req, res, err, allowed);
+            }
+        };
+
+        const call = (handler, err) => {
+            try {
+                const ret = err !== undefined ? handler(err, req, res, next) : handler(req, res, next);
+                if (typeof ret?.then === 'function') {
+                    ret.then(undefined, (e) => next(e ?? new Error('handler rejected')));
+                }
+            } catch (e) {
+                next(e);
+            }
+        };
+
+        next();
+    }
+}
+
+function mimeEssence(type) {
+    return (type ?? '').split(';')[0].trim().toLowerCase();
+}
+
+/**
+ * Middleware parsing `application/json` (and `+json`) request bodies into
+ * `req.body`. Bodies over `limit` bytes fail with 413, invalid JSON with 400.
+ */
+export function json(options = {}) {
+    const limit = options.limit ?? 100 * 1024;
+    return async (req, res, next) => {
+        const type = mimeEssence(req.headers['content-type']);
+        if (req.body !== undefined || !(type === 'application/json' || type.endsWith('+json'))) {
+            next();
+            return;
+        }
+        const chunks = [];
+        let length = 0;
+        for await (const chunk of req) {
+            length += chunk.length;
+            if (length > limit) {
+                const err = new Error('request entity too large');
+                err.status = 413;
+                throw err;
+            }
+            chunks.push(chunk);
+        }
+        const text = Buffer.concat(chunks).toString('utf8');
+        try {
+            req.body = text === '' ? {} : JSON.parse(text);
+        } catch (e) {
+            e.status = 400;
+            throw e;
+        }
+        next();
+    };
+}
+
+const MIME_TYPES = {
+    '.html': 'text/html',
+    '.htm': 'text/html',
+    '.css': 'text/css',
+    '.js': 'text/javascript',
+    '.mjs': 'text/javascript',
+    '.json': 'application/json',
+    '.map': 'application/json',
+    '.txt': 'text/plain',
+    '.md': 'text/markdown',
+    '.xml': 'application/xml',
+    '.svg': 'image/svg+xml',
+    '.png': 'image/png',
+    '.jpg': 'image/jpeg',
+    '.jpeg': 'image/jpeg',
+    '.gif': 'image/gif',
+    '.webp': 'image/webp',
+    '.ico': 'image/x-icon',
+    '.wasm': 'application/wasm',
+    '.pdf': 'application/pdf',
+    '.woff': 'font/woff',
+    '.woff2': 'font/woff2',
+    '.mp4': 'video/mp4',
+    '.mp3': 'audio/mpeg',
+};
+
+function contentType(file) {
+    const type = MIME_TYPES[posix.extname(file).toLowerCase()] ?? 'application/octet-stream';
+    return type.startsWith('text/') || type === 'application/json' ? `${type}; charset=utf-8` : type;
+}
+
+function etagOf(stat) {
+    return `W/"${stat.size.toString(16)}-${Math.floor(stat.mtimeMs).toString(16)}"`;
+}
+
+// true when the validators of the request match the file
+function isFresh(req, etag, mtime) {
+    const noneMatch = req.headers['if-none-match'];
+    if (noneMatch !== undefined) {
+        return noneMatch.trim() === '*'
+            || noneMatch.split(',').some((tag) => tag.trim().replace(/^W\//, '') === etag.replace(/^W\//, ''));
+    }
+    const since = Date.parse(req.headers['if-modified-since'] ?? '');
+    return !Number.isNaN(since) && Math.floor(mtime / 1000) * 1000 <= since;
+}
+
+// The single byte range asked for, null to send everything and undefined
+// when it cannot be satisfied. Lists of ranges get the whole file.
+function parseRange(header, size) {
+    const m = /^bytes=(\d*)-(\d*)$/.exec(header.trim());
+    if (m === null || (m[1] === '' && m[2] === '')) {
+        return null;
+    }
+    let start;
+    let end;
+    if (m[1] === '') {
+        start = Math.max(size - Number(m[2]), 0);
+        end = size - 1;
+    } else {
+        start = Number(m[1]);
+        end = m[2] === '' ? size - 1 : Math.min(Number(m[2]), size - 1);
+    }
+    if (start > end || start >= size) {
+        return undefined;
+    }
+    return { start, end };
+}
+
+async function sendFile(res, file, start, end) {
+    const fd = openSync(file, 'r');
+    try {
+        const buffer = Buffer.alloc(64 * 1024);
+        let pos = start;
+        while (pos <= end && !res.socket.destroyed) {
+            const n = readSync(fd, buffer, 0, Math.min(buffer.length, end - pos + 1), pos);
+            if (n <= 0) {
+                break;
+            }
+            pos += n;
+            if (!res.write(Buffer.from(buffer.subarray(0, n)))) {
+                await new Promise((resolve) => res.once('drain', resolve));
+            }
+        }
+    } finally {
+        closeSync(fd);
+    }
+    res.end();
+}
+
+/**
+ * Middleware serving the files below `root`, which has to be inside a
+ * directory preopened for the program. GET and HEAD requests get `ETag`,
+ * `Last-Modified`, conditional requests and single byte ranges; requests
+ * for missing files, other methods and dotfiles fall through to the next
+ * handler.
+ *
+ * Options: `index` (default `index.html`, false to disable), `maxAge` in
+ * seconds for `Cache-Control`, `dotfiles` (`ignore` or `allow`).
+ */
+export function serveStatic(root, options = {}) {
+    const index = options.index ?? 'index.html';
+    const maxAge = options.maxAge ?? 0;
+    const dotfiles = options.dotfiles ?? 'ignore';
+
+    return async (req, res, next) => {
+        if (req.method !== 'GET' && req.method !== 'HEAD') {
+            next();
+            return;
+        }
+        let path;
+        try {
+            path = decodeURIComponent(req.path);
+        } catch {
+            next();
+            return;
+        }
+        // normalizing from `/` never climbs above `root`
+        path = posix.normalize(`/${path}`);
+        if (path.includes('\0') || (dotfiles !== 'allow' && path.split('/').some((s) => s.startsWith('.')))) {
+            next();
+            return;
+        }
+        let file = posix.join(root, path);
+        let stat;
+        try {
+            stat = statSync(file);
+            if (stat.isDirectory()) {
+                if (!req.path.endsWith('/')) {
+                    res.redirect(`${req.baseUrl}${req.path}/`, 301);
+                    return;
+                }
+                if (index === false) {
+                    next();
+                    return;
+                }
+                file = posix.join(file, index);
+                stat = statSync(file);
+            }
+        } catch {
+            next();
+            return;
+        }
+        if (!stat.isFile()) {
+            next();
+            return;
+        }
+
+        const etag = etagOf(stat);
+        res.setHeader('ETag', etag);
+        res.setHeader('Last-Modified', stat.mtime.toUTCString());
+        res.setHeader('Cache-Control', `public, max-age=${maxAge}`);
+        res.setHeader('Accept-Ranges', 'bytes');
+        if (isFresh(req, etag, stat.mtimeMs)) {
+            res.statusCode = 304;
+            res.end();
+            return;
+        }
+        res.setHeader('Content-Type', contentType(file));
+
+        let start = 0;
+        let end = stat.size - 1;
+        const ifRange = req.headers['if-range'];
+        const rangeApplies = ifRange === undefined || ifRange === etag
+            || Date.parse(ifRange) >= Math.floor(stat.mtimeMs / 1000) * 1000;
+        if (req.headers['range'] !== undefined && rangeApplies) {
+            const range = parseRange(req.headers['range'], stat.size);
+            if (range === undefined) {
+                res.statusCode = 416;
+                res.setHeader('Content-Range', `bytes */${stat.size}`);
+                res.setHeader('Content-Length', 0);
+                res.end();
+                return;
+            }
+            if (range !== null) {
+                ({ start, end } = range);
+                res.statusCode = 206;
+                res.setHeader('Content-Range', `bytes ${start}-${end}/${stat.size}`);
+            }
+        }
+        res.setHeader('Content-Length', end - start + 1);
+        if (req.method === 'HEAD' || end < start) {
+            res.end();
+            return;
+        }
+        await sendFile(res, file, start, end);
+    };
+}
+
+export default {
+    Router,
+    json,
+    serveStatic,
+};
@@ -0,0 +1,395 @@
+// A small subset of the WHATWG streams standard: default (non byte) readable
+// streams with pull sources, readers, tee and async iteration. It is what
+// fetch bodies are built on.
+
+const kState = Symbol('kState');
+
+function createState(stream, strategy) {
+    return {
+        stream,
+        state: 'readable',
+        queue: [],
+        queueSize: 0,
+        readRequests: [],
+        reader: undefined,
+        source: undefined,
+        controller: undefined,
+        started: false,
+        pulling: false,
+        pullAgain: false,
+        closeRequested: false,
+        storedError: undefined,
+        disturbed: false,
+        highWaterMark: strategy.highWaterMark ?? 1,
+        size: typeof strategy.size === 'function' ? strategy.size : () => 1,
+    };
+}
+
+function desiredSize(s) {
+    if (s.state === 'errored') {
+        return null;
+    }
+    if (s.state === 'closed') {
+        return 0;
+    }
+    return s.highWaterMark - s.queueSize;
+}
+
+function shouldPull(s) {
+    if (!s.started || s.state !== 'readable' || s.closeRequested) {
+        return false;
+    }
+    if (s.readRequests.length > 0) {
+        return true;
+    }
+    return desiredSize(s) > 0;
+}
+
+function pullIfNeeded(s) {
+    if (!shouldPull(s)) {
+        return;
+    }
+    if (s.pulling) {
+        s.pullAgain = true;
+        return;
+    }
+    s.pulling = true;
+    let r;
+    try {
+        r = s.source?.pull?.(s.controller);
+    } catch (e) {
+        errorStream(s, e);
+        return;
+    }
+    Promise.resolve(r).then(() => {
+        s.pulling = false;
+        if (s.pullAgain) {
+            s.pullAgain = false;
+            pullIfNeeded(s);
+        }
+    }, (e) => errorStream(s, e));
+}
+
+function finishClose(s) {
+    s.state = 'closed';
+    for (const req of s.readRequests) {
+        req.resolve({ value: undefined, done: true });
+    }
+    s.readRequests = [];
+    s.reader?._resolveClosed();
+}
+
+function errorStream(s, e) {
+    if (s.state !== 'readable') {
+        return;
+    }
+    s.state = 'errored';
+    s.storedError = e;
+    s.queue = [];
+    s.queueSize = 0;
+    for (const req of s.readRequests) {
+        req.reject(e);
+    }
+    s.readRequests = [];
+    s.reader?._rejectClosed(e);
+}
+
+function cancelStream(s, reason) {
+    s.disturbed = true;
+    if (s.state === 'closed') {
+        return Promise.resolve();
+    }
+    if (s.state === 'errored') {
+        return Promise.reject(s.storedError);
+    }
+    s.queue = [];
+    s.queueSize = 0;
+    finishClose(s);
+    try {
+        return Promise.resolve(s.source?.cancel?.(reason)).then(() => undefined);
+    } catch (e) {
+        return Promise.reject(e);
+    }
+}
+
+export class ReadableStreamDefaultController {
+    constructor(state) {
+        if (typeof state !== 'object' || state.stream === undefined) {
+            throw new TypeError('Illegal constructor');
+        }
+        this[kState] = state;
+    }
+
+    get desiredSize() {
+        return desiredSize(this[kState]);
+    }
+
+    enqueue(chunk) {
+        const s = this[kState];
+        if (s.state !== 'readable' || s.closeRequested) {
+            throw new TypeError('ReadableStream is not readable');
+        }
+        if (s.readRequests.length > 0) {
+            s.readRequests.shift().resolve({ value: chunk, done: false });
+        } else {
+            s.queue.push(chunk);
+            s.queueSize += s.size(chunk);
+        }
+        pullIfNeeded(s);
+    }
+
+    close() {
+        const s = this[kState];
+        if (s.state !== 'readable' || s.closeRequested) {
+            throw new TypeError('ReadableStream is not readable');
+        }
+        s.closeRequested = true;
+        if (s.queue.length === 0) {
+            finishClose(s);
+        }
+    }
+
+    error(e) {
+        errorStream(this[kState], e);
+    }
+}
+
+export class ReadableStreamDefaultReader {
+    #closed;
+    #resolveClosed;
+    #rejectClosed;
+
+    constructor(stream) {
+        if (!(stream instanceof ReadableStream)) {
+            throw new TypeError('ReadableStreamDefaultReader needs a ReadableStream');
+        }
+        if (stream.locked) {
+            throw new TypeError('ReadableStream is locked');
+        }
+        const s = stream[kState];
+        this[kState] = s;
+        s.reader = this;
+        this.#closed = new Promise((resolve, reject) => {
+            this.#resolveClosed = resolve;
+            this.#rejectClosed = reject;
+        });
+        // rejections of `closed` are reported through read() as well
+        this.#closed.catch(() => { });
+        if (s.state === 'closed') {
+            this.#resolveClosed();
+        } else if (s.state === 'errored') {
+            this.#rejectClosed(s.storedError);
+        }
+    }
+
+    _resolveClosed() {
+        this.#resolveClosed();
+    }
+
+    _rejectClosed(e) {
+        this.#rejectClosed(e);
+    }
+
+    get closed() {
+        return this.#closed;
+    }
+
+    read() {
+        const s = this[kState];
+        if (s === undefined) {
+            return Promise.reject(new TypeError('reader has been released'));
+        }
+        s.disturbed = true;
+        if (s.queue.length > 0) {
+            const value = s.queue.shift();
+            s.queueSize -= s.size(value);
+            if (s.closeRequested && s.queue.length === 0) {
+                finishClose(s);
+            } else {
+                pullIfNeeded(s);
+            }
+            return Promise.resolve({ value, done: false });
+        }
+        if (s.state === 'closed') {
+            return Promise.resolve({ value: undefined, done: true });
+        }
+        if (s.state === 'errored') {
+            return Promise.reject(s.storedError);
+        }
+        return new Promise((resolve, reject) => {
+            s.readRequests.push({ resolve, reject });
+            pullIfNeeded(s);
+        });
+    }
+
+    cancel(reason) {
+        const s = this[kState];
+        if (s === undefined) {
+            return Promise.reject(new TypeError('reader has been released'));
+        }
+        return cancelStream(s, reason);
+    }
+
+    releaseLock() {
+        const s = this[kState];
+        if (s === undefined) {
+            return;
+        }
+        const e = new TypeError('reader has been released');
+        for (const req of s.readRequests) {
+            req.reject(e);
+        }
+        s.readRequests = [];
+        if (s.state === 'readable') {
+            this.#rejectClosed(e);
+        }
+        s.reader = undefined;
+        this[kState] = undefined;
+    }
+}
+
+export class ReadableStream {
+    constructor(underlyingSource = {}, strategy = {}) {
+        if (underlyingSource?.type === 'bytes') {
+            throw new RangeError('byte streams are not supported');
+        }
+        const s = createState(this, strategy ?? {});
+        this[kState] = s;
+        s.source = underlyingSource ?? {};
+        s.controller = new ReadableStreamDefaultController(s);
+        let start;
+        try {
+            start = s.source.start?.(s.controller);
+        } catch (e) {
+            errorStream(s, e);
+            throw e;
+        }
+        Promise.resolve(start).then(() => {
+            s.started = true;
+            pullIfNeeded(s);
+        }, (e) => errorStream(s, e));
+    }
+
+    static from(iterable) {
+        if (iterable instanceof ReadableStream) {
+            return iterable;
+        }
+        const iterator = iterable[Symbol.asyncIterator]?.() ?? iterable[Symbol.iterator]();
+        return new ReadableStream({
+            async pull(controller) {
+                const { value, done } = await iterator.next();
+                if (done) {
+                    controller.close();
+                } else {
+                    controller.enqueue(value);
+                }
+            },
+            async cancel(reason) {
+                await iterator.return?.(reason);
+            },
+        }, { highWaterMark: 0 });
+    }
+
+    get locked() {
+        return this[kState].reader !== undefined;
+    }
+
+    getReader(options = {}) {
+        if (options?.mode === 'byob') {
+            throw new TypeError('byob readers are not supported');
+        }
+        return new ReadableStreamDefaultReader(this);
+    }
+
+    cancel(reason) {
+        if (this.locked) {
+            return Promise.reject(new TypeError('ReadableStream is locked'));
+        }
+        return cancelStream(this[kState], reason);
+    }
+
+    tee() {
+        const reader = this.getReader();
+        let branches = [];
+        let canceled = 0;
+        let reading = false;
+        const pull = async () => {
+            if (reading) {
+                return;
+            }
+            reading = true;
+            try {
+                const { value, done } = await reader.read();
+                for (const b of branches) {
+                    if (b.canceled) {
+                        continue;
+                    }
+                    if (done) {
+                        b.controller.close();
+                    } else {
+                        b.controller.enqueue(value);
+                    }
+                }
+            } catch (e) {
+                for (const b of branches) {
+                    b.controller.error(e);
+                }
+            } finally {
+                reading = false;
+            }
+        };
+        branches = [0, 1].map(() => {
+            const b = { canceled: false, controller: undefined };
+            b.stream = new ReadableStream({
+                start(controller) {
+                    b.controller = controller;
+                },
+                pull,
+                cancel(reason) {
+                    b.canceled = true;
+                    if (++canceled === 2) {
+                        return reader.cancel(reason);
+                    }
+                },
+            }, { highWaterMark: 0 });
+            return b;
+        });
+        return [branches[0].stream, branches[1].stream];
+    }
+
+    async *values({ preventCancel = false } = {}) {
+        const reader = this.getReader();
+        try {
+            while (true) {
+                const { value, done } = await reader.read();
+                if (done) {
+                    return;
+                }
+                yield value;
+            }
+        } finally {
+            if (!preventCancel && this[kState].state === 'readable') {
+                await reader.cancel();
+            }
+            reader.releaseLock();
+        }
+    }
+
+    [Symbol.asyncIterator](options) {
+        return this.values(options);
+    }
+
+    get [Symbol.toStringTag]() {
+        return 'ReadableStream';
+    }
+}
+
+export function isDisturbed(stream) {
+    return stream[kState]?.disturbed ?? false;
+}
+
+export default {
+    ReadableStream,
+    ReadableStreamDefaultReader,
+    ReadableStreamDefaultController,
+};
@@ -0,0 +1,343 @@
+import * as net from 'wasi_net'
+import * as wasi_tls from 'wasi_tls'
+import { Buffer } from 'buffer'
+import { EventEmitter } from 'events'
+import process from 'process'
+import { Duplex } from 'stream'
+import { isTypedArray } from 'util/types'
+
+export const DEFAULT_MIN_VERSION = 'TLSv1.2';
+export const DEFAULT_MAX_VERSION = 'TLSv1.3';
+
+function toPem(value) {
+    if (value === undefined || value === null) {
+        return undefined;
+    }
+    if (Array.isArray(value)) {
+        return value.map(toPem);
+    }
+    if (typeof value === 'string') {
+        return value;
+    }
+    if (isTypedArray(value) || value instanceof ArrayBuffer) {
+        return Buffer.from(value).toString('utf8');
+    }
+    throw new TypeError('PEM data must be a string or a Buffer');
+}
+
+function toArrayBuffer(chunk) {
+    if (typeof chunk === 'string') {
+        return chunk;
+    }
+    if (chunk instanceof ArrayBuffer) {
+        return chunk;
+    }
+    return chunk.buffer.slice(chunk.byteOffset, chunk.byteOffset + chunk.byteLength);
+}
+
+function splitAddress(addr) {
+    let i = addr.lastIndexOf(':');
+    return { address: addr.slice(0, i), port: Number(addr.slice(i + 1)) };
+}
+
+export class SecureContext {
+    constructor(options = {}) {
+        this.context = new wasi_tls.WasiTlsServer({
+            cert: toPem(options.cert),
+            key: toPem(options.key),
+            ALPNProtocols: options.ALPNProtocols,
+        });
+    }
+}
+
+export function createSecureContext(options) {
+    return new SecureContext(options);
+}
+
+/**
+ * Connects and completes the handshake, resolving with the bare
+ * `WasiTlsConn`. `http` uses it the same way it uses `WasiTcpConn`.
+ */
+export async function rawConnect(options = {}) {
+    const host = options.host ?? 'localhost';
+    const port = options.port ?? 443;
+    const addr = net.nsloopup(host, `${port}`)[0];
+    const tcp = await net.WasiTcpConn.connect(addr, options.timeout);
+    return await wasi_tls.WasiTlsConn.connect(tcp, {
+        servername: options.servername ?? host,
+        ALPNProtocols: options.ALPNProtocols,
+        ca: toPem(options.ca),
+        timeout: options.timeout,
+    });
+}
+
+export class TLSSocket extends Duplex {
+    #conn = undefined;
+    #connecting = undefined;
+    #reading = false;
+    #timeout = 0;
+
+    constructor(conn, options = {}) {
+        super({ allowHalfOpen: options.allowHalfOpen ?? false });
+        this.encrypted = true;
+        this.authorized = false;
+        this.authorizationError = null;
+        this.connecting = false;
+        if (conn instanceof Promise) {
+            this.connecting = true;
+            this.#connecting = conn.then((conn) => {
+                this.#conn = conn;
+                this.connecting = false;
+                this.authorized = true;
+                this.emit('secureConnect');
+                return conn;
+            }, (e) => {
+                this.connecting = false;
+                this.authorizationError = e.message;
+                this.destroy(e);
+            });
+        } else {
+            this.#conn = conn;
+            this.authorized = true;
+        }
+    }
+
+    async #ready() {
+        return this.#conn ?? await this.#connecting;
+    }
+
+    _read(_size) {
+        if (this.#reading) {
+            return;
+        }
+        this.#reading = true;
+        this.#pump();
+    }
+
+    async #pump() {
+        try {
+            const conn = await this.#ready();
+            while (conn) {
+                const data = await conn.read();
+                if (data === undefined) {
+                    this.push(null);
+                    break;
+                }
+                if (!this.push(Buffer.from(data))) {
+                    break;
+                }
+            }
+        } catch (e) {
+            this.destroy(e);
+        } finally {
+            this.#reading = false;
+        }
+    }
+
+    _write(chunk, _encoding, cb) {
+        this.#ready().then((conn) => {
+            if (!conn) {
+                return cb(new Error('socket is not connected'));
+            }
+            conn.write(toArrayBuffer(chunk), cb);
+        }, cb);
+    }
+
+    _final(cb) {
+        this.#ready()
+            .then((conn) => conn?.shutdown())
+            .then(() => cb(), cb);
+    }
+
+    _destroy(err, cb) {
+        this.#conn = undefined;
+        cb(err);
+    }
+
+    get timeout() {
+        return this.#timeout;
+    }
+
+    /**
+     * Emits `timeout` once nothing was read or written for `msecs` ms, 0
+     * turns it off. The socket stays open.
+     */
+    setTimeout(msecs, cb) {
+        this.#timeout = msecs;
+        if (typeof cb === 'function') {
+            if (msecs === 0) {
+                this.removeListener('timeout', cb);
+            } else {
+                this.once('timeout', cb);
+            }
+        }
+        this.#ready().then((conn) => {
+            if (conn) {
+                conn.on('timeout', () => this.emit('timeout'));
+                conn.timeout = this.#timeout;
+            }
+        }, () => {
+            // destroyed while connecting
+        });
+        return this;
+    }
+
+    get alpnProtocol() {
+        return this.#conn ? this.#conn.alpnProtocol : null;
+    }
+
+    get servername() {
+        return this.#conn ? this.#conn.servername : undefined;
+    }
+
+    getProtocol() {
+        return this.#conn ? this.#conn.protocol : null;
+    }
+
+    get bufferSize() {
+        return this.#conn ? this.#conn.bufferedAmount : 0;
+    }
+
+    get remoteAddress() {
+        return this.#conn ? splitAddress(this.#conn.peer()).address : undefined;
+    }
+
+    get remotePort() {
+        return this.#conn ? splitAddress(this.#conn.peer()).port : undefined;
+    }
+
+    get localAddress() {
+        return this.#conn ? splitAddress(this.#conn.local()).address : undefined;
+    }
+
+    get localPort() {
+        return this.#conn ? splitAddress(this.#conn.local()).port : undefined;
+    }
+}
+
+function normalizeConnectArgs(args) {
+    let options = {};
+    let cb = undefined;
+    if (typeof args[args.length - 1] === 'function') {
+        cb = args.pop();
+    }
+    if (typeof args[0] === 'object' && args[0] !== null) {
+        options = { ...args[0] };
+    } else {
+        options.port = args[0];
+        if (typeof args[1] === 'string') {
+            options.host = args[1];
+            Object.assign(options, args[2]);
+        } else {
+            Object.assign(options, args[1]);
+        }
+    }
+    return [options, cb];
+}
+
+export function connect(...args) {
+    const [options, cb] = normalizeConnectArgs(args);
+    const socket = new TLSSocket(rawConnect(options), options);
+    if (cb) {
+        socket.once('secureConnect', cb);
+    }
+    return socket;
+}
+
+export class Server extends EventEmitter {
+    #context;
+    #listener = undefined;
+    #listening = false;
+    #handshakeTimeout;
+
+    constructor(options, secureConnectionListener) {
+        super();
+        if (typeof options === 'function') {
+            secureConnectionListener = options;
+            options = {};
+        }
+        options = options ?? {};
+        this.#context = options.secureContext ?? createSecureContext(options);
+        this.#handshakeTimeout = options.handshakeTimeout ?? 120000;
+        if (secureConnectionListener) {
+            this.on('secureConnection', secureConnectionListener);
+        }
+    }
+
+    listen(port, cb) {
+        if (typeof port === 'object' && port !== null) {
+            port = port.port;
+        }
+        if (typeof cb === 'function') {
+            this.once('listening', cb);
+        }
+        this.#listener = new net.WasiTcpServer((port ?? 0) | 0);
+        this.#listening = true;
+        process.nextTick(() => this.emit('listening'));
+        this.#acceptLoop();
+        return this;
+    }
+
+    async #acceptLoop() {
+        const listener = this.#listener;
+        try {
+            while (this.#listening) {
+                const tcp = await listener.accept();
+                this.#handshake(tcp);
+            }
+        } catch (e) {
+            if (this.#listening) {
+                this.emit('error', e);
+            }
+        }
+    }
+
+    async #handshake(tcp) {
+        try {
+            const conn = await this.#context.context.accept(tcp, this.#handshakeTimeout);
+            this.emit('secureConnection', new TLSSocket(conn));
+        } catch (e) {
+            this.emit('tlsClientError', e);
+        }
+    }
+
+    get listening() {
+        return this.#listening;
+    }
+
+    address() {
+        if (!this.#listener) {
+            return null;
+        }
+        return { family: 'IPv4', ...splitAddress(this.#listener.local()) };
+    }
+
+    close(cb) {
+        if (typeof cb === 'function') {
+            this.once('close', cb);
+        }
+        if (this.#listening) {
+            this.#listening = false;
+            this.#listener.close();
+            this.#listener = undefined;
+        }
+        process.nextTick(() => this.emit('close'));
+        return this;
+    }
+}
+
+export function createServer(options, secureConnectionListener) {
+    return new Server(options, secureConnectionListener);
+}
+
+export default {
+    DEFAULT_MIN_VERSION,
+    DEFAULT_MAX_VERSION,
+    SecureContext,
+    createSecureContext,
+    TLSSocket,
+    Server,
+    createServer,
+    connect,
+    rawConnect,
+};
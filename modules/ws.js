@@ -0,0 +1,11 @@
+// The API of the `ws` package: `WebSocket` for clients and `WebSocketServer`
+// to accept upgrades from an `http.Server`.
+
+import { WebSocket, WebSocketServer } from 'internal/websocket'
+
+export { WebSocket, WebSocketServer };
+
+export default WebSocket;
+WebSocket.WebSocket = WebSocket;
+WebSocket.WebSocketServer = WebSocketServer;
+WebSocket.Server = WebSocketServer;
@@ -0,0 +1,441 @@
+// Node.js compatible `zlib` on top of the native codecs in `_node:zlib`.
+//
+// The window size, memory level and strategy options are accepted but have
+// no effect, the native side picks them.
+
+import { ZlibStream } from '_node:zlib'
+import { Buffer } from 'buffer'
+import process from 'process'
+import { Transform } from 'stream'
+import { ERR_INVALID_ARG_TYPE } from 'internal/errors'
+
+export const constants = Object.freeze({
+    Z_NO_FLUSH: 0,
+    Z_PARTIAL_FLUSH: 1,
+    Z_SYNC_FLUSH: 2,
+    Z_FULL_FLUSH: 3,
+    Z_FINISH: 4,
+    Z_BLOCK: 5,
+    Z_OK: 0,
+    Z_STREAM_END: 1,
+    Z_NEED_DICT: 2,
+    Z_ERRNO: -1,
+    Z_STREAM_ERROR: -2,
+    Z_DATA_ERROR: -3,
+    Z_MEM_ERROR: -4,
+    Z_BUF_ERROR: -5,
+    Z_VERSION_ERROR: -6,
+    Z_NO_COMPRESSION: 0,
+    Z_BEST_SPEED: 1,
+    Z_BEST_COMPRESSION: 9,
+    Z_DEFAULT_COMPRESSION: -1,
+    Z_FILTERED: 1,
+    Z_HUFFMAN_ONLY: 2,
+    Z_RLE: 3,
+    Z_FIXED: 4,
+    Z_DEFAULT_STRATEGY: 0,
+    ZLIB_VERNUM: 4865,
+    DEFLATE: 1,
+    INFLATE: 2,
+    GZIP: 3,
+    GUNZIP: 4,
+    DEFLATERAW: 5,
+    INFLATERAW: 6,
+    UNZIP: 7,
+    BROTLI_DECODE: 8,
+    BROTLI_ENCODE: 9,
+    Z_MIN_WINDOWBITS: 8,
+    Z_MAX_WINDOWBITS: 15,
+    Z_DEFAULT_WINDOWBITS: 15,
+    Z_MIN_CHUNK: 64,
+    Z_MAX_CHUNK: Infinity,
+    Z_DEFAULT_CHUNK: 16384,
+    Z_MIN_MEMLEVEL: 1,
+    Z_MAX_MEMLEVEL: 9,
+    Z_DEFAULT_MEMLEVEL: 8,
+    Z_MIN_LEVEL: -1,
+    Z_MAX_LEVEL: 9,
+    Z_DEFAULT_LEVEL: -1,
+    BROTLI_OPERATION_PROCESS: 0,
+    BROTLI_OPERATION_FLUSH: 1,
+    BROTLI_OPERATION_FINISH: 2,
+    BROTLI_OPERATION_EMIT_METADATA: 3,
+    BROTLI_PARAM_MODE: 0,
+    BROTLI_MODE_GENERIC: 0,
+    BROTLI_MODE_TEXT: 1,
+    BROTLI_MODE_FONT: 2,
+    BROTLI_DEFAULT_MODE: 0,
+    BROTLI_PARAM_QUALITY: 1,
+    BROTLI_MIN_QUALITY: 0,
+    BROTLI_MAX_QUALITY: 11,
+    BROTLI_DEFAULT_QUALITY: 11,
+    BROTLI_PARAM_LGWIN: 2,
+    BROTLI_MIN_WINDOW_BITS: 10,
+    BROTLI_MAX_WINDOW_BITS: 24,
+    BROTLI_DEFAULT_WINDOW: 22,
+    BROTLI_PARAM_LGBLOCK: 3,
+    BROTLI_PARAM_SIZE_HINT: 5,
+})
+
+export const codes = Object.freeze({
+    Z_OK: 0,
+    Z_STREAM_END: 1,
+    Z_NEED_DICT: 2,
+    Z_ERRNO: -1,
+    Z_STREAM_ERROR: -2,
+    Z_DATA_ERROR: -3,
+    Z_MEM_ERROR: -4,
+    Z_BUF_ERROR: -5,
+    Z_VERSION_ERROR: -6,
+    '0': 'Z_OK',
+    '1': 'Z_STREAM_END',
+    '2': 'Z_NEED_DICT',
+    '-1': 'Z_ERRNO',
+    '-2': 'Z_STREAM_ERROR',
+    '-3': 'Z_DATA_ERROR',
+    '-4': 'Z_MEM_ERROR',
+    '-5': 'Z_BUF_ERROR',
+    '-6': 'Z_VERSION_ERROR',
+})
+
+function toArrayBuffer(chunk, name) {
+    if (typeof chunk === 'string') {
+        chunk = Buffer.from(chunk);
+    }
+    if (chunk instanceof ArrayBuffer) {
+        return chunk;
+    }
+    if (ArrayBuffer.isView(chunk)) {
+        return chunk.buffer.slice(chunk.byteOffset, chunk.byteOffset + chunk.byteLength);
+    }
+    throw new ERR_INVALID_ARG_TYPE(name, ['string', 'Buffer', 'TypedArray', 'DataView', 'ArrayBuffer'], chunk);
+}
+
+function nativeOptions(mode, opts = {}) {
+    const native = {};
+    if (typeof opts.level === 'number' && opts.level >= 0) {
+        native.level = opts.level;
+    }
+    const params = opts.params ?? {};
+    if (params[constants.BROTLI_PARAM_QUALITY] !== undefined) {
+        native.quality = params[constants.BROTLI_PARAM_QUALITY];
+    }
+    if (params[constants.BROTLI_PARAM_LGWIN] !== undefined) {
+        native.lgwin = params[constants.BROTLI_PARAM_LGWIN];
+    }
+    return native;
+}
+
+function zlibError(e) {
+    if (typeof e.code === 'string' && codes[e.code] !== undefined) {
+        e.errno = codes[e.code];
+    }
+    return e;
+}
+
+const kFlushFlag = Symbol('kFlushFlag');
+
+class ZlibBase extends Transform {
+    #mode;
+    #opts;
+    #handle;
+
+    constructor(mode, opts) {
+        super({ ...opts, objectMode: false, readableObjectMode: false, writableObjectMode: false });
+        this.#mode = mode;
+        this.#opts = nativeOptions(mode, opts);
+        this.#handle = new ZlibStream(mode, this.#opts);
+        this.bytesWritten = 0;
+    }
+
+    #push(out) {
+        if (out.byteLength > 0) {
+            this.push(Buffer.from(out));
+        }
+    }
+
+    _transform(chunk, encoding, callback) {
+        try {
+            if (chunk[kFlushFlag]) {
+                this.#push(this.#handle.flush());
+            } else {
+                this.bytesWritten += chunk.length;
+                this.#push(this.#handle.write(toArrayBuffer(chunk, 'chunk')));
+            }
+        } catch (e) {
+            return callback(zlibError(e));
+        }
+        callback();
+    }
+
+    _flush(callback) {
+        try {
+            this.#push(this.#handle.finish());
+        } catch (e) {
+            return callback(zlibError(e));
+        }
+        callback();
+    }
+
+    /**
+     * Pushes out everything the codec holds back so far, in order with the
+     * writes before it. `kind` is accepted for compatibility.
+     */
+    flush(kind, callback) {
+        if (typeof kind === 'function') {
+            callback = kind;
+        }
+        if (this.writableEnded) {
+            if (callback) {
+                process.nextTick(callback);
+            }
+            return;
+        }
+        const marker = Buffer.alloc(0);
+        marker[kFlushFlag] = true;
+        this.write(marker, callback);
+    }
+
+    /** Starts over with a fresh codec, dropping any buffered state. */
+    reset() {
+        this.#handle = new ZlibStream(this.#mode, this.#opts);
+    }
+
+    close(callback) {
+        if (callback) {
+            this.once('close', callback);
+        }
+        this.destroy();
+    }
+}
+
+export class Deflate extends ZlibBase {
+    constructor(opts) {
+        super('deflate', opts);
+    }
+}
+
+export class Inflate extends ZlibBase {
+    constructor(opts) {
+        super('inflate', opts);
+    }
+}
+
+export class Gzip extends ZlibBase {
+    constructor(opts) {
+        super('gzip', opts);
+    }
+}
+
+export class Gunzip extends ZlibBase {
+    constructor(opts) {
+        super('gunzip', opts);
+    }
+}
+
+export class DeflateRaw extends ZlibBase {
+    constructor(opts) {
+        super('deflateRaw', opts);
+    }
+}
+
+export class InflateRaw extends ZlibBase {
+    constructor(opts) {
+        super('inflateRaw', opts);
+    }
+}
+
+export class Unzip extends ZlibBase {
+    constructor(opts) {
+        super('unzip', opts);
+    }
+}
+
+export class BrotliCompress extends ZlibBase {
+    constructor(opts) {
+        super('brotliCompress', opts);
+    }
+}
+
+export class BrotliDecompress extends ZlibBase {
+    constructor(opts) {
+        super('brotliDecompress', opts);
+    }
+}
+
+export function createDeflate(opts) {
+    return new Deflate(opts);
+}
+
+export function createInflate(opts) {
+    return new Inflate(opts);
+}
+
+export function createGzip(opts) {
+    return new Gzip(opts);
+}
+
+export function createGunzip(opts) {
+    return new Gunzip(opts);
+}
+
+export function createDeflateRaw(opts) {
+    return new DeflateRaw(opts);
+}
+
+export function createInflateRaw(opts) {
+    return new InflateRaw(opts);
+}
+
+export function createUnzip(opts) {
+    return new Unzip(opts);
+}
+
+export function createBrotliCompress(opts) {
+    return new BrotliCompress(opts);
+}
+
+export function createBrotliDecompress(opts) {
+    return new BrotliDecompress(opts);
+}
+
+function zlibBufferSync(mode, buffer, opts) {
+    const handle = new ZlibStream(mode, nativeOptions(mode, opts));
+    try {
+        const head = handle.write(toArrayBuffer(buffer, 'buffer'));
+        const tail = handle.finish();
+        return Buffer.concat([Buffer.from(head), Buffer.from(tail)]);
+    } catch (e) {
+        throw zlibError(e);
+    }
+}
+
+function zlibBuffer(mode, buffer, opts, callback) {
+    if (typeof opts === 'function') {
+        callback = opts;
+        opts = {};
+    }
+    if (typeof callback !== 'function') {
+        throw new ERR_INVALID_ARG_TYPE('callback', 'Function', callback);
+    }
+    let result, error = null;
+    try {
+        result = zlibBufferSync(mode, buffer, opts);
+    } catch (e) {
+        error = e;
+    }
+    process.nextTick(() => callback(error, result));
+}
+
+export function deflateSync(buffer, opts) {
+    return zlibBufferSync('deflate', buffer, opts);
+}
+
+export function inflateSync(buffer, opts) {
+    return zlibBufferSync('inflate', buffer, opts);
+}
+
+export function gzipSync(buffer, opts) {
+    return zlibBufferSync('gzip', buffer, opts);
+}
+
+export function gunzipSync(buffer, opts) {
+    return zlibBufferSync('gunzip', buffer, opts);
+}
+
+export function deflateRawSync(buffer, opts) {
+    return zlibBufferSync('deflateRaw', buffer, opts);
+}
+
+export function inflateRawSync(buffer, opts) {
+    return zlibBufferSync('inflateRaw', buffer, opts);
+}
+
+export function unzipSync(buffer, opts) {
+    return zlibBufferSync('unzip', buffer, opts);
+}
+
+export function brotliCompressSync(buffer, opts) {
+    return zlibBufferSync('brotliCompress', buffer, opts);
+}
+
+export function brotliDecompressSync(buffer, opts) {
+    return zlibBufferSync('brotliDecompress', buffer, opts);
+}
+
+export function deflate(buffer, opts, callback) {
+    zlibBuffer('deflate', buffer, opts, callback);
+}
+
+export function inflate(buffer, opts, callback) {
+    zlibBuffer('inflate', buffer, opts, callback);
+}
+
+export function gzip(buffer, opts, callback) {
+    zlibBuffer('gzip', buffer, opts, callback);
+}
+
+export function gunzip(buffer, opts, callback) {
+    zlibBuffer('gunzip', buffer, opts, callback);
+}
+
+export function deflateRaw(buffer, opts, callback) {
+    zlibBuffer('deflateRaw', buffer, opts, callback);
+}
+
+export function inflateRaw(buffer, opts, callback) {
+    zlibBuffer('inflateRaw', buffer, opts, callback);
+}
+
+export function unzip(buffer, opts, callback) {
+    zlibBuffer('unzip', buffer, opts, callback);
+}
+
+export function brotliCompress(buffer, opts, callback) {
+    zlibBuffer('brotliCompress', buffer, opts, callback);
+}
+
+export function brotliDecompress(buffer, opts, callback) {
+    zlibBuffer('brotliDecompress', buffer, opts, callback);
+}
+
+export default {
+    constants,
+    codes,
+    Deflate,
+    Inflate,
+    Gzip,
+    Gunzip,
+    DeflateRaw,
+    InflateRaw,
+    Unzip,
+    BrotliCompress,
+    BrotliDecompress,
+    createDeflate,
+    createInflate,
+    createGzip,
+    createGunzip,
+    createDeflateRaw,
+    createInflateRaw,
+    createUnzip,
+    createBrotliCompress,
+    createBrotliDecompress,
+    deflateSync,
+    inflateSync,
+    gzipSync,
+    gunzipSync,
+    deflateRawSync,
+    inflateRawSync,
+    unzipSync,
+    brotliCompressSync,
+    brotliDecompressSync,
+    deflate,
+    inflate,
+    gzip,
+    gunzip,
+    deflateRaw,
+    inflateRaw,
+    unzip,
+    brotliCompress,
+    brotliDecompress,
+}
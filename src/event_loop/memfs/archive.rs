@@ -0,0 +1,606 @@
+//! Seeding a `VirtualFs` from a tar or zip archive and writing it back out.
+//!
+//! Tar archives may be ustar, GNU (long names in `L` and `K` entries) or pax
+//! (`path` and `linkpath` records). Zip archives may store or deflate their
+//! entries, zip64 is not supported. Permission bits and owners are not kept,
+//! the snapshots write the defaults `_node:fs` reports for an unchanged file.
+
+use super::{Kind, Node, Tree, VirtualFs, ROOT_INO};
+use flate2::read::DeflateDecoder;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io::{self, Read};
+
+const BLOCK: usize = 512;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn errno_to_io(e: super::Errno) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.message())
+}
+
+/// An entry read from an archive, `path` relative to the root.
+enum Entry {
+    File(Vec<u8>),
+    Dir,
+    Symlink(String),
+    HardLink(String),
+}
+
+fn clean_path(path: &str) -> String {
+    path.split('/')
+        .filter(|p| !p.is_empty() && *p != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl Tree {
+    fn add_entry(&mut self, path: &str, entry: Entry, mtim: u64) -> Result<(), super::Errno> {
+        let path = clean_path(path);
+        if path.split('/').any(|p| p == "..") {
+            return Err(super::wasi_fs::ERRNO_PERM);
+        }
+        let ino = match entry {
+            Entry::Dir => self.make_dirs(&path)?,
+            Entry::File(data) => {
+                let ino = self.make_file(&path)?;
+                *self.file_data(ino)? = data;
+                ino
+            }
+            Entry::Symlink(target) => {
+                let (dir, name) = super::split_last(&path);
+                let parent = self.make_dirs(dir.unwrap_or(""))?;
+                if self.entries(parent)?.contains_key(name) {
+                    return Err(super::wasi_fs::ERRNO_EXIST);
+                }
+                let ino = self.add_node(Node::new(Kind::Symlink(target)));
+                self.entries_mut(parent)?.insert(name.to_string(), ino);
+                ino
+            }
+            Entry::HardLink(target) => {
+                let ino = self.walk(ROOT_INO, &clean_path(&target), false, &mut 0)?;
+                let (dir, name) = super::split_last(&path);
+                let parent = self.make_dirs(dir.unwrap_or(""))?;
+                if self.node(ino)?.is_dir() {
+                    return Err(super::wasi_fs::ERRNO_PERM);
+                }
+                if self.entries(parent)?.contains_key(name) {
+                    return Err(super::wasi_fs::ERRNO_EXIST);
+                }
+                self.entries_mut(parent)?.insert(name.to_string(), ino);
+                self.node_mut(ino)?.nlink += 1;
+                return Ok(());
+            }
+        };
+        let node = self.node_mut(ino)?;
+        node.atim = mtim;
+        node.mtim = mtim;
+        node.ctim = mtim;
+        Ok(())
+    }
+
+    /// Every path below the root with its inode, parents before children.
+    fn list_all(&self) -> Vec<(String, u64)> {
+        let mut res = vec![];
+        let mut stack = vec![(String::new(), ROOT_INO)];
+        while let Some((prefix, dir)) = stack.pop() {
+            let entries = match self.entries(dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            let mut subdirs = vec![];
+            for (name, ino) in entries {
+                let path = format!("{}{}", prefix, name);
+                if self.nodes.get(ino).is_some_and(Node::is_dir) {
+                    subdirs.push((format!("{}/", path), *ino));
+                }
+                res.push((path, *ino));
+            }
+            stack.extend(subdirs.into_iter().rev());
+        }
+        res
+    }
+}
+
+fn parse_octal(field: &[u8]) -> io::Result<u64> {
+    // GNU base-256 for large values
+    if field.first().is_some_and(|b| b & 0x80 != 0) {
+        let mut n = (field[0] & 0x7f) as u64;
+        for b in &field[1..] {
+            n = n
+                .checked_mul(256)
+                .ok_or_else(|| invalid("tar number too large"))?
+                | *b as u64;
+        }
+        return Ok(n);
+    }
+    let s: String = field
+        .iter()
+        .take_while(|b| **b != 0)
+        .map(|b| *b as char)
+        .collect();
+    let s = s.trim();
+    if s.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(s, 8).map_err(|_| invalid("bad number in tar header"))
+}
+
+fn field_str(field: &[u8]) -> String {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+fn header_checksum(header: &[u8]) -> u64 {
+    header
+        .iter()
+        .enumerate()
+        .map(|(i, b)| if (148..156).contains(&i) { b' ' } else { *b } as u64)
+        .sum()
+}
+
+/// The `path` and `linkpath` records of a pax header.
+fn parse_pax(data: &[u8]) -> BTreeMap<String, String> {
+    let mut res = BTreeMap::new();
+    let mut rest = data;
+    while let Some(space) = rest.iter().position(|b| *b == b' ') {
+        let len: usize = match std::str::from_utf8(&rest[..space])
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            Some(len) if len >= space + 2 && len <= rest.len() && rest[len - 1] == b'\n' => len,
+            _ => break,
+        };
+        let record = String::from_utf8_lossy(&rest[space + 1..len - 1]).to_string();
+        if let Some(eq) = record.find('=') {
+            res.insert(record[..eq].to_string(), record[eq + 1..].to_string());
+        }
+        rest = &rest[len..];
+    }
+    res
+}
+
+fn read_tar(tree: &mut Tree, data: &[u8]) -> io::Result<()> {
+    let mut offset = 0;
+    let mut long_name = None;
+    let mut long_link = None;
+    while offset + BLOCK <= data.len() {
+        let header = &data[offset..offset + BLOCK];
+        if header.iter().all(|b| *b == 0) {
+            break;
+        }
+        if parse_octal(&header[148..156])? != header_checksum(header) {
+            return Err(invalid("bad tar header checksum"));
+        }
+        let size = usize::try_from(parse_octal(&header[124..136])?)
+            .map_err(|_| invalid("tar entry too large"))?;
+        let mtime = parse_octal(&header[136..148])?
+            .checked_mul(1_000_000_000)
+            .ok_or_else(|| invalid("bad tar mtime"))?;
+        let start = offset + BLOCK;
+        let end = start
+            .checked_add(size)
+            .filter(|end| *end <= data.len())
+            .ok_or_else(|| invalid("truncated tar entry"))?;
+        let body = &data[start..end];
+        offset = start + size.div_ceil(BLOCK) * BLOCK;
+
+        let typeflag = header[156];
+        match typeflag {
+            b'L' => {
+                long_name = Some(field_str(body));
+                continue;
+            }
+            b'K' => {
+                long_link = Some(field_str(body));
+                continue;
+            }
+            b'x' => {
+                let records = parse_pax(body);
+                long_name = records.get("path").cloned().or(long_name);
+                long_link = records.get("linkpath").cloned().or(long_link);
+                continue;
+            }
+            b'g' => continue,
+            _ => {}
+        }
+
+        let name = long_name.take().unwrap_or_else(|| {
+            let name = field_str(&header[0..100]);
+            let prefix = field_str(&header[345..500]);
+            if &header[257..262] == b"ustar" && !prefix.is_empty() {
+                format!("{}/{}", prefix, name)
+            } else {
+                name
+            }
+        });
+        let link = long_link
+            .take()
+            .unwrap_or_else(|| field_str(&header[157..257]));
+        let entry = match typeflag {
+            b'0' | 0 | b'7' if name.ends_with('/') => Entry::Dir,
+            b'0' | 0 | b'7' => Entry::File(body.to_vec()),
+            b'5' => Entry::Dir,
+            b'2' => Entry::Symlink(link),
+            b'1' => Entry::HardLink(link),
+            // devices and fifos have no place here
+            _ => continue,
+        };
+        tree.add_entry(&name, entry, mtime).map_err(errno_to_io)?;
+    }
+    Ok(())
+}
+
+fn tar_header(name: &str, size: usize, mode: u32, mtime: u64, typeflag: u8, link: &str) -> Vec<u8> {
+    let mut header = vec![0u8; BLOCK];
+    let put = |header: &mut Vec<u8>, at: usize, len: usize, s: &[u8]| {
+        header[at..at + s.len().min(len)].copy_from_slice(&s[..s.len().min(len)]);
+    };
+    let octal = |n: u64, len: usize| format!("{:0width$o}\0", n, width = len - 1).into_bytes();
+    put(&mut header, 0, 100, name.as_bytes());
+    put(&mut header, 100, 8, &octal(mode as u64, 8));
+    put(&mut header, 108, 8, &octal(0, 8));
+    put(&mut header, 116, 8, &octal(0, 8));
+    put(&mut header, 124, 12, &octal(size as u64, 12));
+    put(&mut header, 136, 12, &octal(mtime, 12));
+    header[156] = typeflag;
+    put(&mut header, 157, 100, link.as_bytes());
+    put(&mut header, 257, 8, b"ustar\x0000");
+    let sum = header_checksum(&header);
+    put(&mut header, 148, 8, format!("{:06o}\0 ", sum).as_bytes());
+    header
+}
+
+fn push_tar_entry(
+    out: &mut Vec<u8>,
+    name: &str,
+    body: &[u8],
+    mode: u32,
+    mtime: u64,
+    typeflag: u8,
+    link: &str,
+) {
+    // GNU long names for what does not fit the header
+    if name.len() > 100 {
+        let mut long = name.as_bytes().to_vec();
+        long.push(0);
+        push_tar_entry(out, "././@LongLink", &long, 0, 0, b'L', "");
+    }
+    if link.len() > 100 {
+        let mut long = link.as_bytes().to_vec();
+        long.push(0);
+        push_tar_entry(out, "././@LongLink", &long, 0, 0, b'K', "");
+    }
+    out.extend(tar_header(name, body.len(), mode, mtime, typeflag, link));
+    out.extend_from_slice(body);
+    let padding = (BLOCK - body.len() % BLOCK) % BLOCK;
+    out.resize(out.len() + padding, 0);
+}
+
+fn write_tar(tree: &Tree) -> Vec<u8> {
+    let mut out = vec![];
+    let mut first_path: BTreeMap<u64, String> = BTreeMap::new();
+    for (path, ino) in tree.list_all() {
+        let node = match tree.nodes.get(&ino) {
+            Some(node) => node,
+            None => continue,
+        };
+        let mtime = node.mtim / 1_000_000_000;
+        match &node.kind {
+            Kind::Dir { .. } => {
+                push_tar_entry(&mut out, &format!("{}/", path), &[], 0o755, mtime, b'5', "")
+            }
+            Kind::Symlink(target) => {
+                push_tar_entry(&mut out, &path, &[], 0o777, mtime, b'2', target)
+            }
+            Kind::File(data) => match first_path.get(&ino) {
+                Some(first) => push_tar_entry(&mut out, &path, &[], 0o644, mtime, b'1', first),
+                None => {
+                    if node.nlink > 1 {
+                        first_path.insert(ino, path.clone());
+                    }
+                    push_tar_entry(&mut out, &path, data, 0o644, mtime, b'0', "")
+                }
+            },
+        }
+    }
+    out.resize(out.len() + 2 * BLOCK, 0);
+    out
+}
+
+fn le16(data: &[u8], at: usize) -> io::Result<u64> {
+    data.get(at..at + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]) as u64)
+        .ok_or_else(|| invalid("truncated zip"))
+}
+
+fn le32(data: &[u8], at: usize) -> io::Result<u64> {
+    data.get(at..at + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as u64)
+        .ok_or_else(|| invalid("truncated zip"))
+}
+
+/// Unix seconds of an MS-DOS date and time, as local time taken for UTC.
+fn dos_to_unix(date: u64, time: u64) -> u64 {
+    let (year, month, day) = (1980 + (date >> 9), (date >> 5) & 0xf, date & 0x1f);
+    let days = days_from_civil(year as i64, month.max(1) as i64, day.max(1) as i64);
+    let secs = (time >> 11) * 3600 + ((time >> 5) & 0x3f) * 60 + (time & 0x1f) * 2;
+    (days.max(0) as u64) * 86400 + secs
+}
+
+fn unix_to_dos(secs: u64) -> (u16, u16) {
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    if year < 1980 {
+        return (0x21, 0);
+    }
+    let rem = secs % 86400;
+    let date = ((year - 1980) << 9 | month << 5 | day) as u16;
+    let time = ((rem / 3600) << 11 | (rem / 60 % 60) << 5 | ((rem % 60) / 2)) as u16;
+    (date, time)
+}
+
+// http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(z: i64) -> (i64, i64, i64) {
+    let z = z + 719468;
+    let era = (if z >= 0 { z } else { z - 146096 }) / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (
+        if m <= 2 {
+            yoe + era * 400 + 1
+        } else {
+            yoe + era * 400
+        },
+        m,
+        d,
+    )
+}
+
+fn read_zip(tree: &mut Tree, data: &[u8]) -> io::Result<()> {
+    // the end of central directory record, the comment may follow it
+    let eocd = (0..data.len().saturating_sub(21))
+        .rev()
+        .find(|i| data[*i..].starts_with(b"PK\x05\x06"))
+        .ok_or_else(|| invalid("not a zip archive"))?;
+    let count = le16(data, eocd + 10)?;
+    let mut at = le32(data, eocd + 16)? as usize;
+    if count == 0xffff || at as u64 == 0xffff_ffff {
+        return Err(invalid("zip64 archives are not supported"));
+    }
+    for _ in 0..count {
+        if !data[at.min(data.len())..].starts_with(b"PK\x01\x02") {
+            return Err(invalid("bad zip central directory"));
+        }
+        let method = le16(data, at + 10)?;
+        let (time, date) = (le16(data, at + 12)?, le16(data, at + 14)?);
+        let compressed = le32(data, at + 20)? as usize;
+        let size = le32(data, at + 24)? as usize;
+        let name_len = le16(data, at + 28)? as usize;
+        let extra_len = le16(data, at + 30)? as usize;
+        let comment_len = le16(data, at + 32)? as usize;
+        let made_by_unix = le16(data, at + 4)? >> 8 == 3;
+        let mode = le32(data, at + 38)? >> 16;
+        let local = le32(data, at + 42)? as usize;
+        let name = data
+            .get(at + 46..at + 46 + name_len)
+            .ok_or_else(|| invalid("truncated zip"))?;
+        let name = String::from_utf8_lossy(name).to_string();
+        at += 46 + name_len + extra_len + comment_len;
+
+        if !data[local.min(data.len())..].starts_with(b"PK\x03\x04") {
+            return Err(invalid("bad zip local header"));
+        }
+        let raw = local
+            .checked_add(30 + le16(data, local + 26)? as usize + le16(data, local + 28)? as usize)
+            .and_then(|start| Some(start..start.checked_add(compressed)?))
+            .and_then(|range| data.get(range))
+            .ok_or_else(|| invalid("truncated zip entry"))?;
+        let body = match method {
+            0 => raw.to_vec(),
+            8 => {
+                // the sizes come from the archive, it may lie about them
+                let mut body = Vec::new();
+                DeflateDecoder::new(raw)
+                    .take(size as u64)
+                    .read_to_end(&mut body)?;
+                body
+            }
+            _ => return Err(invalid("unsupported zip compression method")),
+        };
+        let entry = if name.ends_with('/') {
+            Entry::Dir
+        } else if made_by_unix && mode & 0o170000 == 0o120000 {
+            Entry::Symlink(String::from_utf8_lossy(&body).to_string())
+        } else {
+            Entry::File(body)
+        };
+        tree.add_entry(&name, entry, dos_to_unix(date, time) * 1_000_000_000)
+            .map_err(errno_to_io)?;
+    }
+    Ok(())
+}
+
+fn write_zip(tree: &Tree) -> Vec<u8> {
+    let mut out = vec![];
+    let mut central = vec![];
+    let mut count = 0u16;
+    for (path, ino) in tree.list_all() {
+        let node = match tree.nodes.get(&ino) {
+            Some(node) => node,
+            None => continue,
+        };
+        let (name, body, mode) = match &node.kind {
+            Kind::Dir { .. } => (format!("{}/", path), &[][..], 0o040755u32),
+            Kind::Symlink(target) => (path, target.as_bytes(), 0o120777),
+            Kind::File(data) => (path, &data[..], 0o100644),
+        };
+        let mut crc = flate2::Crc::new();
+        crc.update(body);
+        let (date, time) = unix_to_dos(node.mtim / 1_000_000_000);
+        let offset = out.len() as u32;
+
+        let mut common = vec![];
+        common.extend(20u16.to_le_bytes()); // version needed
+        common.extend(0x0800u16.to_le_bytes()); // utf-8 names
+        common.extend(0u16.to_le_bytes()); // stored
+        common.extend(time.to_le_bytes());
+        common.extend(date.to_le_bytes());
+        common.extend(crc.sum().to_le_bytes());
+        common.extend((body.len() as u32).to_le_bytes());
+        common.extend((body.len() as u32).to_le_bytes());
+        common.extend((name.len() as u16).to_le_bytes());
+        common.extend(0u16.to_le_bytes()); // extra
+
+        out.extend(b"PK\x03\x04");
+        out.extend(&common);
+        out.extend(name.as_bytes());
+        out.extend(body);
+
+        central.extend(b"PK\x01\x02");
+        central.extend((3u16 << 8 | 20).to_le_bytes()); // made by unix
+        central.extend(&common);
+        central.extend(0u16.to_le_bytes()); // comment
+        central.extend(0u16.to_le_bytes()); // disk
+        central.extend(0u16.to_le_bytes()); // internal attributes
+        central.extend((mode << 16).to_le_bytes());
+        central.extend(offset.to_le_bytes());
+        central.extend(name.as_bytes());
+        count += 1;
+    }
+    let central_offset = out.len() as u32;
+    out.extend(&central);
+    out.extend(b"PK\x05\x06");
+    out.extend(0u16.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out.extend(count.to_le_bytes());
+    out.extend(count.to_le_bytes());
+    out.extend((central.len() as u32).to_le_bytes());
+    out.extend(central_offset.to_le_bytes());
+    out.extend(0u16.to_le_bytes());
+    out
+}
+
+impl VirtualFs {
+    /// A filesystem with the contents of a tar archive.
+    pub fn from_tar(data: &[u8]) -> io::Result<Self> {
+        let vfs = VirtualFs::new();
+        read_tar(&mut vfs.0.borrow_mut(), data)?;
+        Ok(vfs)
+    }
+
+    /// A filesystem with the contents of a zip archive.
+    pub fn from_zip(data: &[u8]) -> io::Result<Self> {
+        let vfs = VirtualFs::new();
+        read_zip(&mut vfs.0.borrow_mut(), data)?;
+        Ok(vfs)
+    }
+
+    /// Everything below the root as a ustar archive, hard links to a file
+    /// seen before are written as links.
+    pub fn to_tar(&self) -> Vec<u8> {
+        write_tar(&self.0.borrow())
+    }
+
+    /// Everything below the root as a zip archive with stored entries.
+    pub fn to_zip(&self) -> Vec<u8> {
+        write_zip(&self.0.borrow())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seeded() -> VirtualFs {
+        let vfs = VirtualFs::new();
+        let long = format!("{}/{}", "d".repeat(80), "f".repeat(60));
+        vfs.write_file("a/b.txt", b"hello").unwrap();
+        vfs.write_file(&long, b"long").unwrap();
+        vfs.create_dir_all("empty").unwrap();
+        {
+            let mut tree = vfs.0.borrow_mut();
+            tree.path_symlink("b.txt", super::super::ROOT_FD, "a/link")
+                .unwrap();
+            tree.path_link(
+                super::super::ROOT_FD,
+                0,
+                "a/b.txt",
+                super::super::ROOT_FD,
+                "hard",
+            )
+            .unwrap();
+        }
+        vfs
+    }
+
+    fn check(vfs: &VirtualFs) {
+        assert_eq!(vfs.read_file("a/b.txt").unwrap(), b"hello");
+        assert_eq!(vfs.read_file("a/link").unwrap(), b"hello");
+        let long = format!("{}/{}", "d".repeat(80), "f".repeat(60));
+        assert_eq!(vfs.read_file(&long).unwrap(), b"long");
+        let mut tree = vfs.0.borrow_mut();
+        let root = super::super::ROOT_FD;
+        let empty = tree.path_filestat_get(root, 0, "empty").unwrap();
+        assert_eq!(empty.filetype, super::super::wasi_fs::FILETYPE_DIRECTORY);
+        let link = tree.path_filestat_get(root, 0, "a/link").unwrap();
+        assert_eq!(link.filetype, super::super::wasi_fs::FILETYPE_SYMBOLIC_LINK);
+    }
+
+    #[test]
+    fn tar_round_trip() {
+        let tar = seeded().to_tar();
+        assert_eq!(tar.len() % BLOCK, 0);
+        let vfs = VirtualFs::from_tar(&tar).unwrap();
+        check(&vfs);
+        let mut tree = vfs.0.borrow_mut();
+        let hard = tree
+            .path_filestat_get(super::super::ROOT_FD, 0, "hard")
+            .unwrap();
+        assert_eq!(hard.nlink, 2);
+        drop(tree);
+        assert_eq!(
+            vfs.to_tar(),
+            VirtualFs::from_tar(&vfs.to_tar()).unwrap().to_tar()
+        );
+    }
+
+    #[test]
+    fn zip_round_trip() {
+        let zip = seeded().to_zip();
+        check(&VirtualFs::from_zip(&zip).unwrap());
+    }
+
+    #[test]
+    fn bad_archives() {
+        let mut tar = seeded().to_tar();
+        tar[0] ^= 1;
+        assert!(VirtualFs::from_tar(&tar).is_err());
+        assert!(VirtualFs::from_zip(b"not a zip").is_err());
+        let escaping = tar_header("../x", 0, 0o644, 0, b'0', "");
+        assert!(VirtualFs::from_tar(&escaping).is_err());
+        assert!(parse_pax(b"2 x").is_empty());
+        assert!(parse_pax(b"3 xy").is_empty());
+    }
+
+    #[test]
+    fn dos_dates() {
+        let secs = 1_700_000_000;
+        let (date, time) = unix_to_dos(secs);
+        assert_eq!(dos_to_unix(date as u64, time as u64), secs);
+    }
+}
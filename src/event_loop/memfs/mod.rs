@@ -0,0 +1,1236 @@
+//! An in-memory filesystem for the `wasi_fs` calls.
+//!
+//! A `VirtualFs` is selected per `Context` with `Context::set_virtual_fs`.
+//! While one is active `wasi_fs::open_parent` resolves every path against
+//! its root, which is handed out as the descriptor `ROOT_FD`, and the
+//! `wasi_fs` calls on descriptors from `FD_BASE` up run here instead of on
+//! the host. The errors are the `Errno` values a WASI host returns for the
+//! same call, so `_node:fs` needs no changes to run on top of it.
+//!
+//! The tree can be seeded from a tar or zip archive and written back out as
+//! one, see `archive`.
+
+mod archive;
+
+use super::poll::{Fd, Filesize, Timestamp};
+use super::wasi_fs::{self, Errno, Filestat};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The first descriptor of a virtual filesystem, far above what a WASI host
+/// hands out.
+pub const FD_BASE: Fd = 1 << 30;
+/// The root directory, what `open_parent` returns for every path.
+pub const ROOT_FD: Fd = FD_BASE;
+
+const ROOT_INO: u64 = 1;
+/// Symlinks followed in a single lookup before giving up with `LOOP`.
+const MAX_SYMLINKS: u32 = 40;
+
+thread_local! {
+    static ACTIVE: RefCell<Option<VirtualFs>> = RefCell::default();
+}
+
+/// Each filesystem reports its own device, so inodes of two of them, or of
+/// one and the host, never collide.
+static NEXT_DEV: AtomicU64 = AtomicU64::new(0x766673);
+
+fn now() -> Timestamp {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as Timestamp)
+}
+
+enum Kind {
+    File(Vec<u8>),
+    Dir {
+        entries: BTreeMap<String, u64>,
+        parent: u64,
+    },
+    Symlink(String),
+}
+
+struct Node {
+    kind: Kind,
+    /// Directory entries naming a file, directories count their own.
+    nlink: u64,
+    atim: Timestamp,
+    mtim: Timestamp,
+    ctim: Timestamp,
+}
+
+impl Node {
+    fn new(kind: Kind) -> Self {
+        let t = now();
+        Node {
+            kind,
+            nlink: 1,
+            atim: t,
+            mtim: t,
+            ctim: t,
+        }
+    }
+
+    fn filetype(&self) -> wasi_fs::Filetype {
+        match self.kind {
+            Kind::File(_) => wasi_fs::FILETYPE_REGULAR_FILE,
+            Kind::Dir { .. } => wasi_fs::FILETYPE_DIRECTORY,
+            Kind::Symlink(_) => wasi_fs::FILETYPE_SYMBOLIC_LINK,
+        }
+    }
+
+    fn is_dir(&self) -> bool {
+        matches!(self.kind, Kind::Dir { .. })
+    }
+
+    fn touch(&mut self) {
+        let t = now();
+        self.mtim = t;
+        self.ctim = t;
+    }
+}
+
+struct OpenFile {
+    ino: u64,
+    pos: Filesize,
+    read: bool,
+    write: bool,
+    append: bool,
+    /// What `fd_fdstat_set_rights` left of the rights, all of them until
+    /// it is called.
+    rights: wasi_fs::Rights,
+    inheriting: wasi_fs::Rights,
+}
+
+/// The nodes by inode and the open descriptors of a `VirtualFs`.
+pub(crate) struct Tree {
+    dev: u64,
+    nodes: BTreeMap<u64, Node>,
+    next_ino: u64,
+    files: BTreeMap<Fd, OpenFile>,
+    next_fd: Fd,
+}
+
+impl Default for Tree {
+    fn default() -> Self {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(
+            ROOT_INO,
+            Node::new(Kind::Dir {
+                entries: BTreeMap::new(),
+                parent: ROOT_INO,
+            }),
+        );
+        let mut files = BTreeMap::new();
+        files.insert(
+            ROOT_FD,
+            OpenFile {
+                ino: ROOT_INO,
+                pos: 0,
+                read: true,
+                write: false,
+                append: false,
+                rights: !0,
+                inheriting: !0,
+            },
+        );
+        Tree {
+            dev: NEXT_DEV.fetch_add(1, Ordering::Relaxed),
+            nodes,
+            next_ino: ROOT_INO + 1,
+            files,
+            next_fd: ROOT_FD + 1,
+        }
+    }
+}
+
+/// Splits `path` in the directory part, resolved from `dir`, and the last
+/// name, which is empty for `/` or `.` or `..` for those.
+fn split_last(path: &str) -> (Option<&str>, &str) {
+    let trimmed = path.trim_end_matches('/');
+    match trimmed.rfind('/') {
+        Some(0) => (Some("/"), &trimmed[1..]),
+        Some(i) => (Some(&trimmed[..i]), &trimmed[i + 1..]),
+        None if trimmed.is_empty() && path.starts_with('/') => (Some("/"), ""),
+        None => (None, trimmed),
+    }
+}
+
+fn is_special_name(name: &str) -> bool {
+    name.is_empty() || name == "." || name == ".."
+}
+
+impl Tree {
+    fn node(&self, ino: u64) -> Result<&Node, Errno> {
+        self.nodes.get(&ino).ok_or(wasi_fs::ERRNO_NOENT)
+    }
+
+    fn node_mut(&mut self, ino: u64) -> Result<&mut Node, Errno> {
+        self.nodes.get_mut(&ino).ok_or(wasi_fs::ERRNO_NOENT)
+    }
+
+    fn entries(&self, ino: u64) -> Result<&BTreeMap<String, u64>, Errno> {
+        match &self.node(ino)?.kind {
+            Kind::Dir { entries, .. } => Ok(entries),
+            _ => Err(wasi_fs::ERRNO_NOTDIR),
+        }
+    }
+
+    fn entries_mut(&mut self, ino: u64) -> Result<&mut BTreeMap<String, u64>, Errno> {
+        match &mut self.node_mut(ino)?.kind {
+            Kind::Dir { entries, .. } => Ok(entries),
+            _ => Err(wasi_fs::ERRNO_NOTDIR),
+        }
+    }
+
+    fn file(&self, fd: Fd) -> Result<&OpenFile, Errno> {
+        self.files.get(&fd).ok_or(wasi_fs::ERRNO_BADF)
+    }
+
+    fn file_mut(&mut self, fd: Fd) -> Result<&mut OpenFile, Errno> {
+        self.files.get_mut(&fd).ok_or(wasi_fs::ERRNO_BADF)
+    }
+
+    /// The directory a path passed with `fd` starts from.
+    fn dir_of(&self, fd: Fd) -> Result<u64, Errno> {
+        let ino = self.file(fd)?.ino;
+        if self.node(ino)?.is_dir() {
+            Ok(ino)
+        } else {
+            Err(wasi_fs::ERRNO_NOTDIR)
+        }
+    }
+
+    fn add_node(&mut self, node: Node) -> u64 {
+        let ino = self.next_ino;
+        self.next_ino += 1;
+        self.nodes.insert(ino, node);
+        ino
+    }
+
+    /// Drops a node nothing links to and nothing has open any more.
+    fn release(&mut self, ino: u64) {
+        let unlinked = self.nodes.get(&ino).is_some_and(|node| node.nlink == 0);
+        if unlinked && !self.files.values().any(|file| file.ino == ino) {
+            self.nodes.remove(&ino);
+        }
+    }
+
+    /// Resolves `path` from the directory `start`. Symlinks on the way are
+    /// always followed, the last one only with `follow` or when the path
+    /// ends in a slash.
+    fn walk(&self, start: u64, path: &str, follow: bool, depth: &mut u32) -> Result<u64, Errno> {
+        let mut cur = if path.starts_with('/') {
+            ROOT_INO
+        } else {
+            start
+        };
+        let trailing_slash = path.ends_with('/');
+        let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+        for (i, part) in parts.iter().enumerate() {
+            let next = match (&self.node(cur)?.kind, *part) {
+                (Kind::Dir { .. }, ".") => cur,
+                (Kind::Dir { parent, .. }, "..") => *parent,
+                (Kind::Dir { entries, .. }, name) => {
+                    *entries.get(name).ok_or(wasi_fs::ERRNO_NOENT)?
+                }
+                _ => return Err(wasi_fs::ERRNO_NOTDIR),
+            };
+            if let Kind::Symlink(target) = &self.node(next)?.kind {
+                if i + 1 < parts.len() || follow || trailing_slash {
+                    *depth += 1;
+                    if *depth > MAX_SYMLINKS {
+                        return Err(wasi_fs::ERRNO_LOOP);
+                    }
+                    cur = self.walk(cur, target, true, depth)?;
+                    continue;
+                }
+            }
+            cur = next;
+        }
+        if trailing_slash && !self.node(cur)?.is_dir() {
+            return Err(wasi_fs::ERRNO_NOTDIR);
+        }
+        Ok(cur)
+    }
+
+    fn lookup(&self, fd: Fd, path: &str, follow: bool) -> Result<u64, Errno> {
+        let start = self.dir_of(fd)?;
+        self.walk(start, path, follow, &mut 0)
+    }
+
+    /// The directory that holds the last name of `path`, and that name.
+    fn lookup_parent<'a>(&self, fd: Fd, path: &'a str) -> Result<(u64, &'a str), Errno> {
+        let start = self.dir_of(fd)?;
+        let (dir, name) = split_last(path);
+        let parent = match dir {
+            Some(dir) => self.walk(start, dir, true, &mut 0)?,
+            None => start,
+        };
+        self.entries(parent)?;
+        Ok((parent, name))
+    }
+
+    /// Links a new node under `name`, which must not exist yet.
+    fn create(&mut self, fd: Fd, path: &str, kind: Kind) -> Result<u64, Errno> {
+        let (parent, name) = self.lookup_parent(fd, path)?;
+        if is_special_name(name) || self.entries(parent)?.contains_key(name) {
+            return Err(wasi_fs::ERRNO_EXIST);
+        }
+        if matches!(kind, Kind::File(_) | Kind::Symlink(_)) && path.ends_with('/') {
+            return Err(wasi_fs::ERRNO_NOENT);
+        }
+        let kind = match kind {
+            Kind::Dir { entries, .. } => Kind::Dir { entries, parent },
+            kind => kind,
+        };
+        let ino = self.add_node(Node::new(kind));
+        self.entries_mut(parent)?.insert(name.to_string(), ino);
+        self.node_mut(parent)?.touch();
+        Ok(ino)
+    }
+
+    /// Takes `name` out of `parent` and drops the node once unused.
+    fn unlink_entry(&mut self, parent: u64, name: &str) -> Result<(), Errno> {
+        let ino = self
+            .entries_mut(parent)?
+            .remove(name)
+            .ok_or(wasi_fs::ERRNO_NOENT)?;
+        self.node_mut(parent)?.touch();
+        let node = self.node_mut(ino)?;
+        node.nlink = node.nlink.saturating_sub(1);
+        if node.is_dir() {
+            node.nlink = 0;
+        }
+        node.ctim = now();
+        self.release(ino);
+        Ok(())
+    }
+
+    fn filestat(&self, ino: u64) -> Result<Filestat, Errno> {
+        let node = self.node(ino)?;
+        let (size, nlink) = match &node.kind {
+            Kind::File(data) => (data.len() as Filesize, node.nlink),
+            Kind::Symlink(target) => (target.len() as Filesize, node.nlink),
+            Kind::Dir { entries, .. } => {
+                let subdirs = entries
+                    .values()
+                    .filter(|ino| self.nodes.get(ino).is_some_and(Node::is_dir))
+                    .count();
+                (0, 2 + subdirs as u64)
+            }
+        };
+        Ok(Filestat {
+            dev: self.dev,
+            ino,
+            filetype: node.filetype(),
+            nlink,
+            size,
+            atim: node.atim,
+            mtim: node.mtim,
+            ctim: node.ctim,
+        })
+    }
+
+    fn set_times(
+        &mut self,
+        ino: u64,
+        atim: Timestamp,
+        mtim: Timestamp,
+        fst_flags: wasi_fs::Fstflags,
+    ) -> Result<(), Errno> {
+        let t = now();
+        let node = self.node_mut(ino)?;
+        if fst_flags & wasi_fs::FSTFLAGS_ATIM_NOW != 0 {
+            node.atim = t;
+        } else if fst_flags & wasi_fs::FSTFLAGS_ATIM != 0 {
+            node.atim = atim;
+        }
+        if fst_flags & wasi_fs::FSTFLAGS_MTIM_NOW != 0 {
+            node.mtim = t;
+        } else if fst_flags & wasi_fs::FSTFLAGS_MTIM != 0 {
+            node.mtim = mtim;
+        }
+        node.ctim = t;
+        Ok(())
+    }
+
+    fn file_data(&mut self, ino: u64) -> Result<&mut Vec<u8>, Errno> {
+        match &mut self.node_mut(ino)?.kind {
+            Kind::File(data) => Ok(data),
+            Kind::Dir { .. } => Err(wasi_fs::ERRNO_ISDIR),
+            Kind::Symlink(_) => Err(wasi_fs::ERRNO_INVAL),
+        }
+    }
+
+    fn read_at(&mut self, fd: Fd, pos: Filesize, buf: &mut [u8]) -> Result<usize, Errno> {
+        let file = self.file(fd)?;
+        if !file.read {
+            return Err(wasi_fs::ERRNO_BADF);
+        }
+        let ino = file.ino;
+        let data = self.file_data(ino)?;
+        let start = (pos as usize).min(data.len());
+        let n = buf.len().min(data.len() - start);
+        buf[..n].copy_from_slice(&data[start..start + n]);
+        self.node_mut(ino)?.atim = now();
+        Ok(n)
+    }
+
+    fn write_at(&mut self, fd: Fd, pos: Option<Filesize>, buf: &[u8]) -> Result<usize, Errno> {
+        let file = self.file(fd)?;
+        if !file.write {
+            return Err(wasi_fs::ERRNO_BADF);
+        }
+        let (ino, append, cur) = (file.ino, file.append, file.pos);
+        let data = self.file_data(ino)?;
+        let start = match pos {
+            Some(pos) => pos as usize,
+            None if append => data.len(),
+            None => cur as usize,
+        };
+        if data.len() < start + buf.len() {
+            data.resize(start + buf.len(), 0);
+        }
+        data[start..start + buf.len()].copy_from_slice(buf);
+        self.node_mut(ino)?.touch();
+        if pos.is_none() {
+            self.file_mut(fd)?.pos = (start + buf.len()) as Filesize;
+        }
+        Ok(buf.len())
+    }
+
+    pub(crate) fn fd_close(&mut self, fd: Fd) -> Result<(), Errno> {
+        if fd == ROOT_FD {
+            return Err(wasi_fs::ERRNO_NOTSUP);
+        }
+        let file = self.files.remove(&fd).ok_or(wasi_fs::ERRNO_BADF)?;
+        self.release(file.ino);
+        Ok(())
+    }
+
+    pub(crate) fn fd_sync(&mut self, fd: Fd) -> Result<(), Errno> {
+        self.file(fd).map(|_| ())
+    }
+
+    /// There is no page cache to advise, only the fd and its rights are
+    /// checked.
+    pub(crate) fn fd_advise(&mut self, fd: Fd) -> Result<(), Errno> {
+        if self.fd_fdstat_get(fd)?.fs_rights_base & wasi_fs::RIGHTS_FD_ADVISE == 0 {
+            return Err(wasi_fs::ERRNO_NOTCAPABLE);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn fd_fdstat_get(&mut self, fd: Fd) -> Result<wasi_fs::Fdstat, Errno> {
+        let file = self.file(fd)?;
+        let mut rights = wasi_fs::RIGHTS_FD_SEEK
+            | wasi_fs::RIGHTS_FD_TELL
+            | wasi_fs::RIGHTS_FD_ADVISE
+            | wasi_fs::RIGHTS_FD_FILESTAT_GET
+            | wasi_fs::RIGHTS_FD_READDIR;
+        if file.read {
+            rights |= wasi_fs::RIGHTS_FD_READ;
+        }
+        if file.write {
+            rights |= wasi_fs::RIGHTS_FD_WRITE | wasi_fs::RIGHTS_FD_FILESTAT_SET_SIZE;
+        }
+        Ok(wasi_fs::Fdstat {
+            fs_filetype: self.node(file.ino)?.filetype(),
+            fs_flags: if file.append {
+                wasi_fs::FDFLAGS_APPEND
+            } else {
+                0
+            },
+            fs_rights_base: rights & file.rights,
+            fs_rights_inheriting: rights & file.inheriting,
+        })
+    }
+
+    /// Rights can only be dropped, asking for one the fd does not have fails
+    /// with `NOTCAPABLE`.
+    pub(crate) fn fd_fdstat_set_rights(
+        &mut self,
+        fd: Fd,
+        fs_rights_base: wasi_fs::Rights,
+        fs_rights_inheriting: wasi_fs::Rights,
+    ) -> Result<(), Errno> {
+        let current = self.fd_fdstat_get(fd)?;
+        if fs_rights_base & !current.fs_rights_base != 0
+            || fs_rights_inheriting & !current.fs_rights_inheriting != 0
+        {
+            return Err(wasi_fs::ERRNO_NOTCAPABLE);
+        }
+        let file = self.file_mut(fd)?;
+        file.rights = fs_rights_base;
+        file.inheriting = fs_rights_inheriting;
+        file.read &= fs_rights_base & wasi_fs::RIGHTS_FD_READ != 0;
+        file.write &= fs_rights_base & wasi_fs::RIGHTS_FD_WRITE != 0;
+        Ok(())
+    }
+
+    pub(crate) fn fd_fdstat_set_flags(
+        &mut self,
+        fd: Fd,
+        flags: wasi_fs::Fdflags,
+    ) -> Result<(), Errno> {
+        self.file_mut(fd)?.append = flags & wasi_fs::FDFLAGS_APPEND != 0;
+        Ok(())
+    }
+
+    pub(crate) fn fd_filestat_get(&mut self, fd: Fd) -> Result<Filestat, Errno> {
+        let ino = self.file(fd)?.ino;
+        self.filestat(ino)
+    }
+
+    pub(crate) fn fd_filestat_set_size(&mut self, fd: Fd, size: Filesize) -> Result<(), Errno> {
+        let file = self.file(fd)?;
+        if !file.write {
+            return Err(wasi_fs::ERRNO_BADF);
+        }
+        let ino = file.ino;
+        self.file_data(ino)
+            .map_err(|_| wasi_fs::ERRNO_INVAL)?
+            .resize(size as usize, 0);
+        self.node_mut(ino)?.touch();
+        Ok(())
+    }
+
+    pub(crate) fn fd_allocate(
+        &mut self,
+        fd: Fd,
+        offset: Filesize,
+        len: Filesize,
+    ) -> Result<(), Errno> {
+        let ino = self.file(fd)?.ino;
+        let data = self.file_data(ino)?;
+        let end = (offset + len) as usize;
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn fd_filestat_set_times(
+        &mut self,
+        fd: Fd,
+        atim: Timestamp,
+        mtim: Timestamp,
+        fst_flags: wasi_fs::Fstflags,
+    ) -> Result<(), Errno> {
+        let ino = self.file(fd)?.ino;
+        self.set_times(ino, atim, mtim, fst_flags)
+    }
+
+    pub(crate) fn fd_read(&mut self, fd: Fd, buf: &mut [u8]) -> Result<usize, Errno> {
+        let pos = self.file(fd)?.pos;
+        let n = self.read_at(fd, pos, buf)?;
+        self.file_mut(fd)?.pos = pos + n as Filesize;
+        Ok(n)
+    }
+
+    pub(crate) fn fd_pread(
+        &mut self,
+        fd: Fd,
+        buf: &mut [u8],
+        offset: Filesize,
+    ) -> Result<usize, Errno> {
+        self.read_at(fd, offset, buf)
+    }
+
+    pub(crate) fn fd_write(&mut self, fd: Fd, buf: &[u8]) -> Result<usize, Errno> {
+        self.write_at(fd, None, buf)
+    }
+
+    pub(crate) fn fd_pwrite(
+        &mut self,
+        fd: Fd,
+        buf: &[u8],
+        offset: Filesize,
+    ) -> Result<usize, Errno> {
+        self.write_at(fd, Some(offset), buf)
+    }
+
+    pub(crate) fn fd_seek(
+        &mut self,
+        fd: Fd,
+        offset: wasi_fs::Filedelta,
+        whence: wasi_fs::Whence,
+    ) -> Result<Filesize, Errno> {
+        let file = self.file(fd)?;
+        let base = match whence {
+            wasi_fs::WHENCE_SET => 0,
+            wasi_fs::WHENCE_CUR => file.pos as i64,
+            _ => match &self.node(file.ino)?.kind {
+                Kind::File(data) => data.len() as i64,
+                _ => 0,
+            },
+        };
+        let pos = base.checked_add(offset).filter(|pos| *pos >= 0);
+        let pos = pos.ok_or(wasi_fs::ERRNO_INVAL)? as Filesize;
+        self.file_mut(fd)?.pos = pos;
+        Ok(pos)
+    }
+
+    pub(crate) fn fd_tell(&mut self, fd: Fd) -> Result<Filesize, Errno> {
+        Ok(self.file(fd)?.pos)
+    }
+
+    /// Fills `buf` with `Dirent`s and names like a WASI host, `.` and `..`
+    /// first. The last entry is cut off when it does not fit and the result
+    /// is then the full length of `buf`.
+    pub(crate) fn fd_readdir(
+        &mut self,
+        fd: Fd,
+        buf: &mut [u8],
+        cookie: wasi_fs::Dircookie,
+    ) -> Result<usize, Errno> {
+        let ino = self.file(fd)?.ino;
+        let (entries, parent) = match &self.node(ino)?.kind {
+            Kind::Dir { entries, parent } => (entries, *parent),
+            _ => return Err(wasi_fs::ERRNO_NOTDIR),
+        };
+        let listed = vec![(".", ino), ("..", parent)]
+            .into_iter()
+            .chain(entries.iter().map(|(name, ino)| (name.as_str(), *ino)));
+        let header = std::mem::size_of::<wasi_fs::Dirent>();
+        let mut used = 0;
+        for (i, (name, entry)) in listed.enumerate().skip(cookie as usize) {
+            let dirent = wasi_fs::Dirent {
+                d_next: i as u64 + 1,
+                d_ino: entry,
+                d_namlen: name.len() as u32,
+                d_type: self.node(entry)?.filetype(),
+            };
+            let mut bytes = vec![0u8; header];
+            // the padding of the struct goes out as zeros
+            unsafe {
+                std::ptr::write_unaligned(bytes.as_mut_ptr() as *mut wasi_fs::Dirent, dirent)
+            };
+            bytes.extend_from_slice(name.as_bytes());
+            let n = bytes.len().min(buf.len() - used);
+            buf[used..used + n].copy_from_slice(&bytes[..n]);
+            used += n;
+            if used == buf.len() {
+                break;
+            }
+        }
+        Ok(used)
+    }
+
+    pub(crate) fn path_create_directory(&mut self, fd: Fd, path: &str) -> Result<(), Errno> {
+        let dir = Kind::Dir {
+            entries: BTreeMap::new(),
+            parent: ROOT_INO,
+        };
+        self.create(fd, path, dir).map(|_| ())
+    }
+
+    pub(crate) fn path_filestat_get(
+        &mut self,
+        fd: Fd,
+        flags: wasi_fs::Lookupflags,
+        path: &str,
+    ) -> Result<Filestat, Errno> {
+        let follow = flags & wasi_fs::LOOKUPFLAGS_SYMLINK_FOLLOW != 0;
+        let ino = self.lookup(fd, path, follow)?;
+        self.filestat(ino)
+    }
+
+    pub(crate) fn path_filestat_set_times(
+        &mut self,
+        fd: Fd,
+        flags: wasi_fs::Lookupflags,
+        path: &str,
+        atim: Timestamp,
+        mtim: Timestamp,
+        fst_flags: wasi_fs::Fstflags,
+    ) -> Result<(), Errno> {
+        let follow = flags & wasi_fs::LOOKUPFLAGS_SYMLINK_FOLLOW != 0;
+        let ino = self.lookup(fd, path, follow)?;
+        self.set_times(ino, atim, mtim, fst_flags)
+    }
+
+    pub(crate) fn path_link(
+        &mut self,
+        old_fd: Fd,
+        old_flags: wasi_fs::Lookupflags,
+        old_path: &str,
+        new_fd: Fd,
+        new_path: &str,
+    ) -> Result<(), Errno> {
+        let follow = old_flags & wasi_fs::LOOKUPFLAGS_SYMLINK_FOLLOW != 0;
+        let ino = self.lookup(old_fd, old_path, follow)?;
+        if self.node(ino)?.is_dir() {
+            return Err(wasi_fs::ERRNO_PERM);
+        }
+        let (parent, name) = self.lookup_parent(new_fd, new_path)?;
+        if is_special_name(name) || self.entries(parent)?.contains_key(name) {
+            return Err(wasi_fs::ERRNO_EXIST);
+        }
+        self.entries_mut(parent)?.insert(name.to_string(), ino);
+        self.node_mut(parent)?.touch();
+        let node = self.node_mut(ino)?;
+        node.nlink += 1;
+        node.ctim = now();
+        Ok(())
+    }
+
+    /// Opens `path`. A final symlink is followed with
+    /// `LOOKUPFLAGS_SYMLINK_FOLLOW`, an exclusive create never follows it.
+    pub(crate) fn path_open(
+        &mut self,
+        fd: Fd,
+        dirflags: wasi_fs::Lookupflags,
+        path: &str,
+        oflags: wasi_fs::Oflags,
+        fs_rights_base: wasi_fs::Rights,
+        fdflags: wasi_fs::Fdflags,
+    ) -> Result<Fd, Errno> {
+        let write = fs_rights_base & wasi_fs::RIGHTS_FD_WRITE != 0;
+        let excl = oflags & wasi_fs::OFLAGS_CREAT != 0 && oflags & wasi_fs::OFLAGS_EXCL != 0;
+        // an exclusive create takes any entry, a symlink too, as existing
+        let follow = dirflags & wasi_fs::LOOKUPFLAGS_SYMLINK_FOLLOW != 0 && !excl;
+        let ino = match self.lookup(fd, path, follow) {
+            Ok(_) if excl => return Err(wasi_fs::ERRNO_EXIST),
+            Ok(ino) if self.node(ino)?.filetype() == wasi_fs::FILETYPE_SYMBOLIC_LINK => {
+                return Err(wasi_fs::ERRNO_LOOP)
+            }
+            Ok(ino) => {
+                let is_dir = self.node(ino)?.is_dir();
+                if oflags & wasi_fs::OFLAGS_DIRECTORY != 0 && !is_dir {
+                    return Err(wasi_fs::ERRNO_NOTDIR);
+                }
+                if is_dir && (write || oflags & wasi_fs::OFLAGS_TRUNC != 0) {
+                    return Err(wasi_fs::ERRNO_ISDIR);
+                }
+                if !is_dir && oflags & wasi_fs::OFLAGS_TRUNC != 0 {
+                    self.file_data(ino)?.clear();
+                    self.node_mut(ino)?.touch();
+                }
+                ino
+            }
+            Err(e) if e == wasi_fs::ERRNO_NOENT && oflags & wasi_fs::OFLAGS_CREAT != 0 => {
+                if oflags & wasi_fs::OFLAGS_DIRECTORY != 0 {
+                    return Err(wasi_fs::ERRNO_INVAL);
+                }
+                // a dangling symlink is not created through
+                match self.create(fd, path, Kind::File(vec![])) {
+                    Err(e) if e == wasi_fs::ERRNO_EXIST => return Err(wasi_fs::ERRNO_NOENT),
+                    res => res?,
+                }
+            }
+            Err(e) => return Err(e),
+        };
+        let new_fd = self.next_fd;
+        self.next_fd += 1;
+        self.files.insert(
+            new_fd,
+            OpenFile {
+                ino,
+                pos: 0,
+                read: fs_rights_base & wasi_fs::RIGHTS_FD_READ != 0,
+                write,
+                append: fdflags & wasi_fs::FDFLAGS_APPEND != 0,
+                rights: !0,
+                inheriting: !0,
+            },
+        );
+        Ok(new_fd)
+    }
+
+    pub(crate) fn path_readlink(&mut self, fd: Fd, path: &str) -> Result<Vec<u8>, Errno> {
+        let ino = self.lookup(fd, path, false)?;
+        match &self.node(ino)?.kind {
+            Kind::Symlink(target) => Ok(target.as_bytes().to_vec()),
+            _ => Err(wasi_fs::ERRNO_INVAL),
+        }
+    }
+
+    pub(crate) fn path_remove_directory(&mut self, fd: Fd, path: &str) -> Result<(), Errno> {
+        let (parent, name) = self.lookup_parent(fd, path)?;
+        if name.is_empty() {
+            return Err(wasi_fs::ERRNO_BUSY);
+        }
+        if is_special_name(name) {
+            return Err(wasi_fs::ERRNO_INVAL);
+        }
+        let ino = *self
+            .entries(parent)?
+            .get(name)
+            .ok_or(wasi_fs::ERRNO_NOENT)?;
+        if !self.entries(ino)?.is_empty() {
+            return Err(wasi_fs::ERRNO_NOTEMPTY);
+        }
+        self.unlink_entry(parent, name)
+    }
+
+    pub(crate) fn path_rename(
+        &mut self,
+        fd: Fd,
+        old_path: &str,
+        new_fd: Fd,
+        new_path: &str,
+    ) -> Result<(), Errno> {
+        let (old_parent, old_name) = self.lookup_parent(fd, old_path)?;
+        let (new_parent, new_name) = self.lookup_parent(new_fd, new_path)?;
+        if is_special_name(old_name) || is_special_name(new_name) {
+            return Err(wasi_fs::ERRNO_BUSY);
+        }
+        let ino = *self
+            .entries(old_parent)?
+            .get(old_name)
+            .ok_or(wasi_fs::ERRNO_NOENT)?;
+        let is_dir = self.node(ino)?.is_dir();
+        if !is_dir && (old_path.ends_with('/') || new_path.ends_with('/')) {
+            return Err(wasi_fs::ERRNO_NOTDIR);
+        }
+        if let Some(&existing) = self.entries(new_parent)?.get(new_name) {
+            if existing == ino {
+                return Ok(());
+            }
+            match (is_dir, self.node(existing)?.is_dir()) {
+                (true, false) => return Err(wasi_fs::ERRNO_NOTDIR),
+                (false, true) => return Err(wasi_fs::ERRNO_ISDIR),
+                (true, true) if !self.entries(existing)?.is_empty() => {
+                    return Err(wasi_fs::ERRNO_NOTEMPTY)
+                }
+                _ => {}
+            }
+        }
+        if is_dir {
+            // a directory cannot move below itself
+            let mut up = new_parent;
+            loop {
+                if up == ino {
+                    return Err(wasi_fs::ERRNO_INVAL);
+                }
+                match self.node(up)?.kind {
+                    Kind::Dir { parent, .. } if parent != up => up = parent,
+                    _ => break,
+                }
+            }
+        }
+        if self.entries(new_parent)?.contains_key(new_name) {
+            self.unlink_entry(new_parent, new_name)?;
+        }
+        self.entries_mut(old_parent)?.remove(old_name);
+        self.node_mut(old_parent)?.touch();
+        self.entries_mut(new_parent)?
+            .insert(new_name.to_string(), ino);
+        self.node_mut(new_parent)?.touch();
+        let node = self.node_mut(ino)?;
+        node.ctim = now();
+        if let Kind::Dir { parent, .. } = &mut node.kind {
+            *parent = new_parent;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn path_symlink(
+        &mut self,
+        old_path: &str,
+        fd: Fd,
+        new_path: &str,
+    ) -> Result<(), Errno> {
+        self.create(fd, new_path, Kind::Symlink(old_path.to_string()))
+            .map(|_| ())
+    }
+
+    pub(crate) fn path_unlink_file(&mut self, fd: Fd, path: &str) -> Result<(), Errno> {
+        let (parent, name) = self.lookup_parent(fd, path)?;
+        if is_special_name(name) {
+            return Err(wasi_fs::ERRNO_ISDIR);
+        }
+        let ino = *self
+            .entries(parent)?
+            .get(name)
+            .ok_or(wasi_fs::ERRNO_NOENT)?;
+        match self.node(ino)?.kind {
+            Kind::Dir { .. } => Err(wasi_fs::ERRNO_ISDIR),
+            Kind::File(_) if path.ends_with('/') => Err(wasi_fs::ERRNO_NOTDIR),
+            _ => self.unlink_entry(parent, name),
+        }
+    }
+}
+
+/// A filesystem that lives in memory. Clones share the same tree, so the
+/// caller can keep one to look at or snapshot what a `Context` left behind.
+#[derive(Clone, Default)]
+pub struct VirtualFs(Rc<RefCell<Tree>>);
+
+impl VirtualFs {
+    /// An empty filesystem, only the root directory exists.
+    pub fn new() -> Self {
+        VirtualFs::default()
+    }
+
+    /// Writes `data` to the file `path`, creating it and the directories
+    /// above it as needed.
+    pub fn write_file(&self, path: &str, data: &[u8]) -> Result<(), Errno> {
+        let mut tree = self.0.borrow_mut();
+        let ino = tree.make_file(path)?;
+        *tree.file_data(ino)? = data.to_vec();
+        tree.node_mut(ino)?.touch();
+        Ok(())
+    }
+
+    /// The contents of the file `path`, following symlinks.
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>, Errno> {
+        let mut tree = self.0.borrow_mut();
+        let ino = tree.walk(ROOT_INO, path, true, &mut 0)?;
+        tree.file_data(ino).map(|data| data.clone())
+    }
+
+    /// Creates the directory `path` and the ones missing above it.
+    pub fn create_dir_all(&self, path: &str) -> Result<(), Errno> {
+        self.0.borrow_mut().make_dirs(path).map(|_| ())
+    }
+
+    /// Creates the symlink `path` pointing to `target`.
+    pub fn symlink(&self, target: &str, path: &str) -> Result<(), Errno> {
+        let mut tree = self.0.borrow_mut();
+        if let (Some(dir), _) = split_last(path) {
+            tree.make_dirs(dir)?;
+        }
+        tree.path_symlink(target, ROOT_FD, path.trim_start_matches('/'))
+    }
+
+    /// Whether this is the filesystem the `wasi_fs` calls of the thread
+    /// currently run against.
+    fn is_active(&self) -> bool {
+        ACTIVE.with(|active| {
+            active
+                .borrow()
+                .as_ref()
+                .is_some_and(|vfs| Rc::ptr_eq(&vfs.0, &self.0))
+        })
+    }
+}
+
+impl Tree {
+    /// The directory `path`, created with the ones above it if missing.
+    fn make_dirs(&mut self, path: &str) -> Result<u64, Errno> {
+        let mut cur = ROOT_INO;
+        for part in path.split('/').filter(|p| !p.is_empty() && *p != ".") {
+            cur = match self.walk(cur, part, true, &mut 0) {
+                Ok(ino) if self.node(ino)?.is_dir() => ino,
+                Ok(_) => return Err(wasi_fs::ERRNO_NOTDIR),
+                Err(e) if e == wasi_fs::ERRNO_NOENT => {
+                    let dir = Kind::Dir {
+                        entries: BTreeMap::new(),
+                        parent: cur,
+                    };
+                    let ino = self.add_node(Node::new(dir));
+                    self.entries_mut(cur)?.insert(part.to_string(), ino);
+                    ino
+                }
+                Err(e) => return Err(e),
+            };
+        }
+        Ok(cur)
+    }
+
+    /// The file `path`, created empty if missing.
+    fn make_file(&mut self, path: &str) -> Result<u64, Errno> {
+        let (dir, name) = split_last(path);
+        if is_special_name(name) {
+            return Err(wasi_fs::ERRNO_ISDIR);
+        }
+        let parent = self.make_dirs(dir.unwrap_or(""))?;
+        match self.walk(parent, name, true, &mut 0) {
+            Ok(ino) => Ok(ino),
+            Err(e) if e == wasi_fs::ERRNO_NOENT => {
+                let ino = self.add_node(Node::new(Kind::File(vec![])));
+                self.entries_mut(parent)?.insert(name.to_string(), ino);
+                Ok(ino)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Makes `vfs` the filesystem of the `wasi_fs` calls on this thread, `None`
+/// goes back to the host.
+pub(crate) fn activate(vfs: Option<VirtualFs>) {
+    ACTIVE.with(|active| *active.borrow_mut() = vfs);
+}
+
+/// Goes back to the host if `vfs` is the active filesystem.
+pub(crate) fn deactivate(vfs: &VirtualFs) {
+    if vfs.is_active() {
+        activate(None);
+    }
+}
+
+pub(crate) fn is_active() -> bool {
+    ACTIVE.with(|active| active.borrow().is_some())
+}
+
+pub(crate) fn is_virtual_fd(fd: Fd) -> bool {
+    fd >= FD_BASE
+}
+
+/// Runs `f` on the active tree if `fd` belongs to a virtual filesystem,
+/// `None` leaves the call to the host.
+pub(crate) fn route<R>(
+    fd: Fd,
+    f: impl FnOnce(&mut Tree) -> Result<R, Errno>,
+) -> Option<Result<R, Errno>> {
+    if !is_virtual_fd(fd) {
+        return None;
+    }
+    Some(ACTIVE.with(|active| match active.borrow().as_ref() {
+        Some(vfs) => f(&mut vfs.0.borrow_mut()),
+        None => Err(wasi_fs::ERRNO_BADF),
+    }))
+}
+
+/// What `open_parent` gives for `path` while a virtual filesystem is
+/// active: the root and `path` relative to it.
+pub(crate) fn open_parent(path: &str) -> Option<(Fd, String)> {
+    if !is_active() {
+        return None;
+    }
+    let relative = path.trim_start_matches('/');
+    let relative = if relative.is_empty() { "." } else { relative };
+    Some((ROOT_FD, relative.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(tree: &mut Tree, path: &str, oflags: wasi_fs::Oflags) -> Result<Fd, Errno> {
+        let rights = wasi_fs::RIGHTS_FD_READ | wasi_fs::RIGHTS_FD_WRITE;
+        tree.path_open(
+            ROOT_FD,
+            wasi_fs::LOOKUPFLAGS_SYMLINK_FOLLOW,
+            path,
+            oflags,
+            rights,
+            0,
+        )
+    }
+
+    fn stat(tree: &mut Tree, path: &str) -> Result<Filestat, Errno> {
+        tree.path_filestat_get(ROOT_FD, 0, path)
+    }
+
+    #[test]
+    fn files_and_descriptors() {
+        let mut tree = Tree::default();
+        assert_eq!(open(&mut tree, "a.txt", 0), Err(wasi_fs::ERRNO_NOENT));
+        let fd = open(&mut tree, "a.txt", wasi_fs::OFLAGS_CREAT).unwrap();
+        assert!(is_virtual_fd(fd));
+        assert_eq!(tree.fd_write(fd, b"hello world"), Ok(11));
+        assert_eq!(tree.fd_seek(fd, 6, wasi_fs::WHENCE_SET), Ok(6));
+        let mut buf = [0u8; 16];
+        assert_eq!(tree.fd_read(fd, &mut buf), Ok(5));
+        assert_eq!(&buf[..5], b"world");
+        assert_eq!(tree.fd_pwrite(fd, b"!", 13), Ok(1));
+        assert_eq!(tree.fd_filestat_get(fd).unwrap().size, 14);
+        assert_eq!(tree.fd_pread(fd, &mut buf, 10), Ok(4));
+        assert_eq!(&buf[..4], b"d\0\0!");
+
+        let excl = wasi_fs::OFLAGS_CREAT | wasi_fs::OFLAGS_EXCL;
+        assert_eq!(open(&mut tree, "a.txt", excl), Err(wasi_fs::ERRNO_EXIST));
+        assert_eq!(
+            open(&mut tree, "a.txt", wasi_fs::OFLAGS_DIRECTORY),
+            Err(wasi_fs::ERRNO_NOTDIR)
+        );
+        assert_eq!(open(&mut tree, "a.txt/b", 0), Err(wasi_fs::ERRNO_NOTDIR));
+
+        // an unlinked file stays readable until it is closed
+        tree.path_unlink_file(ROOT_FD, "a.txt").unwrap();
+        assert_eq!(stat(&mut tree, "a.txt").err(), Some(wasi_fs::ERRNO_NOENT));
+        assert_eq!(tree.fd_pread(fd, &mut buf, 0), Ok(14));
+        tree.fd_close(fd).unwrap();
+        assert_eq!(tree.fd_close(fd), Err(wasi_fs::ERRNO_BADF));
+        assert_eq!(tree.nodes.len(), 1);
+    }
+
+    #[test]
+    fn rights() {
+        let mut tree = Tree::default();
+        let fd = open(&mut tree, "a.txt", wasi_fs::OFLAGS_CREAT).unwrap();
+        assert_eq!(tree.fd_advise(fd), Ok(()));
+        assert_eq!(tree.fd_advise(fd + 1), Err(wasi_fs::ERRNO_BADF));
+
+        // rights can be dropped but not taken back
+        let rights = tree.fd_fdstat_get(fd).unwrap().fs_rights_base;
+        let dropped = wasi_fs::RIGHTS_FD_WRITE
+            | wasi_fs::RIGHTS_FD_FILESTAT_SET_SIZE
+            | wasi_fs::RIGHTS_FD_ADVISE;
+        let read_only = rights & !dropped;
+        assert_eq!(tree.fd_fdstat_set_rights(fd, read_only, 0), Ok(()));
+        assert_eq!(tree.fd_fdstat_get(fd).unwrap().fs_rights_base, read_only);
+        assert_eq!(tree.fd_write(fd, b"x"), Err(wasi_fs::ERRNO_BADF));
+        assert_eq!(tree.fd_advise(fd), Err(wasi_fs::ERRNO_NOTCAPABLE));
+        assert_eq!(
+            tree.fd_fdstat_set_rights(fd, rights, 0),
+            Err(wasi_fs::ERRNO_NOTCAPABLE)
+        );
+        assert_eq!(
+            tree.fd_fdstat_set_rights(fd, read_only, wasi_fs::RIGHTS_FD_READ),
+            Err(wasi_fs::ERRNO_NOTCAPABLE)
+        );
+        tree.fd_close(fd).unwrap();
+    }
+
+    #[test]
+    fn directories() {
+        let mut tree = Tree::default();
+        tree.path_create_directory(ROOT_FD, "d").unwrap();
+        tree.path_create_directory(ROOT_FD, "d/e/").unwrap();
+        assert_eq!(
+            tree.path_create_directory(ROOT_FD, "d"),
+            Err(wasi_fs::ERRNO_EXIST)
+        );
+        assert_eq!(
+            tree.path_create_directory(ROOT_FD, "x/y"),
+            Err(wasi_fs::ERRNO_NOENT)
+        );
+        let fd = open(&mut tree, "d/e/../f", wasi_fs::OFLAGS_CREAT).unwrap();
+        tree.fd_close(fd).unwrap();
+        assert_eq!(stat(&mut tree, "d").unwrap().nlink, 3);
+        assert_eq!(
+            tree.path_remove_directory(ROOT_FD, "d"),
+            Err(wasi_fs::ERRNO_NOTEMPTY)
+        );
+        assert_eq!(
+            tree.path_remove_directory(ROOT_FD, "d/f"),
+            Err(wasi_fs::ERRNO_NOTDIR)
+        );
+        assert_eq!(
+            tree.path_unlink_file(ROOT_FD, "d/e"),
+            Err(wasi_fs::ERRNO_ISDIR)
+        );
+
+        let dir = tree
+            .path_open(ROOT_FD, 0, "d", wasi_fs::OFLAGS_DIRECTORY, 0, 0)
+            .unwrap();
+        let mut buf = vec![0u8; 256];
+        let len = tree.fd_readdir(dir, &mut buf, 0).unwrap();
+        let header = std::mem::size_of::<wasi_fs::Dirent>();
+        let mut names = vec![];
+        let mut at = 0;
+        while at < len {
+            let dirent =
+                unsafe { std::ptr::read_unaligned(buf[at..].as_ptr() as *const wasi_fs::Dirent) };
+            let end = at + header + dirent.d_namlen as usize;
+            names.push(String::from_utf8_lossy(&buf[at + header..end]).to_string());
+            at = end;
+        }
+        assert_eq!(names, [".", "..", "e", "f"]);
+        // a buffer that is too small is filled up
+        assert_eq!(tree.fd_readdir(dir, &mut buf[..30], 0), Ok(30));
+        assert_eq!(tree.fd_readdir(dir, &mut buf, 4), Ok(0));
+    }
+
+    #[test]
+    fn symlinks() {
+        let mut tree = Tree::default();
+        tree.path_create_directory(ROOT_FD, "d").unwrap();
+        let fd = open(&mut tree, "d/target", wasi_fs::OFLAGS_CREAT).unwrap();
+        tree.fd_write(fd, b"data").unwrap();
+        tree.path_symlink("target", ROOT_FD, "d/link").unwrap();
+        tree.path_symlink("/d", ROOT_FD, "abs").unwrap();
+        tree.path_symlink("loop", ROOT_FD, "loop").unwrap();
+
+        assert_eq!(tree.path_readlink(ROOT_FD, "d/link").unwrap(), b"target");
+        assert_eq!(
+            tree.path_readlink(ROOT_FD, "d/target"),
+            Err(wasi_fs::ERRNO_INVAL)
+        );
+        let follow = wasi_fs::LOOKUPFLAGS_SYMLINK_FOLLOW;
+        let linked = tree.path_filestat_get(ROOT_FD, follow, "abs/link").unwrap();
+        assert_eq!(linked.size, 4);
+        assert_eq!(linked.filetype, wasi_fs::FILETYPE_REGULAR_FILE);
+        let own = stat(&mut tree, "abs/link").unwrap();
+        assert_eq!(own.filetype, wasi_fs::FILETYPE_SYMBOLIC_LINK);
+        assert_eq!(
+            tree.path_filestat_get(ROOT_FD, follow, "loop").err(),
+            Some(wasi_fs::ERRNO_LOOP)
+        );
+        assert_eq!(
+            tree.path_symlink("x", ROOT_FD, "abs"),
+            Err(wasi_fs::ERRNO_EXIST)
+        );
+        let excl = wasi_fs::OFLAGS_CREAT | wasi_fs::OFLAGS_EXCL;
+        tree.path_symlink("missing", ROOT_FD, "dangling").unwrap();
+        assert_eq!(open(&mut tree, "dangling", excl), Err(wasi_fs::ERRNO_EXIST));
+        assert_eq!(
+            open(&mut tree, "dangling", wasi_fs::OFLAGS_CREAT),
+            Err(wasi_fs::ERRNO_NOENT)
+        );
+        assert_eq!(
+            tree.path_open(ROOT_FD, 0, "d/link", 0, wasi_fs::RIGHTS_FD_READ, 0),
+            Err(wasi_fs::ERRNO_LOOP)
+        );
+
+        tree.path_link(ROOT_FD, 0, "d/target", ROOT_FD, "hard")
+            .unwrap();
+        assert_eq!(stat(&mut tree, "hard").unwrap().nlink, 2);
+        assert_eq!(
+            tree.path_link(ROOT_FD, 0, "d", ROOT_FD, "dir-link"),
+            Err(wasi_fs::ERRNO_PERM)
+        );
+    }
+
+    #[test]
+    fn rename() {
+        let mut tree = Tree::default();
+        tree.path_create_directory(ROOT_FD, "a").unwrap();
+        tree.path_create_directory(ROOT_FD, "a/b").unwrap();
+        tree.path_create_directory(ROOT_FD, "c").unwrap();
+        let fd = open(&mut tree, "f", wasi_fs::OFLAGS_CREAT).unwrap();
+        tree.fd_close(fd).unwrap();
+
+        assert_eq!(
+            tree.path_rename(ROOT_FD, "a", ROOT_FD, "a/b/x"),
+            Err(wasi_fs::ERRNO_INVAL)
+        );
+        assert_eq!(
+            tree.path_rename(ROOT_FD, "f", ROOT_FD, "c"),
+            Err(wasi_fs::ERRNO_ISDIR)
+        );
+        assert_eq!(
+            tree.path_rename(ROOT_FD, "c", ROOT_FD, "f"),
+            Err(wasi_fs::ERRNO_NOTDIR)
+        );
+        assert_eq!(
+            tree.path_rename(ROOT_FD, "c", ROOT_FD, "a"),
+            Err(wasi_fs::ERRNO_NOTEMPTY)
+        );
+        tree.path_rename(ROOT_FD, "a", ROOT_FD, "c").unwrap();
+        assert!(stat(&mut tree, "c/b/..").is_ok());
+        assert_eq!(stat(&mut tree, "a").err(), Some(wasi_fs::ERRNO_NOENT));
+        tree.path_rename(ROOT_FD, "f", ROOT_FD, "c/b/g").unwrap();
+        let moved = stat(&mut tree, "c/b/g").unwrap();
+        assert_eq!(moved.filetype, wasi_fs::FILETYPE_REGULAR_FILE);
+    }
+
+    #[test]
+    fn times_and_sizes() {
+        let mut tree = Tree::default();
+        let fd = open(&mut tree, "f", wasi_fs::OFLAGS_CREAT).unwrap();
+        tree.fd_filestat_set_size(fd, 10).unwrap();
+        let flags = wasi_fs::FSTFLAGS_ATIM | wasi_fs::FSTFLAGS_MTIM;
+        tree.path_filestat_set_times(ROOT_FD, 0, "f", 1000, 2000, flags)
+            .unwrap();
+        let st = stat(&mut tree, "f").unwrap();
+        assert_eq!((st.size, st.atim, st.mtim), (10, 1000, 2000));
+        assert_eq!(tree.fd_seek(fd, -1, wasi_fs::WHENCE_END), Ok(9));
+        assert_eq!(
+            tree.fd_seek(fd, -11, wasi_fs::WHENCE_END),
+            Err(wasi_fs::ERRNO_INVAL)
+        );
+        let trunc = wasi_fs::OFLAGS_TRUNC;
+        let again = open(&mut tree, "f", trunc).unwrap();
+        assert_eq!(tree.fd_filestat_get(again).unwrap().size, 0);
+    }
+}
@@ -1,16 +1,17 @@
-mod poll;
+pub mod memfs;
+pub(crate) mod poll;
 pub mod wasi_fs;
 mod wasi_sock;
 
 use crate::event_loop::poll::{Eventtype, Subscription};
 use crate::{quickjs_sys as qjs, Context, JsValue};
-use std::borrow::BorrowMut;
 use std::cell::RefCell;
 use std::collections::{HashMap, LinkedList};
 use std::io;
 use std::mem::ManuallyDrop;
 use std::net::{SocketAddr, SocketAddrV4};
 use std::ops::Add;
+use std::rc::Rc;
 
 pub use wasi_sock::nslookup;
 
@@ -18,6 +19,8 @@ pub(crate) enum NetPollEvent {
     Accept,
     Read,
     Connect,
+    /// Read a single datagram.
+    Datagram,
 }
 
 pub struct AsyncTcpServer(wasi_sock::Socket);
@@ -25,7 +28,7 @@ impl AsyncTcpServer {
     pub fn async_accept(
         &mut self,
         event_loop: &mut EventLoop,
-        callback: Box<dyn FnOnce(&mut qjs::Context, PollResult)>,
+        callback: PollCallback,
         timeout: Option<std::time::Duration>,
     ) {
         let s = self.0 .0;
@@ -53,41 +56,393 @@ impl AsyncTcpServer {
                 }));
         }
     }
+
+    /// Stops listening. Pending accepts fail with `ConnectionAborted`.
+    pub fn close(&mut self, event_loop: &mut EventLoop) {
+        event_loop.cancel_socket_tasks(self.0 .0);
+        // a listener that never connected reports `NotConnected`, the
+        // socket is closed on drop either way
+        let _ = self.0.shutdown(std::net::Shutdown::Both);
+    }
+
+    pub fn local(&self) -> io::Result<SocketAddr> {
+        self.0.get_local()
+    }
 }
 
-pub struct AsyncTcpConn(wasi_sock::Socket);
-impl AsyncTcpConn {
-    pub fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.0.send(buf)
+/// A UDP socket connected to one peer, so every datagram goes to and
+/// comes from that address.
+pub struct AsyncUdpSocket(wasi_sock::Socket);
+
+impl AsyncUdpSocket {
+    /// Sends `data` as one datagram.
+    pub fn send(&self, data: &[u8]) -> io::Result<usize> {
+        self.0.send(data)
     }
 
-    pub fn read(&mut self) -> io::Result<Vec<u8>> {
-        let mut buff = [0u8; 1024];
-        let mut data = vec![];
-        loop {
-            match self.0.recv(&mut buff) {
-                Ok(0) => {
-                    return Ok(data);
-                }
+    /// Waits for the next datagram, `PollResult::Read` carries it whole.
+    pub fn async_recv(
+        &mut self,
+        event_loop: &mut EventLoop,
+        callback: PollCallback,
+        timeout: Option<std::time::Duration>,
+    ) {
+        let s = self.0 .0;
+        if let Some(timeout) = timeout {
+            let ddl = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .add(timeout)
+                .as_nanos();
+            event_loop
+                .io_selector
+                .add_task(PollTask::SocketTimeout(SocketTimeoutTask {
+                    s,
+                    event: NetPollEvent::Datagram,
+                    timeout: ddl,
+                    callback,
+                }));
+        } else {
+            event_loop
+                .io_selector
+                .add_task(PollTask::Socket(SocketTask {
+                    s,
+                    event: NetPollEvent::Datagram,
+                    callback,
+                }));
+        }
+    }
+
+    /// Pending receives fail with `ConnectionAborted`.
+    pub fn close(&mut self, event_loop: &mut EventLoop) {
+        event_loop.cancel_socket_tasks(self.0 .0);
+        // an unconnected UDP socket reports `NotConnected`, the socket is
+        // closed on drop either way
+        let _ = self.0.shutdown(std::net::Shutdown::Both);
+    }
+
+    pub fn local(&self) -> io::Result<SocketAddr> {
+        self.0.get_local()
+    }
+
+    pub fn peer(&self) -> io::Result<SocketAddr> {
+        self.0.get_peer()
+    }
+}
+
+#[derive(Clone)]
+pub struct AsyncTcpConn(Rc<RefCell<TcpConnState>>);
+
+/// Default number of buffered outbound bytes above which `write` asks the
+/// caller to wait for `drain`, same as node's `writableHighWaterMark`.
+pub const DEFAULT_HIGH_WATER_MARK: usize = 16 * 1024;
+
+/// Called once with the outcome of a socket or timer task.
+pub type PollCallback = Box<dyn FnOnce(&mut qjs::Context, PollResult)>;
+
+fn unix_nanos() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos()
+}
+
+/// Timeouts of a connection that hold for every operation on it, `None`
+/// disables one.
+#[derive(Clone, Copy, Default)]
+pub struct SocketTimeouts {
+    /// Calls the timeout listener once nothing was read or written for this
+    /// long. The connection stays open, like node's `socket.setTimeout()`.
+    pub idle: Option<std::time::Duration>,
+    /// Fails reads that wait longer than this and do not set their own
+    /// timeout.
+    pub read: Option<std::time::Duration>,
+    /// Fails the pending writes once queued data made no progress for this
+    /// long.
+    pub write: Option<std::time::Duration>,
+}
+
+/// State shared between the JS object and a pending write-readiness task,
+/// so the socket stays open until everything queued on it has been sent.
+struct TcpConnState {
+    socket: wasi_sock::Socket,
+    outbound: Vec<u8>,
+    outbound_pos: usize,
+    high_water_mark: usize,
+    need_drain: bool,
+    write_armed: bool,
+    shutdown_pending: bool,
+    enqueued: u64,
+    flushed: u64,
+    flush_callbacks: LinkedList<(u64, PollCallback)>,
+    on_drain: Option<qjs::JsFunction>,
+    timeouts: SocketTimeouts,
+    last_active: u128,
+    idle_timer: Option<usize>,
+    on_timeout: Option<qjs::JsFunction>,
+}
+
+impl TcpConnState {
+    fn buffered(&self) -> usize {
+        self.outbound.len() - self.outbound_pos
+    }
+
+    /// Sends as much of the outbound buffer as the socket accepts without
+    /// blocking.
+    fn send_pending(&mut self) -> io::Result<()> {
+        while self.outbound_pos < self.outbound.len() {
+            match self.socket.send(&self.outbound[self.outbound_pos..]) {
+                Ok(0) => break,
                 Ok(n) => {
-                    data.extend_from_slice(&buff[0..n]);
-                }
-                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
-                    return Ok(data);
+                    self.outbound_pos += n;
+                    self.flushed += n as u64;
                 }
-                Err(e) => {
-                    return Err(e);
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        if self.outbound_pos > 0 {
+            self.last_active = unix_nanos();
+        }
+        if self.outbound_pos == self.outbound.len() {
+            self.outbound.clear();
+            self.outbound_pos = 0;
+        } else if self.outbound_pos > self.high_water_mark {
+            self.outbound.drain(..self.outbound_pos);
+            self.outbound_pos = 0;
+        }
+        Ok(())
+    }
+
+    fn take_flushed_callbacks(&mut self) -> Vec<PollCallback> {
+        let mut done = vec![];
+        while let Some((target, _)) = self.flush_callbacks.front() {
+            if *target > self.flushed {
+                break;
+            }
+            if let Some((_, callback)) = self.flush_callbacks.pop_front() {
+                done.push(callback);
+            }
+        }
+        done
+    }
+
+    fn fail(&mut self) -> Vec<PollCallback> {
+        self.outbound.clear();
+        self.outbound_pos = 0;
+        self.shutdown_pending = false;
+        self.need_drain = false;
+        self.flush_callbacks
+            .split_off(0)
+            .into_iter()
+            .map(|(_, callback)| callback)
+            .collect()
+    }
+}
+
+impl AsyncTcpConn {
+    fn new(socket: wasi_sock::Socket) -> Self {
+        AsyncTcpConn(Rc::new(RefCell::new(TcpConnState {
+            socket,
+            outbound: vec![],
+            outbound_pos: 0,
+            high_water_mark: DEFAULT_HIGH_WATER_MARK,
+            need_drain: false,
+            write_armed: false,
+            shutdown_pending: false,
+            enqueued: 0,
+            flushed: 0,
+            flush_callbacks: LinkedList::new(),
+            on_drain: None,
+            timeouts: SocketTimeouts::default(),
+            last_active: unix_nanos(),
+            idle_timer: None,
+            on_timeout: None,
+        })))
+    }
+
+    /// Queues `buf` behind any data still waiting for the socket and tries to
+    /// send it right away. Whatever the socket does not accept is flushed on
+    /// write-readiness events. `callback` runs once `buf` has been handed to
+    /// the socket. Returns `false` when the queue is above the high-water
+    /// mark, in which case the drain listener fires once it has emptied.
+    pub fn write(
+        &mut self,
+        event_loop: &mut EventLoop,
+        buf: &[u8],
+        callback: Option<PollCallback>,
+    ) -> io::Result<bool> {
+        let mut state = self.0.borrow_mut();
+        if state.shutdown_pending {
+            return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+        }
+        state.outbound.extend_from_slice(buf);
+        state.enqueued += buf.len() as u64;
+        state.last_active = unix_nanos();
+        if !state.write_armed {
+            if let Err(e) = state.send_pending() {
+                // earlier writes still waiting for their flush fail with it
+                for callback in state.fail() {
+                    let e = io::Error::new(e.kind(), e.to_string());
+                    event_loop
+                        .set_next_tick(Box::new(move |ctx| callback(ctx, PollResult::Error(e))));
                 }
+                return Err(e);
             }
         }
+
+        if let Some(callback) = callback {
+            if state.enqueued <= state.flushed {
+                let n = buf.len();
+                event_loop.set_next_tick(Box::new(move |ctx| callback(ctx, PollResult::Write(n))));
+            } else {
+                let target = state.enqueued;
+                state.flush_callbacks.push_back((target, callback));
+            }
+        }
+
+        let below_mark = state.buffered() < state.high_water_mark;
+        if !below_mark {
+            state.need_drain = true;
+        }
+        if state.buffered() > 0 && !state.write_armed {
+            state.write_armed = true;
+            let task = SocketWriteTask::new(&state, self.0.clone());
+            drop(state);
+            event_loop.io_selector.add_task(PollTask::SocketWrite(task));
+        }
+        arm_idle_timer(&self.0, event_loop);
+        Ok(below_mark)
+    }
+
+    /// Runs `callback` once everything queued so far has been sent.
+    pub fn flush(&mut self, event_loop: &mut EventLoop, callback: PollCallback) {
+        let mut state = self.0.borrow_mut();
+        if state.buffered() == 0 {
+            event_loop.set_next_tick(Box::new(move |ctx| callback(ctx, PollResult::Write(0))));
+        } else {
+            let target = state.enqueued;
+            state.flush_callbacks.push_back((target, callback));
+        }
+    }
+
+    /// Half-closes the connection once the outbound buffer has been flushed.
+    /// Reads keep working until the peer closes its side.
+    pub fn shutdown(&mut self, event_loop: &mut EventLoop, callback: PollCallback) {
+        let mut state = self.0.borrow_mut();
+        if state.buffered() == 0 {
+            let r = state.socket.shutdown(std::net::Shutdown::Write);
+            event_loop.set_next_tick(Box::new(move |ctx| match r {
+                Ok(()) => callback(ctx, PollResult::Write(0)),
+                Err(e) => callback(ctx, PollResult::Error(e)),
+            }));
+        } else {
+            state.shutdown_pending = true;
+            let target = state.enqueued;
+            state.flush_callbacks.push_back((target, callback));
+        }
+    }
+
+    /// Shuts down both directions right away, dropping whatever is still
+    /// queued. Pending reads fail with `ConnectionAborted`.
+    pub fn close(&mut self, event_loop: &mut EventLoop) {
+        let (s, callbacks) = {
+            let mut state = self.0.borrow_mut();
+            state.timeouts = SocketTimeouts::default();
+            if let Some(id) = state.idle_timer.take() {
+                event_loop.clear_timeout(id);
+            }
+            let callbacks = state.fail();
+            (state.socket.0, callbacks)
+        };
+        event_loop.cancel_socket_tasks(s);
+        // the peer may have gone already, the socket is closed on drop
+        let _ = self.0.borrow().socket.shutdown(std::net::Shutdown::Both);
+        for callback in callbacks {
+            event_loop.set_next_tick(Box::new(move |ctx| {
+                let e = io::Error::from(io::ErrorKind::ConnectionAborted);
+                callback(ctx, PollResult::Error(e))
+            }));
+        }
+    }
+
+    pub fn buffered_amount(&self) -> usize {
+        self.0.borrow().buffered()
+    }
+
+    pub fn high_water_mark(&self) -> usize {
+        self.0.borrow().high_water_mark
+    }
+
+    pub fn set_high_water_mark(&mut self, high_water_mark: usize) {
+        self.0.borrow_mut().high_water_mark = high_water_mark;
+    }
+
+    pub fn set_drain_listener(&mut self, listener: Option<qjs::JsFunction>) {
+        self.0.borrow_mut().on_drain = listener;
+    }
+
+    pub fn drain_listener(&self) -> Option<qjs::JsFunction> {
+        self.0.borrow().on_drain.clone()
+    }
+
+    pub fn timeouts(&self) -> SocketTimeouts {
+        self.0.borrow().timeouts
+    }
+
+    /// Replaces the timeouts of the connection. Changing them counts as
+    /// activity, so the idle timer starts over.
+    pub fn set_timeouts(&mut self, event_loop: &mut EventLoop, timeouts: SocketTimeouts) {
+        {
+            let mut state = self.0.borrow_mut();
+            state.timeouts = timeouts;
+            state.last_active = unix_nanos();
+            if let Some(id) = state.idle_timer.take() {
+                event_loop.clear_timeout(id);
+            }
+        }
+        arm_idle_timer(&self.0, event_loop);
+    }
+
+    pub fn set_timeout_listener(&mut self, listener: Option<qjs::JsFunction>) {
+        self.0.borrow_mut().on_timeout = listener;
+    }
+
+    pub fn timeout_listener(&self) -> Option<qjs::JsFunction> {
+        self.0.borrow().on_timeout.clone()
     }
 
+    pub fn read(&mut self) -> io::Result<Vec<u8>> {
+        let mut state = self.0.borrow_mut();
+        let data = recv_all(&state.socket)?;
+        if !data.is_empty() {
+            state.last_active = unix_nanos();
+        }
+        Ok(data)
+    }
+
+    /// Waits for data. Without `timeout` the read timeout of the connection
+    /// applies.
     pub fn async_read(
         &mut self,
         event_loop: &mut EventLoop,
-        callback: Box<dyn FnOnce(&mut qjs::Context, PollResult)>,
+        callback: PollCallback,
         timeout: Option<std::time::Duration>,
     ) {
+        let (s, timeout) = {
+            let state = self.0.borrow();
+            (state.socket.0, timeout.or(state.timeouts.read))
+        };
+        let conn = self.0.clone();
+        let callback: PollCallback = Box::new(move |ctx, event| {
+            if let PollResult::Read(_) = &event {
+                conn.borrow_mut().last_active = unix_nanos();
+                if let Some(event_loop) = ctx.event_loop() {
+                    arm_idle_timer(&conn, event_loop);
+                }
+            }
+            callback(ctx, event)
+        });
         if let Some(timeout) = timeout {
             let ddl = std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
@@ -98,7 +453,7 @@ impl AsyncTcpConn {
             event_loop
                 .io_selector
                 .add_task(PollTask::SocketTimeout(SocketTimeoutTask {
-                    s: self.0 .0,
+                    s,
                     event: NetPollEvent::Read,
                     timeout: ddl,
                     callback,
@@ -107,23 +462,153 @@ impl AsyncTcpConn {
             event_loop
                 .io_selector
                 .add_task(PollTask::Socket(SocketTask {
-                    s: self.0 .0,
+                    s,
                     event: NetPollEvent::Read,
                     callback,
                 }));
         }
     }
 
-    pub fn flush(&mut self) -> io::Result<()> {
-        Ok(())
-    }
-
     pub fn local(&self) -> io::Result<SocketAddr> {
-        self.0.get_local()
+        self.0.borrow().socket.get_local()
     }
 
     pub fn peer(&self) -> io::Result<SocketAddr> {
-        self.0.get_peer()
+        self.0.borrow().socket.get_peer()
+    }
+}
+
+/// Arms the idle timer unless it is running already or there is no idle
+/// timeout. The timer only holds a weak reference, a dropped connection
+/// does not fire.
+fn arm_idle_timer(conn: &Rc<RefCell<TcpConnState>>, event_loop: &mut EventLoop) {
+    let mut state = conn.borrow_mut();
+    let idle = match state.timeouts.idle {
+        Some(idle) if state.idle_timer.is_none() => idle,
+        _ => return,
+    };
+    let weak = Rc::downgrade(conn);
+    let id = event_loop
+        .io_selector
+        .add_task(PollTask::Timeout(TimeoutTask {
+            timeout: state.last_active + idle.as_nanos(),
+            callback: Box::new(move |ctx, _| {
+                if let Some(conn) = weak.upgrade() {
+                    on_idle_timer(ctx, &conn);
+                }
+            }),
+        }));
+    state.idle_timer = Some(id);
+}
+
+/// Fires the timeout listener if the connection has been idle long enough,
+/// or waits for the rest of the period after recent activity. Once fired,
+/// the next activity arms the timer again.
+fn on_idle_timer(ctx: &mut qjs::Context, conn: &Rc<RefCell<TcpConnState>>) {
+    let listener = {
+        let mut state = conn.borrow_mut();
+        state.idle_timer = None;
+        let idle = match state.timeouts.idle {
+            Some(idle) => idle,
+            None => return,
+        };
+        if unix_nanos() < state.last_active + idle.as_nanos() {
+            drop(state);
+            if let Some(event_loop) = ctx.event_loop() {
+                arm_idle_timer(conn, event_loop);
+            }
+            return;
+        }
+        state.on_timeout.clone()
+    };
+    if let Some(listener) = listener {
+        listener.call(&[]);
+    }
+}
+
+fn recv_all(s: &wasi_sock::Socket) -> io::Result<Vec<u8>> {
+    let mut buff = [0u8; 1024];
+    let mut data = vec![];
+    loop {
+        match s.recv(&mut buff) {
+            Ok(0) => {
+                return Ok(data);
+            }
+            Ok(n) => {
+                data.extend_from_slice(&buff[0..n]);
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                return Ok(data);
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+}
+
+/// Continues flushing `conn` after a write-readiness event. Returns whether
+/// data is still queued and the task has to be armed again.
+fn flush_tcp_conn(
+    ctx: &mut qjs::Context,
+    conn: &Rc<RefCell<TcpConnState>>,
+    error: Option<io::Error>,
+) -> bool {
+    let (callbacks, drain, error) = {
+        let mut state = conn.borrow_mut();
+        state.write_armed = false;
+        let r = match error {
+            Some(e) => Err(e),
+            None => state.send_pending(),
+        };
+        let r = r.and_then(|_| {
+            if state.buffered() == 0 && state.shutdown_pending {
+                state.shutdown_pending = false;
+                state.socket.shutdown(std::net::Shutdown::Write)
+            } else {
+                Ok(())
+            }
+        });
+        match r {
+            Ok(()) => {
+                let drain = if state.need_drain && state.buffered() == 0 {
+                    state.need_drain = false;
+                    state.on_drain.clone()
+                } else {
+                    None
+                };
+                (state.take_flushed_callbacks(), drain, None)
+            }
+            Err(e) => (state.fail(), None, Some(e)),
+        }
+    };
+
+    match error {
+        Some(e) => {
+            for callback in callbacks {
+                let e = io::Error::new(e.kind(), e.to_string());
+                callback(ctx, PollResult::Error(e));
+            }
+        }
+        None => {
+            for callback in callbacks {
+                callback(ctx, PollResult::Write(0));
+            }
+            if let Some(drain) = drain {
+                drain.call(&[]);
+            }
+        }
+    }
+
+    if let Some(event_loop) = ctx.event_loop() {
+        arm_idle_timer(conn, event_loop);
+    }
+    let mut state = conn.borrow_mut();
+    if state.buffered() > 0 && !state.write_armed {
+        state.write_armed = true;
+        true
+    } else {
+        false
     }
 }
 
@@ -138,7 +623,7 @@ pub enum PollResult {
 
 struct TimeoutTask {
     timeout: u128,
-    callback: Box<dyn FnOnce(&mut qjs::Context, PollResult)>,
+    callback: PollCallback,
 }
 
 impl TimeoutTask {
@@ -164,23 +649,25 @@ impl TimeoutTask {
 struct SocketTask {
     s: wasi_sock::RawSocket,
     event: NetPollEvent,
-    callback: Box<dyn FnOnce(&mut qjs::Context, PollResult)>,
+    callback: PollCallback,
 }
 
 impl SocketTask {
     fn as_subscription(&self, index: usize) -> Subscription {
         match self.event {
-            NetPollEvent::Accept | NetPollEvent::Read => poll::Subscription {
-                userdata: index as u64,
-                u: poll::SubscriptionU {
-                    tag: poll::EVENTTYPE_FD_READ,
-                    u: poll::SubscriptionUU {
-                        fd_read: poll::SubscriptionFdReadwrite {
-                            file_descriptor: self.s as u32,
+            NetPollEvent::Accept | NetPollEvent::Read | NetPollEvent::Datagram => {
+                poll::Subscription {
+                    userdata: index as u64,
+                    u: poll::SubscriptionU {
+                        tag: poll::EVENTTYPE_FD_READ,
+                        u: poll::SubscriptionUU {
+                            fd_read: poll::SubscriptionFdReadwrite {
+                                file_descriptor: self.s as u32,
+                            },
                         },
                     },
-                },
-            },
+                }
+            }
             NetPollEvent::Connect => poll::Subscription {
                 userdata: index as u64,
                 u: poll::SubscriptionU {
@@ -200,23 +687,25 @@ struct SocketTimeoutTask {
     s: wasi_sock::RawSocket,
     event: NetPollEvent,
     timeout: u128,
-    callback: Box<dyn FnOnce(&mut qjs::Context, PollResult)>,
+    callback: PollCallback,
 }
 
 impl SocketTimeoutTask {
     fn as_subscription(&self, index: usize) -> (Subscription, Subscription) {
         let socket_task = match self.event {
-            NetPollEvent::Accept | NetPollEvent::Read => poll::Subscription {
-                userdata: index as u64,
-                u: poll::SubscriptionU {
-                    tag: poll::EVENTTYPE_FD_READ,
-                    u: poll::SubscriptionUU {
-                        fd_read: poll::SubscriptionFdReadwrite {
-                            file_descriptor: self.s as u32,
+            NetPollEvent::Accept | NetPollEvent::Read | NetPollEvent::Datagram => {
+                poll::Subscription {
+                    userdata: index as u64,
+                    u: poll::SubscriptionU {
+                        tag: poll::EVENTTYPE_FD_READ,
+                        u: poll::SubscriptionUU {
+                            fd_read: poll::SubscriptionFdReadwrite {
+                                file_descriptor: self.s as u32,
+                            },
                         },
                     },
-                },
-            },
+                }
+            }
             NetPollEvent::Connect => poll::Subscription {
                 userdata: index as u64,
                 u: poll::SubscriptionU {
@@ -254,7 +743,7 @@ struct FdReadTask {
     fd: std::os::wasi::io::RawFd,
     pos: i64,
     len: u64,
-    callback: Box<dyn FnOnce(&mut qjs::Context, PollResult)>,
+    callback: PollCallback,
 }
 
 impl FdReadTask {
@@ -277,7 +766,7 @@ struct FdWriteTask {
     fd: std::os::wasi::io::RawFd,
     pos: i64,
     buf: Vec<u8>,
-    callback: Box<dyn FnOnce(&mut qjs::Context, PollResult)>,
+    callback: PollCallback,
 }
 
 impl FdWriteTask {
@@ -296,10 +785,61 @@ impl FdWriteTask {
     }
 }
 
+struct SocketWriteTask {
+    s: wasi_sock::RawSocket,
+    conn: Rc<RefCell<TcpConnState>>,
+    /// From the write timeout of the connection, set again every time the
+    /// task is armed after progress.
+    timeout: Option<u128>,
+}
+
+impl SocketWriteTask {
+    fn new(state: &TcpConnState, conn: Rc<RefCell<TcpConnState>>) -> Self {
+        SocketWriteTask {
+            s: state.socket.0,
+            conn,
+            timeout: state
+                .timeouts
+                .write
+                .map(|timeout| unix_nanos() + timeout.as_nanos()),
+        }
+    }
+
+    fn as_subscription(&self, index: usize) -> (Subscription, Option<Subscription>) {
+        let write_task = poll::Subscription {
+            userdata: index as u64,
+            u: poll::SubscriptionU {
+                tag: poll::EVENTTYPE_FD_WRITE,
+                u: poll::SubscriptionUU {
+                    fd_write: poll::SubscriptionFdReadwrite {
+                        file_descriptor: self.s as u32,
+                    },
+                },
+            },
+        };
+        let timeout_task = self.timeout.map(|nanoseconds| poll::Subscription {
+            userdata: index as u64,
+            u: poll::SubscriptionU {
+                tag: poll::EVENTTYPE_CLOCK,
+                u: poll::SubscriptionUU {
+                    clock: poll::SubscriptionClock {
+                        id: poll::CLOCKID_REALTIME,
+                        timeout: nanoseconds as u64,
+                        precision: 0,
+                        flags: poll::SUBCLOCKFLAGS_SUBSCRIPTION_CLOCK_ABSTIME,
+                    },
+                },
+            },
+        });
+        (write_task, timeout_task)
+    }
+}
+
 enum PollTask {
     Timeout(TimeoutTask),
     Socket(SocketTask),
     SocketTimeout(SocketTimeoutTask),
+    SocketWrite(SocketWriteTask),
     FdRead(FdReadTask),
     FdWrite(FdWriteTask),
 }
@@ -343,6 +883,11 @@ impl IoSelector {
                         subscription_vec.push(task1);
                         subscription_vec.push(task2);
                     }
+                    PollTask::SocketWrite(task) => {
+                        let (task1, task2) = task.as_subscription(i);
+                        subscription_vec.push(task1);
+                        subscription_vec.extend(task2);
+                    }
                     PollTask::FdRead(task) => {
                         subscription_vec.push(task.as_subscription(i));
                     }
@@ -386,9 +931,18 @@ impl IoSelector {
                         callback(ctx, PollResult::Timeout);
                     }
                     (
-                        PollTask::SocketTimeout(SocketTimeoutTask { callback, .. }),
+                        PollTask::SocketTimeout(SocketTimeoutTask {
+                            s,
+                            event: net_event,
+                            callback,
+                            ..
+                        }),
                         poll::EVENTTYPE_CLOCK,
                     ) => {
+                        if let NetPollEvent::Connect = net_event {
+                            // the socket never reached the JS side
+                            drop(wasi_sock::Socket(s));
+                        }
                         callback(ctx, PollResult::Timeout);
                     }
                     (
@@ -407,6 +961,9 @@ impl IoSelector {
                         poll::EVENTTYPE_FD_READ | poll::EVENTTYPE_FD_WRITE,
                     ) => {
                         if event.error > 0 {
+                            if let NetPollEvent::Connect = net_event {
+                                drop(wasi_sock::Socket(s));
+                            }
                             let e = io::Error::from_raw_os_error(event.error as i32);
                             callback(ctx, PollResult::Error(e));
                             continue;
@@ -416,31 +973,69 @@ impl IoSelector {
                             NetPollEvent::Accept => {
                                 let s = std::mem::ManuallyDrop::new(wasi_sock::Socket(s));
                                 match s.accept(true) {
-                                    Ok(cs) => callback(ctx, PollResult::Accept(AsyncTcpConn(cs))),
+                                    Ok(cs) => {
+                                        callback(ctx, PollResult::Accept(AsyncTcpConn::new(cs)))
+                                    }
                                     Err(e) => callback(ctx, PollResult::Error(e)),
                                 }
                             }
                             NetPollEvent::Read => {
-                                let mut s =
-                                    std::mem::ManuallyDrop::new(AsyncTcpConn(wasi_sock::Socket(s)));
-                                match s.read() {
+                                let s = std::mem::ManuallyDrop::new(wasi_sock::Socket(s));
+                                match recv_all(&s) {
                                     Ok(data) => callback(ctx, PollResult::Read(data)),
                                     Err(e) => callback(ctx, PollResult::Error(e)),
                                 }
                             }
+                            NetPollEvent::Datagram => {
+                                let s = std::mem::ManuallyDrop::new(wasi_sock::Socket(s));
+                                let mut buf = vec![0u8; 65536];
+                                match s.recv(&mut buf) {
+                                    Ok(n) => {
+                                        buf.truncate(n);
+                                        callback(ctx, PollResult::Read(buf))
+                                    }
+                                    Err(e) => callback(ctx, PollResult::Error(e)),
+                                }
+                            }
                             NetPollEvent::Connect => {
                                 if event.fd_readwrite.flags & poll::EVENTRWFLAGS_FD_READWRITE_HANGUP
                                     > 0
                                 {
+                                    drop(wasi_sock::Socket(s));
                                     let e = io::Error::from(io::ErrorKind::ConnectionAborted);
                                     callback(ctx, PollResult::Error(e));
                                 } else {
-                                    let s = AsyncTcpConn(wasi_sock::Socket(s));
+                                    let s = AsyncTcpConn::new(wasi_sock::Socket(s));
                                     callback(ctx, PollResult::Connect(s));
                                 }
                             }
                         };
                     }
+                    (
+                        PollTask::SocketWrite(SocketWriteTask { conn, .. }),
+                        poll::EVENTTYPE_CLOCK,
+                    ) => {
+                        let e = io::Error::from(io::ErrorKind::TimedOut);
+                        flush_tcp_conn(ctx, &conn, Some(e));
+                    }
+                    (
+                        PollTask::SocketWrite(SocketWriteTask { conn, .. }),
+                        poll::EVENTTYPE_FD_WRITE,
+                    ) => {
+                        let error = if event.error > 0 {
+                            Some(io::Error::from_raw_os_error(event.error as i32))
+                        } else if event.fd_readwrite.flags & poll::EVENTRWFLAGS_FD_READWRITE_HANGUP
+                            > 0
+                        {
+                            Some(io::Error::from(io::ErrorKind::BrokenPipe))
+                        } else {
+                            None
+                        };
+                        if flush_tcp_conn(ctx, &conn, error) {
+                            let task = SocketWriteTask::new(&conn.borrow(), conn.clone());
+                            self.add_task(PollTask::SocketWrite(task));
+                        }
+                    }
                     (
                         PollTask::FdRead(FdReadTask {
                             fd,
@@ -548,9 +1143,39 @@ impl IoSelector {
 pub struct EventLoop {
     next_tick_queue: LinkedList<Box<dyn FnOnce(&mut qjs::Context)>>,
     io_selector: IoSelector,
+    virtual_fs: Option<memfs::VirtualFs>,
+}
+
+impl Drop for EventLoop {
+    fn drop(&mut self) {
+        if let Some(vfs) = &self.virtual_fs {
+            memfs::deactivate(vfs);
+        }
+    }
 }
 
 impl EventLoop {
+    /// Runs the `wasi_fs` calls against `vfs` instead of the host, `None`
+    /// goes back to the host.
+    pub fn set_virtual_fs(&mut self, vfs: Option<memfs::VirtualFs>) {
+        if let Some(old) = &self.virtual_fs {
+            memfs::deactivate(old);
+        }
+        self.virtual_fs = vfs;
+        self.activate_virtual_fs();
+    }
+
+    pub fn virtual_fs(&self) -> Option<memfs::VirtualFs> {
+        self.virtual_fs.clone()
+    }
+
+    /// Makes the filesystem of this loop the one of the thread again.
+    pub(crate) fn activate_virtual_fs(&self) {
+        if self.virtual_fs.is_some() {
+            memfs::activate(self.virtual_fs.clone());
+        }
+    }
+
     pub fn run_once(&mut self, ctx: &mut qjs::Context) -> io::Result<usize> {
         let n = self.run_tick_task(ctx);
         if n > 0 {
@@ -593,6 +1218,19 @@ impl EventLoop {
         self.io_selector.add_task(timeout_task)
     }
 
+    /// Calls `callback` once `timeout` has passed, like `set_timeout` for
+    /// native code. The id works with `clear_timeout`.
+    pub fn add_timer(
+        &mut self,
+        timeout: std::time::Duration,
+        callback: Box<dyn FnOnce(&mut qjs::Context)>,
+    ) -> usize {
+        self.io_selector.add_task(PollTask::Timeout(TimeoutTask {
+            timeout: unix_nanos() + timeout.as_nanos(),
+            callback: Box::new(move |ctx, _| callback(ctx)),
+        }))
+    }
+
     pub fn clear_timeout(&mut self, timeout_id: usize) {
         if let Some(t) = self.io_selector.tasks.get_mut(timeout_id) {
             if let Some(PollTask::Timeout(_)) = t {
@@ -605,6 +1243,30 @@ impl EventLoop {
         self.next_tick_queue.push_back(callback);
     }
 
+    /// Drops every read/accept/connect task waiting on `s` and fails its
+    /// callback on the next tick.
+    pub(crate) fn cancel_socket_tasks(&mut self, s: wasi_sock::RawSocket) {
+        for t in self.io_selector.tasks.iter_mut() {
+            let matched = match t {
+                Some(PollTask::Socket(task)) => task.s == s,
+                Some(PollTask::SocketTimeout(task)) => task.s == s,
+                _ => false,
+            };
+            if !matched {
+                continue;
+            }
+            let callback = match t.take() {
+                Some(PollTask::Socket(SocketTask { callback, .. }))
+                | Some(PollTask::SocketTimeout(SocketTimeoutTask { callback, .. })) => callback,
+                _ => continue,
+            };
+            self.next_tick_queue.push_back(Box::new(move |ctx| {
+                let e = io::Error::from(io::ErrorKind::ConnectionAborted);
+                callback(ctx, PollResult::Error(e))
+            }));
+        }
+    }
+
     pub fn tcp_listen(&mut self, port: u16) -> io::Result<AsyncTcpServer> {
         let addr = format!("0.0.0.0:{}", port)
             .parse()
@@ -620,10 +1282,20 @@ impl EventLoop {
         Ok(AsyncTcpServer(s))
     }
 
+    pub fn udp_connect(&mut self, addr: &SocketAddr) -> io::Result<AsyncUdpSocket> {
+        let s = wasi_sock::Socket::new(
+            wasi_sock::AddressFamily::Inet4,
+            wasi_sock::SocketType::Datagram,
+        )?;
+        s.set_nonblocking(true)?;
+        s.connect(addr)?;
+        Ok(AsyncUdpSocket(s))
+    }
+
     pub fn tcp_connect(
         &mut self,
         addr: &SocketAddr,
-        callback: Box<dyn FnOnce(&mut qjs::Context, PollResult)>,
+        callback: PollCallback,
         timeout: Option<std::time::Duration>,
     ) -> io::Result<()> {
         let s = wasi_sock::Socket::new(
@@ -637,7 +1309,84 @@ impl EventLoop {
                 return Err(e);
             }
         }
+        self.add_connect_task(s, callback, timeout);
+        Ok(())
+    }
+
+    /// Listens on the unix socket at `path`, which must not exist yet.
+    pub fn unix_listen(&mut self, path: &str) -> io::Result<AsyncTcpServer> {
+        let s = wasi_sock::Socket::new(
+            wasi_sock::AddressFamily::Unix,
+            wasi_sock::SocketType::Stream,
+        )?;
+        s.set_nonblocking(true)?;
+        s.bind_unix(path)?;
+        s.listen(1024)?;
+        Ok(AsyncTcpServer(s))
+    }
+
+    /// Connects to the unix socket at `path`, `callback` gets a connection
+    /// that works like a tcp one.
+    pub fn unix_connect(
+        &mut self,
+        path: &str,
+        callback: PollCallback,
+        timeout: Option<std::time::Duration>,
+    ) -> io::Result<()> {
+        let s = wasi_sock::Socket::new(
+            wasi_sock::AddressFamily::Unix,
+            wasi_sock::SocketType::Stream,
+        )?;
+        s.set_nonblocking(true)?;
+        if let Err(e) = s.connect_unix(path) {
+            // Operation in progress
+            if e.raw_os_error() != Some(26) {
+                return Err(e);
+            }
+        }
+        self.add_connect_task(s, callback, timeout);
+        Ok(())
+    }
+
+    /// Two connected unix sockets. There is no `socketpair` in the WasmEdge
+    /// socket extension, so this listens on a fresh path in `dir`, connects
+    /// to it and removes the path again.
+    pub fn unix_socketpair(&mut self, dir: &str) -> io::Result<(AsyncTcpConn, AsyncTcpConn)> {
+        static NEXT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let n = NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let path = format!(
+            "{}/wasmedge_quickjs-{}-{}.sock",
+            dir.trim_end_matches('/'),
+            unix_nanos(),
+            n
+        );
+        let listener = wasi_sock::Socket::new(
+            wasi_sock::AddressFamily::Unix,
+            wasi_sock::SocketType::Stream,
+        )?;
+        listener.bind_unix(&path)?;
+        let pair = listener.listen(1).and_then(|_| {
+            let a = wasi_sock::Socket::new(
+                wasi_sock::AddressFamily::Unix,
+                wasi_sock::SocketType::Stream,
+            )?;
+            a.connect_unix(&path)?;
+            a.set_nonblocking(true)?;
+            let b = listener.accept(true)?;
+            Ok((AsyncTcpConn::new(a), AsyncTcpConn::new(b)))
+        });
+        // the path is only reachable this way when the host directory is
+        // preopened under the same name
+        let _ = std::fs::remove_file(&path);
+        pair
+    }
 
+    fn add_connect_task(
+        &mut self,
+        s: wasi_sock::Socket,
+        callback: PollCallback,
+        timeout: Option<std::time::Duration>,
+    ) {
         if let Some(timeout) = timeout {
             let ddl = std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
@@ -660,22 +1409,47 @@ impl EventLoop {
             }));
         }
         std::mem::forget(s);
-        Ok(())
     }
 
+    /// Reads up to `len` bytes once `fd` is readable, the id works with
+    /// `cancel_fd_read`.
     pub fn fd_read(
         &mut self,
         fd: std::os::wasi::io::RawFd,
         pos: i64,
         len: u64,
-        callback: Box<dyn FnOnce(&mut qjs::Context, PollResult)>,
-    ) {
+        callback: PollCallback,
+    ) -> usize {
         self.io_selector.add_task(PollTask::FdRead(FdReadTask {
             fd,
             pos,
             len,
             callback,
-        }));
+        }))
+    }
+
+    /// Stops a read of `fd_read` on `fd` that is still waiting, its
+    /// callback gets `Interrupted` on the next tick. `false` if it already
+    /// ran, then `id` may belong to another task.
+    pub fn cancel_fd_read(&mut self, id: usize, fd: std::os::wasi::io::RawFd) -> bool {
+        let task = match self.io_selector.tasks.get_mut(id) {
+            Some(t @ Some(PollTask::FdRead(_))) => t,
+            _ => return false,
+        };
+        match task.take() {
+            Some(PollTask::FdRead(read)) if read.fd == fd => {
+                let callback = read.callback;
+                self.set_next_tick(Box::new(move |ctx| {
+                    let e = io::Error::from(io::ErrorKind::Interrupted);
+                    callback(ctx, PollResult::Error(e))
+                }));
+                true
+            }
+            other => {
+                *task = other;
+                false
+            }
+        }
     }
 
     pub fn fd_write(
@@ -683,7 +1457,7 @@ impl EventLoop {
         fd: std::os::wasi::io::RawFd,
         pos: i64,
         buf: Vec<u8>,
-        callback: Box<dyn FnOnce(&mut qjs::Context, PollResult)>,
+        callback: PollCallback,
     ) {
         self.io_selector.add_task(PollTask::FdWrite(FdWriteTask {
             fd,
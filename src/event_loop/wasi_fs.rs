@@ -1,6 +1,8 @@
+use crate::event_loop::memfs;
 use crate::event_loop::poll::*;
 use core::fmt;
 use core::mem::{ManuallyDrop, MaybeUninit};
+use std::cell::RefCell;
 use std::ffi::{CStr, CString, OsStr, OsString};
 use std::io;
 use std::os::raw::c_int;
@@ -838,6 +840,29 @@ mod wasi {
         /// Return `errno::isdir` if the path refers to a directory.
         /// Note: This is similar to `unlinkat(fd, path, 0)` in POSIX.
         pub fn path_unlink_file(arg0: i32, arg1: i32, arg2: i32) -> i32;
+        /// Return the time value of a clock.
+        /// Note: This is similar to `clock_gettime` in POSIX.
+        pub fn clock_time_get(arg0: i32, arg1: i64, arg2: i32) -> i32;
+    }
+}
+
+/// Return the time value of a clock.
+/// Note: This is similar to `clock_gettime` in POSIX.
+///
+/// ## Parameters
+///
+/// * `id` - The clock for which to return the time.
+/// * `precision` - The maximum lag (exclusive) that the returned time value may have, compared to its actual value.
+///
+/// ## Return
+///
+/// The time value of the clock, in nanoseconds.
+pub unsafe fn clock_time_get(id: Clockid, precision: Timestamp) -> Result<Timestamp, Errno> {
+    let mut rp0 = MaybeUninit::<Timestamp>::uninit();
+    let ret = wasi::clock_time_get(id as i32, precision as i64, rp0.as_mut_ptr() as i32);
+    match ret {
+        0 => Ok(core::ptr::read(rp0.as_mut_ptr() as i32 as *const Timestamp)),
+        _ => Err(Errno(ret as u16)),
     }
 }
 
@@ -855,6 +880,9 @@ pub unsafe fn fd_advise(
     len: Filesize,
     advice: Advice,
 ) -> Result<(), Errno> {
+    if let Some(res) = memfs::route(fd, |fs| fs.fd_advise(fd)) {
+        return res;
+    }
     let ret = wasi::fd_advise(fd as i32, offset as i64, len as i64, advice.0 as i32);
     match ret {
         0 => Ok(()),
@@ -870,6 +898,9 @@ pub unsafe fn fd_advise(
 /// * `offset` - The offset at which to start the allocation.
 /// * `len` - The length of the area that is allocated.
 pub unsafe fn fd_allocate(fd: Fd, offset: Filesize, len: Filesize) -> Result<(), Errno> {
+    if let Some(res) = memfs::route(fd, |fs| fs.fd_allocate(fd, offset, len)) {
+        return res;
+    }
     let ret = wasi::fd_allocate(fd as i32, offset as i64, len as i64);
     match ret {
         0 => Ok(()),
@@ -880,6 +911,9 @@ pub unsafe fn fd_allocate(fd: Fd, offset: Filesize, len: Filesize) -> Result<(),
 /// Close a file descriptor.
 /// Note: This is similar to `close` in POSIX.
 pub unsafe fn fd_close(fd: Fd) -> Result<(), Errno> {
+    if let Some(res) = memfs::route(fd, |fs| fs.fd_close(fd)) {
+        return res;
+    }
     let ret = wasi::fd_close(fd as i32);
     match ret {
         0 => Ok(()),
@@ -890,6 +924,9 @@ pub unsafe fn fd_close(fd: Fd) -> Result<(), Errno> {
 /// Synchronize the data of a file to disk.
 /// Note: This is similar to `fdatasync` in POSIX.
 pub unsafe fn fd_datasync(fd: Fd) -> Result<(), Errno> {
+    if let Some(res) = memfs::route(fd, |fs| fs.fd_sync(fd)) {
+        return res;
+    }
     let ret = wasi::fd_datasync(fd as i32);
     match ret {
         0 => Ok(()),
@@ -904,6 +941,9 @@ pub unsafe fn fd_datasync(fd: Fd) -> Result<(), Errno> {
 ///
 /// The buffer where the file descriptor's attributes are stored.
 pub unsafe fn fd_fdstat_get(fd: Fd) -> Result<Fdstat, Errno> {
+    if let Some(res) = memfs::route(fd, |fs| fs.fd_fdstat_get(fd)) {
+        return res;
+    }
     let mut rp0 = MaybeUninit::<Fdstat>::uninit();
     let ret = wasi::fd_fdstat_get(fd as i32, rp0.as_mut_ptr() as i32);
     match ret {
@@ -919,6 +959,9 @@ pub unsafe fn fd_fdstat_get(fd: Fd) -> Result<Fdstat, Errno> {
 ///
 /// * `flags` - The desired values of the file descriptor flags.
 pub unsafe fn fd_fdstat_set_flags(fd: Fd, flags: Fdflags) -> Result<(), Errno> {
+    if let Some(res) = memfs::route(fd, |fs| fs.fd_fdstat_set_flags(fd, flags)) {
+        return res;
+    }
     let ret = wasi::fd_fdstat_set_flags(fd as i32, flags as i32);
     match ret {
         0 => Ok(()),
@@ -937,6 +980,11 @@ pub unsafe fn fd_fdstat_set_rights(
     fs_rights_base: Rights,
     fs_rights_inheriting: Rights,
 ) -> Result<(), Errno> {
+    if let Some(res) = memfs::route(fd, |fs| {
+        fs.fd_fdstat_set_rights(fd, fs_rights_base, fs_rights_inheriting)
+    }) {
+        return res;
+    }
     let ret = wasi::fd_fdstat_set_rights(
         fd as i32,
         fs_rights_base as i64,
@@ -954,6 +1002,9 @@ pub unsafe fn fd_fdstat_set_rights(
 ///
 /// The buffer where the file's attributes are stored.
 pub unsafe fn fd_filestat_get(fd: Fd) -> Result<Filestat, Errno> {
+    if let Some(res) = memfs::route(fd, |fs| fs.fd_filestat_get(fd)) {
+        return res;
+    }
     let mut rp0 = MaybeUninit::<Filestat>::uninit();
     let ret = wasi::fd_filestat_get(fd as i32, rp0.as_mut_ptr() as i32);
     match ret {
@@ -969,6 +1020,9 @@ pub unsafe fn fd_filestat_get(fd: Fd) -> Result<Filestat, Errno> {
 ///
 /// * `size` - The desired file size.
 pub unsafe fn fd_filestat_set_size(fd: Fd, size: Filesize) -> Result<(), Errno> {
+    if let Some(res) = memfs::route(fd, |fs| fs.fd_filestat_set_size(fd, size)) {
+        return res;
+    }
     let ret = wasi::fd_filestat_set_size(fd as i32, size as i64);
     match ret {
         0 => Ok(()),
@@ -990,6 +1044,9 @@ pub unsafe fn fd_filestat_set_times(
     mtim: Timestamp,
     fst_flags: Fstflags,
 ) -> Result<(), Errno> {
+    if let Some(res) = memfs::route(fd, |fs| fs.fd_filestat_set_times(fd, atim, mtim, fst_flags)) {
+        return res;
+    }
     let ret = wasi::fd_filestat_set_times(fd as i32, atim as i64, mtim as i64, fst_flags as i32);
     match ret {
         0 => Ok(()),
@@ -1009,6 +1066,20 @@ pub unsafe fn fd_filestat_set_times(
 ///
 /// The number of bytes read.
 pub unsafe fn fd_pread(fd: Fd, iovs: IovecArray<'_>, offset: Filesize) -> Result<Size, Errno> {
+    if let Some(res) = memfs::route(fd, |fs| {
+        let mut n = 0;
+        for iov in iovs {
+            let buf = std::slice::from_raw_parts_mut(iov.buf, iov.buf_len);
+            let len = fs.fd_pread(fd, buf, offset + n as Filesize)?;
+            n += len;
+            if len < buf.len() {
+                break;
+            }
+        }
+        Ok(n)
+    }) {
+        return res;
+    }
     let mut rp0 = MaybeUninit::<Size>::uninit();
     let ret = wasi::fd_pread(
         fd as i32,
@@ -1029,6 +1100,9 @@ pub unsafe fn fd_pread(fd: Fd, iovs: IovecArray<'_>, offset: Filesize) -> Result
 ///
 /// The buffer where the description is stored.
 pub unsafe fn fd_prestat_get(fd: Fd) -> Result<Prestat, Errno> {
+    if let Some(res) = memfs::route(fd, |_| Err(ERRNO_BADF)) {
+        return res;
+    }
     let mut rp0 = MaybeUninit::<Prestat>::uninit();
     let ret = wasi::fd_prestat_get(fd as i32, rp0.as_mut_ptr() as i32);
     match ret {
@@ -1043,6 +1117,9 @@ pub unsafe fn fd_prestat_get(fd: Fd) -> Result<Prestat, Errno> {
 ///
 /// * `path` - A buffer into which to write the preopened directory name.
 pub unsafe fn fd_prestat_dir_name(fd: Fd, path: *mut u8, path_len: Size) -> Result<(), Errno> {
+    if let Some(res) = memfs::route(fd, |_| Err(ERRNO_BADF)) {
+        return res;
+    }
     let ret = wasi::fd_prestat_dir_name(fd as i32, path as i32, path_len as i32);
     match ret {
         0 => Ok(()),
@@ -1062,6 +1139,16 @@ pub unsafe fn fd_prestat_dir_name(fd: Fd, path: *mut u8, path_len: Size) -> Resu
 ///
 /// The number of bytes written.
 pub unsafe fn fd_pwrite(fd: Fd, iovs: CiovecArray<'_>, offset: Filesize) -> Result<Size, Errno> {
+    if let Some(res) = memfs::route(fd, |fs| {
+        let mut n = 0;
+        for iov in iovs {
+            let buf = std::slice::from_raw_parts(iov.buf, iov.buf_len);
+            n += fs.fd_pwrite(fd, buf, offset + n as Filesize)?;
+        }
+        Ok(n)
+    }) {
+        return res;
+    }
     let mut rp0 = MaybeUninit::<Size>::uninit();
     let ret = wasi::fd_pwrite(
         fd as i32,
@@ -1087,6 +1174,20 @@ pub unsafe fn fd_pwrite(fd: Fd, iovs: CiovecArray<'_>, offset: Filesize) -> Resu
 ///
 /// The number of bytes read.
 pub unsafe fn fd_read(fd: Fd, iovs: IovecArray<'_>) -> Result<Size, Errno> {
+    if let Some(res) = memfs::route(fd, |fs| {
+        let mut n = 0;
+        for iov in iovs {
+            let buf = std::slice::from_raw_parts_mut(iov.buf, iov.buf_len);
+            let len = fs.fd_read(fd, buf)?;
+            n += len;
+            if len < buf.len() {
+                break;
+            }
+        }
+        Ok(n)
+    }) {
+        return res;
+    }
     let mut rp0 = MaybeUninit::<Size>::uninit();
     let ret = wasi::fd_read(
         fd as i32,
@@ -1124,6 +1225,11 @@ pub unsafe fn fd_readdir(
     buf_len: Size,
     cookie: Dircookie,
 ) -> Result<Size, Errno> {
+    if let Some(res) = memfs::route(fd, |fs| {
+        fs.fd_readdir(fd, std::slice::from_raw_parts_mut(buf, buf_len), cookie)
+    }) {
+        return res;
+    }
     let mut rp0 = MaybeUninit::<Size>::uninit();
     let ret = wasi::fd_readdir(
         fd as i32,
@@ -1151,6 +1257,9 @@ pub unsafe fn fd_readdir(
 ///
 /// * `to` - The file descriptor to overwrite.
 pub unsafe fn fd_renumber(fd: Fd, to: Fd) -> Result<(), Errno> {
+    if let Some(res) = memfs::route(fd, |_| Err(ERRNO_NOTSUP)) {
+        return res;
+    }
     let ret = wasi::fd_renumber(fd as i32, to as i32);
     match ret {
         0 => Ok(()),
@@ -1170,6 +1279,9 @@ pub unsafe fn fd_renumber(fd: Fd, to: Fd) -> Result<(), Errno> {
 ///
 /// The new offset of the file descriptor, relative to the start of the file.
 pub unsafe fn fd_seek(fd: Fd, offset: Filedelta, whence: Whence) -> Result<Filesize, Errno> {
+    if let Some(res) = memfs::route(fd, |fs| fs.fd_seek(fd, offset, whence)) {
+        return res;
+    }
     let mut rp0 = MaybeUninit::<Filesize>::uninit();
     let ret = wasi::fd_seek(fd as i32, offset, whence.0 as i32, rp0.as_mut_ptr() as i32);
     match ret {
@@ -1181,6 +1293,9 @@ pub unsafe fn fd_seek(fd: Fd, offset: Filedelta, whence: Whence) -> Result<Files
 /// Synchronize the data and metadata of a file to disk.
 /// Note: This is similar to `fsync` in POSIX.
 pub unsafe fn fd_sync(fd: Fd) -> Result<(), Errno> {
+    if let Some(res) = memfs::route(fd, |fs| fs.fd_sync(fd)) {
+        return res;
+    }
     let ret = wasi::fd_sync(fd as i32);
     match ret {
         0 => Ok(()),
@@ -1195,6 +1310,9 @@ pub unsafe fn fd_sync(fd: Fd) -> Result<(), Errno> {
 ///
 /// The current offset of the file descriptor, relative to the start of the file.
 pub unsafe fn fd_tell(fd: Fd) -> Result<Filesize, Errno> {
+    if let Some(res) = memfs::route(fd, |fs| fs.fd_tell(fd)) {
+        return res;
+    }
     let mut rp0 = MaybeUninit::<Filesize>::uninit();
     let ret = wasi::fd_tell(fd as i32, rp0.as_mut_ptr() as i32);
     match ret {
@@ -1210,6 +1328,15 @@ pub unsafe fn fd_tell(fd: Fd) -> Result<Filesize, Errno> {
 ///
 /// * `iovs` - List of scatter/gather vectors from which to retrieve data.
 pub unsafe fn fd_write(fd: Fd, iovs: CiovecArray<'_>) -> Result<Size, Errno> {
+    if let Some(res) = memfs::route(fd, |fs| {
+        let mut n = 0;
+        for iov in iovs {
+            n += fs.fd_write(fd, std::slice::from_raw_parts(iov.buf, iov.buf_len))?;
+        }
+        Ok(n)
+    }) {
+        return res;
+    }
     let mut rp0 = MaybeUninit::<Size>::uninit();
     let ret = wasi::fd_write(
         fd as i32,
@@ -1230,6 +1357,9 @@ pub unsafe fn fd_write(fd: Fd, iovs: CiovecArray<'_>) -> Result<Size, Errno> {
 ///
 /// * `path` - The path at which to create the directory.
 pub unsafe fn path_create_directory(fd: Fd, path: &str) -> Result<(), Errno> {
+    if let Some(res) = memfs::route(fd, |fs| fs.path_create_directory(fd, path)) {
+        return res;
+    }
     let ret = wasi::path_create_directory(fd as i32, path.as_ptr() as i32, path.len() as i32);
     match ret {
         0 => Ok(()),
@@ -1249,6 +1379,9 @@ pub unsafe fn path_create_directory(fd: Fd, path: &str) -> Result<(), Errno> {
 ///
 /// The buffer where the file's attributes are stored.
 pub unsafe fn path_filestat_get(fd: Fd, flags: Lookupflags, path: &str) -> Result<Filestat, Errno> {
+    if let Some(res) = memfs::route(fd, |fs| fs.path_filestat_get(fd, flags, path)) {
+        return res;
+    }
     let mut rp0 = MaybeUninit::<Filestat>::uninit();
     let ret = wasi::path_filestat_get(
         fd as i32,
@@ -1281,6 +1414,11 @@ pub unsafe fn path_filestat_set_times(
     mtim: Timestamp,
     fst_flags: Fstflags,
 ) -> Result<(), Errno> {
+    if let Some(res) = memfs::route(fd, |fs| {
+        fs.path_filestat_set_times(fd, flags, path, atim, mtim, fst_flags)
+    }) {
+        return res;
+    }
     let ret = wasi::path_filestat_set_times(
         fd as i32,
         flags as i32,
@@ -1312,6 +1450,11 @@ pub unsafe fn path_link(
     new_fd: Fd,
     new_path: &str,
 ) -> Result<(), Errno> {
+    if let Some(res) = memfs::route(old_fd, |fs| {
+        fs.path_link(old_fd, old_flags, old_path, new_fd, new_path)
+    }) {
+        return res;
+    }
     let ret = wasi::path_link(
         old_fd as i32,
         old_flags as i32,
@@ -1361,6 +1504,11 @@ pub unsafe fn path_open(
     fs_rights_inheriting: Rights,
     fdflags: Fdflags,
 ) -> Result<Fd, Errno> {
+    if let Some(res) = memfs::route(fd, |fs| {
+        fs.path_open(fd, dirflags, path, oflags, fs_rights_base, fdflags)
+    }) {
+        return res;
+    }
     let mut rp0 = MaybeUninit::<Fd>::uninit();
     let ret = wasi::path_open(
         fd as i32,
@@ -1396,6 +1544,14 @@ pub unsafe fn path_readlink(
     buf: *mut u8,
     buf_len: Size,
 ) -> Result<Size, Errno> {
+    if let Some(res) = memfs::route(fd, |fs| {
+        let target = fs.path_readlink(fd, path)?;
+        let n = target.len().min(buf_len);
+        ptr::copy_nonoverlapping(target.as_ptr(), buf, n);
+        Ok(n)
+    }) {
+        return res;
+    }
     let mut rp0 = MaybeUninit::<Size>::uninit();
     let ret = wasi::path_readlink(
         fd as i32,
@@ -1419,6 +1575,9 @@ pub unsafe fn path_readlink(
 ///
 /// * `path` - The path to a directory to remove.
 pub unsafe fn path_remove_directory(fd: Fd, path: &str) -> Result<(), Errno> {
+    if let Some(res) = memfs::route(fd, |fs| fs.path_remove_directory(fd, path)) {
+        return res;
+    }
     let ret = wasi::path_remove_directory(fd as i32, path.as_ptr() as i32, path.len() as i32);
     match ret {
         0 => Ok(()),
@@ -1435,6 +1594,9 @@ pub unsafe fn path_remove_directory(fd: Fd, path: &str) -> Result<(), Errno> {
 /// * `new_fd` - The working directory at which the resolution of the new path starts.
 /// * `new_path` - The destination path to which to rename the file or directory.
 pub unsafe fn path_rename(fd: Fd, old_path: &str, new_fd: Fd, new_path: &str) -> Result<(), Errno> {
+    if let Some(res) = memfs::route(fd, |fs| fs.path_rename(fd, old_path, new_fd, new_path)) {
+        return res;
+    }
     let ret = wasi::path_rename(
         fd as i32,
         old_path.as_ptr() as i32,
@@ -1457,6 +1619,9 @@ pub unsafe fn path_rename(fd: Fd, old_path: &str, new_fd: Fd, new_path: &str) ->
 /// * `old_path` - The contents of the symbolic link.
 /// * `new_path` - The destination path at which to create the symbolic link.
 pub unsafe fn path_symlink(old_path: &str, fd: Fd, new_path: &str) -> Result<(), Errno> {
+    if let Some(res) = memfs::route(fd, |fs| fs.path_symlink(old_path, fd, new_path)) {
+        return res;
+    }
     let ret = wasi::path_symlink(
         old_path.as_ptr() as i32,
         old_path.len() as i32,
@@ -1478,6 +1643,9 @@ pub unsafe fn path_symlink(old_path: &str, fd: Fd, new_path: &str) -> Result<(),
 ///
 /// * `path` - The path to a file to unlink.
 pub unsafe fn path_unlink_file(fd: Fd, path: &str) -> Result<(), Errno> {
+    if let Some(res) = memfs::route(fd, |fs| fs.path_unlink_file(fd, path)) {
+        return res;
+    }
     let ret = wasi::path_unlink_file(fd as i32, path.as_ptr() as i32, path.len() as i32);
     match ret {
         0 => Ok(()),
@@ -1511,8 +1679,12 @@ pub unsafe fn path_unlink_file(fd: Fd, path: &str) -> Result<(), Errno> {
 /// appropriate rights for performing `rights` actions.
 ///
 /// Note that this can fail if `p` doesn't look like it can be opened relative
-/// to any pre-opened file descriptor.
+/// to any pre-opened file descriptor. A relative `p` is taken from [`cwd`].
 pub fn open_parent(p: &str) -> io::Result<(Fd, String)> {
+    let p = absolute_path(p);
+    if let Some(res) = memfs::open_parent(&p) {
+        return Ok(res);
+    }
     let p = CString::new(p.as_bytes())?;
     let mut buf = Vec::<u8>::with_capacity(512);
     loop {
@@ -1558,3 +1730,66 @@ pub fn open_parent(p: &str) -> io::Result<(Fd, String)> {
         ) -> libc::c_int;
     }
 }
+
+thread_local! {
+    static CWD: RefCell<String> = RefCell::new(String::from(START_CWD));
+}
+
+/// The cwd before the first [`chdir`], relative paths are then looked up
+/// in the preopens as they are.
+const START_CWD: &str = "./";
+
+/// The directory relative paths are looked up from, `./` until [`chdir`].
+pub fn cwd() -> String {
+    CWD.with(|cwd| cwd.borrow().clone())
+}
+
+/// `path` joined onto [`cwd`] if it is relative. Nothing is folded, a `..`
+/// after a symlink still goes up from where the symlink points.
+pub fn absolute_path(path: &str) -> String {
+    if path.is_empty() || path.starts_with('/') {
+        return path.to_string();
+    }
+    CWD.with(|cwd| match cwd.borrow().as_str() {
+        START_CWD => path.to_string(),
+        cwd => format!("{}/{}", cwd.trim_end_matches('/'), path),
+    })
+}
+
+/// Makes `path` the new [`cwd`], it has to be a directory. The cwd is kept
+/// without `.`, `..` and repeated slashes, the same way `path.resolve` has it.
+/// A cwd relative to the preopens stays relative and cannot go above them.
+pub fn chdir(path: &str) -> Result<(), Errno> {
+    if path.is_empty() {
+        return Err(ERRNO_NOENT);
+    }
+    let path = absolute_path(path);
+    let absolute = path.starts_with('/');
+    let mut parts = vec![];
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                if parts.pop().is_none() && !absolute {
+                    return Err(ERRNO_NOTCAPABLE);
+                }
+            }
+            part => parts.push(part),
+        }
+    }
+    let target = match (absolute, parts.is_empty()) {
+        (true, _) => format!("/{}", parts.join("/")),
+        (false, false) => parts.join("/"),
+        (false, true) => {
+            CWD.with(|cwd| *cwd.borrow_mut() = START_CWD.to_string());
+            return Ok(());
+        }
+    };
+    let (dir, file) = open_parent(&target).map_err(|_| ERRNO_NOTCAPABLE)?;
+    let stat = unsafe { path_filestat_get(dir, LOOKUPFLAGS_SYMLINK_FOLLOW, &file)? };
+    if stat.filetype != FILETYPE_DIRECTORY {
+        return Err(ERRNO_NOTDIR);
+    }
+    CWD.with(|cwd| *cwd.borrow_mut() = target);
+    Ok(())
+}
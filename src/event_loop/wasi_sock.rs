@@ -12,6 +12,8 @@ pub enum AddressFamily {
     Unspec,
     Inet4,
     Inet6,
+    /// Unix domain sockets, on WasmEdge versions that support them.
+    Unix,
 }
 
 impl From<&SocketAddr> for AddressFamily {
@@ -137,6 +139,23 @@ pub struct IovecWrite {
 
 pub type RawSocket = RawFd;
 
+/// Longest path a unix socket address holds, `sun_path` less the NUL.
+pub const UNIX_PATH_MAX: usize = 107;
+
+/// WasmEdge takes unix socket addresses as a 128 byte buffer with the
+/// address family in the first two bytes and the NUL terminated path after
+/// them. The path is a host path, the preopened directories do not apply.
+fn unix_address(path: &str) -> io::Result<[u8; 128]> {
+    let path = path.as_bytes();
+    if path.is_empty() || path.len() > UNIX_PATH_MAX || path.contains(&0) {
+        return Err(io::Error::from(io::ErrorKind::InvalidInput));
+    }
+    let mut buf = [0u8; 128];
+    buf[0..2].copy_from_slice(&(AddressFamily::Unix as u16).to_le_bytes());
+    buf[2..2 + path.len()].copy_from_slice(path);
+    Ok(buf)
+}
+
 #[derive(Debug)]
 pub struct Socket(pub RawSocket);
 
@@ -275,6 +294,38 @@ impl Socket {
         }
     }
 
+    pub fn connect_unix(&self, path: &str) -> io::Result<()> {
+        let buf = unix_address(path)?;
+        let mut addr = WasiAddress {
+            buf: buf.as_ptr(),
+            size: buf.len(),
+        };
+        unsafe {
+            let res = sock_connect(self.as_raw_fd() as u32, &mut addr, 0);
+            if res != 0 {
+                Err(io::Error::from_raw_os_error(res as i32))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    pub fn bind_unix(&self, path: &str) -> io::Result<()> {
+        let buf = unix_address(path)?;
+        let mut addr = WasiAddress {
+            buf: buf.as_ptr(),
+            size: buf.len(),
+        };
+        unsafe {
+            let res = sock_bind(self.as_raw_fd() as u32, &mut addr, 0);
+            if res != 0 {
+                Err(io::Error::from_raw_os_error(res as i32))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
     pub fn bind(&self, addrs: &SocketAddr) -> io::Result<()> {
         unsafe {
             let fd = self.as_raw_fd();
@@ -586,7 +637,7 @@ pub fn nslookup(node: &str, service: &str) -> std::io::Result<Vec<SocketAddr>> {
                 // unimplemented!("not support IPv6")
                 continue;
             }
-            AddressFamily::Unspec => {
+            AddressFamily::Unspec | AddressFamily::Unix => {
                 // unimplemented!("not support Unspec")
                 continue;
             }
@@ -0,0 +1,453 @@
+//! Native side of the `dns` module: the DNS message format (RFC 1035).
+//!
+//! `encodeQuery` builds a recursive query for one name and record type,
+//! `decodeResponse` turns the answer section of a reply into plain objects.
+//! Sending the messages over UDP or TCP and caching the answers happens in
+//! JS, on top of `wasi_net`.
+
+use std::fmt::{Display, Formatter};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::quickjs_sys::*;
+
+pub const TYPE_A: u16 = 1;
+pub const TYPE_NS: u16 = 2;
+pub const TYPE_CNAME: u16 = 5;
+pub const TYPE_SOA: u16 = 6;
+pub const TYPE_PTR: u16 = 12;
+pub const TYPE_MX: u16 = 15;
+pub const TYPE_TXT: u16 = 16;
+pub const TYPE_AAAA: u16 = 28;
+pub const TYPE_SRV: u16 = 33;
+pub const TYPE_ANY: u16 = 255;
+
+const CLASS_IN: u16 = 1;
+
+const RECORD_TYPES: &[(&str, u16)] = &[
+    ("A", TYPE_A),
+    ("NS", TYPE_NS),
+    ("CNAME", TYPE_CNAME),
+    ("SOA", TYPE_SOA),
+    ("PTR", TYPE_PTR),
+    ("MX", TYPE_MX),
+    ("TXT", TYPE_TXT),
+    ("AAAA", TYPE_AAAA),
+    ("SRV", TYPE_SRV),
+    ("ANY", TYPE_ANY),
+];
+
+pub fn type_from_name(name: &str) -> Option<u16> {
+    RECORD_TYPES
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, t)| *t)
+}
+
+pub fn type_name(rtype: u16) -> Option<&'static str> {
+    RECORD_TYPES
+        .iter()
+        .find(|(_, t)| *t == rtype)
+        .map(|(n, _)| *n)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DnsError {
+    /// The name cannot be sent in a query.
+    BadName,
+    /// The reply does not parse.
+    Malformed,
+}
+
+impl Display for DnsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DnsError::BadName => f.write_str("misformatted domain name"),
+            DnsError::Malformed => f.write_str("misformatted DNS reply"),
+        }
+    }
+}
+
+/// A recursive query with a single question.
+pub fn encode_query(id: u16, name: &str, rtype: u16) -> Result<Vec<u8>, DnsError> {
+    let mut msg = Vec::with_capacity(18 + name.len());
+    msg.extend_from_slice(&id.to_be_bytes());
+    // RD, recursion desired
+    msg.extend_from_slice(&0x0100u16.to_be_bytes());
+    msg.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    let name = name.strip_suffix('.').unwrap_or(name);
+    if name.len() > 253 {
+        return Err(DnsError::BadName);
+    }
+    if !name.is_empty() {
+        for label in name.split('.') {
+            if label.is_empty() || label.len() > 63 {
+                return Err(DnsError::BadName);
+            }
+            msg.push(label.len() as u8);
+            msg.extend_from_slice(label.as_bytes());
+        }
+    }
+    msg.push(0);
+    msg.extend_from_slice(&rtype.to_be_bytes());
+    msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(msg)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    /// CNAME, NS and PTR records.
+    Name(String),
+    Mx {
+        priority: u16,
+        exchange: String,
+    },
+    Txt(Vec<String>),
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        name: String,
+    },
+    Soa {
+        nsname: String,
+        hostmaster: String,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minttl: u32,
+    },
+    Other(Vec<u8>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    pub name: String,
+    pub rtype: u16,
+    pub ttl: u32,
+    pub data: RData,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Reply {
+    pub id: u16,
+    pub rcode: u8,
+    pub truncated: bool,
+    pub answers: Vec<Record>,
+    /// The SOA records of the authority section, which carry the time a
+    /// negative answer may be cached.
+    pub authority: Vec<Record>,
+}
+
+struct Reader<'a> {
+    msg: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], DnsError> {
+        let end = self.pos.checked_add(n).ok_or(DnsError::Malformed)?;
+        let bytes = self.msg.get(self.pos..end).ok_or(DnsError::Malformed)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, DnsError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, DnsError> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, DnsError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// A possibly compressed name. Pointers have to point backwards, which
+    /// rules out loops.
+    fn name(&mut self) -> Result<String, DnsError> {
+        let mut labels: Vec<String> = vec![];
+        let mut pos = self.pos;
+        let mut end = None;
+        loop {
+            let len = *self.msg.get(pos).ok_or(DnsError::Malformed)? as usize;
+            match len & 0xc0 {
+                0x00 if len == 0 => {
+                    pos += 1;
+                    break;
+                }
+                0x00 => {
+                    let label = self
+                        .msg
+                        .get(pos + 1..pos + 1 + len)
+                        .ok_or(DnsError::Malformed)?;
+                    labels.push(String::from_utf8_lossy(label).into_owned());
+                    pos += 1 + len;
+                }
+                0xc0 => {
+                    let low = *self.msg.get(pos + 1).ok_or(DnsError::Malformed)? as usize;
+                    let target = (len & 0x3f) << 8 | low;
+                    if target >= pos {
+                        return Err(DnsError::Malformed);
+                    }
+                    end.get_or_insert(pos + 2);
+                    pos = target;
+                }
+                _ => return Err(DnsError::Malformed),
+            }
+        }
+        self.pos = end.unwrap_or(pos);
+        Ok(labels.join("."))
+    }
+
+    fn record(&mut self) -> Result<(Record, u16), DnsError> {
+        let name = self.name()?;
+        let rtype = self.u16()?;
+        let class = self.u16()?;
+        let ttl = self.u32()?;
+        let len = self.u16()? as usize;
+        let end = self.pos + len;
+        if end > self.msg.len() {
+            return Err(DnsError::Malformed);
+        }
+        let data = match rtype {
+            TYPE_A if len == 4 => {
+                let b = self.bytes(4)?;
+                RData::A(Ipv4Addr::new(b[0], b[1], b[2], b[3]))
+            }
+            TYPE_AAAA if len == 16 => {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(self.bytes(16)?);
+                RData::Aaaa(Ipv6Addr::from(octets))
+            }
+            TYPE_CNAME | TYPE_NS | TYPE_PTR => RData::Name(self.name()?),
+            TYPE_MX => RData::Mx {
+                priority: self.u16()?,
+                exchange: self.name()?,
+            },
+            TYPE_TXT => {
+                let mut entries = vec![];
+                while self.pos < end {
+                    let n = self.u8()? as usize;
+                    entries.push(String::from_utf8_lossy(self.bytes(n)?).into_owned());
+                }
+                RData::Txt(entries)
+            }
+            TYPE_SRV => RData::Srv {
+                priority: self.u16()?,
+                weight: self.u16()?,
+                port: self.u16()?,
+                name: self.name()?,
+            },
+            TYPE_SOA => RData::Soa {
+                nsname: self.name()?,
+                hostmaster: self.name()?,
+                serial: self.u32()?,
+                refresh: self.u32()?,
+                retry: self.u32()?,
+                expire: self.u32()?,
+                minttl: self.u32()?,
+            },
+            _ => RData::Other(self.bytes(len)?.to_vec()),
+        };
+        if self.pos != end {
+            return Err(DnsError::Malformed);
+        }
+        Ok((
+            Record {
+                name,
+                rtype,
+                ttl,
+                data,
+            },
+            class,
+        ))
+    }
+}
+
+/// Parses a reply. Records of other classes than IN are left out.
+pub fn decode_reply(msg: &[u8]) -> Result<Reply, DnsError> {
+    let mut r = Reader { msg, pos: 0 };
+    let id = r.u16()?;
+    let flags = r.u16()?;
+    if flags & 0x8000 == 0 {
+        return Err(DnsError::Malformed);
+    }
+    let (qdcount, ancount, nscount) = (r.u16()?, r.u16()?, r.u16()?);
+    let _arcount = r.u16()?;
+    let truncated = flags & 0x0200 != 0;
+    let mut reply = Reply {
+        id,
+        rcode: (flags & 0x000f) as u8,
+        truncated,
+        answers: vec![],
+        authority: vec![],
+    };
+    // a truncated reply may end anywhere, take what is complete
+    let result = (|| {
+        for _ in 0..qdcount {
+            r.name()?;
+            r.bytes(4)?;
+        }
+        for _ in 0..ancount {
+            let (record, class) = r.record()?;
+            if class == CLASS_IN {
+                reply.answers.push(record);
+            }
+        }
+        for _ in 0..nscount {
+            let (record, class) = r.record()?;
+            if class == CLASS_IN && record.rtype == TYPE_SOA {
+                reply.authority.push(record);
+            }
+        }
+        Ok(())
+    })();
+    match result {
+        Err(e) if !truncated => Err(e),
+        _ => Ok(reply),
+    }
+}
+
+fn record_to_js(ctx: &mut Context, record: &Record) -> JsValue {
+    let mut obj = ctx.new_object();
+    obj.set("name", ctx.new_string(&record.name).into());
+    let rtype = match type_name(record.rtype) {
+        Some(name) => ctx.new_string(name).into(),
+        None => JsValue::Int(record.rtype as i32),
+    };
+    obj.set("type", rtype);
+    obj.set("ttl", JsValue::Float(record.ttl as f64));
+    match &record.data {
+        RData::A(ip) => obj.set("address", ctx.new_string(&ip.to_string()).into()),
+        RData::Aaaa(ip) => obj.set("address", ctx.new_string(&ip.to_string()).into()),
+        RData::Name(name) => obj.set("value", ctx.new_string(name).into()),
+        RData::Mx { priority, exchange } => {
+            obj.set("priority", JsValue::Int(*priority as i32));
+            obj.set("exchange", ctx.new_string(exchange).into())
+        }
+        RData::Txt(entries) => {
+            let mut array = ctx.new_array();
+            for (i, entry) in entries.iter().enumerate() {
+                array.put(i, ctx.new_string(entry).into());
+            }
+            obj.set("entries", array.into())
+        }
+        RData::Srv {
+            priority,
+            weight,
+            port,
+            name,
+        } => {
+            obj.set("priority", JsValue::Int(*priority as i32));
+            obj.set("weight", JsValue::Int(*weight as i32));
+            obj.set("port", JsValue::Int(*port as i32));
+            obj.set("name", ctx.new_string(name).into())
+        }
+        RData::Soa {
+            nsname,
+            hostmaster,
+            serial,
+            refresh,
+            retry,
+            expire,
+            minttl,
+        } => {
+            obj.set("nsname", ctx.new_string(nsname).into());
+            obj.set("hostmaster", ctx.new_string(hostmaster).into());
+            obj.set("serial", JsValue::Float(*serial as f64));
+            obj.set("refresh", JsValue::Float(*refresh as f64));
+            obj.set("retry", JsValue::Float(*retry as f64));
+            obj.set("expire", JsValue::Float(*expire as f64));
+            obj.set("minttl", JsValue::Float(*minttl as f64))
+        }
+        RData::Other(data) => obj.set("data", ctx.new_array_buffer(data).into()),
+    };
+    obj.into()
+}
+
+fn dns_error(ctx: &mut Context, e: DnsError) -> JsValue {
+    let code = match e {
+        DnsError::BadName => "EBADNAME",
+        DnsError::Malformed => "EBADRESP",
+    };
+    let mut err = ctx.new_error(e.to_string().as_str());
+    if let JsValue::Object(obj) = &mut err {
+        obj.set("code", ctx.new_string(code).into());
+    }
+    ctx.throw_error(err).into()
+}
+
+/// `encodeQuery(id, name, type)` with the record type by name, e.g.
+/// `"AAAA"`.
+fn js_encode_query(ctx: &mut Context, _this: JsValue, argv: &[JsValue]) -> JsValue {
+    let id = match argv.first() {
+        Some(JsValue::Int(id)) => *id as u16,
+        _ => 0,
+    };
+    let (name, rtype) = match (argv.get(1), argv.get(2)) {
+        (Some(JsValue::String(name)), Some(JsValue::String(rtype))) => {
+            (name.to_string(), rtype.to_string())
+        }
+        _ => return ctx.throw_type_error("name and type must be strings").into(),
+    };
+    let rtype = match type_from_name(&rtype) {
+        Some(rtype) => rtype,
+        None => {
+            return ctx
+                .throw_type_error(&format!("unknown record type {}", rtype))
+                .into()
+        }
+    };
+    match encode_query(id, &name, rtype) {
+        Ok(msg) => ctx.new_array_buffer(&msg).into(),
+        Err(e) => dns_error(ctx, e),
+    }
+}
+
+/// `decodeResponse(buffer)` returns `{ id, rcode, truncated, answers,
+/// authority }`, the records as `{ name, type, ttl, ... }` with the fields
+/// of node's `resolveAny()` results.
+fn js_decode_response(ctx: &mut Context, _this: JsValue, argv: &[JsValue]) -> JsValue {
+    let reply = match argv.first() {
+        Some(JsValue::ArrayBuffer(buf)) => decode_reply(buf.as_ref()),
+        _ => return ctx.throw_type_error("reply must be an ArrayBuffer").into(),
+    };
+    let reply = match reply {
+        Ok(reply) => reply,
+        Err(e) => return dns_error(ctx, e),
+    };
+    let mut obj = ctx.new_object();
+    obj.set("id", JsValue::Int(reply.id as i32));
+    obj.set("rcode", JsValue::Int(reply.rcode as i32));
+    obj.set("truncated", JsValue::Bool(reply.truncated));
+    for (key, records) in [("answers", &reply.answers), ("authority", &reply.authority)] {
+        let mut array = ctx.new_array();
+        for (i, record) in records.iter().enumerate() {
+            array.put(i, record_to_js(ctx, record));
+        }
+        obj.set(key, array.into());
+    }
+    obj.into()
+}
+
+struct DnsModule;
+
+impl ModuleInit for DnsModule {
+    fn init_module(ctx: &mut Context, m: &mut JsModuleDef) {
+        let f = ctx.wrap_function("encodeQuery", js_encode_query);
+        m.add_export("encodeQuery", f.into());
+
+        let f = ctx.wrap_function("decodeResponse", js_decode_response);
+        m.add_export("decodeResponse", f.into());
+    }
+}
+
+pub fn init_module(ctx: &mut Context) {
+    ctx.register_module("_node:dns\0", DnsModule, &["encodeQuery", "decodeResponse"])
+}
@@ -1,11 +1,17 @@
+use crate::event_loop::memfs;
 use crate::event_loop::wasi_fs;
+use crate::event_loop::EventLoop;
 use crate::event_loop::PollResult;
 use crate::quickjs_sys::*;
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
 use std::convert::TryInto;
-use std::fs;
-use std::fs::Permissions;
 use std::io;
 use std::os::wasi::prelude::FromRawFd;
+use std::rc::Rc;
+use std::time::Duration;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 
@@ -15,17 +21,74 @@ impl From<u64> for JsValue {
     }
 }
 
-fn permissions_to_mode(permit: Permissions) -> i32 {
-    const F_OK: i32 = 0;
-    const R_OK: i32 = 4;
-    const W_OK: i32 = 2;
-    const X_OK: i32 = 1;
-    let p = if permit.readonly() {
-        F_OK | R_OK | X_OK
-    } else {
-        F_OK | R_OK | W_OK | X_OK
-    };
-    p | p << 3 | p << 6
+/// A `chmod` or `chown` of a file. WASI has neither, so they live in memory
+/// for the run, keyed by device and inode so that every path and fd of the
+/// file sees them.
+#[derive(Default, Clone, Copy)]
+struct FileMeta {
+    mode: Option<u32>,
+    uid: Option<u32>,
+    gid: Option<u32>,
+}
+
+thread_local! {
+    static FILE_META: RefCell<BTreeMap<(u64, u64), FileMeta>> = RefCell::default();
+    static UMASK: Cell<u32> = const { Cell::new(0o022) };
+}
+
+fn file_meta(stat: &wasi_fs::Filestat) -> FileMeta {
+    FILE_META.with(|meta| {
+        meta.borrow()
+            .get(&(stat.dev, stat.ino))
+            .copied()
+            .unwrap_or_default()
+    })
+}
+
+fn update_file_meta(stat: &wasi_fs::Filestat, f: impl FnOnce(&mut FileMeta)) {
+    FILE_META.with(|meta| f(meta.borrow_mut().entry((stat.dev, stat.ino)).or_default()))
+}
+
+/// Drops what was kept for a file whose last link is gone, its inode can be
+/// handed out again.
+fn forget_file_meta(stat: &wasi_fs::Filestat) {
+    if stat.nlink <= 1 || stat.filetype == wasi_fs::FILETYPE_DIRECTORY {
+        FILE_META.with(|meta| meta.borrow_mut().remove(&(stat.dev, stat.ino)));
+    }
+}
+
+/// Drops what was kept for a symlink that was just created, in case its
+/// inode belonged to a file removed outside this module. The mode of a
+/// symlink is not masked.
+fn reset_file_meta(path: &str) {
+    if let Ok(stat) = tree_stat(path, false, "symlink") {
+        FILE_META.with(|meta| meta.borrow_mut().remove(&(stat.dev, stat.ino)));
+    }
+}
+
+/// A file that was just created gets the `mode` it was created with, less
+/// the umask.
+fn set_created_mode(stat: &wasi_fs::Filestat, mode: u32) {
+    let mode = mode & 0o7777 & !UMASK.with(|umask| umask.get());
+    update_file_meta(stat, |meta| {
+        *meta = FileMeta {
+            mode: Some(mode),
+            ..FileMeta::default()
+        }
+    });
+}
+
+/// The file type bits of `st_mode` and the permission bits the file has
+/// when nobody changed them.
+fn filetype_mode(filetype: wasi_fs::Filetype) -> (u32, u32) {
+    match filetype {
+        wasi_fs::FILETYPE_DIRECTORY => (0o040000, 0o755),
+        wasi_fs::FILETYPE_SYMBOLIC_LINK => (0o120000, 0o777),
+        wasi_fs::FILETYPE_BLOCK_DEVICE => (0o060000, 0o660),
+        wasi_fs::FILETYPE_CHARACTER_DEVICE => (0o020000, 0o620),
+        wasi_fs::FILETYPE_SOCKET_DGRAM | wasi_fs::FILETYPE_SOCKET_STREAM => (0o140000, 0o755),
+        _ => (0o100000, 0o644),
+    }
 }
 
 fn stat_to_js_object(ctx: &mut Context, stat: wasi_fs::Filestat) -> JsValue {
@@ -62,21 +125,23 @@ fn stat_to_js_object(ctx: &mut Context, stat: wasi_fs::Filestat) -> JsValue {
     res.set("birthtime", (stat.ctim / 1000000).into());
     res.set("dev", stat.dev.into());
     res.set("ino", stat.ino.into());
-    res.set("mode", 0o666.into());
+    let meta = file_meta(&stat);
+    let (kind, perm) = filetype_mode(stat.filetype);
+    res.set("mode", ((kind | meta.mode.unwrap_or(perm)) as i32).into());
     res.set("nlink", stat.nlink.into());
-    res.set("uid", 0.into());
-    res.set("gid", 0.into());
+    res.set("uid", (meta.uid.unwrap_or(0) as u64).into());
+    res.set("gid", (meta.gid.unwrap_or(0) as u64).into());
     res.set("rdev", 0.into());
     res.set("blksize", 0.into());
     res.set("blocks", 0.into());
     JsValue::Object(res)
 }
 
-fn err_to_js_object(ctx: &mut Context, e: io::Error) -> JsValue {
+pub(crate) fn err_to_js_object(ctx: &mut Context, e: io::Error) -> JsValue {
     errno_to_js_object(ctx, wasi_fs::Errno(e.raw_os_error().unwrap() as u16))
 }
 
-fn errno_to_js_object(ctx: &mut Context, e: wasi_fs::Errno) -> JsValue {
+pub(crate) fn errno_to_js_object(ctx: &mut Context, e: wasi_fs::Errno) -> JsValue {
     let mut res = ctx.new_object();
     res.set("message", JsValue::String(ctx.new_string(e.message())));
     res.set("code", JsValue::String(ctx.new_string(e.name())));
@@ -167,18 +232,17 @@ fn mkdir_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue
     }
     if let Some(JsValue::String(s)) = path {
         if let Some(JsValue::Bool(r)) = recursive {
-            if let Some(JsValue::Int(_m)) = mode {
-                let res = if *r {
-                    fs::create_dir_all(s.as_str())
-                } else {
-                    fs::create_dir(s.as_str())
-                };
-                return match res {
+            if let Some(JsValue::Int(m)) = mode {
+                if *r {
+                    return match mkdir_p(s.as_str(), *m as u32) {
+                        Ok(Some(first)) => ctx.new_string(first.as_str()).into(),
+                        Ok(None) => JsValue::UnDefined,
+                        Err(e) => e.throw(ctx),
+                    };
+                }
+                return match tree_mkdir(s.as_str(), *m as u32) {
                     Ok(()) => JsValue::UnDefined,
-                    Err(e) => {
-                        let err = err_to_js_object(ctx, e);
-                        JsValue::Exception(ctx.throw_error(err))
-                    }
+                    Err(e) => e.throw(ctx),
                 };
             }
         }
@@ -194,17 +258,18 @@ fn rmdir_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue
     }
     if let Some(JsValue::String(s)) = path {
         if let Some(JsValue::Bool(r)) = recursive {
-            let res = if *r {
-                fs::remove_dir_all(s.as_str())
-            } else {
-                fs::remove_dir(s.as_str())
-            };
-            return match res {
-                Ok(()) => JsValue::UnDefined,
-                Err(e) => {
-                    let err = err_to_js_object(ctx, e);
-                    JsValue::Exception(ctx.throw_error(err))
+            if !*r {
+                return match tree_rmdir(s.as_str()) {
+                    Ok(()) => JsValue::UnDefined,
+                    Err(e) => e.throw(ctx),
+                };
+            }
+            return match tree_stat(s.as_str(), false, "rmdir") {
+                Ok(stat) if stat.filetype == wasi_fs::FILETYPE_DIRECTORY => {
+                    run_fs_job_sync(ctx, RemoveJob::new(s.to_string(), true, false))
                 }
+                Ok(_) => FsError::os(wasi_fs::ERRNO_NOTDIR, "rmdir", s.as_str()).throw(ctx),
+                Err(e) => e.throw(ctx),
             };
         }
     }
@@ -212,41 +277,10 @@ fn rmdir_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue
 }
 
 fn rm_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
-    let path = arg.get(0);
-    let recursive = arg.get(1);
-    let force = arg.get(2);
-    if path.is_none() {
-        return JsValue::UnDefined;
-    }
-    if let Some(JsValue::String(s)) = path {
-        if let Some(JsValue::Bool(r)) = recursive {
-            if let Some(JsValue::Bool(f)) = force {
-                let res = fs::metadata(s.as_str()).and_then(|stat| {
-                    if stat.is_file() {
-                        fs::remove_file(s.as_str())
-                    } else {
-                        if *r {
-                            fs::remove_dir_all(s.as_str())
-                        } else {
-                            fs::remove_dir(s.as_str())
-                        }
-                    }
-                });
-                return match res {
-                    Ok(()) => JsValue::UnDefined,
-                    Err(e) => {
-                        if e.kind() == std::io::ErrorKind::NotFound && *f {
-                            JsValue::UnDefined
-                        } else {
-                            let err = err_to_js_object(ctx, e);
-                            JsValue::Exception(ctx.throw_error(err))
-                        }
-                    }
-                };
-            }
-        }
+    match rm_args(arg) {
+        Some(job) => run_fs_job_sync(ctx, job),
+        None => JsValue::UnDefined,
     }
-    return JsValue::UnDefined;
 }
 
 fn rename_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
@@ -257,12 +291,9 @@ fn rename_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValu
     }
     if let Some(JsValue::String(from)) = old_path {
         if let Some(JsValue::String(to)) = new_path {
-            return match fs::rename(from.as_str(), to.as_str()) {
+            return match tree_rename(from.as_str(), to.as_str()) {
                 Ok(()) => JsValue::UnDefined,
-                Err(e) => {
-                    let err = err_to_js_object(ctx, e);
-                    JsValue::Exception(ctx.throw_error(err))
-                }
+                Err(e) => e.throw(ctx),
             };
         }
     }
@@ -277,16 +308,9 @@ fn truncate_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsVa
     }
     if let Some(JsValue::String(p)) = path {
         if let Some(l) = get_js_number(len) {
-            let res = fs::OpenOptions::new()
-                .write(true)
-                .open(p.as_str())
-                .and_then(|file| file.set_len(l as u64));
-            return match res {
+            return match tree_truncate(p.as_str(), l as u64) {
                 Ok(()) => JsValue::UnDefined,
-                Err(e) => {
-                    let err = err_to_js_object(ctx, e);
-                    JsValue::Exception(ctx.throw_error(err))
-                }
+                Err(e) => e.throw(ctx),
             };
         }
     }
@@ -353,13 +377,9 @@ fn copy_file_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsV
     }
     if let Some(JsValue::String(from)) = from_path {
         if let Some(JsValue::String(to)) = to_path {
-            let res = fs::copy(from.as_str(), to.as_str());
-            return match res {
-                Ok(_) => JsValue::UnDefined,
-                Err(e) => {
-                    let err = err_to_js_object(ctx, e);
-                    JsValue::Exception(ctx.throw_error(err))
-                }
+            return match tree_copy_file(from.as_str(), to.as_str(), false) {
+                Ok(()) => JsValue::UnDefined,
+                Err(e) => e.throw(ctx),
             };
         }
     }
@@ -374,13 +394,9 @@ fn link_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue
     }
     if let Some(JsValue::String(from)) = from_path {
         if let Some(JsValue::String(to)) = to_path {
-            let res = fs::hard_link(from.as_str(), to.as_str());
-            return match res {
-                Ok(_) => JsValue::UnDefined,
-                Err(e) => {
-                    let err = err_to_js_object(ctx, e);
-                    JsValue::Exception(ctx.throw_error(err))
-                }
+            return match tree_link(from.as_str(), to.as_str()) {
+                Ok(()) => JsValue::UnDefined,
+                Err(e) => e.throw(ctx),
             };
         }
     }
@@ -406,7 +422,10 @@ fn symlink_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsVal
             };
             let res = unsafe { wasi_fs::path_symlink(from.as_str(), dir, file.as_str()) };
             return match res {
-                Ok(_) => JsValue::UnDefined,
+                Ok(_) => {
+                    reset_file_meta(to.as_str());
+                    JsValue::UnDefined
+                }
                 Err(e) => {
                     let err = errno_to_js_object(ctx, e);
                     JsValue::Exception(ctx.throw_error(err))
@@ -501,6 +520,69 @@ fn lutime_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValu
     return JsValue::UnDefined;
 }
 
+/// The stat of the path or fd in `arg[0]` for the `chmod` and `chown`
+/// families, `follow` is false for `lchmod` and `lchown`.
+fn meta_target(
+    arg: &[JsValue],
+    follow: bool,
+    syscall: &'static str,
+) -> Result<wasi_fs::Filestat, FsError> {
+    match arg.first() {
+        Some(JsValue::String(path)) => tree_stat(path.as_str(), follow, syscall),
+        fd => match get_js_number(fd) {
+            Some(fd) => unsafe { wasi_fs::fd_filestat_get(fd as u32) }
+                .map_err(|e| FsError::os(e, syscall, "")),
+            None => Err(FsError::os(wasi_fs::ERRNO_BADF, syscall, "")),
+        },
+    }
+}
+
+/// `chmodSync(pathOrFd, mode, follow)`
+fn chmod_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
+    let mode = match get_js_number(arg.get(1)) {
+        Some(mode) => mode as u32 & 0o7777,
+        None => return JsValue::UnDefined,
+    };
+    let follow = !matches!(arg.get(2), Some(JsValue::Bool(false)));
+    match meta_target(arg, follow, "chmod") {
+        Ok(stat) => {
+            update_file_meta(&stat, |meta| meta.mode = Some(mode));
+            JsValue::UnDefined
+        }
+        Err(e) => e.throw(ctx),
+    }
+}
+
+/// `chownSync(pathOrFd, uid, gid, follow)`, an id of -1 is left as it is.
+fn chown_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
+    let id = |v: Option<&JsValue>| get_js_number(v).filter(|id| *id >= 0).map(|id| id as u32);
+    let (uid, gid) = (id(arg.get(1)), id(arg.get(2)));
+    let follow = !matches!(arg.get(3), Some(JsValue::Bool(false)));
+    match meta_target(arg, follow, "chown") {
+        Ok(stat) => {
+            update_file_meta(&stat, |meta| {
+                meta.uid = uid.or(meta.uid);
+                meta.gid = gid.or(meta.gid);
+            });
+            JsValue::UnDefined
+        }
+        Err(e) => e.throw(ctx),
+    }
+}
+
+/// `umask(mask)` sets the mask of the modes files are created with and
+/// returns the old one, `umask()` only returns it.
+fn umask(_ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
+    let old = UMASK.with(|umask| {
+        let old = umask.get();
+        if let Some(mask) = get_js_number(arg.first()) {
+            umask.set(mask as u32 & 0o777);
+        }
+        old
+    });
+    JsValue::Int(old as i32)
+}
+
 fn futime_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
     let fd = arg.get(0);
     let atime = arg.get(1);
@@ -594,10 +676,16 @@ fn get_js_number(val: Option<&JsValue>) -> Option<i64> {
     }
 }
 
+/// `fread(fd, position, length)`, a regular file is always ready to poll so
+/// it is read in chunks on the fs pool instead.
 fn fread(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
     if let Some(JsValue::Int(fd)) = arg.get(0) {
         if let Some(position) = get_js_number(arg.get(1)) {
             if let Some(JsValue::Int(length)) = arg.get(2) {
+                if on_fs_pool(*fd as u32) {
+                    let job = ReadJob::new(*fd as u32, position, *length as usize);
+                    return run_fs_job(ctx, Box::new(job));
+                }
                 let (promise, ok, error) = ctx.new_promise();
                 if let Some(event_loop) = ctx.event_loop() {
                     event_loop.fd_read(
@@ -674,7 +762,7 @@ fn fread_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue
 fn open_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
     if let Some(JsValue::String(path)) = arg.get(0) {
         if let Some(JsValue::Int(flag)) = arg.get(1) {
-            if let Some(JsValue::Int(_mode)) = arg.get(2) {
+            if let Some(JsValue::Int(mode)) = arg.get(2) {
                 let fdflag = if flag & 128 == 128 {
                     wasi_fs::FDFLAGS_NONBLOCK
                 } else {
@@ -723,10 +811,32 @@ fn open_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue
                         }
                     }
                 };
-                let res =
-                    unsafe { wasi_fs::path_open(dir, 0, file.as_str(), oflag, right, 0, fdflag) };
+                // `wx` fails on any existing entry, a dangling symlink too,
+                // which makes it safe for lock files shared between instances
+                let excl = oflag & (wasi_fs::OFLAGS_CREAT | wasi_fs::OFLAGS_EXCL)
+                    == wasi_fs::OFLAGS_CREAT | wasi_fs::OFLAGS_EXCL;
+                let lookup = if excl {
+                    0
+                } else {
+                    wasi_fs::LOOKUPFLAGS_SYMLINK_FOLLOW
+                };
+                // only a file this call creates takes `mode`
+                let created = excl
+                    || oflag & wasi_fs::OFLAGS_CREAT != 0
+                        && unsafe { wasi_fs::path_filestat_get(dir, lookup, file.as_str()) }
+                            .is_err();
+                let res = unsafe {
+                    wasi_fs::path_open(dir, lookup, file.as_str(), oflag, right, 0, fdflag)
+                };
                 return match res {
-                    Ok(fd) => JsValue::Int(fd as i32),
+                    Ok(fd) => {
+                        if created {
+                            if let Ok(stat) = unsafe { wasi_fs::fd_filestat_get(fd) } {
+                                set_created_mode(&stat, *mode as u32);
+                            }
+                        }
+                        JsValue::Int(fd as i32)
+                    }
                     Err(e) => {
                         let err = errno_to_js_object(ctx, e);
                         JsValue::Exception(ctx.throw_error(err))
@@ -766,10 +876,16 @@ fn readlink_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsVa
     return JsValue::UnDefined;
 }
 
+/// `fwrite(fd, position, buffer)`, regular files go to the fs pool like in
+/// `fread`.
 fn fwrite(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
     if let Some(JsValue::Int(fd)) = arg.get(0) {
         if let Some(position) = get_js_number(arg.get(1)) {
             if let Some(JsValue::ArrayBuffer(buf)) = arg.get(2) {
+                if on_fs_pool(*fd as u32) {
+                    let job = WriteJob::new(*fd as u32, position, buf.to_vec());
+                    return run_fs_job(ctx, Box::new(job));
+                }
                 let (promise, ok, error) = ctx.new_promise();
                 if let Some(event_loop) = ctx.event_loop() {
                     event_loop.fd_write(
@@ -884,62 +1000,2341 @@ fn freaddir_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsVa
     return JsValue::UnDefined;
 }
 
+/// Stat of `path` without following a final symlink when `follow` is
+/// false, `None` when it cannot be read.
+fn poll_stat(path: &str, follow: bool) -> Option<wasi_fs::Filestat> {
+    let (dir, file) = wasi_fs::open_parent(path).ok()?;
+    let flags = if follow {
+        wasi_fs::LOOKUPFLAGS_SYMLINK_FOLLOW
+    } else {
+        0
+    };
+    unsafe { wasi_fs::path_filestat_get(dir, flags, file.as_str()) }.ok()
+}
+
+/// What `fs.watchFile` reports for a file that does not exist.
+fn missing_stat() -> wasi_fs::Filestat {
+    wasi_fs::Filestat {
+        dev: 0,
+        ino: 0,
+        filetype: wasi_fs::FILETYPE_UNKNOWN,
+        nlink: 0,
+        size: 0,
+        atim: 0,
+        mtim: 0,
+        ctim: 0,
+    }
+}
+
+fn same_stat(a: &wasi_fs::Filestat, b: &wasi_fs::Filestat) -> bool {
+    a.dev == b.dev
+        && a.ino == b.ino
+        && a.filetype == b.filetype
+        && a.nlink == b.nlink
+        && a.size == b.size
+        && a.mtim == b.mtim
+        && a.ctim == b.ctim
+}
+
+/// Names under `dir` with their stats, relative to `dir`. Subdirectories
+/// are walked too when `recursive`.
+fn list_dir(
+    dir: &str,
+    prefix: &str,
+    recursive: bool,
+    entries: &mut BTreeMap<String, wasi_fs::Filestat>,
+) {
+    let read_dir = match tree_read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(_) => return,
+    };
+    for (name, _) in read_dir {
+        let path = format!("{}/{}", dir, name);
+        let stat = match poll_stat(&path, false) {
+            Some(stat) => stat,
+            None => continue,
+        };
+        let key = format!("{}{}", prefix, name);
+        if recursive && stat.filetype == wasi_fs::FILETYPE_DIRECTORY {
+            list_dir(&path, &format!("{}/", key), recursive, entries);
+        }
+        entries.insert(key, stat);
+    }
+}
+
+/// A path watched by polling its stat, and for directories the stats of
+/// the entries in it.
+struct WatchState {
+    path: String,
+    interval: Duration,
+    /// `fs.watchFile` semantics, the listener gets the stats.
+    stat_mode: bool,
+    recursive: bool,
+    stat: Option<wasi_fs::Filestat>,
+    entries: BTreeMap<String, wasi_fs::Filestat>,
+    /// Whether a missing file was reported to a stat listener already.
+    reported_missing: bool,
+    /// `None` once closed.
+    listener: Option<JsFunction>,
+    timer: Option<usize>,
+}
+
+impl WatchState {
+    fn new(
+        path: String,
+        interval: Duration,
+        stat_mode: bool,
+        recursive: bool,
+        listener: JsFunction,
+    ) -> Self {
+        let mut state = WatchState {
+            path,
+            interval,
+            stat_mode,
+            recursive,
+            stat: None,
+            entries: BTreeMap::new(),
+            reported_missing: false,
+            listener: Some(listener),
+            timer: None,
+        };
+        state.stat = poll_stat(&state.path, true);
+        state.entries = state.list();
+        state
+    }
+
+    fn list(&self) -> BTreeMap<String, wasi_fs::Filestat> {
+        let mut entries = BTreeMap::new();
+        match self.stat {
+            Some(stat) if !self.stat_mode && stat.filetype == wasi_fs::FILETYPE_DIRECTORY => {
+                list_dir(&self.path, "", self.recursive, &mut entries)
+            }
+            _ => {}
+        }
+        entries
+    }
+
+    fn basename(&self) -> String {
+        self.path
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    /// `(event, filename)` pairs for `fs.watch` since the last poll.
+    /// Entries that were added, removed or replaced are a `rename`, files
+    /// that were written to a `change`.
+    fn poll_events(&mut self) -> Vec<(&'static str, String)> {
+        let mut events = vec![];
+        let stat = poll_stat(&self.path, true);
+        match (&self.stat, &stat) {
+            (None, None) => {}
+            (Some(old), Some(new)) if old.ino == new.ino && old.filetype == new.filetype => {
+                let is_dir = new.filetype == wasi_fs::FILETYPE_DIRECTORY;
+                if !is_dir && (old.mtim != new.mtim || old.size != new.size) {
+                    events.push(("change", self.basename()));
+                }
+            }
+            _ => events.push(("rename", self.basename())),
+        }
+        self.stat = stat;
+
+        let entries = self.list();
+        for (name, old) in self.entries.iter() {
+            match entries.get(name) {
+                None => events.push(("rename", name.clone())),
+                Some(new) if new.ino != old.ino || new.filetype != old.filetype => {
+                    events.push(("rename", name.clone()))
+                }
+                Some(new) => {
+                    let is_dir = new.filetype == wasi_fs::FILETYPE_DIRECTORY;
+                    if !is_dir && (old.mtim != new.mtim || old.size != new.size) {
+                        events.push(("change", name.clone()));
+                    }
+                }
+            }
+        }
+        for name in entries.keys() {
+            if !self.entries.contains_key(name) {
+                events.push(("rename", name.clone()));
+            }
+        }
+        self.entries = entries;
+        events
+    }
+
+    /// `(current, previous)` for `fs.watchFile` when the stat changed. A
+    /// missing file is reported once, with zeroed stats.
+    fn poll_stat_change(&mut self) -> Option<(wasi_fs::Filestat, wasi_fs::Filestat)> {
+        let stat = poll_stat(&self.path, true);
+        let change = match (self.stat, stat) {
+            (None, None) if self.reported_missing => None,
+            (None, None) => Some((missing_stat(), missing_stat())),
+            (Some(old), Some(new)) if same_stat(&old, &new) => None,
+            (old, new) => Some((
+                new.unwrap_or_else(missing_stat),
+                old.unwrap_or_else(missing_stat),
+            )),
+        };
+        self.reported_missing = stat.is_none();
+        self.stat = stat;
+        change
+    }
+}
+
+/// The handle behind `fs.watch` and `fs.watchFile`. It polls on the event
+/// loop timer until it is closed.
+pub struct FsWatcher(Rc<RefCell<WatchState>>);
+
+impl FsWatcher {
+    fn start(ctx: &mut Context, state: WatchState) -> JsValue {
+        let watcher = Rc::new(RefCell::new(state));
+        arm_watcher(ctx, &watcher);
+        FsWatcher::wrap_obj(ctx, FsWatcher(watcher))
+    }
+
+    pub fn js_close(
+        &mut self,
+        _this: &mut JsObject,
+        ctx: &mut Context,
+        _argv: &[JsValue],
+    ) -> JsValue {
+        let mut state = self.0.borrow_mut();
+        state.listener = None;
+        if let (Some(id), Some(event_loop)) = (state.timer.take(), ctx.event_loop()) {
+            event_loop.clear_timeout(id);
+        }
+        JsValue::UnDefined
+    }
+}
+
+impl JsClassDef for FsWatcher {
+    const CLASS_NAME: &'static str = "FsWatcher";
+    const CONSTRUCTOR_ARGC: u8 = 0;
+
+    type RefType = FsWatcher;
+
+    const FIELDS: &'static [JsClassField<Self::RefType>] = &[];
+
+    const METHODS: &'static [JsClassMethod<Self::RefType>] = &[("close", 0, Self::js_close)];
+
+    unsafe fn mut_class_id_ptr() -> &'static mut u32 {
+        static mut CLASS_ID: u32 = 0;
+        &mut *std::ptr::addr_of_mut!(CLASS_ID)
+    }
+
+    fn constructor_fn(_ctx: &mut Context, _argv: &[JsValue]) -> Result<Self::RefType, JsValue> {
+        Err(JsValue::Null)
+    }
+
+    fn gc_mark(data: &Self::RefType, make: &mut dyn Fn(&JsValue)) {
+        if let Some(f) = data.0.borrow().listener.clone() {
+            make(&JsValue::Function(f))
+        }
+    }
+}
+
+fn arm_watcher(ctx: &mut Context, watcher: &Rc<RefCell<WatchState>>) {
+    let interval = watcher.borrow().interval;
+    if let Some(event_loop) = ctx.event_loop() {
+        let w = watcher.clone();
+        let id = event_loop.add_timer(interval, Box::new(move |ctx| poll_watcher(ctx, &w)));
+        watcher.borrow_mut().timer = Some(id);
+    }
+}
+
+fn poll_watcher(ctx: &mut Context, watcher: &Rc<RefCell<WatchState>>) {
+    let mut state = watcher.borrow_mut();
+    state.timer = None;
+    let listener = match state.listener.clone() {
+        Some(listener) => listener,
+        None => return,
+    };
+    if state.stat_mode {
+        let change = state.poll_stat_change();
+        drop(state);
+        if let Some((current, previous)) = change {
+            let current = stat_to_js_object(ctx, current);
+            let previous = stat_to_js_object(ctx, previous);
+            listener.call(&[current, previous]);
+        }
+    } else {
+        let events = state.poll_events();
+        drop(state);
+        for (event, filename) in events {
+            let event = ctx.new_string(event).into();
+            let filename = ctx.new_string(filename.as_str()).into();
+            listener.call(&[event, filename]);
+        }
+    }
+    // the listener may have closed it
+    if watcher.borrow().listener.is_some() {
+        arm_watcher(ctx, watcher);
+    }
+}
+
+fn watch_args(arg: &[JsValue]) -> Option<(String, Duration, JsFunction)> {
+    let path = match arg.first() {
+        Some(JsValue::String(path)) => path.to_string(),
+        _ => return None,
+    };
+    let interval = get_js_number(arg.get(1)).filter(|ms| *ms >= 0)?;
+    let listener = match arg.last() {
+        Some(JsValue::Function(f)) => f.clone(),
+        _ => return None,
+    };
+    Some((path, Duration::from_millis(interval as u64), listener))
+}
+
+/// `watch(path, interval, recursive, listener)`, calls
+/// `listener(eventType, filename)` for every change seen while polling.
+fn watch(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
+    let recursive = matches!(arg.get(2), Some(JsValue::Bool(true)));
+    match watch_args(arg) {
+        Some((path, interval, listener)) => FsWatcher::start(
+            ctx,
+            WatchState::new(path, interval, false, recursive, listener),
+        ),
+        None => JsValue::UnDefined,
+    }
+}
+
+/// `watchFile(path, interval, listener)`, calls `listener(current,
+/// previous)` with the stats whenever they change.
+fn watch_file(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
+    match watch_args(arg) {
+        Some((path, interval, listener)) => {
+            FsWatcher::start(ctx, WatchState::new(path, interval, true, false, listener))
+        }
+        None => JsValue::UnDefined,
+    }
+}
+
+/// How many units of work an async fs job does before it gives the event
+/// loop a turn. A unit is one entry of a recursive `cp`, `rm` or `readdir`,
+/// one `CHUNK_SIZE` chunk of a read or write, or one deferred call.
+const WORK_PER_TURN: usize = 64;
+
+/// Regular files are read and written in chunks of this size.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// How many async fs jobs advance at the same time, like the default size of
+/// the libuv thread pool. WASI has no threads here, so the jobs take turns on
+/// the event loop instead of running elsewhere, the others wait in line.
+const FS_POOL_SIZE: usize = 4;
+
+/// Why a recursive operation stopped.
+enum FsError {
+    /// A WASI call on `path` failed.
+    Os {
+        errno: wasi_fs::Errno,
+        syscall: &'static str,
+        path: String,
+    },
+    /// `cp` refused to copy, `code` names the node error.
+    Cp {
+        code: &'static str,
+        errno: wasi_fs::Errno,
+        message: String,
+        path: String,
+    },
+    /// JS code called by the job threw, the exception is pending.
+    Thrown(JsValue),
+}
+
+impl FsError {
+    fn os(errno: wasi_fs::Errno, syscall: &'static str, path: &str) -> Self {
+        FsError::Os {
+            errno,
+            syscall,
+            path: path.to_string(),
+        }
+    }
+
+    fn cp(code: &'static str, errno: wasi_fs::Errno, message: String, path: &str) -> Self {
+        FsError::Cp {
+            code,
+            errno,
+            message,
+            path: path.to_string(),
+        }
+    }
+
+    fn is_errno(&self, e: wasi_fs::Errno) -> bool {
+        matches!(self, FsError::Os { errno, .. } if *errno == e)
+    }
+
+    fn to_js_object(&self, ctx: &mut Context) -> JsValue {
+        let (errno, syscall, path) = match self {
+            FsError::Os {
+                errno,
+                syscall,
+                path,
+            } => (*errno, *syscall, path),
+            FsError::Cp { errno, path, .. } => (*errno, "cp", path),
+            FsError::Thrown(e) => return e.clone(),
+        };
+        let mut res = errno_to_js_object(ctx, errno);
+        if let JsValue::Object(obj) = &mut res {
+            obj.set("syscall", ctx.new_string(syscall).into());
+            // calls on an fd have no path
+            if !path.is_empty() {
+                obj.set("path", ctx.new_string(path.as_str()).into());
+            }
+            if let FsError::Cp { code, message, .. } = self {
+                obj.set("cpCode", ctx.new_string(code).into());
+                obj.set("message", ctx.new_string(message.as_str()).into());
+            }
+        }
+        res
+    }
+
+    fn throw(self, ctx: &mut Context) -> JsValue {
+        match self {
+            FsError::Thrown(e) => e,
+            e => {
+                let err = e.to_js_object(ctx);
+                JsValue::Exception(ctx.throw_error(err))
+            }
+        }
+    }
+}
+
+fn join_path(dir: &str, name: &str) -> String {
+    if dir.ends_with('/') {
+        format!("{}{}", dir, name)
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+fn parent_path(path: &str) -> Option<&str> {
+    let path = path.trim_end_matches('/');
+    match path.rfind('/') {
+        Some(0) | None => None,
+        Some(i) => Some(&path[..i]),
+    }
+}
+
+/// Whether `dest` is `src` or below it, comparing the path strings only.
+fn is_src_subdir(src: &str, dest: &str) -> bool {
+    fn components(path: &str) -> Vec<&str> {
+        let mut res = vec![];
+        for part in path.split('/') {
+            match part {
+                "" | "." => {}
+                ".." => {
+                    res.pop();
+                }
+                part => res.push(part),
+            }
+        }
+        res
+    }
+    let dest = components(dest);
+    components(src)
+        .iter()
+        .enumerate()
+        .all(|(i, part)| dest.get(i) == Some(part))
+}
+
+fn tree_parent(path: &str, syscall: &'static str) -> Result<(u32, String), FsError> {
+    wasi_fs::open_parent(path).map_err(|_| FsError::os(wasi_fs::ERRNO_NOTCAPABLE, syscall, path))
+}
+
+fn tree_stat(
+    path: &str,
+    follow: bool,
+    syscall: &'static str,
+) -> Result<wasi_fs::Filestat, FsError> {
+    let (dir, file) = tree_parent(path, syscall)?;
+    let flags = if follow {
+        wasi_fs::LOOKUPFLAGS_SYMLINK_FOLLOW
+    } else {
+        0
+    };
+    unsafe { wasi_fs::path_filestat_get(dir, flags, file.as_str()) }
+        .map_err(|e| FsError::os(e, syscall, path))
+}
+
+/// The stat of `path`, `None` when it does not exist.
+fn tree_stat_opt(
+    path: &str,
+    follow: bool,
+    syscall: &'static str,
+) -> Result<Option<wasi_fs::Filestat>, FsError> {
+    match tree_stat(path, follow, syscall) {
+        Ok(stat) => Ok(Some(stat)),
+        Err(e) if e.is_errno(wasi_fs::ERRNO_NOENT) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// The names and types of the entries in the directory `path`, without
+/// `.` and `..`.
+fn tree_read_dir(path: &str) -> Result<Vec<(String, wasi_fs::Filetype)>, FsError> {
+    let (dir, file) = tree_parent(path, "scandir")?;
+    let fd = unsafe {
+        wasi_fs::path_open(
+            dir,
+            wasi_fs::LOOKUPFLAGS_SYMLINK_FOLLOW,
+            file.as_str(),
+            wasi_fs::OFLAGS_DIRECTORY,
+            wasi_fs::RIGHTS_FD_READDIR | wasi_fs::RIGHTS_FD_FILESTAT_GET,
+            0,
+            0,
+        )
+    }
+    .map_err(|e| FsError::os(e, "scandir", path))?;
+    let s = std::mem::size_of::<wasi_fs::Dirent>();
+    let mut entries = vec![];
+    let mut buf = vec![0u8; 4096];
+    let mut cookie = 0;
+    let res = loop {
+        let len = match unsafe { wasi_fs::fd_readdir(fd, buf.as_mut_ptr(), buf.len(), cookie) } {
+            Ok(len) => len.min(buf.len()),
+            Err(e) => break Err(FsError::os(e, "scandir", path)),
+        };
+        let mut idx = 0;
+        let mut progressed = false;
+        while idx + s <= len {
+            let dirent =
+                unsafe { std::ptr::read_unaligned(buf[idx..].as_ptr() as *const wasi_fs::Dirent) };
+            let end = idx + s + dirent.d_namlen as usize;
+            // cut off, the next call starts at this entry again
+            if end > len {
+                break;
+            }
+            let name = String::from_utf8_lossy(&buf[idx + s..end]).to_string();
+            if name != "." && name != ".." {
+                entries.push((name, dirent.d_type));
+            }
+            cookie = dirent.d_next;
+            progressed = true;
+            idx = end;
+        }
+        if len < buf.len() {
+            break Ok(entries);
+        }
+        if !progressed {
+            // a single entry does not fit
+            let grown = buf.len() * 2;
+            buf.resize(grown, 0);
+        }
+    };
+    let _ = unsafe { wasi_fs::fd_close(fd) };
+    res
+}
+
+/// Creates the directory `path` with `mode`, less the umask.
+fn tree_mkdir(path: &str, mode: u32) -> Result<(), FsError> {
+    let (dir, file) = tree_parent(path, "mkdir")?;
+    unsafe { wasi_fs::path_create_directory(dir, file.as_str()) }
+        .map_err(|e| FsError::os(e, "mkdir", path))?;
+    if let Ok(stat) = tree_stat(path, false, "mkdir") {
+        set_created_mode(&stat, mode);
+    }
+    Ok(())
+}
+
+fn tree_rmdir(path: &str) -> Result<(), FsError> {
+    let (dir, file) = tree_parent(path, "rmdir")?;
+    let stat = tree_stat(path, false, "rmdir").ok();
+    unsafe { wasi_fs::path_remove_directory(dir, file.as_str()) }
+        .map_err(|e| FsError::os(e, "rmdir", path))?;
+    if let Some(stat) = stat {
+        forget_file_meta(&stat);
+    }
+    Ok(())
+}
+
+fn tree_unlink(path: &str) -> Result<(), FsError> {
+    let (dir, file) = tree_parent(path, "unlink")?;
+    let stat = tree_stat(path, false, "unlink").ok();
+    unsafe { wasi_fs::path_unlink_file(dir, file.as_str()) }
+        .map_err(|e| FsError::os(e, "unlink", path))?;
+    if let Some(stat) = stat {
+        forget_file_meta(&stat);
+    }
+    Ok(())
+}
+
+fn tree_readlink(path: &str) -> Result<String, FsError> {
+    let (dir, file) = tree_parent(path, "readlink")?;
+    let mut buf = vec![0; 1024];
+    let len = unsafe { wasi_fs::path_readlink(dir, file.as_str(), buf.as_mut_ptr(), buf.len()) }
+        .map_err(|e| FsError::os(e, "readlink", path))?;
+    Ok(String::from_utf8_lossy(&buf[..len.min(buf.len())]).to_string())
+}
+
+fn tree_symlink(target: &str, path: &str) -> Result<(), FsError> {
+    let (dir, file) = tree_parent(path, "symlink")?;
+    unsafe { wasi_fs::path_symlink(target, dir, file.as_str()) }
+        .map_err(|e| FsError::os(e, "symlink", path))?;
+    reset_file_meta(path);
+    Ok(())
+}
+
+fn tree_rename(from: &str, to: &str) -> Result<(), FsError> {
+    let (from_dir, from_file) = tree_parent(from, "rename")?;
+    let (to_dir, to_file) = tree_parent(to, "rename")?;
+    let from_stat = tree_stat(from, false, "rename").ok();
+    let replaced = tree_stat(to, false, "rename").ok();
+    unsafe { wasi_fs::path_rename(from_dir, from_file.as_str(), to_dir, to_file.as_str()) }
+        .map_err(|e| FsError::os(e, "rename", from))?;
+    // the entry that was at `to` lost a link, unless both name one file
+    if let Some(stat) = replaced {
+        let same = from_stat.is_some_and(|from| from.dev == stat.dev && from.ino == stat.ino);
+        if !same {
+            forget_file_meta(&stat);
+        }
+    }
+    Ok(())
+}
+
+fn tree_link(from: &str, to: &str) -> Result<(), FsError> {
+    let (from_dir, from_file) = tree_parent(from, "link")?;
+    let (to_dir, to_file) = tree_parent(to, "link")?;
+    unsafe { wasi_fs::path_link(from_dir, 0, from_file.as_str(), to_dir, to_file.as_str()) }
+        .map_err(|e| FsError::os(e, "link", from))
+}
+
+/// Opens `path` for the whole of a call, the caller closes the fd.
+fn tree_open(
+    path: &str,
+    oflags: wasi_fs::Oflags,
+    rights: wasi_fs::Rights,
+    syscall: &'static str,
+) -> Result<u32, FsError> {
+    let (dir, file) = tree_parent(path, syscall)?;
+    unsafe {
+        wasi_fs::path_open(
+            dir,
+            wasi_fs::LOOKUPFLAGS_SYMLINK_FOLLOW,
+            file.as_str(),
+            oflags,
+            rights | wasi_fs::RIGHTS_FD_FILESTAT_GET,
+            0,
+            0,
+        )
+    }
+    .map_err(|e| FsError::os(e, syscall, path))
+}
+
+fn tree_truncate(path: &str, len: u64) -> Result<(), FsError> {
+    let fd = tree_open(
+        path,
+        0,
+        wasi_fs::RIGHTS_FD_WRITE | wasi_fs::RIGHTS_FD_FILESTAT_SET_SIZE,
+        "open",
+    )?;
+    let res = unsafe { wasi_fs::fd_filestat_set_size(fd, len) }
+        .map_err(|e| FsError::os(e, "ftruncate", path));
+    let _ = unsafe { wasi_fs::fd_close(fd) };
+    res
+}
+
+/// Copies what is left to read of `from` to `to`, in chunks.
+fn copy_fd(from: u32, to: u32) -> Result<(), wasi_fs::Errno> {
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let iovec = [wasi_fs::Iovec {
+            buf: buf.as_mut_ptr(),
+            buf_len: buf.len(),
+        }];
+        let n = unsafe { wasi_fs::fd_read(from, &iovec) }?;
+        if n == 0 {
+            return Ok(());
+        }
+        let mut written = 0;
+        while written < n {
+            let ciovec = [wasi_fs::Ciovec {
+                buf: buf[written..n].as_ptr(),
+                buf_len: n - written,
+            }];
+            written += unsafe { wasi_fs::fd_write(to, &ciovec) }?;
+        }
+    }
+}
+
+fn tree_copy_file(src: &str, dest: &str, preserve_timestamps: bool) -> Result<(), FsError> {
+    let from = tree_open(src, 0, wasi_fs::RIGHTS_FD_READ, "copyfile")?;
+    // an existing `dest` is truncated and keeps its mode
+    let created = tree_stat(dest, true, "copyfile").is_err();
+    let res = tree_open(
+        dest,
+        wasi_fs::OFLAGS_CREAT | wasi_fs::OFLAGS_TRUNC,
+        wasi_fs::RIGHTS_FD_WRITE,
+        "copyfile",
+    )
+    .and_then(|to| {
+        if created {
+            if let Ok(stat) = unsafe { wasi_fs::fd_filestat_get(to) } {
+                set_created_mode(&stat, 0o666);
+            }
+        }
+        let res = copy_fd(from, to).map_err(|e| FsError::os(e, "copyfile", src));
+        let _ = unsafe { wasi_fs::fd_close(to) };
+        res
+    });
+    let _ = unsafe { wasi_fs::fd_close(from) };
+    res?;
+    if preserve_timestamps {
+        // read again, copying touched the access time
+        let stat = tree_stat(src, true, "stat")?;
+        let (dir, file) = tree_parent(dest, "utime")?;
+        unsafe {
+            wasi_fs::path_filestat_set_times(
+                dir,
+                wasi_fs::LOOKUPFLAGS_SYMLINK_FOLLOW,
+                file.as_str(),
+                stat.atim,
+                stat.mtim,
+                wasi_fs::FSTFLAGS_ATIM | wasi_fs::FSTFLAGS_MTIM,
+            )
+        }
+        .map_err(|e| FsError::os(e, "utime", dest))?;
+    }
+    Ok(())
+}
+
+/// Creates `path` and the directories missing above it, returns the first
+/// one it created.
+fn mkdir_p(path: &str, mode: u32) -> Result<Option<String>, FsError> {
+    let mut missing = vec![];
+    let mut current = path;
+    loop {
+        match tree_stat(current, true, "mkdir") {
+            Ok(stat) if stat.filetype == wasi_fs::FILETYPE_DIRECTORY => break,
+            Ok(_) if missing.is_empty() => {
+                return Err(FsError::os(wasi_fs::ERRNO_EXIST, "mkdir", path))
+            }
+            Ok(_) => return Err(FsError::os(wasi_fs::ERRNO_NOTDIR, "mkdir", path)),
+            Err(e) if e.is_errno(wasi_fs::ERRNO_NOENT) => missing.push(current),
+            Err(e) => return Err(e),
+        }
+        match parent_path(current) {
+            Some(parent) => current = parent,
+            None => break,
+        }
+    }
+    for dir in missing.iter().rev() {
+        match tree_mkdir(dir, mode) {
+            Ok(()) => {}
+            Err(e) if !e.is_errno(wasi_fs::ERRNO_EXIST) => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(missing.last().map(|dir| dir.to_string()))
+}
+
+/// Work that can stop after any unit, so the async bindings can spread it
+/// over several turns of the event loop.
+trait FsJob {
+    /// Does up to `budget` units of work, `true` once the job is complete.
+    fn step(&mut self, ctx: &mut Context, budget: usize) -> Result<bool, FsError>;
+
+    fn finish(&mut self, ctx: &mut Context) -> JsValue;
+}
+
+fn run_fs_job_sync(ctx: &mut Context, mut job: impl FsJob) -> JsValue {
+    loop {
+        match job.step(ctx, usize::MAX) {
+            Ok(true) => return job.finish(ctx),
+            Ok(false) => {}
+            Err(e) => return e.throw(ctx),
+        }
+    }
+}
+
+/// The async jobs in line for the pool, by ticket.
+#[derive(Default)]
+struct FsPool {
+    running: usize,
+    next_ticket: u64,
+    waiting: VecDeque<u64>,
+}
+
+thread_local! {
+    static FS_POOL: RefCell<FsPool> = RefCell::default();
+}
+
+impl FsPool {
+    fn enqueue() -> u64 {
+        FS_POOL.with(|pool| {
+            let mut pool = pool.borrow_mut();
+            let ticket = pool.next_ticket;
+            pool.next_ticket += 1;
+            pool.waiting.push_back(ticket);
+            ticket
+        })
+    }
+
+    /// Whether the job holding `ticket` is next in line and a slot is free,
+    /// it takes the slot if so.
+    fn try_start(ticket: u64) -> bool {
+        FS_POOL.with(|pool| {
+            let mut pool = pool.borrow_mut();
+            if pool.running < FS_POOL_SIZE && pool.waiting.front() == Some(&ticket) {
+                pool.waiting.pop_front();
+                pool.running += 1;
+                true
+            } else {
+                false
+            }
+        })
+    }
+
+    fn release() {
+        FS_POOL.with(|pool| pool.borrow_mut().running -= 1);
+    }
+}
+
+/// Returns a promise for the result of `job`. Once it gets a slot in the
+/// pool it advances `WORK_PER_TURN` units per turn of the event loop.
+fn run_fs_job(ctx: &mut Context, job: Box<dyn FsJob>) -> JsValue {
+    if ctx.event_loop().is_none() {
+        return JsValue::UnDefined;
+    }
+    let (promise, ok, error) = ctx.new_promise();
+    let ticket = FsPool::enqueue();
+    wait_fs_job(ctx, ticket, job, ok, error);
+    promise
+}
+
+fn next_turn(ctx: &mut Context, f: Box<dyn FnOnce(&mut Context)>) {
+    if let Some(event_loop) = ctx.event_loop() {
+        event_loop.add_timer(Duration::ZERO, f);
+    }
+}
+
+fn wait_fs_job(ctx: &mut Context, ticket: u64, job: Box<dyn FsJob>, ok: JsValue, error: JsValue) {
+    next_turn(
+        ctx,
+        Box::new(move |ctx| {
+            if FsPool::try_start(ticket) {
+                advance_fs_job(ctx, job, ok, error)
+            } else {
+                wait_fs_job(ctx, ticket, job, ok, error)
+            }
+        }),
+    );
+}
+
+fn schedule_fs_job(ctx: &mut Context, job: Box<dyn FsJob>, ok: JsValue, error: JsValue) {
+    next_turn(
+        ctx,
+        Box::new(move |ctx| advance_fs_job(ctx, job, ok, error)),
+    );
+}
+
+fn advance_fs_job(ctx: &mut Context, mut job: Box<dyn FsJob>, ok: JsValue, error: JsValue) {
+    let res = job.step(ctx, WORK_PER_TURN);
+    if let Ok(false) = res {
+        return schedule_fs_job(ctx, job, ok, error);
+    }
+    // free the slot before any JS runs, it may start more jobs
+    FsPool::release();
+    match res {
+        Ok(_) => {
+            let res = job.finish(ctx);
+            if let JsValue::Function(resolve) = ok {
+                resolve.call(&[res]);
+            }
+        }
+        Err(e) => {
+            let err = match e {
+                FsError::Thrown(_) => ctx.take_exception(),
+                e => e.to_js_object(ctx),
+            };
+            if let JsValue::Function(reject) = error {
+                reject.call(&[err]);
+            }
+        }
+    }
+}
+
+/// `fread` on a regular file, which is always ready to poll.
+struct ReadJob {
+    fd: u32,
+    /// -1 reads at the current offset of `fd`.
+    position: i64,
+    len: usize,
+    data: Vec<u8>,
+    eof: bool,
+}
+
+impl ReadJob {
+    fn new(fd: u32, position: i64, len: usize) -> Self {
+        ReadJob {
+            fd,
+            position,
+            len,
+            data: vec![],
+            eof: false,
+        }
+    }
+}
+
+impl FsJob for ReadJob {
+    fn step(&mut self, _ctx: &mut Context, mut budget: usize) -> Result<bool, FsError> {
+        while budget > 0 && !self.eof && self.data.len() < self.len {
+            budget -= 1;
+            let start = self.data.len();
+            let want = CHUNK_SIZE.min(self.len - start);
+            self.data.resize(start + want, 0);
+            let iovec = [wasi_fs::Iovec {
+                buf: self.data[start..].as_mut_ptr(),
+                buf_len: want,
+            }];
+            let res = if self.position >= 0 {
+                unsafe { wasi_fs::fd_pread(self.fd, &iovec, (self.position as u64) + start as u64) }
+            } else {
+                unsafe { wasi_fs::fd_read(self.fd, &iovec) }
+            };
+            let n = res.map_err(|e| FsError::os(e, "read", ""))?;
+            self.data.truncate(start + n);
+            self.eof = n == 0;
+        }
+        Ok(self.eof || self.data.len() >= self.len)
+    }
+
+    fn finish(&mut self, ctx: &mut Context) -> JsValue {
+        JsValue::ArrayBuffer(ctx.new_array_buffer(&self.data))
+    }
+}
+
+/// `fwrite` on a regular file, resolves to the number of bytes written.
+struct WriteJob {
+    fd: u32,
+    /// -1 writes at the current offset of `fd`. Otherwise every chunk is
+    /// written at its own offset, another job on `fd` may move the offset
+    /// between turns.
+    position: i64,
+    data: Vec<u8>,
+    written: usize,
+}
+
+impl WriteJob {
+    fn new(fd: u32, position: i64, data: Vec<u8>) -> Self {
+        WriteJob {
+            fd,
+            position,
+            data,
+            written: 0,
+        }
+    }
+}
+
+impl FsJob for WriteJob {
+    fn step(&mut self, _ctx: &mut Context, mut budget: usize) -> Result<bool, FsError> {
+        while budget > 0 && self.written < self.data.len() {
+            budget -= 1;
+            let chunk = &self.data[self.written..self.data.len().min(self.written + CHUNK_SIZE)];
+            let ciovec = [wasi_fs::Ciovec {
+                buf: chunk.as_ptr(),
+                buf_len: chunk.len(),
+            }];
+            let res = if self.position >= 0 {
+                let offset = (self.position as u64) + self.written as u64;
+                unsafe { wasi_fs::fd_pwrite(self.fd, &ciovec, offset) }
+            } else {
+                unsafe { wasi_fs::fd_write(self.fd, &ciovec) }
+            };
+            let n = res.map_err(|e| FsError::os(e, "write", ""))?;
+            if n == 0 {
+                // nothing more fits
+                return Ok(true);
+            }
+            self.written += n;
+        }
+        Ok(self.written >= self.data.len())
+    }
+
+    fn finish(&mut self, _ctx: &mut Context) -> JsValue {
+        JsValue::Int(self.written as i32)
+    }
+}
+
+/// Whether reads and writes on `fd` go to the pool rather than the poll,
+/// an `fd` that cannot be looked at fails there as well.
+fn on_fs_pool(fd: u32) -> bool {
+    if memfs::is_virtual_fd(fd) {
+        return true;
+    }
+    match unsafe { wasi_fs::fd_filestat_get(fd) } {
+        Ok(stat) => {
+            stat.filetype == wasi_fs::FILETYPE_REGULAR_FILE
+                || stat.filetype == wasi_fs::FILETYPE_BLOCK_DEVICE
+        }
+        Err(_) => true,
+    }
+}
+
+type SyncBinding = fn(&mut Context, JsValue, &[JsValue]) -> JsValue;
+
+/// A sync binding run as a single unit of work on the pool. It waits for
+/// its turn like any job but is not split: a `stat`, `rename` or the like
+/// still blocks the event loop for as long as the WASI call takes.
+struct CallJob {
+    f: SyncBinding,
+    args: Vec<JsValue>,
+    res: JsValue,
+}
+
+impl FsJob for CallJob {
+    fn step(&mut self, ctx: &mut Context, _budget: usize) -> Result<bool, FsError> {
+        match (self.f)(ctx, JsValue::UnDefined, &self.args) {
+            JsValue::Exception(e) => Err(FsError::Thrown(JsValue::Exception(e))),
+            res => {
+                self.res = res;
+                Ok(true)
+            }
+        }
+    }
+
+    fn finish(&mut self, _ctx: &mut Context) -> JsValue {
+        std::mem::replace(&mut self.res, JsValue::UnDefined)
+    }
+}
+
+/// Defines `$name`, the async twin of the binding `$sync`: the same call on
+/// the pool, it resolves to the result and rejects with the error thrown.
+macro_rules! deferred {
+    ($name:ident, $sync:ident) => {
+        fn $name(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
+            let job = CallJob {
+                f: $sync,
+                args: arg.to_vec(),
+                res: JsValue::UnDefined,
+            };
+            run_fs_job(ctx, Box::new(job))
+        }
+    };
+}
+
+deferred!(stat, stat_sync);
+deferred!(lstat, lstat_sync);
+deferred!(fstat, fstat_sync);
+deferred!(mkdir, mkdir_sync);
+deferred!(rmdir, rmdir_sync);
+deferred!(rename, rename_sync);
+deferred!(truncate, truncate_sync);
+deferred!(ftruncate, ftruncate_sync);
+deferred!(realpath, realpath_sync);
+deferred!(copy_file, copy_file_sync);
+deferred!(link, link_sync);
+deferred!(symlink, symlink_sync);
+deferred!(utime, utime_sync);
+deferred!(futime, futime_sync);
+deferred!(fclose, fclose_sync);
+deferred!(fsync, fsync_sync);
+deferred!(fdatasync, fdatasync_sync);
+deferred!(open, open_sync);
+deferred!(readlink, readlink_sync);
+deferred!(chmod, chmod_sync);
+deferred!(chown, chown_sync);
+
+enum RemoveOp {
+    Visit(String),
+    /// Its entries are gone by now.
+    RemoveDir(String),
+}
+
+/// `rm`, symlinks are removed and never followed.
+struct RemoveJob {
+    ops: Vec<RemoveOp>,
+    recursive: bool,
+    force: bool,
+}
+
+impl RemoveJob {
+    fn new(path: String, recursive: bool, force: bool) -> Self {
+        RemoveJob {
+            ops: vec![RemoveOp::Visit(path)],
+            recursive,
+            force,
+        }
+    }
+}
+
+impl FsJob for RemoveJob {
+    fn step(&mut self, _ctx: &mut Context, mut budget: usize) -> Result<bool, FsError> {
+        while budget > 0 {
+            budget -= 1;
+            let path = match self.ops.pop() {
+                Some(RemoveOp::Visit(path)) => path,
+                Some(RemoveOp::RemoveDir(path)) => {
+                    tree_rmdir(&path)?;
+                    continue;
+                }
+                None => break,
+            };
+            let stat = match tree_stat(&path, false, "rm") {
+                Ok(stat) => stat,
+                Err(e) if self.force && e.is_errno(wasi_fs::ERRNO_NOENT) => continue,
+                Err(e) => return Err(e),
+            };
+            if stat.filetype != wasi_fs::FILETYPE_DIRECTORY {
+                tree_unlink(&path)?;
+                continue;
+            }
+            if !self.recursive {
+                return Err(FsError::os(wasi_fs::ERRNO_ISDIR, "rm", &path));
+            }
+            let entries = tree_read_dir(&path)?;
+            self.ops.push(RemoveOp::RemoveDir(path.clone()));
+            for (name, _) in entries {
+                self.ops.push(RemoveOp::Visit(join_path(&path, &name)));
+            }
+        }
+        Ok(self.ops.is_empty())
+    }
+
+    fn finish(&mut self, _ctx: &mut Context) -> JsValue {
+        JsValue::UnDefined
+    }
+}
+
+/// `readdir`, with `recursive` breadth first like node. Symlinks to
+/// directories are listed but not entered. Each directory is read whole
+/// within one turn, only a recursive walk is spread over several.
+struct ReadDirJob {
+    root: String,
+    recursive: bool,
+    pending: VecDeque<String>,
+    entries: Vec<(String, wasi_fs::Filetype)>,
+}
+
+impl ReadDirJob {
+    fn new(root: String, recursive: bool) -> Self {
+        ReadDirJob {
+            root,
+            recursive,
+            pending: vec![String::new()].into(),
+            entries: vec![],
+        }
+    }
+}
+
+impl FsJob for ReadDirJob {
+    fn step(&mut self, _ctx: &mut Context, mut budget: usize) -> Result<bool, FsError> {
+        while budget > 0 {
+            let relative = match self.pending.pop_front() {
+                Some(relative) => relative,
+                None => break,
+            };
+            let dir = if relative.is_empty() {
+                self.root.clone()
+            } else {
+                join_path(&self.root, &relative)
+            };
+            for (name, filetype) in tree_read_dir(&dir)? {
+                budget = budget.saturating_sub(1);
+                let name = if relative.is_empty() {
+                    name
+                } else {
+                    join_path(&relative, &name)
+                };
+                if self.recursive && filetype == wasi_fs::FILETYPE_DIRECTORY {
+                    self.pending.push_back(name.clone());
+                }
+                self.entries.push((name, filetype));
+            }
+        }
+        Ok(self.pending.is_empty())
+    }
+
+    /// `[{name, filetype}]`, the names are relative to the root.
+    fn finish(&mut self, ctx: &mut Context) -> JsValue {
+        let mut res = ctx.new_array();
+        for (i, (name, filetype)) in self.entries.drain(..).enumerate() {
+            let mut entry = ctx.new_object();
+            entry.set("filetype", JsValue::Int(filetype.raw() as i32));
+            entry.set("name", ctx.new_string(name.as_str()).into());
+            res.put(i, entry.into());
+        }
+        res.into()
+    }
+}
+
+/// The options of `fs.cp` the native copy looks at.
+#[derive(Clone, Copy, Default)]
+struct CopyOptions {
+    dereference: bool,
+    error_on_exist: bool,
+    force: bool,
+    preserve_timestamps: bool,
+    recursive: bool,
+    verbatim_symlinks: bool,
+}
+
+impl CopyOptions {
+    fn from_js(opts: Option<&JsValue>) -> Self {
+        let flag = |name: &str| {
+            matches!(
+                opts.and_then(|opts| opts.get(name)),
+                Some(JsValue::Bool(true))
+            )
+        };
+        CopyOptions {
+            dereference: flag("dereference"),
+            error_on_exist: flag("errorOnExist"),
+            force: flag("force"),
+            preserve_timestamps: flag("preserveTimestamps"),
+            recursive: flag("recursive"),
+            verbatim_symlinks: flag("verbatimSymlinks"),
+        }
+    }
+}
+
+struct CopyOp {
+    src: String,
+    dest: String,
+    /// The root is checked and filtered in JS already.
+    root: bool,
+}
+
+/// `cp`, following the checks of node's `cpSync` for every entry.
+struct CopyJob {
+    ops: Vec<CopyOp>,
+    opts: CopyOptions,
+    /// `filter(src, dest)`, returns whether to copy.
+    filter: Option<JsFunction>,
+}
+
+impl CopyJob {
+    fn new(src: String, dest: String, opts: CopyOptions, filter: Option<JsFunction>) -> Self {
+        CopyJob {
+            ops: vec![CopyOp {
+                src,
+                dest,
+                root: true,
+            }],
+            opts,
+            filter,
+        }
+    }
+
+    /// Node's `checkPathsSync` without the subdirectory check, which the
+    /// root covers for the whole tree.
+    fn check_paths(
+        &self,
+        src: &str,
+        dest: &str,
+    ) -> Result<(wasi_fs::Filestat, Option<wasi_fs::Filestat>), FsError> {
+        let follow = self.opts.dereference;
+        let src_stat = tree_stat(src, follow, if follow { "stat" } else { "lstat" })?;
+        let dest_stat = match tree_stat_opt(dest, follow, if follow { "stat" } else { "lstat" })? {
+            Some(dest_stat) => dest_stat,
+            None => return Ok((src_stat, None)),
+        };
+        let src_is_dir = src_stat.filetype == wasi_fs::FILETYPE_DIRECTORY;
+        let dest_is_dir = dest_stat.filetype == wasi_fs::FILETYPE_DIRECTORY;
+        if dest_stat.ino != 0
+            && dest_stat.dev != 0
+            && dest_stat.ino == src_stat.ino
+            && dest_stat.dev == src_stat.dev
+        {
+            return Err(FsError::cp(
+                "ERR_FS_CP_EINVAL",
+                wasi_fs::ERRNO_INVAL,
+                "src and dest cannot be the same".to_string(),
+                dest,
+            ));
+        }
+        if src_is_dir && !dest_is_dir {
+            return Err(FsError::cp(
+                "ERR_FS_CP_DIR_TO_NON_DIR",
+                wasi_fs::ERRNO_ISDIR,
+                format!(
+                    "cannot overwrite directory {} with non-directory {}",
+                    src, dest
+                ),
+                dest,
+            ));
+        }
+        if !src_is_dir && dest_is_dir {
+            return Err(FsError::cp(
+                "ERR_FS_CP_NON_DIR_TO_DIR",
+                wasi_fs::ERRNO_NOTDIR,
+                format!(
+                    "cannot overwrite non-directory {} with directory {}",
+                    src, dest
+                ),
+                dest,
+            ));
+        }
+        Ok((src_stat, Some(dest_stat)))
+    }
+
+    fn include(&self, src: &str, dest: &str, ctx: &mut Context) -> Result<bool, FsError> {
+        let filter = match &self.filter {
+            Some(filter) => filter,
+            None => return Ok(true),
+        };
+        let src = ctx.new_string(src).into();
+        let dest = ctx.new_string(dest).into();
+        match filter.call(&[src, dest]) {
+            JsValue::Exception(e) => Err(FsError::Thrown(JsValue::Exception(e))),
+            JsValue::Bool(include) => Ok(include),
+            _ => Ok(true),
+        }
+    }
+
+    fn copy_dir(
+        &mut self,
+        src: &str,
+        dest: &str,
+        dest_stat: Option<wasi_fs::Filestat>,
+    ) -> Result<(), FsError> {
+        if !self.opts.recursive {
+            return Err(FsError::cp(
+                "ERR_FS_EISDIR",
+                wasi_fs::ERRNO_ISDIR,
+                format!("{} is a directory (not copied)", src),
+                src,
+            ));
+        }
+        if dest_stat.is_none() {
+            tree_mkdir(dest, 0o777)?;
+        }
+        let entries = tree_read_dir(src)?;
+        for (name, _) in entries.into_iter().rev() {
+            self.ops.push(CopyOp {
+                src: join_path(src, &name),
+                dest: join_path(dest, &name),
+                root: false,
+            });
+        }
+        Ok(())
+    }
+
+    fn copy_file(
+        &self,
+        src: &str,
+        dest: &str,
+        dest_stat: Option<wasi_fs::Filestat>,
+    ) -> Result<(), FsError> {
+        if dest_stat.is_some() {
+            if self.opts.force {
+                tree_unlink(dest)?;
+            } else if self.opts.error_on_exist {
+                return Err(FsError::cp(
+                    "ERR_FS_CP_EEXIST",
+                    wasi_fs::ERRNO_EXIST,
+                    format!("{} already exists", dest),
+                    dest,
+                ));
+            } else {
+                return Ok(());
+            }
+        }
+        tree_copy_file(src, dest, self.opts.preserve_timestamps)
+    }
+
+    fn copy_link(
+        &self,
+        src: &str,
+        dest: &str,
+        dest_stat: Option<wasi_fs::Filestat>,
+    ) -> Result<(), FsError> {
+        let mut resolved_src = tree_readlink(src)?;
+        if !self.opts.verbatim_symlinks && !resolved_src.starts_with('/') {
+            resolved_src = join_path(parent_path(src).unwrap_or("."), &resolved_src);
+        }
+        if dest_stat.is_none() {
+            return tree_symlink(&resolved_src, dest);
+        }
+        let mut resolved_dest = match tree_readlink(dest) {
+            Ok(link) => link,
+            // not a link, symlinking fails with EEXIST like node
+            Err(e) if e.is_errno(wasi_fs::ERRNO_INVAL) => return tree_symlink(&resolved_src, dest),
+            Err(e) => return Err(e),
+        };
+        if !resolved_dest.starts_with('/') {
+            resolved_dest = join_path(parent_path(dest).unwrap_or("."), &resolved_dest);
+        }
+        if is_src_subdir(&resolved_src, &resolved_dest) {
+            return Err(FsError::cp(
+                "ERR_FS_CP_EINVAL",
+                wasi_fs::ERRNO_INVAL,
+                format!(
+                    "cannot copy {} to a subdirectory of self {}",
+                    resolved_src, resolved_dest
+                ),
+                dest,
+            ));
+        }
+        let dest_is_dir = tree_stat(dest, true, "stat")?.filetype == wasi_fs::FILETYPE_DIRECTORY;
+        if dest_is_dir && is_src_subdir(&resolved_dest, &resolved_src) {
+            return Err(FsError::cp(
+                "ERR_FS_CP_SYMLINK_TO_SUBDIRECTORY",
+                wasi_fs::ERRNO_INVAL,
+                format!("cannot overwrite {} with {}", resolved_dest, resolved_src),
+                dest,
+            ));
+        }
+        tree_unlink(dest)?;
+        tree_symlink(&resolved_src, dest)
+    }
+}
+
+impl FsJob for CopyJob {
+    fn step(&mut self, ctx: &mut Context, mut budget: usize) -> Result<bool, FsError> {
+        while budget > 0 {
+            budget -= 1;
+            let CopyOp { src, dest, root } = match self.ops.pop() {
+                Some(op) => op,
+                None => break,
+            };
+            let (src_stat, dest_stat) = self.check_paths(&src, &dest)?;
+            if !root && !self.include(&src, &dest, ctx)? {
+                continue;
+            }
+            match src_stat.filetype {
+                wasi_fs::FILETYPE_DIRECTORY => self.copy_dir(&src, &dest, dest_stat)?,
+                wasi_fs::FILETYPE_REGULAR_FILE
+                | wasi_fs::FILETYPE_CHARACTER_DEVICE
+                | wasi_fs::FILETYPE_BLOCK_DEVICE => self.copy_file(&src, &dest, dest_stat)?,
+                wasi_fs::FILETYPE_SYMBOLIC_LINK => self.copy_link(&src, &dest, dest_stat)?,
+                wasi_fs::FILETYPE_SOCKET_DGRAM | wasi_fs::FILETYPE_SOCKET_STREAM => {
+                    return Err(FsError::cp(
+                        "ERR_FS_CP_SOCKET",
+                        wasi_fs::ERRNO_INVAL,
+                        format!("cannot copy a socket file: {}", dest),
+                        &dest,
+                    ))
+                }
+                _ => {
+                    return Err(FsError::cp(
+                        "ERR_FS_CP_UNKNOWN",
+                        wasi_fs::ERRNO_INVAL,
+                        format!("cannot copy an unknown file type: {}", dest),
+                        &dest,
+                    ))
+                }
+            }
+        }
+        Ok(self.ops.is_empty())
+    }
+
+    fn finish(&mut self, _ctx: &mut Context) -> JsValue {
+        JsValue::UnDefined
+    }
+}
+
+fn rm_args(arg: &[JsValue]) -> Option<RemoveJob> {
+    match (arg.first(), arg.get(1), arg.get(2)) {
+        (
+            Some(JsValue::String(path)),
+            Some(JsValue::Bool(recursive)),
+            Some(JsValue::Bool(force)),
+        ) => Some(RemoveJob::new(path.to_string(), *recursive, *force)),
+        _ => None,
+    }
+}
+
+/// `rm(path, recursive, force)`, resolves once everything is removed.
+fn rm(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
+    match rm_args(arg) {
+        Some(job) => run_fs_job(ctx, Box::new(job)),
+        None => JsValue::UnDefined,
+    }
+}
+
+fn readdir_args(arg: &[JsValue]) -> Option<ReadDirJob> {
+    match (arg.first(), arg.get(1)) {
+        (Some(JsValue::String(path)), Some(JsValue::Bool(recursive))) => {
+            Some(ReadDirJob::new(path.to_string(), *recursive))
+        }
+        _ => None,
+    }
+}
+
+/// `readdirSync(path, recursive)`, `[{name, filetype}]` with the names
+/// relative to `path`.
+fn readdir_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
+    match readdir_args(arg) {
+        Some(job) => run_fs_job_sync(ctx, job),
+        None => JsValue::UnDefined,
+    }
+}
+
+/// `readdir(path, recursive)`, resolves to what `readdirSync` returns.
+fn readdir(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
+    match readdir_args(arg) {
+        Some(job) => run_fs_job(ctx, Box::new(job)),
+        None => JsValue::UnDefined,
+    }
+}
+
+fn cp_args(arg: &[JsValue]) -> Option<(String, String, CopyOptions)> {
+    match (arg.first(), arg.get(1)) {
+        (Some(JsValue::String(src)), Some(JsValue::String(dest))) => Some((
+            src.to_string(),
+            dest.to_string(),
+            CopyOptions::from_js(arg.get(2)),
+        )),
+        _ => None,
+    }
+}
+
+/// `cpSync(src, dest, options, filter)`, the checks on `src` and `dest`
+/// themselves are left to the caller.
+fn cp_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
+    let filter = match arg.get(3) {
+        Some(JsValue::Function(f)) => Some(f.clone()),
+        _ => None,
+    };
+    match cp_args(arg) {
+        Some((src, dest, opts)) => run_fs_job_sync(ctx, CopyJob::new(src, dest, opts, filter)),
+        None => JsValue::UnDefined,
+    }
+}
+
+/// `cp(src, dest, options)`, like `cpSync` without a filter.
+fn cp(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
+    match cp_args(arg) {
+        Some((src, dest, opts)) => run_fs_job(ctx, Box::new(CopyJob::new(src, dest, opts, None))),
+        None => JsValue::UnDefined,
+    }
+}
+
+/// `len` random bytes in hex.
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    // wasi random_get only fails when the host has no entropy source
+    getrandom::getrandom(&mut bytes).expect("no random source");
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// A name next to `path` that no other call, in this instance or another,
+/// comes up with.
+fn sibling_name(path: &str, what: &str) -> String {
+    let path = path.trim_end_matches('/');
+    let (dir, name) = match path.rfind('/') {
+        Some(i) => (&path[..i + 1], &path[i + 1..]),
+        None => ("", path),
+    };
+    format!("{}.{}.{}.{}", dir, name, random_hex(6), what)
+}
+
+/// Creates `path` for writing with `mode`, less the umask, failing with
+/// `EXIST` on any entry already there, a dangling symlink too.
+fn tree_create_excl(path: &str, mode: u32, syscall: &'static str) -> Result<u32, FsError> {
+    let (dir, file) = tree_parent(path, syscall)?;
+    let fd = unsafe {
+        wasi_fs::path_open(
+            dir,
+            0,
+            file.as_str(),
+            wasi_fs::OFLAGS_CREAT | wasi_fs::OFLAGS_EXCL,
+            wasi_fs::RIGHTS_FD_WRITE | wasi_fs::RIGHTS_FD_SYNC | wasi_fs::RIGHTS_FD_FILESTAT_GET,
+            0,
+            0,
+        )
+    }
+    .map_err(|e| FsError::os(e, syscall, path))?;
+    if let Ok(stat) = unsafe { wasi_fs::fd_filestat_get(fd) } {
+        set_created_mode(&stat, mode);
+    }
+    Ok(fd)
+}
+
+enum AtomicWrite {
+    Create(Vec<u8>),
+    Write(u32, WriteJob),
+    Done,
+}
+
+/// `writeFileAtomic`, the data goes to a new file next to `path` which is
+/// then renamed over it, so readers see the old contents or the new ones
+/// and never a part of them.
+struct AtomicWriteJob {
+    path: String,
+    temp: String,
+    mode: u32,
+    fsync: bool,
+    state: AtomicWrite,
+}
+
+impl AtomicWriteJob {
+    fn new(path: String, data: Vec<u8>, mode: u32, fsync: bool) -> Self {
+        AtomicWriteJob {
+            temp: sibling_name(&path, "tmp"),
+            path,
+            mode,
+            fsync,
+            state: AtomicWrite::Create(data),
+        }
+    }
+
+    fn advance(&mut self, ctx: &mut Context, budget: usize) -> Result<bool, FsError> {
+        match &mut self.state {
+            AtomicWrite::Create(data) => {
+                let fd = tree_create_excl(&self.temp, self.mode, "open")?;
+                let write = WriteJob::new(fd, -1, std::mem::take(data));
+                self.state = AtomicWrite::Write(fd, write);
+                Ok(false)
+            }
+            AtomicWrite::Write(fd, write) => {
+                if !write.step(ctx, budget)? {
+                    return Ok(false);
+                }
+                let fd = *fd;
+                if write.written < write.data.len() {
+                    return Err(FsError::os(wasi_fs::ERRNO_NOSPC, "write", &self.path));
+                }
+                if self.fsync {
+                    unsafe { wasi_fs::fd_sync(fd) }
+                        .map_err(|e| FsError::os(e, "fsync", &self.path))?;
+                }
+                self.state = AtomicWrite::Done;
+                unsafe { wasi_fs::fd_close(fd) }
+                    .map_err(|e| FsError::os(e, "close", &self.path))?;
+                tree_rename(&self.temp, &self.path).map_err(|e| match e {
+                    FsError::Os { errno, .. } => FsError::os(errno, "rename", &self.path),
+                    e => e,
+                })?;
+                Ok(true)
+            }
+            AtomicWrite::Done => Ok(true),
+        }
+    }
+}
+
+impl FsJob for AtomicWriteJob {
+    fn step(&mut self, ctx: &mut Context, budget: usize) -> Result<bool, FsError> {
+        let res = self.advance(ctx, budget);
+        if res.is_err() {
+            // nothing of a failed write is left behind
+            if let AtomicWrite::Write(fd, _) = self.state {
+                let _ = unsafe { wasi_fs::fd_close(fd) };
+            }
+            if !matches!(self.state, AtomicWrite::Create(_)) {
+                let _ = tree_unlink(&self.temp);
+            }
+            self.state = AtomicWrite::Done;
+        }
+        res
+    }
+
+    fn finish(&mut self, _ctx: &mut Context) -> JsValue {
+        JsValue::UnDefined
+    }
+}
+
+fn write_file_atomic_args(arg: &[JsValue]) -> Option<AtomicWriteJob> {
+    let mut data = vec![];
+    match (
+        arg.first(),
+        arg.get(1),
+        get_js_number(arg.get(2)),
+        arg.get(3),
+    ) {
+        (Some(JsValue::String(path)), Some(chunk), Some(mode), Some(JsValue::Bool(fsync)))
+            if extend_with_chunk(&mut data, chunk) =>
+        {
+            Some(AtomicWriteJob::new(
+                path.to_string(),
+                data,
+                mode as u32,
+                *fsync,
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// `writeFileAtomicSync(path, buffer, mode, fsync)`.
+fn write_file_atomic_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
+    match write_file_atomic_args(arg) {
+        Some(job) => run_fs_job_sync(ctx, job),
+        None => JsValue::UnDefined,
+    }
+}
+
+/// `writeFileAtomic(path, buffer, mode, fsync)`, resolves once `path` has
+/// the new contents.
+fn write_file_atomic(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
+    match write_file_atomic_args(arg) {
+        Some(job) => run_fs_job(ctx, Box::new(job)),
+        None => JsValue::UnDefined,
+    }
+}
+
+/// Whether the lock file `stat` was last refreshed more than `stale_ms`
+/// ago.
+fn is_stale_lock(stat: &wasi_fs::Filestat, stale_ms: u64) -> bool {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    now.saturating_sub(stat.mtim) > stale_ms.saturating_mul(1_000_000)
+}
+
+/// Whether the lock file `path` is stale, or gone already.
+fn lock_is_stale(path: &str, stale_ms: u64) -> Result<bool, FsError> {
+    Ok(match tree_stat_opt(path, false, "lock")? {
+        Some(stat) => is_stale_lock(&stat, stale_ms),
+        None => true,
+    })
+}
+
+/// Takes a lock file found stale out of the way, `false` when it is fresh
+/// by now. It is renamed aside before it is removed, so of several
+/// instances that found it stale only one removes it, and a fresh lock that
+/// took its place in between, or the old one refreshed by its holder, is
+/// put back.
+fn take_stale_lock(path: &str, stale_ms: u64) -> Result<bool, FsError> {
+    let aside = sibling_name(path, "stale");
+    match tree_rename(path, &aside) {
+        Ok(()) => {}
+        Err(e) if e.is_errno(wasi_fs::ERRNO_NOENT) => return Ok(true),
+        Err(e) => return Err(e),
+    }
+    let moved = tree_stat(&aside, false, "lock")?;
+    if !is_stale_lock(&moved, stale_ms) {
+        // fails when yet another lock was taken meanwhile, that one stays
+        let _ = tree_link(&aside, path);
+        let _ = tree_unlink(&aside);
+        return Ok(false);
+    }
+    tree_unlink(&aside)?;
+    Ok(true)
+}
+
+/// The token written to the lock file `path`, `None` when there is none.
+fn read_lock_token(path: &str) -> Result<Option<String>, FsError> {
+    let fd = match tree_open(path, 0, wasi_fs::RIGHTS_FD_READ, "lock") {
+        Ok(fd) => fd,
+        Err(e) if e.is_errno(wasi_fs::ERRNO_NOENT) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let mut buf = vec![0u8; 128];
+    let iovec = [wasi_fs::Iovec {
+        buf: buf.as_mut_ptr(),
+        buf_len: buf.len(),
+    }];
+    let res = unsafe { wasi_fs::fd_read(fd, &iovec) };
+    let _ = unsafe { wasi_fs::fd_close(fd) };
+    let n = res.map_err(|e| FsError::os(e, "lock", path))?;
+    Ok(Some(String::from_utf8_lossy(&buf[..n]).to_string()))
+}
+
+/// Creates the lock file `path` holding a new token, `None` when there is
+/// one already.
+fn create_lock(path: &str) -> Result<Option<String>, FsError> {
+    let token = random_hex(16);
+    let fd = match tree_create_excl(path, 0o666, "lock") {
+        Ok(fd) => fd,
+        Err(e) if e.is_errno(wasi_fs::ERRNO_EXIST) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let ciovec = [wasi_fs::Ciovec {
+        buf: token.as_ptr(),
+        buf_len: token.len(),
+    }];
+    let res = unsafe { wasi_fs::fd_write(fd, &ciovec) };
+    let _ = unsafe { wasi_fs::fd_close(fd) };
+    match res {
+        Ok(n) if n == token.len() => Ok(Some(token)),
+        res => {
+            let _ = tree_unlink(path);
+            Err(FsError::os(
+                res.err().unwrap_or(wasi_fs::ERRNO_NOSPC),
+                "lock",
+                path,
+            ))
+        }
+    }
+}
+
+/// Creates the lock file `path` holding a new token, `None` while a fresh
+/// one exists.
+fn tree_lock(path: &str, stale_ms: u64) -> Result<Option<String>, FsError> {
+    if let Some(token) = create_lock(path)? {
+        return Ok(Some(token));
+    }
+    // a second try once a stale lock is gone
+    if lock_is_stale(path, stale_ms)? && take_stale_lock(path, stale_ms)? {
+        return create_lock(path);
+    }
+    Ok(None)
+}
+
+enum LockStep {
+    Create,
+    TakeStale,
+    Retry,
+    Done(Option<String>),
+}
+
+/// `lock`, the async `lockSync`. A stale lock is taken aside a turn after it
+/// was found, the other jobs and instances may get to it in between.
+struct LockJob {
+    path: String,
+    stale_ms: u64,
+    step: LockStep,
+}
+
+impl FsJob for LockJob {
+    fn step(&mut self, _ctx: &mut Context, _budget: usize) -> Result<bool, FsError> {
+        self.step = match self.step {
+            LockStep::Create => match create_lock(&self.path)? {
+                Some(token) => LockStep::Done(Some(token)),
+                None if lock_is_stale(&self.path, self.stale_ms)? => LockStep::TakeStale,
+                None => LockStep::Done(None),
+            },
+            LockStep::TakeStale if take_stale_lock(&self.path, self.stale_ms)? => LockStep::Retry,
+            LockStep::TakeStale => LockStep::Done(None),
+            LockStep::Retry => LockStep::Done(create_lock(&self.path)?),
+            LockStep::Done(_) => return Ok(true),
+        };
+        Ok(matches!(self.step, LockStep::Done(_)))
+    }
+
+    fn finish(&mut self, ctx: &mut Context) -> JsValue {
+        match std::mem::replace(&mut self.step, LockStep::Done(None)) {
+            LockStep::Done(Some(token)) => ctx.new_string(&token).into(),
+            _ => JsValue::Null,
+        }
+    }
+}
+
+/// `(path, token)`, the arguments of `unlock` and `touchLock`.
+fn lock_token_args(arg: &[JsValue]) -> Option<(String, String)> {
+    match (arg.first(), arg.get(1)) {
+        (Some(JsValue::String(path)), Some(JsValue::String(token))) => {
+            Some((path.to_string(), token.to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// `lockSync(path, stale)`, the token of the new lock file `path` or `null`
+/// while someone else holds it. A lock nobody refreshed for `stale` ms is
+/// taken over.
+fn lock_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
+    match (arg.first(), get_js_number(arg.get(1))) {
+        (Some(JsValue::String(path)), Some(stale)) => {
+            match tree_lock(path.as_str(), stale.max(0) as u64) {
+                Ok(Some(token)) => ctx.new_string(&token).into(),
+                Ok(None) => JsValue::Null,
+                Err(e) => e.throw(ctx),
+            }
+        }
+        _ => JsValue::UnDefined,
+    }
+}
+
+/// `unlockSync(path, token)`, removes the lock file `path` if it still
+/// holds `token`. `false` when it was taken over.
+fn unlock_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
+    let (path, token) = match lock_token_args(arg) {
+        Some(args) => args,
+        None => return JsValue::UnDefined,
+    };
+    let res = read_lock_token(&path).and_then(|held| match held {
+        Some(held) if held == token => tree_unlink(&path).map(|_| true),
+        _ => Ok(false),
+    });
+    match res {
+        Ok(removed) => JsValue::Bool(removed),
+        Err(e) => e.throw(ctx),
+    }
+}
+
+/// `touchLockSync(path, token)`, marks the lock file `path` fresh if it
+/// still holds `token`. `false` when it was taken over.
+fn touch_lock_sync(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
+    let (path, token) = match lock_token_args(arg) {
+        Some(args) => args,
+        None => return JsValue::UnDefined,
+    };
+    let res = read_lock_token(&path).and_then(|held| match held {
+        Some(held) if held == token => {
+            let (dir, file) = tree_parent(&path, "lock")?;
+            unsafe {
+                wasi_fs::path_filestat_set_times(
+                    dir,
+                    0,
+                    file.as_str(),
+                    0,
+                    0,
+                    wasi_fs::FSTFLAGS_ATIM_NOW | wasi_fs::FSTFLAGS_MTIM_NOW,
+                )
+            }
+            .map_err(|e| FsError::os(e, "lock", &path))?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    });
+    match res {
+        Ok(touched) => JsValue::Bool(touched),
+        Err(e) => e.throw(ctx),
+    }
+}
+
+fn lock(ctx: &mut Context, _this_val: JsValue, arg: &[JsValue]) -> JsValue {
+    match (arg.first(), get_js_number(arg.get(1))) {
+        (Some(JsValue::String(path)), Some(stale)) => {
+            let job = LockJob {
+                path: path.to_string(),
+                stale_ms: stale.max(0) as u64,
+                step: LockStep::Create,
+            };
+            run_fs_job(ctx, Box::new(job))
+        }
+        _ => JsValue::UnDefined,
+    }
+}
+
+deferred!(unlock, unlock_sync);
+deferred!(touch_lock, touch_lock_sync);
+
+/// Appends the bytes of an `ArrayBuffer` or of a view on one, like a
+/// `Buffer`, to `data`. `false` for anything else.
+fn extend_with_chunk(data: &mut Vec<u8>, chunk: &JsValue) -> bool {
+    let (buf, offset, len) = match chunk {
+        JsValue::ArrayBuffer(buf) => {
+            data.extend_from_slice(buf.as_ref());
+            return true;
+        }
+        JsValue::Object(view) => match (
+            view.get("buffer"),
+            get_js_number(Some(&view.get("byteOffset"))),
+            get_js_number(Some(&view.get("byteLength"))),
+        ) {
+            (JsValue::ArrayBuffer(buf), Some(offset), Some(len)) => {
+                (buf, offset as usize, len as usize)
+            }
+            _ => return false,
+        },
+        _ => return false,
+    };
+    match buf.as_ref().get(offset..offset + len) {
+        Some(bytes) => {
+            data.extend_from_slice(bytes);
+            true
+        }
+        None => false,
+    }
+}
+
+struct ReaderState {
+    fd: u32,
+    /// -1 reads on from the current offset of `fd`.
+    position: i64,
+    /// What is left of the range, `None` reads to the end of the file.
+    remaining: Option<u64>,
+    bytes_read: u64,
+}
+
+/// The native side of `fs.ReadStream`. `new FileReader(fd, start, end)`
+/// reads the inclusive range `[start, end]` of `fd` one chunk per `read()`
+/// on the fs pool, each chunk in an `ArrayBuffer` of its own.
+pub struct FileReader(Rc<RefCell<ReaderState>>);
+
+/// A `read()` of a `FileReader`, it moves the reader on once done.
+struct StreamReadJob {
+    read: ReadJob,
+    state: Rc<RefCell<ReaderState>>,
+}
+
+impl FsJob for StreamReadJob {
+    fn step(&mut self, ctx: &mut Context, budget: usize) -> Result<bool, FsError> {
+        self.read.step(ctx, budget)
+    }
+
+    /// The chunk, `null` at the end of the file.
+    fn finish(&mut self, ctx: &mut Context) -> JsValue {
+        let n = self.read.data.len() as u64;
+        let mut state = self.state.borrow_mut();
+        if state.position >= 0 {
+            state.position += n as i64;
+        }
+        state.remaining = match state.remaining {
+            _ if n == 0 => Some(0),
+            remaining => remaining.map(|r| r.saturating_sub(n)),
+        };
+        state.bytes_read += n;
+        drop(state);
+        if n == 0 {
+            JsValue::Null
+        } else {
+            self.read.finish(ctx)
+        }
+    }
+}
+
+impl FileReader {
+    /// `read(size)`, resolves to the next chunk of at most `size` bytes or
+    /// to `null` once the range is read. One read at a time.
+    pub fn js_read(
+        &mut self,
+        _this: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let size = match get_js_number(argv.first()) {
+            Some(size) if size > 0 => size as u64,
+            _ => CHUNK_SIZE as u64,
+        };
+        let state = self.0.borrow();
+        let len = state.remaining.map_or(size, |r| r.min(size)) as usize;
+        if len == 0 {
+            let (promise, ok, _) = ctx.new_promise();
+            if let JsValue::Function(resolve) = ok {
+                resolve.call(&[JsValue::Null]);
+            }
+            return promise;
+        }
+        let job = StreamReadJob {
+            read: ReadJob::new(state.fd, state.position, len),
+            state: self.0.clone(),
+        };
+        drop(state);
+        run_fs_job(ctx, Box::new(job))
+    }
+
+    pub fn js_get_bytes_read(&self, _ctx: &mut Context) -> JsValue {
+        JsValue::Float(self.0.borrow().bytes_read as f64)
+    }
+}
+
+impl JsClassDef for FileReader {
+    const CLASS_NAME: &'static str = "FileReader";
+    const CONSTRUCTOR_ARGC: u8 = 3;
+
+    type RefType = FileReader;
+
+    const FIELDS: &'static [JsClassField<Self::RefType>] =
+        &[("bytesRead", Self::js_get_bytes_read, None)];
+
+    const METHODS: &'static [JsClassMethod<Self::RefType>] = &[("read", 1, Self::js_read)];
+
+    unsafe fn mut_class_id_ptr() -> &'static mut u32 {
+        static mut CLASS_ID: u32 = 0;
+        &mut *std::ptr::addr_of_mut!(CLASS_ID)
+    }
+
+    fn constructor_fn(ctx: &mut Context, argv: &[JsValue]) -> Result<Self::RefType, JsValue> {
+        let fd = match argv.first() {
+            Some(JsValue::Int(fd)) if *fd >= 0 => *fd as u32,
+            _ => return Err(ctx.throw_type_error("fd must be a file descriptor").into()),
+        };
+        let position = get_js_number(argv.get(1)).filter(|p| *p >= 0).unwrap_or(-1);
+        let remaining = match argv.get(2) {
+            Some(JsValue::Float(end)) if end.is_infinite() => None,
+            end => get_js_number(end).map(|end| (end + 1 - position.max(0)).max(0) as u64),
+        };
+        Ok(FileReader(Rc::new(RefCell::new(ReaderState {
+            fd,
+            position,
+            remaining,
+            bytes_read: 0,
+        }))))
+    }
+}
+
+struct WriterState {
+    fd: u32,
+    /// -1 writes at the current offset of `fd`.
+    position: i64,
+    bytes_written: u64,
+}
+
+/// The native side of `fs.WriteStream`. `new FileWriter(fd, start)` writes
+/// on from `start` through the fs pool.
+pub struct FileWriter(Rc<RefCell<WriterState>>);
+
+/// A `write()` of a `FileWriter`, it moves the writer on once done.
+struct StreamWriteJob {
+    write: WriteJob,
+    state: Rc<RefCell<WriterState>>,
+}
+
+impl FsJob for StreamWriteJob {
+    fn step(&mut self, ctx: &mut Context, budget: usize) -> Result<bool, FsError> {
+        self.write.step(ctx, budget)
+    }
+
+    fn finish(&mut self, ctx: &mut Context) -> JsValue {
+        let n = self.write.written as u64;
+        let mut state = self.state.borrow_mut();
+        if state.position >= 0 {
+            state.position += n as i64;
+        }
+        state.bytes_written += n;
+        drop(state);
+        self.write.finish(ctx)
+    }
+}
+
+impl FileWriter {
+    /// `write(chunks)`, writes an array of `ArrayBuffer`s or views one
+    /// after the other and resolves to the number of bytes written.
+    pub fn js_write(
+        &mut self,
+        _this: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let chunks = match argv.first() {
+            Some(JsValue::Array(chunks)) => chunks.to_vec().unwrap_or_default(),
+            Some(chunk) => vec![chunk.clone()],
+            None => vec![],
+        };
+        let mut data = vec![];
+        for chunk in chunks.iter() {
+            if !extend_with_chunk(&mut data, chunk) {
+                return ctx
+                    .throw_type_error("chunks must be ArrayBuffers or views")
+                    .into();
+            }
+        }
+        let state = self.0.borrow();
+        let job = StreamWriteJob {
+            write: WriteJob::new(state.fd, state.position, data),
+            state: self.0.clone(),
+        };
+        drop(state);
+        run_fs_job(ctx, Box::new(job))
+    }
+
+    pub fn js_get_bytes_written(&self, _ctx: &mut Context) -> JsValue {
+        JsValue::Float(self.0.borrow().bytes_written as f64)
+    }
+}
+
+impl JsClassDef for FileWriter {
+    const CLASS_NAME: &'static str = "FileWriter";
+    const CONSTRUCTOR_ARGC: u8 = 2;
+
+    type RefType = FileWriter;
+
+    const FIELDS: &'static [JsClassField<Self::RefType>] =
+        &[("bytesWritten", Self::js_get_bytes_written, None)];
+
+    const METHODS: &'static [JsClassMethod<Self::RefType>] = &[("write", 1, Self::js_write)];
+
+    unsafe fn mut_class_id_ptr() -> &'static mut u32 {
+        static mut CLASS_ID: u32 = 0;
+        &mut *std::ptr::addr_of_mut!(CLASS_ID)
+    }
+
+    fn constructor_fn(ctx: &mut Context, argv: &[JsValue]) -> Result<Self::RefType, JsValue> {
+        let fd = match argv.first() {
+            Some(JsValue::Int(fd)) if *fd >= 0 => *fd as u32,
+            _ => return Err(ctx.throw_type_error("fd must be a file descriptor").into()),
+        };
+        let position = get_js_number(argv.get(1)).filter(|p| *p >= 0).unwrap_or(-1);
+        Ok(FileWriter(Rc::new(RefCell::new(WriterState {
+            fd,
+            position,
+            bytes_written: 0,
+        }))))
+    }
+}
+
+/// Owns the fd of a `FileHandle`. `release()` hands it back once the handle
+/// closes it; a guard that is collected first closes the fd and warns, like
+/// node does for a leaked `FileHandle`.
+pub struct FdGuard {
+    fd: Option<u32>,
+}
+
+impl FdGuard {
+    pub fn js_release(
+        &mut self,
+        _this: &mut JsObject,
+        _ctx: &mut Context,
+        _argv: &[JsValue],
+    ) -> JsValue {
+        self.fd = None;
+        JsValue::UnDefined
+    }
+}
+
+impl JsClassDef for FdGuard {
+    const CLASS_NAME: &'static str = "FdGuard";
+    const CONSTRUCTOR_ARGC: u8 = 1;
+
+    type RefType = FdGuard;
+
+    const FIELDS: &'static [JsClassField<Self::RefType>] = &[];
+
+    const METHODS: &'static [JsClassMethod<Self::RefType>] = &[("release", 0, Self::js_release)];
+
+    unsafe fn mut_class_id_ptr() -> &'static mut u32 {
+        static mut CLASS_ID: u32 = 0;
+        &mut *std::ptr::addr_of_mut!(CLASS_ID)
+    }
+
+    fn constructor_fn(ctx: &mut Context, argv: &[JsValue]) -> Result<Self::RefType, JsValue> {
+        match argv.first() {
+            Some(JsValue::Int(fd)) if *fd >= 0 => Ok(FdGuard {
+                fd: Some(*fd as u32),
+            }),
+            _ => Err(ctx.throw_type_error("fd must be a file descriptor").into()),
+        }
+    }
+
+    fn finalizer(data: &mut Self::RefType, _event_loop: Option<&mut EventLoop>) {
+        if let Some(fd) = data.fd.take() {
+            let _ = unsafe { wasi_fs::fd_close(fd) };
+            eprintln!(
+                "Warning: Closing file descriptor {} on garbage collection",
+                fd
+            );
+        }
+    }
+}
+
 struct FS;
 
 impl ModuleInit for FS {
     fn init_module(ctx: &mut Context, m: &mut JsModuleDef) {
         let stat_s = ctx.wrap_function("statSync", stat_sync);
+        let stat_a = ctx.wrap_function("stat", stat);
         let lstat_s = ctx.wrap_function("lstatSync", lstat_sync);
+        let lstat_a = ctx.wrap_function("lstat", lstat);
         let fstat_s = ctx.wrap_function("fstatSync", fstat_sync);
+        let fstat_a = ctx.wrap_function("fstat", fstat);
         let mkdir_s = ctx.wrap_function("mkdirSync", mkdir_sync);
+        let mkdir_a = ctx.wrap_function("mkdir", mkdir);
         let rmdir_s = ctx.wrap_function("rmdirSync", rmdir_sync);
+        let rmdir_a = ctx.wrap_function("rmdir", rmdir);
         let rm_s = ctx.wrap_function("rmSync", rm_sync);
+        let rm_a = ctx.wrap_function("rm", rm);
         let rename_s = ctx.wrap_function("renameSync", rename_sync);
+        let rename_a = ctx.wrap_function("rename", rename);
         let truncate_s = ctx.wrap_function("truncateSync", truncate_sync);
+        let truncate_a = ctx.wrap_function("truncate", truncate);
         let ftruncate_s = ctx.wrap_function("ftruncateSync", ftruncate_sync);
+        let ftruncate_a = ctx.wrap_function("ftruncate", ftruncate);
         let realpath_s = ctx.wrap_function("realpathSync", realpath_sync);
+        let realpath_a = ctx.wrap_function("realpath", realpath);
         let copy_file_s = ctx.wrap_function("copyFileSync", copy_file_sync);
+        let copy_file_a = ctx.wrap_function("copyFile", copy_file);
         let link_s = ctx.wrap_function("linkSync", link_sync);
+        let link_a = ctx.wrap_function("link", link);
         let symlink_s = ctx.wrap_function("symlinkSync", symlink_sync);
+        let symlink_a = ctx.wrap_function("symlink", symlink);
         let utime_s = ctx.wrap_function("utimeSync", utime_sync);
+        let utime_a = ctx.wrap_function("utime", utime);
         let lutime_s = ctx.wrap_function("lutimeSync", lutime_sync);
         let futime_s = ctx.wrap_function("futimeSync", futime_sync);
+        let futime_a = ctx.wrap_function("futime", futime);
         let fclose_s = ctx.wrap_function("fcloseSync", fclose_sync);
+        let fclose_a = ctx.wrap_function("fclose", fclose);
         let fsync_s = ctx.wrap_function("fsyncSync", fsync_sync);
+        let fsync_a = ctx.wrap_function("fsync", fsync);
         let fdatasync_s = ctx.wrap_function("fdatasyncSync", fdatasync_sync);
+        let fdatasync_a = ctx.wrap_function("fdatasync", fdatasync);
         let fread_s = ctx.wrap_function("freadSync", fread_sync);
         let fread_a = ctx.wrap_function("fread", fread);
         let open_s = ctx.wrap_function("openSync", open_sync);
+        let open_a = ctx.wrap_function("open", open);
         let readlink_s = ctx.wrap_function("readlinkSync", readlink_sync);
+        let readlink_a = ctx.wrap_function("readlink", readlink);
+        let chmod_s = ctx.wrap_function("chmodSync", chmod_sync);
+        let chmod_a = ctx.wrap_function("chmod", chmod);
+        let chown_s = ctx.wrap_function("chownSync", chown_sync);
+        let chown_a = ctx.wrap_function("chown", chown);
+        let umask_f = ctx.wrap_function("umask", umask);
         let fwrite_s = ctx.wrap_function("fwriteSync", fwrite_sync);
         let fwrite_a = ctx.wrap_function("fwrite", fwrite);
         let freaddir_s = ctx.wrap_function("freaddirSync", freaddir_sync);
+        let readdir_s = ctx.wrap_function("readdirSync", readdir_sync);
+        let readdir_a = ctx.wrap_function("readdir", readdir);
+        let cp_s = ctx.wrap_function("cpSync", cp_sync);
+        let cp_a = ctx.wrap_function("cp", cp);
+        let write_file_atomic_s = ctx.wrap_function("writeFileAtomicSync", write_file_atomic_sync);
+        let write_file_atomic_a = ctx.wrap_function("writeFileAtomic", write_file_atomic);
+        let lock_s = ctx.wrap_function("lockSync", lock_sync);
+        let lock_a = ctx.wrap_function("lock", lock);
+        let unlock_s = ctx.wrap_function("unlockSync", unlock_sync);
+        let unlock_a = ctx.wrap_function("unlock", unlock);
+        let touch_lock_s = ctx.wrap_function("touchLockSync", touch_lock_sync);
+        let touch_lock_a = ctx.wrap_function("touchLock", touch_lock);
+        let watch_a = ctx.wrap_function("watch", watch);
+        let watch_file_a = ctx.wrap_function("watchFile", watch_file);
+        register_class::<FsWatcher>(ctx);
+        let file_reader = register_class::<FileReader>(ctx);
+        let file_writer = register_class::<FileWriter>(ctx);
+        let fd_guard = register_class::<FdGuard>(ctx);
         m.add_export("statSync", stat_s.into());
+        m.add_export("stat", stat_a.into());
         m.add_export("lstatSync", lstat_s.into());
+        m.add_export("lstat", lstat_a.into());
         m.add_export("fstatSync", fstat_s.into());
+        m.add_export("fstat", fstat_a.into());
         m.add_export("mkdirSync", mkdir_s.into());
+        m.add_export("mkdir", mkdir_a.into());
         m.add_export("rmdirSync", rmdir_s.into());
+        m.add_export("rmdir", rmdir_a.into());
         m.add_export("rmSync", rm_s.into());
+        m.add_export("rm", rm_a.into());
         m.add_export("renameSync", rename_s.into());
+        m.add_export("rename", rename_a.into());
         m.add_export("truncateSync", truncate_s.into());
+        m.add_export("truncate", truncate_a.into());
         m.add_export("ftruncateSync", ftruncate_s.into());
+        m.add_export("ftruncate", ftruncate_a.into());
         m.add_export("realpathSync", realpath_s.into());
+        m.add_export("realpath", realpath_a.into());
         m.add_export("copyFileSync", copy_file_s.into());
+        m.add_export("copyFile", copy_file_a.into());
         m.add_export("linkSync", link_s.into());
+        m.add_export("link", link_a.into());
         m.add_export("symlinkSync", symlink_s.into());
+        m.add_export("symlink", symlink_a.into());
         m.add_export("utimeSync", utime_s.into());
+        m.add_export("utime", utime_a.into());
         m.add_export("lutimeSync", lutime_s.into());
         m.add_export("futimeSync", futime_s.into());
+        m.add_export("futime", futime_a.into());
         m.add_export("fcloseSync", fclose_s.into());
+        m.add_export("fclose", fclose_a.into());
         m.add_export("fsyncSync", fsync_s.into());
+        m.add_export("fsync", fsync_a.into());
         m.add_export("fdatasyncSync", fdatasync_s.into());
+        m.add_export("fdatasync", fdatasync_a.into());
         m.add_export("freadSync", fread_s.into());
         m.add_export("fread", fread_a.into());
         m.add_export("openSync", open_s.into());
+        m.add_export("open", open_a.into());
         m.add_export("readlinkSync", readlink_s.into());
+        m.add_export("readlink", readlink_a.into());
+        m.add_export("chmodSync", chmod_s.into());
+        m.add_export("chmod", chmod_a.into());
+        m.add_export("chownSync", chown_s.into());
+        m.add_export("chown", chown_a.into());
+        m.add_export("umask", umask_f.into());
         m.add_export("fwriteSync", fwrite_s.into());
         m.add_export("fwrite", fwrite_a.into());
         m.add_export("freaddirSync", freaddir_s.into());
+        m.add_export("readdirSync", readdir_s.into());
+        m.add_export("readdir", readdir_a.into());
+        m.add_export("cpSync", cp_s.into());
+        m.add_export("cp", cp_a.into());
+        m.add_export("writeFileAtomicSync", write_file_atomic_s.into());
+        m.add_export("writeFileAtomic", write_file_atomic_a.into());
+        m.add_export("lockSync", lock_s.into());
+        m.add_export("lock", lock_a.into());
+        m.add_export("unlockSync", unlock_s.into());
+        m.add_export("unlock", unlock_a.into());
+        m.add_export("touchLockSync", touch_lock_s.into());
+        m.add_export("touchLock", touch_lock_a.into());
+        m.add_export("watch", watch_a.into());
+        m.add_export("watchFile", watch_file_a.into());
+        m.add_export(FileReader::CLASS_NAME, file_reader);
+        m.add_export(FileWriter::CLASS_NAME, file_writer);
+        m.add_export(FdGuard::CLASS_NAME, fd_guard);
     }
 }
 
@@ -949,31 +3344,73 @@ pub fn init_module(ctx: &mut Context) {
         FS,
         &[
             "statSync\0",
+            "stat\0",
             "lstatSync\0",
+            "lstat\0",
             "fstatSync\0",
+            "fstat\0",
             "mkdirSync\0",
+            "mkdir\0",
             "rmdirSync\0",
+            "rmdir\0",
             "rmSync\0",
+            "rm\0",
             "renameSync\0",
+            "rename\0",
             "truncateSync\0",
+            "truncate\0",
             "ftruncateSync\0",
+            "ftruncate\0",
             "realpathSync\0",
+            "realpath\0",
             "copyFileSync\0",
+            "copyFile\0",
             "linkSync\0",
+            "link\0",
             "symlinkSync\0",
+            "symlink\0",
             "utimeSync\0",
+            "utime\0",
             "lutimeSync\0",
             "futimeSync\0",
+            "futime\0",
             "fcloseSync\0",
+            "fclose\0",
             "fsyncSync\0",
+            "fsync\0",
             "fdatasyncSync\0",
+            "fdatasync\0",
             "freadSync\0",
             "fread\0",
             "openSync\0",
+            "open\0",
             "readlinkSync\0",
+            "readlink\0",
+            "chmodSync\0",
+            "chmod\0",
+            "chownSync\0",
+            "chown\0",
+            "umask\0",
             "fwriteSync\0",
             "fwrite\0",
             "freaddirSync\0",
+            "readdirSync\0",
+            "readdir\0",
+            "cpSync\0",
+            "cp\0",
+            "writeFileAtomicSync\0",
+            "writeFileAtomic\0",
+            "lockSync\0",
+            "lock\0",
+            "unlockSync\0",
+            "unlock\0",
+            "touchLockSync\0",
+            "touchLock\0",
+            "watch\0",
+            "watchFile\0",
+            "FileReader\0",
+            "FileWriter\0",
+            "FdGuard\0",
         ],
     )
 }
@@ -1,38 +1,322 @@
-use std::{
-    io::{BufRead, Cursor},
-    ops::Index,
-};
+//! Chunked transfer coding (RFC 9112 section 7.1).
+//!
+//! A chunk is `size [; ext]* CRLF data CRLF`. The last chunk has size zero
+//! and is followed by an optional trailer section and an empty line.
 
-use super::ParseError;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+
+use super::parser::{self, Header, Limits, Status};
+use super::{HeaderMap, ParseError};
+
+/// A `name[=value]` chunk extension. Quoted values are unescaped.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ChunkExtension<'b> {
+    pub name: &'b str,
+    pub value: Option<Cow<'b, str>>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Chunk<'b> {
+    Data {
+        data: &'b [u8],
+        extensions: Vec<ChunkExtension<'b>>,
+    },
+    /// The zero sized chunk together with the trailer fields.
+    Last {
+        extensions: Vec<ChunkExtension<'b>>,
+        trailers: Vec<Header<'b>>,
+    },
+}
 
 pub struct HttpChunk;
 
 impl HttpChunk {
+    /// Decodes one chunk and returns its data with the number of bytes it
+    /// took. The last chunk gives an empty slice; its trailers are consumed
+    /// but dropped, use [`HttpChunk::decode`] to keep them.
     pub fn parse(buf: &[u8]) -> Result<(&[u8], usize), ParseError> {
-        let mut c = Cursor::new(buf);
-        let mut header = String::new();
-        let n = c
-            .read_line(&mut header)
-            .map_err(|_| ParseError::InvalidChunk)?;
-        if n == 0 {
-            return Err(ParseError::Pending);
+        match Self::decode(buf, &Limits::default())? {
+            (Chunk::Data { data, .. }, n) => Ok((data, n)),
+            (Chunk::Last { .. }, n) => Ok((&[], n)),
+        }
+    }
+
+    /// Decodes one chunk including extensions and, for the last chunk, the
+    /// trailer section. Returns `ParseError::Pending` until the whole chunk
+    /// is in `buf`.
+    pub fn decode<'b>(buf: &'b [u8], limits: &Limits) -> Result<(Chunk<'b>, usize), ParseError> {
+        let lf = match buf.iter().position(|b| *b == b'\n') {
+            Some(lf) => lf,
+            None if buf.len() > limits.max_line_size => return Err(ParseError::InvalidChunk),
+            None => return Err(ParseError::Pending),
+        };
+        if lf > limits.max_line_size {
+            return Err(ParseError::InvalidChunk);
+        }
+        let mut line = &buf[..lf];
+        if let Some(b'\r') = line.last() {
+            line = &line[..line.len() - 1];
+        }
+        let (len, extensions) = parse_size_line(line)?;
+        let start = lf + 1;
+
+        if len == 0 {
+            return match parser::parse_trailers(&buf[start..], limits)? {
+                Status::Complete((trailers, n)) => Ok((
+                    Chunk::Last {
+                        extensions,
+                        trailers,
+                    },
+                    start + n,
+                )),
+                Status::Partial => Err(ParseError::Pending),
+            };
+        }
+
+        let len = usize::try_from(len).map_err(|_| ParseError::InvalidChunk)?;
+        let end = start.checked_add(len).ok_or(ParseError::InvalidChunk)?;
+        let data = buf.get(start..end).ok_or(ParseError::Pending)?;
+        let n = match &buf[end..] {
+            [] | [b'\r'] => return Err(ParseError::Pending),
+            [b'\n', ..] => 1,
+            [b'\r', b'\n', ..] => 2,
+            _ => return Err(ParseError::InvalidChunk),
+        };
+        Ok((Chunk::Data { data, extensions }, end + n))
+    }
+
+    /// Appends `data` as one chunk. Empty data writes nothing, it would
+    /// otherwise end the body.
+    pub fn encode(data: &[u8], out: &mut Vec<u8>) {
+        if data.is_empty() {
+            return;
+        }
+        out.extend_from_slice(format!("{:x}\r\n", data.len()).as_bytes());
+        out.extend_from_slice(data);
+        out.extend_from_slice(b"\r\n");
+    }
+
+    /// Appends the last chunk followed by `trailers`. Nothing is appended
+    /// when a name is not a token or a value holds CR, LF or other controls.
+    pub fn encode_last(trailers: &HeaderMap, out: &mut Vec<u8>) -> Result<(), ParseError> {
+        for (name, value) in trailers {
+            if !parser::is_token(name.as_bytes()) {
+                return Err(ParseError::InvalidHeaderName);
+            }
+            if !parser::is_field_value(value.as_bytes()) {
+                return Err(ParseError::InvalidHeaderValue);
+            }
+        }
+        out.extend_from_slice(b"0\r\n");
+        for (name, value) in trailers {
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(b": ");
+            out.extend_from_slice(value.as_bytes());
+            out.extend_from_slice(b"\r\n");
+        }
+        out.extend_from_slice(b"\r\n");
+        Ok(())
+    }
+}
+
+fn parse_size_line(line: &[u8]) -> Result<(u64, Vec<ChunkExtension<'_>>), ParseError> {
+    let digits = line.iter().take_while(|b| b.is_ascii_hexdigit()).count();
+    // 16 hex digits already overflow what a body can hold in memory
+    if digits == 0 || digits > 16 {
+        return Err(ParseError::InvalidChunk);
+    }
+    let size = std::str::from_utf8(&line[..digits]).map_err(|_| ParseError::InvalidChunk)?;
+    let len = u64::from_str_radix(size, 16).map_err(|_| ParseError::InvalidChunk)?;
+
+    let mut extensions = vec![];
+    let mut rest = parser::trim_ows(&line[digits..]);
+    while !rest.is_empty() {
+        if rest[0] != b';' {
+            return Err(ParseError::InvalidChunk);
         }
+        rest = parser::trim_ows(&rest[1..]);
+        let name_len = rest
+            .iter()
+            .position(|b| matches!(b, b'=' | b';' | b' ' | b'\t'))
+            .unwrap_or(rest.len());
+        let name = &rest[..name_len];
+        if !parser::is_token(name) {
+            return Err(ParseError::InvalidChunk);
+        }
+        rest = parser::trim_ows(&rest[name_len..]);
+        let mut value = None;
+        if let Some(b'=') = rest.first() {
+            rest = parser::trim_ows(&rest[1..]);
+            let (v, n) = parse_ext_value(rest)?;
+            value = Some(v);
+            rest = parser::trim_ows(&rest[n..]);
+        }
+        extensions.push(ChunkExtension {
+            // tokens are ASCII
+            name: std::str::from_utf8(name).map_err(|_| ParseError::InvalidChunk)?,
+            value,
+        });
+    }
+    Ok((len, extensions))
+}
+
+/// A token or a quoted-string, returns the value and the bytes it took.
+fn parse_ext_value(s: &[u8]) -> Result<(Cow<'_, str>, usize), ParseError> {
+    if let Some(b'"') = s.first() {
+        let mut value = Vec::new();
+        let mut i = 1;
+        while i < s.len() {
+            match s[i] {
+                b'"' => {
+                    let value = parser::decode_value(&value).into_owned();
+                    return Ok((Cow::Owned(value), i + 1));
+                }
+                b'\\' if i + 1 < s.len() => {
+                    value.push(s[i + 1]);
+                    i += 2;
+                }
+                b => {
+                    value.push(b);
+                    i += 1;
+                }
+            }
+        }
+        return Err(ParseError::InvalidChunk);
+    }
+    let n = s
+        .iter()
+        .position(|b| matches!(b, b';' | b' ' | b'\t'))
+        .unwrap_or(s.len());
+    if !parser::is_token(&s[..n]) {
+        return Err(ParseError::InvalidChunk);
+    }
+    Ok((parser::decode_value(&s[..n]), n))
+}
 
-        let len = usize::from_str_radix(header.trim(), 16).map_err(|_| ParseError::InvalidChunk)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let r_buf = buf.get(n..n + len).ok_or(ParseError::Pending)?;
+    #[test]
+    fn test_chunk_parse() {
+        let data = b"5\r\nhello\r\n0\r\n\r\n";
+        let (chunk, n) = HttpChunk::parse(data).unwrap();
+        assert_eq!(b"hello", chunk);
+        assert_eq!(10, n);
+        let (chunk, n) = HttpChunk::parse(&data[n..]).unwrap();
+        assert!(chunk.is_empty());
+        assert_eq!(5, n);
+    }
 
-        c.set_position((n + len) as u64);
+    #[test]
+    fn test_chunk_parse_pending() {
+        assert!(matches!(HttpChunk::parse(b"1"), Err(ParseError::Pending)));
+        assert!(matches!(
+            HttpChunk::parse(b"10\r\nabc"),
+            Err(ParseError::Pending)
+        ));
+        assert!(matches!(
+            HttpChunk::parse(b"3\r\nabc\r"),
+            Err(ParseError::Pending)
+        ));
+        assert!(matches!(
+            HttpChunk::parse(b"3\r\nabcd\r\n"),
+            Err(ParseError::InvalidChunk)
+        ));
+        assert!(matches!(
+            HttpChunk::parse(b"0\r\nExpires: never\r\n"),
+            Err(ParseError::Pending)
+        ));
+    }
 
-        let mut end = header;
-        let end_n = c
-            .read_line(&mut end)
-            .map_err(|_| ParseError::InvalidChunk)?;
+    #[test]
+    fn test_chunk_extensions() {
+        let data = b"5;name=val; q = \"a \\\"b\\\"\" ;flag\r\nhello\r\n";
+        let (chunk, n) = HttpChunk::decode(data, &Limits::default()).unwrap();
+        assert_eq!(data.len(), n);
+        match chunk {
+            Chunk::Data { data, extensions } => {
+                assert_eq!(b"hello", data);
+                let ext: Vec<_> = extensions
+                    .iter()
+                    .map(|e| (e.name, e.value.as_deref()))
+                    .collect();
+                assert_eq!(
+                    vec![
+                        ("name", Some("val")),
+                        ("q", Some("a \"b\"")),
+                        ("flag", None)
+                    ],
+                    ext
+                );
+            }
+            c => panic!("{:?}", c),
+        }
 
-        if end_n == 0 {
-            return Err(ParseError::Pending);
+        for bad in [
+            &b"5;\r\nhello\r\n"[..],
+            b"5 x\r\nhello\r\n",
+            b"5;a=\"b\r\nhello\r\n",
+        ] {
+            assert!(matches!(
+                HttpChunk::parse(bad),
+                Err(ParseError::InvalidChunk)
+            ));
         }
+    }
+
+    #[test]
+    fn test_chunk_trailers() {
+        let data = b"0;end\r\nExpires: never\r\nX-Sum: 42\r\n\r\nnext";
+        let (chunk, n) = HttpChunk::decode(data, &Limits::default()).unwrap();
+        assert_eq!(data.len() - 4, n);
+        match chunk {
+            Chunk::Last {
+                extensions,
+                trailers,
+            } => {
+                assert_eq!("end", extensions[0].name);
+                let trailers = HeaderMap::from(trailers);
+                assert_eq!("never", trailers.get("expires").unwrap());
+                assert_eq!("42", trailers.get("x-sum").unwrap());
+            }
+            c => panic!("{:?}", c),
+        }
+    }
+
+    #[test]
+    fn test_chunk_encode() {
+        let mut out = vec![];
+        HttpChunk::encode(b"hello world", &mut out);
+        HttpChunk::encode(b"", &mut out);
+        let mut trailers = HeaderMap::new();
+        trailers.append("x-sum".to_string(), "42".to_string());
+        HttpChunk::encode_last(&trailers, &mut out).unwrap();
+        assert_eq!(&b"b\r\nhello world\r\n0\r\nx-sum: 42\r\n\r\n"[..], &out[..]);
+
+        let (chunk, n) = HttpChunk::parse(&out).unwrap();
+        assert_eq!(b"hello world", chunk);
+        let (chunk, m) = HttpChunk::parse(&out[n..]).unwrap();
+        assert!(chunk.is_empty());
+        assert_eq!(out.len(), n + m);
+    }
 
-        Ok((r_buf, n + len + end_n))
+    #[test]
+    fn test_chunk_encode_bad_trailers() {
+        let mut out = vec![];
+        let mut trailers = HeaderMap::new();
+        trailers.append("x-sum".to_string(), "42\r\nx-injected: 1".to_string());
+        assert!(matches!(
+            HttpChunk::encode_last(&trailers, &mut out),
+            Err(ParseError::InvalidHeaderValue)
+        ));
+        let mut trailers = HeaderMap::new();
+        trailers.append("x sum".to_string(), "42".to_string());
+        assert!(matches!(
+            HttpChunk::encode_last(&trailers, &mut out),
+            Err(ParseError::InvalidHeaderName)
+        ));
+        assert!(out.is_empty());
     }
 }
@@ -0,0 +1,759 @@
+//! Cookies (RFC 6265): the `Cookie` request header, `Set-Cookie` response
+//! headers and a [`CookieJar`] that keeps what servers set between requests.
+//!
+//! Times are seconds since the Unix epoch. They are passed in by the caller,
+//! the jar itself never reads the clock.
+
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+#[derive(Debug)]
+pub enum CookieError {
+    /// The name is empty or not a token.
+    InvalidName,
+    /// The value contains characters a cookie value cannot carry.
+    InvalidValue,
+    /// The value of the named attribute contains `;` or control characters.
+    InvalidAttribute(&'static str),
+}
+
+impl Display for CookieError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CookieError::InvalidName => f.write_str("invalid cookie name"),
+            CookieError::InvalidValue => f.write_str("invalid cookie value"),
+            CookieError::InvalidAttribute(name) => write!(f, "invalid cookie {}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl SameSite {
+    pub fn parse(s: &str) -> Option<SameSite> {
+        if s.eq_ignore_ascii_case("strict") {
+            Some(SameSite::Strict)
+        } else if s.eq_ignore_ascii_case("lax") {
+            Some(SameSite::Lax)
+        } else if s.eq_ignore_ascii_case("none") {
+            Some(SameSite::None)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        }
+    }
+}
+
+/// A cookie with the attributes of a `Set-Cookie` header.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SetCookie {
+    pub name: String,
+    pub value: String,
+    pub expires: Option<i64>,
+    pub max_age: Option<i64>,
+    pub domain: Option<String>,
+    pub path: Option<String>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<SameSite>,
+    pub partitioned: bool,
+}
+
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+fn is_cookie_value(s: &str) -> bool {
+    let s = match s.strip_prefix('"') {
+        Some(quoted) => match quoted.strip_suffix('"') {
+            Some(inner) => inner,
+            None => return false,
+        },
+        None => s,
+    };
+    s.bytes().all(|b| {
+        b == 0x21
+            || (0x23..=0x2b).contains(&b)
+            || (0x2d..=0x3a).contains(&b)
+            || (0x3c..=0x5b).contains(&b)
+            || (0x5d..=0x7e).contains(&b)
+    })
+}
+
+fn is_attribute_value(s: &str) -> bool {
+    s.bytes().all(|b| b != b';' && !b.is_ascii_control())
+}
+
+impl SetCookie {
+    pub fn new(name: &str, value: &str) -> SetCookie {
+        SetCookie {
+            name: name.to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Parses a `Set-Cookie` header value the way user agents do (RFC 6265
+    /// section 5.2): unknown attributes and malformed attribute values are
+    /// ignored, only a missing `name=` makes the whole header invalid.
+    pub fn parse(header: &str) -> Option<SetCookie> {
+        let mut parts = header.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let mut cookie = SetCookie::new(name, value.trim());
+        for attr in parts {
+            let (key, value) = match attr.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (attr.trim(), ""),
+            };
+            if key.eq_ignore_ascii_case("expires") {
+                if let Some(time) = parse_http_date(value) {
+                    cookie.expires = Some(time);
+                }
+            } else if key.eq_ignore_ascii_case("max-age") {
+                let digits = value.strip_prefix('-').unwrap_or(value);
+                if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+                    // absurdly long values still mean "far away" or "now"
+                    let overflow = if digits.len() == value.len() {
+                        i64::MAX
+                    } else {
+                        i64::MIN
+                    };
+                    cookie.max_age = Some(value.parse().unwrap_or(overflow));
+                }
+            } else if key.eq_ignore_ascii_case("domain") {
+                let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                if !domain.is_empty() {
+                    cookie.domain = Some(domain);
+                }
+            } else if key.eq_ignore_ascii_case("path") {
+                cookie.path = if value.starts_with('/') {
+                    Some(value.to_string())
+                } else {
+                    None
+                };
+            } else if key.eq_ignore_ascii_case("secure") {
+                cookie.secure = true;
+            } else if key.eq_ignore_ascii_case("httponly") {
+                cookie.http_only = true;
+            } else if key.eq_ignore_ascii_case("samesite") {
+                cookie.same_site = SameSite::parse(value);
+            } else if key.eq_ignore_ascii_case("partitioned") {
+                cookie.partitioned = true;
+            }
+        }
+        Some(cookie)
+    }
+
+    /// Checks that the cookie can be serialized without changing meaning.
+    pub fn validate(&self) -> Result<(), CookieError> {
+        if !is_token(&self.name) {
+            return Err(CookieError::InvalidName);
+        }
+        if !is_cookie_value(&self.value) {
+            return Err(CookieError::InvalidValue);
+        }
+        if !self.domain.as_deref().is_none_or(is_attribute_value) {
+            return Err(CookieError::InvalidAttribute("domain"));
+        }
+        if !self.path.as_deref().is_none_or(is_attribute_value) {
+            return Err(CookieError::InvalidAttribute("path"));
+        }
+        Ok(())
+    }
+}
+
+/// The `Set-Cookie` header value, see [`SetCookie::validate`].
+impl Display for SetCookie {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age)?;
+        }
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={}", domain)?;
+        }
+        if let Some(path) = &self.path {
+            write!(f, "; Path={}", path)?;
+        }
+        if let Some(expires) = self.expires {
+            write!(f, "; Expires={}", format_http_date(expires))?;
+        }
+        if self.http_only {
+            f.write_str("; HttpOnly")?;
+        }
+        if self.secure {
+            f.write_str("; Secure")?;
+        }
+        if let Some(same_site) = self.same_site {
+            write!(f, "; SameSite={}", same_site.as_str())?;
+        }
+        if self.partitioned {
+            f.write_str("; Partitioned")?;
+        }
+        Ok(())
+    }
+}
+
+/// The `name=value` pairs of a `Cookie` request header, in order. Quotes
+/// around a value are removed, pairs without `=` are skipped.
+pub fn parse_cookie_header(header: &str) -> Vec<(String, String)> {
+    header
+        .split(';')
+        .filter_map(|pair| {
+            let (name, value) = pair.split_once('=')?;
+            let name = name.trim();
+            if name.is_empty() {
+                return None;
+            }
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+
+// days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// `Sun, 06 Nov 1994 08:49:37 GMT`
+pub fn format_http_date(time: i64) -> String {
+    let days = time.div_euclid(86400);
+    let secs = time.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[days.rem_euclid(7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+// the leading 1 to `max` digits of `token`, which may be followed by
+// anything that is not a digit
+fn leading_digits(token: &str, min: usize, max: usize) -> Option<i64> {
+    let n = token.bytes().take_while(u8::is_ascii_digit).count();
+    if n < min || n > max {
+        return None;
+    }
+    token[..n].parse().ok()
+}
+
+fn parse_time(token: &str) -> Option<(i64, i64, i64)> {
+    let mut fields = token.splitn(3, ':');
+    let hour = fields.next()?;
+    let minute = fields.next()?;
+    let second = fields.next()?;
+    let exact = |s: &str| {
+        if (1..=2).contains(&s.len()) && s.bytes().all(|b| b.is_ascii_digit()) {
+            s.parse().ok()
+        } else {
+            None
+        }
+    };
+    Some((exact(hour)?, exact(minute)?, leading_digits(second, 1, 2)?))
+}
+
+/// Parses the dates found in `Expires` attributes with the lenient
+/// algorithm of RFC 6265 section 5.1.1, which covers IMF-fixdate, RFC 850
+/// and asctime dates alike.
+pub fn parse_http_date(s: &str) -> Option<i64> {
+    let is_delimiter = |c: char| {
+        c == '\t'
+            || (' '..='/').contains(&c)
+            || (';'..='@').contains(&c)
+            || ('['..='`').contains(&c)
+            || ('{'..='~').contains(&c)
+    };
+    let (mut time, mut day, mut month, mut year) = (None, None, None, None);
+    for token in s.split(is_delimiter).filter(|t| !t.is_empty()) {
+        if time.is_none() {
+            if let Some(t) = parse_time(token) {
+                time = Some(t);
+                continue;
+            }
+        }
+        if day.is_none() {
+            if let Some(d) = leading_digits(token, 1, 2) {
+                day = Some(d);
+                continue;
+            }
+        }
+        if month.is_none() && token.len() >= 3 {
+            if let Some(m) = MONTHS
+                .iter()
+                .position(|m| token.get(..3).is_some_and(|t| t.eq_ignore_ascii_case(m)))
+            {
+                month = Some(m as i64 + 1);
+                continue;
+            }
+        }
+        if year.is_none() {
+            if let Some(y) = leading_digits(token, 2, 4) {
+                year = Some(y);
+            }
+        }
+    }
+    let (hour, minute, second) = time?;
+    let (day, month, mut year) = (day?, month?, year?);
+    if (70..=99).contains(&year) {
+        year += 1900;
+    } else if (0..=69).contains(&year) {
+        year += 2000;
+    }
+    if !(1..=31).contains(&day) || year < 1601 || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+/// A cookie held by a [`CookieJar`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredCookie {
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    /// Only sent to `domain` itself, not to its subdomains.
+    pub host_only: bool,
+    pub path: String,
+    /// `None` for session cookies.
+    pub expires: Option<i64>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<SameSite>,
+    pub creation: i64,
+    seq: u64,
+}
+
+impl StoredCookie {
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+}
+
+fn is_ip(host: &str) -> bool {
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+        .is_ok()
+}
+
+/// Whether `host` is `domain` or one of its subdomains.
+pub fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.len() > domain.len()
+            && host.ends_with(domain)
+            && host.as_bytes()[host.len() - domain.len() - 1] == b'.'
+            && !is_ip(host))
+}
+
+/// Whether a cookie for `cookie_path` is sent with a request for `path`.
+pub fn path_match(path: &str, cookie_path: &str) -> bool {
+    path == cookie_path
+        || (path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || path.as_bytes()[cookie_path.len()] == b'/'))
+}
+
+/// The path of a cookie set without a `Path` attribute: the directory of
+/// the request path.
+pub fn default_path(request_path: &str) -> &str {
+    if !request_path.starts_with('/') {
+        return "/";
+    }
+    match request_path.rfind('/') {
+        Some(0) | None => "/",
+        Some(i) => &request_path[..i],
+    }
+}
+
+/// Whether a plain http URL of `host` still counts as a secure context,
+/// which browsers grant to the loopback interface.
+pub fn is_loopback(host: &str) -> bool {
+    host == "localhost"
+        || host.ends_with(".localhost")
+        || host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+/// The cookies a client received, matched against later requests by
+/// domain, path, expiry and the `Secure` flag (RFC 6265 section 5.3 and
+/// 5.4, with the cookie prefixes of its successor draft). `SameSite` is
+/// kept but not enforced, requests made here have no site of their own.
+#[derive(Debug, Default, Clone)]
+pub struct CookieJar {
+    cookies: Vec<StoredCookie>,
+    seq: u64,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores the cookie of a `Set-Cookie` header received in reply to a
+    /// request for `host` and `request_path`. Returns false when the header
+    /// is malformed or the cookie was refused.
+    pub fn set(
+        &mut self,
+        header: &str,
+        host: &str,
+        request_path: &str,
+        secure: bool,
+        now: i64,
+    ) -> bool {
+        match SetCookie::parse(header) {
+            Some(cookie) => self.insert(cookie, host, request_path, secure, now),
+            None => false,
+        }
+    }
+
+    /// Like [`CookieJar::set`] with a parsed cookie. A cookie that is
+    /// already expired removes the one it replaces.
+    pub fn insert(
+        &mut self,
+        cookie: SetCookie,
+        host: &str,
+        request_path: &str,
+        secure: bool,
+        now: i64,
+    ) -> bool {
+        let host = host.to_ascii_lowercase();
+        let (domain, host_only) = match cookie.domain {
+            Some(domain) => {
+                // a bare top level domain would reach every site below it
+                if !domain_match(&host, &domain) || (!domain.contains('.') && domain != host) {
+                    return false;
+                }
+                (domain, false)
+            }
+            None => (host.clone(), true),
+        };
+        let path = match cookie.path {
+            Some(path) => path,
+            None => default_path(request_path).to_string(),
+        };
+        if cookie.secure && !secure {
+            return false;
+        }
+        if cookie.same_site == Some(SameSite::None) && !cookie.secure {
+            return false;
+        }
+        if cookie.name.starts_with("__Secure-") && !cookie.secure {
+            return false;
+        }
+        if cookie.name.starts_with("__Host-") && (!cookie.secure || !host_only || path != "/") {
+            return false;
+        }
+        let expires = match cookie.max_age {
+            Some(max_age) if max_age <= 0 => Some(i64::MIN),
+            Some(max_age) => Some(now.saturating_add(max_age)),
+            None => cookie.expires,
+        };
+
+        let name = cookie.name;
+        let existing = self
+            .cookies
+            .iter()
+            .position(|c| c.name == name && c.domain == domain && c.path == path);
+        let mut creation = now;
+        if let Some(i) = existing {
+            // a plain http response cannot replace a secure cookie
+            if self.cookies[i].secure && !secure {
+                return false;
+            }
+            creation = self.cookies[i].creation;
+            self.cookies.remove(i);
+        }
+        if expires.is_some_and(|expires| expires <= now) {
+            return true;
+        }
+        self.seq += 1;
+        self.cookies.push(StoredCookie {
+            name,
+            value: cookie.value,
+            domain,
+            host_only,
+            path,
+            expires,
+            secure: cookie.secure,
+            http_only: cookie.http_only,
+            same_site: cookie.same_site,
+            creation,
+            seq: self.seq,
+        });
+        true
+    }
+
+    /// The cookies to send with a request for `host` and `path`, longest
+    /// path first and older before newer on the same path.
+    pub fn matches(&self, host: &str, path: &str, secure: bool, now: i64) -> Vec<&StoredCookie> {
+        let host = host.to_ascii_lowercase();
+        let mut found: Vec<&StoredCookie> = self
+            .cookies
+            .iter()
+            .filter(|c| {
+                let domain_ok = if c.host_only {
+                    host == c.domain
+                } else {
+                    domain_match(&host, &c.domain)
+                };
+                domain_ok
+                    && path_match(path, &c.path)
+                    && (secure || !c.secure)
+                    && !c.is_expired(now)
+            })
+            .collect();
+        found.sort_by(|a, b| {
+            b.path
+                .len()
+                .cmp(&a.path.len())
+                .then((a.creation, a.seq).cmp(&(b.creation, b.seq)))
+        });
+        found
+    }
+
+    /// The `Cookie` header for a request, `None` when no cookie matches.
+    pub fn header(&self, host: &str, path: &str, secure: bool, now: i64) -> Option<String> {
+        let cookies = self.matches(host, path, secure, now);
+        if cookies.is_empty() {
+            return None;
+        }
+        let pairs: Vec<String> = cookies
+            .iter()
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect();
+        Some(pairs.join("; "))
+    }
+
+    pub fn remove_expired(&mut self, now: i64) {
+        self.cookies.retain(|c| !c.is_expired(now));
+    }
+
+    pub fn clear(&mut self) {
+        self.cookies.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &StoredCookie> {
+        self.cookies.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cookies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    #[test]
+    fn test_parse_set_cookie() {
+        let cookie = SetCookie::parse(
+            "sid=\"a b\"; Path=/app; Domain=.Example.com; Max-Age=60; \
+             Expires=Wed, 21 Oct 2015 07:28:00 GMT; Secure; HttpOnly; SameSite=lax; Foo=bar",
+        )
+        .unwrap();
+        assert_eq!(cookie.name, "sid");
+        assert_eq!(cookie.value, "\"a b\"");
+        assert_eq!(cookie.path.as_deref(), Some("/app"));
+        assert_eq!(cookie.domain.as_deref(), Some("example.com"));
+        assert_eq!(cookie.max_age, Some(60));
+        assert_eq!(cookie.expires, Some(1445412480));
+        assert!(cookie.secure && cookie.http_only);
+        assert_eq!(cookie.same_site, Some(SameSite::Lax));
+
+        let cookie = SetCookie::parse("a=1; path=relative; max-age=soon; expires=never").unwrap();
+        assert_eq!(
+            (cookie.path, cookie.max_age, cookie.expires),
+            (None, None, None)
+        );
+        assert!(SetCookie::parse("no-equals-sign").is_none());
+        assert!(SetCookie::parse("=value").is_none());
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut cookie = SetCookie::new("id", "42");
+        cookie.path = Some("/".into());
+        cookie.expires = Some(784111777);
+        cookie.max_age = Some(3600);
+        cookie.http_only = true;
+        cookie.same_site = Some(SameSite::Strict);
+        assert_eq!(
+            cookie.to_string(),
+            "id=42; Max-Age=3600; Path=/; Expires=Sun, 06 Nov 1994 08:49:37 GMT; HttpOnly; SameSite=Strict"
+        );
+        assert!(cookie.validate().is_ok());
+        assert!(SetCookie::new("a b", "1").validate().is_err());
+        assert!(SetCookie::new("a", "x;y").validate().is_err());
+        assert!(SetCookie::new("a", "\"quoted\"").validate().is_ok());
+        cookie.path = Some("/;evil".into());
+        assert!(cookie.validate().is_err());
+    }
+
+    #[test]
+    fn test_dates() {
+        for date in [
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+            "6 nov 1994 8:49:37",
+        ] {
+            assert_eq!(parse_http_date(date), Some(784111777), "{}", date);
+        }
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994"), None);
+        assert_eq!(parse_http_date("Sun, 32 Nov 1994 08:49:37 GMT"), None);
+        assert_eq!(format_http_date(0), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(format_http_date(951782400), "Tue, 29 Feb 2000 00:00:00 GMT");
+    }
+
+    #[test]
+    fn test_cookie_header() {
+        assert_eq!(
+            parse_cookie_header("a=1; b=\"two\";c; =x;  d = 4 "),
+            vec![
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "two".to_string()),
+                ("d".to_string(), "4".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_jar_matching() {
+        let mut jar = CookieJar::new();
+        assert!(jar.set("host=1", "www.example.com", "/a/b", false, NOW));
+        assert!(jar.set(
+            "dom=2; Domain=example.com; Path=/",
+            "www.example.com",
+            "/",
+            false,
+            NOW
+        ));
+        assert!(jar.set("deep=3; Path=/a/b", "www.example.com", "/", false, NOW));
+        assert!(jar.set("sec=4; Secure", "www.example.com", "/a", true, NOW));
+        assert!(!jar.set("sec=5; Secure", "www.example.com", "/a", false, NOW));
+        assert!(!jar.set("x=1; Domain=other.com", "www.example.com", "/", false, NOW));
+        assert!(!jar.set("x=1; Domain=com", "www.example.com", "/", false, NOW));
+        assert!(!jar.set(
+            "__Host-x=1; Secure; Domain=example.com; Path=/",
+            "example.com",
+            "/",
+            true,
+            NOW
+        ));
+
+        // `host` defaults to the directory of the request path
+        assert_eq!(
+            jar.header("www.example.com", "/a/b/c", true, NOW)
+                .as_deref(),
+            Some("deep=3; host=1; dom=2; sec=4")
+        );
+        assert_eq!(
+            jar.header("WWW.example.com", "/a", false, NOW).as_deref(),
+            Some("host=1; dom=2")
+        );
+        assert_eq!(
+            jar.header("api.example.com", "/a/bc", false, NOW)
+                .as_deref(),
+            Some("dom=2")
+        );
+        assert_eq!(jar.header("example.org", "/", true, NOW), None);
+    }
+
+    #[test]
+    fn test_jar_expiry() {
+        let mut jar = CookieJar::new();
+        assert!(jar.set("a=1; Max-Age=10", "h", "/", false, NOW));
+        assert!(jar.set(
+            "b=1; Expires=Thu, 01 Jan 1970 00:00:00 GMT",
+            "h",
+            "/",
+            false,
+            NOW
+        ));
+        assert!(jar.set("c=1", "h", "/", false, NOW));
+        assert_eq!(
+            jar.header("h", "/", false, NOW + 5).as_deref(),
+            Some("a=1; c=1")
+        );
+        assert_eq!(
+            jar.header("h", "/", false, NOW + 10).as_deref(),
+            Some("c=1")
+        );
+
+        // replacing keeps the position, expiring removes
+        assert!(jar.set("c=2", "h", "/", false, NOW + 1));
+        assert!(jar.set("a=0; Max-Age=0", "h", "/", false, NOW + 1));
+        assert_eq!(jar.header("h", "/", false, NOW + 1).as_deref(), Some("c=2"));
+        jar.remove_expired(NOW + 20);
+        assert_eq!(jar.len(), 1);
+    }
+
+    #[test]
+    fn test_ip_hosts() {
+        assert!(!domain_match("1.2.3.4", "3.4"));
+        assert!(domain_match("a.b.c", "b.c"));
+        assert!(!domain_match("ab.c", "b.c"));
+        assert!(is_loopback("127.0.0.1") && is_loopback("[::1]") && is_loopback("localhost"));
+        assert!(!is_loopback("example.com"));
+    }
+}
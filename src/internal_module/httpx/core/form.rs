@@ -0,0 +1,688 @@
+//! `multipart/form-data` (RFC 7578) and `application/x-www-form-urlencoded`
+//! bodies.
+//!
+//! [`MultipartParser`] is fed the body as it arrives and turns it into part
+//! events, holding back at most a delimiter's worth of part data.
+//! [`FormReader`] collects the events into fields and moves file parts to
+//! disk once they grow over a threshold, so an upload never has to fit in
+//! memory.
+
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::parser::{self, Limits, Status};
+use super::ParseError;
+
+#[derive(Debug)]
+pub enum FormError {
+    /// The content type has no usable boundary.
+    InvalidBoundary,
+    /// A boundary line or the header section of a part is malformed.
+    InvalidPart(ParseError),
+    /// A part without `Content-Disposition: form-data` and a name.
+    InvalidDisposition,
+    /// The body ended before the closing boundary.
+    Truncated,
+    /// More parts than allowed.
+    TooManyParts,
+    /// A field that is not a file is over the size limit.
+    FieldTooLarge,
+    /// Writing a spilled file part failed.
+    Io(std::io::Error),
+}
+
+impl Display for FormError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormError::InvalidBoundary => f.write_str("missing or invalid multipart boundary"),
+            FormError::InvalidPart(e) => write!(f, "malformed part: {:?}", e),
+            FormError::InvalidDisposition => {
+                f.write_str("part without a form-data Content-Disposition")
+            }
+            FormError::Truncated => f.write_str("unexpected end of form data"),
+            FormError::TooManyParts => f.write_str("too many parts"),
+            FormError::FieldTooLarge => f.write_str("field over the size limit"),
+            FormError::Io(e) => write!(f, "cannot spill file part: {}", e),
+        }
+    }
+}
+
+impl From<ParseError> for FormError {
+    fn from(e: ParseError) -> Self {
+        FormError::InvalidPart(e)
+    }
+}
+
+/// Returns the `boundary` parameter of a `multipart/form-data` content type.
+pub fn boundary(content_type: &str) -> Option<String> {
+    let (essence, params) = content_type.split_once(';')?;
+    if !essence.trim().eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    let (_, value) = parse_params(params)
+        .into_iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("boundary"))?;
+    // RFC 2046 section 5.1.1
+    let valid = (1..=70).contains(&value.len())
+        && !value.ends_with(' ')
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"'()+_,-./:=? ".contains(&b));
+    if valid {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// Splits `; name=value` parameters, unquoting quoted values.
+fn parse_params(s: &str) -> Vec<(String, String)> {
+    let mut params = vec![];
+    let mut rest = s;
+    loop {
+        rest = rest.trim_start_matches([';', ' ', '\t']);
+        if rest.is_empty() {
+            return params;
+        }
+        let eq = rest.find(['=', ';']).unwrap_or(rest.len());
+        let name = rest[..eq].trim().to_string();
+        rest = &rest[eq..];
+        if !rest.starts_with('=') {
+            params.push((name, String::new()));
+            continue;
+        }
+        rest = rest[1..].trim_start();
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            let mut end = quoted.len();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => {
+                        if let Some((_, c)) = chars.next() {
+                            value.push(c);
+                        }
+                    }
+                    '"' => {
+                        end = i + 1;
+                        break;
+                    }
+                    c => value.push(c),
+                }
+            }
+            params.push((name, value));
+            rest = &quoted[end..];
+        } else {
+            let end = rest.find(';').unwrap_or(rest.len());
+            params.push((name, rest[..end].trim().to_string()));
+            rest = &rest[end..];
+        }
+    }
+}
+
+fn percent_decode(s: &[u8], plus_as_space: bool) -> Vec<u8> {
+    let hex = |b: u8| (b as char).to_digit(16).map(|d| d as u8);
+    let mut out = Vec::with_capacity(s.len());
+    let mut i = 0;
+    while i < s.len() {
+        match s[i] {
+            b'+' if plus_as_space => out.push(b' '),
+            b'%' if i + 2 < s.len() => match (hex(s[i + 1]), hex(s[i + 2])) {
+                (Some(h), Some(l)) => {
+                    out.push(h << 4 | l);
+                    i += 2;
+                }
+                _ => out.push(b'%'),
+            },
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    out
+}
+
+/// Decodes an `application/x-www-form-urlencoded` body into name/value pairs
+/// the way the URL standard does: `+` is a space, bad escapes are kept as
+/// they are and invalid UTF-8 becomes U+FFFD.
+pub fn parse_urlencoded(input: &[u8]) -> Vec<(String, String)> {
+    input
+        .split(|b| *b == b'&')
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            let (name, value) = match s.iter().position(|b| *b == b'=') {
+                Some(eq) => (&s[..eq], &s[eq + 1..]),
+                None => (s, &s[s.len()..]),
+            };
+            (
+                String::from_utf8_lossy(&percent_decode(name, true)).into_owned(),
+                String::from_utf8_lossy(&percent_decode(value, true)).into_owned(),
+            )
+        })
+        .collect()
+}
+
+/// Undoes the escaping of `"`, CR and LF that browsers apply to names and
+/// filenames.
+fn unescape_name(s: &str) -> String {
+    s.replace("%0A", "\n")
+        .replace("%0D", "\r")
+        .replace("%22", "\"")
+}
+
+/// The header section of a part.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PartHead {
+    pub name: String,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub headers: Vec<(String, String)>,
+}
+
+impl PartHead {
+    fn new(headers: Vec<(String, String)>) -> Result<Self, FormError> {
+        let disposition = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-disposition"))
+            .ok_or(FormError::InvalidDisposition)?;
+        let mut params = disposition.1.splitn(2, ';');
+        if !params
+            .next()
+            .unwrap_or("")
+            .trim()
+            .eq_ignore_ascii_case("form-data")
+        {
+            return Err(FormError::InvalidDisposition);
+        }
+        let params = parse_params(params.next().unwrap_or(""));
+        let param = |key: &str| {
+            params
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(key))
+                .map(|(_, value)| value.clone())
+        };
+        let name = unescape_name(&param("name").ok_or(FormError::InvalidDisposition)?);
+        // RFC 5987 `filename*=UTF-8''...` wins over the plain parameter
+        let filename = param("filename*")
+            .and_then(|v| {
+                let (charset, rest) = v.split_once('\'')?;
+                let (_, encoded) = rest.split_once('\'')?;
+                if !charset.eq_ignore_ascii_case("utf-8") {
+                    return None;
+                }
+                String::from_utf8(percent_decode(encoded.as_bytes(), false)).ok()
+            })
+            .or_else(|| param("filename").map(|v| unescape_name(&v)));
+        let content_type = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            .map(|(_, value)| value.clone());
+        Ok(PartHead {
+            name,
+            filename,
+            content_type,
+            headers,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum MultipartEvent {
+    /// A part starts.
+    Part(PartHead),
+    /// More bytes of the current part.
+    Data(Vec<u8>),
+    /// The current part is complete.
+    PartEnd,
+    /// The closing boundary, anything after it is ignored.
+    Done,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum State {
+    Preamble,
+    Boundary,
+    Headers,
+    Body,
+    BodyEnd,
+    Epilogue,
+}
+
+/// Incremental `multipart/form-data` parser. Feed it with
+/// [`MultipartParser::push`] and drain [`MultipartParser::next`] until it
+/// returns `None`.
+pub struct MultipartParser {
+    /// `CRLF--boundary`, the delimiter that ends a part.
+    delimiter: Vec<u8>,
+    buf: Vec<u8>,
+    state: State,
+    limits: Limits,
+}
+
+impl MultipartParser {
+    pub fn new(boundary: &str, limits: Limits) -> Self {
+        MultipartParser {
+            delimiter: format!("\r\n--{}", boundary).into_bytes(),
+            buf: vec![],
+            state: State::Preamble,
+            limits,
+        }
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        if self.state != State::Epilogue {
+            self.buf.extend_from_slice(data);
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.state == State::Epilogue
+    }
+
+    /// The next event, or `None` when more input is needed.
+    pub fn next(&mut self) -> Result<Option<MultipartEvent>, FormError> {
+        loop {
+            match self.state {
+                State::Preamble => {
+                    // the first boundary may start the body without a CRLF
+                    let dash_boundary = &self.delimiter[2..];
+                    let found = find(&self.buf, dash_boundary)
+                        .filter(|&i| i == 0 || self.buf[i - 1] == b'\n');
+                    match found {
+                        Some(i) => {
+                            self.buf.drain(..i + dash_boundary.len());
+                            self.state = State::Boundary;
+                        }
+                        None => {
+                            let keep = dash_boundary.len() + 1;
+                            if self.buf.len() > keep {
+                                self.buf.drain(..self.buf.len() - keep);
+                            }
+                            return Ok(None);
+                        }
+                    }
+                }
+                State::Boundary => {
+                    if self.buf.len() < 2 {
+                        return Ok(None);
+                    }
+                    if self.buf.starts_with(b"--") {
+                        self.buf = vec![];
+                        self.state = State::Epilogue;
+                        return Ok(Some(MultipartEvent::Done));
+                    }
+                    // transport padding may follow the boundary
+                    let lf = match self.buf.iter().position(|b| *b == b'\n') {
+                        Some(lf) => lf,
+                        None if self.buf.len() > self.limits.max_line_size => {
+                            return Err(ParseError::InvalidHeaders.into())
+                        }
+                        None => return Ok(None),
+                    };
+                    let line = &self.buf[..lf];
+                    let line = line.strip_suffix(b"\r").unwrap_or(line);
+                    if !line.iter().all(|b| *b == b' ' || *b == b'\t') {
+                        return Err(ParseError::InvalidHeaders.into());
+                    }
+                    self.buf.drain(..lf + 1);
+                    self.state = State::Headers;
+                }
+                State::Headers => {
+                    let (head, n) = match parser::parse_trailers(&self.buf, &self.limits)? {
+                        Status::Complete((headers, n)) => {
+                            let headers = headers
+                                .into_iter()
+                                .map(|h| (h.name.to_string(), h.value.into_owned()))
+                                .collect();
+                            (PartHead::new(headers)?, n)
+                        }
+                        Status::Partial => return Ok(None),
+                    };
+                    self.buf.drain(..n);
+                    self.state = State::Body;
+                    return Ok(Some(MultipartEvent::Part(head)));
+                }
+                State::Body => match find(&self.buf, &self.delimiter) {
+                    Some(i) => {
+                        let data: Vec<u8> = self.buf.drain(..i).collect();
+                        self.buf.drain(..self.delimiter.len());
+                        self.state = State::BodyEnd;
+                        if !data.is_empty() {
+                            return Ok(Some(MultipartEvent::Data(data)));
+                        }
+                    }
+                    None => {
+                        // the tail may be the start of the delimiter
+                        let safe = self.buf.len().saturating_sub(self.delimiter.len() - 1);
+                        if safe == 0 {
+                            return Ok(None);
+                        }
+                        let data = self.buf.drain(..safe).collect();
+                        return Ok(Some(MultipartEvent::Data(data)));
+                    }
+                },
+                State::BodyEnd => {
+                    self.state = State::Boundary;
+                    return Ok(Some(MultipartEvent::PartEnd));
+                }
+                State::Epilogue => return Ok(None),
+            }
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() || haystack.len() < needle.len() {
+        return None;
+    }
+    let first = needle[0];
+    (0..=haystack.len() - needle.len())
+        .find(|&i| haystack[i] == first && &haystack[i..i + needle.len()] == needle)
+}
+
+#[derive(Debug, Clone)]
+pub struct FormOptions {
+    /// File parts larger than this are written to `upload_dir`.
+    pub spill_threshold: usize,
+    pub upload_dir: PathBuf,
+    /// Size limit of a field that is not a file.
+    pub max_field_size: usize,
+    pub max_parts: usize,
+    /// Limits of the header section of each part.
+    pub limits: Limits,
+}
+
+impl Default for FormOptions {
+    fn default() -> Self {
+        FormOptions {
+            spill_threshold: 1024 * 1024,
+            upload_dir: std::env::temp_dir(),
+            max_field_size: 1024 * 1024,
+            max_parts: 1000,
+            limits: Limits {
+                max_head_size: 16 * 1024,
+                max_line_size: 8 * 1024,
+                max_headers: 32,
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FieldValue {
+    Memory(Vec<u8>),
+    /// A file part written to `path`.
+    Spilled {
+        path: PathBuf,
+        size: u64,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FormField {
+    pub head: PartHead,
+    pub value: FieldValue,
+}
+
+enum Sink {
+    Memory(Vec<u8>),
+    File(File, PathBuf, u64),
+}
+
+/// Collects a multipart body into [`FormField`]s.
+///
+/// Spilled files belong to the caller once [`FormReader::finish`] returned
+/// them; when the body fails to parse they are removed again.
+pub struct FormReader {
+    parser: MultipartParser,
+    options: FormOptions,
+    fields: Vec<FormField>,
+    current: Option<(PartHead, Sink)>,
+    spilled: Vec<PathBuf>,
+}
+
+impl FormReader {
+    pub fn new(boundary: &str, options: FormOptions) -> Self {
+        FormReader {
+            parser: MultipartParser::new(boundary, options.limits),
+            options,
+            fields: vec![],
+            current: None,
+            spilled: vec![],
+        }
+    }
+
+    pub fn push(&mut self, data: &[u8]) -> Result<(), FormError> {
+        self.parser.push(data);
+        while let Some(event) = self.parser.next()? {
+            match event {
+                MultipartEvent::Part(head) => {
+                    if self.fields.len() >= self.options.max_parts {
+                        return Err(FormError::TooManyParts);
+                    }
+                    self.current = Some((head, Sink::Memory(vec![])));
+                }
+                MultipartEvent::Data(data) => self.write(&data)?,
+                MultipartEvent::PartEnd => {
+                    if let Some((head, sink)) = self.current.take() {
+                        let value = match sink {
+                            Sink::Memory(data) => FieldValue::Memory(data),
+                            Sink::File(mut file, path, size) => {
+                                file.flush().map_err(FormError::Io)?;
+                                FieldValue::Spilled { path, size }
+                            }
+                        };
+                        self.fields.push(FormField { head, value });
+                    }
+                }
+                MultipartEvent::Done => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), FormError> {
+        let (head, sink) = match self.current.as_mut() {
+            Some(current) => current,
+            None => return Ok(()),
+        };
+        match sink {
+            Sink::Memory(buf) if buf.len() + data.len() > self.options.spill_threshold => {
+                if head.filename.is_none() {
+                    if buf.len() + data.len() > self.options.max_field_size {
+                        return Err(FormError::FieldTooLarge);
+                    }
+                    buf.extend_from_slice(data);
+                    return Ok(());
+                }
+                let (mut file, path) = create_upload(&self.options.upload_dir)?;
+                self.spilled.push(path.clone());
+                file.write_all(buf).map_err(FormError::Io)?;
+                file.write_all(data).map_err(FormError::Io)?;
+                let size = (buf.len() + data.len()) as u64;
+                *sink = Sink::File(file, path, size);
+            }
+            Sink::Memory(buf) => {
+                if head.filename.is_none() && buf.len() + data.len() > self.options.max_field_size {
+                    return Err(FormError::FieldTooLarge);
+                }
+                buf.extend_from_slice(data);
+            }
+            Sink::File(file, _, size) => {
+                file.write_all(data).map_err(FormError::Io)?;
+                *size += data.len() as u64;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the fields once the closing boundary was seen.
+    pub fn finish(mut self) -> Result<Vec<FormField>, FormError> {
+        if !self.parser.is_done() {
+            return Err(FormError::Truncated);
+        }
+        self.spilled.clear();
+        Ok(std::mem::take(&mut self.fields))
+    }
+}
+
+impl Drop for FormReader {
+    fn drop(&mut self) {
+        for path in &self.spilled {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+fn create_upload(dir: &Path) -> Result<(File, PathBuf), FormError> {
+    loop {
+        let mut id = [0u8; 8];
+        getrandom::getrandom(&mut id)
+            .map_err(|e| FormError::Io(std::io::Error::other(e.to_string())))?;
+        let path = dir.join(format!("upload-{:016x}", u64::from_le_bytes(id)));
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((file, path)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(FormError::Io(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = b"preamble\r\n--XyZ\r\n\
+        Content-Disposition: form-data; name=\"ti%22tle\"\r\n\r\n\
+        hello\r\n--XyZ\r\n\
+        Content-Disposition: form-data; name=\"doc\"; filename=\"a \\\"b\\\".txt\"\r\n\
+        Content-Type: text/plain\r\n\r\n\
+        line one\r\nline two\r\n--XyZ--\r\nepilogue";
+
+    fn collect(chunk: usize) -> Vec<MultipartEvent> {
+        let mut parser = MultipartParser::new("XyZ", Limits::default());
+        let mut events = vec![];
+        for piece in BODY.chunks(chunk) {
+            parser.push(piece);
+            while let Some(event) = parser.next().unwrap() {
+                // merge data events, their split depends on the chunk size
+                match (events.last_mut(), event) {
+                    (Some(MultipartEvent::Data(prev)), MultipartEvent::Data(more)) => {
+                        prev.extend(more)
+                    }
+                    (_, event) => events.push(event),
+                }
+            }
+        }
+        assert!(parser.is_done());
+        events
+    }
+
+    #[test]
+    fn test_multipart_events() {
+        for chunk in [1, 2, 3, 7, BODY.len()] {
+            let events = collect(chunk);
+            assert_eq!(events.len(), 7, "chunk size {}", chunk);
+            match &events[0] {
+                MultipartEvent::Part(head) => {
+                    assert_eq!(head.name, "ti\"tle");
+                    assert_eq!(head.filename, None);
+                }
+                e => panic!("{:?}", e),
+            }
+            assert_eq!(events[1], MultipartEvent::Data(b"hello".to_vec()));
+            assert_eq!(events[2], MultipartEvent::PartEnd);
+            match &events[3] {
+                MultipartEvent::Part(head) => {
+                    assert_eq!(head.name, "doc");
+                    assert_eq!(head.filename.as_deref(), Some("a \"b\".txt"));
+                    assert_eq!(head.content_type.as_deref(), Some("text/plain"));
+                }
+                e => panic!("{:?}", e),
+            }
+            assert_eq!(
+                events[4],
+                MultipartEvent::Data(b"line one\r\nline two".to_vec())
+            );
+            assert_eq!(events[5], MultipartEvent::PartEnd);
+            assert_eq!(events[6], MultipartEvent::Done);
+        }
+    }
+
+    #[test]
+    fn test_multipart_errors() {
+        let mut parser = MultipartParser::new("b", Limits::default());
+        parser.push(b"--b\r\nContent-Type: text/plain\r\n\r\nx");
+        assert!(matches!(parser.next(), Err(FormError::InvalidDisposition)));
+
+        let mut parser = MultipartParser::new("b", Limits::default());
+        parser.push(b"--bjunk\r\n");
+        assert!(matches!(parser.next(), Err(FormError::InvalidPart(_))));
+
+        let reader = FormReader::new("b", FormOptions::default());
+        assert!(matches!(reader.finish(), Err(FormError::Truncated)));
+    }
+
+    #[test]
+    fn test_form_reader_spills_files() {
+        let dir = std::env::temp_dir();
+        let options = FormOptions {
+            spill_threshold: 4,
+            max_field_size: 8,
+            upload_dir: dir.clone(),
+            ..FormOptions::default()
+        };
+        let body = b"--b\r\nContent-Disposition: form-data; name=\"f\"; filename=\"x\"\r\n\r\n\
+            0123456789\r\n--b\r\nContent-Disposition: form-data; name=\"s\"\r\n\r\nsmall\r\n--b--";
+        let mut reader = FormReader::new("b", options.clone());
+        for piece in body.chunks(5) {
+            reader.push(piece).unwrap();
+        }
+        let fields = reader.finish().unwrap();
+        assert_eq!(fields.len(), 2);
+        match &fields[0].value {
+            FieldValue::Spilled { path, size } => {
+                assert_eq!(*size, 10);
+                assert_eq!(std::fs::read(path).unwrap(), b"0123456789");
+                std::fs::remove_file(path).unwrap();
+            }
+            v => panic!("{:?}", v),
+        }
+        assert_eq!(fields[1].value, FieldValue::Memory(b"small".to_vec()));
+
+        // a field that is not a file is never spilled
+        let mut reader = FormReader::new("b", options);
+        let body = b"--b\r\nContent-Disposition: form-data; name=\"s\"\r\n\r\n0123456789\r\n--b--";
+        assert!(matches!(reader.push(body), Err(FormError::FieldTooLarge)));
+    }
+
+    #[test]
+    fn test_boundary() {
+        assert_eq!(
+            boundary("multipart/form-data; boundary=abc").as_deref(),
+            Some("abc")
+        );
+        assert_eq!(
+            boundary("Multipart/Form-Data; charset=utf-8; boundary=\"a b:c\"").as_deref(),
+            Some("a b:c")
+        );
+        assert_eq!(boundary("multipart/form-data"), None);
+        assert_eq!(boundary("text/plain; boundary=abc"), None);
+        assert_eq!(boundary("multipart/form-data; boundary=\"\""), None);
+    }
+
+    #[test]
+    fn test_parse_urlencoded() {
+        assert_eq!(
+            parse_urlencoded(b"a=1&b=x+y%21&&c&d=%zz&%C3%A9=%FF"),
+            vec![
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "x y!".to_string()),
+                ("c".to_string(), "".to_string()),
+                ("d".to_string(), "%zz".to_string()),
+                ("\u{e9}".to_string(), "\u{fffd}".to_string()),
+            ]
+        );
+    }
+}
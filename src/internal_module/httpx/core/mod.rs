@@ -3,8 +3,12 @@ use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
 pub mod chunk;
+pub mod cookie;
+pub mod form;
+pub mod parser;
 pub mod request;
 pub mod response;
+pub mod websocket;
 
 /// Http method
 #[derive(Debug, PartialEq, Clone)]
@@ -92,6 +96,103 @@ pub enum ParseError {
     InvalidHeaders,
     /// Invalid chunk data.
     InvalidChunk,
+    /// Header field name is not a token.
+    InvalidHeaderName,
+    /// Header field value holds control characters.
+    InvalidHeaderValue,
+    /// Malformed or conflicting `Content-Length`.
+    InvalidContentLength,
+    /// Unusable `Transfer-Encoding`.
+    InvalidTransferEncoding,
+    /// Both `Content-Length` and `Transfer-Encoding` are present.
+    AmbiguousLength,
+    /// The head or one of its lines is over the size limit.
+    HeadersTooLarge,
+    /// More header fields than allowed.
+    TooManyHeaders,
     /// Pending
     Pending,
 }
+
+/// Header fields in the order they were received, names compare case
+/// insensitively and repeated fields are all kept.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct HeaderMap(Vec<(String, String)>);
+
+impl HeaderMap {
+    pub fn new() -> Self {
+        HeaderMap(Vec::new())
+    }
+
+    /// The first value of `name`.
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.0
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a String> {
+        self.0
+            .iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Replaces every field called `name`.
+    pub fn insert(&mut self, name: String, value: String) {
+        self.remove(&name);
+        self.0.push((name, value));
+    }
+
+    pub fn append(&mut self, name: String, value: String) {
+        self.0.push((name, value));
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.0.retain(|(k, _)| !k.eq_ignore_ascii_case(name));
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.0.iter().map(|(k, v)| (k, v))
+    }
+}
+
+impl<'a> IntoIterator for &'a HeaderMap {
+    type Item = (&'a String, &'a String);
+    type IntoIter = std::iter::Map<
+        std::slice::Iter<'a, (String, String)>,
+        fn(&'a (String, String)) -> (&'a String, &'a String),
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter().map(|(k, v)| (k, v))
+    }
+}
+
+impl<'b> From<Vec<parser::Header<'b>>> for HeaderMap {
+    fn from(headers: Vec<parser::Header<'b>>) -> Self {
+        HeaderMap(
+            headers
+                .into_iter()
+                .map(|h| (h.name.to_string(), h.value.into_owned()))
+                .collect(),
+        )
+    }
+}
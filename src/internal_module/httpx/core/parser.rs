@@ -0,0 +1,655 @@
+//! Incremental HTTP/1.1 head parser.
+//!
+//! The parser borrows from the input buffer and only allocates when a value
+//! has to be rewritten (obs-fold, non UTF-8 bytes). It can be called again
+//! with a longer buffer every time more data arrives: until the end of the
+//! head is seen it returns [`Status::Partial`] without inspecting the lines.
+//!
+//! Message framing follows RFC 9112 section 6.3 and refuses the ambiguous
+//! cases that allow request smuggling: `Content-Length` together with
+//! `Transfer-Encoding`, conflicting `Content-Length` values, a request whose
+//! final transfer coding is not `chunked`, and whitespace before the colon of
+//! a header name.
+
+use super::{ParseError, Version};
+use std::borrow::Cow;
+
+/// Upper bounds enforced while parsing a head.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Size of the request/status line plus all header lines.
+    pub max_head_size: usize,
+    /// Size of any single line.
+    pub max_line_size: usize,
+    /// Number of header fields.
+    pub max_headers: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_head_size: 64 * 1024,
+            max_line_size: 16 * 1024,
+            max_headers: 128,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Status<T> {
+    Complete(T),
+    Partial,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Header<'b> {
+    pub name: &'b str,
+    pub value: Cow<'b, str>,
+}
+
+/// How the body following a head is delimited.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BodyKind {
+    /// No body at all.
+    Empty,
+    /// Exactly this many bytes.
+    Length(u64),
+    /// Chunked transfer coding.
+    Chunked,
+    /// Everything until the connection is closed (responses only).
+    UntilClose,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RequestHead<'b> {
+    pub method: &'b str,
+    pub target: &'b str,
+    pub version: Version,
+    pub headers: Vec<Header<'b>>,
+    pub body: BodyKind,
+    /// Bytes taken by the head, including the empty line ending it.
+    pub head_len: usize,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResponseHead<'b> {
+    pub version: Version,
+    pub status: u16,
+    pub reason: Cow<'b, str>,
+    pub headers: Vec<Header<'b>>,
+    pub body: BodyKind,
+    pub head_len: usize,
+}
+
+pub fn parse_request<'b>(
+    buf: &'b [u8],
+    limits: &Limits,
+) -> Result<Status<RequestHead<'b>>, ParseError> {
+    // a server should ignore empty lines received before the request line
+    let skip = buf
+        .iter()
+        .take_while(|b| **b == b'\r' || **b == b'\n')
+        .count();
+    let (lines, head_len) = match split_head(&buf[skip..], limits)? {
+        Status::Complete(r) => r,
+        Status::Partial => return Ok(Status::Partial),
+    };
+
+    let (method, target, version) = parse_request_line(lines[0])?;
+    let headers = parse_headers(&lines[1..], limits)?;
+    let body = request_body(version, &headers)?;
+
+    Ok(Status::Complete(RequestHead {
+        method,
+        target,
+        version,
+        headers,
+        body,
+        head_len: skip + head_len,
+    }))
+}
+
+/// `head_request` tells whether the response answers a `HEAD` request, which
+/// never has a body whatever the headers say.
+pub fn parse_response<'b>(
+    buf: &'b [u8],
+    limits: &Limits,
+    head_request: bool,
+) -> Result<Status<ResponseHead<'b>>, ParseError> {
+    let (lines, head_len) = match split_head(buf, limits)? {
+        Status::Complete(r) => r,
+        Status::Partial => return Ok(Status::Partial),
+    };
+
+    let (version, status, reason) = parse_status_line(lines[0])?;
+    let headers = parse_headers(&lines[1..], limits)?;
+    let body = response_body(status, head_request, &headers)?;
+
+    Ok(Status::Complete(ResponseHead {
+        version,
+        status,
+        reason,
+        headers,
+        body,
+        head_len,
+    }))
+}
+
+/// Parses the trailer section that follows the last chunk of a chunked body:
+/// header lines up to and including an empty line. Returns the fields and
+/// the number of bytes they took.
+pub fn parse_trailers<'b>(
+    buf: &'b [u8],
+    limits: &Limits,
+) -> Result<Status<(Vec<Header<'b>>, usize)>, ParseError> {
+    match buf {
+        [] | [b'\r'] => return Ok(Status::Partial),
+        [b'\n', ..] => return Ok(Status::Complete((vec![], 1))),
+        [b'\r', b'\n', ..] => return Ok(Status::Complete((vec![], 2))),
+        _ => {}
+    }
+    let (lines, len) = match split_head(buf, limits)? {
+        Status::Complete(r) => r,
+        Status::Partial => return Ok(Status::Partial),
+    };
+    Ok(Status::Complete((parse_headers(&lines, limits)?, len)))
+}
+
+/// The lines of a head and its length with the blank line.
+type HeadLines<'b> = (Vec<&'b [u8]>, usize);
+
+/// Splits the head into lines without their line endings. Lines end with
+/// CRLF or a bare LF; a CR anywhere else is an error.
+fn split_head<'b>(buf: &'b [u8], limits: &Limits) -> Result<Status<HeadLines<'b>>, ParseError> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    loop {
+        let rest = &buf[start..];
+        let lf = match rest.iter().position(|b| *b == b'\n') {
+            Some(lf) => lf,
+            None => {
+                if rest.len() > limits.max_line_size || buf.len() > limits.max_head_size {
+                    return Err(ParseError::HeadersTooLarge);
+                }
+                return Ok(Status::Partial);
+            }
+        };
+        let mut line = &rest[..lf];
+        if let Some(b'\r') = line.last() {
+            line = &line[..line.len() - 1];
+        }
+        if line.contains(&b'\r') {
+            return Err(ParseError::InvalidHeaders);
+        }
+        if line.len() > limits.max_line_size {
+            return Err(ParseError::HeadersTooLarge);
+        }
+        start += lf + 1;
+        if start > limits.max_head_size {
+            return Err(ParseError::HeadersTooLarge);
+        }
+        if line.is_empty() {
+            if lines.is_empty() {
+                return Err(ParseError::InvalidHeaders);
+            }
+            return Ok(Status::Complete((lines, start)));
+        }
+        lines.push(line);
+    }
+}
+
+fn is_tchar(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+pub(super) fn is_token(s: &[u8]) -> bool {
+    !s.is_empty() && s.iter().all(|b| is_tchar(*b))
+}
+
+/// Field values may hold HTAB, SP, visible ASCII and obs-text.
+pub(super) fn is_field_value(s: &[u8]) -> bool {
+    s.iter().all(|b| *b == b'\t' || (*b >= b' ' && *b != 0x7f))
+}
+
+fn as_str(s: &[u8]) -> &str {
+    // only called on bytes already checked to be ASCII
+    std::str::from_utf8(s).unwrap_or_default()
+}
+
+/// Values are usually ASCII, obs-text that is not UTF-8 is read as latin1.
+pub(super) fn decode_value(s: &[u8]) -> Cow<'_, str> {
+    match std::str::from_utf8(s) {
+        Ok(s) => Cow::Borrowed(s),
+        Err(_) => Cow::Owned(s.iter().map(|b| *b as char).collect()),
+    }
+}
+
+pub(super) fn trim_ows(mut s: &[u8]) -> &[u8] {
+    while let Some(b' ' | b'\t') = s.first() {
+        s = &s[1..];
+    }
+    while let Some(b' ' | b'\t') = s.last() {
+        s = &s[..s.len() - 1];
+    }
+    s
+}
+
+fn parse_version(s: &[u8]) -> Result<Version, ParseError> {
+    match s {
+        b"HTTP/1.1" => Ok(Version::V1_1),
+        b"HTTP/1.0" => Ok(Version::V1_0),
+        _ => Err(ParseError::InvalidVersion),
+    }
+}
+
+fn parse_request_line(line: &[u8]) -> Result<(&str, &str, Version), ParseError> {
+    let mut parts = line.split(|b| *b == b' ');
+    let method = parts.next().ok_or(ParseError::InvalidRequestLine)?;
+    let target = parts.next().ok_or(ParseError::InvalidRequestLine)?;
+    let version = parts.next().ok_or(ParseError::InvalidRequestLine)?;
+    if parts.next().is_some() {
+        return Err(ParseError::InvalidRequestLine);
+    }
+    if !is_token(method) {
+        return Err(ParseError::InvalidMethod);
+    }
+    if target.is_empty() || !target.iter().all(|b| b.is_ascii_graphic()) {
+        return Err(ParseError::InvalidUrl);
+    }
+    Ok((as_str(method), as_str(target), parse_version(version)?))
+}
+
+fn parse_status_line(line: &[u8]) -> Result<(Version, u16, Cow<'_, str>), ParseError> {
+    let mut parts = line.splitn(3, |b| *b == b' ');
+    let version = parse_version(parts.next().ok_or(ParseError::InvalidStatusLine)?)?;
+    let code = parts.next().ok_or(ParseError::InvalidStatusLine)?;
+    if code.len() != 3 || !code.iter().all(|b| b.is_ascii_digit()) {
+        return Err(ParseError::InvalidStatusCode);
+    }
+    let status = as_str(code)
+        .parse()
+        .map_err(|_| ParseError::InvalidStatusCode)?;
+    let reason = parts.next().unwrap_or_default();
+    if !is_field_value(reason) {
+        return Err(ParseError::InvalidStatusLine);
+    }
+    Ok((version, status, decode_value(reason)))
+}
+
+fn parse_headers<'b>(lines: &[&'b [u8]], limits: &Limits) -> Result<Vec<Header<'b>>, ParseError> {
+    let mut headers: Vec<Header<'b>> = Vec::with_capacity(lines.len());
+    for line in lines {
+        if let Some(b' ' | b'\t') = line.first() {
+            // obs-fold, the continuation is joined with a single space
+            let last = headers.last_mut().ok_or(ParseError::InvalidHeaders)?;
+            let more = trim_ows(line);
+            if !is_field_value(more) {
+                return Err(ParseError::InvalidHeaderValue);
+            }
+            if !more.is_empty() {
+                let value = last.value.to_mut();
+                if !value.is_empty() {
+                    value.push(' ');
+                }
+                value.push_str(&decode_value(more));
+            }
+            continue;
+        }
+        let colon = line
+            .iter()
+            .position(|b| *b == b':')
+            .ok_or(ParseError::InvalidHeaders)?;
+        let name = &line[..colon];
+        // this also refuses whitespace between the name and the colon
+        if !is_token(name) {
+            return Err(ParseError::InvalidHeaderName);
+        }
+        let value = trim_ows(&line[colon + 1..]);
+        if !is_field_value(value) {
+            return Err(ParseError::InvalidHeaderValue);
+        }
+        if headers.len() == limits.max_headers {
+            return Err(ParseError::TooManyHeaders);
+        }
+        headers.push(Header {
+            name: as_str(name),
+            value: decode_value(value),
+        });
+    }
+    Ok(headers)
+}
+
+fn values<'a, 'b>(headers: &'a [Header<'b>], name: &'a str) -> impl Iterator<Item = &'a str> {
+    headers
+        .iter()
+        .filter(move |h| h.name.eq_ignore_ascii_case(name))
+        .flat_map(|h| h.value.split(','))
+        .map(|v| v.trim_matches(|c| c == ' ' || c == '\t'))
+}
+
+/// The single value of all `Content-Length` fields, identical duplicates
+/// (`5, 5`) are allowed.
+fn content_length(headers: &[Header]) -> Result<Option<u64>, ParseError> {
+    let mut length = None;
+    for v in values(headers, "content-length") {
+        if v.is_empty() || v.len() > 19 || !v.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseError::InvalidContentLength);
+        }
+        let n: u64 = v.parse().map_err(|_| ParseError::InvalidContentLength)?;
+        match length {
+            Some(m) if m != n => return Err(ParseError::InvalidContentLength),
+            _ => length = Some(n),
+        }
+    }
+    Ok(length)
+}
+
+/// `Some(true)` when the final coding is chunked, `Some(false)` for any other
+/// final coding, `None` without `Transfer-Encoding`.
+fn transfer_chunked(headers: &[Header]) -> Result<Option<bool>, ParseError> {
+    let mut codings = values(headers, "transfer-encoding")
+        .filter(|v| !v.is_empty())
+        .peekable();
+    if codings.peek().is_none() {
+        if headers
+            .iter()
+            .any(|h| h.name.eq_ignore_ascii_case("transfer-encoding"))
+        {
+            return Err(ParseError::InvalidTransferEncoding);
+        }
+        return Ok(None);
+    }
+    let mut chunked = false;
+    for coding in codings {
+        if chunked {
+            // chunked must be the last coding, and only applied once
+            return Err(ParseError::InvalidTransferEncoding);
+        }
+        let name = coding.split(';').next().unwrap_or_default().trim();
+        if !is_token(name.as_bytes()) {
+            return Err(ParseError::InvalidTransferEncoding);
+        }
+        chunked = name.eq_ignore_ascii_case("chunked");
+    }
+    Ok(Some(chunked))
+}
+
+fn request_body(version: Version, headers: &[Header]) -> Result<BodyKind, ParseError> {
+    let chunked = transfer_chunked(headers)?;
+    let length = content_length(headers)?;
+    match (chunked, length) {
+        (Some(_), Some(_)) => Err(ParseError::AmbiguousLength),
+        (Some(_), None) if version == Version::V1_0 => Err(ParseError::InvalidTransferEncoding),
+        (Some(true), None) => Ok(BodyKind::Chunked),
+        // the length of the body could not be determined
+        (Some(false), None) => Err(ParseError::InvalidTransferEncoding),
+        (None, Some(0)) | (None, None) => Ok(BodyKind::Empty),
+        (None, Some(n)) => Ok(BodyKind::Length(n)),
+    }
+}
+
+fn response_body(
+    status: u16,
+    head_request: bool,
+    headers: &[Header],
+) -> Result<BodyKind, ParseError> {
+    if head_request || (100..200).contains(&status) || status == 204 || status == 304 {
+        return Ok(BodyKind::Empty);
+    }
+    let chunked = transfer_chunked(headers)?;
+    let length = content_length(headers)?;
+    match (chunked, length) {
+        (Some(_), Some(_)) => Err(ParseError::AmbiguousLength),
+        (Some(true), None) => Ok(BodyKind::Chunked),
+        (Some(false), None) => Ok(BodyKind::UntilClose),
+        (None, Some(0)) => Ok(BodyKind::Empty),
+        (None, Some(n)) => Ok(BodyKind::Length(n)),
+        (None, None) => Ok(BodyKind::UntilClose),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(s: &str) -> Result<Status<RequestHead<'_>>, ParseError> {
+        parse_request(s.as_bytes(), &Limits::default())
+    }
+
+    fn complete_request(s: &str) -> RequestHead<'_> {
+        match request(s) {
+            Ok(Status::Complete(head)) => head,
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_request_partial() {
+        let data = "GET /a?b=c HTTP/1.1\r\nHost: localhost\r\nAccept: */*\r\n\r\n";
+        for i in 0..data.len() {
+            assert!(matches!(request(&data[..i]), Ok(Status::Partial)));
+        }
+        let head = complete_request(data);
+        assert_eq!("GET", head.method);
+        assert_eq!("/a?b=c", head.target);
+        assert_eq!(Version::V1_1, head.version);
+        assert_eq!(BodyKind::Empty, head.body);
+        assert_eq!(data.len(), head.head_len);
+    }
+
+    #[test]
+    fn test_header_order_and_duplicates() {
+        let head = complete_request(
+            "POST / HTTP/1.1\r\nB: 1\r\nSet-Cookie: a=1\r\nA: 2\r\nset-cookie: b=2\r\nContent-Length: 3\r\n\r\nabc",
+        );
+        let names: Vec<_> = head.headers.iter().map(|h| h.name).collect();
+        assert_eq!(
+            vec!["B", "Set-Cookie", "A", "set-cookie", "Content-Length"],
+            names
+        );
+        assert_eq!("b=2", head.headers[3].value);
+        assert_eq!(BodyKind::Length(3), head.body);
+    }
+
+    #[test]
+    fn test_lf_line_endings_and_leading_empty_lines() {
+        let data = "\r\nGET / HTTP/1.0\nHost: a\n\nbody";
+        let head = complete_request(data);
+        assert_eq!(Version::V1_0, head.version);
+        assert_eq!("a", head.headers[0].value);
+        assert_eq!(data.len() - 4, head.head_len);
+    }
+
+    #[test]
+    fn test_obs_fold() {
+        let head = complete_request("GET / HTTP/1.1\r\nX-Long: a\r\n  b\r\n\tc\r\nY: d\r\n\r\n");
+        assert_eq!("a b c", head.headers[0].value);
+        assert_eq!("d", head.headers[1].value);
+        assert!(matches!(
+            request("GET / HTTP/1.1\r\n  b\r\n\r\n"),
+            Err(ParseError::InvalidHeaders)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_lines() {
+        for data in [
+            "GET  / HTTP/1.1\r\n\r\n",
+            "GET / HTTP/1.1 x\r\n\r\n",
+            "GET /\r\n\r\n",
+        ] {
+            assert!(
+                matches!(request(data), Err(ParseError::InvalidRequestLine)),
+                "{}",
+                data
+            );
+        }
+        assert!(matches!(
+            request("G(T / HTTP/1.1\r\n\r\n"),
+            Err(ParseError::InvalidMethod)
+        ));
+        assert!(matches!(
+            request("GET / HTTP/2.0\r\n\r\n"),
+            Err(ParseError::InvalidVersion)
+        ));
+        assert!(matches!(
+            request("GET / HTTP/1.1\r\nA\rB: c\r\n\r\n"),
+            Err(ParseError::InvalidHeaders)
+        ));
+        assert!(matches!(
+            request("GET / HTTP/1.1\r\nA: b\x01\r\n\r\n"),
+            Err(ParseError::InvalidHeaderValue)
+        ));
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = Limits {
+            max_head_size: 64,
+            max_line_size: 32,
+            max_headers: 2,
+        };
+        let long = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(40));
+        assert!(matches!(
+            parse_request(long.as_bytes(), &limits),
+            Err(ParseError::HeadersTooLarge)
+        ));
+        // an unterminated line is refused as soon as it is too long
+        assert!(matches!(
+            parse_request(&long.as_bytes()[..36], &limits),
+            Err(ParseError::HeadersTooLarge)
+        ));
+        let many = "GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n";
+        assert!(matches!(
+            parse_request(many.as_bytes(), &limits),
+            Err(ParseError::TooManyHeaders)
+        ));
+        let big = format!("GET / HTTP/1.1\r\n{}", "A: 1234567890\r\n".repeat(5));
+        assert!(matches!(
+            parse_request(big.as_bytes(), &limits),
+            Err(ParseError::HeadersTooLarge)
+        ));
+    }
+
+    #[test]
+    fn test_request_smuggling() {
+        for (data, err) in [
+            (
+                "POST / HTTP/1.1\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n",
+                ParseError::AmbiguousLength,
+            ),
+            (
+                "POST / HTTP/1.1\r\nContent-Length: 3\r\nContent-Length: 4\r\n\r\n",
+                ParseError::InvalidContentLength,
+            ),
+            (
+                "POST / HTTP/1.1\r\nContent-Length: +3\r\n\r\n",
+                ParseError::InvalidContentLength,
+            ),
+            (
+                "POST / HTTP/1.1\r\nTransfer-Encoding: chunked, gzip\r\n\r\n",
+                ParseError::InvalidTransferEncoding,
+            ),
+            (
+                "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nTransfer-Encoding: chunked\r\n\r\n",
+                ParseError::InvalidTransferEncoding,
+            ),
+            (
+                "POST / HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n",
+                ParseError::InvalidTransferEncoding,
+            ),
+            (
+                "POST / HTTP/1.0\r\nTransfer-Encoding: chunked\r\n\r\n",
+                ParseError::InvalidTransferEncoding,
+            ),
+            (
+                "POST / HTTP/1.1\r\nTransfer-Encoding : chunked\r\n\r\n",
+                ParseError::InvalidHeaderName,
+            ),
+        ] {
+            match request(data) {
+                Err(e) => assert_eq!(format!("{:?}", err), format!("{:?}", e), "{}", data),
+                r => panic!("{} => {:?}", data, r),
+            }
+        }
+
+        let head = complete_request(
+            "POST / HTTP/1.1\r\nContent-Length: 5, 5\r\nContent-Length: 5\r\n\r\n",
+        );
+        assert_eq!(BodyKind::Length(5), head.body);
+        let head = complete_request("POST / HTTP/1.1\r\nTransfer-Encoding: gzip, Chunked\r\n\r\n");
+        assert_eq!(BodyKind::Chunked, head.body);
+    }
+
+    fn response(s: &str, head_request: bool) -> ResponseHead<'_> {
+        match parse_response(s.as_bytes(), &Limits::default(), head_request) {
+            Ok(Status::Complete(head)) => head,
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_response() {
+        let head = response(
+            "HTTP/1.1 404 Not Found Here\r\nLocation: http://a:80/b\r\n\r\n",
+            false,
+        );
+        assert_eq!(404, head.status);
+        assert_eq!("Not Found Here", head.reason);
+        assert_eq!("http://a:80/b", head.headers[0].value);
+        assert_eq!(BodyKind::UntilClose, head.body);
+
+        assert_eq!("", response("HTTP/1.1 200\r\n\r\n", false).reason);
+        assert_eq!(
+            BodyKind::Empty,
+            response("HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n", true).body
+        );
+        assert_eq!(
+            BodyKind::Empty,
+            response(
+                "HTTP/1.1 304 Not Modified\r\nContent-Length: 10\r\n\r\n",
+                false
+            )
+            .body
+        );
+        assert_eq!(
+            BodyKind::UntilClose,
+            response("HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip\r\n\r\n", false).body
+        );
+        assert_eq!(
+            BodyKind::Chunked,
+            response(
+                "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n",
+                false
+            )
+            .body
+        );
+        assert!(matches!(
+            parse_response(
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nContent-Length: 1\r\n\r\n",
+                &Limits::default(),
+                false
+            ),
+            Err(ParseError::AmbiguousLength)
+        ));
+        assert!(matches!(
+            parse_response(b"HTTP/1.1 20 OK\r\n\r\n", &Limits::default(), false),
+            Err(ParseError::InvalidStatusCode)
+        ));
+    }
+
+    #[test]
+    fn test_obs_text() {
+        let head = response("HTTP/1.1 200 OK\r\nX: caf\u{e9}\r\n\r\n", false);
+        assert_eq!("caf\u{e9}", head.headers[0].value);
+        let head = match parse_response(
+            b"HTTP/1.1 200 OK\r\nX: caf\xe9\r\n\r\n",
+            &Limits::default(),
+            false,
+        ) {
+            Ok(Status::Complete(head)) => head,
+            r => panic!("{:?}", r),
+        };
+        assert_eq!("caf\u{e9}", head.headers[0].value);
+    }
+}
@@ -1,9 +1,11 @@
+use super::chunk::{Chunk, HttpChunk};
+use super::parser::{self, BodyKind, Limits, Status};
+use super::HeaderMap;
 use super::Method;
 use super::ParseError;
 use super::Version;
-use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::fmt::{Display, Formatter};
-use std::io::BufRead;
 
 /// Resource requested
 #[derive(Debug, PartialEq, Clone)]
@@ -24,108 +26,78 @@ pub struct HttpRequest {
     pub method: Method,
     pub version: Version,
     pub resource: Resource,
-    pub headers: HashMap<String, String>,
+    pub headers: HeaderMap,
     pub body: Vec<u8>,
+    /// Trailer fields of a chunked body.
+    pub trailers: HeaderMap,
+    /// How the body following the head is delimited.
+    pub body_kind: BodyKind,
 }
 
 impl HttpRequest {
+    /// Parses a whole request, waiting for the full body when it has a
+    /// `Content-Length` or is chunked.
     pub fn parse(req: &[u8]) -> Result<Self, ParseError> {
-        let mut req_header = req;
-        let parsed_method;
-        let parsed_version;
-        let parsed_resource;
-        let mut parsed_headers = HashMap::new();
-        let mut body_len = 0;
-
-        let mut total = 0;
-
-        {
-            let mut line = String::new();
-            let n = req_header
-                .read_line(&mut line)
-                .map_err(|_| ParseError::OtherParseError)?;
-            if n == 0 {
-                return Err(ParseError::Pending);
-            }
-            let line = line.trim_end();
-            total += n;
-
-            let (method, resource, version) = process_req_line(line)?;
-            parsed_method = method;
-            parsed_version = version;
-            parsed_resource = resource;
-        }
+        Self::parse_with_limits(req, &Limits::default())
+    }
 
-        'exit: loop {
-            let mut line = String::new();
-            let n = req_header
-                .read_line(&mut line)
-                .map_err(|_| ParseError::OtherParseError)?;
-            if n == 0 {
-                return Err(ParseError::Pending);
+    pub fn parse_with_limits(req: &[u8], limits: &Limits) -> Result<Self, ParseError> {
+        let (mut request, head_len) = Self::parse_head(req, limits)?;
+        let rest = &req[head_len..];
+        match request.body_kind {
+            BodyKind::Empty | BodyKind::UntilClose => {}
+            BodyKind::Length(n) => {
+                let n = usize::try_from(n).map_err(|_| ParseError::InvalidContentLength)?;
+                request.body = rest.get(..n).ok_or(ParseError::Pending)?.to_vec()
             }
-            let line = line.trim_end();
-            total += n;
-
-            {
-                if line.is_empty() {
-                    break 'exit;
-                } else if line.contains(':') {
-                    let (key, value) = process_header_line(line);
-                    if key.to_lowercase().as_str() == "content-length" {
-                        body_len = value
-                            .as_str()
-                            .parse()
-                            .map_err(|_| ParseError::InvalidHeaders)?;
+            BodyKind::Chunked => {
+                let mut rest = rest;
+                loop {
+                    match HttpChunk::decode(rest, limits)? {
+                        (Chunk::Data { data, .. }, n) => {
+                            request.body.extend_from_slice(data);
+                            rest = &rest[n..];
+                        }
+                        (Chunk::Last { trailers, .. }, _) => {
+                            for h in trailers {
+                                request
+                                    .trailers
+                                    .append(h.name.to_lowercase(), h.value.into_owned());
+                            }
+                            break;
+                        }
                     }
-                    parsed_headers.insert(key, value);
-                } else {
-                    return Err(ParseError::InvalidHeaders);
                 }
             }
         }
-
-        let body = req
-            .get(total..total + body_len)
-            .ok_or(ParseError::Pending)?;
-
-        Ok(HttpRequest {
-            method: parsed_method,
-            version: parsed_version,
-            resource: parsed_resource,
-            headers: parsed_headers,
-            body: body.to_vec(),
-        })
+        Ok(request)
     }
-}
-
-fn process_req_line(s: &str) -> Result<(Method, Resource, Version), ParseError> {
-    let mut words = s.split_whitespace();
-    let method = words.next().ok_or(ParseError::InvalidMethod)?;
-    let resource = words.next().ok_or(ParseError::InvalidUrl)?;
-    let version = words.next().ok_or(ParseError::InvalidVersion)?;
-
-    Ok((
-        method.parse()?,
-        Resource::Path(resource.to_string()),
-        version.parse()?,
-    ))
-}
 
-fn process_header_line(s: &str) -> (String, String) {
-    let mut header_items = s.split(':');
-    let mut key = String::from("");
-    let mut value = String::from("");
+    /// Parses the head only and returns it with the number of bytes it took.
+    /// The body is left in the buffer, `body_kind` tells how it is framed.
+    pub fn parse_head(req: &[u8], limits: &Limits) -> Result<(Self, usize), ParseError> {
+        let head = match parser::parse_request(req, limits)? {
+            Status::Complete(head) => head,
+            Status::Partial => return Err(ParseError::Pending),
+        };
+        let method = head.method.parse()?;
 
-    if let Some(k) = header_items.next() {
-        key = k.to_lowercase();
-    }
+        let mut headers = HeaderMap::new();
+        for h in head.headers {
+            headers.append(h.name.to_lowercase(), h.value.into_owned());
+        }
 
-    if let Some(v) = header_items.next() {
-        value = v.to_string().trim_start().to_string()
+        let request = HttpRequest {
+            method,
+            version: head.version,
+            resource: Resource::Path(head.target.to_string()),
+            headers,
+            body: vec![],
+            trailers: HeaderMap::new(),
+            body_kind: head.body,
+        };
+        Ok((request, head.head_len))
     }
-
-    (key, value)
 }
 
 impl From<HttpRequest> for String {
@@ -172,11 +144,11 @@ mod tests {
     #[test]
     fn test_read_http() {
         let s: String = String::from("POST /greeting HTTP/1.1\r\nHost: localhost:3000\r\nUser-Agent: curl/7.64.1\r\nContent-Length: 11\r\nAccept: */*\r\n\r\ntestbody123");
-        let mut headers_expected = HashMap::new();
-        headers_expected.insert("Host".into(), "localhost".into());
-        headers_expected.insert("Accept".into(), "*/*".into());
-        headers_expected.insert("User-Agent".into(), "curl/7.64.1".into());
-        headers_expected.insert("Content-Length".into(), "11".into());
+        let mut headers_expected = HeaderMap::new();
+        headers_expected.append("host".into(), "localhost:3000".into());
+        headers_expected.append("user-agent".into(), "curl/7.64.1".into());
+        headers_expected.append("content-length".into(), "11".into());
+        headers_expected.append("accept".into(), "*/*".into());
         let r = HttpRequest::parse(s.as_bytes());
         println!("{:?}", r);
         let req = r.unwrap();
@@ -187,6 +159,38 @@ mod tests {
         assert_eq!(req.body.as_slice(), b"testbody123");
     }
 
+    #[test]
+    fn test_parse_head() {
+        let s = b"POST /upload HTTP/1.1\r\nContent-Length: 10\r\nExpect: 100-continue\r\n\r\nfirst";
+        let (req, n) = HttpRequest::parse_head(s, &Limits::default()).unwrap();
+        assert_eq!(s.len() - 5, n);
+        assert_eq!(BodyKind::Length(10), req.body_kind);
+        assert_eq!("100-continue", req.headers.get("expect").unwrap());
+        assert!(req.body.is_empty());
+
+        let s = b"PUT / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n";
+        let (req, _) = HttpRequest::parse_head(s, &Limits::default()).unwrap();
+        assert_eq!(BodyKind::Chunked, req.body_kind);
+    }
+
+    #[test]
+    fn test_read_chunked_http() {
+        let s = "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nCookie: a=1\r\nCookie: b=2\r\n\r\n4\r\ntest\r\n3\r\nbod\r\n";
+        assert!(matches!(
+            HttpRequest::parse(s.as_bytes()),
+            Err(ParseError::Pending)
+        ));
+        let req = HttpRequest::parse(format!("{}0\r\n\r\n", s).as_bytes()).unwrap();
+        assert_eq!(req.body.as_slice(), b"testbod");
+        assert!(req.trailers.is_empty());
+        let req =
+            HttpRequest::parse(format!("{}0\r\nX-Checksum: 1f\r\n\r\n", s).as_bytes()).unwrap();
+        assert_eq!(req.body.as_slice(), b"testbod");
+        assert_eq!("1f", req.trailers.get("x-checksum").unwrap());
+        let cookies: Vec<_> = req.headers.get_all("cookie").collect();
+        assert_eq!(vec!["a=1", "b=2"], cookies);
+    }
+
     #[test]
     fn test_display_http() {
         let req = HttpRequest {
@@ -194,11 +198,13 @@ mod tests {
             version: Version::V1_0,
             resource: Resource::Path("/abc".to_string()),
             headers: {
-                let mut h = HashMap::new();
+                let mut h = HeaderMap::new();
                 h.insert("Content-Type".to_string(), "text/html".to_string());
                 h
             },
             body: b"abcd".to_vec(),
+            trailers: HeaderMap::new(),
+            body_kind: BodyKind::Length(4),
         };
         assert_eq!(
             "GET /abc HTTP/1.0\r\nContent-Type: text/html\r\nContent-Length: 4\r\n\r\n",
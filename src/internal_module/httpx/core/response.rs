@@ -1,13 +1,16 @@
+use super::parser::{self, BodyKind, Limits, Status};
+use super::HeaderMap;
 use super::ParseError;
 use super::Version;
-use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::fmt::Display;
-use std::io::BufRead;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum BodyLen {
     Length(usize),
     Chunked,
+    /// Delimited by the end of the connection.
+    UntilClose,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -15,7 +18,7 @@ pub struct HttpResponse {
     pub version: Version,
     pub status_code: u16,
     pub status_text: String,
-    pub headers: HashMap<String, String>,
+    pub headers: HeaderMap,
     pub body_len: BodyLen,
 }
 
@@ -25,7 +28,7 @@ impl Default for HttpResponse {
             version: Version::V1_0,
             status_code: 200,
             status_text: "OK".to_string(),
-            headers: HashMap::default(),
+            headers: HeaderMap::default(),
             body_len: BodyLen::Length(0),
         }
     }
@@ -33,7 +36,7 @@ impl Default for HttpResponse {
 
 impl HttpResponse {
     #[deprecated]
-    pub fn new(status_code: u16, headers: Option<HashMap<String, String>>) -> HttpResponse {
+    pub fn new(status_code: u16, headers: Option<HeaderMap>) -> HttpResponse {
         let mut response = HttpResponse::default();
         response.status_code = status_code;
         if let Some(headers) = headers {
@@ -77,6 +80,7 @@ impl From<&HttpResponse> for String {
             BodyLen::Chunked => {
                 header_string.push_str(&format!("Transfer-Encoding: chunked\r\n"));
             }
+            BodyLen::UntilClose => {}
         }
 
         format!(
@@ -93,11 +97,7 @@ impl Display for HttpResponse {
 }
 
 impl HttpResponse {
-    pub fn from_status(
-        headers: Option<HashMap<String, String>>,
-        status_code: u16,
-        status_text: &str,
-    ) -> Self {
+    pub fn from_status(headers: Option<HeaderMap>, status_code: u16, status_text: &str) -> Self {
         let mut response = HttpResponse::default();
 
         response.status_code = status_code;
@@ -109,111 +109,42 @@ impl HttpResponse {
         response
     }
 
-    pub fn parse(mut req: &[u8]) -> Result<(Self, usize), ParseError> {
-        let parsed_version;
-        let parsed_status;
-        let parsed_status_text;
-        let mut parsed_headers = HashMap::new();
-        let mut parsed_body_len = BodyLen::Length(0);
-
-        let mut total = 0;
-
-        {
-            let mut line = String::new();
-            let n = req
-                .read_line(&mut line)
-                .map_err(|_| ParseError::OtherParseError)?;
-            if n == 0 {
-                return Err(ParseError::Pending);
-            }
-            let line = line.trim_end();
-            total += n;
-
-            let (version, status, status_text) = process_resp_line(line)?;
-            parsed_version = version;
-            parsed_status = status;
-            parsed_status_text = status_text;
-        }
-
-        'exit: loop {
-            let mut line = String::new();
-            let n = req
-                .read_line(&mut line)
-                .map_err(|_| ParseError::OtherParseError)?;
-            if n == 0 {
-                return Err(ParseError::Pending);
-            }
-            let line = line.trim_end();
-            total += n;
-
-            {
-                if line.is_empty() {
-                    break 'exit;
-                } else if line.contains(':') {
-                    let (key, value) = process_header_line(line);
-                    let header_key = key.to_lowercase();
-                    if header_key.as_str() == "content-length" {
-                        let len = value.parse().map_err(|_| ParseError::InvalidHeaders)?;
-                        parsed_body_len = BodyLen::Length(len);
-                    } else if header_key.as_str() == "transfer-encoding"
-                        && value.as_str() == "chunked"
-                    {
-                        parsed_body_len = BodyLen::Chunked;
-                    }
-                    parsed_headers.insert(key, value);
-                } else {
-                    return Err(ParseError::InvalidHeaders);
-                }
-            }
-        }
+    /// Parses the head of a response, the body is left in the buffer.
+    /// Returns the response and the length of its head.
+    pub fn parse(req: &[u8]) -> Result<(Self, usize), ParseError> {
+        Self::parse_with_limits(req, &Limits::default(), false)
+    }
 
-        if let BodyLen::Length(n) = parsed_body_len {
-            if req.len() < n {
-                return Err(ParseError::Pending);
+    pub fn parse_with_limits(
+        req: &[u8],
+        limits: &Limits,
+        head_request: bool,
+    ) -> Result<(Self, usize), ParseError> {
+        let head = match parser::parse_response(req, limits, head_request)? {
+            Status::Complete(head) => head,
+            Status::Partial => return Err(ParseError::Pending),
+        };
+        let body_len = match head.body {
+            BodyKind::Empty => BodyLen::Length(0),
+            BodyKind::Length(n) => {
+                BodyLen::Length(usize::try_from(n).map_err(|_| ParseError::InvalidContentLength)?)
             }
-        }
-
+            BodyKind::Chunked => BodyLen::Chunked,
+            BodyKind::UntilClose => BodyLen::UntilClose,
+        };
         Ok((
             HttpResponse {
-                version: parsed_version,
-                status_code: parsed_status,
-                status_text: parsed_status_text,
-                headers: parsed_headers,
-                body_len: parsed_body_len,
+                version: head.version,
+                status_code: head.status,
+                status_text: head.reason.into_owned(),
+                headers: head.headers.into(),
+                body_len,
             },
-            total,
+            head.head_len,
         ))
     }
 }
 
-fn process_resp_line(s: &str) -> Result<(Version, u16, String), ParseError> {
-    let mut words = s.split_whitespace();
-    let version = words.next().ok_or(ParseError::InvalidStatusLine)?;
-    let status = words.next().ok_or(ParseError::InvalidStatusLine)?;
-    let resource = words.next().unwrap_or_default();
-
-    Ok((
-        version.parse()?,
-        status.parse().map_err(|_| ParseError::InvalidStatusCode)?,
-        resource.to_string(),
-    ))
-}
-fn process_header_line(s: &str) -> (String, String) {
-    let mut header_items = s.split(':');
-    let mut key = String::from("");
-    let mut value = String::from("");
-
-    if let Some(k) = header_items.next() {
-        key = k.to_string();
-    }
-
-    if let Some(v) = header_items.next() {
-        value = v.to_string().trim_start().to_string()
-    }
-
-    (key, value)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -225,7 +156,7 @@ mod tests {
             status_code: 200,
             status_text: "OK".to_string(),
             headers: {
-                let mut h = HashMap::new();
+                let mut h = HeaderMap::new();
                 h.insert("Content-Type".to_string(), "text/html".to_string());
                 h
             },
@@ -248,4 +179,36 @@ mod tests {
         assert_eq!(BodyLen::Length(4), resp.body_len);
         assert_eq!("haha", data.split_at(n).1)
     }
+
+    #[test]
+    fn test_response_parse_pipelined() {
+        let data = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok\
+                    HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n\
+                    HTTP/1.1 20";
+        let (first, n) = HttpResponse::parse(data.as_bytes()).unwrap();
+        assert_eq!(200, first.status_code);
+        assert_eq!(BodyLen::Length(2), first.body_len);
+        let rest = &data.as_bytes()[n + 2..];
+        let (second, m) = HttpResponse::parse(rest).unwrap();
+        assert_eq!(404, second.status_code);
+        assert_eq!("Not Found", second.status_text);
+        assert!(matches!(
+            HttpResponse::parse(&rest[m..]),
+            Err(ParseError::Pending)
+        ));
+    }
+
+    #[test]
+    fn test_response_parse_head_only() {
+        let data = "HTTP/1.1 301 Moved Permanently\r\nLocation: http://localhost:8000/a\r\nContent-Length: 4\r\n\r\n";
+        let (resp, n) = HttpResponse::parse(data.as_bytes()).unwrap();
+        assert_eq!(301, resp.status_code);
+        assert_eq!("Moved Permanently", resp.status_text);
+        assert_eq!(BodyLen::Length(4), resp.body_len);
+        assert_eq!(
+            Some(&"http://localhost:8000/a".to_string()),
+            resp.headers.get("Location")
+        );
+        assert_eq!(data.len(), n);
+    }
 }
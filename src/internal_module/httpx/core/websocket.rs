@@ -0,0 +1,862 @@
+//! WebSocket framing (RFC 6455) with the permessage-deflate extension
+//! (RFC 7692).
+//!
+//! A frame is a 2 byte header, an extended length, an optional masking key
+//! and the payload. Messages are one or more data frames; control frames
+//! (close, ping, pong) may arrive between the fragments of a message.
+
+use std::fmt::{Display, Formatter};
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+
+/// Appended to `Sec-WebSocket-Key` before hashing it into
+/// `Sec-WebSocket-Accept`.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Every compressed message ends with this empty stored block, it is
+/// stripped on the wire.
+const DEFLATE_TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OpCode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl OpCode {
+    fn from_u8(b: u8) -> Option<Self> {
+        Some(match b {
+            0x0 => OpCode::Continuation,
+            0x1 => OpCode::Text,
+            0x2 => OpCode::Binary,
+            0x8 => OpCode::Close,
+            0x9 => OpCode::Ping,
+            0xa => OpCode::Pong,
+            _ => return None,
+        })
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            OpCode::Continuation => 0x0,
+            OpCode::Text => 0x1,
+            OpCode::Binary => 0x2,
+            OpCode::Close => 0x8,
+            OpCode::Ping => 0x9,
+            OpCode::Pong => 0xa,
+        }
+    }
+
+    pub fn is_control(self) -> bool {
+        matches!(self, OpCode::Close | OpCode::Ping | OpCode::Pong)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum WsError {
+    /// More bytes are needed.
+    Pending,
+    /// The peer broke the protocol.
+    Protocol(&'static str),
+    /// A text message or close reason is not UTF-8.
+    InvalidUtf8,
+    /// A message is over the size limit.
+    TooBig,
+    /// The extension parameters cannot be honoured.
+    Extension(&'static str),
+}
+
+impl WsError {
+    /// The close code to fail the connection with.
+    pub fn close_code(&self) -> u16 {
+        match self {
+            WsError::InvalidUtf8 => 1007,
+            WsError::TooBig => 1009,
+            WsError::Extension(_) => 1010,
+            _ => 1002,
+        }
+    }
+}
+
+impl Display for WsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WsError::Pending => f.write_str("incomplete frame"),
+            WsError::Protocol(reason) | WsError::Extension(reason) => f.write_str(reason),
+            WsError::InvalidUtf8 => f.write_str("invalid UTF-8 sequence"),
+            WsError::TooBig => f.write_str("max payload size exceeded"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Frame {
+    pub fin: bool,
+    /// Set on the first frame of a compressed message.
+    pub rsv1: bool,
+    pub opcode: OpCode,
+    pub masked: bool,
+    /// Unmasked payload.
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    /// Decodes one frame and returns it with the number of bytes it took.
+    /// Payloads over `max_payload` fail before they are buffered.
+    pub fn decode(buf: &[u8], max_payload: usize) -> Result<(Frame, usize), WsError> {
+        if buf.len() < 2 {
+            return Err(WsError::Pending);
+        }
+        let (b0, b1) = (buf[0], buf[1]);
+        if b0 & 0x30 != 0 {
+            return Err(WsError::Protocol("RSV2 and RSV3 must be clear"));
+        }
+        let fin = b0 & 0x80 != 0;
+        let opcode = OpCode::from_u8(b0 & 0x0f).ok_or(WsError::Protocol("invalid opcode"))?;
+        let masked = b1 & 0x80 != 0;
+        let (len, mut pos) = match b1 & 0x7f {
+            126 => {
+                let b = buf.get(2..4).ok_or(WsError::Pending)?;
+                (u16::from_be_bytes([b[0], b[1]]) as u64, 4)
+            }
+            127 => {
+                let b = buf.get(2..10).ok_or(WsError::Pending)?;
+                let mut n = [0; 8];
+                n.copy_from_slice(b);
+                let len = u64::from_be_bytes(n);
+                if len >> 63 != 0 {
+                    return Err(WsError::Protocol("invalid payload length"));
+                }
+                (len, 10)
+            }
+            n => (n as u64, 2),
+        };
+        if opcode.is_control() && (!fin || len > 125) {
+            return Err(WsError::Protocol("invalid control frame"));
+        }
+        if len > max_payload as u64 {
+            return Err(WsError::TooBig);
+        }
+        let mut mask = None;
+        if masked {
+            let key = buf.get(pos..pos + 4).ok_or(WsError::Pending)?;
+            mask = Some([key[0], key[1], key[2], key[3]]);
+            pos += 4;
+        }
+        let end = pos + len as usize;
+        let mut payload = buf.get(pos..end).ok_or(WsError::Pending)?.to_vec();
+        if let Some(key) = mask {
+            apply_mask(&mut payload, key);
+        }
+        let frame = Frame {
+            fin,
+            rsv1: b0 & 0x40 != 0,
+            opcode,
+            masked,
+            payload,
+        };
+        Ok((frame, end))
+    }
+
+    /// Appends a frame holding `payload`, masked with `mask` if given.
+    pub fn encode(
+        opcode: OpCode,
+        fin: bool,
+        rsv1: bool,
+        payload: &[u8],
+        mask: Option<[u8; 4]>,
+        out: &mut Vec<u8>,
+    ) {
+        let mut b0 = opcode.as_u8();
+        if fin {
+            b0 |= 0x80;
+        }
+        if rsv1 {
+            b0 |= 0x40;
+        }
+        out.push(b0);
+        let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+        match payload.len() {
+            n if n < 126 => out.push(mask_bit | n as u8),
+            n if n <= u16::MAX as usize => {
+                out.push(mask_bit | 126);
+                out.extend_from_slice(&(n as u16).to_be_bytes());
+            }
+            n => {
+                out.push(mask_bit | 127);
+                out.extend_from_slice(&(n as u64).to_be_bytes());
+            }
+        }
+        match mask {
+            Some(key) => {
+                out.extend_from_slice(&key);
+                let start = out.len();
+                out.extend_from_slice(payload);
+                apply_mask(&mut out[start..], key);
+            }
+            None => out.extend_from_slice(payload),
+        }
+    }
+}
+
+fn apply_mask(data: &mut [u8], key: [u8; 4]) {
+    for (i, b) in data.iter_mut().enumerate() {
+        *b ^= key[i % 4];
+    }
+}
+
+/// The `Sec-WebSocket-Accept` value answering `key`.
+pub fn accept_key(key: &str) -> String {
+    let mut sha1 = sha1_smol::Sha1::new();
+    sha1.update(key.trim().as_bytes());
+    sha1.update(ACCEPT_GUID.as_bytes());
+    base64_encode(&sha1.digest().bytes())
+}
+
+pub fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn is_valid_close_code(code: u16) -> bool {
+    matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999)
+}
+
+/// The payload of a close frame: nothing, or a status code and a reason.
+pub fn decode_close(payload: &[u8]) -> Result<Option<(u16, String)>, WsError> {
+    match payload {
+        [] => Ok(None),
+        [_] => Err(WsError::Protocol("invalid close frame")),
+        [hi, lo, reason @ ..] => {
+            let code = u16::from_be_bytes([*hi, *lo]);
+            if !is_valid_close_code(code) {
+                return Err(WsError::Protocol("invalid close code"));
+            }
+            let reason = std::str::from_utf8(reason).map_err(|_| WsError::InvalidUtf8)?;
+            Ok(Some((code, reason.to_string())))
+        }
+    }
+}
+
+pub fn encode_close(close: Option<(u16, &str)>) -> Vec<u8> {
+    match close {
+        None => vec![],
+        Some((code, reason)) => {
+            let mut payload = code.to_be_bytes().to_vec();
+            // the whole control frame payload is at most 125 bytes
+            let mut end = reason.len().min(123);
+            while !reason.is_char_boundary(end) {
+                end -= 1;
+            }
+            payload.extend_from_slice(&reason.as_bytes()[..end]);
+            payload
+        }
+    }
+}
+
+/// Negotiated permessage-deflate parameters. Only the full 15 bit window
+/// is supported for compressing, so offers limiting our window are refused.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct DeflateConfig {
+    pub server_no_context_takeover: bool,
+    pub client_no_context_takeover: bool,
+}
+
+impl DeflateConfig {
+    /// The offer a client sends in `Sec-WebSocket-Extensions`.
+    pub const OFFER: &'static str = "permessage-deflate; client_max_window_bits";
+
+    /// Picks the first acceptable permessage-deflate offer of a client.
+    pub fn negotiate(header: &str) -> Option<Self> {
+        for (name, params) in parse_extensions(header) {
+            if name != "permessage-deflate" {
+                continue;
+            }
+            let mut config = DeflateConfig::default();
+            let mut acceptable = true;
+            for (key, value) in params {
+                match (key.as_str(), value.as_deref()) {
+                    ("server_no_context_takeover", None) => {
+                        config.server_no_context_takeover = true
+                    }
+                    ("client_no_context_takeover", None) => {
+                        config.client_no_context_takeover = true
+                    }
+                    // we decompress with a full window whatever the client uses
+                    ("client_max_window_bits", None) => {}
+                    ("client_max_window_bits", Some(bits)) if valid_window_bits(bits) => {}
+                    ("server_max_window_bits", Some("15")) => {}
+                    _ => acceptable = false,
+                }
+            }
+            if acceptable {
+                return Some(config);
+            }
+        }
+        None
+    }
+
+    /// Checks the server's answer to [`DeflateConfig::OFFER`]. `Ok(None)`
+    /// means the server declined the extension.
+    pub fn accept(header: &str) -> Result<Option<Self>, WsError> {
+        let mut extensions = parse_extensions(header).into_iter();
+        let (name, params) = match extensions.next() {
+            None => return Ok(None),
+            Some(ext) => ext,
+        };
+        if name != "permessage-deflate" || extensions.next().is_some() {
+            return Err(WsError::Extension("unexpected extension in the response"));
+        }
+        let mut config = DeflateConfig::default();
+        for (key, value) in params {
+            match (key.as_str(), value.as_deref()) {
+                ("server_no_context_takeover", None) => config.server_no_context_takeover = true,
+                ("client_no_context_takeover", None) => config.client_no_context_takeover = true,
+                ("server_max_window_bits", Some(bits)) if valid_window_bits(bits) => {}
+                ("client_max_window_bits", Some("15")) => {}
+                _ => {
+                    return Err(WsError::Extension(
+                        "unsupported permessage-deflate parameter",
+                    ))
+                }
+            }
+        }
+        Ok(Some(config))
+    }
+
+    /// The `Sec-WebSocket-Extensions` value describing this configuration.
+    pub fn to_header(self) -> String {
+        let mut header = String::from("permessage-deflate");
+        if self.server_no_context_takeover {
+            header.push_str("; server_no_context_takeover");
+        }
+        if self.client_no_context_takeover {
+            header.push_str("; client_no_context_takeover");
+        }
+        header
+    }
+}
+
+fn valid_window_bits(bits: &str) -> bool {
+    matches!(bits.parse::<u8>(), Ok(8..=15))
+}
+
+/// An extension name with its parameters and their values.
+type Extension = (String, Vec<(String, Option<String>)>);
+
+/// Splits `ext; param=value, ext2` into names with their parameters.
+fn parse_extensions(header: &str) -> Vec<Extension> {
+    header
+        .split(',')
+        .filter_map(|ext| {
+            let mut parts = ext.split(';').map(str::trim);
+            let name = parts.next().filter(|n| !n.is_empty())?.to_ascii_lowercase();
+            let params = parts
+                .filter(|p| !p.is_empty())
+                .map(|p| match p.split_once('=') {
+                    Some((k, v)) => (
+                        k.trim().to_ascii_lowercase(),
+                        Some(v.trim().trim_matches('"').to_string()),
+                    ),
+                    None => (p.to_ascii_lowercase(), None),
+                })
+                .collect();
+            Some((name, params))
+        })
+        .collect()
+}
+
+struct Deflate {
+    compress: Compress,
+    decompress: Decompress,
+    /// reset the compressor after each message
+    own_no_context_takeover: bool,
+    /// reset the decompressor after each message
+    peer_no_context_takeover: bool,
+}
+
+impl Deflate {
+    fn new(config: DeflateConfig, client: bool) -> Self {
+        let (own, peer) = if client {
+            (
+                config.client_no_context_takeover,
+                config.server_no_context_takeover,
+            )
+        } else {
+            (
+                config.server_no_context_takeover,
+                config.client_no_context_takeover,
+            )
+        };
+        Deflate {
+            compress: Compress::new(Compression::default(), false),
+            decompress: Decompress::new(false),
+            own_no_context_takeover: own,
+            peer_no_context_takeover: peer,
+        }
+    }
+
+    fn compress(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len() / 2 + 16);
+        let mut input = data;
+        loop {
+            out.reserve(1024);
+            let before = self.compress.total_in();
+            // only fails on a stream error, which cannot happen here
+            let _ = self
+                .compress
+                .compress_vec(input, &mut out, FlushCompress::Sync);
+            input = &input[(self.compress.total_in() - before) as usize..];
+            if input.is_empty() && out.len() < out.capacity() {
+                break;
+            }
+        }
+        if out.ends_with(&DEFLATE_TAIL) {
+            out.truncate(out.len() - DEFLATE_TAIL.len());
+        }
+        if self.own_no_context_takeover {
+            self.compress.reset();
+        }
+        out
+    }
+
+    fn decompress(&mut self, data: &[u8], max_size: usize) -> Result<Vec<u8>, WsError> {
+        let mut out = Vec::with_capacity(data.len() * 2 + 16);
+        for mut input in [data, &DEFLATE_TAIL[..]] {
+            loop {
+                out.reserve(4096);
+                let before = self.decompress.total_in();
+                let status = self
+                    .decompress
+                    .decompress_vec(input, &mut out, FlushDecompress::Sync)
+                    .map_err(|_| WsError::Protocol("invalid compressed data"))?;
+                input = &input[(self.decompress.total_in() - before) as usize..];
+                if out.len() > max_size {
+                    return Err(WsError::TooBig);
+                }
+                if let Status::StreamEnd = status {
+                    // a final block ends the deflate stream, start a new one
+                    self.decompress.reset(false);
+                }
+                if input.is_empty() && out.len() < out.capacity() {
+                    break;
+                }
+            }
+        }
+        if self.peer_no_context_takeover {
+            self.decompress.reset(false);
+        }
+        Ok(out)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    Close(Option<(u16, String)>),
+}
+
+/// One side of a WebSocket connection: assembles incoming frames into
+/// messages and frames outgoing ones. Clients mask what they send and
+/// servers require masked frames.
+pub struct WebSocketCodec {
+    client: bool,
+    max_payload: usize,
+    deflate: Option<Deflate>,
+    buf: Vec<u8>,
+    /// opcode and compression of the message being reassembled
+    fragmented: Option<(OpCode, bool)>,
+    fragments: Vec<u8>,
+}
+
+impl WebSocketCodec {
+    pub fn new(client: bool, max_payload: usize, deflate: Option<DeflateConfig>) -> Self {
+        WebSocketCodec {
+            client,
+            max_payload,
+            deflate: deflate.map(|config| Deflate::new(config, client)),
+            buf: vec![],
+            fragmented: None,
+            fragments: vec![],
+        }
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Bytes received but not decoded yet.
+    pub fn pending(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// The next complete message in what was pushed so far, `Ok(None)` when
+    /// more bytes are needed. After an error the connection must be closed
+    /// with [`WsError::close_code`].
+    pub fn next_message(&mut self) -> Result<Option<Message>, WsError> {
+        loop {
+            let limit = self.max_payload.saturating_sub(self.fragments.len());
+            let (frame, n) = match Frame::decode(&self.buf, limit) {
+                Err(WsError::Pending) => return Ok(None),
+                r => r?,
+            };
+            self.buf.drain(..n);
+            if frame.masked == self.client {
+                return Err(WsError::Protocol(if self.client {
+                    "a server must not mask frames"
+                } else {
+                    "a client must mask frames"
+                }));
+            }
+            if frame.rsv1 && (self.deflate.is_none() || frame.opcode.is_control()) {
+                return Err(WsError::Protocol("RSV1 must be clear"));
+            }
+            if let Some(message) = self.on_frame(frame)? {
+                return Ok(Some(message));
+            }
+        }
+    }
+
+    fn on_frame(&mut self, frame: Frame) -> Result<Option<Message>, WsError> {
+        let (opcode, compressed) = match (frame.opcode, self.fragmented) {
+            (OpCode::Close, _) => {
+                return decode_close(&frame.payload).map(|c| Some(Message::Close(c)))
+            }
+            (OpCode::Ping, _) => return Ok(Some(Message::Ping(frame.payload))),
+            (OpCode::Pong, _) => return Ok(Some(Message::Pong(frame.payload))),
+            (OpCode::Continuation, None) => {
+                return Err(WsError::Protocol("unexpected continuation frame"))
+            }
+            (OpCode::Continuation, Some((opcode, compressed))) => {
+                if frame.rsv1 {
+                    return Err(WsError::Protocol("RSV1 must be clear"));
+                }
+                (opcode, compressed)
+            }
+            (_, Some(_)) => return Err(WsError::Protocol("expected a continuation frame")),
+            (opcode, None) => (opcode, frame.rsv1),
+        };
+        if !frame.fin {
+            self.fragmented = Some((opcode, compressed));
+            self.fragments.extend_from_slice(&frame.payload);
+            return Ok(None);
+        }
+        self.fragmented = None;
+        let mut data = if self.fragments.is_empty() {
+            frame.payload
+        } else {
+            let mut data = std::mem::take(&mut self.fragments);
+            data.extend_from_slice(&frame.payload);
+            data
+        };
+        if compressed {
+            // rsv1 is only accepted with the extension
+            if let Some(deflate) = &mut self.deflate {
+                data = deflate.decompress(&data, self.max_payload)?;
+            }
+        }
+        Ok(Some(match opcode {
+            OpCode::Text => {
+                Message::Text(String::from_utf8(data).map_err(|_| WsError::InvalidUtf8)?)
+            }
+            _ => Message::Binary(data),
+        }))
+    }
+
+    /// Appends `message` as a single frame. Data messages are compressed
+    /// when permessage-deflate was negotiated and `compress` is set. Clients
+    /// must pass a fresh random `mask` for every frame.
+    pub fn encode(
+        &mut self,
+        message: &Message,
+        compress: bool,
+        mask: Option<[u8; 4]>,
+        out: &mut Vec<u8>,
+    ) {
+        let (opcode, payload) = match message {
+            Message::Text(text) => (OpCode::Text, text.as_bytes()),
+            Message::Binary(data) => (OpCode::Binary, &data[..]),
+            Message::Ping(data) => (OpCode::Ping, &data[..125.min(data.len())]),
+            Message::Pong(data) => (OpCode::Pong, &data[..125.min(data.len())]),
+            Message::Close(close) => {
+                let payload = encode_close(
+                    close
+                        .as_ref()
+                        .map(|(code, reason)| (*code, reason.as_str())),
+                );
+                Frame::encode(OpCode::Close, true, false, &payload, mask, out);
+                return;
+            }
+        };
+        match &mut self.deflate {
+            Some(deflate) if compress && !opcode.is_control() => {
+                let payload = deflate.compress(payload);
+                Frame::encode(opcode, true, true, &payload, mask, out);
+            }
+            _ => Frame::encode(opcode, true, false, payload, mask, out),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASK: [u8; 4] = [0x37, 0xfa, 0x21, 0x3d];
+
+    #[test]
+    fn test_accept_key() {
+        // the example of RFC 6455 section 1.3
+        assert_eq!(
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=",
+            accept_key("dGhlIHNhbXBsZSBub25jZQ==")
+        );
+        assert_eq!("Zm9vYmE=", base64_encode(b"fooba"));
+    }
+
+    #[test]
+    fn test_frame_decode() {
+        // examples of RFC 6455 section 5.7
+        let (frame, n) = Frame::decode(b"\x81\x05Hello", 1024).unwrap();
+        assert_eq!(
+            (true, OpCode::Text, &b"Hello"[..]),
+            (frame.fin, frame.opcode, &frame.payload[..])
+        );
+        assert_eq!(7, n);
+        let masked = b"\x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58";
+        let (frame, n) = Frame::decode(masked, 1024).unwrap();
+        assert!(frame.masked);
+        assert_eq!(b"Hello", &frame.payload[..]);
+        assert_eq!(11, n);
+        let (frame, _) = Frame::decode(b"\x01\x03Hel", 1024).unwrap();
+        assert!(!frame.fin);
+
+        for partial in [
+            &b"\x81"[..],
+            b"\x81\x05Hel",
+            b"\x82\x7e\x01",
+            b"\x81\x85\x37\xfa",
+        ] {
+            assert_eq!(Err(WsError::Pending), Frame::decode(partial, 1024));
+        }
+        assert_eq!(
+            Err(WsError::TooBig),
+            Frame::decode(b"\x82\x7e\x01\x00", 255)
+        );
+        assert!(matches!(
+            Frame::decode(b"\x83\x00", 1024),
+            Err(WsError::Protocol(_))
+        ));
+        assert!(matches!(
+            Frame::decode(b"\xa1\x00", 1024),
+            Err(WsError::Protocol(_))
+        ));
+        // fragmented and oversized control frames
+        assert!(matches!(
+            Frame::decode(b"\x09\x00", 1024),
+            Err(WsError::Protocol(_))
+        ));
+        assert!(matches!(
+            Frame::decode(b"\x89\x7e\x00\x7e", 1024),
+            Err(WsError::Protocol(_))
+        ));
+    }
+
+    #[test]
+    fn test_frame_encode() {
+        let mut out = vec![];
+        Frame::encode(OpCode::Text, true, false, b"Hello", Some(MASK), &mut out);
+        assert_eq!(
+            &b"\x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58"[..],
+            &out[..]
+        );
+
+        for len in [125, 126, 65535, 65536] {
+            let data = vec![7; len];
+            let mut out = vec![];
+            Frame::encode(OpCode::Binary, true, false, &data, None, &mut out);
+            let (frame, n) = Frame::decode(&out, 1 << 20).unwrap();
+            assert_eq!(out.len(), n);
+            assert_eq!(data, frame.payload);
+        }
+    }
+
+    #[test]
+    fn test_codec_messages() {
+        let mut server = WebSocketCodec::new(false, 1024, None);
+        let mut wire = vec![];
+        Frame::encode(OpCode::Text, false, false, b"Hel", Some(MASK), &mut wire);
+        Frame::encode(OpCode::Ping, true, false, b"p", Some(MASK), &mut wire);
+        Frame::encode(
+            OpCode::Continuation,
+            true,
+            false,
+            b"lo",
+            Some(MASK),
+            &mut wire,
+        );
+        Frame::encode(
+            OpCode::Close,
+            true,
+            false,
+            &encode_close(Some((1000, "bye"))),
+            Some(MASK),
+            &mut wire,
+        );
+
+        // byte by byte, every message shows up once its last byte arrived
+        let mut messages = vec![];
+        for b in &wire {
+            server.push(&[*b]);
+            while let Some(m) = server.next_message().unwrap() {
+                messages.push(m);
+            }
+        }
+        assert_eq!(
+            vec![
+                Message::Ping(b"p".to_vec()),
+                Message::Text("Hello".to_string()),
+                Message::Close(Some((1000, "bye".to_string()))),
+            ],
+            messages
+        );
+        assert_eq!(0, server.pending());
+    }
+
+    #[test]
+    fn test_codec_errors() {
+        let cases: Vec<(&[u8], u16)> = vec![
+            // unmasked frame to a server
+            (b"\x81\x02hi", 1002),
+            // continuation without a message
+            (b"\x80\x80\x00\x00\x00\x00", 1002),
+            // a new message before the last one ended
+            (b"\x01\x80\x00\x00\x00\x00\x01\x80\x00\x00\x00\x00", 1002),
+            // compressed without the extension
+            (b"\xc1\x80\x00\x00\x00\x00", 1002),
+            // invalid UTF-8 text
+            (b"\x81\x82\x00\x00\x00\x00\xc3\x28", 1007),
+            // close code 1005 must not be sent
+            (b"\x88\x82\x00\x00\x00\x00\x03\xed", 1002),
+            // the message grows over the limit across fragments
+            (
+                b"\x02\x84\x00\x00\x00\x00abcd\x80\x84\x00\x00\x00\x00abcd",
+                1009,
+            ),
+        ];
+        for (wire, code) in cases {
+            let mut server = WebSocketCodec::new(false, 6, None);
+            server.push(wire);
+            let e = server.next_message().unwrap_err();
+            assert_eq!(code, e.close_code(), "{:?}", e);
+        }
+
+        let mut client = WebSocketCodec::new(true, 1024, None);
+        client.push(b"\x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58");
+        assert!(matches!(client.next_message(), Err(WsError::Protocol(_))));
+    }
+
+    #[test]
+    fn test_permessage_deflate() {
+        // the example of RFC 7692 section 7.2.3.1
+        let mut client = WebSocketCodec::new(true, 1024, Some(DeflateConfig::default()));
+        client.push(b"\xc1\x07\xf2\x48\xcd\xc9\xc9\x07\x00");
+        assert_eq!(
+            Some(Message::Text("Hello".to_string())),
+            client.next_message().unwrap()
+        );
+
+        for takeover in [false, true] {
+            let config = DeflateConfig {
+                server_no_context_takeover: takeover,
+                client_no_context_takeover: takeover,
+            };
+            let mut client = WebSocketCodec::new(true, 1 << 16, Some(config));
+            let mut server = WebSocketCodec::new(false, 1 << 16, Some(config));
+            let text = "compressible ".repeat(100);
+            let mut sizes = vec![];
+            for _ in 0..3 {
+                let mut wire = vec![];
+                client.encode(&Message::Text(text.clone()), true, Some(MASK), &mut wire);
+                sizes.push(wire.len());
+                server.push(&wire);
+                assert_eq!(
+                    Some(Message::Text(text.clone())),
+                    server.next_message().unwrap()
+                );
+            }
+            assert!(sizes[0] < text.len() / 4);
+            // with context takeover repeated messages only reference the window
+            assert_eq!(takeover, sizes[1] == sizes[0]);
+
+            let mut wire = vec![];
+            server.encode(&Message::Binary(vec![]), true, None, &mut wire);
+            server.encode(&Message::Binary(vec![1, 2, 3]), false, None, &mut wire);
+            client.push(&wire);
+            assert_eq!(
+                Some(Message::Binary(vec![])),
+                client.next_message().unwrap()
+            );
+            assert_eq!(
+                Some(Message::Binary(vec![1, 2, 3])),
+                client.next_message().unwrap()
+            );
+        }
+
+        // a message inflating over the limit
+        let mut client = WebSocketCodec::new(true, 1 << 20, Some(DeflateConfig::default()));
+        let mut server = WebSocketCodec::new(false, 1000, Some(DeflateConfig::default()));
+        let mut wire = vec![];
+        client.encode(&Message::Binary(vec![0; 5000]), true, Some(MASK), &mut wire);
+        server.push(&wire);
+        assert_eq!(Err(WsError::TooBig), server.next_message());
+    }
+
+    #[test]
+    fn test_deflate_negotiation() {
+        assert_eq!(
+            Some(DeflateConfig::default()),
+            DeflateConfig::negotiate(DeflateConfig::OFFER)
+        );
+        let config = DeflateConfig::negotiate(
+            "permessage-deflate; server_max_window_bits=10, permessage-deflate; client_no_context_takeover",
+        )
+        .unwrap();
+        assert!(config.client_no_context_takeover);
+        assert_eq!(
+            "permessage-deflate; client_no_context_takeover",
+            config.to_header()
+        );
+        assert_eq!(None, DeflateConfig::negotiate("x-webkit-deflate-frame"));
+
+        assert_eq!(Ok(None), DeflateConfig::accept(""));
+        let config = DeflateConfig::accept(
+            "permessage-deflate; server_no_context_takeover; server_max_window_bits=12",
+        )
+        .unwrap()
+        .unwrap();
+        assert!(config.server_no_context_takeover);
+        assert!(DeflateConfig::accept("permessage-deflate; client_max_window_bits=9").is_err());
+        assert!(DeflateConfig::accept("foo").is_err());
+    }
+}
@@ -1,5 +1,10 @@
-use super::core::chunk::HttpChunk;
+use super::core::chunk::{Chunk, HttpChunk};
+use super::core::cookie::{self, CookieJar, SameSite, SetCookie};
+use super::core::form::{self, FieldValue, FormOptions, FormReader};
+use super::core::parser::{BodyKind, Limits};
 use super::core::request::HttpRequest;
+use super::core::websocket::{self, DeflateConfig, Message, WebSocketCodec};
+use super::core::HeaderMap;
 use super::core::ParseError;
 use crate::event_loop::AsyncTcpConn;
 use crate::internal_module::httpx::core::response::{BodyLen, HttpResponse};
@@ -8,13 +13,14 @@ use crate::{
     register_class, AsObject, Context, JsClassDef, JsClassProto, JsClassTool, JsFn, JsModuleDef,
     JsObject, JsValue, ModuleInit,
 };
-use std::collections::HashMap;
 use std::fmt::format;
 use std::io::BufReader;
 use std::ops::{Deref, DerefMut};
 use std::str::FromStr;
 
-struct Buffer(Vec<u8>, usize);
+/// Bytes received on a connection, the offset of the first unconsumed byte
+/// and the trailers of the last chunked body read by `parseChunk`.
+struct Buffer(Vec<u8>, usize, HeaderMap);
 
 impl Deref for Buffer {
     type Target = Vec<u8>;
@@ -39,7 +45,41 @@ impl AsRef<[u8]> for Buffer {
     }
 }
 
+/// `{ maxHeaderSize, maxHeadersCount }` as accepted by `parseRequest` and
+/// `parseResponse`, missing fields keep the defaults.
+fn parse_limits(opts: Option<&JsValue>) -> Limits {
+    let mut limits = Limits::default();
+    if let Some(JsValue::Object(opts)) = opts {
+        if let JsValue::Int(n) = opts.get("maxHeaderSize") {
+            if n > 0 {
+                limits.max_head_size = n as usize;
+                limits.max_line_size = limits.max_line_size.min(n as usize);
+            }
+        }
+        if let JsValue::Int(n) = opts.get("maxHeadersCount") {
+            if n > 0 {
+                limits.max_headers = n as usize;
+            }
+        }
+    }
+    limits
+}
+
 impl Buffer {
+    /// Marks `n` bytes as consumed. The consumed prefix is dropped once it is
+    /// everything or once it dominates the buffer, so a connection that is
+    /// reused for many responses does not keep growing it.
+    fn advance(&mut self, n: usize) {
+        self.1 += n;
+        if self.1 >= self.0.len() {
+            self.0.clear();
+            self.1 = 0;
+        } else if self.1 > 4096 && self.1 * 2 > self.0.len() {
+            self.0.drain(..self.1);
+            self.1 = 0;
+        }
+    }
+
     fn js_buffer(&self, ctx: &mut Context) -> JsValue {
         let buf = self.as_ref();
         if buf.len() > 0 {
@@ -80,9 +120,10 @@ impl Buffer {
         &mut self,
         _this_obj: &mut JsObject,
         ctx: &mut Context,
-        _argv: &[JsValue],
+        argv: &[JsValue],
     ) -> JsValue {
-        match HttpRequest::parse(self.as_ref()) {
+        let limits = parse_limits(argv.first());
+        match HttpRequest::parse_with_limits(self.as_ref(), &limits) {
             Ok(req) => HttpRequest::wrap_obj(ctx, req),
             Err(ParseError::Pending) => JsValue::UnDefined,
             Err(e) => {
@@ -92,15 +133,43 @@ impl Buffer {
         }
     }
 
+    /// Like `parseRequest` but returns as soon as the head is complete. The
+    /// head is consumed, the body stays in the buffer for `take` and
+    /// `parseChunk` according to the request's `bodyLength`.
+    fn js_parse_request_head(
+        &mut self,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let limits = parse_limits(argv.first());
+        match HttpRequest::parse_head(self.as_ref(), &limits) {
+            Ok((req, n)) => {
+                self.advance(n);
+                HttpRequest::wrap_obj(ctx, req)
+            }
+            Err(ParseError::Pending) => JsValue::UnDefined,
+            Err(e) => {
+                let err = ctx.new_error(format!("{:?}", e).as_str());
+                ctx.throw_error(err).into()
+            }
+        }
+    }
+
     fn js_parse_response(
         &mut self,
         _this_obj: &mut JsObject,
         ctx: &mut Context,
-        _argv: &[JsValue],
+        argv: &[JsValue],
     ) -> JsValue {
-        match HttpResponse::parse(self.as_ref()) {
+        let limits = parse_limits(argv.first());
+        let head_request = match argv.first() {
+            Some(JsValue::Object(opts)) => matches!(opts.get("head"), JsValue::Bool(true)),
+            _ => false,
+        };
+        match HttpResponse::parse_with_limits(self.as_ref(), &limits, head_request) {
             Ok((resp, n)) => {
-                self.1 += n;
+                self.advance(n);
                 HttpResponse::wrap_obj(ctx, resp)
             }
             Err(ParseError::Pending) => JsValue::UnDefined,
@@ -114,20 +183,52 @@ impl Buffer {
         ctx: &mut Context,
         _argv: &[JsValue],
     ) -> JsValue {
-        match HttpChunk::parse(self.as_ref()) {
-            Ok((buf, n)) => {
-                let r = if buf.len() == 0 {
-                    JsValue::Null
-                } else {
-                    let array_buf = ctx.new_array_buffer(buf);
-                    array_buf.into()
-                };
-                self.1 += n;
-                r
+        let (r, n, trailers) = match HttpChunk::decode(self.as_ref(), &Limits::default()) {
+            Ok((Chunk::Data { data, .. }, n)) => (ctx.new_array_buffer(data).into(), n, None),
+            Ok((Chunk::Last { trailers, .. }, n)) => {
+                let mut map = HeaderMap::new();
+                for h in trailers {
+                    map.append(h.name.to_lowercase(), h.value.into_owned());
+                }
+                (JsValue::Null, n, Some(map))
             }
-            Err(ParseError::Pending) => JsValue::UnDefined,
-            Err(e) => ctx.new_error(format!("{:?}", e).as_str()),
+            Err(ParseError::Pending) => return JsValue::UnDefined,
+            Err(e) => return ctx.new_error(format!("{:?}", e).as_str()),
+        };
+        if let Some(trailers) = trailers {
+            self.2 = trailers;
         }
+        self.advance(n);
+        r
+    }
+
+    fn js_trailers(&self, ctx: &mut Context) -> JsValue {
+        headers_to_js(ctx, &self.2)
+    }
+
+    fn js_raw_trailers(&self, ctx: &mut Context) -> JsValue {
+        raw_headers_to_js(ctx, &self.2)
+    }
+
+    fn js_take(
+        &mut self,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let available = self.as_ref().len();
+        let n = match argv.first() {
+            Some(JsValue::Int(n)) if *n >= 0 => (*n as usize).min(available),
+            Some(JsValue::Float(n)) if *n >= 0.0 => (*n as usize).min(available),
+            Some(JsValue::UnDefined) | None => available,
+            _ => return JsValue::Null,
+        };
+        if n == 0 {
+            return JsValue::Null;
+        }
+        let array_buf = ctx.new_array_buffer(&self.as_ref()[..n]);
+        self.advance(n);
+        array_buf.into()
     }
 
     fn js_clear(
@@ -138,6 +239,7 @@ impl Buffer {
     ) -> JsValue {
         self.0.clear();
         self.1 = 0;
+        self.2.clear();
         JsValue::UnDefined
     }
 }
@@ -150,30 +252,91 @@ impl JsClassDef for Buffer {
 
     fn constructor_fn(_ctx: &mut Context, argv: &[JsValue]) -> Result<Buffer, JsValue> {
         if let Some(JsValue::ArrayBuffer(s)) = argv.get(0) {
-            Ok(Buffer(s.as_ref().to_vec(), 0))
+            Ok(Buffer(s.as_ref().to_vec(), 0, HeaderMap::new()))
         } else {
-            Ok(Buffer(vec![], 0))
+            Ok(Buffer(vec![], 0, HeaderMap::new()))
         }
     }
 
     const FIELDS: &'static [crate::JsClassField<Self::RefType>] = &[
         ("length", Self::js_length, None),
         ("buffer", Self::js_buffer, None),
+        ("trailers", Self::js_trailers, None),
+        ("rawTrailers", Self::js_raw_trailers, None),
     ];
 
     const METHODS: &'static [crate::JsClassMethod<Self::RefType>] = &[
         ("append", 1, Self::js_append),
         ("write", 1, Self::js_append),
         ("parseRequest", 0, Self::js_parse_request),
+        ("parseRequestHead", 0, Self::js_parse_request_head),
         ("parseResponse", 0, Self::js_parse_response),
         ("parseChunk", 0, Self::js_parse_chunk_data),
+        ("take", 1, Self::js_take),
         ("clear", 0, Self::js_clear),
     ];
 
     unsafe fn mut_class_id_ptr() -> &'static mut u32 {
         static mut CLASS_ID: u32 = 0;
-        &mut CLASS_ID
+        &mut *std::ptr::addr_of_mut!(CLASS_ID)
+    }
+}
+
+/// Repeated fields are joined with ", ", except `set-cookie` which becomes an
+/// array as in node's `message.headers`.
+fn headers_to_js(ctx: &mut Context, headers: &HeaderMap) -> JsValue {
+    let mut obj = ctx.new_object();
+    for (k, v) in headers {
+        let key = k.as_str();
+        if key.eq_ignore_ascii_case("set-cookie") {
+            let mut cookies = match obj.get(key) {
+                JsValue::Array(a) => a,
+                _ => ctx.new_array(),
+            };
+            let len = cookies.get_length();
+            cookies.put(len, ctx.new_string(v).into());
+            obj.set(key, cookies.into());
+        } else if let JsValue::String(prev) = obj.get(key) {
+            let joined = format!("{}, {}", prev.as_str(), v);
+            obj.set(key, ctx.new_string(&joined).into());
+        } else {
+            obj.set(key, ctx.new_string(v).into());
+        }
+    }
+    obj.into()
+}
+
+/// `[name, value, name, value, ...]` in the order the fields were received.
+fn raw_headers_to_js(ctx: &mut Context, headers: &HeaderMap) -> JsValue {
+    let mut raw = ctx.new_array();
+    for (i, (k, v)) in headers.iter().enumerate() {
+        raw.put(i * 2, ctx.new_string(k).into());
+        raw.put(i * 2 + 1, ctx.new_string(v).into());
+    }
+    raw.into()
+}
+
+/// Array values turn into repeated fields.
+fn headers_from_js(ctx: &mut Context, val: JsValue, lowercase: bool) -> Option<HeaderMap> {
+    let obj = match val {
+        JsValue::Object(obj) => obj,
+        _ => return None,
+    };
+    let map = obj.to_map().ok()?;
+    let mut headers = HeaderMap::new();
+    for (k, v) in map {
+        let k = if lowercase { k.to_lowercase() } else { k };
+        let values = match v {
+            JsValue::Array(a) => a.to_vec().unwrap_or_default(),
+            v => vec![v],
+        };
+        for v in values {
+            if let JsValue::String(v_str) = ctx.value_to_string(&v) {
+                headers.append(k.clone(), v_str.to_string());
+            }
+        }
     }
+    Some(headers)
 }
 
 impl HttpRequest {
@@ -203,26 +366,37 @@ impl HttpRequest {
     }
 
     pub fn js_get_headers(&self, ctx: &mut Context) -> JsValue {
-        let mut headers = ctx.new_object();
-        for (k, v) in &self.headers {
-            headers.set(k.as_str(), ctx.new_string(v.as_str()).into());
-        }
-        headers.into()
+        headers_to_js(ctx, &self.headers)
     }
 
     pub fn js_set_headers(&mut self, ctx: &mut Context, val: JsValue) {
-        if let JsValue::Object(headers) = val {
-            if let Ok(h) = headers.to_map() {
-                self.headers.clear();
-                for (k, v) in h {
-                    if let JsValue::String(v_str) = ctx.value_to_string(&v) {
-                        self.headers.insert(k.to_lowercase(), v_str.to_string());
-                    }
-                }
-            }
+        if let Some(headers) = headers_from_js(ctx, val, true) {
+            self.headers = headers;
+        }
+    }
+
+    pub fn js_get_raw_headers(&self, ctx: &mut Context) -> JsValue {
+        raw_headers_to_js(ctx, &self.headers)
+    }
+
+    pub fn js_get_trailers(&self, ctx: &mut Context) -> JsValue {
+        headers_to_js(ctx, &self.trailers)
+    }
+
+    pub fn js_get_body_length(&self, ctx: &mut Context) -> JsValue {
+        match self.body_kind {
+            BodyKind::Empty => JsValue::Int(0),
+            BodyKind::Length(n) if n <= i32::MAX as u64 => JsValue::Int(n as i32),
+            BodyKind::Length(n) => JsValue::Float(n as f64),
+            BodyKind::Chunked => ctx.new_string("chunked").into(),
+            BodyKind::UntilClose => ctx.new_string("close").into(),
         }
     }
 
+    pub fn js_get_raw_trailers(&self, ctx: &mut Context) -> JsValue {
+        raw_headers_to_js(ctx, &self.trailers)
+    }
+
     pub fn js_get_method(&self, ctx: &mut Context) -> JsValue {
         ctx.new_string(&format!("{:?}", self.method)).into()
     }
@@ -313,7 +487,7 @@ impl JsClassDef for HttpRequest {
 
     unsafe fn mut_class_id_ptr() -> &'static mut u32 {
         static mut CLASS_ID: u32 = 0;
-        &mut CLASS_ID
+        &mut *std::ptr::addr_of_mut!(CLASS_ID)
     }
 
     fn constructor_fn(_ctx: &mut Context, _argv: &[JsValue]) -> Result<HttpRequest, JsValue> {
@@ -325,12 +499,18 @@ impl JsClassDef for HttpRequest {
             resource: request::Resource::Path(Default::default()),
             headers: Default::default(),
             body: vec![],
+            trailers: Default::default(),
+            body_kind: BodyKind::Empty,
         })
     }
 
     const FIELDS: &'static [crate::JsClassField<Self::RefType>] = &[
         ("body", Self::js_get_body, Some(Self::js_set_body)),
         ("headers", Self::js_get_headers, Some(Self::js_set_headers)),
+        ("rawHeaders", Self::js_get_raw_headers, None),
+        ("trailers", Self::js_get_trailers, None),
+        ("rawTrailers", Self::js_get_raw_trailers, None),
+        ("bodyLength", Self::js_get_body_length, None),
         ("method", Self::js_get_method, Some(Self::js_set_method)),
         ("version", Self::js_get_version, Some(Self::js_set_version)),
         ("uri", Self::js_get_uri, Some(Self::js_set_uri)),
@@ -348,6 +528,7 @@ impl HttpResponse {
         match self.body_len {
             BodyLen::Length(n) => JsValue::Int(n as i32),
             BodyLen::Chunked => ctx.new_string("chunked").into(),
+            BodyLen::UntilClose => ctx.new_string("close").into(),
         }
     }
 
@@ -364,26 +545,19 @@ impl HttpResponse {
     }
 
     fn js_get_headers(&self, ctx: &mut Context) -> JsValue {
-        let mut headers = ctx.new_object();
-        for (k, v) in &self.headers {
-            headers.set(k.as_str(), ctx.new_string(v.as_str()).into());
-        }
-        headers.into()
+        headers_to_js(ctx, &self.headers)
     }
 
     fn js_set_headers(&mut self, ctx: &mut Context, val: JsValue) {
-        if let JsValue::Object(headers) = val {
-            if let Ok(h) = headers.to_map() {
-                self.headers.clear();
-                for (k, v) in h {
-                    if let JsValue::String(v_str) = ctx.value_to_string(&v) {
-                        self.headers.insert(k, v_str.to_string());
-                    }
-                }
-            }
+        if let Some(headers) = headers_from_js(ctx, val, false) {
+            self.headers = headers;
         }
     }
 
+    fn js_get_raw_headers(&self, ctx: &mut Context) -> JsValue {
+        raw_headers_to_js(ctx, &self.headers)
+    }
+
     fn js_get_status(&self, _ctx: &mut Context) -> JsValue {
         JsValue::Int(self.status_code as i32)
     }
@@ -486,6 +660,7 @@ impl JsClassDef for HttpResponse {
             Some(Self::js_set_body_length),
         ),
         ("headers", Self::js_get_headers, Some(Self::js_set_headers)),
+        ("rawHeaders", Self::js_get_raw_headers, None),
         ("status", Self::js_get_status, Some(Self::js_set_status)),
         ("version", Self::js_get_version, Some(Self::js_set_version)),
         (
@@ -500,7 +675,7 @@ impl JsClassDef for HttpResponse {
 
     unsafe fn mut_class_id_ptr() -> &'static mut u32 {
         static mut CLASS_ID: u32 = 0;
-        &mut CLASS_ID
+        &mut *std::ptr::addr_of_mut!(CLASS_ID)
     }
 
     fn constructor_fn(_ctx: &mut Context, _argv: &[JsValue]) -> Result<HttpResponse, JsValue> {
@@ -545,6 +720,10 @@ impl WasiChunkResponse {
             Some(JsValue::String(s)) => {
                 let data = s.to_string();
                 let data_len = data.len();
+                // a zero sized chunk would end the body
+                if data_len == 0 {
+                    return JsValue::Bool(true);
+                }
                 self.0.invoke(
                     "write",
                     &[ctx
@@ -557,6 +736,9 @@ impl WasiChunkResponse {
             Some(JsValue::ArrayBuffer(buff)) => {
                 let data = buff.as_ref();
                 let data_len = data.len();
+                if data_len == 0 {
+                    return JsValue::Bool(true);
+                }
                 self.0.invoke(
                     "write",
                     &[ctx
@@ -604,12 +786,26 @@ impl WasiChunkResponse {
         if let JsValue::UnDefined = self.0 {
             return ctx.throw_internal_type_error("socket is shutdown").into();
         }
-        let e = this_obj.invoke("write", argv);
+        // `end(data, trailers)` sends the trailer fields after the last chunk,
+        // they are checked before anything is written
+        let trailers = argv
+            .get(1)
+            .and_then(|v| headers_from_js(ctx, v.clone(), false))
+            .unwrap_or_default();
+        let mut last = vec![];
+        match HttpChunk::encode_last(&trailers, &mut last) {
+            Ok(()) => {}
+            Err(ParseError::InvalidHeaderName) => {
+                return ctx.throw_type_error("invalid trailer name").into();
+            }
+            Err(_) => return ctx.throw_type_error("invalid trailer value").into(),
+        }
+
+        let e = this_obj.invoke("write", &argv[..argv.len().min(1)]);
         if e.is_exception() {
             return e;
         }
-
-        self.0.invoke("end", &[ctx.new_string("0\r\n\r\n").into()]);
+        self.0.invoke("end", &[ctx.new_array_buffer(&last).into()]);
         // drop socket
         self.0 = JsValue::UnDefined;
         JsValue::Bool(true)
@@ -627,12 +823,12 @@ impl JsClassDef for WasiChunkResponse {
     const METHODS: &'static [crate::JsClassMethod<Self::RefType>] = &[
         ("on", 2, Self::js_on),
         ("write", 1, Self::js_write),
-        ("end", 1, Self::js_end),
+        ("end", 2, Self::js_end),
     ];
 
     unsafe fn mut_class_id_ptr() -> &'static mut u32 {
         static mut CLASS_ID: u32 = 0;
-        &mut CLASS_ID
+        &mut *std::ptr::addr_of_mut!(CLASS_ID)
     }
 
     fn constructor_fn(_ctx: &mut Context, _argv: &[JsValue]) -> Result<WasiChunkResponse, JsValue> {
@@ -740,7 +936,7 @@ mod js_url {
 
         unsafe fn mut_class_id_ptr() -> &'static mut u32 {
             static mut CLASS_ID: u32 = 0;
-            &mut CLASS_ID
+            &mut *std::ptr::addr_of_mut!(CLASS_ID)
         }
 
         fn constructor_fn(ctx: &mut Context, argv: &[JsValue]) -> Result<URL, JsValue> {
@@ -756,6 +952,636 @@ mod js_url {
         }
     }
 }
+
+fn websocket_error(ctx: &mut Context, e: websocket::WsError) -> JsValue {
+    let err = ctx.new_error(e.to_string().as_str());
+    if let JsValue::Object(mut obj) = err.clone() {
+        obj.set("closeCode", JsValue::Int(e.close_code() as i32));
+    }
+    ctx.throw_error(err).into()
+}
+
+fn random_mask() -> [u8; 4] {
+    let mut mask = [0; 4];
+    // wasi random_get only fails when the host has no entropy source
+    getrandom::getrandom(&mut mask).expect("no random source");
+    mask
+}
+
+fn bytes_of(v: Option<&JsValue>) -> Vec<u8> {
+    match v {
+        Some(JsValue::ArrayBuffer(buf)) => buf.to_vec(),
+        Some(JsValue::String(s)) => s.to_string().into_bytes(),
+        _ => vec![],
+    }
+}
+
+/// `WebSocketCodec` together with the extension header it negotiated.
+struct JsWebSocketCodec(WebSocketCodec, bool, Option<DeflateConfig>);
+
+impl JsWebSocketCodec {
+    fn js_push(
+        &mut self,
+        _this_obj: &mut JsObject,
+        _ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        if let Some(JsValue::ArrayBuffer(data)) = argv.first() {
+            self.0.push(data.as_ref());
+        }
+        JsValue::UnDefined
+    }
+
+    /// The next message as `{ type, data }`, `{ type: "close", code, reason }`
+    /// for close frames, or undefined when more bytes are needed.
+    fn js_next(
+        &mut self,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        _argv: &[JsValue],
+    ) -> JsValue {
+        let message = match self.0.next_message() {
+            Ok(Some(message)) => message,
+            Ok(None) => return JsValue::UnDefined,
+            Err(e) => return websocket_error(ctx, e),
+        };
+        let mut obj = ctx.new_object();
+        let (kind, data) = match message {
+            Message::Text(text) => ("text", ctx.new_string(&text).into()),
+            Message::Binary(data) => ("binary", ctx.new_array_buffer(&data).into()),
+            Message::Ping(data) => ("ping", ctx.new_array_buffer(&data).into()),
+            Message::Pong(data) => ("pong", ctx.new_array_buffer(&data).into()),
+            Message::Close(close) => {
+                if let Some((code, reason)) = close {
+                    obj.set("code", JsValue::Int(code as i32));
+                    obj.set("reason", ctx.new_string(&reason).into());
+                }
+                ("close", JsValue::UnDefined)
+            }
+        };
+        obj.set("type", ctx.new_string(kind).into());
+        obj.set("data", data);
+        obj.into()
+    }
+
+    /// `encode(type, data, compress)` or `encode("close", code, reason)`,
+    /// returns the frame as an ArrayBuffer. Clients mask it.
+    fn js_encode(
+        &mut self,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let kind = match argv.first() {
+            Some(JsValue::String(s)) => s.to_string(),
+            _ => return ctx.throw_type_error("type must be a string").into(),
+        };
+        let message = match kind.as_str() {
+            "text" => match argv.get(1) {
+                Some(JsValue::String(s)) => Message::Text(s.to_string()),
+                _ => return ctx.throw_type_error("text data must be a string").into(),
+            },
+            "binary" => Message::Binary(bytes_of(argv.get(1))),
+            "ping" => Message::Ping(bytes_of(argv.get(1))),
+            "pong" => Message::Pong(bytes_of(argv.get(1))),
+            "close" => match argv.get(1) {
+                Some(JsValue::Int(code)) => {
+                    let reason = match argv.get(2) {
+                        Some(JsValue::String(s)) => s.to_string(),
+                        _ => String::new(),
+                    };
+                    Message::Close(Some((*code as u16, reason)))
+                }
+                _ => Message::Close(None),
+            },
+            _ => return ctx.throw_type_error("unknown frame type").into(),
+        };
+        let compress = !matches!(argv.get(2), Some(JsValue::Bool(false)));
+        let mask = if self.1 { Some(random_mask()) } else { None };
+        let mut out = vec![];
+        self.0.encode(&message, compress, mask, &mut out);
+        ctx.new_array_buffer(&out).into()
+    }
+
+    fn js_extensions(&self, ctx: &mut Context) -> JsValue {
+        match &self.2 {
+            Some(config) => ctx.new_string(&config.to_header()).into(),
+            None => ctx.new_string("").into(),
+        }
+    }
+}
+
+impl JsClassDef for JsWebSocketCodec {
+    type RefType = JsWebSocketCodec;
+
+    const CLASS_NAME: &'static str = "WebSocketCodec";
+    const CONSTRUCTOR_ARGC: u8 = 1;
+
+    /// `new WebSocketCodec({ client, maxPayload, extensions, perMessageDeflate })`.
+    /// A server passes the `Sec-WebSocket-Extensions` offer of the client in
+    /// `extensions`, a client the answer of the server, which throws when it
+    /// cannot be honoured.
+    fn constructor_fn(ctx: &mut Context, argv: &[JsValue]) -> Result<Self, JsValue> {
+        let mut client = false;
+        let mut max_payload = 100 * 1024 * 1024;
+        let mut extensions = String::new();
+        let mut per_message_deflate = true;
+        if let Some(JsValue::Object(opts)) = argv.first() {
+            client = matches!(opts.get("client"), JsValue::Bool(true));
+            match opts.get("maxPayload") {
+                JsValue::Int(n) if n > 0 => max_payload = n as usize,
+                JsValue::Float(n) if n > 0.0 => max_payload = n as usize,
+                _ => {}
+            }
+            if let JsValue::String(s) = opts.get("extensions") {
+                extensions = s.to_string();
+            }
+            per_message_deflate = !matches!(opts.get("perMessageDeflate"), JsValue::Bool(false));
+        }
+        let deflate = if !per_message_deflate {
+            None
+        } else if client {
+            DeflateConfig::accept(&extensions).map_err(|e| websocket_error(ctx, e))?
+        } else {
+            DeflateConfig::negotiate(&extensions)
+        };
+        Ok(JsWebSocketCodec(
+            WebSocketCodec::new(client, max_payload, deflate),
+            client,
+            deflate,
+        ))
+    }
+
+    const FIELDS: &'static [crate::JsClassField<Self::RefType>] =
+        &[("extensions", Self::js_extensions, None)];
+
+    const METHODS: &'static [crate::JsClassMethod<Self::RefType>] = &[
+        ("push", 1, Self::js_push),
+        ("next", 0, Self::js_next),
+        ("encode", 3, Self::js_encode),
+    ];
+
+    unsafe fn mut_class_id_ptr() -> &'static mut u32 {
+        static mut CLASS_ID: u32 = 0;
+        &mut *std::ptr::addr_of_mut!(CLASS_ID)
+    }
+}
+
+/// A fresh random `Sec-WebSocket-Key`.
+fn js_websocket_key(ctx: &mut Context, _this: JsValue, _argv: &[JsValue]) -> JsValue {
+    let mut nonce = [0; 16];
+    getrandom::getrandom(&mut nonce).expect("no random source");
+    ctx.new_string(&websocket::base64_encode(&nonce)).into()
+}
+
+/// The `Sec-WebSocket-Accept` value for a `Sec-WebSocket-Key`.
+fn js_websocket_accept(ctx: &mut Context, _this: JsValue, argv: &[JsValue]) -> JsValue {
+    match argv.first() {
+        Some(JsValue::String(key)) => ctx.new_string(&websocket::accept_key(key.as_str())).into(),
+        _ => ctx.throw_type_error("key must be a string").into(),
+    }
+}
+
+/// `FormReader` until `finish()` hands out the fields.
+struct JsFormReader(Option<FormReader>);
+
+fn form_error(ctx: &mut Context, e: form::FormError) -> JsValue {
+    let err = ctx.new_error(e.to_string().as_str());
+    ctx.throw_error(err).into()
+}
+
+impl JsFormReader {
+    fn js_push(
+        &mut self,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let reader = match self.0.as_mut() {
+            Some(reader) => reader,
+            None => return ctx.throw_type_error("the form is finished").into(),
+        };
+        if let Some(JsValue::ArrayBuffer(data)) = argv.first() {
+            if let Err(e) = reader.push(data.as_ref()) {
+                // the spilled files go with the reader
+                self.0 = None;
+                return form_error(ctx, e);
+            }
+        }
+        JsValue::UnDefined
+    }
+
+    /// The fields as `{ name, filename, type, headers, data }`, or with
+    /// `path` and `size` instead of `data` for spilled file parts.
+    fn js_finish(
+        &mut self,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        _argv: &[JsValue],
+    ) -> JsValue {
+        let fields = match self.0.take().map(FormReader::finish) {
+            Some(Ok(fields)) => fields,
+            Some(Err(e)) => return form_error(ctx, e),
+            None => return ctx.throw_type_error("the form is finished").into(),
+        };
+        let mut array = ctx.new_array();
+        for (i, field) in fields.into_iter().enumerate() {
+            let mut obj = ctx.new_object();
+            obj.set("name", ctx.new_string(&field.head.name).into());
+            if let Some(filename) = &field.head.filename {
+                obj.set("filename", ctx.new_string(filename).into());
+            }
+            if let Some(content_type) = &field.head.content_type {
+                obj.set("type", ctx.new_string(content_type).into());
+            }
+            let mut headers = ctx.new_array();
+            for (j, (k, v)) in field.head.headers.iter().enumerate() {
+                headers.put(j * 2, ctx.new_string(k).into());
+                headers.put(j * 2 + 1, ctx.new_string(v).into());
+            }
+            obj.set("headers", headers.into());
+            match field.value {
+                FieldValue::Memory(data) => {
+                    obj.set("data", ctx.new_array_buffer(&data).into());
+                }
+                FieldValue::Spilled { path, size } => {
+                    obj.set("path", ctx.new_string(&path.to_string_lossy()).into());
+                    obj.set("size", JsValue::Float(size as f64));
+                }
+            }
+            array.put(i, obj.into());
+        }
+        array.into()
+    }
+}
+
+impl JsClassDef for JsFormReader {
+    type RefType = JsFormReader;
+
+    const CLASS_NAME: &'static str = "MultipartParser";
+    const CONSTRUCTOR_ARGC: u8 = 2;
+
+    /// `new MultipartParser(contentType, { spillThreshold, uploadDir,
+    /// maxFieldSize, maxParts })`, throws when the content type carries no
+    /// multipart boundary.
+    fn constructor_fn(ctx: &mut Context, argv: &[JsValue]) -> Result<Self, JsValue> {
+        let boundary = match argv.first() {
+            Some(JsValue::String(s)) => form::boundary(s.as_str()),
+            _ => None,
+        };
+        let boundary = match boundary {
+            Some(boundary) => boundary,
+            None => return Err(form_error(ctx, form::FormError::InvalidBoundary)),
+        };
+        let mut options = FormOptions::default();
+        if let Some(JsValue::Object(opts)) = argv.get(1) {
+            let size = |v: JsValue| match v {
+                JsValue::Int(n) if n >= 0 => Some(n as usize),
+                JsValue::Float(n) if n >= 0.0 => Some(n as usize),
+                _ => None,
+            };
+            if let Some(n) = size(opts.get("spillThreshold")) {
+                options.spill_threshold = n;
+            }
+            if let Some(n) = size(opts.get("maxFieldSize")) {
+                options.max_field_size = n;
+            }
+            if let Some(n) = size(opts.get("maxParts")) {
+                options.max_parts = n;
+            }
+            if let JsValue::String(dir) = opts.get("uploadDir") {
+                options.upload_dir = dir.as_str().into();
+            }
+        }
+        Ok(JsFormReader(Some(FormReader::new(&boundary, options))))
+    }
+
+    const FIELDS: &'static [crate::JsClassField<Self::RefType>] = &[];
+
+    const METHODS: &'static [crate::JsClassMethod<Self::RefType>] =
+        &[("push", 1, Self::js_push), ("finish", 0, Self::js_finish)];
+
+    unsafe fn mut_class_id_ptr() -> &'static mut u32 {
+        static mut CLASS_ID: u32 = 0;
+        &mut *std::ptr::addr_of_mut!(CLASS_ID)
+    }
+}
+
+/// `parseUrlencoded(body)` with an ArrayBuffer or string, returns the
+/// `[name, value]` pairs.
+fn js_parse_urlencoded(ctx: &mut Context, _this: JsValue, argv: &[JsValue]) -> JsValue {
+    let pairs = match argv.first() {
+        Some(JsValue::ArrayBuffer(buf)) => form::parse_urlencoded(buf.as_ref()),
+        Some(JsValue::String(s)) => form::parse_urlencoded(s.as_str().as_bytes()),
+        _ => {
+            return ctx
+                .throw_type_error("body must be an ArrayBuffer or a string")
+                .into()
+        }
+    };
+    let mut array = ctx.new_array();
+    for (i, (name, value)) in pairs.iter().enumerate() {
+        let mut pair = ctx.new_array();
+        pair.put(0, ctx.new_string(name).into());
+        pair.put(1, ctx.new_string(value).into());
+        array.put(i, pair.into());
+    }
+    array.into()
+}
+
+/// `readUpload(path, start, end)` reads a range of a spilled file part.
+fn js_read_upload(ctx: &mut Context, _this: JsValue, argv: &[JsValue]) -> JsValue {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let path = match argv.first() {
+        Some(JsValue::String(s)) => s.to_string(),
+        _ => return ctx.throw_type_error("path must be a string").into(),
+    };
+    let offset = |v: Option<&JsValue>| match v {
+        Some(JsValue::Int(n)) if *n >= 0 => *n as u64,
+        Some(JsValue::Float(n)) if *n >= 0.0 => *n as u64,
+        _ => 0,
+    };
+    let (start, end) = (offset(argv.get(1)), offset(argv.get(2)));
+    let mut data = vec![];
+    let res = std::fs::File::open(&path).and_then(|mut file| {
+        file.seek(SeekFrom::Start(start))?;
+        file.take(end.saturating_sub(start)).read_to_end(&mut data)
+    });
+    match res {
+        Ok(_) => ctx.new_array_buffer(&data).into(),
+        Err(e) => {
+            let err = ctx.new_error(format!("{}: {}", path, e).as_str());
+            ctx.throw_error(err).into()
+        }
+    }
+}
+
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+fn number_of(v: &JsValue) -> Option<f64> {
+    match v {
+        JsValue::Int(n) => Some(*n as f64),
+        JsValue::Float(n) if n.is_finite() => Some(*n),
+        _ => None,
+    }
+}
+
+/// The host, path and whether the request counts as secure for a cookie
+/// lookup, from an absolute http(s) or ws(s) URL.
+fn cookie_target(
+    ctx: &mut Context,
+    v: Option<&JsValue>,
+) -> Result<(String, String, bool), JsValue> {
+    let url = match v {
+        Some(JsValue::String(s)) => url::Url::parse(s.as_str()).ok(),
+        _ => None,
+    };
+    match url.as_ref().and_then(|url| Some((url, url.host_str()?))) {
+        Some((url, host)) => {
+            let secure = matches!(url.scheme(), "https" | "wss") || cookie::is_loopback(host);
+            Ok((host.to_string(), url.path().to_string(), secure))
+        }
+        None => Err(ctx.throw_type_error("url must be an absolute URL").into()),
+    }
+}
+
+fn time_to_js(secs: i64) -> JsValue {
+    JsValue::Float(secs as f64 * 1000.0)
+}
+
+fn set_cookie_to_js(ctx: &mut Context, cookie: &SetCookie) -> JsValue {
+    let mut obj = ctx.new_object();
+    obj.set("name", ctx.new_string(&cookie.name).into());
+    obj.set("value", ctx.new_string(&cookie.value).into());
+    if let Some(expires) = cookie.expires {
+        obj.set("expires", time_to_js(expires));
+    }
+    if let Some(max_age) = cookie.max_age {
+        obj.set("maxAge", JsValue::Float(max_age as f64));
+    }
+    if let Some(domain) = &cookie.domain {
+        obj.set("domain", ctx.new_string(domain).into());
+    }
+    if let Some(path) = &cookie.path {
+        obj.set("path", ctx.new_string(path).into());
+    }
+    obj.set("secure", JsValue::Bool(cookie.secure));
+    obj.set("httpOnly", JsValue::Bool(cookie.http_only));
+    if let Some(same_site) = cookie.same_site {
+        obj.set("sameSite", ctx.new_string(same_site.as_str()).into());
+    }
+    obj.set("partitioned", JsValue::Bool(cookie.partitioned));
+    obj.into()
+}
+
+/// The cookies fetch keeps between requests, exported as `CookieJar`.
+struct JsCookieJar(CookieJar);
+
+impl JsCookieJar {
+    /// `setCookie(setCookieHeader, url)`, false when the cookie was refused.
+    fn js_set_cookie(
+        &mut self,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let header = match argv.first() {
+            Some(JsValue::String(s)) => s.to_string(),
+            _ => return ctx.throw_type_error("cookie must be a string").into(),
+        };
+        match cookie_target(ctx, argv.get(1)) {
+            Ok((host, path, secure)) => {
+                JsValue::Bool(self.0.set(&header, &host, &path, secure, unix_now()))
+            }
+            Err(e) => e,
+        }
+    }
+
+    /// `getCookieString(url)`, the `Cookie` header for a request to `url`.
+    fn js_get_cookie_string(
+        &mut self,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        match cookie_target(ctx, argv.first()) {
+            Ok((host, path, secure)) => {
+                let header = self.0.header(&host, &path, secure, unix_now());
+                ctx.new_string(header.as_deref().unwrap_or("")).into()
+            }
+            Err(e) => e,
+        }
+    }
+
+    /// `getCookies(url)` lists the cookies for `url` as objects, all that
+    /// have not expired without one.
+    fn js_get_cookies(
+        &mut self,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let now = unix_now();
+        let cookies = match argv.first() {
+            None | Some(JsValue::UnDefined) => {
+                self.0.iter().filter(|c| !c.is_expired(now)).collect()
+            }
+            url => match cookie_target(ctx, url) {
+                Ok((host, path, secure)) => self.0.matches(&host, &path, secure, now),
+                Err(e) => return e,
+            },
+        };
+        let mut array = ctx.new_array();
+        for (i, c) in cookies.into_iter().enumerate() {
+            let mut obj = ctx.new_object();
+            obj.set("name", ctx.new_string(&c.name).into());
+            obj.set("value", ctx.new_string(&c.value).into());
+            obj.set("domain", ctx.new_string(&c.domain).into());
+            obj.set("path", ctx.new_string(&c.path).into());
+            if let Some(expires) = c.expires {
+                obj.set("expires", time_to_js(expires));
+            }
+            obj.set("hostOnly", JsValue::Bool(c.host_only));
+            obj.set("secure", JsValue::Bool(c.secure));
+            obj.set("httpOnly", JsValue::Bool(c.http_only));
+            if let Some(same_site) = c.same_site {
+                obj.set("sameSite", ctx.new_string(same_site.as_str()).into());
+            }
+            array.put(i, obj.into());
+        }
+        array.into()
+    }
+
+    fn js_remove_expired(
+        &mut self,
+        _this_obj: &mut JsObject,
+        _ctx: &mut Context,
+        _argv: &[JsValue],
+    ) -> JsValue {
+        self.0.remove_expired(unix_now());
+        JsValue::UnDefined
+    }
+
+    fn js_clear(
+        &mut self,
+        _this_obj: &mut JsObject,
+        _ctx: &mut Context,
+        _argv: &[JsValue],
+    ) -> JsValue {
+        self.0.clear();
+        JsValue::UnDefined
+    }
+
+    fn js_size(&self, _ctx: &mut Context) -> JsValue {
+        JsValue::Int(self.0.len() as i32)
+    }
+}
+
+impl JsClassDef for JsCookieJar {
+    type RefType = JsCookieJar;
+
+    const CLASS_NAME: &'static str = "CookieJar";
+    const CONSTRUCTOR_ARGC: u8 = 0;
+
+    fn constructor_fn(_ctx: &mut Context, _argv: &[JsValue]) -> Result<Self, JsValue> {
+        Ok(JsCookieJar(CookieJar::new()))
+    }
+
+    const FIELDS: &'static [crate::JsClassField<Self::RefType>] = &[("size", Self::js_size, None)];
+
+    const METHODS: &'static [crate::JsClassMethod<Self::RefType>] = &[
+        ("setCookie", 2, Self::js_set_cookie),
+        ("getCookieString", 1, Self::js_get_cookie_string),
+        ("getCookies", 1, Self::js_get_cookies),
+        ("removeExpired", 0, Self::js_remove_expired),
+        ("clear", 0, Self::js_clear),
+    ];
+
+    unsafe fn mut_class_id_ptr() -> &'static mut u32 {
+        static mut CLASS_ID: u32 = 0;
+        &mut *std::ptr::addr_of_mut!(CLASS_ID)
+    }
+}
+
+/// `parseCookie(header)`, the `[name, value]` pairs of a `Cookie` header.
+fn js_parse_cookie(ctx: &mut Context, _this: JsValue, argv: &[JsValue]) -> JsValue {
+    let header = match argv.first() {
+        Some(JsValue::String(s)) => s.to_string(),
+        _ => return ctx.throw_type_error("header must be a string").into(),
+    };
+    let mut array = ctx.new_array();
+    for (i, (name, value)) in cookie::parse_cookie_header(&header).iter().enumerate() {
+        let mut pair = ctx.new_array();
+        pair.put(0, ctx.new_string(name).into());
+        pair.put(1, ctx.new_string(value).into());
+        array.put(i, pair.into());
+    }
+    array.into()
+}
+
+/// `parseSetCookie(header)`, the cookie and its attributes as an object or
+/// null when the header is malformed.
+fn js_parse_set_cookie(ctx: &mut Context, _this: JsValue, argv: &[JsValue]) -> JsValue {
+    match argv.first() {
+        Some(JsValue::String(s)) => match SetCookie::parse(s.as_str()) {
+            Some(cookie) => set_cookie_to_js(ctx, &cookie),
+            None => JsValue::Null,
+        },
+        _ => ctx.throw_type_error("header must be a string").into(),
+    }
+}
+
+/// `serializeCookie(name, value, { expires, maxAge, domain, path, secure,
+/// httpOnly, sameSite, partitioned })` with `expires` in milliseconds,
+/// returns the `Set-Cookie` header value.
+fn js_serialize_cookie(ctx: &mut Context, _this: JsValue, argv: &[JsValue]) -> JsValue {
+    let (name, value) = match (argv.first(), argv.get(1)) {
+        (Some(JsValue::String(name)), Some(JsValue::String(value))) => {
+            (name.to_string(), value.to_string())
+        }
+        _ => {
+            return ctx
+                .throw_type_error("name and value must be strings")
+                .into()
+        }
+    };
+    let mut cookie = SetCookie::new(&name, &value);
+    if let Some(JsValue::Object(opts)) = argv.get(2) {
+        if let Some(ms) = number_of(&opts.get("expires")) {
+            cookie.expires = Some((ms / 1000.0).floor() as i64);
+        }
+        if let Some(secs) = number_of(&opts.get("maxAge")) {
+            cookie.max_age = Some(secs.floor() as i64);
+        }
+        if let JsValue::String(domain) = opts.get("domain") {
+            cookie.domain = Some(domain.to_string());
+        }
+        if let JsValue::String(path) = opts.get("path") {
+            cookie.path = Some(path.to_string());
+        }
+        cookie.secure = matches!(opts.get("secure"), JsValue::Bool(true));
+        cookie.http_only = matches!(opts.get("httpOnly"), JsValue::Bool(true));
+        cookie.partitioned = matches!(opts.get("partitioned"), JsValue::Bool(true));
+        match opts.get("sameSite") {
+            JsValue::UnDefined | JsValue::Null | JsValue::Bool(false) => {}
+            JsValue::Bool(true) => cookie.same_site = Some(SameSite::Strict),
+            JsValue::String(s) if SameSite::parse(s.as_str()).is_some() => {
+                cookie.same_site = SameSite::parse(s.as_str());
+            }
+            _ => return ctx.throw_type_error("invalid cookie sameSite").into(),
+        }
+    }
+    match cookie.validate() {
+        Ok(()) => ctx.new_string(&cookie.to_string()).into(),
+        Err(e) => ctx.throw_type_error(&e.to_string()).into(),
+    }
+}
+
 use js_url::URL;
 
 struct HttpX;
@@ -776,6 +1602,36 @@ impl ModuleInit for HttpX {
 
         let class_ctor = register_class::<URL>(ctx);
         m.add_export(URL::CLASS_NAME, class_ctor);
+
+        let class_ctor = register_class::<JsWebSocketCodec>(ctx);
+        m.add_export(JsWebSocketCodec::CLASS_NAME, class_ctor);
+
+        let f = ctx.wrap_function("websocketKey", js_websocket_key);
+        m.add_export("websocketKey", f.into());
+
+        let f = ctx.wrap_function("websocketAccept", js_websocket_accept);
+        m.add_export("websocketAccept", f.into());
+
+        let class_ctor = register_class::<JsFormReader>(ctx);
+        m.add_export(JsFormReader::CLASS_NAME, class_ctor);
+
+        let f = ctx.wrap_function("parseUrlencoded", js_parse_urlencoded);
+        m.add_export("parseUrlencoded", f.into());
+
+        let f = ctx.wrap_function("readUpload", js_read_upload);
+        m.add_export("readUpload", f.into());
+
+        let class_ctor = register_class::<JsCookieJar>(ctx);
+        m.add_export(JsCookieJar::CLASS_NAME, class_ctor);
+
+        let f = ctx.wrap_function("parseCookie", js_parse_cookie);
+        m.add_export("parseCookie", f.into());
+
+        let f = ctx.wrap_function("parseSetCookie", js_parse_set_cookie);
+        m.add_export("parseSetCookie", f.into());
+
+        let f = ctx.wrap_function("serializeCookie", js_serialize_cookie);
+        m.add_export("serializeCookie", f.into());
     }
 }
 
@@ -789,6 +1645,16 @@ pub fn init_module(ctx: &mut Context) {
             HttpResponse::CLASS_NAME,
             WasiChunkResponse::CLASS_NAME,
             URL::CLASS_NAME,
+            JsWebSocketCodec::CLASS_NAME,
+            "websocketKey",
+            "websocketAccept",
+            JsFormReader::CLASS_NAME,
+            "parseUrlencoded",
+            "readUpload",
+            JsCookieJar::CLASS_NAME,
+            "parseCookie",
+            "parseSetCookie",
+            "serializeCookie",
         ],
     )
 }
@@ -1,10 +1,15 @@
 pub mod core;
+pub mod dns_module;
 pub mod encoding;
 pub mod fs;
 pub mod httpx;
 #[cfg(feature = "img")]
 pub mod img_module;
 pub mod os;
+pub mod process;
 #[cfg(feature = "tensorflow")]
 pub mod tensorflow_module;
 pub mod wasi_net_module;
+pub mod zlib_module;
+#[cfg(feature = "tls")]
+pub mod wasi_tls_module;
@@ -7,15 +7,26 @@ fn memory_size(_ctx: &mut Context, _this_val: JsValue, _argv: &[JsValue]) -> JsV
     JsValue::Int(arch::wasm32::memory_size::<0>() as i32)
 }
 
+fn features(ctx: &mut Context) -> JsObject {
+    let mut features = ctx.new_object();
+    let tls = cfg!(feature = "tls");
+    features.set("tls", tls.into());
+    features.set("tls_alpn", tls.into());
+    features.set("tls_sni", tls.into());
+    features
+}
+
 struct OS;
 
 impl ModuleInit for OS {
     fn init_module(ctx: &mut Context, m: &mut JsModuleDef) {
         let f = ctx.wrap_function("_memorySize", memory_size);
         m.add_export("_memorySize\0", f.into());
+        let features = features(ctx);
+        m.add_export("_features\0", features.into());
     }
 }
 
 pub fn init_module(ctx: &mut Context) {
-    ctx.register_module("_node:os\0", OS, &["_memorySize\0"])
+    ctx.register_module("_node:os\0", OS, &["_memorySize\0", "_features\0"])
 }
@@ -0,0 +1,193 @@
+use core::arch;
+use std::cell::Cell;
+use std::io;
+
+use crate::event_loop::poll::{CLOCKID_MONOTONIC, CLOCKID_PROCESS_CPUTIME_ID};
+use crate::event_loop::wasi_fs;
+use crate::event_loop::PollResult;
+use crate::internal_module::fs::{err_to_js_object, errno_to_js_object};
+use crate::quickjs_sys::*;
+
+thread_local! {
+    // the monotonic clock when the runtime came up, `uptime` counts from it
+    static START: Cell<u64> = const { Cell::new(0) };
+    // the poll task of the read on stdin that waits for input
+    static STDIN_READ: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+fn monotonic_now() -> u64 {
+    unsafe { wasi_fs::clock_time_get(CLOCKID_MONOTONIC, 1) }.unwrap_or(0)
+}
+
+fn cwd(ctx: &mut Context, _this_val: JsValue, _argv: &[JsValue]) -> JsValue {
+    ctx.new_string(&wasi_fs::cwd()).into()
+}
+
+fn chdir(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
+    if let Some(JsValue::String(path)) = argv.first() {
+        if let Err(e) = wasi_fs::chdir(path.as_str()) {
+            let err = errno_to_js_object(ctx, e);
+            return JsValue::Exception(ctx.throw_error(err));
+        }
+    }
+    JsValue::UnDefined
+}
+
+/// `[seconds, nanoseconds]` of the monotonic clock.
+fn hrtime(ctx: &mut Context, _this_val: JsValue, _argv: &[JsValue]) -> JsValue {
+    let now = monotonic_now();
+    let mut time = ctx.new_array();
+    time.put(0, JsValue::Float((now / 1_000_000_000) as f64));
+    time.put(1, JsValue::Int((now % 1_000_000_000) as i32));
+    time.into()
+}
+
+fn uptime(_ctx: &mut Context, _this_val: JsValue, _argv: &[JsValue]) -> JsValue {
+    let start = START.with(|start| start.get());
+    JsValue::Float((monotonic_now() - start) as f64 / 1e9)
+}
+
+/// The CPU time of the process in microseconds, 0 where the host has no
+/// process clock.
+fn cpu_usage(_ctx: &mut Context, _this_val: JsValue, _argv: &[JsValue]) -> JsValue {
+    let time = unsafe { wasi_fs::clock_time_get(CLOCKID_PROCESS_CPUTIME_ID, 1000) };
+    JsValue::Float((time.unwrap_or(0) / 1000) as f64)
+}
+
+fn memory_usage(ctx: &mut Context, _this_val: JsValue, _argv: &[JsValue]) -> JsValue {
+    let (malloc_size, used_size, array_buffers) = ctx.memory_usage();
+    let rss = arch::wasm32::memory_size::<0>() * 65536;
+    let mut usage = ctx.new_object();
+    usage.set("rss", JsValue::Float(rss as f64));
+    usage.set("heapTotal", JsValue::Float(malloc_size as f64));
+    usage.set("heapUsed", JsValue::Float(used_size as f64));
+    usage.set("external", JsValue::Float(array_buffers as f64));
+    usage.set("arrayBuffers", JsValue::Float(array_buffers as f64));
+    usage.into()
+}
+
+/// What is behind `fd`, as node's `guessHandleType` names it.
+fn guess_handle_type(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
+    let mut kind = "UNKNOWN";
+    if let Some(JsValue::Int(fd)) = argv.first() {
+        if let Ok(stat) = unsafe { wasi_fs::fd_fdstat_get(*fd as u32) } {
+            kind = match stat.fs_filetype {
+                wasi_fs::FILETYPE_CHARACTER_DEVICE => "TTY",
+                wasi_fs::FILETYPE_REGULAR_FILE => "FILE",
+                wasi_fs::FILETYPE_SOCKET_STREAM | wasi_fs::FILETYPE_SOCKET_DGRAM => "PIPE",
+                _ => "UNKNOWN",
+            };
+        }
+    }
+    ctx.new_string(kind).into()
+}
+
+/// `stdinRead(length)`, the next chunk of stdin once there is input, an
+/// empty `ArrayBuffer` at its end, `undefined` once stopped by
+/// `stdinReadStop`. One read waits at a time.
+fn stdin_read(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
+    let length = match argv.first() {
+        Some(JsValue::Int(n)) if *n > 0 => *n as u64,
+        _ => 65536,
+    };
+    let (promise, ok, error) = ctx.new_promise();
+    if let Some(event_loop) = ctx.event_loop() {
+        let id = event_loop.fd_read(
+            0,
+            -1,
+            length,
+            Box::new(move |ctx, res| {
+                STDIN_READ.with(|read| read.set(None));
+                match res {
+                    PollResult::Read(data) => {
+                        let buf = ctx.new_array_buffer(&data);
+                        if let JsValue::Function(resolve) = ok {
+                            resolve.call(&[JsValue::ArrayBuffer(buf)]);
+                        }
+                    }
+                    // stopped by `stdinReadStop`
+                    PollResult::Error(e) if e.kind() == io::ErrorKind::Interrupted => {
+                        if let JsValue::Function(resolve) = ok {
+                            resolve.call(&[JsValue::UnDefined]);
+                        }
+                    }
+                    PollResult::Error(e) => {
+                        if let JsValue::Function(reject) = error {
+                            reject.call(&[err_to_js_object(ctx, e)]);
+                        }
+                    }
+                    _ => {}
+                }
+            }),
+        );
+        STDIN_READ.with(|read| read.set(Some(id)));
+    }
+    promise
+}
+
+/// Stops the read of `stdinRead` that still waits, its promise resolves
+/// with `undefined`. Without it the event loop would wait for input nobody asked
+/// for. `false` if there was none.
+fn stdin_read_stop(ctx: &mut Context, _this_val: JsValue, _argv: &[JsValue]) -> JsValue {
+    let stopped = match (STDIN_READ.with(|read| read.take()), ctx.event_loop()) {
+        (Some(id), Some(event_loop)) => event_loop.cancel_fd_read(id, 0),
+        _ => false,
+    };
+    stopped.into()
+}
+
+// the stdio streams need `stream`, which in turn imports `process`, so
+// process.js only loads them once they are asked for
+fn import(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
+    if let Some(JsValue::String(name)) = argv.first() {
+        return ctx.import_module(name.as_str());
+    }
+    JsValue::UnDefined
+}
+
+struct Process;
+
+impl ModuleInit for Process {
+    fn init_module(ctx: &mut Context, m: &mut JsModuleDef) {
+        let f = ctx.wrap_function("cwd", cwd);
+        m.add_export("cwd\0", f.into());
+        let f = ctx.wrap_function("chdir", chdir);
+        m.add_export("chdir\0", f.into());
+        let f = ctx.wrap_function("hrtime", hrtime);
+        m.add_export("hrtime\0", f.into());
+        let f = ctx.wrap_function("uptime", uptime);
+        m.add_export("uptime\0", f.into());
+        let f = ctx.wrap_function("cpuUsage", cpu_usage);
+        m.add_export("cpuUsage\0", f.into());
+        let f = ctx.wrap_function("memoryUsage", memory_usage);
+        m.add_export("memoryUsage\0", f.into());
+        let f = ctx.wrap_function("guessHandleType", guess_handle_type);
+        m.add_export("guessHandleType\0", f.into());
+        let f = ctx.wrap_function("stdinRead", stdin_read);
+        m.add_export("stdinRead\0", f.into());
+        let f = ctx.wrap_function("stdinReadStop", stdin_read_stop);
+        m.add_export("stdinReadStop\0", f.into());
+        let f = ctx.wrap_function("_import", import);
+        m.add_export("_import\0", f.into());
+    }
+}
+
+pub fn init_module(ctx: &mut Context) {
+    START.with(|start| start.set(monotonic_now()));
+    ctx.register_module(
+        "_node:process\0",
+        Process,
+        &[
+            "cwd\0",
+            "chdir\0",
+            "hrtime\0",
+            "uptime\0",
+            "cpuUsage\0",
+            "memoryUsage\0",
+            "guessHandleType\0",
+            "stdinRead\0",
+            "stdinReadStop\0",
+            "_import\0",
+        ],
+    )
+}
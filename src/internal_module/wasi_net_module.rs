@@ -1,5 +1,41 @@
-use crate::event_loop::{AsyncTcpConn, AsyncTcpServer, PollResult};
+use crate::event_loop::{
+    AsyncTcpConn, AsyncTcpServer, AsyncUdpSocket, PollCallback, PollResult, SocketTimeouts,
+};
 use crate::*;
+use std::time::Duration;
+
+/// A timeout in ms for JS, 0 when it is disabled.
+pub(crate) fn timeout_to_js(timeout: Option<Duration>) -> JsValue {
+    JsValue::Int(timeout.map_or(0, |t| t.as_millis().min(i32::MAX as u128) as i32))
+}
+
+/// A timeout in ms from JS, 0 disables it. `None` for anything else.
+pub(crate) fn timeout_from_js(val: &JsValue) -> Option<Option<Duration>> {
+    let ms = match val {
+        JsValue::Int(n) if *n >= 0 => *n as u64,
+        JsValue::Float(n) if *n >= 0.0 && n.is_finite() => *n as u64,
+        _ => return None,
+    };
+    Some(if ms > 0 {
+        Some(Duration::from_millis(ms))
+    } else {
+        None
+    })
+}
+
+/// Sets one of the timeouts of `conn` from a JS field setter.
+pub(crate) fn set_conn_timeout(
+    conn: &mut AsyncTcpConn,
+    ctx: &mut Context,
+    val: JsValue,
+    set: fn(&mut SocketTimeouts, Option<Duration>),
+) {
+    if let (Some(timeout), Some(event_loop)) = (timeout_from_js(&val), ctx.event_loop()) {
+        let mut timeouts = conn.timeouts();
+        set(&mut timeouts, timeout);
+        conn.set_timeouts(event_loop, timeouts);
+    }
+}
 
 impl AsyncTcpConn {
     pub fn connect(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
@@ -17,39 +53,9 @@ impl AsyncTcpConn {
             let addr = addr.to_string().parse();
             match addr {
                 Ok(addr) => {
-                    if let Err(e) = event_loop.tcp_connect(
-                        &addr,
-                        Box::new(move |ctx, event| match event {
-                            PollResult::Connect(cs) => {
-                                if let JsValue::Function(ok) = ok {
-                                    let cs = AsyncTcpConn::wrap_obj(ctx, cs);
-                                    ok.call(&[cs]);
-                                }
-                            }
-                            PollResult::Error(e) => {
-                                let err_msg = e.to_string();
-                                let e = ctx.new_error(err_msg.as_str());
-                                if let JsValue::Function(error) = error {
-                                    error.call(&[e]);
-                                }
-                            }
-                            PollResult::Timeout => {
-                                let e = std::io::Error::from(std::io::ErrorKind::TimedOut);
-                                let e = ctx.new_error(e.to_string().as_str());
-                                if let JsValue::Function(error) = error {
-                                    error.call(&[e]);
-                                }
-                            }
-                            _ => {
-                                let e = std::io::Error::from(std::io::ErrorKind::Unsupported);
-                                let e = ctx.new_error(e.to_string().as_str());
-                                if let JsValue::Function(error) = error {
-                                    error.call(&[e]);
-                                }
-                            }
-                        }),
-                        timeout,
-                    ) {
+                    if let Err(e) =
+                        event_loop.tcp_connect(&addr, settle_connect(ok, error), timeout)
+                    {
                         let e = ctx.throw_internal_type_error(e.to_string().as_str());
                         return e.into();
                     };
@@ -65,12 +71,49 @@ impl AsyncTcpConn {
         }
     }
 
+    /// `WasiTcpConn.connectUnix(path, timeout)`, the same connection over a
+    /// unix socket.
+    pub fn connect_unix(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        let path = match argv.first() {
+            Some(JsValue::String(path)) => path.to_string(),
+            _ => return ctx.throw_type_error("path must be a string").into(),
+        };
+        let timeout = if let Some(JsValue::Int(timeout)) = argv.get(1) {
+            Some(std::time::Duration::from_millis((*timeout) as u64))
+        } else {
+            None
+        };
+        let (p, ok, error) = ctx.new_promise();
+        if let Some(event_loop) = ctx.event_loop() {
+            if let Err(e) = event_loop.unix_connect(&path, settle_connect(ok, error), timeout) {
+                return ctx.throw_internal_type_error(e.to_string().as_str()).into();
+            }
+            p
+        } else {
+            JsValue::UnDefined
+        }
+    }
+
+    /// `on(event, listener)` for 'drain' and 'timeout'. There is one
+    /// listener per event, a later `on` replaces it and anything but a
+    /// function removes it.
     pub fn on(
-        _this_val: &mut AsyncTcpConn,
+        this_val: &mut AsyncTcpConn,
         _this_obj: &mut JsObject,
         _ctx: &mut Context,
-        _argv: &[JsValue],
+        argv: &[JsValue],
     ) -> JsValue {
+        if let (Some(JsValue::String(event)), Some(listener)) = (argv.first(), argv.get(1)) {
+            let listener = match listener {
+                JsValue::Function(f) => Some(f.clone()),
+                _ => None,
+            };
+            match event.as_str() {
+                "drain" => this_val.set_drain_listener(listener),
+                "timeout" => this_val.set_timeout_listener(listener),
+                _ => {}
+            }
+        }
         JsValue::UnDefined
     }
 
@@ -134,27 +177,133 @@ impl AsyncTcpConn {
     pub fn js_write(
         this_val: &mut AsyncTcpConn,
         _this_obj: &mut JsObject,
-        _ctx: &mut Context,
+        ctx: &mut Context,
         argv: &[JsValue],
     ) -> JsValue {
-        let data = argv.get(0);
-        match data {
-            Some(JsValue::String(s)) => {
-                this_val.write(s.to_string().as_bytes());
-            }
-            Some(JsValue::ArrayBuffer(buff)) => {
-                this_val.write(buff.as_ref());
-            }
-            Some(JsValue::Object(o)) => {
-                this_val.write(o.to_string().as_bytes());
+        let data = write_data(argv.first());
+        let callback = write_callback(argv.get(1));
+        if let Some(event_loop) = ctx.event_loop() {
+            match this_val.write(event_loop, &data, callback) {
+                Ok(below_mark) => JsValue::Bool(below_mark),
+                Err(e) => {
+                    if let Some(JsValue::Function(f)) = argv.get(1) {
+                        let e = ctx.new_error(e.to_string().as_str());
+                        f.call(&[e]);
+                    }
+                    JsValue::Bool(false)
+                }
             }
-            Some(JsValue::Symbol(s)) => {
-                let data = format!("{:?}", s);
-                this_val.write(data.as_bytes());
+        } else {
+            JsValue::UnDefined
+        }
+    }
+
+    /// `end(data, callback)`, writes `data` and half-closes the connection
+    /// once everything queued has been sent. `callback` is called after the
+    /// shutdown, with the error of the write or the shutdown if one failed.
+    pub fn js_end(
+        this_val: &mut AsyncTcpConn,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let data = write_data(argv.first());
+        let callback = write_callback(argv.get(1)).unwrap_or_else(|| Box::new(|_, _| {}));
+        if let Some(event_loop) = ctx.event_loop() {
+            if !data.is_empty() {
+                if let Err(e) = this_val.write(event_loop, &data, None) {
+                    if let Some(JsValue::Function(f)) = argv.get(1) {
+                        let e = ctx.new_error(e.to_string().as_str());
+                        f.call(&[e]);
+                    }
+                    return JsValue::UnDefined;
+                }
             }
+            this_val.shutdown(event_loop, callback);
+        }
+        JsValue::UnDefined
+    }
+
+    pub fn js_flush(
+        this_val: &mut AsyncTcpConn,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        _argv: &[JsValue],
+    ) -> JsValue {
+        let (p, ok, error) = ctx.new_promise();
+        if let Some(event_loop) = ctx.event_loop() {
+            this_val.flush(event_loop, settle_write_promise(ok, error));
+            p
+        } else {
+            JsValue::UnDefined
+        }
+    }
+
+    pub fn js_shutdown(
+        this_val: &mut AsyncTcpConn,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        _argv: &[JsValue],
+    ) -> JsValue {
+        let (p, ok, error) = ctx.new_promise();
+        if let Some(event_loop) = ctx.event_loop() {
+            this_val.shutdown(event_loop, settle_write_promise(ok, error));
+            p
+        } else {
+            JsValue::UnDefined
+        }
+    }
+
+    pub fn js_close(
+        this_val: &mut AsyncTcpConn,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        _argv: &[JsValue],
+    ) -> JsValue {
+        if let Some(event_loop) = ctx.event_loop() {
+            this_val.close(event_loop);
+        }
+        JsValue::UnDefined
+    }
+
+    pub fn js_get_buffered_amount(&self, _ctx: &mut Context) -> JsValue {
+        JsValue::Int(self.buffered_amount() as i32)
+    }
+
+    fn js_get_high_water_mark(&self, _ctx: &mut Context) -> JsValue {
+        JsValue::Int(self.high_water_mark() as i32)
+    }
+
+    fn js_set_high_water_mark(&mut self, _ctx: &mut Context, val: JsValue) {
+        match val {
+            JsValue::Int(n) if n >= 0 => self.set_high_water_mark(n as usize),
+            JsValue::Float(n) if n >= 0.0 => self.set_high_water_mark(n as usize),
             _ => {}
-        };
-        JsValue::Bool(true)
+        }
+    }
+
+    pub fn js_get_timeout(&self, _ctx: &mut Context) -> JsValue {
+        timeout_to_js(self.timeouts().idle)
+    }
+
+    pub fn js_set_timeout(&mut self, ctx: &mut Context, val: JsValue) {
+        set_conn_timeout(self, ctx, val, |t, v| t.idle = v)
+    }
+
+    pub fn js_get_read_timeout(&self, _ctx: &mut Context) -> JsValue {
+        timeout_to_js(self.timeouts().read)
+    }
+
+    pub fn js_set_read_timeout(&mut self, ctx: &mut Context, val: JsValue) {
+        set_conn_timeout(self, ctx, val, |t, v| t.read = v)
+    }
+
+    pub fn js_get_write_timeout(&self, _ctx: &mut Context) -> JsValue {
+        timeout_to_js(self.timeouts().write)
+    }
+
+    pub fn js_set_write_timeout(&mut self, ctx: &mut Context, val: JsValue) {
+        set_conn_timeout(self, ctx, val, |t, v| t.write = v)
     }
 
     pub fn js_local(
@@ -187,25 +336,132 @@ impl JsClassDef for AsyncTcpConn {
     const CLASS_NAME: &'static str = "WasiTcpConn";
     const CONSTRUCTOR_ARGC: u8 = 0;
 
-    const FIELDS: &'static [JsClassField<Self::RefType>] = &[];
+    const FIELDS: &'static [JsClassField<Self::RefType>] = &[
+        ("bufferedAmount", Self::js_get_buffered_amount, None),
+        (
+            "highWaterMark",
+            Self::js_get_high_water_mark,
+            Some(Self::js_set_high_water_mark),
+        ),
+        ("timeout", Self::js_get_timeout, Some(Self::js_set_timeout)),
+        (
+            "readTimeout",
+            Self::js_get_read_timeout,
+            Some(Self::js_set_read_timeout),
+        ),
+        (
+            "writeTimeout",
+            Self::js_get_write_timeout,
+            Some(Self::js_set_write_timeout),
+        ),
+    ];
 
     const METHODS: &'static [JsClassMethod<Self::RefType>] = &[
-        ("on", 1, Self::on),
+        ("on", 2, Self::on),
         ("read", 0, Self::js_read),
-        ("write", 1, Self::js_write),
-        ("end", 1, Self::js_write),
+        ("write", 2, Self::js_write),
+        ("end", 2, Self::js_end),
+        ("flush", 0, Self::js_flush),
+        ("shutdown", 0, Self::js_shutdown),
+        ("close", 0, Self::js_close),
         ("local", 0, Self::js_local),
         ("peer", 0, Self::js_peer),
     ];
 
     unsafe fn mut_class_id_ptr() -> &'static mut u32 {
         static mut CLASS_ID: u32 = 0;
-        &mut CLASS_ID
+        &mut *std::ptr::addr_of_mut!(CLASS_ID)
     }
 
     fn constructor_fn(_ctx: &mut Context, _argv: &[JsValue]) -> Result<Self::RefType, JsValue> {
         Err(JsValue::Null)
     }
+
+    fn gc_mark(data: &Self::RefType, make: &mut dyn Fn(&JsValue)) {
+        if let Some(f) = data.drain_listener() {
+            make(&JsValue::Function(f))
+        }
+        if let Some(f) = data.timeout_listener() {
+            make(&JsValue::Function(f))
+        }
+    }
+}
+
+/// The bytes of the first argument of `write` and `end`.
+pub(crate) fn write_data(data: Option<&JsValue>) -> Vec<u8> {
+    match data {
+        Some(JsValue::String(s)) => s.to_string().into_bytes(),
+        Some(JsValue::ArrayBuffer(buff)) => buff.as_ref().to_vec(),
+        Some(JsValue::Object(o)) => o.to_string().into_bytes(),
+        Some(JsValue::Symbol(s)) => format!("{:?}", s).into_bytes(),
+        _ => vec![],
+    }
+}
+
+/// The callback of `write` and `end`, it gets the error if there is one.
+pub(crate) fn write_callback(f: Option<&JsValue>) -> Option<PollCallback> {
+    if let Some(JsValue::Function(f)) = f {
+        let f = f.clone();
+        Some(Box::new(move |ctx, event| match event {
+            PollResult::Error(e) => {
+                let e = ctx.new_error(e.to_string().as_str());
+                f.call(&[e]);
+            }
+            _ => {
+                f.call(&[]);
+            }
+        }))
+    } else {
+        None
+    }
+}
+
+fn settle_connect(ok: JsValue, error: JsValue) -> PollCallback {
+    Box::new(move |ctx, event| match event {
+        PollResult::Connect(cs) => {
+            if let JsValue::Function(ok) = ok {
+                let cs = AsyncTcpConn::wrap_obj(ctx, cs);
+                ok.call(&[cs]);
+            }
+        }
+        PollResult::Error(e) => {
+            let err_msg = e.to_string();
+            let e = ctx.new_error(err_msg.as_str());
+            if let JsValue::Function(error) = error {
+                error.call(&[e]);
+            }
+        }
+        PollResult::Timeout => {
+            let e = std::io::Error::from(std::io::ErrorKind::TimedOut);
+            let e = ctx.new_error(e.to_string().as_str());
+            if let JsValue::Function(error) = error {
+                error.call(&[e]);
+            }
+        }
+        _ => {
+            let e = std::io::Error::from(std::io::ErrorKind::Unsupported);
+            let e = ctx.new_error(e.to_string().as_str());
+            if let JsValue::Function(error) = error {
+                error.call(&[e]);
+            }
+        }
+    })
+}
+
+fn settle_write_promise(ok: JsValue, error: JsValue) -> PollCallback {
+    Box::new(move |ctx, event| match event {
+        PollResult::Error(e) => {
+            let e = ctx.new_error(e.to_string().as_str());
+            if let JsValue::Function(error) = error {
+                error.call(&[e]);
+            }
+        }
+        _ => {
+            if let JsValue::Function(ok) = ok {
+                ok.call(&[]);
+            }
+        }
+    })
 }
 
 impl AsyncTcpServer {
@@ -262,6 +518,32 @@ impl AsyncTcpServer {
     }
 }
 
+impl AsyncTcpServer {
+    pub fn js_close(
+        &mut self,
+        _this: &mut JsObject,
+        ctx: &mut Context,
+        _argv: &[JsValue],
+    ) -> JsValue {
+        if let Some(event_loop) = ctx.event_loop() {
+            self.close(event_loop);
+        }
+        JsValue::UnDefined
+    }
+
+    pub fn js_local(
+        &mut self,
+        _this: &mut JsObject,
+        ctx: &mut Context,
+        _argv: &[JsValue],
+    ) -> JsValue {
+        match self.local() {
+            Ok(addr) => ctx.new_string(addr.to_string().as_str()).into(),
+            Err(e) => ctx.throw_internal_type_error(e.to_string().as_str()).into(),
+        }
+    }
+}
+
 impl JsClassDef for AsyncTcpServer {
     const CLASS_NAME: &'static str = "WasiTcpServer";
     const CONSTRUCTOR_ARGC: u8 = 1;
@@ -270,26 +552,199 @@ impl JsClassDef for AsyncTcpServer {
 
     const FIELDS: &'static [JsClassField<Self::RefType>] = &[];
 
-    const METHODS: &'static [JsClassMethod<Self::RefType>] = &[("accept", 0, Self::js_accept)];
+    const METHODS: &'static [JsClassMethod<Self::RefType>] = &[
+        ("accept", 0, Self::js_accept),
+        ("close", 0, Self::js_close),
+        ("local", 0, Self::js_local),
+    ];
 
     unsafe fn mut_class_id_ptr() -> &'static mut u32 {
         static mut CLASS_ID: u32 = 0;
-        &mut CLASS_ID
+        &mut *std::ptr::addr_of_mut!(CLASS_ID)
     }
 
     fn constructor_fn(ctx: &mut Context, argv: &[JsValue]) -> Result<Self::RefType, JsValue> {
+        // a port listens on TCP, a path on a unix socket
         let port = argv.get(0).ok_or_else(|| JsValue::UnDefined)?;
-        if let (JsValue::Int(port), Some(event_loop)) = (port, ctx.event_loop()) {
-            match event_loop.tcp_listen(*port as u16) {
-                Ok(tcp_server) => Ok(tcp_server),
-                Err(e) => Err(ctx.throw_internal_type_error(e.to_string().as_str()).into()),
+        let r = match (port, ctx.event_loop()) {
+            (JsValue::Int(port), Some(event_loop)) => event_loop.tcp_listen(*port as u16),
+            (JsValue::String(path), Some(event_loop)) => event_loop.unix_listen(path.as_str()),
+            _ => return Err(JsValue::UnDefined),
+        };
+        r.map_err(|e| ctx.throw_internal_type_error(e.to_string().as_str()).into())
+    }
+}
+
+impl AsyncUdpSocket {
+    /// `WasiUdpSocket.connect(addr)`, a socket that talks to `addr` only.
+    pub fn connect(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        let addr = match argv.first() {
+            Some(JsValue::String(addr)) => addr.to_string(),
+            _ => return ctx.throw_type_error("addr must be a string").into(),
+        };
+        let addr: std::net::SocketAddr = match addr.parse() {
+            Ok(addr) => addr,
+            Err(e) => return ctx.throw_internal_type_error(e.to_string().as_str()).into(),
+        };
+        match ctx
+            .event_loop()
+            .map(|event_loop| event_loop.udp_connect(&addr))
+        {
+            Some(Ok(s)) => AsyncUdpSocket::wrap_obj(ctx, s),
+            Some(Err(e)) => ctx.throw_internal_type_error(e.to_string().as_str()).into(),
+            None => JsValue::UnDefined,
+        }
+    }
+
+    pub fn js_send(
+        &mut self,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let data = match argv.first() {
+            Some(JsValue::String(s)) => s.to_string().into_bytes(),
+            Some(JsValue::ArrayBuffer(buff)) => buff.as_ref().to_vec(),
+            _ => {
+                return ctx
+                    .throw_type_error("data must be an ArrayBuffer or a string")
+                    .into()
             }
+        };
+        match self.send(&data) {
+            Ok(n) => JsValue::Int(n as i32),
+            Err(e) => ctx.throw_internal_type_error(e.to_string().as_str()).into(),
+        }
+    }
+
+    /// `recv(timeout)` resolves with the next datagram.
+    pub fn js_recv(
+        &mut self,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let (p, ok, error) = ctx.new_promise();
+        if let Some(event_loop) = ctx.event_loop() {
+            let timeout = if let Some(JsValue::Int(timeout)) = argv.first() {
+                Some(std::time::Duration::from_millis((*timeout) as u64))
+            } else {
+                None
+            };
+            self.async_recv(
+                event_loop,
+                Box::new(move |ctx, event| {
+                    let e = match event {
+                        PollResult::Read(data) => {
+                            if let JsValue::Function(ok) = ok {
+                                let buff = ctx.new_array_buffer(data.as_slice());
+                                ok.call(&[JsValue::ArrayBuffer(buff)]);
+                            }
+                            return;
+                        }
+                        PollResult::Error(e) => e,
+                        PollResult::Timeout => std::io::Error::from(std::io::ErrorKind::TimedOut),
+                        _ => std::io::Error::from(std::io::ErrorKind::Unsupported),
+                    };
+                    let e = ctx.new_error(e.to_string().as_str());
+                    if let JsValue::Function(error) = error {
+                        error.call(&[e]);
+                    }
+                }),
+                timeout,
+            );
+            p
         } else {
-            Err(JsValue::UnDefined)
+            JsValue::UnDefined
+        }
+    }
+
+    pub fn js_close(
+        &mut self,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        _argv: &[JsValue],
+    ) -> JsValue {
+        if let Some(event_loop) = ctx.event_loop() {
+            self.close(event_loop);
+        }
+        JsValue::UnDefined
+    }
+
+    pub fn js_local(
+        &mut self,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        _argv: &[JsValue],
+    ) -> JsValue {
+        match self.local() {
+            Ok(addr) => ctx.new_string(addr.to_string().as_str()).into(),
+            Err(e) => ctx.throw_internal_type_error(e.to_string().as_str()).into(),
+        }
+    }
+
+    pub fn js_peer(
+        &mut self,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        _argv: &[JsValue],
+    ) -> JsValue {
+        match self.peer() {
+            Ok(addr) => ctx.new_string(addr.to_string().as_str()).into(),
+            Err(e) => ctx.throw_internal_type_error(e.to_string().as_str()).into(),
         }
     }
 }
 
+impl JsClassDef for AsyncUdpSocket {
+    type RefType = AsyncUdpSocket;
+    const CLASS_NAME: &'static str = "WasiUdpSocket";
+    const CONSTRUCTOR_ARGC: u8 = 0;
+
+    const FIELDS: &'static [JsClassField<Self::RefType>] = &[];
+
+    const METHODS: &'static [JsClassMethod<Self::RefType>] = &[
+        ("send", 1, Self::js_send),
+        ("recv", 1, Self::js_recv),
+        ("close", 0, Self::js_close),
+        ("local", 0, Self::js_local),
+        ("peer", 0, Self::js_peer),
+    ];
+
+    unsafe fn mut_class_id_ptr() -> &'static mut u32 {
+        static mut CLASS_ID: u32 = 0;
+        &mut *std::ptr::addr_of_mut!(CLASS_ID)
+    }
+
+    fn constructor_fn(_ctx: &mut Context, _argv: &[JsValue]) -> Result<Self::RefType, JsValue> {
+        Err(JsValue::Null)
+    }
+}
+
+/// `socketpair(dir)`, two connected `WasiTcpConn`s over a unix socket that
+/// is bound in `dir` only while they connect.
+fn js_socketpair(ctx: &mut Context, _this: JsValue, argv: &[JsValue]) -> JsValue {
+    let dir = match argv.first() {
+        Some(JsValue::String(dir)) => dir.to_string(),
+        _ => return ctx.throw_type_error("dir must be a string").into(),
+    };
+    match ctx
+        .event_loop()
+        .map(|event_loop| event_loop.unix_socketpair(&dir))
+    {
+        Some(Ok((a, b))) => {
+            let mut array = ctx.new_array();
+            let a = AsyncTcpConn::wrap_obj(ctx, a);
+            let b = AsyncTcpConn::wrap_obj(ctx, b);
+            array.put(0, a);
+            array.put(1, b);
+            array.into()
+        }
+        Some(Err(e)) => ctx.throw_internal_type_error(e.to_string().as_str()).into(),
+        None => JsValue::UnDefined,
+    }
+}
+
 fn js_nsloopup(ctx: &mut Context, _this: JsValue, param: &[JsValue]) -> JsValue {
     let node = param.get(0);
     let service = param.get(1);
@@ -316,8 +771,10 @@ pub fn init_module(ctx: &mut Context) {
         &[
             AsyncTcpServer::CLASS_NAME,
             AsyncTcpConn::CLASS_NAME,
+            AsyncUdpSocket::CLASS_NAME,
             "connect",
             "nsloopup",
+            "socketpair",
         ],
         |ctx, m| {
             let class_ctor = register_class::<AsyncTcpServer>(ctx);
@@ -327,12 +784,24 @@ pub fn init_module(ctx: &mut Context) {
             if let JsValue::Function(tcp_conn_ctor) = &mut class_ctor {
                 let conn = ctx.wrap_function("connect", AsyncTcpConn::connect);
                 tcp_conn_ctor.set("connect", conn.into());
+                let conn = ctx.wrap_function("connectUnix", AsyncTcpConn::connect_unix);
+                tcp_conn_ctor.set("connectUnix", conn.into());
             }
 
             m.add_export(AsyncTcpConn::CLASS_NAME, class_ctor);
 
+            let mut class_ctor = register_class::<AsyncUdpSocket>(ctx);
+            if let JsValue::Function(udp_socket_ctor) = &mut class_ctor {
+                let connect = ctx.wrap_function("connect", AsyncUdpSocket::connect);
+                udp_socket_ctor.set("connect", connect.into());
+            }
+            m.add_export(AsyncUdpSocket::CLASS_NAME, class_ctor);
+
             let f = ctx.wrap_function("nsloopup", js_nsloopup);
             m.add_export("nsloopup", f.into());
+
+            let f = ctx.wrap_function("socketpair", js_socketpair);
+            m.add_export("socketpair", f.into());
         },
     )
 }
@@ -0,0 +1,695 @@
+use super::wasi_net_module::{write_callback, write_data};
+use crate::event_loop::{AsyncTcpConn, PollCallback, PollResult};
+use crate::*;
+use rustls::{
+    Certificate, ClientConfig, ClientConnection, Connection, OwnedTrustAnchor, PrivateKey,
+    RootCertStore, ServerConfig, ServerConnection, ServerName,
+};
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+use std::sync::Arc;
+
+type TlsCallback<T> = Box<dyn FnOnce(&mut Context, io::Result<T>)>;
+
+struct TlsState {
+    tcp: AsyncTcpConn,
+    tls: Connection,
+    plaintext: Vec<u8>,
+    eof: bool,
+}
+
+impl TlsState {
+    /// Hands every pending TLS record to the tcp connection's outbound queue.
+    fn send_records(
+        &mut self,
+        event_loop: &mut EventLoop,
+        callback: Option<PollCallback>,
+    ) -> io::Result<bool> {
+        let mut records = vec![];
+        while self.tls.wants_write() {
+            self.tls.write_tls(&mut records)?;
+        }
+        self.tcp.write(event_loop, &records, callback)
+    }
+
+    /// Feeds bytes read from the socket into the TLS session and moves the
+    /// decrypted application data into `plaintext`.
+    fn receive_records(&mut self, mut data: &[u8]) -> io::Result<()> {
+        if data.is_empty() {
+            self.eof = true;
+            return Ok(());
+        }
+        while !data.is_empty() {
+            self.tls.read_tls(&mut data)?;
+            self.tls
+                .process_new_packets()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let mut buf = [0u8; 4096];
+            loop {
+                match self.tls.reader().read(&mut buf) {
+                    Ok(0) => {
+                        self.eof = true;
+                        break;
+                    }
+                    Ok(n) => self.plaintext.extend_from_slice(&buf[..n]),
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct TlsConn(Rc<RefCell<TlsState>>);
+
+impl TlsConn {
+    fn new(tcp: AsyncTcpConn, tls: Connection) -> Self {
+        TlsConn(Rc::new(RefCell::new(TlsState {
+            tcp,
+            tls,
+            plaintext: vec![],
+            eof: false,
+        })))
+    }
+
+    /// Waits for one read event on the underlying socket and feeds what
+    /// arrived into the TLS session.
+    fn read_records(
+        &self,
+        ctx: &mut Context,
+        timeout: Option<std::time::Duration>,
+        callback: TlsCallback<()>,
+    ) {
+        let mut tcp = self.0.borrow().tcp.clone();
+        let conn = self.clone();
+        if let Some(event_loop) = ctx.event_loop() {
+            tcp.async_read(
+                event_loop,
+                Box::new(move |ctx, event| {
+                    let r = match event {
+                        PollResult::Read(data) => conn.0.borrow_mut().receive_records(&data),
+                        PollResult::Error(e) => Err(e),
+                        PollResult::Timeout => Err(io::Error::from(io::ErrorKind::TimedOut)),
+                        _ => Err(io::Error::from(io::ErrorKind::Unsupported)),
+                    };
+                    // alerts, key updates and session tickets are written back right away
+                    if let Some(event_loop) = ctx.event_loop() {
+                        let _ = conn.0.borrow_mut().send_records(event_loop, None);
+                    }
+                    callback(ctx, r)
+                }),
+                timeout,
+            );
+        }
+    }
+
+    fn handshake(
+        self,
+        ctx: &mut Context,
+        timeout: Option<std::time::Duration>,
+        callback: TlsCallback<TlsConn>,
+    ) {
+        let r = match ctx.event_loop() {
+            Some(event_loop) => self.0.borrow_mut().send_records(event_loop, None),
+            None => Err(io::Error::from(io::ErrorKind::Unsupported)),
+        };
+        if let Err(e) = r {
+            return callback(ctx, Err(e));
+        }
+        let (handshaking, eof) = {
+            let state = self.0.borrow();
+            (state.tls.is_handshaking(), state.eof)
+        };
+        if !handshaking {
+            return callback(ctx, Ok(self));
+        }
+        if eof {
+            return callback(ctx, Err(io::Error::from(io::ErrorKind::UnexpectedEof)));
+        }
+        let conn = self.clone();
+        self.read_records(
+            ctx,
+            timeout,
+            Box::new(move |ctx, r| match r {
+                Ok(()) => conn.handshake(ctx, timeout, callback),
+                Err(e) => callback(ctx, Err(e)),
+            }),
+        );
+    }
+
+    /// Resolves with decrypted data, or `None` once the peer has closed the
+    /// session.
+    fn read(
+        self,
+        ctx: &mut Context,
+        timeout: Option<std::time::Duration>,
+        callback: TlsCallback<Option<Vec<u8>>>,
+    ) {
+        let (data, eof) = {
+            let mut state = self.0.borrow_mut();
+            (std::mem::take(&mut state.plaintext), state.eof)
+        };
+        if !data.is_empty() {
+            return callback(ctx, Ok(Some(data)));
+        }
+        if eof {
+            return callback(ctx, Ok(None));
+        }
+        let conn = self.clone();
+        self.read_records(
+            ctx,
+            timeout,
+            Box::new(move |ctx, r| match r {
+                Ok(()) => conn.read(ctx, timeout, callback),
+                Err(e) => callback(ctx, Err(e)),
+            }),
+        );
+    }
+
+    pub fn js_connect(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        let tcp = match argv.first().and_then(AsyncTcpConn::opaque) {
+            Some(tcp) => tcp.clone(),
+            None => {
+                return ctx
+                    .throw_internal_type_error("the first argument must be a WasiTcpConn")
+                    .into()
+            }
+        };
+        let options = argv.get(1).cloned().unwrap_or(JsValue::UnDefined);
+        let servername = match options.get("servername") {
+            Some(JsValue::String(s)) => s.to_string(),
+            _ => {
+                return ctx
+                    .throw_internal_type_error("options.servername is required")
+                    .into()
+            }
+        };
+        let config = match client_config(&options) {
+            Ok(config) => config,
+            Err(e) => return ctx.throw_internal_type_error(e.to_string().as_str()).into(),
+        };
+        let server_name = match ServerName::try_from(servername.as_str()) {
+            Ok(name) => name,
+            Err(e) => return ctx.throw_internal_type_error(e.to_string().as_str()).into(),
+        };
+        let tls = match ClientConnection::new(Arc::new(config), server_name) {
+            Ok(tls) => tls,
+            Err(e) => return ctx.throw_internal_type_error(e.to_string().as_str()).into(),
+        };
+        let timeout = get_timeout(options.get("timeout"));
+
+        let (p, ok, error) = ctx.new_promise();
+        TlsConn::new(tcp, tls.into()).handshake(ctx, timeout, settle_handshake(ok, error));
+        p
+    }
+
+    pub fn on(
+        this_val: &mut TlsConn,
+        this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let mut tcp = this_val.0.borrow().tcp.clone();
+        AsyncTcpConn::on(&mut tcp, this_obj, ctx, argv)
+    }
+
+    pub fn js_read(
+        this_val: &mut TlsConn,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let (p, ok, error) = ctx.new_promise();
+        let timeout = get_timeout(argv.first().cloned());
+        this_val.clone().read(
+            ctx,
+            timeout,
+            Box::new(move |ctx, r| match r {
+                Ok(data) => {
+                    if let JsValue::Function(ok) = ok {
+                        let ret = match data {
+                            Some(data) => JsValue::ArrayBuffer(ctx.new_array_buffer(&data)),
+                            None => JsValue::UnDefined,
+                        };
+                        ok.call(&[ret]);
+                    }
+                }
+                Err(e) => {
+                    let e = ctx.new_error(e.to_string().as_str());
+                    if let JsValue::Function(error) = error {
+                        error.call(&[e]);
+                    }
+                }
+            }),
+        );
+        p
+    }
+
+    pub fn js_write(
+        this_val: &mut TlsConn,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let data = write_data(argv.first());
+        let callback = write_callback(argv.get(1));
+        if let Some(event_loop) = ctx.event_loop() {
+            let mut state = this_val.0.borrow_mut();
+            let r = state
+                .tls
+                .writer()
+                .write_all(&data)
+                .and_then(|_| state.send_records(event_loop, callback));
+            drop(state);
+            match r {
+                Ok(below_mark) => JsValue::Bool(below_mark),
+                Err(e) => {
+                    if let Some(JsValue::Function(f)) = argv.get(1) {
+                        let e = ctx.new_error(e.to_string().as_str());
+                        f.call(&[e]);
+                    }
+                    JsValue::Bool(false)
+                }
+            }
+        } else {
+            JsValue::UnDefined
+        }
+    }
+
+    /// `end(data, callback)`, writes `data` and close_notify, then
+    /// half-closes the tcp connection once the records have been sent.
+    /// `callback` is called after the shutdown, with the error if one failed.
+    pub fn js_end(
+        this_val: &mut TlsConn,
+        _this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let data = write_data(argv.first());
+        let callback = write_callback(argv.get(1)).unwrap_or_else(|| Box::new(|_, _| {}));
+        if let Some(event_loop) = ctx.event_loop() {
+            let mut state = this_val.0.borrow_mut();
+            let r = state.tls.writer().write_all(&data).and_then(|_| {
+                state.tls.send_close_notify();
+                state.send_records(event_loop, None)
+            });
+            if let Err(e) = r {
+                drop(state);
+                if let Some(JsValue::Function(f)) = argv.get(1) {
+                    let e = ctx.new_error(e.to_string().as_str());
+                    f.call(&[e]);
+                }
+                return JsValue::UnDefined;
+            }
+            state.tcp.shutdown(event_loop, callback);
+        }
+        JsValue::UnDefined
+    }
+
+    pub fn js_flush(
+        this_val: &mut TlsConn,
+        this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let mut tcp = this_val.0.borrow().tcp.clone();
+        AsyncTcpConn::js_flush(&mut tcp, this_obj, ctx, argv)
+    }
+
+    /// Sends close_notify, then half-closes the tcp connection once
+    /// everything has been flushed.
+    pub fn js_shutdown(
+        this_val: &mut TlsConn,
+        this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        if let Some(event_loop) = ctx.event_loop() {
+            let mut state = this_val.0.borrow_mut();
+            state.tls.send_close_notify();
+            if let Err(e) = state.send_records(event_loop, None) {
+                drop(state);
+                return ctx.throw_internal_type_error(e.to_string().as_str()).into();
+            }
+        }
+        let mut tcp = this_val.0.borrow().tcp.clone();
+        AsyncTcpConn::js_shutdown(&mut tcp, this_obj, ctx, argv)
+    }
+
+    pub fn js_close(
+        this_val: &mut TlsConn,
+        this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let mut tcp = this_val.0.borrow().tcp.clone();
+        AsyncTcpConn::js_close(&mut tcp, this_obj, ctx, argv)
+    }
+
+    pub fn js_local(
+        this_val: &mut TlsConn,
+        this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let mut tcp = this_val.0.borrow().tcp.clone();
+        AsyncTcpConn::js_local(&mut tcp, this_obj, ctx, argv)
+    }
+
+    pub fn js_peer(
+        this_val: &mut TlsConn,
+        this_obj: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let mut tcp = this_val.0.borrow().tcp.clone();
+        AsyncTcpConn::js_peer(&mut tcp, this_obj, ctx, argv)
+    }
+
+    fn js_get_alpn_protocol(&self, ctx: &mut Context) -> JsValue {
+        match self.0.borrow().tls.alpn_protocol() {
+            Some(proto) => ctx
+                .new_string(String::from_utf8_lossy(proto).as_ref())
+                .into(),
+            None => JsValue::Bool(false),
+        }
+    }
+
+    fn js_get_servername(&self, ctx: &mut Context) -> JsValue {
+        match &self.0.borrow().tls {
+            Connection::Server(conn) => match conn.server_name() {
+                Some(name) => ctx.new_string(name).into(),
+                None => JsValue::Bool(false),
+            },
+            Connection::Client(_) => JsValue::UnDefined,
+        }
+    }
+
+    fn js_get_protocol(&self, ctx: &mut Context) -> JsValue {
+        match self.0.borrow().tls.protocol_version() {
+            Some(rustls::ProtocolVersion::TLSv1_3) => ctx.new_string("TLSv1.3").into(),
+            Some(rustls::ProtocolVersion::TLSv1_2) => ctx.new_string("TLSv1.2").into(),
+            _ => JsValue::Null,
+        }
+    }
+
+    fn js_get_buffered_amount(&self, ctx: &mut Context) -> JsValue {
+        let tcp = self.0.borrow().tcp.clone();
+        tcp.js_get_buffered_amount(ctx)
+    }
+
+    fn js_get_timeout(&self, ctx: &mut Context) -> JsValue {
+        let tcp = self.0.borrow().tcp.clone();
+        tcp.js_get_timeout(ctx)
+    }
+
+    fn js_set_timeout(&mut self, ctx: &mut Context, val: JsValue) {
+        let mut tcp = self.0.borrow().tcp.clone();
+        tcp.js_set_timeout(ctx, val)
+    }
+
+    fn js_get_read_timeout(&self, ctx: &mut Context) -> JsValue {
+        let tcp = self.0.borrow().tcp.clone();
+        tcp.js_get_read_timeout(ctx)
+    }
+
+    fn js_set_read_timeout(&mut self, ctx: &mut Context, val: JsValue) {
+        let mut tcp = self.0.borrow().tcp.clone();
+        tcp.js_set_read_timeout(ctx, val)
+    }
+
+    fn js_get_write_timeout(&self, ctx: &mut Context) -> JsValue {
+        let tcp = self.0.borrow().tcp.clone();
+        tcp.js_get_write_timeout(ctx)
+    }
+
+    fn js_set_write_timeout(&mut self, ctx: &mut Context, val: JsValue) {
+        let mut tcp = self.0.borrow().tcp.clone();
+        tcp.js_set_write_timeout(ctx, val)
+    }
+}
+
+impl JsClassDef for TlsConn {
+    type RefType = TlsConn;
+    const CLASS_NAME: &'static str = "WasiTlsConn";
+    const CONSTRUCTOR_ARGC: u8 = 0;
+
+    const FIELDS: &'static [JsClassField<Self::RefType>] = &[
+        ("alpnProtocol", Self::js_get_alpn_protocol, None),
+        ("servername", Self::js_get_servername, None),
+        ("protocol", Self::js_get_protocol, None),
+        ("bufferedAmount", Self::js_get_buffered_amount, None),
+        ("timeout", Self::js_get_timeout, Some(Self::js_set_timeout)),
+        (
+            "readTimeout",
+            Self::js_get_read_timeout,
+            Some(Self::js_set_read_timeout),
+        ),
+        (
+            "writeTimeout",
+            Self::js_get_write_timeout,
+            Some(Self::js_set_write_timeout),
+        ),
+    ];
+
+    const METHODS: &'static [JsClassMethod<Self::RefType>] = &[
+        ("on", 2, Self::on),
+        ("read", 0, Self::js_read),
+        ("write", 2, Self::js_write),
+        ("end", 2, Self::js_end),
+        ("flush", 0, Self::js_flush),
+        ("shutdown", 0, Self::js_shutdown),
+        ("close", 0, Self::js_close),
+        ("local", 0, Self::js_local),
+        ("peer", 0, Self::js_peer),
+    ];
+
+    unsafe fn mut_class_id_ptr() -> &'static mut u32 {
+        static mut CLASS_ID: u32 = 0;
+        &mut *std::ptr::addr_of_mut!(CLASS_ID)
+    }
+
+    fn constructor_fn(_ctx: &mut Context, _argv: &[JsValue]) -> Result<Self::RefType, JsValue> {
+        Err(JsValue::Null)
+    }
+
+    fn gc_mark(data: &Self::RefType, make: &mut dyn Fn(&JsValue)) {
+        let state = data.0.borrow();
+        if let Some(f) = state.tcp.drain_listener() {
+            make(&JsValue::Function(f))
+        }
+        if let Some(f) = state.tcp.timeout_listener() {
+            make(&JsValue::Function(f))
+        }
+    }
+}
+
+/// Server side settings shared by every connection accepted with them,
+/// the equivalent of node's `tls.SecureContext`.
+pub struct TlsServer(Arc<ServerConfig>);
+
+impl TlsServer {
+    pub fn js_accept(
+        &mut self,
+        _this: &mut JsObject,
+        ctx: &mut Context,
+        argv: &[JsValue],
+    ) -> JsValue {
+        let tcp = match argv.first().and_then(AsyncTcpConn::opaque) {
+            Some(tcp) => tcp.clone(),
+            None => {
+                return ctx
+                    .throw_internal_type_error("the first argument must be a WasiTcpConn")
+                    .into()
+            }
+        };
+        let tls = match ServerConnection::new(self.0.clone()) {
+            Ok(tls) => tls,
+            Err(e) => return ctx.throw_internal_type_error(e.to_string().as_str()).into(),
+        };
+        let timeout = get_timeout(argv.get(1).cloned());
+
+        let (p, ok, error) = ctx.new_promise();
+        TlsConn::new(tcp, tls.into()).handshake(ctx, timeout, settle_handshake(ok, error));
+        p
+    }
+}
+
+impl JsClassDef for TlsServer {
+    type RefType = TlsServer;
+    const CLASS_NAME: &'static str = "WasiTlsServer";
+    const CONSTRUCTOR_ARGC: u8 = 1;
+
+    const FIELDS: &'static [JsClassField<Self::RefType>] = &[];
+
+    const METHODS: &'static [JsClassMethod<Self::RefType>] = &[("accept", 2, Self::js_accept)];
+
+    unsafe fn mut_class_id_ptr() -> &'static mut u32 {
+        static mut CLASS_ID: u32 = 0;
+        &mut *std::ptr::addr_of_mut!(CLASS_ID)
+    }
+
+    fn constructor_fn(ctx: &mut Context, argv: &[JsValue]) -> Result<Self::RefType, JsValue> {
+        let options = argv.first().cloned().unwrap_or(JsValue::UnDefined);
+        match server_config(&options) {
+            Ok(config) => Ok(TlsServer(Arc::new(config))),
+            Err(e) => Err(ctx.throw_internal_type_error(e.to_string().as_str()).into()),
+        }
+    }
+}
+
+fn get_timeout(timeout: Option<JsValue>) -> Option<std::time::Duration> {
+    match timeout {
+        Some(JsValue::Int(ms)) if ms >= 0 => Some(std::time::Duration::from_millis(ms as u64)),
+        Some(JsValue::Float(ms)) if ms >= 0.0 => Some(std::time::Duration::from_millis(ms as u64)),
+        _ => None,
+    }
+}
+
+fn settle_handshake(ok: JsValue, error: JsValue) -> TlsCallback<TlsConn> {
+    Box::new(move |ctx, r| match r {
+        Ok(conn) => {
+            if let JsValue::Function(ok) = ok {
+                let conn = TlsConn::wrap_obj(ctx, conn);
+                ok.call(&[conn]);
+            }
+        }
+        Err(e) => {
+            let e = ctx.new_error(e.to_string().as_str());
+            if let JsValue::Function(error) = error {
+                error.call(&[e]);
+            }
+        }
+    })
+}
+
+/// Collects the PEM strings (or arrays of them) stored under `key`.
+fn pem_option(options: &JsValue, key: &str) -> Vec<String> {
+    match options.get(key) {
+        Some(JsValue::String(s)) => vec![s.to_string()],
+        Some(JsValue::Array(arr)) => arr
+            .to_vec()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|v| match v {
+                JsValue::String(s) => Some(s.to_string()),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn alpn_option(options: &JsValue) -> Vec<Vec<u8>> {
+    match options.get("ALPNProtocols") {
+        Some(JsValue::Array(arr)) => arr
+            .to_vec()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|v| match v {
+                JsValue::String(s) => Some(s.to_string().into_bytes()),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn parse_certs(pem: &str) -> io::Result<Vec<Certificate>> {
+    let certs = rustls_pemfile::certs(&mut pem.as_bytes())?;
+    if certs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no certificate found in PEM data",
+        ));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn parse_private_key(pem: &str) -> io::Result<PrivateKey> {
+    for item in rustls_pemfile::read_all(&mut pem.as_bytes())? {
+        match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => return Ok(PrivateKey(key)),
+            _ => {}
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "no private key found in PEM data",
+    ))
+}
+
+fn client_config(options: &JsValue) -> io::Result<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    let ca = pem_option(options, "ca");
+    if ca.is_empty() {
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                ta.subject,
+                ta.spki,
+                ta.name_constraints,
+            )
+        }));
+    } else {
+        for pem in ca {
+            for cert in parse_certs(&pem)? {
+                roots
+                    .add(&cert)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            }
+        }
+    }
+
+    let mut config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.alpn_protocols = alpn_option(options);
+    Ok(config)
+}
+
+fn server_config(options: &JsValue) -> io::Result<ServerConfig> {
+    let cert = pem_option(options, "cert");
+    let key = pem_option(options, "key");
+    let (cert, key) = match (cert.first(), key.first()) {
+        (Some(cert), Some(key)) => (parse_certs(cert)?, parse_private_key(key)?),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "options.cert and options.key are required",
+            ))
+        }
+    };
+
+    let mut config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(cert, key)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    config.alpn_protocols = alpn_option(options);
+    Ok(config)
+}
+
+pub fn init_module(ctx: &mut Context) {
+    ctx.register_fn_module(
+        "wasi_tls\0",
+        &[TlsConn::CLASS_NAME, TlsServer::CLASS_NAME],
+        |ctx, m| {
+            let mut class_ctor = register_class::<TlsConn>(ctx);
+            if let JsValue::Function(tls_conn_ctor) = &mut class_ctor {
+                let conn = ctx.wrap_function("connect", TlsConn::js_connect);
+                tls_conn_ctor.set("connect", conn.into());
+            }
+            m.add_export(TlsConn::CLASS_NAME, class_ctor);
+
+            let class_ctor = register_class::<TlsServer>(ctx);
+            m.add_export(TlsServer::CLASS_NAME, class_ctor);
+        },
+    )
+}
@@ -0,0 +1,329 @@
+//! Native side of the `zlib` module.
+//!
+//! `ZlibStream` is an incremental codec: every `write` returns the output
+//! that is ready so far, `flush` forces out what is buffered and `finish`
+//! ends the stream. The JS module builds the sync, callback and stream
+//! forms of node's API on top of it.
+
+use std::io::{self, Write};
+
+use flate2::write::{DeflateEncoder, GzEncoder, MultiGzDecoder, ZlibEncoder};
+use flate2::{Compression, Decompress, FlushDecompress, Status};
+
+use crate::quickjs_sys::*;
+
+/// zlib and raw deflate decoding on top of `Decompress`, which, unlike the
+/// `Write` adapters, tells whether the stream actually ended.
+struct Inflater {
+    z: Decompress,
+    done: bool,
+}
+
+impl Inflater {
+    fn new(zlib_header: bool) -> Self {
+        Inflater {
+            z: Decompress::new(zlib_header),
+            done: false,
+        }
+    }
+
+    fn run(
+        &mut self,
+        mut input: &[u8],
+        flush: FlushDecompress,
+        out: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        // bytes after the end of the stream are ignored
+        while !self.done {
+            out.reserve(16 * 1024);
+            let (total_in, total_out) = (self.z.total_in(), self.z.total_out());
+            let status = self
+                .z
+                .decompress_vec(input, out, flush)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let consumed = (self.z.total_in() - total_in) as usize;
+            let produced = self.z.total_out() - total_out;
+            input = &input[consumed..];
+            if let Status::StreamEnd = status {
+                self.done = true;
+            } else if consumed == 0 && produced == 0
+                || input.is_empty() && out.len() < out.capacity()
+            {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> io::Result<()> {
+        self.run(&[], FlushDecompress::Finish, out)?;
+        if self.done {
+            Ok(())
+        } else {
+            Err(unexpected_eof())
+        }
+    }
+}
+
+fn unexpected_eof() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "unexpected end of file")
+}
+
+enum Codec {
+    Deflate(ZlibEncoder<Vec<u8>>),
+    DeflateRaw(DeflateEncoder<Vec<u8>>),
+    Gzip(GzEncoder<Vec<u8>>),
+    Inflate(Inflater),
+    InflateRaw(Inflater),
+    Gunzip(MultiGzDecoder<Vec<u8>>),
+    /// gunzip or inflate, decided by the first two bytes
+    Unzip(Vec<u8>),
+    BrotliCompress(Box<brotli::CompressorWriter<Vec<u8>>>),
+    BrotliDecompress(Box<brotli::DecompressorWriter<Vec<u8>>>),
+    Finished,
+}
+
+/// Compression settings, fields that do not apply to a mode are ignored.
+#[derive(Debug, Clone, Copy)]
+pub struct ZlibOptions {
+    /// 0-9 for the deflate family.
+    pub level: u32,
+    /// 0-11 for brotli.
+    pub quality: u32,
+    /// Brotli window size, 10-24.
+    pub lgwin: u32,
+}
+
+impl Default for ZlibOptions {
+    fn default() -> Self {
+        ZlibOptions {
+            level: 6,
+            quality: 11,
+            lgwin: 22,
+        }
+    }
+}
+
+pub struct ZlibStream {
+    codec: Codec,
+    out: Vec<u8>,
+}
+
+impl ZlibStream {
+    pub fn new(mode: &str, opts: ZlibOptions) -> Option<Self> {
+        let level = Compression::new(opts.level.min(9));
+        let codec = match mode {
+            "deflate" => Codec::Deflate(ZlibEncoder::new(vec![], level)),
+            "deflateRaw" => Codec::DeflateRaw(DeflateEncoder::new(vec![], level)),
+            "gzip" => Codec::Gzip(GzEncoder::new(vec![], level)),
+            "inflate" => Codec::Inflate(Inflater::new(true)),
+            "inflateRaw" => Codec::InflateRaw(Inflater::new(false)),
+            "gunzip" => Codec::Gunzip(MultiGzDecoder::new(vec![])),
+            "unzip" => Codec::Unzip(vec![]),
+            "brotliCompress" => Codec::BrotliCompress(Box::new(brotli::CompressorWriter::new(
+                vec![],
+                4096,
+                opts.quality.min(11),
+                opts.lgwin.clamp(10, 24),
+            ))),
+            "brotliDecompress" => {
+                Codec::BrotliDecompress(Box::new(brotli::DecompressorWriter::new(vec![], 4096)))
+            }
+            _ => return None,
+        };
+        Some(ZlibStream { codec, out: vec![] })
+    }
+
+    /// Feeds `data` and returns the output produced so far.
+    pub fn write(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        if let Codec::Unzip(head) = &mut self.codec {
+            head.extend_from_slice(data);
+            if head.len() < 2 {
+                return Ok(vec![]);
+            }
+            let head = std::mem::take(head);
+            self.codec = if head.starts_with(&[0x1f, 0x8b]) {
+                Codec::Gunzip(MultiGzDecoder::new(vec![]))
+            } else {
+                Codec::Inflate(Inflater::new(true))
+            };
+            return self.write(&head);
+        }
+        match &mut self.codec {
+            Codec::Deflate(w) => w.write_all(data)?,
+            Codec::DeflateRaw(w) => w.write_all(data)?,
+            Codec::Gzip(w) => w.write_all(data)?,
+            Codec::Inflate(z) | Codec::InflateRaw(z) => {
+                z.run(data, FlushDecompress::None, &mut self.out)?
+            }
+            Codec::Gunzip(w) => w.write_all(data)?,
+            Codec::BrotliCompress(w) => w.write_all(data)?,
+            Codec::BrotliDecompress(w) => w.write_all(data)?,
+            Codec::Unzip(_) => {}
+            Codec::Finished => return Err(io::Error::other("write after end")),
+        }
+        Ok(self.take())
+    }
+
+    /// Returns everything that can be produced without ending the stream.
+    pub fn flush(&mut self) -> io::Result<Vec<u8>> {
+        match &mut self.codec {
+            Codec::Deflate(w) => w.flush()?,
+            Codec::DeflateRaw(w) => w.flush()?,
+            Codec::Gzip(w) => w.flush()?,
+            Codec::Inflate(z) | Codec::InflateRaw(z) => {
+                z.run(&[], FlushDecompress::Sync, &mut self.out)?
+            }
+            Codec::Gunzip(w) => w.flush()?,
+            Codec::BrotliCompress(w) => w.flush()?,
+            Codec::BrotliDecompress(w) => w.flush()?,
+            Codec::Unzip(_) | Codec::Finished => {}
+        }
+        Ok(self.take())
+    }
+
+    /// Ends the stream and returns the remaining output. Decoders fail when
+    /// the input stopped in the middle of the compressed data.
+    pub fn finish(&mut self) -> io::Result<Vec<u8>> {
+        let codec = std::mem::replace(&mut self.codec, Codec::Finished);
+        let mut out = std::mem::take(&mut self.out);
+        match codec {
+            Codec::Deflate(w) => out.extend(w.finish()?),
+            Codec::DeflateRaw(w) => out.extend(w.finish()?),
+            Codec::Gzip(w) => out.extend(w.finish()?),
+            Codec::Inflate(mut z) | Codec::InflateRaw(mut z) => z.finish(&mut out)?,
+            Codec::Gunzip(w) => out.extend(w.finish()?),
+            Codec::Unzip(_) => return Err(unexpected_eof()),
+            Codec::BrotliCompress(w) => out.extend(w.into_inner()),
+            Codec::BrotliDecompress(mut w) => {
+                w.close()?;
+                out.extend(std::mem::take(w.get_mut()));
+            }
+            Codec::Finished => {}
+        }
+        Ok(out)
+    }
+
+    fn take(&mut self) -> Vec<u8> {
+        let mut out = std::mem::take(&mut self.out);
+        let produced = match &mut self.codec {
+            Codec::Deflate(w) => std::mem::take(w.get_mut()),
+            Codec::DeflateRaw(w) => std::mem::take(w.get_mut()),
+            Codec::Gzip(w) => std::mem::take(w.get_mut()),
+            Codec::Gunzip(w) => std::mem::take(w.get_mut()),
+            Codec::BrotliCompress(w) => std::mem::take(w.get_mut()),
+            Codec::BrotliDecompress(w) => std::mem::take(w.get_mut()),
+            _ => vec![],
+        };
+        out.extend(produced);
+        out
+    }
+}
+
+fn read_u32(obj: &JsObject, key: &str) -> Option<u32> {
+    match obj.get(key) {
+        JsValue::Int(n) if n >= 0 => Some(n as u32),
+        JsValue::Float(n) if n >= 0.0 => Some(n as u32),
+        _ => None,
+    }
+}
+
+fn bytes_arg(argv: &[JsValue]) -> Option<Vec<u8>> {
+    match argv.first() {
+        Some(JsValue::ArrayBuffer(buf)) => Some(buf.to_vec()),
+        Some(JsValue::String(s)) => Some(s.to_string().into_bytes()),
+        Some(JsValue::UnDefined) | Some(JsValue::Null) | None => Some(vec![]),
+        _ => None,
+    }
+}
+
+fn to_js(ctx: &mut Context, r: io::Result<Vec<u8>>) -> JsValue {
+    match r {
+        Ok(out) => ctx.new_array_buffer(&out).into(),
+        Err(e) => {
+            let code = match e.kind() {
+                io::ErrorKind::UnexpectedEof => "Z_BUF_ERROR",
+                io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput => "Z_DATA_ERROR",
+                _ => "ERR_ZLIB",
+            };
+            let err = ctx.new_error(e.to_string().as_str());
+            if let JsValue::Object(mut obj) = err.clone() {
+                obj.set("code", ctx.new_string(code).into());
+            }
+            ctx.throw_error(err).into()
+        }
+    }
+}
+
+impl ZlibStream {
+    fn js_write(&mut self, _this: &mut JsObject, ctx: &mut Context, argv: &[JsValue]) -> JsValue {
+        match bytes_arg(argv) {
+            Some(data) => {
+                let r = self.write(&data);
+                to_js(ctx, r)
+            }
+            None => ctx.throw_type_error("data must be an ArrayBuffer").into(),
+        }
+    }
+
+    fn js_flush(&mut self, _this: &mut JsObject, ctx: &mut Context, _argv: &[JsValue]) -> JsValue {
+        let r = self.flush();
+        to_js(ctx, r)
+    }
+
+    fn js_finish(&mut self, _this: &mut JsObject, ctx: &mut Context, _argv: &[JsValue]) -> JsValue {
+        let r = self.finish();
+        to_js(ctx, r)
+    }
+}
+
+impl JsClassDef for ZlibStream {
+    type RefType = ZlibStream;
+
+    const CLASS_NAME: &'static str = "ZlibStream";
+    const CONSTRUCTOR_ARGC: u8 = 2;
+
+    const FIELDS: &'static [JsClassField<Self::RefType>] = &[];
+
+    const METHODS: &'static [JsClassMethod<Self::RefType>] = &[
+        ("write", 1, Self::js_write),
+        ("flush", 0, Self::js_flush),
+        ("finish", 0, Self::js_finish),
+    ];
+
+    unsafe fn mut_class_id_ptr() -> &'static mut u32 {
+        static mut CLASS_ID: u32 = 0;
+        &mut *std::ptr::addr_of_mut!(CLASS_ID)
+    }
+
+    /// `new ZlibStream(mode, { level, quality, lgwin })`
+    fn constructor_fn(ctx: &mut Context, argv: &[JsValue]) -> Result<ZlibStream, JsValue> {
+        let mode = match argv.first() {
+            Some(JsValue::String(s)) => s.to_string(),
+            _ => return Err(ctx.throw_type_error("mode must be a string").into()),
+        };
+        let mut opts = ZlibOptions::default();
+        if let Some(JsValue::Object(o)) = argv.get(1) {
+            opts.level = read_u32(o, "level").unwrap_or(opts.level);
+            opts.quality = read_u32(o, "quality").unwrap_or(opts.quality);
+            opts.lgwin = read_u32(o, "lgwin").unwrap_or(opts.lgwin);
+        }
+        ZlibStream::new(&mode, opts).ok_or_else(|| {
+            ctx.throw_type_error(&format!("unknown mode {}", mode))
+                .into()
+        })
+    }
+}
+
+struct ZlibModule;
+
+impl ModuleInit for ZlibModule {
+    fn init_module(ctx: &mut Context, m: &mut JsModuleDef) {
+        let class_ctor = register_class::<ZlibStream>(ctx);
+        m.add_export(ZlibStream::CLASS_NAME, class_ctor);
+    }
+}
+
+pub fn init_module(ctx: &mut Context) {
+    ctx.register_module("_node:zlib\0", ZlibModule, &[ZlibStream::CLASS_NAME])
+}
@@ -6,6 +6,7 @@ mod event_loop;
 mod internal_module;
 mod quickjs_sys;
 
+pub use event_loop::memfs::VirtualFs;
 pub use event_loop::EventLoop;
 
 pub use quickjs_sys::*;
@@ -102,7 +102,13 @@ unsafe extern "C" fn module_loader(
         }
     }
 
-    if !path.is_file() {
+    // relative to the cwd of `process.chdir` first, then the bundled modules
+    let resolved = std::path::PathBuf::from(crate::event_loop::wasi_fs::absolute_path(
+        &path.to_string_lossy(),
+    ));
+    if resolved.is_file() {
+        path = resolved;
+    } else {
         let modules_dir = std::env::var("QJS_LIB").unwrap_or("./modules".to_string());
         path = std::path::PathBuf::from(modules_dir).join(path);
     }
@@ -173,6 +179,9 @@ impl Runtime {
     pub fn run_with_context<F: FnMut(&mut Context) -> R, R>(&mut self, mut f: F) -> R {
         unsafe {
             let mut ctx = Context::new_with_rt(self.0);
+            if let Some(event_loop) = ctx.event_loop() {
+                event_loop.activate_virtual_fs();
+            }
             f(&mut ctx)
         }
     }
@@ -289,11 +298,109 @@ fn js_init_dirname(ctx: &mut Context) {
     };
 }
 
+/// Replaces a lazy global accessor with a plain writable value.
+fn js_define_global_value(ctx: &mut Context, name: &str, value: JsValue) {
+    let global = ctx.get_global();
+    unsafe {
+        JS_DefinePropertyValueStr(
+            ctx.ctx,
+            global.0.v,
+            make_c_string(name).as_ptr(),
+            value.into_qjs_value(),
+            (JS_PROP_CONFIGURABLE | JS_PROP_WRITABLE) as i32,
+        );
+    }
+}
+
+/// Web APIs such as `fetch` are implemented in js modules. The globals are
+/// accessors that evaluate the owning module on first use, and the module
+/// then installs the real values in their place.
+fn js_init_lazy_globals(ctx: &mut Context) {
+    macro_rules! lazy_global {
+        ($name:expr, $module:expr) => {{
+            // a module that reads the global while it loads gets `undefined`
+            // instead of loading itself again
+            thread_local! {
+                static LOADING: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+            }
+
+            fn get(ctx: &mut Context, _this_val: JsValue, _argv: &[JsValue]) -> JsValue {
+                if LOADING.with(|loading| loading.replace(true)) {
+                    return JsValue::UnDefined;
+                }
+                // the getter stays when the module fails, the next use tries
+                // again and the error is thrown as it is
+                let r = ctx.import_module($module);
+                let value = match r {
+                    JsValue::Exception(_) => r,
+                    _ => ctx.get_global().get($name),
+                };
+                LOADING.with(|loading| loading.set(false));
+                value
+            }
+
+            fn set(ctx: &mut Context, _this_val: JsValue, argv: &[JsValue]) -> JsValue {
+                let value = argv.get(0).cloned().unwrap_or(JsValue::UnDefined);
+                js_define_global_value(ctx, $name, value);
+                JsValue::UnDefined
+            }
+
+            let global = ctx.get_global();
+            let get: JsValue = ctx.wrap_function($name, get).into();
+            let set: JsValue = ctx.wrap_function($name, set).into();
+            unsafe {
+                let ctx = ctx.ctx;
+                let name = JS_NewAtom(ctx, make_c_string($name).as_ptr());
+                JS_DefineProperty(
+                    ctx,
+                    global.0.v,
+                    name,
+                    js_undefined(),
+                    get.get_qjs_value(),
+                    set.get_qjs_value(),
+                    (JS_PROP_THROW
+                        | JS_PROP_HAS_CONFIGURABLE
+                        | JS_PROP_CONFIGURABLE
+                        | JS_PROP_HAS_GET
+                        | JS_PROP_HAS_SET) as i32,
+                );
+                JS_FreeAtom(ctx, name);
+            }
+        }};
+    }
+
+    lazy_global!("fetch", "internal/fetch");
+    lazy_global!("Headers", "internal/fetch");
+    lazy_global!("Request", "internal/fetch");
+    lazy_global!("Response", "internal/fetch");
+    lazy_global!("FormData", "internal/fetch");
+    lazy_global!("Blob", "internal/fetch");
+    lazy_global!("File", "internal/fetch");
+    lazy_global!("AbortController", "internal/fetch");
+    lazy_global!("AbortSignal", "internal/fetch");
+    lazy_global!("DOMException", "internal/fetch");
+    lazy_global!("ReadableStream", "internal/fetch");
+    lazy_global!("WebSocket", "internal/websocket");
+}
+
 impl Context {
     pub fn event_loop(&mut self) -> Option<&mut super::EventLoop> {
         unsafe { (JS_GetRuntimeOpaque(self.rt()) as *mut super::EventLoop).as_mut() }
     }
 
+    /// Runs the `_node:fs` calls of this context against `vfs` instead of
+    /// the host filesystem, `None` goes back to the host.
+    pub fn set_virtual_fs(&mut self, vfs: Option<super::VirtualFs>) {
+        if let Some(event_loop) = self.event_loop() {
+            event_loop.set_virtual_fs(vfs);
+        }
+    }
+
+    pub fn virtual_fs(&mut self) -> Option<super::VirtualFs> {
+        self.event_loop()
+            .and_then(|event_loop| event_loop.virtual_fs())
+    }
+
     fn event_loop_run_once(&mut self) -> std::io::Result<usize> {
         unsafe {
             if let Some(event_loop) =
@@ -306,6 +413,22 @@ impl Context {
         }
     }
 
+    /// What `JS_ComputeMemoryUsage` counts for the runtime of this context:
+    /// the bytes malloc'd, the bytes of them in use and the bytes held by
+    /// `ArrayBuffer`s.
+    pub fn memory_usage(&mut self) -> (i64, i64, i64) {
+        unsafe {
+            let mut usage = mem::MaybeUninit::<JSMemoryUsage>::zeroed();
+            JS_ComputeMemoryUsage(self.rt(), usage.as_mut_ptr());
+            let usage = usage.assume_init();
+            (
+                usage.malloc_size,
+                usage.memory_used_size,
+                usage.binary_object_size,
+            )
+        }
+    }
+
     #[inline]
     unsafe fn rt(&mut self) -> *mut JSRuntime {
         JS_GetRuntime(self.ctx)
@@ -336,14 +459,20 @@ impl Context {
         }
 
         js_init_dirname(&mut ctx);
+        js_init_lazy_globals(&mut ctx);
 
         super::internal_module::core::init_global_function(&mut ctx);
         super::internal_module::core::init_ext_function(&mut ctx);
         super::internal_module::encoding::init_encoding_module(&mut ctx);
         super::internal_module::wasi_net_module::init_module(&mut ctx);
+        #[cfg(feature = "tls")]
+        super::internal_module::wasi_tls_module::init_module(&mut ctx);
         super::internal_module::httpx::init_module(&mut ctx);
         super::internal_module::os::init_module(&mut ctx);
+        super::internal_module::process::init_module(&mut ctx);
         super::internal_module::fs::init_module(&mut ctx);
+        super::internal_module::zlib_module::init_module(&mut ctx);
+        super::internal_module::dns_module::init_module(&mut ctx);
 
         ctx
     }
@@ -415,6 +544,30 @@ impl Context {
         self.promise_loop_poll();
     }
 
+    /// Evaluates the module `name` unless it already was, as a bare
+    /// `import` would. Gives an exception if it failed to load, the error
+    /// is left pending so that a native function can return it as thrown.
+    pub fn import_module(&mut self, name: &str) -> JsValue {
+        unsafe {
+            let ctx = self.ctx;
+            let code = format!("import '{}';", name);
+            let len = code.len();
+            let val = JS_Eval(
+                ctx,
+                make_c_string(code).as_ptr(),
+                len,
+                make_c_string("<globals>").as_ptr(),
+                (JS_EVAL_TYPE_MODULE | JS_EVAL_FLAG_COMPILE_ONLY) as i32,
+            );
+            let val = if JS_IsException_real(val) <= 0 {
+                JS_EvalFunction(ctx, val)
+            } else {
+                val
+            };
+            JsValue::from_qjs_value(ctx, val)
+        }
+    }
+
     pub fn new_function<F: JsFn>(&mut self, name: &str) -> JsFunction {
         unsafe {
             let name = make_c_string(name);
@@ -535,6 +688,11 @@ impl Context {
         }
     }
 
+    /// Clears the pending exception and returns the value that was thrown.
+    pub fn take_exception(&mut self) -> JsValue {
+        unsafe { JsValue::from_qjs_value(self.ctx, JS_GetException(self.ctx)) }
+    }
+
     pub fn new_promise(&mut self) -> (JsValue, JsValue, JsValue) {
         unsafe {
             let ctx = self.ctx;
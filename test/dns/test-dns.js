@@ -0,0 +1,209 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import * as net from 'wasi_net';
+import dns from 'dns';
+import * as dnsPromises from 'dns/promises';
+
+// A DNS server over TCP that answers from a fixed zone.
+const TYPES = { A: 1, NS: 2, CNAME: 5, SOA: 6, PTR: 12, MX: 15, TXT: 16, AAAA: 28, SRV: 33 };
+
+function name(n) {
+  const out = [];
+  for (const label of n.split('.').filter((l) => l !== '')) {
+    out.push(label.length, ...Array.from(label, (c) => c.charCodeAt(0)));
+  }
+  out.push(0);
+  return out;
+}
+
+const u16 = (n) => [n >> 8, n & 0xff];
+const u32 = (n) => [n >>> 24, (n >> 16) & 0xff, (n >> 8) & 0xff, n & 0xff];
+const txt = (...strings) => strings.flatMap((s) => [s.length, ...Array.from(s, (c) => c.charCodeAt(0))]);
+const soa = ['ns.example.test', 'admin.example.test', 7, 3600, 600, 86400, 60];
+const soaData = [...name(soa[0]), ...name(soa[1]), ...soa.slice(2).flatMap(u32)];
+
+const zone = {
+  'A example.test': [['example.test', 'A', 300, [10, 0, 0, 1]], ['example.test', 'A', 200, [10, 0, 0, 2]]],
+  'AAAA example.test': [['example.test', 'AAAA', 300, [0x20, 0x01, 0x0d, 0xb8, ...Array(11).fill(0), 1]]],
+  'MX example.test': [['example.test', 'MX', 300, [...u16(10), ...name('mail.example.test')]]],
+  'TXT example.test': [['example.test', 'TXT', 300, txt('v=spf1', ' -all')], ['example.test', 'TXT', 300, txt('hello')]],
+  'SOA example.test': [['example.test', 'SOA', 300, soaData]],
+  'NS example.test': [['example.test', 'NS', 300, name('ns.example.test')]],
+  'SRV _sip._tcp.example.test': [['_sip._tcp.example.test', 'SRV', 300, [...u16(1), ...u16(2), ...u16(5060), ...name('sip.example.test')]]],
+  'CNAME alias.example.test': [['alias.example.test', 'CNAME', 300, name('example.test')]],
+  'A alias.example.test': [
+    ['alias.example.test', 'CNAME', 300, name('example.test')],
+    ['example.test', 'A', 300, [10, 0, 0, 1]],
+  ],
+  'A short.example.test': [['short.example.test', 'A', 0, [10, 0, 0, 9]]],
+  'PTR 1.0.0.10.in-addr.arpa': [['1.0.0.10.in-addr.arpa', 'PTR', 300, name('example.test')]],
+};
+const queries = {};
+
+function answer(query) {
+  let pos = 12;
+  const labels = [];
+  while (query[pos] !== 0) {
+    labels.push(String.fromCharCode(...query.slice(pos + 1, pos + 1 + query[pos])));
+    pos += query[pos] + 1;
+  }
+  const qname = labels.join('.');
+  const qtype = Object.keys(TYPES).find((t) => TYPES[t] === (query[pos + 1] << 8 | query[pos + 2]));
+  const key = `${qtype} ${qname}`;
+  queries[key] = (queries[key] ?? 0) + 1;
+  if (qname.startsWith('slow.')) {
+    return null;
+  }
+  let rcode = 0;
+  let authority = [];
+  const answers = zone[key] ?? [];
+  if (qname.startsWith('fail.')) {
+    rcode = 2;
+  } else if (answers.length === 0) {
+    rcode = Object.keys(zone).some((k) => k.endsWith(` ${qname}`)) ? 0 : 3;
+    authority = [['example.test', 'SOA', 600, soaData]];
+  }
+  const records = (list) => list.flatMap(([n, type, ttl, data]) => [
+    ...name(n), ...u16(TYPES[type]), ...u16(1), ...u32(ttl), ...u16(data.length), ...data,
+  ]);
+  return [
+    query[0], query[1], 0x81, 0x80 | rcode, 0, 1, ...u16(answers.length), ...u16(authority.length), 0, 0,
+    ...query.slice(12, pos + 5), ...records(answers), ...records(authority),
+  ];
+}
+
+const server = new net.WasiTcpServer(0);
+const port = Number(server.local().split(':').pop());
+
+async function handle(conn) {
+  let data = [];
+  while (data.length < 2 || data.length < 2 + (data[0] << 8 | data[1])) {
+    const chunk = await conn.read();
+    if (chunk === undefined) {
+      return;
+    }
+    data = data.concat(Array.from(new Uint8Array(chunk)));
+  }
+  const reply = answer(data.slice(2));
+  if (reply !== null) {
+    conn.write(new Uint8Array([...u16(reply.length), ...reply]).buffer);
+  }
+}
+
+async function serve() {
+  try {
+    while (true) {
+      handle(await server.accept());
+    }
+  } catch {
+    // closed
+  }
+}
+serve();
+
+async function rejects(promise, code, syscall, hostname) {
+  await assert.rejects(promise, (err) => {
+    assert.strictEqual(err.code, code);
+    assert.strictEqual(err.syscall, syscall);
+    assert.strictEqual(err.hostname, hostname);
+    return true;
+  });
+}
+
+async function run() {
+  const resolver = new dnsPromises.Resolver({ tcp: true, timeout: 2000, tries: 1 });
+  resolver.setServers([`127.0.0.1:${port}`]);
+  assert.deepStrictEqual(resolver.getServers(), [`127.0.0.1:${port}`]);
+
+  // record types
+  assert.deepStrictEqual(await resolver.resolve4('example.test'), ['10.0.0.1', '10.0.0.2']);
+  assert.deepStrictEqual(await resolver.resolve4('example.test', { ttl: true }), [
+    { address: '10.0.0.1', ttl: 300 },
+    { address: '10.0.0.2', ttl: 200 },
+  ]);
+  assert.deepStrictEqual(await resolver.resolve6('example.test'), ['2001:db8::1']);
+  assert.deepStrictEqual(await resolver.resolveMx('example.test'), [{ priority: 10, exchange: 'mail.example.test' }]);
+  assert.deepStrictEqual(await resolver.resolveTxt('example.test'), [['v=spf1', ' -all'], ['hello']]);
+  assert.deepStrictEqual(await resolver.resolveNs('example.test'), ['ns.example.test']);
+  assert.deepStrictEqual(await resolver.resolveSrv('_sip._tcp.example.test'), [
+    { priority: 1, weight: 2, port: 5060, name: 'sip.example.test' },
+  ]);
+  assert.deepStrictEqual(await resolver.resolveSoa('example.test'), {
+    nsname: 'ns.example.test',
+    hostmaster: 'admin.example.test',
+    serial: 7,
+    refresh: 3600,
+    retry: 600,
+    expire: 86400,
+    minttl: 60,
+  });
+  assert.deepStrictEqual(await resolver.resolveCname('alias.example.test'), ['example.test']);
+  assert.deepStrictEqual(await resolver.resolve('alias.example.test'), ['10.0.0.1']);
+  assert.deepStrictEqual(await resolver.reverse('10.0.0.1'), ['example.test']);
+
+  // answers are cached for their TTL, names case-insensitively
+  assert.deepStrictEqual(await resolver.resolve4('EXAMPLE.test.'), ['10.0.0.1', '10.0.0.2']);
+  assert.strictEqual(queries['A example.test'], 1);
+  await resolver.resolve4('short.example.test');
+  await resolver.resolve4('short.example.test');
+  assert.strictEqual(queries['A short.example.test'], 2);
+
+  // errors carry node's codes, negative answers are cached too
+  await rejects(resolver.resolve4('missing.example.test'), 'ENOTFOUND', 'queryA', 'missing.example.test');
+  await rejects(resolver.resolve4('missing.example.test'), 'ENOTFOUND', 'queryA', 'missing.example.test');
+  assert.strictEqual(queries['A missing.example.test'], 1);
+  await rejects(resolver.resolveMx('short.example.test'), 'ENODATA', 'queryMx', 'short.example.test');
+  await rejects(resolver.resolve4('fail.example.test'), 'ESERVFAIL', 'queryA', 'fail.example.test');
+  await rejects(resolver.resolve4('a..b'), 'EBADNAME', 'queryA', 'a..b');
+  await assert.rejects(resolver.resolve('example.test', 'BOGUS'), { code: 'ERR_INVALID_ARG_VALUE' });
+  assert.throws(() => resolver.setServers(['not an ip']), { code: 'ERR_INVALID_ARG_VALUE' });
+
+  // a server that never answers
+  const impatient = new dnsPromises.Resolver({ tcp: true, timeout: 100, tries: 2 });
+  impatient.setServers([`127.0.0.1:${port}`]);
+  await rejects(impatient.resolve4('slow.example.test'), 'ETIMEOUT', 'queryA', 'slow.example.test');
+  assert.strictEqual(queries['A slow.example.test'], 2);
+  const pending = impatient.resolveTxt('slow.example.test');
+  impatient.cancel();
+  await rejects(pending, 'ECANCELLED', 'queryTxt', 'slow.example.test');
+
+  // the callback API on its own resolver
+  const callbacks = new dns.Resolver({ tcp: true });
+  callbacks.setServers([`127.0.0.1:${port}`]);
+  await new Promise((resolve) => callbacks.resolveSrv('_sip._tcp.example.test', common.mustCall((err, records) => {
+    assert.ifError(err);
+    assert.strictEqual(records[0].port, 5060);
+    resolve();
+  })));
+  await new Promise((resolve) => callbacks.resolve('missing.example.test', 'AAAA', common.mustCall((err) => {
+    assert.strictEqual(err.code, 'ENOTFOUND');
+    resolve();
+  })));
+
+  // lookup answers literals and localhost without a server
+  assert.deepStrictEqual(await dnsPromises.lookup('10.1.2.3'), { address: '10.1.2.3', family: 4 });
+  assert.deepStrictEqual(await dnsPromises.lookup('::1', { all: true }), [{ address: '::1', family: 6 }]);
+  assert.deepStrictEqual(await dnsPromises.lookup('localhost', { family: 6 }), { address: '::1', family: 6 });
+  await assert.rejects(dnsPromises.lookup('10.1.2.3', { family: 6 }), { code: 'ENOTFOUND', syscall: 'getaddrinfo' });
+  await new Promise((resolve) => dns.lookup('127.0.0.1', common.mustCall((err, address, family) => {
+    assert.ifError(err);
+    assert.strictEqual(address, '127.0.0.1');
+    assert.strictEqual(family, 4);
+    resolve();
+  })));
+
+  // the default resolver asks over UDP, nobody listens there
+  dns.setServers([`127.0.0.1:${port}`]);
+  assert.deepStrictEqual(dnsPromises.getServers(), [`127.0.0.1:${port}`]);
+  const udp = new dnsPromises.Resolver({ timeout: 200, tries: 1 });
+  udp.setServers([`127.0.0.1:${port}`]);
+  await assert.rejects(udp.resolve4('example.test'), (err) => {
+    assert.ok(['ECONNREFUSED', 'ETIMEOUT'].includes(err.code), err.code);
+    return true;
+  });
+
+  server.close();
+}
+
+run().then(common.mustCall());
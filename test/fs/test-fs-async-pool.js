@@ -0,0 +1,125 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import fs from 'fs';
+import path from 'path';
+import tmpdir from '../common/tmpdir';
+
+// The async fs calls run on a pool of jobs that take turns on the event
+// loop, large reads and writes are split in chunks.
+
+tmpdir.refresh();
+
+const big = Buffer.alloc(4 * 1024 * 1024 + 123);
+for (let i = 0; i < big.length; i++) {
+  big[i] = i % 251;
+}
+
+async function run() {
+  // other callbacks run while a large file is written and read
+  let turns = 0;
+  const ticker = setInterval(() => turns++, 0);
+
+  const file = path.join(tmpdir.path, 'big.bin');
+  let before = turns;
+  await fs.promises.writeFile(file, big);
+  assert(turns > before);
+  assert.strictEqual(fs.statSync(file).size, big.length);
+
+  before = turns;
+  const read = await fs.promises.readFile(file);
+  assert(turns > before);
+  assert(read.equals(big));
+  clearInterval(ticker);
+
+  // positional reads and writes through a FileHandle
+  const handle = await fs.promises.open(file, 'r+');
+  const { bytesRead, buffer } = await handle.read(Buffer.alloc(100000), 0, 100000, 3000000);
+  assert.strictEqual(bytesRead, 100000);
+  assert(buffer.equals(big.subarray(3000000, 3100000)));
+  await handle.write(Buffer.from('pooled'), 0, 6, 10);
+  const stat = await handle.stat();
+  assert.strictEqual(stat.size, big.length);
+  await handle.close();
+  assert.strictEqual(fs.readFileSync(file).subarray(10, 16).toString(), 'pooled');
+
+  // large positional writes on one fd in flight at once keep their offsets
+  {
+    const halves = [Buffer.alloc(2 * 1024 * 1024, 'a'), Buffer.alloc(2 * 1024 * 1024, 'b')];
+    const target = path.join(tmpdir.path, 'halves.bin');
+    const fd = fs.openSync(target, 'w');
+    await Promise.all(halves.map((half, i) => new Promise((resolve, reject) => {
+      fs.write(fd, half, 0, half.length, i * half.length, (err, n) => {
+        return err ? reject(err) : resolve(n);
+      });
+    })));
+    fs.closeSync(fd);
+    assert(fs.readFileSync(target).equals(Buffer.concat(halves)));
+  }
+
+  // a short read stops at the end of the file
+  const tail = await new Promise((resolve, reject) => {
+    fs.open(file, 'r', (err, fd) => {
+      if (err) {
+        return reject(err);
+      }
+      fs.read(fd, Buffer.alloc(1000), 0, 1000, big.length - 10, (err, len) => {
+        fs.closeSync(fd);
+        return err ? reject(err) : resolve(len);
+      });
+    });
+  });
+  assert.strictEqual(tail, 10);
+
+  // more calls than the pool runs at once settle in the order they came
+  const order = [];
+  await Promise.all(Array.from({ length: 12 }, (_, i) => {
+    return fs.promises.stat(file).then((stat) => {
+      assert(stat.isFile());
+      order.push(i);
+    });
+  }));
+  assert.deepStrictEqual(order, Array.from({ length: 12 }, (_, i) => i));
+
+  // the metadata calls
+  const dir = path.join(tmpdir.path, 'meta');
+  assert.strictEqual(await fs.promises.mkdir(path.join(dir, 'a', 'b'), { recursive: true }), dir);
+  await fs.promises.copyFile(file, path.join(dir, 'copy.bin'));
+  await assert.rejects(fs.promises.copyFile(file, path.join(dir, 'copy.bin'), fs.constants.COPYFILE_EXCL), {
+    code: 'EEXIST',
+    syscall: 'copyfile',
+  });
+  await fs.promises.rename(path.join(dir, 'copy.bin'), path.join(dir, 'moved.bin'));
+  await fs.promises.access(path.join(dir, 'moved.bin'));
+  await fs.promises.utimes(path.join(dir, 'moved.bin'), 1000, 2000);
+  assert.strictEqual((await fs.promises.stat(path.join(dir, 'moved.bin'))).mtime.getTime(), 2000);
+  await fs.promises.truncate(path.join(dir, 'moved.bin'), 5);
+  assert.strictEqual((await fs.promises.lstat(path.join(dir, 'moved.bin'))).size, 5);
+  assert.strictEqual(await fs.promises.realpath(path.join(dir, 'a', '..', 'moved.bin')), path.join(dir, 'moved.bin'));
+
+  const names = await fs.promises.readdir(dir);
+  assert.deepStrictEqual(names.sort(), ['a', 'moved.bin']);
+  const dirents = await fs.promises.readdir(dir, { withFileTypes: true });
+  assert.strictEqual(dirents.find((d) => d.name === 'a').isDirectory(), true);
+  assert.strictEqual(dirents[0].parentPath, dir);
+
+  await fs.promises.rmdir(path.join(dir, 'a', 'b'));
+  assert(!fs.existsSync(path.join(dir, 'a', 'b')));
+  assert.strictEqual(await new Promise((resolve) => fs.exists(path.join(dir, 'a'), resolve)), true);
+
+  // errors carry the code, syscall and path like the sync calls
+  const missing = path.join(tmpdir.path, 'missing');
+  await assert.rejects(fs.promises.stat(missing), { code: 'ENOENT', syscall: 'stat', path: missing });
+  await assert.rejects(fs.promises.readdir(missing), { code: 'ENOENT', syscall: 'scandir' });
+  await assert.rejects(fs.promises.rename(missing, path.join(dir, 'x')), { code: 'ENOENT', syscall: 'rename' });
+  await assert.rejects(fs.promises.open(missing, 'r'), { code: 'ENOENT', syscall: 'open' });
+  await new Promise((resolve) => {
+    fs.stat(missing, { throwIfNoEntry: false }, common.mustCall((err, stat) => {
+      assert.strictEqual(err, null);
+      assert.strictEqual(stat, undefined);
+      resolve();
+    }));
+  });
+}
+
+run().then(common.mustCall());
@@ -24,6 +24,7 @@ import common from '../common';
 import assert from 'assert';
 import path from 'path';
 import fs from 'fs';
+import process from 'process';
 
 let mode_async;
 let mode_sync;
@@ -0,0 +1,159 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import fs from 'fs';
+import path from 'path';
+import tmpdir from '../common/tmpdir';
+
+// Lock files and atomic writes, for instances sharing a directory.
+
+tmpdir.refresh();
+
+const leftovers = () => fs.readdirSync(tmpdir.path).filter((name) => /\.(tmp|stale)$/.test(name));
+
+// `wx` fails on anything already there, a dangling symlink too
+{
+  const file = path.join(tmpdir.path, 'excl.txt');
+  const fd = fs.openSync(file, 'wx');
+  fs.closeSync(fd);
+  assert.throws(() => fs.openSync(file, 'wx'), { code: 'EEXIST', syscall: 'open' });
+
+  const dangling = path.join(tmpdir.path, 'dangling');
+  fs.symlinkSync(path.join(tmpdir.path, 'nowhere'), dangling);
+  assert.throws(() => fs.openSync(dangling, 'wx'), { code: 'EEXIST', syscall: 'open' });
+  assert(!fs.existsSync(path.join(tmpdir.path, 'nowhere')));
+
+  // without `x` the symlink is followed
+  const linked = path.join(tmpdir.path, 'linked');
+  fs.symlinkSync(file, linked);
+  fs.writeFileSync(linked, 'through the link');
+  assert.strictEqual(fs.readFileSync(file, 'utf8'), 'through the link');
+}
+
+// writeFileAtomicSync replaces the whole file and leaves nothing behind
+{
+  const file = path.join(tmpdir.path, 'atomic.json');
+  fs.writeFileAtomicSync(file, '{"v":1}');
+  assert.strictEqual(fs.readFileSync(file, 'utf8'), '{"v":1}');
+  fs.writeFileAtomicSync(file, Buffer.from('{"v":2}'), { fsync: false });
+  assert.strictEqual(fs.readFileSync(file, 'utf8'), '{"v":2}');
+  fs.writeFileAtomicSync(file, 'aGk=', 'base64');
+  assert.strictEqual(fs.readFileSync(file, 'utf8'), 'hi');
+  assert.deepStrictEqual(leftovers(), []);
+
+  assert.throws(() => fs.writeFileAtomicSync(path.join(tmpdir.path, 'missing', 'x'), 'x'), {
+    code: 'ENOENT',
+    syscall: 'open',
+  });
+  const dir = path.join(tmpdir.path, 'a-dir');
+  fs.mkdirSync(dir);
+  assert.throws(() => fs.writeFileAtomicSync(dir, 'x'), { code: 'EISDIR', syscall: 'rename' });
+  // the temporary file goes next to the directory, not into it
+  assert.throws(() => fs.writeFileAtomicSync(`${dir}/`, 'x'), { syscall: 'rename' });
+  assert.deepStrictEqual(leftovers(), []);
+  assert.throws(() => fs.writeFileAtomicSync(file, 1), { code: 'ERR_INVALID_ARG_TYPE' });
+  assert.throws(() => fs.writeFileAtomicSync(file, 'x', { fsync: 1 }), { code: 'ERR_INVALID_ARG_TYPE' });
+}
+
+// lockSync takes the lock once until it is released
+{
+  const file = path.join(tmpdir.path, 'cache.bin');
+  const lock = fs.lockSync(file);
+  assert.strictEqual(lock.path, `${file}.lock`);
+  assert(lock.held);
+  assert(fs.existsSync(`${file}.lock`));
+  assert.throws(() => fs.lockSync(file), {
+    code: 'ERR_FS_LOCKED',
+    syscall: 'lock',
+    path: `${file}.lock`,
+  });
+  lock.refreshSync();
+  lock.releaseSync();
+  assert(!lock.held);
+  assert(!fs.existsSync(`${file}.lock`));
+  assert.throws(() => lock.releaseSync(), { code: 'ERR_FS_LOCK_COMPROMISED' });
+
+  const other = fs.lockSync(file, { lockfilePath: path.join(tmpdir.path, 'custom.lock') });
+  assert.strictEqual(other.path, path.join(tmpdir.path, 'custom.lock'));
+  other.releaseSync();
+
+  assert.throws(() => fs.lockSync(file, { stale: -1 }), { code: 'ERR_OUT_OF_RANGE' });
+}
+
+// a lock nobody refreshed is taken over, its old holder finds out
+{
+  const file = path.join(tmpdir.path, 'stale.bin');
+  const old = fs.lockSync(file);
+  const past = new Date(Date.now() - 60000);
+  fs.utimesSync(old.path, past, past);
+
+  const fresh = fs.lockSync(file, { stale: 10000 });
+  assert.throws(() => old.refreshSync(), { code: 'ERR_FS_LOCK_COMPROMISED', path: old.path });
+  assert(!old.held);
+  assert.throws(() => old.releaseSync(), { code: 'ERR_FS_LOCK_COMPROMISED' });
+  assert(fs.existsSync(fresh.path));
+  // a fresh lock is not stale
+  assert.throws(() => fs.lockSync(file, { stale: 10000 }), { code: 'ERR_FS_LOCKED' });
+  fresh.releaseSync();
+  assert.deepStrictEqual(leftovers(), []);
+}
+
+async function run() {
+  const file = path.join(tmpdir.path, 'async.json');
+
+  // writers racing on one file, every read sees one of them whole
+  const bodies = Array.from({ length: 8 }, (_, i) => JSON.stringify({ writer: i, pad: 'x'.repeat(50000) }));
+  await Promise.all(bodies.map((body) => fs.promises.writeFileAtomic(file, body)));
+  assert(bodies.includes(fs.readFileSync(file, 'utf8')));
+  assert.deepStrictEqual(leftovers(), []);
+
+  await new Promise((resolve) => {
+    fs.writeFileAtomic(file, 'callback', common.mustCall((err) => {
+      assert.strictEqual(err, null);
+      resolve();
+    }));
+  });
+  assert.strictEqual(fs.readFileSync(file, 'utf8'), 'callback');
+  await assert.rejects(fs.promises.writeFileAtomic(path.join(tmpdir.path, 'missing', 'x'), 'x'), {
+    code: 'ENOENT',
+  });
+
+  // a waiting lock gets it once the holder lets go
+  const held = await fs.promises.lock(file);
+  await assert.rejects(fs.promises.lock(file), { code: 'ERR_FS_LOCKED' });
+  setTimeout(() => held.release(), 50);
+  const next = await fs.promises.lock(file, { retries: 20, retryWait: 20 });
+  assert(!held.held);
+  await next.refresh();
+  await next.release();
+
+  // `update` keeps a lock fresh while it is held
+  const kept = await fs.promises.lock(file, { stale: 300, update: 50 });
+  await new Promise((resolve) => setTimeout(resolve, 500));
+  await assert.rejects(fs.promises.lock(file, { stale: 300 }), { code: 'ERR_FS_LOCKED' });
+  await kept.release();
+  assert(!fs.existsSync(kept.path));
+
+  // a stale lock replaced by a fresh one before it is taken aside is put
+  // back, whichever of the two gets there first the fresh lock stays
+  const raced = path.join(tmpdir.path, 'raced.bin');
+  const past = new Date(Date.now() - 60000);
+  for (let i = 0; i < 8; i++) {
+    fs.writeFileSync(`${raced}.lock`, 'stale');
+    fs.utimesSync(`${raced}.lock`, past, past);
+    fs.writeFileSync(`${raced}.fresh`, 'fresh');
+    const taking = fs.promises.lock(raced, { stale: 10000 }).catch((err) => err);
+    await fs.promises.rename(`${raced}.fresh`, `${raced}.lock`);
+    const res = await taking;
+    assert.strictEqual(fs.readFileSync(`${raced}.lock`, 'utf8'), 'fresh');
+    if (res instanceof Error) {
+      assert.strictEqual(res.code, 'ERR_FS_LOCKED');
+    } else {
+      await assert.rejects(res.release(), { code: 'ERR_FS_LOCK_COMPROMISED' });
+    }
+    assert.deepStrictEqual(leftovers(), []);
+    fs.unlinkSync(`${raced}.lock`);
+  }
+}
+
+run().then(common.mustCall());
@@ -0,0 +1,113 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import fs from 'fs';
+import path from 'path';
+
+// Runs against a VirtualFs seeded by tests/test-fs.rs with
+// /data/hello.txt, /data/sub/nested.txt and the link /data/link -> hello.txt,
+// nothing here reaches the host.
+
+const data = '/data';
+
+// the seeded tree
+assert.strictEqual(fs.readFileSync(path.join(data, 'hello.txt'), 'utf8'), 'hello from the archive');
+assert.deepStrictEqual(fs.readdirSync(data).sort(), ['hello.txt', 'link', 'sub']);
+assert.deepStrictEqual(fs.readdirSync(data, { recursive: true }).sort(), [
+  'hello.txt',
+  'link',
+  'sub',
+  path.join('sub', 'nested.txt'),
+]);
+assert.strictEqual(fs.readlinkSync(path.join(data, 'link')), 'hello.txt');
+assert(fs.lstatSync(path.join(data, 'link')).isSymbolicLink());
+assert.strictEqual(fs.readFileSync(path.join(data, 'link'), 'utf8'), 'hello from the archive');
+assert.strictEqual(fs.realpathSync(path.join(data, 'sub', '..', 'link')), path.join(data, 'hello.txt'));
+assert(!fs.existsSync('/test'));
+
+// files, directories and timestamps
+{
+  const dir = path.join(data, 'out');
+  fs.mkdirSync(path.join(dir, 'a', 'b'), { recursive: true });
+  const file = path.join(dir, 'a', 'file.txt');
+  fs.writeFileSync(file, 'abc');
+  fs.appendFileSync(file, 'def');
+  assert.strictEqual(fs.readFileSync(file, 'utf8'), 'abcdef');
+  fs.truncateSync(file, 4);
+  assert.strictEqual(fs.statSync(file).size, 4);
+
+  const fd = fs.openSync(file, 'r+');
+  fs.writeSync(fd, 'XY', 1);
+  const buf = Buffer.alloc(4);
+  assert.strictEqual(fs.readSync(fd, buf, 0, 4, 0), 4);
+  assert.strictEqual(buf.toString(), 'aXYd');
+  fs.closeSync(fd);
+
+  fs.utimesSync(file, 1000, 2000);
+  assert.strictEqual(fs.statSync(file).mtime.getTime(), 2000);
+  assert.strictEqual(fs.statSync(file).atime.getTime(), 1000);
+
+  fs.copyFileSync(file, path.join(dir, 'copy.txt'));
+  fs.renameSync(path.join(dir, 'copy.txt'), path.join(dir, 'a', 'b', 'moved.txt'));
+  fs.linkSync(file, path.join(dir, 'hard.txt'));
+  assert.strictEqual(fs.statSync(file).nlink, 2);
+  fs.symlinkSync('a/file.txt', path.join(dir, 'soft.txt'));
+  assert.strictEqual(fs.readFileSync(path.join(dir, 'soft.txt'), 'utf8'), 'aXYd');
+
+  fs.unlinkSync(path.join(dir, 'hard.txt'));
+  fs.rmSync(path.join(dir, 'a', 'b'), { recursive: true });
+  assert.deepStrictEqual(fs.readdirSync(path.join(dir, 'a')), ['file.txt']);
+}
+
+// the same errors as the host
+{
+  const missing = path.join(data, 'missing');
+  assert.throws(() => fs.readFileSync(missing), { code: 'ENOENT', syscall: 'open' });
+  assert.throws(() => fs.mkdirSync(data), { code: 'EEXIST', syscall: 'mkdir' });
+  assert.throws(() => fs.rmdirSync(path.join(data, 'sub')), { code: 'ENOTEMPTY', syscall: 'rmdir' });
+  assert.throws(() => fs.readdirSync(path.join(data, 'hello.txt')), { code: 'ENOTDIR', syscall: 'scandir' });
+  assert.throws(() => fs.unlinkSync(path.join(data, 'sub')), { code: 'EISDIR', syscall: 'unlink' });
+  assert.throws(() => fs.writeFileSync(path.join(data, 'sub'), ''), { code: 'EISDIR', syscall: 'open' });
+  fs.symlinkSync('loop', path.join(data, 'loop'));
+  assert.throws(() => fs.statSync(path.join(data, 'loop')), { code: 'ELOOP', syscall: 'stat' });
+  fs.unlinkSync(path.join(data, 'loop'));
+}
+
+async function run() {
+  const file = path.join(data, 'async.txt');
+  await fs.promises.writeFile(file, 'written async');
+  assert.strictEqual(await fs.promises.readFile(file, 'utf8'), 'written async');
+
+  const handle = await fs.promises.open(file, 'r+');
+  await handle.write('WRITTEN', 0);
+  assert.strictEqual((await handle.stat()).size, 13);
+  await handle.close();
+
+  await fs.promises.cp(path.join(data, 'sub'), path.join(data, 'sub-copy'), { recursive: true });
+  assert.strictEqual(await fs.promises.readFile(path.join(data, 'sub-copy', 'nested.txt'), 'utf8'), 'nested');
+  await assert.rejects(fs.promises.stat(path.join(data, 'missing')), { code: 'ENOENT', syscall: 'stat' });
+
+  // streams go through the same descriptors
+  await new Promise((resolve, reject) => {
+    fs.createReadStream(path.join(data, 'hello.txt'))
+      .pipe(fs.createWriteStream(path.join(data, 'streamed.txt')))
+      .on('finish', resolve)
+      .on('error', reject);
+  });
+  assert.strictEqual(fs.readFileSync(path.join(data, 'streamed.txt'), 'utf8'), 'hello from the archive');
+
+  // fs.watch polls the directory listing of the VirtualFs too
+  const watcher = fs.watch(data, { interval: 20 });
+  const events = [];
+  watcher.on('change', (eventType, filename) => events.push(`${eventType} ${filename}`));
+  fs.writeFileSync(path.join(data, 'watched.txt'), 'watched');
+  await new Promise((resolve) => setTimeout(resolve, 100));
+  watcher.close();
+  assert.deepStrictEqual(events, ['rename watched.txt']);
+  fs.unlinkSync(path.join(data, 'watched.txt'));
+
+  await fs.promises.rm(path.join(data, 'sub-copy'), { recursive: true });
+  await fs.promises.rm(path.join(data, 'out'), { recursive: true });
+}
+
+run().then(common.mustCall());
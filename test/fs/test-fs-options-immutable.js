@@ -45,15 +45,15 @@ if (false /*common.canCreateSymLink()*/) {
   fs.appendFile(fileName, 'ABCD', options, common.mustSucceed());
 }
 
-/*if (!common.isIBMi) { // IBMi does not support fs.watch()
+if (!common.isIBMi) { // IBMi does not support fs.watch()
   const watch = fs.watch(__filename, options, common.mustNotCall());
   watch.close();
-}*/
+}
 
-/*{
+{
   fs.watchFile(__filename, options, common.mustNotCall());
   fs.unwatchFile(__filename);
-}*/
+}
 
 /*{
   fs.realpathSync(__filename, options);
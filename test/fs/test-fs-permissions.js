@@ -0,0 +1,126 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import fs from 'fs';
+import path from 'path';
+import process from 'process';
+import tmpdir from '../common/tmpdir';
+
+// WASI has no chmod or chown, the modes and owners are kept for the run
+// and show up in stat and access.
+
+tmpdir.refresh();
+
+const file = path.join(tmpdir.path, 'file.txt');
+const dir = path.join(tmpdir.path, 'dir');
+fs.writeFileSync(file, 'mode');
+fs.mkdirSync(dir);
+
+// the file type bits are set and new entries follow the umask
+assert.strictEqual(process.umask(), 0o022);
+assert.strictEqual(fs.statSync(file).mode, fs.constants.S_IFREG | 0o644);
+assert.strictEqual(fs.statSync(dir).mode, fs.constants.S_IFDIR | 0o755);
+assert(fs.statSync(file).isFile());
+
+assert.strictEqual(process.umask(0o077), 0o022);
+const secret = path.join(tmpdir.path, 'secret');
+fs.writeFileSync(secret, '', { mode: 0o666 });
+assert.strictEqual(fs.statSync(secret).mode & 0o777, 0o600);
+fs.mkdirSync(path.join(tmpdir.path, 'a', 'b'), { recursive: true, mode: 0o777 });
+assert.strictEqual(fs.statSync(path.join(tmpdir.path, 'a', 'b')).mode & 0o777, 0o700);
+const copy = path.join(tmpdir.path, 'copy');
+fs.copyFileSync(file, copy);
+assert.strictEqual(fs.statSync(copy).mode & 0o777, 0o600);
+const atomic = path.join(tmpdir.path, 'atomic');
+fs.writeFileAtomicSync(atomic, 'data');
+assert.strictEqual(fs.statSync(atomic).mode & 0o777, 0o600);
+fs.symlinkSync(file, path.join(tmpdir.path, 'new-link'));
+assert.strictEqual(fs.lstatSync(path.join(tmpdir.path, 'new-link')).mode & 0o777, 0o777);
+process.umask(0o022);
+
+// a file renamed over another one shows its own mode, not the one replaced
+fs.chmodSync(copy, 0o640);
+fs.renameSync(atomic, copy);
+assert.strictEqual(fs.statSync(copy).mode & 0o777, 0o600);
+fs.unlinkSync(copy);
+
+// every path and fd of a file sees a chmod
+const link = path.join(tmpdir.path, 'hard-link');
+fs.linkSync(file, link);
+fs.chmodSync(file, 0o640);
+assert.strictEqual(fs.statSync(link).mode & 0o777, 0o640);
+const fd = fs.openSync(file, 'r');
+assert.strictEqual(fs.fstatSync(fd).mode & 0o777, 0o640);
+fs.fchmodSync(fd, '600');
+assert.strictEqual(fs.statSync(file).mode & 0o777, 0o600);
+fs.closeSync(fd);
+
+// the permission and special bits are kept, anything above is dropped
+fs.chmodSync(dir, 0o11777);
+assert.strictEqual(fs.statSync(dir).mode, fs.constants.S_IFDIR | 0o1777);
+
+// lchmod changes the link and not its target
+const symlink = path.join(tmpdir.path, 'symlink');
+fs.symlinkSync(file, symlink);
+fs.lchmodSync(symlink, 0o700);
+assert.strictEqual(fs.lstatSync(symlink).mode & 0o777, 0o700);
+assert.strictEqual(fs.statSync(symlink).mode & 0o777, 0o600);
+
+// a rename keeps the mode, a new file in the same place does not
+const moved = path.join(tmpdir.path, 'moved.txt');
+fs.renameSync(link, moved);
+assert.strictEqual(fs.statSync(moved).mode & 0o777, 0o600);
+fs.unlinkSync(moved);
+fs.unlinkSync(file);
+fs.writeFileSync(file, 'again');
+assert.strictEqual(fs.statSync(file).mode & 0o777, 0o644);
+
+// access checks the owner bits, after a chown the group or other bits
+fs.chmodSync(file, 0o460);
+fs.accessSync(file, fs.constants.R_OK);
+assert.throws(() => fs.accessSync(file, fs.constants.W_OK), {
+  code: 'EACCES',
+  syscall: 'access',
+  path: file,
+});
+fs.chownSync(file, 1000, -1);
+assert.strictEqual(fs.statSync(file).uid, 1000);
+assert.strictEqual(fs.statSync(file).gid, 0);
+fs.accessSync(file, fs.constants.R_OK | fs.constants.W_OK);
+fs.chownSync(file, -1, 1000);
+assert.strictEqual(fs.statSync(file).uid, 1000);
+assert.throws(() => fs.accessSync(file, fs.constants.R_OK), { code: 'EACCES' });
+assert.strictEqual(fs.existsSync(file), true);
+
+assert.throws(() => fs.chmodSync(path.join(tmpdir.path, 'missing'), 0o600), {
+  code: 'ENOENT',
+  syscall: 'chmod',
+});
+assert.throws(() => fs.chownSync(file, -2, 0), { code: 'ERR_OUT_OF_RANGE' });
+assert.throws(() => fs.chmodSync(file, '8'), { code: 'ERR_INVALID_ARG_VALUE' });
+
+async function run() {
+  await fs.promises.chmod(file, 0o754);
+  await fs.promises.access(file, fs.constants.X_OK).then(common.mustNotCall(), common.mustCall());
+  await fs.promises.chown(file, 0, 0);
+  await fs.promises.access(file, fs.constants.X_OK);
+
+  const handle = await fs.promises.open(file, 'r');
+  await handle.chmod(0o400);
+  await handle.chown(7, 8);
+  const stat = await handle.stat();
+  assert.strictEqual(stat.mode & 0o777, 0o400);
+  assert.strictEqual(stat.uid, 7);
+  assert.strictEqual(stat.gid, 8);
+  await handle.close();
+
+  await new Promise((resolve) => {
+    fs.lchown(symlink, 3, 4, common.mustSucceed(() => {
+      assert.strictEqual(fs.lstatSync(symlink).uid, 3);
+      assert.strictEqual(fs.statSync(symlink).uid, 7);
+      resolve();
+    }));
+  });
+}
+
+run().then(common.mustCall());
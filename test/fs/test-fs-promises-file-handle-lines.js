@@ -0,0 +1,98 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import fs from 'fs';
+import path from 'path';
+import tmpdir from '../common/tmpdir';
+
+// FileHandle.readLines() and readableWebStream() stream the file through the
+// native reader, read() takes the options forms of node.
+
+tmpdir.refresh();
+
+const lines = Array.from({ length: 20000 }, (_, i) => `line ${i} ${'x'.repeat(i % 50)}`);
+const file = path.join(tmpdir.path, 'lines.txt');
+fs.writeFileSync(file, lines.join('\r\n') + '\n' + 'last without newline');
+const content = fs.readFileSync(file);
+
+const sleep = (ms) => new Promise((resolve) => setTimeout(resolve, ms));
+
+async function run() {
+  // for await gets every line, whatever the chunk boundaries
+  let handle = await fs.promises.open(file);
+  const seen = [];
+  for await (const line of handle.readLines({ highWaterMark: 1000 })) {
+    seen.push(line);
+  }
+  assert.deepStrictEqual(seen, [...lines, 'last without newline']);
+  // the stream closes the handle once it is read
+  await sleep(20);
+  assert.strictEqual(handle.fd, -1);
+
+  // and so do 'line' events, with start and end
+  handle = await fs.promises.open(file);
+  const reader = handle.readLines({ start: 0, end: lines[0].length + 1 });
+  const events = [];
+  reader.on('line', (line) => events.push(line));
+  await new Promise((resolve) => reader.on('close', resolve));
+  assert.deepStrictEqual(events, [lines[0]]);
+
+  // leaving the loop early stops reading
+  handle = await fs.promises.open(file);
+  let count = 0;
+  for await (const line of handle.readLines()) {
+    assert.strictEqual(line, lines[count]);
+    if (++count === 3) {
+      break;
+    }
+  }
+  assert.notStrictEqual(handle.fd, -1);
+  await handle.close();
+
+  // read() with options
+  handle = await fs.promises.open(file, 'r+');
+  let result = await handle.read();
+  assert.strictEqual(result.bytesRead, 16384);
+  assert.strictEqual(result.buffer.length, 16384);
+  result = await handle.read({ buffer: Buffer.alloc(10), position: 0 });
+  assert(result.buffer.equals(content.subarray(0, 10)));
+  result = await handle.read(Buffer.alloc(10), { offset: 2, length: 4, position: 5 });
+  assert.strictEqual(result.bytesRead, 4);
+  assert(result.buffer.subarray(2, 6).equals(content.subarray(5, 9)));
+  result = await handle.read(Buffer.alloc(4), 0, 4, 0);
+  assert.strictEqual(result.buffer.toString(), 'line');
+
+  // writev, sync, datasync and truncate
+  const { bytesWritten } = await handle.writev([Buffer.from('LI'), Buffer.from('NE')], 0);
+  assert.strictEqual(bytesWritten, 4);
+  await handle.sync();
+  await handle.datasync();
+  await handle.truncate(8);
+  assert.strictEqual((await handle.stat()).size, 8);
+  await handle.close();
+  assert.strictEqual(fs.readFileSync(file, 'utf8'), 'LINE' + content.subarray(4, 8).toString());
+
+  // a web stream reads on from the current position
+  const big = Buffer.alloc(100000, 'abc');
+  fs.writeFileSync(file, big);
+  handle = await fs.promises.open(file);
+  await handle.read(Buffer.alloc(10), 0, 10);
+  const stream = handle.readableWebStream();
+  assert.throws(() => handle.readableWebStream(), { code: 'ERR_INVALID_STATE' });
+  const chunks = [];
+  for await (const chunk of stream) {
+    assert(chunk instanceof Uint8Array);
+    chunks.push(Buffer.from(chunk));
+  }
+  assert(Buffer.concat(chunks).equals(big.subarray(10)));
+  await handle.close();
+  assert.throws(() => handle.readableWebStream(), { code: 'ERR_INVALID_STATE' });
+
+  handle = await fs.promises.open(file);
+  const reader2 = handle.readableWebStream({ autoClose: true }).getReader();
+  while (!(await reader2.read()).done);
+  await sleep(20);
+  assert.strictEqual(handle.fd, -1);
+}
+
+run().then(common.mustCall());
@@ -0,0 +1,170 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import fs from 'fs';
+import path from 'path';
+import tmpdir from '../common/tmpdir';
+
+// mkdir -p, rm -r, cp -r and readdir({ recursive }) walk the tree natively,
+// the async variants spread it over several turns of the event loop.
+
+tmpdir.refresh();
+
+// 3 levels of 4 directories with 5 files each, well over one turn of entries
+function makeTree(root, depth = 3) {
+  fs.mkdirSync(root, { recursive: true });
+  for (let f = 0; f < 5; f++) {
+    fs.writeFileSync(path.join(root, `f${f}.txt`), `${root} ${f}`);
+  }
+  if (depth > 1) {
+    for (let d = 0; d < 4; d++) {
+      makeTree(path.join(root, `d${d}`), depth - 1);
+    }
+  }
+}
+
+const treeEntries = 5 * 21 + 4 * 5;
+
+// mkdir -p returns the first directory it created
+{
+  const base = path.join(tmpdir.path, 'mkdirp');
+  const deep = path.join(base, 'a', 'b', 'c');
+  assert.strictEqual(fs.mkdirSync(deep, { recursive: true }), base);
+  assert(fs.statSync(deep).isDirectory());
+  assert.strictEqual(fs.mkdirSync(deep, { recursive: true }), undefined);
+  assert.strictEqual(fs.mkdirSync(path.join(deep, 'd'), { recursive: true }), path.join(deep, 'd'));
+
+  const file = path.join(base, 'file');
+  fs.writeFileSync(file, '');
+  assert.throws(() => fs.mkdirSync(file, { recursive: true }), { code: 'EEXIST', syscall: 'mkdir' });
+  assert.throws(() => fs.mkdirSync(path.join(file, 'x', 'y'), { recursive: true }), {
+    code: 'ENOTDIR',
+    syscall: 'mkdir',
+  });
+}
+
+// readdir recursive is breadth first with names relative to the root
+{
+  const root = path.join(tmpdir.path, 'listed');
+  makeTree(root);
+  const names = fs.readdirSync(root, { recursive: true });
+  assert.strictEqual(names.length, treeEntries);
+  assert(names.includes('f0.txt'));
+  assert(names.includes(path.join('d3', 'd2', 'f4.txt')));
+  assert(names.indexOf('d0') < names.indexOf(path.join('d0', 'f0.txt')));
+  assert(names.indexOf(path.join('d3', 'f0.txt')) < names.indexOf(path.join('d0', 'd0', 'f0.txt')));
+
+  const dirents = fs.readdirSync(root, { recursive: true, withFileTypes: true });
+  assert.strictEqual(dirents.length, treeEntries);
+  const deep = dirents.find((d) => d.parentPath === path.join(root, 'd1', 'd2') && d.name === 'f3.txt');
+  assert(deep.isFile());
+  assert.strictEqual(deep.path, deep.parentPath);
+  assert.strictEqual(dirents.filter((d) => d.isDirectory()).length, 20);
+
+  // plain readdir Dirents know their directory too
+  assert.strictEqual(fs.readdirSync(root, { withFileTypes: true })[0].parentPath, root);
+
+  assert.throws(() => fs.readdirSync(path.join(root, 'missing'), { recursive: true }), {
+    code: 'ENOENT',
+    syscall: 'scandir',
+  });
+}
+
+// rm leaves symlinked directories outside the tree alone
+{
+  const root = path.join(tmpdir.path, 'removed');
+  const outside = path.join(tmpdir.path, 'outside');
+  makeTree(root, 2);
+  fs.mkdirSync(outside);
+  fs.writeFileSync(path.join(outside, 'keep.txt'), 'keep');
+  fs.symlinkSync(outside, path.join(root, 'd0', 'link'));
+
+  assert.throws(() => fs.rmSync(root), { code: 'EISDIR', syscall: 'rm' });
+  fs.rmSync(root, { recursive: true });
+  assert(!fs.existsSync(root));
+  assert(fs.existsSync(path.join(outside, 'keep.txt')));
+  fs.rmSync(root, { recursive: true, force: true });
+  assert.throws(() => fs.rmSync(root, { recursive: true }), { code: 'ENOENT', syscall: 'rm' });
+}
+
+// cpSync filters every entry below the root and keeps timestamps
+{
+  const src = path.join(tmpdir.path, 'copy-src');
+  const dest = path.join(tmpdir.path, 'copy-dest', 'nested');
+  makeTree(src, 2);
+  fs.utimesSync(path.join(src, 'd2', 'f1.txt'), 1000, 2000);
+  const seen = [];
+  fs.cpSync(src, dest, {
+    recursive: true,
+    preserveTimestamps: true,
+    filter: (from, to) => {
+      seen.push(path.relative(src, from));
+      assert.strictEqual(path.relative(dest, to), path.relative(src, from));
+      return !from.endsWith('f0.txt') && !from.endsWith('d3');
+    },
+  });
+  assert.strictEqual(seen.length, 1 + 5 + 4 + 3 * 5);
+  const copied = fs.readdirSync(dest, { recursive: true });
+  assert.strictEqual(copied.length, 4 + 3 + 3 * 4);
+  assert(!copied.includes(path.join('d1', 'f0.txt')));
+  assert.strictEqual(fs.readFileSync(path.join(dest, 'd2', 'f2.txt'), 'utf8'), `${path.join(src, 'd2')} 2`);
+  assert.strictEqual(
+    fs.statSync(path.join(dest, 'd2', 'f1.txt')).mtime.getTime(),
+    fs.statSync(path.join(src, 'd2', 'f1.txt')).mtime.getTime()
+  );
+
+  assert.throws(() => fs.cpSync(src, dest, {
+    recursive: true,
+    filter: (from) => {
+      if (from.endsWith('f4.txt')) {
+        throw new Error('from the filter');
+      }
+      return true;
+    },
+  }), /from the filter/);
+  assert.throws(() => fs.cpSync(src, dest, { recursive: true, force: false, errorOnExist: true }), {
+    code: 'ERR_FS_CP_EEXIST',
+  });
+  assert.throws(() => fs.cpSync(src, path.join(tmpdir.path, 'copy-flat')), { code: 'ERR_FS_EISDIR' });
+}
+
+async function run() {
+  const root = path.join(tmpdir.path, 'async');
+  makeTree(root);
+
+  // other callbacks run while a tree is walked
+  let turns = 0;
+  const ticker = setInterval(() => turns++, 0);
+
+  const names = await fs.promises.readdir(root, { recursive: true });
+  assert.strictEqual(names.length, treeEntries);
+
+  const dest = path.join(tmpdir.path, 'async-copy');
+  await fs.promises.cp(root, dest, { recursive: true });
+  assert.strictEqual(fs.readdirSync(dest, { recursive: true }).length, treeEntries);
+  await assert.rejects(fs.promises.cp(root, dest, { recursive: true, force: false, errorOnExist: true }), {
+    code: 'ERR_FS_CP_EEXIST',
+  });
+
+  const before = turns;
+  await fs.promises.rm(dest, { recursive: true });
+  assert(turns > before);
+  assert(!fs.existsSync(dest));
+  clearInterval(ticker);
+
+  await new Promise((resolve) => {
+    fs.rm(dest, common.mustCall((err) => {
+      assert.strictEqual(err.code, 'ENOENT');
+      assert.strictEqual(err.syscall, 'rm');
+      resolve();
+    }));
+  });
+  await fs.promises.rm(dest, { recursive: true, force: true });
+
+  const dirents = await new Promise((resolve, reject) => {
+    fs.readdir(root, { recursive: true, withFileTypes: true }, (err, res) => (err ? reject(err) : resolve(res)));
+  });
+  assert.strictEqual(dirents.filter((d) => d.isFile()).length, 5 * 21);
+}
+
+run().then(common.mustCall());
@@ -0,0 +1,100 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import fs from 'fs';
+import path from 'path';
+import { pipeline } from 'stream/promises';
+import tmpdir from '../common/tmpdir';
+
+// File streams read and write through native chunked readers and writers,
+// one highWaterMark at a time.
+
+tmpdir.refresh();
+
+const big = Buffer.alloc(1024 * 1024 + 77);
+for (let i = 0; i < big.length; i++) {
+  big[i] = i % 253;
+}
+const file = path.join(tmpdir.path, 'big.bin');
+fs.writeFileSync(file, big);
+
+function collect(stream) {
+  return new Promise((resolve, reject) => {
+    const chunks = [];
+    stream.on('data', (chunk) => chunks.push(chunk));
+    stream.on('end', () => resolve(chunks));
+    stream.on('error', reject);
+  });
+}
+
+async function run() {
+  // chunks never exceed the highWaterMark
+  const stream = fs.createReadStream(file, { highWaterMark: 10000 });
+  const chunks = await collect(stream);
+  assert(chunks.every((chunk) => chunk.length <= 10000));
+  assert(Buffer.concat(chunks).equals(big));
+  assert.strictEqual(stream.bytesRead, big.length);
+
+  // start and end are inclusive
+  const range = Buffer.concat(await collect(fs.createReadStream(file, { start: 100000, end: 300000 })));
+  assert(range.equals(big.subarray(100000, 300001)));
+  const one = Buffer.concat(await collect(fs.createReadStream(file, { start: 5, end: 5 })));
+  assert(one.equals(big.subarray(5, 6)));
+  const tail = Buffer.concat(await collect(fs.createReadStream(file, { start: big.length - 3 })));
+  assert(tail.equals(big.subarray(big.length - 3)));
+
+  // a pipe keeps the reader in step with the writer
+  const copy = path.join(tmpdir.path, 'copy.bin');
+  const writer = fs.createWriteStream(copy, { highWaterMark: 4096 });
+  await pipeline(fs.createReadStream(file), writer);
+  assert.strictEqual(writer.bytesWritten, big.length);
+  assert(fs.readFileSync(copy).equals(big));
+
+  // writes from start leave the rest of the file in place
+  const patch = fs.createWriteStream(copy, { flags: 'r+', start: 10 });
+  patch.write('abc');
+  patch.end(Buffer.from('def'));
+  await new Promise((resolve) => patch.on('close', resolve));
+  const patched = fs.readFileSync(copy);
+  assert.strictEqual(patched.length, big.length);
+  assert.strictEqual(patched.subarray(10, 16).toString(), 'abcdef');
+  assert(patched.subarray(16).equals(big.subarray(16)));
+
+  // corked writes go down together
+  const corked = path.join(tmpdir.path, 'corked.txt');
+  const batch = fs.createWriteStream(corked);
+  await new Promise((resolve) => batch.on('ready', resolve));
+  batch.cork();
+  for (let i = 0; i < 10; i++) {
+    batch.write(`${i}`);
+  }
+  batch.uncork();
+  batch.end();
+  await new Promise((resolve) => batch.on('finish', resolve));
+  assert.strictEqual(batch.bytesWritten, 10);
+  assert.strictEqual(fs.readFileSync(corked, 'utf8'), '0123456789');
+
+  // an fd is used as it is and closed with the stream
+  const fd = fs.openSync(file, 'r');
+  const fromFd = fs.createReadStream(null, { fd, start: 1, end: 3 });
+  assert.strictEqual(fromFd.pending, false);
+  assert(Buffer.concat(await collect(fromFd)).equals(big.subarray(1, 4)));
+  await new Promise((resolve) => fromFd.on('close', resolve));
+  assert.throws(() => fs.fstatSync(fd), { code: 'EBADF' });
+
+  // a FileHandle too
+  const handle = await fs.promises.open(file);
+  const fromHandle = Buffer.concat(await collect(handle.createReadStream({ end: 9 })));
+  assert(fromHandle.equals(big.subarray(0, 10)));
+
+  // open errors destroy the stream
+  const missing = path.join(tmpdir.path, 'missing', 'file');
+  await assert.rejects(collect(fs.createReadStream(missing)), { code: 'ENOENT', syscall: 'open' });
+  const failed = fs.createWriteStream(missing);
+  await new Promise((resolve) => failed.on('error', common.mustCall((err) => {
+    assert.strictEqual(err.code, 'ENOENT');
+    resolve();
+  })));
+}
+
+run().then(common.mustCall());
@@ -0,0 +1,101 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import fs from 'fs';
+import path from 'path';
+import tmpdir from '../common/tmpdir';
+
+// WASI has no file notifications, fs.watch and fs.watchFile poll.
+
+tmpdir.refresh();
+const dir = path.join(tmpdir.path, 'polled');
+fs.mkdirSync(dir);
+fs.mkdirSync(path.join(dir, 'sub'));
+const file = path.join(dir, 'file.txt');
+fs.writeFileSync(file, 'one');
+
+const sleep = (ms) => new Promise((resolve) => setTimeout(resolve, ms));
+
+function collect(watcher) {
+  const events = [];
+  watcher.on('change', (eventType, filename) => events.push(`${eventType} ${filename}`));
+  return events;
+}
+
+async function run() {
+  // a directory reports the entries that come, go and change
+  const watcher = fs.watch(dir, { interval: 20 });
+  const events = collect(watcher);
+  fs.writeFileSync(path.join(dir, 'new.txt'), 'new');
+  await sleep(100);
+  assert.deepStrictEqual(events.splice(0), ['rename new.txt']);
+  fs.writeFileSync(file, 'one two');
+  await sleep(100);
+  assert.deepStrictEqual(events.splice(0), ['change file.txt']);
+  fs.unlinkSync(path.join(dir, 'new.txt'));
+  fs.writeFileSync(path.join(dir, 'sub', 'deep.txt'), 'deep');
+  await sleep(100);
+  assert.deepStrictEqual(events.splice(0), ['rename new.txt']);
+  const closed = new Promise((resolve) => watcher.once('close', resolve));
+  watcher.close();
+  await closed;
+  fs.writeFileSync(file, 'closed');
+  await sleep(60);
+  assert.deepStrictEqual(events, []);
+
+  // recursive names are relative to the watched directory
+  const recursive = fs.watch(dir, { interval: 20, recursive: true });
+  const deep = collect(recursive);
+  fs.writeFileSync(path.join(dir, 'sub', 'deep.txt'), 'deeper');
+  await sleep(100);
+  assert.deepStrictEqual(deep, [`change ${path.join('sub', 'deep.txt')}`]);
+  recursive.close();
+
+  // a file reports its own name
+  const single = fs.watch(file, { interval: 20 });
+  const own = collect(single);
+  fs.appendFileSync(file, '!');
+  await sleep(100);
+  assert.deepStrictEqual(own.splice(0), ['change file.txt']);
+  fs.unlinkSync(file);
+  await sleep(100);
+  assert.deepStrictEqual(own.splice(0), ['rename file.txt']);
+  single.close();
+
+  // watchFile hands over the stats, a deleted file has zeroed ones
+  fs.writeFileSync(file, 'stat');
+  let calls = 0;
+  const listener = common.mustCall((current, previous) => {
+    calls++;
+    if (calls === 1) {
+      assert.strictEqual(current.size, 12);
+      assert.strictEqual(previous.size, 4);
+    } else {
+      assert.strictEqual(current.ino, 0);
+      assert.strictEqual(current.size, 0);
+      assert.strictEqual(previous.size, 12);
+    }
+  }, 2);
+  const statWatcher = fs.watchFile(file, { interval: 20 }, listener);
+  assert.strictEqual(fs.watchFile(file, { interval: 20 }, listener), statWatcher);
+  fs.unwatchFile(file, listener);
+  await sleep(60);
+  fs.writeFileSync(file, 'stat changed');
+  await sleep(100);
+  assert.strictEqual(calls, 1);
+  fs.unlinkSync(file);
+  await sleep(100);
+  assert.strictEqual(calls, 2);
+  statWatcher.on('stop', common.mustCall());
+  fs.unwatchFile(file);
+
+  assert.throws(() => fs.watch(path.join(dir, 'missing')), {
+    code: 'ENOENT',
+    syscall: 'watch',
+  });
+  assert.throws(() => fs.watchFile(file, { interval: -1 }, common.mustNotCall()), {
+    code: 'ERR_OUT_OF_RANGE',
+  });
+}
+
+run().then(common.mustCall());
@@ -5,6 +5,8 @@ import common from '../common';
 import assert from 'assert';
 import fs from 'fs';
 
+const __filename = args[0];
+
 const watch = fs.watchFile(__filename, common.mustNotCall());
 let triggered;
 const listener = common.mustCall(() => {
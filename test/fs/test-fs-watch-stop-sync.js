@@ -12,6 +12,8 @@ import common from '../common';
 
 import fs from 'fs';
 
+const __filename = args[0];
+
 const listener = common.mustNotCall(
   'listener should have been removed before the event was emitted'
 );
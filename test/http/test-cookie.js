@@ -0,0 +1,108 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import * as http from 'http';
+import { CookieJar, globalCookieJar, parse, parseSetCookie, serialize } from 'cookie';
+
+// the helpers on their own
+assert.deepStrictEqual(parse('a=1; b="two"; a=3; c=x%20y; broken'), { a: '1', b: 'two', c: 'x y' });
+assert.strictEqual(
+  serialize('sid', 'a b', { path: '/', httpOnly: true, secure: true, sameSite: 'lax', maxAge: 60 }),
+  'sid=a%20b; Max-Age=60; Path=/; HttpOnly; Secure; SameSite=Lax',
+);
+assert.strictEqual(
+  serialize('old', '', { expires: new Date(0) }),
+  'old=; Expires=Thu, 01 Jan 1970 00:00:00 GMT',
+);
+assert.throws(() => serialize('bad name', 'x'), TypeError);
+assert.throws(() => serialize('a', 'x', { path: '/;x' }), TypeError);
+assert.throws(() => serialize('a', 'x', { sameSite: 'sometimes' }), TypeError);
+
+const parsed = parseSetCookie('id=7; Domain=.Example.com; Path=/p; Expires=Sun, 06 Nov 1994 08:49:37 GMT; HttpOnly');
+assert.strictEqual(parsed.name, 'id');
+assert.strictEqual(parsed.value, '7');
+assert.strictEqual(parsed.domain, 'example.com');
+assert.strictEqual(parsed.path, '/p');
+assert.strictEqual(parsed.expires.getTime(), Date.UTC(1994, 10, 6, 8, 49, 37));
+assert.strictEqual(parsed.httpOnly, true);
+assert.strictEqual(parsed.secure, false);
+assert.strictEqual(parseSetCookie('no pair'), null);
+
+// jar matching by domain, path, expiry and scheme
+const jar = new CookieJar();
+assert.ok(jar.setCookie('a=1; Path=/docs', 'http://www.example.com/'));
+assert.ok(jar.setCookie('b=2; Domain=example.com', 'http://www.example.com/docs/x'));
+assert.ok(jar.setCookie('s=3; Secure', 'https://www.example.com/'));
+assert.ok(!jar.setCookie('s=4; Secure', 'http://www.example.com/'));
+assert.ok(!jar.setCookie('x=1; Domain=example.org', 'http://www.example.com/'));
+assert.strictEqual(jar.getCookieString('http://www.example.com/docs/page'), 'a=1; b=2');
+assert.strictEqual(jar.getCookieString('https://api.example.com/docs'), 'b=2');
+assert.strictEqual(jar.getCookieString('https://www.example.com/'), 's=3');
+assert.strictEqual(jar.getCookieString('http://example.net/'), '');
+assert.ok(jar.setCookie('a=gone; Path=/docs; Max-Age=0', 'http://www.example.com/'));
+assert.deepStrictEqual(jar.getCookies().map((c) => c.name).sort(), ['b', 's']);
+assert.strictEqual(jar.size, 2);
+assert.throws(() => jar.getCookieString('/relative'), TypeError);
+jar.clear();
+assert.strictEqual(jar.size, 0);
+
+// server helpers and fetch keeping cookies between requests
+const server = http.createServer((req, res) => {
+  const cookies = req.cookies;
+  if (req.url === '/login') {
+    res.setCookie('sid', 'abc 123', { path: '/', httpOnly: true });
+    res.setCookie('theme', 'dark', { path: '/app' });
+    res.setCookie('short', 'x', { maxAge: 0 });
+    res.end('ok');
+  } else if (req.url === '/redirect') {
+    res.setCookie('hop', '1', { path: '/' });
+    res.writeHead(302, { Location: '/app/echo' });
+    res.end();
+  } else if (req.url === '/logout') {
+    res.clearCookie('sid', { path: '/' });
+    res.end();
+  } else {
+    res.setHeader('Content-Type', 'application/json');
+    res.end(JSON.stringify({ cookies, header: req.headers.cookie ?? null }));
+  }
+});
+server.listen(0);
+const base = `http://127.0.0.1:${server.address().port}`;
+
+async function run() {
+  let res = await fetch(`${base}/login`);
+  assert.deepStrictEqual(res.headers.getSetCookie(), [
+    'sid=abc%20123; Path=/; HttpOnly',
+    'theme=dark; Path=/app',
+    'short=x; Max-Age=0',
+  ]);
+  await res.text();
+
+  let echo = await (await fetch(`${base}/app/echo`)).json();
+  assert.deepStrictEqual(echo.cookies, { theme: 'dark', sid: 'abc 123' });
+  echo = await (await fetch(`${base}/echo`)).json();
+  assert.deepStrictEqual(echo.cookies, { sid: 'abc 123' });
+
+  // redirects store and send cookies on every hop
+  echo = await (await fetch(`${base}/redirect`)).json();
+  assert.deepStrictEqual(echo.cookies, { theme: 'dark', sid: 'abc 123', hop: '1' });
+
+  // an explicit header wins, credentials: 'omit' sends none
+  echo = await (await fetch(`${base}/echo`, { headers: { Cookie: 'mine=1' } })).json();
+  assert.strictEqual(echo.header, 'mine=1');
+  echo = await (await fetch(`${base}/echo`, { credentials: 'omit' })).json();
+  assert.strictEqual(echo.header, null);
+
+  // a private jar stays apart from the global one
+  const own = new CookieJar();
+  await (await fetch(`${base}/redirect`, { cookieJar: own })).text();
+  assert.deepStrictEqual(own.getCookies().map((c) => c.name), ['hop']);
+  echo = await (await fetch(`${base}/echo`, { cookieJar: false })).json();
+  assert.strictEqual(echo.header, null);
+
+  await (await fetch(`${base}/logout`)).text();
+  assert.strictEqual(globalCookieJar.getCookieString(`${base}/`), 'hop=1');
+  server.close();
+}
+
+run().then(common.mustCall());
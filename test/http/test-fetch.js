@@ -0,0 +1,171 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import * as net from 'wasi_net';
+import { TextDecoder, TextEncoder } from 'util';
+import { URLSearchParams } from 'whatwg_url';
+import * as http from 'http';
+
+// A raw server so the test controls the exact framing of every response.
+const routes = {
+  '/length': 'HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nhello',
+  '/chunked': 'HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n' +
+    '6\r\n{"a":1\r\n1\r\n}\r\n0\r\n\r\n',
+  '/trailers': 'HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nTrailer: X-Sum\r\n\r\n' +
+    '3;part=1\r\nhel\r\n2;part="2"\r\nlo\r\n0\r\nX-Sum: 42\r\n\r\n',
+  '/eof': 'HTTP/1.1 200 Everything Is Fine\r\n\r\nuntil close',
+  '/empty': 'HTTP/1.1 204 No Content\r\n\r\n',
+  '/redirect': 'HTTP/1.1 302 Found\r\nLocation: /length\r\nContent-Length: 0\r\n\r\n',
+  '/see-other': 'HTTP/1.1 303 See Other\r\nLocation: http://127.0.0.1:PORT/echo\r\nContent-Length: 0\r\n\r\n',
+  '/loop': 'HTTP/1.1 307 Temporary Redirect\r\nLocation: /loop\r\nContent-Length: 0\r\n\r\n',
+};
+
+const server = new net.WasiTcpServer(0);
+const port = Number(server.local().split(':').pop());
+
+async function handle(conn) {
+  let data = '';
+  while (!data.includes('\r\n\r\n')) {
+    const chunk = await conn.read();
+    if (chunk === undefined) {
+      return;
+    }
+    data += new TextDecoder().decode(chunk);
+  }
+  const [line] = data.split('\r\n');
+  const [method, path] = line.split(' ');
+  if (path === '/slow') {
+    // never answers, the client aborts
+    return;
+  }
+  if (path === '/echo') {
+    const length = Number(/content-length: (\d+)/i.exec(data)?.[1] ?? 0);
+    const chunked = /transfer-encoding: chunked/i.test(data);
+    const body = () => data.slice(data.indexOf('\r\n\r\n') + 4);
+    while (chunked ? !body().endsWith('0\r\n\r\n') : new TextEncoder().encode(body()).byteLength < length) {
+      data += new TextDecoder().decode(await conn.read());
+    }
+    const reply = JSON.stringify({ method, body: body() });
+    conn.write(`HTTP/1.1 200 OK\r\nContent-Length: ${reply.length}\r\n\r\n${reply}`);
+  } else {
+    conn.write(routes[path].replace('PORT', `${port}`));
+  }
+  await conn.shutdown();
+}
+
+async function serve() {
+  try {
+    while (true) {
+      handle(await server.accept());
+    }
+  } catch (e) {
+    // closed
+  }
+}
+serve();
+
+const base = `http://127.0.0.1:${port}`;
+
+async function run() {
+  let r = await fetch(`${base}/length`);
+  assert.strictEqual(r.status, 200);
+  assert.strictEqual(r.ok, true);
+  assert.strictEqual(r.headers.get('content-type'), 'text/plain');
+  assert.strictEqual(r.redirected, false);
+  assert.strictEqual(await r.text(), 'hello');
+  assert.strictEqual(r.bodyUsed, true);
+  await assert.rejects(r.text(), TypeError);
+
+  r = await fetch(`${base}/chunked`);
+  assert.ok(r.body instanceof ReadableStream);
+  assert.deepStrictEqual(await r.json(), { a: 1 });
+
+  r = await fetch(`${base}/trailers`);
+  assert.strictEqual(await r.text(), 'hello');
+
+  const res = await new Promise((resolve) => http.get(`${base}/trailers`, resolve));
+  let text = '';
+  res.setEncoding('utf8');
+  res.on('data', (chunk) => text += chunk);
+  await new Promise((resolve) => res.on('end', resolve));
+  assert.strictEqual(text, 'hello');
+  assert.deepStrictEqual(res.trailers, { 'x-sum': '42' });
+
+  r = await fetch(`${base}/eof`);
+  assert.strictEqual(r.statusText, 'Everything Is Fine');
+  assert.strictEqual(new TextDecoder().decode(await r.arrayBuffer()), 'until close');
+
+  r = await fetch(`${base}/empty`);
+  assert.strictEqual(r.status, 204);
+  assert.strictEqual(r.body, null);
+
+  r = await fetch(`${base}/redirect`);
+  assert.strictEqual(r.redirected, true);
+  assert.strictEqual(r.url, `${base}/length`);
+  assert.strictEqual(await r.text(), 'hello');
+
+  r = await fetch(`${base}/redirect`, { redirect: 'manual' });
+  assert.strictEqual(r.status, 302);
+  assert.strictEqual(r.headers.get('location'), '/length');
+  await assert.rejects(fetch(`${base}/redirect`, { redirect: 'error' }), TypeError);
+  await assert.rejects(fetch(`${base}/loop`), /too many redirects/);
+
+  r = await fetch(`${base}/see-other`, { method: 'POST', body: 'dropped' });
+  assert.deepStrictEqual(await r.json(), { method: 'GET', body: '' });
+
+  r = await fetch(new Request(`${base}/echo`, { method: 'put', body: new URLSearchParams({ x: 'y' }) }));
+  assert.deepStrictEqual(await r.json(), { method: 'PUT', body: 'x=y' });
+
+  r = await fetch(`${base}/echo`, {
+    method: 'POST',
+    body: new ReadableStream({
+      start(controller) {
+        controller.enqueue(new TextEncoder().encode('streamed '));
+        controller.enqueue('body');
+        controller.close();
+      },
+    }),
+  });
+  assert.deepStrictEqual(await r.json(), { method: 'POST', body: '9\r\nstreamed \r\n4\r\nbody\r\n0\r\n\r\n' });
+
+  const controller = new AbortController();
+  const pending = fetch(`${base}/slow`, { signal: controller.signal });
+  setTimeout(() => controller.abort(), 10);
+  await assert.rejects(pending, { name: 'AbortError' });
+
+  await assert.rejects(fetch(`${base}/slow`, { timeout: 10 }), { name: 'TimeoutError' });
+
+  server.close();
+}
+
+run().then(common.mustCall());
+
+// Headers, Request and Response on their own
+const headers = new Headers([['B', '1'], ['a', '2'], ['set-cookie', 'x=1'], ['Set-Cookie', 'y=2']]);
+headers.append('b', '3');
+assert.deepStrictEqual([...headers], [['a', '2'], ['b', '1, 3'], ['set-cookie', 'x=1'], ['set-cookie', 'y=2']]);
+assert.deepStrictEqual(headers.getSetCookie(), ['x=1', 'y=2']);
+assert.throws(() => headers.set('bad name', 'x'), TypeError);
+
+assert.throws(() => new Request(`${base}/`, { method: 'GET', body: 'x' }), TypeError);
+assert.throws(() => new Request(`${base}/`, { method: 'CONNECT' }), TypeError);
+assert.strictEqual(new Request(`${base}/`, { method: 'patch' }).method, 'patch');
+assert.throws(() => new Response(null, { status: 99 }), RangeError);
+assert.throws(() => new Response('x', { status: 204 }), TypeError);
+assert.strictEqual(Response.error().type, 'error');
+assert.strictEqual(Response.redirect(`${base}/x`, 301).headers.get('location'), `${base}/x`);
+
+const json = Response.json({ ok: true });
+assert.strictEqual(json.headers.get('content-type'), 'application/json');
+const copy = json.clone();
+Promise.all([json.json(), copy.text()]).then(common.mustCall(([a, b]) => {
+  assert.deepStrictEqual(a, { ok: true });
+  assert.strictEqual(b, '{"ok":true}');
+}));
+
+const form = new FormData();
+form.append('name', 'value');
+form.append('file', new Blob(['data'], { type: 'text/plain' }), 'a.txt');
+new Response(form).text().then(common.mustCall((text) => {
+  assert.match(text, /name="file"; filename="a\.txt"\r\nContent-Type: text\/plain\r\n\r\ndata\r\n/);
+}));
@@ -0,0 +1,101 @@
+'use strict';
+import common from '../common';
+import tmpdir from '../common/tmpdir';
+import assert from 'assert';
+import * as http from 'http';
+import { existsSync, readFileSync, rmSync } from 'fs';
+
+tmpdir.refresh();
+
+const big = 'x'.repeat(5000);
+
+const server = http.createServer(async (req, res) => {
+  let form;
+  try {
+    form = await req.formData({ uploadDir: tmpdir.path, spillThreshold: 1024 });
+  } catch (e) {
+    res.writeHead(400);
+    res.end(e.message);
+    return;
+  }
+  const entries = [];
+  for (const [name, value] of form) {
+    if (typeof value === 'string') {
+      entries.push([name, value]);
+    } else {
+      entries.push([name, value.name, value.type, value.size, await value.text(), value.filepath ?? null]);
+    }
+  }
+  res.setHeader('Content-Type', 'application/json');
+  res.end(JSON.stringify(entries));
+});
+server.listen(0);
+const port = server.address().port;
+const url = `http://127.0.0.1:${port}/`;
+
+async function run() {
+  // multipart from a FormData body, the big file lands on disk
+  const form = new FormData();
+  form.append('title', 'héllo wörld');
+  form.append('small', new Blob(['tiny'], { type: 'text/plain' }), 'small.txt');
+  form.append('big', new Blob([big]), 'big.bin');
+  form.append('title', 'again');
+  let entries = await (await fetch(url, { method: 'POST', body: form })).json();
+  assert.deepStrictEqual(entries[0], ['title', 'héllo wörld']);
+  assert.deepStrictEqual(entries[1], ['small', 'small.txt', 'text/plain', 4, 'tiny', null]);
+  assert.deepStrictEqual(entries[2].slice(0, 5), ['big', 'big.bin', 'application/octet-stream', 5000, big]);
+  assert.deepStrictEqual(entries[3], ['title', 'again']);
+  const spilled = entries[2][5];
+  assert.ok(spilled.startsWith(tmpdir.path));
+  assert.strictEqual(readFileSync(spilled, 'utf8'), big);
+  rmSync(spilled);
+
+  // urlencoded
+  const params = new URLSearchParams({ a: '1 + 2', b: 'ü&=' });
+  params.append('a', 'again');
+  entries = await (await fetch(url, { method: 'POST', body: params })).json();
+  assert.deepStrictEqual(entries, [['a', '1 + 2'], ['b', 'ü&='], ['a', 'again']]);
+
+  // broken bodies are refused
+  let res = await fetch(url, {
+    method: 'POST',
+    headers: { 'Content-Type': 'multipart/form-data; boundary=abc' },
+    body: '--abc\r\nContent-Disposition: form-data; name="a"\r\n\r\nno closing boundary',
+  });
+  assert.strictEqual(res.status, 400);
+  assert.match(await res.text(), /unexpected end of form data/);
+  res = await fetch(url, { method: 'POST', headers: { 'Content-Type': 'text/plain' }, body: 'a=1' });
+  assert.strictEqual(res.status, 400);
+
+  // Response.formData() parses multipart too
+  const body = '--b\r\nContent-Disposition: form-data; name="f"; filename="a.txt"\r\n\r\n'
+    + 'file body\r\n--b\r\nContent-Disposition: form-data; name="s"\r\n\r\nvalue\r\n--b--\r\n';
+  const parsed = await new Response(body, { headers: { 'Content-Type': 'multipart/form-data; boundary=b' } }).formData();
+  assert.strictEqual(parsed.get('s'), 'value');
+  const file = parsed.get('f');
+  assert.ok(file instanceof File);
+  assert.strictEqual(file.name, 'a.txt');
+  assert.strictEqual(file.type, 'text/plain');
+  assert.strictEqual(await file.text(), 'file body');
+
+  // spilled files read lazily, in slices and as streams
+  const spilledForm = await new Response(body.replace('file body', big), {
+    headers: { 'Content-Type': 'multipart/form-data; boundary=b' },
+  }).formData({ uploadDir: tmpdir.path, spillThreshold: 100 });
+  const spilledFile = spilledForm.get('f');
+  assert.ok(existsSync(spilledFile.filepath));
+  assert.strictEqual(spilledFile.size, big.length);
+  assert.strictEqual(await spilledFile.slice(-3).text(), 'xxx');
+  assert.strictEqual(spilledFile.slice(10, 20).size, 10);
+  let streamed = 0;
+  for await (const chunk of spilledFile.stream()) {
+    streamed += chunk.byteLength;
+  }
+  assert.strictEqual(streamed, big.length);
+  assert.strictEqual(await new Blob([spilledFile, '!']).text(), `${big}!`);
+  rmSync(spilledFile.filepath);
+
+  server.close();
+}
+
+run().then(common.mustCall());
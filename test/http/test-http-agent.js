@@ -0,0 +1,140 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import * as net from 'wasi_net';
+import http from 'http';
+import { TextDecoder } from 'util';
+
+// A raw keep-alive server that counts connections and answers every request
+// found on a connection in order.
+let connections = 0;
+let active = 0;
+let maxActive = 0;
+const requestHeaders = [];
+
+const server = new net.WasiTcpServer(0);
+const port = Number(server.local().split(':').pop());
+
+function respond(body, extra = '') {
+  return `HTTP/1.1 200 OK\r\nContent-Length: ${body.length}\r\n${extra}\r\n${body}`;
+}
+
+async function handle(conn) {
+  const id = ++connections;
+  active++;
+  maxActive = Math.max(active, maxActive);
+  let data = '';
+  let pending = null;
+  try {
+    while (true) {
+      let end;
+      while ((end = data.indexOf('\r\n\r\n')) < 0) {
+        const chunk = await conn.read();
+        if (chunk === undefined) {
+          return;
+        }
+        data += new TextDecoder().decode(chunk);
+      }
+      const head = data.slice(0, end);
+      data = data.slice(end + 4);
+      requestHeaders.push(head.toLowerCase());
+      const path = head.split(' ')[1];
+      if (pending !== null) {
+        // already sent ahead of this request
+        pending = null;
+        continue;
+      }
+      if (path === '/slow') {
+        await new Promise((resolve) => setTimeout(resolve, 20));
+        conn.write(respond(`${id}`));
+      } else if (path === '/double') {
+        // the second response arrives before its request is sent
+        pending = respond(`${id}-second`);
+        conn.write(respond(`${id}-first`) + pending);
+      } else if (path === '/close') {
+        conn.write(respond(`${id}`, 'Connection: close\r\n'));
+        await conn.shutdown();
+        return;
+      } else if (path === '/drop') {
+        // closes without telling the client
+        conn.write(respond(`${id}`));
+        await conn.shutdown();
+        return;
+      } else {
+        conn.write(respond(`${id}`));
+      }
+    }
+  } finally {
+    active--;
+  }
+}
+
+(async () => {
+  try {
+    while (true) {
+      handle(await server.accept());
+    }
+  } catch (e) {
+    // closed
+  }
+})();
+
+const base = `http://127.0.0.1:${port}`;
+
+async function get(path, init) {
+  const r = await fetch(`${base}${path}`, init);
+  return await r.text();
+}
+
+async function run() {
+  const agent = new http.Agent({ keepAlive: true, timeout: 1000 });
+
+  // sequential requests share one connection
+  const first = await get('/', { agent });
+  assert.strictEqual(await get('/', { agent }), first);
+  assert.strictEqual(await get('/', { agent }), first);
+  assert.strictEqual(connections, 1);
+  assert.strictEqual(agent.freeSockets[`http:` + `//127.0.0.1:${port}`].length, 1);
+  assert.match(requestHeaders[0], /connection: keep-alive/);
+
+  // bytes past one response are kept for the next one on the connection
+  assert.strictEqual(await get('/double', { agent }), `${first}-first`);
+  assert.strictEqual(await get('/', { agent }), `${first}-second`);
+
+  // Connection: close is honoured
+  assert.strictEqual(await get('/close', { agent }), first);
+  assert.notStrictEqual(await get('/', { agent }), first);
+  assert.strictEqual(connections, 2);
+
+  // a connection the server dropped while idle is retried transparently
+  const dropped = await get('/drop', { agent });
+  assert.strictEqual(await get('/', { agent }), `${Number(dropped) + 1}`);
+
+  // idle connections expire
+  const idle = new http.Agent({ keepAlive: true, timeout: 20 });
+  const a = await get('/', { agent: idle });
+  await new Promise((resolve) => setTimeout(resolve, 50));
+  assert.notStrictEqual(await get('/', { agent: idle }), a);
+
+  // maxSockets queues the requests above the limit
+  const limited = new http.Agent({ keepAlive: true, maxSockets: 1 });
+  const before = connections;
+  active = 0;
+  maxActive = 0;
+  const bodies = await Promise.all([1, 2, 3].map(() => get('/slow', { agent: limited })));
+  assert.deepStrictEqual(new Set(bodies).size, 1);
+  assert.strictEqual(connections, before + 1);
+
+  // without keep-alive every request closes its connection
+  const count = requestHeaders.length;
+  await get('/', { agent: false });
+  assert.match(requestHeaders[count], /connection: close/);
+
+  for (const ag of [agent, idle, limited]) {
+    ag.destroy();
+  }
+  http.globalAgent.destroy();
+  server.close();
+}
+
+run().then(common.mustCall());
@@ -0,0 +1,141 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import * as net from 'wasi_net';
+import * as httpx from 'wasi_http';
+import * as http from 'http';
+import { TextDecoder } from 'util';
+
+const server = http.createServer();
+server.headersTimeout = 200;
+server.keepAliveTimeout = 100;
+
+server.on('request', (req, res) => {
+  if (req.url === '/stream') {
+    // answers each piece of the body before the next one is sent
+    res.writeHead(200);
+    req.on('data', (chunk) => res.write(`got ${chunk.length};`));
+    req.on('end', () => res.end('done'));
+  } else if (req.url === '/trailers') {
+    let body = '';
+    req.setEncoding('utf8');
+    req.on('data', (chunk) => body += chunk);
+    req.on('end', () => {
+      res.end(`${body}:${req.trailers['x-sum']}`);
+    });
+  } else if (req.url === '/ignore-body') {
+    res.end('ignored');
+  } else {
+    let body = '';
+    req.on('data', (chunk) => body += chunk);
+    req.on('end', () => res.end(`${req.method} ${req.url} ${body}`));
+  }
+});
+
+const clientErrors = [];
+server.on('clientError', (err, socket) => {
+  clientErrors.push(err.code);
+  socket.end('HTTP/1.1 400 Bad Request\r\nConnection: close\r\n\r\n');
+});
+
+server.listen(0);
+const port = server.address().port;
+
+class Client {
+  data = '';
+
+  static async connect() {
+    const client = new Client();
+    client.conn = await net.WasiTcpConn.connect(`127.0.0.1:${port}`);
+    return client;
+  }
+
+  write(s) {
+    this.conn.write(s);
+  }
+
+  // reads until `text` shows up, returns everything up to and including it
+  async until(text) {
+    while (!this.data.includes(text)) {
+      const chunk = await this.conn.read();
+      if (chunk === undefined) {
+        throw new Error(`connection closed while waiting for ${JSON.stringify(text)}: ${this.data}`);
+      }
+      this.data += new TextDecoder().decode(chunk);
+    }
+    const i = this.data.indexOf(text) + text.length;
+    const head = this.data.slice(0, i);
+    this.data = this.data.slice(i);
+    return head;
+  }
+
+  async closed() {
+    while (true) {
+      const chunk = await this.conn.read();
+      if (chunk === undefined) {
+        return this.data;
+      }
+      this.data += new TextDecoder().decode(chunk);
+    }
+  }
+}
+
+async function run() {
+  // the handler sees the first part of the body before the rest is sent
+  let c = await Client.connect();
+  c.write('POST /stream HTTP/1.1\r\nHost: a\r\nContent-Length: 8\r\n\r\nfour');
+  await c.until('got 4;');
+  c.write('more');
+  await c.until('got 4;');
+  await c.until('done');
+
+  // chunked request body with extensions and trailers, then keep-alive
+  c.write('POST /trailers HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n' +
+    '3;x=1\r\nabc\r\n2\r\nde\r\n0\r\nX-Sum: 5\r\n\r\n');
+  await c.until('abcde:5');
+
+  // an unread body is skipped before the next request on the connection
+  c.write('POST /ignore-body HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\r\nxxxxx' +
+    'GET /after HTTP/1.1\r\nHost: a\r\n\r\n');
+  await c.until('ignored');
+  await c.until('GET /after ');
+
+  // idle keep-alive connections are closed after keepAliveTimeout
+  await c.closed();
+
+  // Expect: 100-continue
+  c = await Client.connect();
+  c.write('PUT /continue HTTP/1.1\r\nHost: a\r\nExpect: 100-continue\r\nContent-Length: 4\r\nConnection: close\r\n\r\n');
+  assert.ok((await c.until('\r\n\r\n')).startsWith('HTTP/1.1 100 Continue'));
+  c.write('body');
+  assert.ok((await c.closed()).endsWith('PUT /continue body'));
+
+  // malformed requests go to the clientError listener
+  c = await Client.connect();
+  c.write('GET / HTTP/1.1\r\nBad Header: x\r\n\r\n');
+  assert.ok((await c.closed()).startsWith('HTTP/1.1 400 Bad Request'));
+
+  // a head that does not complete within headersTimeout
+  c = await Client.connect();
+  c.write('GET / HTTP/1.1\r\nHost: a\r\n');
+  assert.ok((await c.closed()).startsWith('HTTP/1.1 400 Bad Request'));
+
+  assert.deepStrictEqual(clientErrors, ['HPE_INVALID_HEADER_TOKEN', 'ERR_HTTP_REQUEST_TIMEOUT']);
+  server.close();
+}
+
+// the chunk encoder refuses trailers that would inject fields of their own
+{
+  const sent = [];
+  const chunk = new httpx.WasiResponse().chunk({
+    write: (data) => sent.push(data),
+    end: (data) => sent.push(data),
+  });
+  assert.throws(() => chunk.end(undefined, { 'x-sum': '5\r\nInjected: 1' }), TypeError);
+  assert.throws(() => chunk.end(undefined, { 'x sum': '5' }), TypeError);
+  assert.strictEqual(sent.length, 1);
+  chunk.end(undefined, { 'x-sum': '5' });
+  assert.ok(new TextDecoder().decode(sent[sent.length - 1]).endsWith('0\r\nx-sum: 5\r\n\r\n'));
+}
+
+run().then(common.mustCall());
@@ -0,0 +1,152 @@
+'use strict';
+import common from '../common';
+import tmpdir from '../common/tmpdir';
+import assert from 'assert';
+import * as http from 'http';
+import { mkdirSync, writeFileSync } from 'fs';
+import { Router, json, serveStatic } from 'router';
+
+tmpdir.refresh();
+const content = 'abcdefghijklmnopqrstuvwxyz'.repeat(4000);
+mkdirSync(`${tmpdir.path}/docs`);
+writeFileSync(`${tmpdir.path}/big.txt`, content);
+writeFileSync(`${tmpdir.path}/docs/index.html`, '<p>index</p>');
+writeFileSync(`${tmpdir.path}/.secret`, 'hidden');
+
+const api = new Router();
+api.use((req, res, next) => {
+  res.setHeader('X-Api', req.baseUrl);
+  next();
+});
+api.get('/users/:id', (req, res) => res.json({ id: req.params.id, path: req.path, query: req.query }));
+api.post('/users', json({ limit: 64 }), (req, res) => res.status(201).json(req.body));
+api.delete('/users/:id', (req, res) => res.status(204).end());
+api.get('/files/*path', (req, res) => res.json(req.params));
+api.get('/fail', async () => {
+  const err = new Error('teapot');
+  err.status = 418;
+  throw err;
+});
+
+const router = new Router();
+const seen = [];
+router.use((req, res, next) => {
+  seen.push(`${req.method} ${req.url}`);
+  next();
+});
+router.use('/api', api);
+router.get('/opt/:a/:b?', (req, res) => res.json(req.params));
+router.get('/skip', (req, res, next) => next('route'), (req, res) => res.end('skipped'));
+router.get('/old', (req, res) => res.redirect('/new'));
+router.use('/static', serveStatic(tmpdir.path, { maxAge: 60 }));
+router.use((err, req, res, next) => {
+  if (err.message === 'teapot') {
+    res.status(err.status).end(`handled ${err.message}`);
+  } else {
+    next(err);
+  }
+});
+
+const server = http.createServer(router.handler());
+server.listen(0);
+const base = `http://127.0.0.1:${server.address().port}`;
+
+async function run() {
+  // parameters, query strings and mounted routers
+  let res = await fetch(`${base}/api/users/a%20b?x=1&y=2&x=3`);
+  assert.strictEqual(res.status, 200);
+  assert.strictEqual(res.headers.get('x-api'), '/api');
+  assert.strictEqual(res.headers.get('content-type'), 'application/json; charset=utf-8');
+  assert.deepStrictEqual(await res.json(), { id: 'a b', path: '/users/a%20b', query: { x: ['1', '3'], y: '2' } });
+  assert.deepStrictEqual(await (await fetch(`${base}/api/files/a/b/c.txt`)).json(), { path: 'a/b/c.txt' });
+  assert.deepStrictEqual(await (await fetch(`${base}/opt/1`)).json(), { a: '1' });
+  assert.deepStrictEqual(await (await fetch(`${base}/opt/1/2`)).json(), { a: '1', b: '2' });
+  assert.strictEqual(await (await fetch(`${base}/skip`)).text(), 'skipped');
+
+  // json bodies
+  res = await fetch(`${base}/api/users`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ name: 'x' }),
+  });
+  assert.strictEqual(res.status, 201);
+  assert.deepStrictEqual(await res.json(), { name: 'x' });
+  res = await fetch(`${base}/api/users`, { method: 'POST', headers: { 'Content-Type': 'application/json' }, body: '{' });
+  assert.strictEqual(res.status, 400);
+  res = await fetch(`${base}/api/users`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ name: 'x'.repeat(100) }),
+  });
+  assert.strictEqual(res.status, 413);
+
+  // method matching
+  res = await fetch(`${base}/api/users/1`, { method: 'PUT' });
+  assert.strictEqual(res.status, 405);
+  assert.strictEqual(res.headers.get('allow'), 'GET, HEAD, DELETE');
+  res = await fetch(`${base}/api/users/1`, { method: 'OPTIONS' });
+  assert.strictEqual(res.status, 204);
+  res = await fetch(`${base}/api/users/1`, { method: 'HEAD' });
+  assert.strictEqual(res.status, 200);
+  assert.strictEqual(res.headers.get('content-length'), `${JSON.stringify({ id: '1', path: '/users/1', query: {} }).length}`);
+  assert.strictEqual(await res.text(), '');
+  assert.strictEqual((await fetch(`${base}/api/users/1`, { method: 'DELETE' })).status, 204);
+  res = await fetch(`${base}/nowhere`);
+  assert.strictEqual(res.status, 404);
+  assert.strictEqual(await res.text(), 'Not Found');
+
+  // errors reach the error handlers
+  res = await fetch(`${base}/api/fail`);
+  assert.strictEqual(res.status, 418);
+  assert.strictEqual(await res.text(), 'handled teapot');
+  assert.strictEqual((await fetch(`${base}/api/users/%E0%A4%A`)).status, 400);
+
+  res = await fetch(`${base}/old`, { redirect: 'manual' });
+  assert.strictEqual(res.status, 302);
+  assert.strictEqual(res.headers.get('location'), '/new');
+
+  // static files
+  res = await fetch(`${base}/static/big.txt`);
+  assert.strictEqual(res.status, 200);
+  assert.strictEqual(res.headers.get('content-type'), 'text/plain; charset=utf-8');
+  assert.strictEqual(res.headers.get('cache-control'), 'public, max-age=60');
+  assert.strictEqual(res.headers.get('content-length'), `${content.length}`);
+  const etag = res.headers.get('etag');
+  const lastModified = res.headers.get('last-modified');
+  assert.ok(etag.startsWith('W/"'));
+  assert.strictEqual(await res.text(), content);
+
+  res = await fetch(`${base}/static/big.txt`, { headers: { 'If-None-Match': etag } });
+  assert.strictEqual(res.status, 304);
+  res = await fetch(`${base}/static/big.txt`, { headers: { 'If-Modified-Since': lastModified } });
+  assert.strictEqual(res.status, 304);
+
+  res = await fetch(`${base}/static/big.txt`, { headers: { Range: 'bytes=2-5' } });
+  assert.strictEqual(res.status, 206);
+  assert.strictEqual(res.headers.get('content-range'), `bytes 2-5/${content.length}`);
+  assert.strictEqual(await res.text(), 'cdef');
+  res = await fetch(`${base}/static/big.txt`, { headers: { Range: 'bytes=-3' } });
+  assert.strictEqual(await res.text(), 'xyz');
+  res = await fetch(`${base}/static/big.txt`, { headers: { Range: 'bytes=2-5', 'If-Range': '"stale"' } });
+  assert.strictEqual(res.status, 200);
+  await res.text();
+  res = await fetch(`${base}/static/big.txt`, { headers: { Range: `bytes=${content.length}-` } });
+  assert.strictEqual(res.status, 416);
+  assert.strictEqual(res.headers.get('content-range'), `bytes */${content.length}`);
+
+  res = await fetch(`${base}/static/docs`, { redirect: 'manual' });
+  assert.strictEqual(res.status, 301);
+  assert.strictEqual(res.headers.get('location'), '/static/docs/');
+  res = await fetch(`${base}/static/docs/`);
+  assert.strictEqual(res.headers.get('content-type'), 'text/html; charset=utf-8');
+  assert.strictEqual(await res.text(), '<p>index</p>');
+
+  assert.strictEqual((await fetch(`${base}/static/.secret`)).status, 404);
+  assert.strictEqual((await fetch(`${base}/static/missing.txt`)).status, 404);
+  assert.strictEqual((await fetch(`${base}/static/..%2f..%2fetc%2fpasswd`)).status, 404);
+
+  assert.strictEqual(seen[0], 'GET /api/users/a%20b?x=1&y=2&x=3');
+  server.close();
+}
+
+run().then(common.mustCall());
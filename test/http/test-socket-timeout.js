@@ -0,0 +1,83 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import * as net from 'wasi_net';
+import * as http from 'http';
+
+const sleep = (ms) => new Promise((resolve) => setTimeout(resolve, ms));
+
+const server = new net.WasiTcpServer(0);
+const port = Number(server.local().split(':').pop());
+
+async function pair() {
+  const accepted = server.accept();
+  const client = await net.WasiTcpConn.connect(`127.0.0.1:${port}`);
+  return [client, await accepted];
+}
+
+async function run() {
+  const [client, peer] = await pair();
+
+  // the read timeout holds for every read without its own
+  assert.strictEqual(peer.readTimeout, 0);
+  peer.readTimeout = 50;
+  assert.strictEqual(peer.readTimeout, 50);
+  await assert.rejects(peer.read(), /timed out/i);
+  await assert.rejects(peer.read(), /timed out/i);
+  client.write('ping');
+  assert.strictEqual(new TextDecoder().decode(await peer.read()), 'ping');
+  await assert.rejects(peer.read(20), /timed out/i);
+  peer.readTimeout = 0;
+
+  // the idle timeout fires once per quiet period and restarts on activity
+  let fired = 0;
+  client.on('timeout', () => fired++);
+  client.timeout = 100;
+  assert.strictEqual(client.timeout, 100);
+  await sleep(60);
+  client.write('keep alive');
+  await sleep(60);
+  assert.strictEqual(fired, 0);
+  await sleep(150);
+  assert.strictEqual(fired, 1);
+  await sleep(150);
+  assert.strictEqual(fired, 1);
+  client.write('again');
+  await sleep(200);
+  assert.strictEqual(fired, 2);
+  client.timeout = 0;
+  client.write('off');
+  await sleep(150);
+  assert.strictEqual(fired, 2);
+
+  // data nobody reads stops making progress
+  client.writeTimeout = 100;
+  const err = await new Promise((resolve) => client.write(new ArrayBuffer(64 * 1024 * 1024), resolve));
+  assert.match(`${err?.message}`, /timed out/i);
+  client.close();
+  peer.close();
+
+  // closing stops the timer
+  const [quiet, other] = await pair();
+  quiet.on('timeout', common.mustNotCall());
+  quiet.timeout = 20;
+  quiet.close();
+  await sleep(60);
+  other.close();
+
+  // the server's socket timeout goes through the connection
+  const httpServer = http.createServer(common.mustNotCall());
+  httpServer.setTimeout(50);
+  httpServer.on('timeout', common.mustCall((socket) => {
+    assert.strictEqual(socket.timeout, 50);
+    socket.destroy();
+  }));
+  httpServer.listen(0);
+  const idle = await net.WasiTcpConn.connect(`127.0.0.1:${httpServer.address().port}`);
+  assert.strictEqual(await idle.read(2000), undefined);
+  idle.close();
+  httpServer.close();
+  server.close();
+}
+
+run().then(common.mustCall());
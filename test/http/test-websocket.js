@@ -0,0 +1,162 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import * as net from 'wasi_net';
+import * as http from 'http';
+import { Buffer } from 'buffer';
+import { WebSocketServer } from 'ws';
+
+const server = http.createServer((req, res) => res.end('plain'));
+const wss = new WebSocketServer({ server, perMessageDeflate: true, handleProtocols: (protocols) => protocols.has('chat') ? 'chat' : false });
+const serverCloses = [];
+
+wss.on('connection', (ws, req) => {
+  ws.on('message', (data, isBinary) => {
+    const text = data.toString();
+    if (text === 'close me') {
+      ws.close(4001, 'as asked');
+    } else {
+      ws.send(isBinary ? data : `echo ${text} ${req.url}`);
+    }
+  });
+  ws.on('close', (code, reason) => serverCloses.push([code, reason.toString()]));
+});
+
+server.listen(0);
+const port = server.address().port;
+
+function once(ws, type) {
+  return new Promise((resolve) => ws.addEventListener(type, resolve, { once: true }));
+}
+
+// a masked client frame, built by hand
+function frame(opcode, payload, fin = true) {
+  payload = Buffer.from(payload);
+  const mask = [1, 2, 3, 4];
+  const head = Buffer.from([(fin ? 0x80 : 0) | opcode, 0x80 | payload.length, ...mask]);
+  return Buffer.concat([head, payload.map((b, i) => b ^ mask[i % 4])]);
+}
+
+function arrayBuffer(buf) {
+  return buf.buffer.slice(buf.byteOffset, buf.byteOffset + buf.length);
+}
+
+async function rawUpgrade(headers) {
+  const conn = await net.WasiTcpConn.connect(`127.0.0.1:${port}`);
+  conn.write(`GET /raw HTTP/1.1\r\nHost: a\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n${headers}\r\n`);
+  let data = Buffer.alloc(0);
+  while (!data.includes('\r\n\r\n')) {
+    const chunk = await conn.read();
+    if (chunk === undefined) {
+      break;
+    }
+    data = Buffer.concat([data, Buffer.from(chunk)]);
+  }
+  const i = data.indexOf('\r\n\r\n') + 4;
+  return { conn, head: data.subarray(0, i).toString(), rest: data.subarray(i) };
+}
+
+async function readAll(conn, rest) {
+  const chunks = [rest];
+  let chunk;
+  while ((chunk = await conn.read()) !== undefined) {
+    chunks.push(Buffer.from(chunk));
+  }
+  return Buffer.concat(chunks);
+}
+
+async function run() {
+  // the browser API, with permessage-deflate and a subprotocol
+  const ws = new WebSocket(`ws://127.0.0.1:${port}/path?q=1`, ['chat', 'other']);
+  assert.strictEqual(ws.readyState, WebSocket.CONNECTING);
+  await once(ws, 'open');
+  assert.strictEqual(ws.readyState, WebSocket.OPEN);
+  assert.strictEqual(ws.protocol, 'chat');
+  assert.ok(ws.extensions.startsWith('permessage-deflate'));
+
+  ws.send('hello');
+  let event = await once(ws, 'message');
+  assert.strictEqual(event.data, 'echo hello /path?q=1');
+
+  ws.binaryType = 'arraybuffer';
+  ws.send(new Uint8Array([1, 2, 3]));
+  event = await once(ws, 'message');
+  assert.deepStrictEqual([...new Uint8Array(event.data)], [1, 2, 3]);
+
+  // large compressible messages
+  const big = 'x'.repeat(100000);
+  ws.send(big);
+  event = await once(ws, 'message');
+  assert.strictEqual(event.data, `echo ${big} /path?q=1`);
+
+  // ping is answered by the peer
+  const pong = new Promise((resolve) => ws.once('pong', resolve));
+  ws.ping('are you there');
+  assert.strictEqual((await pong).toString(), 'are you there');
+
+  // a close from the client
+  ws.close(4000, 'done');
+  assert.strictEqual(ws.readyState, WebSocket.CLOSING);
+  event = await once(ws, 'close');
+  assert.strictEqual(event.code, 4000);
+  assert.strictEqual(event.reason, 'done');
+  assert.ok(event.wasClean);
+  assert.strictEqual(ws.readyState, WebSocket.CLOSED);
+
+  // a close from the server
+  const ws2 = new WebSocket(`ws://127.0.0.1:${port}/`);
+  ws2.onopen = () => ws2.send('close me');
+  event = await once(ws2, 'close');
+  assert.strictEqual(event.code, 4001);
+  assert.strictEqual(event.reason, 'as asked');
+
+  // fragmented messages with a ping in between
+  let { conn, head, rest } = await rawUpgrade('Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n');
+  assert.ok(head.startsWith('HTTP/1.1 101 Switching Protocols'));
+  assert.ok(head.includes('Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n'));
+  conn.write(arrayBuffer(Buffer.concat([
+    frame(0x1, 'frag', false),
+    frame(0x9, 'p'),
+    frame(0x0, 'ment', false),
+    frame(0x0, 'ed'),
+    frame(0x8, Buffer.from([0x03, 0xe8])),
+  ])));
+  let bytes = await readAll(conn, rest);
+  assert.deepStrictEqual([...bytes.subarray(0, 3)], [0x8a, 1, 'p'.charCodeAt(0)]);
+  const echo = 'echo fragmented /raw';
+  assert.deepStrictEqual([...bytes.subarray(3, 5)], [0x81, echo.length]);
+  assert.strictEqual(bytes.subarray(5, 5 + echo.length).toString(), echo);
+  assert.deepStrictEqual([...bytes.subarray(5 + echo.length)], [0x88, 2, 0x03, 0xe8]);
+
+  // an unmasked client frame is a protocol error
+  ({ conn, rest } = await rawUpgrade('Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n'));
+  conn.write(arrayBuffer(Buffer.from([0x81, 0x01, 0x61])));
+  bytes = await readAll(conn, rest);
+  assert.strictEqual(bytes[0], 0x88);
+  assert.strictEqual(bytes.readUInt16BE(2), 1002);
+
+  // handshakes that are refused
+  ({ head } = await rawUpgrade('Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 8\r\n'));
+  assert.ok(head.startsWith('HTTP/1.1 426 Upgrade Required'));
+  assert.ok(head.includes('Sec-WebSocket-Version: 13'));
+  ({ head } = await rawUpgrade('Sec-WebSocket-Version: 13\r\n'));
+  assert.ok(head.startsWith('HTTP/1.1 400 Bad Request'));
+
+  // plain requests still reach the request handler
+  assert.strictEqual(await (await fetch(`http://127.0.0.1:${port}/`)).text(), 'plain');
+
+  // closing before the connection is established
+  const early = new WebSocket(`http://127.0.0.1:${port}/`);
+  assert.strictEqual(early.url, `ws://127.0.0.1:${port}/`);
+  const closed = once(early, 'close');
+  early.close();
+  assert.strictEqual((await closed).code, 1006);
+  assert.strictEqual(early.readyState, WebSocket.CLOSED);
+  assert.throws(() => new WebSocket('ftp://example.com'), { name: 'SyntaxError' });
+
+  assert.deepStrictEqual(serverCloses.slice(0, 3), [[4000, 'done'], [4001, 'as asked'], [1000, '']]);
+  wss.close();
+  server.close();
+}
+
+run().then(common.mustCall());
@@ -0,0 +1,65 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import * as net from 'wasi_net';
+
+// more than the socket takes at once, so part of it waits for writability
+const SIZE = 16 << 20;
+
+function payload(byte) {
+  return new Uint8Array(SIZE).fill(byte).buffer;
+}
+
+// reads `n` bytes, or everything up to the end of the stream when `n` is
+// undefined, and checks that the chunks hold `byte`
+async function readBytes(conn, byte, n) {
+  let total = 0;
+  while (n === undefined || total < n) {
+    const data = await conn.read();
+    if (data === undefined) {
+      break;
+    }
+    const bytes = new Uint8Array(data);
+    assert.strictEqual(bytes[0], byte);
+    assert.strictEqual(bytes[bytes.length - 1], byte);
+    total += data.byteLength;
+  }
+  return total;
+}
+
+async function run() {
+  const server = new net.WasiTcpServer(0);
+  const port = Number(server.local().split(':').pop());
+  const client = await net.WasiTcpConn.connect(`127.0.0.1:${port}`);
+  const peer = await server.accept();
+
+  // below the high-water mark a write asks for more
+  assert.strictEqual(client.write('a'), true);
+  assert.strictEqual(await readBytes(peer, 'a'.charCodeAt(0), 1), 1);
+
+  // a write the socket only partly accepts stays queued, returns false
+  // and 'drain' fires once the queue has been flushed
+  client.highWaterMark = 1024;
+  const drained = new Promise((resolve) => client.on('drain', common.mustCall(resolve)));
+  const written = new Promise((resolve) => {
+    assert.strictEqual(client.write(payload(1), common.mustCall(resolve)), false);
+  });
+  assert.ok(client.bufferedAmount > 0);
+  assert.strictEqual(await readBytes(peer, 1, SIZE), SIZE);
+  await written;
+  await drained;
+  assert.strictEqual(client.bufferedAmount, 0);
+
+  // end() sends everything still queued before it half-closes the socket
+  const ended = new Promise((resolve) => client.end(payload(2), common.mustCall(resolve)));
+  // nothing can be queued behind the shutdown
+  assert.strictEqual(client.write('late', common.mustCall((e) => assert.ok(e instanceof Error))), false);
+  assert.strictEqual(await readBytes(peer, 2), SIZE);
+  assert.strictEqual(await ended, undefined);
+
+  peer.close();
+  client.close();
+  server.close();
+}
+
+run().then(common.mustCall());
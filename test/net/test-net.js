@@ -0,0 +1,110 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import net from 'net';
+
+function once(emitter, event) {
+  return new Promise((resolve, reject) => {
+    emitter.once(event, resolve);
+    if (event !== 'error') {
+      emitter.once('error', reject);
+    }
+  });
+}
+
+function echoServer() {
+  return net.createServer((socket) => {
+    socket.on('data', (data) => socket.write(data));
+    socket.on('end', () => socket.end());
+  });
+}
+
+async function roundTrip(socket, message) {
+  const reply = once(socket, 'data');
+  socket.write(message);
+  return `${await reply}`;
+}
+
+async function tcp() {
+  const server = echoServer();
+  server.listen(0);
+  await once(server, 'listening');
+  const { port } = server.address();
+  assert.ok(port > 0);
+
+  const client = net.connect(port, '127.0.0.1', common.mustCall());
+  assert.strictEqual(client.connecting, true);
+  await once(client, 'connect');
+  assert.strictEqual(client.remotePort, port);
+  assert.strictEqual(client.remoteAddress, '127.0.0.1');
+  assert.strictEqual(await roundTrip(client, 'hello'), 'hello');
+  const closed = once(client, 'close');
+  client.end();
+  await once(client, 'end');
+  await closed;
+
+  server.close();
+  await once(server, 'close');
+
+  const refused = net.connect({ port, host: '127.0.0.1' });
+  const err = await once(refused, 'error');
+  assert.strictEqual(err.code, 'ECONNREFUSED');
+  assert.strictEqual(err.syscall, 'connect');
+}
+
+async function unix() {
+  const path = `/tmp/wasmedge_quickjs-test-net-${Date.now()}.sock`;
+  const server = echoServer();
+  server.listen(path);
+  const listening = await Promise.race([once(server, 'listening'), once(server, 'error')]);
+  if (listening !== undefined) {
+    common.skip(`unix sockets: ${listening.message}`);
+    return;
+  }
+  assert.strictEqual(server.address(), path);
+
+  const client = net.createConnection({ path });
+  await once(client, 'connect');
+  assert.strictEqual(client.remoteAddress, undefined);
+  assert.strictEqual(await roundTrip(client, 'over a path'), 'over a path');
+  client.destroy();
+
+  // a path that is taken cannot be listened on
+  const second = net.createServer();
+  second.listen(path);
+  assert.strictEqual((await once(second, 'error')).code, 'EADDRINUSE');
+
+  // closing removes the socket file, so the path can be reused
+  server.close();
+  await once(server, 'close');
+  const missing = net.connect(path);
+  assert.strictEqual((await once(missing, 'error')).code, 'ENOENT');
+  const again = net.createServer().listen(path);
+  await once(again, 'listening');
+  again.close();
+}
+
+async function socketpair() {
+  let a, b;
+  try {
+    [a, b] = net.socketpair();
+  } catch (e) {
+    common.skip(`socketpair: ${e.message}`);
+    return;
+  }
+  const data = once(b, 'data');
+  a.write('from a');
+  assert.strictEqual(`${await data}`, 'from a');
+  b.end('from b');
+  assert.strictEqual(`${await once(a, 'data')}`, 'from b');
+  await once(a, 'end');
+  a.destroy();
+}
+
+async function run() {
+  await tcp();
+  await unix();
+  await socketpair();
+}
+
+run().then(common.mustCall());
@@ -0,0 +1,56 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import fs from 'fs';
+import path from 'path';
+import process from 'process';
+import tmpdir from '../common/tmpdir';
+
+// process.chdir moves the cwd that relative paths of fs and path.resolve
+// are taken from.
+
+// Until the first chdir relative paths are taken from the preopens.
+const original = process.cwd();
+assert.strictEqual(original, './');
+assert.strictEqual(path.resolve('a', 'b'), 'a/b');
+
+tmpdir.refresh();
+const dir = path.join(tmpdir.path, 'cwd');
+fs.mkdirSync(path.join(dir, 'sub'), { recursive: true });
+
+process.chdir(dir);
+assert.strictEqual(process.cwd(), dir);
+assert.strictEqual(path.resolve('file.txt'), path.join(dir, 'file.txt'));
+
+fs.writeFileSync('file.txt', 'relative');
+assert.strictEqual(fs.readFileSync(path.join(dir, 'file.txt'), 'utf8'), 'relative');
+assert.deepStrictEqual(fs.readdirSync('.').sort(), ['file.txt', 'sub']);
+assert(fs.statSync('sub').isDirectory());
+
+// `.`, `..` and trailing slashes are folded
+process.chdir('sub/');
+assert.strictEqual(process.cwd(), path.join(dir, 'sub'));
+process.chdir('./../sub/..');
+assert.strictEqual(process.cwd(), dir);
+
+assert.throws(() => process.chdir('missing'), {
+  code: 'ENOENT',
+  syscall: 'chdir',
+  path: dir,
+  dest: 'missing',
+});
+assert.throws(() => process.chdir('file.txt'), { code: 'ENOTDIR', syscall: 'chdir' });
+assert.throws(() => process.chdir(''), { code: 'ENOENT', syscall: 'chdir' });
+assert.throws(() => process.chdir(1), { code: 'ERR_INVALID_ARG_TYPE' });
+assert.strictEqual(process.cwd(), dir);
+
+async function run() {
+  assert.strictEqual(await fs.promises.readFile('file.txt', 'utf8'), 'relative');
+  // as many `..` as `dir` is deep lead back to the preopens
+  process.chdir(dir.split('/').map(() => '..').join('/'));
+  assert.strictEqual(process.cwd(), original);
+  await assert.rejects(fs.promises.readFile('file.txt'), { code: 'ENOENT' });
+  assert.throws(() => process.chdir('..'), { syscall: 'chdir' });
+}
+
+run().then(common.mustCall());
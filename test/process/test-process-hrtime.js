@@ -0,0 +1,36 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import process from 'process';
+
+// hrtime and uptime come from the monotonic clock of the host.
+
+{
+  const start = process.hrtime();
+  assert.strictEqual(start.length, 2);
+  assert(Number.isInteger(start[0]) && start[0] >= 0);
+  assert(Number.isInteger(start[1]) && start[1] >= 0 && start[1] < 1e9);
+
+  const diff = process.hrtime(start);
+  assert(diff[0] >= 0);
+  assert(diff[1] >= 0 && diff[1] < 1e9);
+
+  assert.throws(() => process.hrtime(1), { code: 'ERR_INVALID_ARG_TYPE' });
+  assert.throws(() => process.hrtime([1]), { code: 'ERR_OUT_OF_RANGE' });
+}
+
+{
+  const first = process.hrtime.bigint();
+  const second = process.hrtime.bigint();
+  assert.strictEqual(typeof first, 'bigint');
+  assert(second >= first);
+}
+
+const uptime = process.uptime();
+assert(uptime >= 0);
+
+setTimeout(common.mustCall(() => {
+  const elapsed = process.hrtime.bigint();
+  assert(process.uptime() - uptime >= 0.05);
+  assert(elapsed > 0n);
+}), 60);
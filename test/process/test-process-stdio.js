@@ -0,0 +1,38 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import { Readable, Writable } from 'stream';
+import process, { stdout as namedStdout } from 'process';
+
+// The stdio streams sit on fds 0 to 2 and are made on first use.
+
+assert(process.stdout instanceof Writable);
+assert(process.stderr instanceof Writable);
+assert(process.stdin instanceof Readable);
+assert.strictEqual(process.stdout.fd, 1);
+assert.strictEqual(process.stderr.fd, 2);
+assert.strictEqual(process.stdin.fd, 0);
+assert.strictEqual(process.stdout, process.stdout);
+assert.strictEqual(namedStdout, process.stdout);
+
+assert.strictEqual(process.stdout.write('written to stdout\n'), true);
+process.stderr.write(Buffer.from('written to stderr\n'), common.mustCall((err) => {
+  assert.ifError(err);
+}));
+
+// more than highWaterMark asks the writer to wait for 'drain'
+{
+  const line = '.'.repeat(process.stdout.writableHighWaterMark) + '\n';
+  assert.strictEqual(process.stdout.write(line), false);
+  process.stdout.once('drain', common.mustCall());
+}
+
+// a paused stdin has no read waiting, the event loop still ends
+{
+  assert.strictEqual(process.stdin.setRawMode(true), process.stdin);
+  assert.strictEqual(process.stdin.isRaw, true);
+  process.stdin.setRawMode(false);
+  process.stdin.resume();
+  process.stdin.pause();
+  assert(process.stdin.isPaused());
+}
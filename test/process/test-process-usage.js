@@ -0,0 +1,31 @@
+'use strict';
+import assert from 'assert';
+import process from 'process';
+
+// memoryUsage counts the wasm memory and what QuickJS allocated in it.
+
+{
+  const usage = process.memoryUsage();
+  for (const key of ['rss', 'heapTotal', 'heapUsed', 'external', 'arrayBuffers']) {
+    assert(Number.isInteger(usage[key]) && usage[key] >= 0, key);
+  }
+  assert(usage.rss > 0 && usage.rss % 65536 === 0);
+  assert(usage.heapUsed > 0);
+  assert(usage.heapTotal > 0 && usage.heapTotal <= usage.rss);
+
+  const buffer = new ArrayBuffer(1 << 20);
+  assert(process.memoryUsage().arrayBuffers >= buffer.byteLength);
+  assert.strictEqual(process.memoryUsage.rss() % 65536, 0);
+}
+
+{
+  const usage = process.cpuUsage();
+  assert(usage.user >= 0);
+  assert.strictEqual(usage.system, 0);
+  const diff = process.cpuUsage(usage);
+  assert(diff.user >= 0);
+
+  const resources = process.resourceUsage();
+  assert(resources.userCPUTime >= 0);
+  assert.strictEqual(resources.maxRSS, Math.floor(process.memoryUsage.rss() / 1024));
+}
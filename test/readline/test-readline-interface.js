@@ -0,0 +1,105 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import readline from 'readline';
+import { createInterface as createPromisesInterface } from 'readline/promises';
+import { PassThrough, Writable } from 'stream';
+
+// Lines of a stream through readline, the way they come from process.stdin.
+
+function collect() {
+  const output = new Writable({
+    write(chunk, encoding, cb) {
+      output.data += chunk;
+      cb();
+    },
+  });
+  output.data = '';
+  return output;
+}
+
+// 'line' for every line, the last one without a newline too, then 'close'
+{
+  const input = new PassThrough();
+  const rl = readline.createInterface({ input });
+  const lines = [];
+  rl.on('line', (line) => lines.push(line));
+  rl.on('close', common.mustCall(() => {
+    assert.deepStrictEqual(lines, ['first', 'second', 'crlf', 'héllo', 'last']);
+    assert(rl.closed);
+  }));
+  input.write('first\nsec');
+  input.write('ond\ncrlf\r\n');
+  // a character split between two chunks
+  const bytes = Buffer.from('héllo\n');
+  input.write(bytes.subarray(0, 2));
+  input.write(bytes.subarray(2));
+  input.end('last');
+}
+
+// question() writes the query and gets the next line, not 'line'
+{
+  const input = new PassThrough();
+  const output = collect();
+  const rl = readline.createInterface({ input, output, prompt: '$ ' });
+  rl.on('line', common.mustCall((line) => {
+    assert.strictEqual(line, 'not an answer');
+    rl.close();
+  }));
+  rl.question('name? ', common.mustCall((answer) => {
+    assert.strictEqual(answer, 'wasm');
+    assert.strictEqual(output.data, 'name? ');
+    rl.prompt();
+    assert.strictEqual(output.data, 'name? $ ');
+    input.write('not an answer\n');
+  }));
+  input.write('wasm\n');
+  assert.strictEqual(rl.getPrompt(), '$ ');
+  assert.throws(() => rl.question('x', 1), { code: 'ERR_INVALID_ARG_TYPE' });
+}
+
+// pause and resume go through to the input
+{
+  const input = new PassThrough();
+  const rl = readline.createInterface(input);
+  // close() pauses as well
+  rl.on('pause', common.mustCall(2));
+  rl.on('resume', common.mustCall());
+  rl.pause();
+  assert(input.isPaused());
+  rl.resume();
+  assert(!input.isPaused());
+  rl.close();
+  rl.close();
+}
+
+async function run() {
+  // the lines with for await
+  {
+    const input = new PassThrough();
+    const rl = readline.createInterface({ input });
+    input.end('a\nb\nc\n');
+    const lines = [];
+    for await (const line of rl) {
+      lines.push(line);
+    }
+    assert.deepStrictEqual(lines, ['a', 'b', 'c']);
+  }
+
+  // readline/promises answers with a promise, closing rejects it
+  {
+    const input = new PassThrough();
+    const output = collect();
+    const rl = createPromisesInterface({ input, output });
+    const answer = rl.question('? ');
+    input.write('yes\n');
+    assert.strictEqual(await answer, 'yes');
+
+    const unanswered = rl.question('? ');
+    rl.close();
+    await assert.rejects(unanswered, { name: 'AbortError' });
+    assert.strictEqual(output.data, '? ? ');
+  }
+}
+
+run().then(common.mustCall());
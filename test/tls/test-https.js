@@ -0,0 +1,62 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import * as fixtures from '../common/fixtures';
+import https from 'https';
+import tls from 'tls';
+import { TextDecoder } from 'util';
+
+const server = https.createServer({
+  key: fixtures.readKey('agent1-key.pem'),
+  cert: fixtures.readKey('agent1-cert.pem'),
+  ALPNProtocols: ['http/1.1'],
+}, common.mustCall((req, res) => {
+  assert.strictEqual(req.socket.servername, 'agent1');
+  assert.strictEqual(req.socket.alpnProtocol, 'http/1.1');
+  let body = '';
+  req.setEncoding('utf8');
+  req.on('data', (chunk) => body += chunk);
+  req.on('end', () => res.end(`${req.method} ${req.url} ${body}`));
+}, 2));
+
+async function rawRequest(port) {
+  // `end` sends the request with close_notify and half-closes the socket,
+  // the response still arrives before the server closes its side
+  const conn = await tls.rawConnect({
+    host: '127.0.0.1',
+    port,
+    servername: 'agent1',
+    ca: fixtures.readKey('ca1-cert.pem'),
+  });
+  conn.end('GET /end HTTP/1.1\r\nHost: agent1\r\n\r\n');
+  const decoder = new TextDecoder();
+  let received = '';
+  for (let data = await conn.read(); data !== undefined; data = await conn.read()) {
+    received += decoder.decode(data);
+  }
+  assert.ok(received.startsWith('HTTP/1.1 200'));
+  assert.ok(received.endsWith('GET /end '));
+}
+
+server.listen(0, common.mustCall(() => {
+  const port = server.address().port;
+  const req = https.request({
+    host: '127.0.0.1',
+    port,
+    path: '/echo',
+    method: 'POST',
+    servername: 'agent1',
+    ca: fixtures.readKey('ca1-cert.pem'),
+    agent: false,
+  }, common.mustCall((res) => {
+    assert.strictEqual(res.statusCode, 200);
+    let body = '';
+    res.setEncoding('utf8');
+    res.on('data', (chunk) => body += chunk);
+    res.on('end', common.mustCall(() => {
+      assert.strictEqual(body, 'POST /echo hello');
+      rawRequest(port).then(common.mustCall(() => server.close()));
+    }));
+  }));
+  req.end('hello');
+}));
@@ -0,0 +1,67 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import * as fixtures from '../common/fixtures';
+import process from 'process';
+import tls from 'tls';
+
+assert.strictEqual(process.features.tls, true);
+
+const options = {
+  key: fixtures.readKey('agent1-key.pem'),
+  cert: fixtures.readKey('agent1-cert.pem'),
+  ALPNProtocols: ['echo/1', 'http/1.1'],
+};
+
+const message = 'hello over tls';
+
+let pending = 2;
+function done() {
+  if (--pending === 0) {
+    server.close();
+  }
+}
+
+const server = tls.createServer(options, common.mustCall((socket) => {
+  assert.strictEqual(socket.servername, 'agent1');
+  assert.strictEqual(socket.alpnProtocol, 'echo/1');
+  socket.pipe(socket);
+}));
+
+server.on('tlsClientError', common.mustCall());
+
+server.listen(0, common.mustCall(() => {
+  const port = server.address().port;
+
+  const client = tls.connect({
+    port,
+    host: '127.0.0.1',
+    servername: 'agent1',
+    ca: fixtures.readKey('ca1-cert.pem'),
+    ALPNProtocols: ['echo/1'],
+  }, common.mustCall(() => {
+    assert.strictEqual(client.authorized, true);
+    assert.strictEqual(client.alpnProtocol, 'echo/1');
+    client.end(message);
+  }));
+
+  let received = '';
+  client.setEncoding('utf8');
+  client.on('data', (chunk) => {
+    received += chunk;
+  });
+  client.on('end', common.mustCall(() => {
+    assert.strictEqual(received, message);
+    done();
+  }));
+
+  // the server certificate does not chain up to this ca
+  const untrusted = tls.connect({
+    port,
+    host: '127.0.0.1',
+    servername: 'agent1',
+    ca: fixtures.readKey('agent1-cert.pem'),
+  });
+  untrusted.on('secureConnect', common.mustNotCall());
+  untrusted.on('error', common.mustCall(done));
+}));
@@ -0,0 +1,126 @@
+'use strict';
+import common from '../common';
+import assert from 'assert';
+import * as zlib from 'zlib';
+import * as http from 'http';
+import { Buffer } from 'buffer';
+
+const text = 'The quick brown fox jumps over the lazy dog. '.repeat(200);
+const input = Buffer.from(text);
+
+// sync round trips
+const pairs = [
+  ['deflateSync', 'inflateSync'],
+  ['deflateRawSync', 'inflateRawSync'],
+  ['gzipSync', 'gunzipSync'],
+  ['gzipSync', 'unzipSync'],
+  ['deflateSync', 'unzipSync'],
+  ['brotliCompressSync', 'brotliDecompressSync'],
+];
+for (const [enc, dec] of pairs) {
+  const compressed = zlib[enc](input);
+  assert.ok(compressed.length < input.length, enc);
+  assert.strictEqual(zlib[dec](compressed).toString(), text, dec);
+}
+assert.strictEqual(zlib.gunzipSync(zlib.gzipSync('string input')).toString(), 'string input');
+assert.strictEqual(zlib.gzipSync(input)[0], 0x1f);
+
+// options reach the codec
+const fast = zlib.deflateSync(input, { level: zlib.constants.Z_NO_COMPRESSION });
+assert.ok(fast.length > input.length);
+const brotli = zlib.brotliCompressSync(input, {
+  params: { [zlib.constants.BROTLI_PARAM_QUALITY]: 4 },
+});
+assert.strictEqual(zlib.brotliDecompressSync(brotli).toString(), text);
+
+// broken input
+const gzipped = zlib.gzipSync(input);
+assert.throws(() => zlib.gunzipSync(gzipped.subarray(0, gzipped.length - 10)), { code: 'Z_BUF_ERROR', errno: -5 });
+assert.throws(() => zlib.inflateSync(Buffer.from('not deflated')), { code: 'Z_DATA_ERROR', errno: -3 });
+assert.throws(() => zlib.gzipSync(42), { code: 'ERR_INVALID_ARG_TYPE' });
+
+// callback forms
+zlib.deflate(input, common.mustCall((err, compressed) => {
+  assert.ifError(err);
+  zlib.inflate(compressed, {}, common.mustCall((err, out) => {
+    assert.ifError(err);
+    assert.strictEqual(out.toString(), text);
+  }));
+}));
+zlib.gunzip(Buffer.from('garbage'), common.mustCall((err) => {
+  assert.strictEqual(err.code, 'Z_DATA_ERROR');
+}));
+
+// stream forms, written in pieces
+function collect(stream) {
+  return new Promise((resolve, reject) => {
+    const chunks = [];
+    stream.on('data', (chunk) => chunks.push(chunk));
+    stream.on('end', () => resolve(Buffer.concat(chunks)));
+    stream.on('error', reject);
+  });
+}
+
+async function streams() {
+  const gzip = zlib.createGzip();
+  const gunzip = zlib.createGunzip();
+  gzip.pipe(gunzip);
+  const out = collect(gunzip);
+  for (let i = 0; i < input.length; i += 1000) {
+    gzip.write(input.subarray(i, i + 1000));
+  }
+  gzip.end();
+  assert.strictEqual((await out).toString(), text);
+  assert.strictEqual(gzip.bytesWritten, input.length);
+
+  // flush makes what was written so far decodable
+  const deflate = zlib.createDeflate();
+  const inflate = zlib.createInflate();
+  deflate.pipe(inflate);
+  const received = [];
+  inflate.on('data', (chunk) => received.push(chunk));
+  deflate.write('first part');
+  await new Promise((resolve) => deflate.flush(resolve));
+  await new Promise((resolve) => setTimeout(resolve, 10));
+  assert.strictEqual(Buffer.concat(received).toString(), 'first part');
+  deflate.end();
+
+  const broken = zlib.createBrotliDecompress();
+  const failed = collect(broken);
+  broken.end(Buffer.from('definitely not brotli'));
+  await assert.rejects(failed);
+}
+
+// a server compressing with zlib, and fetch decoding it transparently
+async function http_compression() {
+  let accepted;
+  const server = http.createServer((req, res) => {
+    accepted = req.headers['accept-encoding'];
+    const coding = req.url.slice(1);
+    res.setHeader('Content-Encoding', coding);
+    const encoder = coding === 'gzip' ? zlib.createGzip()
+      : coding === 'deflate' ? zlib.createDeflate()
+        : zlib.createBrotliCompress();
+    encoder.pipe(res);
+    encoder.end(text);
+  });
+  server.listen(0);
+  const port = server.address().port;
+
+  for (const coding of ['gzip', 'deflate', 'br']) {
+    const resp = await fetch(`http://127.0.0.1:${port}/${coding}`);
+    assert.strictEqual(resp.headers.get('content-encoding'), coding);
+    assert.strictEqual(await resp.text(), text);
+    assert.strictEqual(accepted, 'gzip, deflate, br');
+  }
+
+  // opting out keeps the encoded bytes
+  const resp = await fetch(`http://127.0.0.1:${port}/gzip`, { decompress: false });
+  assert.strictEqual(accepted, undefined);
+  const raw = Buffer.from(await resp.arrayBuffer());
+  assert.strictEqual(zlib.gunzipSync(raw).toString(), text);
+
+  server.close();
+}
+
+streams().then(http_compression).then(common.mustCall());
@@ -4,18 +4,23 @@ use std::borrow::{Borrow, BorrowMut};
 use wasmedge_quickjs::*;
 
 fn test_js_file(file_path: &str) {
+    test_js_file_on(file_path, None);
+}
+
+fn test_js_file_on(file_path: &str, vfs: Option<VirtualFs>) {
     use wasmedge_quickjs as q;
     let mut rt = q::Runtime::new();
     rt.run_with_context(|ctx| {
-        let code = std::fs::read_to_string(&file_path);
+        ctx.set_virtual_fs(vfs.clone());
+        let code = std::fs::read_to_string(file_path);
         match code {
             Ok(code) => {
-                ctx.put_args(vec![file_path.clone()]);
-                ctx.eval_module_str(code, &file_path);
+                ctx.put_args(vec![file_path]);
+                ctx.eval_module_str(code, file_path);
             }
             Err(e) => {
-                eprintln!("{}", e.to_string());
-                assert!(false, "run js test file fail");
+                eprintln!("{}", e);
+                panic!("run js test file fail");
             }
         }
         ctx.js_loop().unwrap();
@@ -28,7 +33,7 @@ fn test_js_file(file_path: &str) {
         }
         ctx.js_loop().unwrap();
         if let JsValue::Bool(false) = ctx.get_global().get("assertPass") {
-            assert!(false, "js assert fail");
+            panic!("js assert fail");
         }
     });
     std::fs::remove_dir_all("./test/.tmp.0");
@@ -64,17 +69,17 @@ fn test_fs_buffertype_writesync() {
     test_js_file("test/fs/test-fs-buffertype-writesync.js");
 }
 
-#[ignore = "unsupported, chmod"]
+#[test]
 fn test_fs_chmod() {
     test_js_file("test/fs/test-fs-chmod.js");
 }
 
-#[ignore = "unsupported, chmod"]
+#[test]
 fn test_fs_chmod_mask() {
     test_js_file("test/fs/test-fs-chmod-mask.js");
 }
 
-#[ignore = "unsupported, chown"]
+#[test]
 fn test_fs_chown_type_check() {
     test_js_file("test/fs/test-fs-chown-type-check.js");
 }
@@ -129,12 +134,12 @@ fn test_fs_existssync_false() {
     test_js_file("test/fs/test-fs-existssync-false.js");
 }
 
-#[ignore = "unsupported, chmod"]
+#[test]
 fn test_fs_fchmod() {
     test_js_file("test/fs/test-fs-fchmod.js");
 }
 
-#[ignore = "unsupported, chown"]
+#[test]
 fn test_fs_fchown() {
     test_js_file("test/fs/test-fs-fchown.js");
 }
@@ -194,7 +199,7 @@ fn test_fs_mkdir() {
     test_js_file("test/fs/test-fs-mkdir.js");
 }
 
-#[ignore = "unsupported, chmod"]
+#[test]
 fn test_fs_mkdir_mode_mask() {
     test_js_file("test/fs/test-fs-mkdir-mode-mask.js");
 }
@@ -279,7 +284,7 @@ fn test_fs_promises_file_handle_append_file() {
     test_js_file("test/fs/test-fs-promises-file-handle-append-file.js");
 }
 
-#[ignore = "unsupported, chomd"]
+#[test]
 fn test_fs_promises_file_handle_chmod() {
     test_js_file("test/fs/test-fs-promises-file-handle-chmod.js");
 }
@@ -320,7 +325,6 @@ fn test_fs_promises_file_handle_stat() {
 }
 
 #[test]
-#[ignore = "working"]
 fn test_fs_promises_file_handle_stream() {
     test_js_file("test/fs/test-fs-promises-file-handle-stream.js");
 }
@@ -604,7 +608,6 @@ fn test_fs_read_stream_resume() {
 }
 
 #[test]
-#[ignore = "working"]
 fn test_fs_read_stream_throw_type_error() {
     test_js_file("test/fs/test-fs-read-stream-throw-type-error.js");
 }
@@ -679,6 +682,60 @@ fn test_fs_realpath_pipe() {
     test_js_file("test/fs/test-fs-realpath-pipe.js");
 }
 
+#[test]
+fn test_fs_recursive_native() {
+    test_js_file("test/fs/test-fs-recursive-native.js");
+}
+
+#[test]
+fn test_fs_async_pool() {
+    test_js_file("test/fs/test-fs-async-pool.js");
+}
+
+#[test]
+fn test_fs_stream_native() {
+    test_js_file("test/fs/test-fs-stream-native.js");
+}
+
+#[test]
+fn test_fs_promises_file_handle_lines() {
+    test_js_file("test/fs/test-fs-promises-file-handle-lines.js");
+}
+
+#[test]
+fn test_fs_memfs() {
+    let seed = VirtualFs::new();
+    seed.write_file("/data/hello.txt", b"hello from the archive")
+        .unwrap();
+    seed.write_file("/data/sub/nested.txt", b"nested").unwrap();
+    seed.symlink("hello.txt", "/data/link").unwrap();
+
+    let vfs = VirtualFs::from_tar(&seed.to_tar()).unwrap();
+    test_js_file_on("test/fs/test-fs-memfs.js", Some(vfs.clone()));
+
+    let snapshot = VirtualFs::from_zip(&vfs.to_zip()).unwrap();
+    assert_eq!(
+        snapshot.read_file("/data/async.txt").unwrap(),
+        b"WRITTEN async"
+    );
+    assert_eq!(
+        snapshot.read_file("/data/streamed.txt").unwrap(),
+        b"hello from the archive"
+    );
+    assert!(snapshot.read_file("/data/out/a/file.txt").is_err());
+    assert!(!std::path::Path::new("/data").exists());
+}
+
+#[test]
+fn test_fs_lock_atomic() {
+    test_js_file("test/fs/test-fs-lock-atomic.js");
+}
+
+#[test]
+fn test_fs_permissions() {
+    test_js_file("test/fs/test-fs-permissions.js");
+}
+
 #[test]
 fn test_fs_rename_type_check() {
     test_js_file("test/fs/test-fs-rename-type-check.js");
@@ -897,7 +954,7 @@ fn test_fs_watch_abort_signal() {
     test_js_file("test/fs/test-fs-watch-abort-signal.js");
 }
 
-#[ignore = "unsupported, watch"]
+#[test]
 fn test_fs_watch_close_when_destroyed() {
     test_js_file("test/fs/test-fs-watch-close-when-destroyed.js");
 }
@@ -917,7 +974,7 @@ fn test_fs_watchfile_bigint() {
     test_js_file("test/fs/test-fs-watchfile-bigint.js");
 }
 
-#[ignore = "unsupported, watch"]
+#[test]
 fn test_fs_watch_file_enoent_after_deletion() {
     test_js_file("test/fs/test-fs-watch-file-enoent-after-deletion.js");
 }
@@ -932,7 +989,7 @@ fn test_fs_watchfile_ref_unref() {
     test_js_file("test/fs/test-fs-watchfile-ref-unref.js");
 }
 
-#[ignore = "unsupported, watch"]
+#[test]
 fn test_fs_watch() {
     test_js_file("test/fs/test-fs-watch.js");
 }
@@ -947,12 +1004,17 @@ fn test_fs_watch_ref_unref() {
     test_js_file("test/fs/test-fs-watch-ref-unref.js");
 }
 
-#[ignore = "unsupported, watch"]
+#[test]
+fn test_fs_watch_polling() {
+    test_js_file("test/fs/test-fs-watch-polling.js");
+}
+
+#[test]
 fn test_fs_watch_stop_async() {
     test_js_file("test/fs/test-fs-watch-stop-async.js");
 }
 
-#[ignore = "unsupported, watch"]
+#[test]
 fn test_fs_watch_stop_sync() {
     test_js_file("test/fs/test-fs-watch-stop-sync.js");
 }
@@ -7,18 +7,18 @@ fn test_js_file(file_path: &str) {
     use wasmedge_quickjs as q;
     let mut rt = q::Runtime::new();
     rt.run_with_context(|ctx| {
-        let code = std::fs::read_to_string(&file_path);
+        let code = std::fs::read_to_string(file_path);
         match code {
             Ok(code) => {
-                ctx.put_args(vec![file_path.clone()]);
-                ctx.eval_module_str(code, &file_path);
+                ctx.put_args(vec![file_path]);
+                ctx.eval_module_str(code, file_path);
                 if let JsValue::Bool(false) = ctx.get_global().get("assertPass") {
-                    assert!(false, "js assert fail");
+                    panic!("js assert fail");
                 }
             }
             Err(e) => {
-                eprintln!("{}", e.to_string());
-                assert!(false, "run js test file fail");
+                eprintln!("{}", e);
+                panic!("run js test file fail");
             }
         }
         ctx.js_loop().unwrap();
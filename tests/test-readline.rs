@@ -0,0 +1,38 @@
+#![allow(dead_code, unused_imports, unused_must_use)]
+
+use wasmedge_quickjs::*;
+
+fn test_js_file(file_path: &str) {
+    use wasmedge_quickjs as q;
+    let mut rt = q::Runtime::new();
+    rt.run_with_context(|ctx| {
+        let code = std::fs::read_to_string(file_path);
+        match code {
+            Ok(code) => {
+                ctx.put_args(vec![file_path]);
+                ctx.eval_module_str(code, file_path);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                panic!("run js test file fail");
+            }
+        }
+        ctx.js_loop().unwrap();
+        if let JsValue::Function(func) = ctx.get_global().get("_onExit") {
+            func.call(&[]);
+        }
+        ctx.js_loop().unwrap();
+        if let JsValue::Function(func) = ctx.get_global().get("commonExitCheck") {
+            func.call(&[]);
+        }
+        ctx.js_loop().unwrap();
+        if let JsValue::Bool(false) = ctx.get_global().get("assertPass") {
+            panic!("js assert fail");
+        }
+    });
+}
+
+#[test]
+fn test_readline_interface() {
+    test_js_file("test/readline/test-readline-interface.js");
+}